@@ -0,0 +1,13 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The gRPC code is only generated for builds with the `grpc` feature
+    #[cfg(feature = "grpc")]
+    {
+        // Bundled protoc, so building doesn't need one installed
+        // SAFETY: build scripts are single threaded
+        unsafe { std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?) };
+        tonic_prost_build::compile_protos("proto/homies.proto")?;
+    }
+    #[cfg(not(feature = "grpc"))]
+    println!("cargo:rerun-if-changed=build.rs");
+    Ok(())
+}
@@ -0,0 +1,1326 @@
+use crate::audit::AuditLog;
+use crate::capabilities::Capabilities;
+use crate::clock::{SharedClock, SystemClock};
+use crate::data_dirs::DataDirs;
+use crate::state_actor::StateHandle;
+use crate::upload_budget::UploadBudget;
+#[cfg(feature = "graphql")]
+use crate::graphql;
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::i18n::Msg;
+use crate::{
+    config, diagnostics, handlers, i18n, jobs, session, state, stats,
+    tasks, video_processing, websocket,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use warp::{Filter, Rejection, Reply};
+
+/// A configured homies backend: shared state plus the warp filter serving it
+pub struct App {
+    config: Arc<config::AppConfig>,
+    capabilities: Arc<Capabilities>,
+    state: StateHandle,
+    ws_clients: websocket::WsClients,
+    jobs: jobs::SharedJobs,
+    audit: AuditLog,
+    upload_budget: UploadBudget,
+    data_dirs: DataDirs,
+}
+
+/// Builds an [`App`], see [`App::builder`]
+pub struct AppBuilder {
+    config: config::AppConfig,
+    background_tasks: bool,
+    clock: SharedClock,
+    data_dirs: DataDirs,
+}
+
+impl AppBuilder {
+    pub fn config(mut self, config: config::AppConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Whether to run the queue, cleanup and highlights tasks, on by default
+    /// Tests usually turn them off so the state only changes through requests
+    pub fn background_tasks(mut self, enabled: bool) -> Self {
+        self.background_tasks = enabled;
+        self
+    }
+
+    /// Time source for retention, the display queue and quiet hours, the system clock by default
+    pub fn clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Where uploads, the trash and the archive are kept, in the working directory by default
+    /// Tests give each app its own so they can run in parallel
+    pub fn data_dirs(mut self, data_dirs: DataDirs) -> Self {
+        self.data_dirs = data_dirs;
+        self
+    }
+
+    /// Set up the shared state, restoring sounds, music and pinned media from disk
+    pub async fn build(self) -> App {
+        let app_config = Arc::new(self.config);
+        stats::init_person_salt(app_config.person_salt.as_deref());
+        #[cfg(not(feature = "graphql"))]
+        if app_config.graphql {
+            tracing::warn!("graphql is on in the config, but this build lacks the graphql feature");
+        }
+
+        // Create shared state
+        let mut media_state = state::MediaViewState::with_clock(self.clock);
+        media_state.set_display_policy(app_config.display_policy);
+        media_state.set_quiet_hours(app_config.quiet_hours);
+        media_state.set_theme(app_config.theme);
+        media_state.set_data_dirs(self.data_dirs.clone());
+        media_state
+            .cleanup_mut()
+            .set_retry_policy(app_config.cleanup.retry_policy());
+        let audit = AuditLog::open(app_config.audit_log.clone());
+        media_state.set_audit_log(audit.clone());
+
+        // Probe hardware acceleration once instead of on every encode
+        let hwaccel_setting = app_config.hwaccel;
+        let hw_accel = tokio::task::spawn_blocking(move || {
+            video_processing::HwAccel::resolve(hwaccel_setting)
+        })
+        .await
+        .unwrap_or_default();
+        media_state.set_hw_accel(hw_accel);
+
+        // Same for ffmpeg and yt-dlp, the upload form hides what they'd do when missing
+        let capabilities = Capabilities::new(&app_config, hw_accel);
+        let capabilities = tokio::task::spawn_blocking({
+            let capabilities = capabilities.clone();
+            move || capabilities.probe()
+        })
+        .await
+        .unwrap_or(capabilities);
+        // Self-check logged before serving, admins can rerun it on GET /diagnostics
+        let report = capabilities.clone();
+        if let Ok(report) =
+            tokio::task::spawn_blocking({
+                let data_dirs = self.data_dirs.clone();
+                move || diagnostics::Report::run(&report, &data_dirs)
+            })
+            .await
+        {
+            report.log();
+        }
+
+        // Event sinks are outgoing only, running even without the other background tasks
+        if !app_config.webhooks.is_empty() {
+            let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+            media_state.add_event_sink(sender);
+            tasks::start_webhook_task(receiver, app_config.webhooks.clone());
+            tracing::info!("Webhook task started for {} endpoints", app_config.webhooks.len());
+        }
+        if let Some(notifier) = app_config.notifier.clone() {
+            let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+            media_state.add_event_sink(sender);
+            tasks::start_notifier_task(receiver, notifier);
+            tracing::info!("Notifier task started");
+        }
+        let mqtt_events = app_config.mqtt.as_ref().map(|_| {
+            let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+            media_state.add_event_sink(sender);
+            receiver
+        });
+        // Animated previews are made from the upload events, one at a time
+        let preview_events = (self.background_tasks && capabilities.ffmpeg).then(|| {
+            let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+            media_state.add_event_sink(sender);
+            receiver
+        });
+        let media_state = StateHandle::spawn(media_state);
+        tracing::info!("Media state initialized");
+
+        // Register sounds already on disk
+        handlers::soundboard::load_sound_library(media_state.clone()).await;
+        handlers::music::load_music_library(media_state.clone()).await;
+
+        // Restore media pinned to the archive
+        handlers::archive::load_archive(media_state.clone(), &app_config.archive_index).await;
+        handlers::banner::load_banner(media_state.clone(), &app_config.banner_file).await;
+        handlers::leaderboard::load_leaderboard(media_state.clone(), &app_config.leaderboard_file)
+            .await;
+        handlers::displays::load_displays(media_state.clone(), &app_config.displays_file).await;
+        handlers::bans::load_bans(media_state.clone(), &app_config.bans_file).await;
+        handlers::word_filter::load_word_filter(media_state.clone(), &app_config).await;
+        handlers::trash::load_trash(media_state.clone()).await;
+
+        // Create WebSocket state
+        let ws_clients = websocket::create_ws_state(app_config.ws_broadcast_capacity);
+        tracing::info!("WebSocket state initialized");
+
+        if let (Some(mqtt), Some(events)) = (app_config.mqtt.clone(), mqtt_events) {
+            tasks::start_mqtt_task(media_state.clone(), ws_clients.clone(), events, mqtt);
+            tracing::info!("MQTT task started");
+        }
+
+        // Create jobs registry for long-running URL downloads
+        let jobs = jobs::create_jobs_state();
+        let upload_budget = UploadBudget::new(app_config.upload_limits.in_flight_bytes());
+
+        let app = App {
+            config: app_config,
+            capabilities: Arc::new(capabilities),
+            state: media_state,
+            ws_clients,
+            jobs,
+            audit,
+            upload_budget,
+            data_dirs: self.data_dirs,
+        };
+        if self.background_tasks {
+            app.start_background_tasks();
+        }
+        if let Some(events) = preview_events {
+            tasks::start_preview_task(
+                app.state.clone(),
+                app.ws_clients.clone(),
+                app.jobs.clone(),
+                events,
+            );
+            tracing::info!("Preview task started");
+        }
+        app
+    }
+}
+
+impl App {
+    /// Default configuration with the background tasks running
+    pub fn builder() -> AppBuilder {
+        AppBuilder {
+            config: config::AppConfig::default(),
+            background_tasks: true,
+            clock: Arc::new(SystemClock),
+            data_dirs: DataDirs::default(),
+        }
+    }
+
+    pub fn config(&self) -> &Arc<config::AppConfig> {
+        &self.config
+    }
+
+    /// Tools and limits found at startup
+    pub fn capabilities(&self) -> &Arc<Capabilities> {
+        &self.capabilities
+    }
+
+    pub fn state(&self) -> &StateHandle {
+        &self.state
+    }
+
+    pub fn ws_clients(&self) -> &websocket::WsClients {
+        &self.ws_clients
+    }
+
+    pub fn data_dirs(&self) -> &DataDirs {
+        &self.data_dirs
+    }
+
+    /// gRPC service over the same state, served by `main` when `[grpc]` is configured
+    #[cfg(feature = "grpc")]
+    pub fn grpc_service(&self) -> grpc::HomiesService {
+        grpc::HomiesService::new(self.state.clone(), self.ws_clients.clone(), self.config.clone())
+    }
+
+    fn start_background_tasks(&self) {
+        tasks::start_cleanup_task(self.state.clone(), self.config.cleanup.clone());
+        tracing::info!("Background cleanup task started");
+
+        tasks::start_trash_purge_task(self.state.clone(), self.config.trash.clone());
+        tracing::info!("Trash purge task started");
+
+        tasks::start_staging_sweep_task(self.state.clone(), self.config.staging.clone());
+        tracing::info!("Staging sweep task started");
+
+        tasks::start_queue_task(self.state.clone(), self.ws_clients.clone());
+        tracing::info!("Display queue task started");
+
+        tasks::start_timer_task(self.state.clone(), self.ws_clients.clone());
+        tracing::info!("Timer task started");
+
+        tasks::start_poll_task(self.state.clone(), self.ws_clients.clone());
+        tracing::info!("Poll task started");
+
+        tasks::start_draw_task(self.state.clone(), self.ws_clients.clone());
+        tracing::info!("Drawing relay task started");
+
+        if self.config.highlights.enabled {
+            tasks::start_highlights_task(
+                self.state.clone(),
+                self.ws_clients.clone(),
+                self.config.clone(),
+            );
+            tracing::info!("Highlights task started");
+        }
+
+        if self.config.recap.enabled {
+            tasks::start_recap_task(self.ws_clients.clone(), self.config.recap.clone());
+            tracing::info!("Stats recap task started");
+        }
+
+        if !self.config.game_status.servers.is_empty() {
+            tasks::start_game_status_task(
+                self.state.clone(),
+                self.ws_clients.clone(),
+                self.config.game_status.clone(),
+            );
+            tracing::info!("Game status task started");
+        }
+
+        if let Some(twitch) = self.config.twitch.clone() {
+            tasks::start_twitch_task(self.state.clone(), self.ws_clients.clone(), twitch);
+            tracing::info!("Twitch task started");
+        }
+
+        if let Some(ingest) = self.config.ingest.clone() {
+            if self.capabilities.ffmpeg {
+                tasks::start_ingest_task(self.ws_clients.clone(), ingest, self.data_dirs.clone());
+                tracing::info!("Screen-share ingest task started");
+            } else {
+                tracing::warn!("Screen-share ingest configured but ffmpeg is missing");
+            }
+        }
+
+        if let Some(source) = self.config.now_playing.clone() {
+            tasks::start_now_playing_task(self.state.clone(), self.ws_clients.clone(), source);
+            tracing::info!("Now playing task started");
+        }
+    }
+
+    /// Every route of the backend, ready for `warp::serve` or `warp::test`
+    pub fn routes(
+        &self,
+    ) -> impl Filter<Extract = (impl Reply + use<>,), Error = Rejection> + Clone + use<> {
+        let media_state = self.state.clone();
+        let ws_clients = self.ws_clients.clone();
+        let jobs = self.jobs.clone();
+        let audit = self.audit.clone();
+        let upload_budget = self.upload_budget.clone();
+        let app_config = self.config.clone();
+        let capabilities = self.capabilities.clone();
+        let default_locale = app_config.locale;
+        let media_bytes = app_config.upload_limits.media_bytes();
+        let sound_bytes = app_config.upload_limits.sound_bytes();
+
+        // Clone for different routes
+        let media_state_upload = media_state.clone();
+        let media_state_media = media_state.clone();
+        let ws_clients_upload = ws_clients.clone();
+        let ws_clients_route = ws_clients.clone();
+
+        // Index route
+        let index_route = warp::get()
+            .and(warp::path::end())
+            .and(session::existing_session())
+            .and(with_state(media_state.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::media::index_page);
+
+        // Upload routes
+        let upload_form_route = warp::get()
+            .and(warp::path("upload"))
+            .and(session::existing_session())
+            .and(session::csrf_cookie())
+            .and(with_config(app_config.clone()))
+            .and(with_capabilities(capabilities.clone()))
+            .and_then(handlers::upload::upload_form);
+
+        let capabilities_route = warp::get()
+            .and(warp::path("capabilities"))
+            .and(warp::path::end())
+            .and(with_capabilities(capabilities.clone()))
+            .map(|capabilities: Arc<Capabilities>| warp::reply::json(&*capabilities));
+
+        let upload_route = warp::post()
+            .and(warp::path("upload"))
+            .and(session::csrf_protected())
+            .and(handlers::bans::not_banned(media_state_upload.clone()))
+            .and(handlers::upload::size_limit(media_bytes, Msg::FileTooLarge, default_locale))
+            .and(handlers::upload::reserve(upload_budget.clone(), media_bytes, default_locale))
+            .and(
+                warp::multipart::form()
+                    .max_length(media_bytes)
+                    .and(session::client_id())
+                    .and(with_state(media_state_upload.clone()))
+                    .and(with_ws_state(ws_clients_upload.clone())) // Add WebSocket state
+                    .and(with_config(app_config.clone()))
+                    .and(i18n::locale(app_config.locale))
+                    .and(handlers::upload::reply_format())
+                    .and_then(handlers::upload::upload_image),
+            )
+            .map(handlers::upload::release);
+
+        let upload_video_route = warp::post()
+            .and(warp::path("upload-video"))
+            .and(session::csrf_protected())
+            .and(handlers::bans::not_banned(media_state_upload.clone()))
+            .and(warp::body::form())
+            .and(session::client_id())
+            .and(with_state(media_state_upload.clone()))
+            .and(with_ws_state(ws_clients_upload.clone()))
+            .and(with_config(app_config.clone()))
+            .and(with_jobs(jobs.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::upload::upload_video_url);
+
+        let upload_url_route = warp::post()
+            .and(warp::path("upload-url"))
+            .and(session::csrf_protected())
+            .and(handlers::bans::not_banned(media_state_upload.clone()))
+            .and(warp::body::form())
+            .and(session::client_id())
+            .and(with_state(media_state_upload.clone()))
+            .and(with_ws_state(ws_clients_upload.clone()))
+            .and(with_config(app_config.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::upload::upload_url);
+
+        let preview_caption_route = warp::post()
+            .and(warp::path("preview-caption"))
+            .and(warp::path::end())
+            .and(session::csrf_protected())
+            .and(handlers::upload::size_limit(media_bytes, Msg::FileTooLarge, default_locale))
+            .and(handlers::upload::reserve(upload_budget.clone(), media_bytes, default_locale))
+            .and(
+                warp::multipart::form()
+                    .max_length(media_bytes)
+                    .and(with_state(media_state_upload.clone()))
+                    .and(with_config(app_config.clone()))
+                    .and(i18n::locale(app_config.locale))
+                    .and_then(handlers::upload::preview_caption),
+            )
+            .map(handlers::upload::release);
+
+        let stage_route = warp::post()
+            .and(warp::path("stage"))
+            .and(warp::path::end())
+            .and(session::csrf_protected())
+            .and(handlers::bans::not_banned(media_state_upload.clone()))
+            .and(handlers::upload::size_limit(media_bytes, Msg::FileTooLarge, default_locale))
+            .and(handlers::upload::reserve(upload_budget.clone(), media_bytes, default_locale))
+            .and(
+                warp::multipart::form()
+                    .max_length(media_bytes)
+                    .and(session::client_id())
+                    .and(with_state(media_state_upload.clone()))
+                    .and(with_ws_state(ws_clients_upload.clone()))
+                    .and(with_config(app_config.clone()))
+                    .and(i18n::locale(app_config.locale))
+                    .and(handlers::upload::reply_format())
+                    .and_then(handlers::upload::stage_upload),
+            )
+            .map(handlers::upload::release);
+
+        let publish_staged_route = warp::post()
+            .and(warp::path!("publish" / u64))
+            .and(session::csrf_protected())
+            .and(handlers::bans::not_banned(media_state_upload.clone()))
+            .and(with_state(media_state_upload.clone()))
+            .and(with_ws_state(ws_clients_upload.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::upload::publish_staged);
+
+        let imagine_route = warp::post()
+            .and(warp::path("imagine"))
+            .and(warp::path::end())
+            .and(session::csrf_protected())
+            .and(handlers::bans::not_banned(media_state_upload.clone()))
+            .and(warp::body::form())
+            .and(session::client_id())
+            .and(with_state(media_state_upload.clone()))
+            .and(with_ws_state(ws_clients_upload.clone()))
+            .and(with_config(app_config.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::upload::imagine);
+
+        let paste_route = warp::post()
+            .and(warp::path("paste"))
+            .and(warp::path::end())
+            .and(handlers::bans::not_banned(media_state_upload.clone()))
+            .and(handlers::upload::size_limit(media_bytes, Msg::FileTooLarge, default_locale))
+            .and(handlers::upload::reserve(upload_budget.clone(), media_bytes, default_locale))
+            .and(
+                warp::header::optional::<String>("content-type")
+                    .and(warp::query::<HashMap<String, String>>())
+                    .and(warp::body::content_length_limit(media_bytes))
+                    .and(warp::body::bytes())
+                    .and(session::client_id())
+                    .and(with_state(media_state_upload.clone()))
+                    .and(with_ws_state(ws_clients_upload.clone()))
+                    .and(with_config(app_config.clone()))
+                    .and(i18n::locale(app_config.locale))
+                    .and(handlers::upload::reply_format())
+                    .and_then(handlers::upload::paste_image),
+            )
+            .map(handlers::upload::release);
+
+        // Backward compatibility for YouTube uploads
+        let upload_youtube_route = warp::post()
+            .and(warp::path("upload-youtube"))
+            .and(session::csrf_protected())
+            .and(handlers::bans::not_banned(media_state_upload.clone()))
+            .and(warp::body::form())
+            .and(session::client_id())
+            .and(with_state(media_state_upload.clone()))
+            .and(with_ws_state(ws_clients_upload.clone()))
+            .and(with_config(app_config.clone()))
+            .and(with_jobs(jobs.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::upload::upload_video_url);
+
+        let upload_sound_route = warp::post()
+            .and(warp::path("upload-sound"))
+            .and(session::csrf_protected())
+            .and(handlers::bans::not_banned(media_state_upload.clone()))
+            .and(handlers::upload::size_limit(sound_bytes, Msg::SoundTooLarge, default_locale))
+            .and(handlers::upload::reserve(upload_budget.clone(), sound_bytes, default_locale))
+            .and(
+                warp::multipart::form()
+                    .max_length(sound_bytes)
+                    .and(warp::addr::remote())
+                    .and(with_state(media_state_upload.clone()))
+                    .and(with_ws_state(ws_clients_upload.clone())) // Add WebSocket state
+                    .and(with_config(app_config.clone()))
+                    .and(i18n::locale(app_config.locale))
+                    .and_then(handlers::upload::upload_sound),
+            )
+            .map(handlers::upload::release);
+
+        let upload_music_route = warp::post()
+            .and(warp::path("upload-music"))
+            .and(session::csrf_protected())
+            .and(handlers::bans::not_banned(media_state_upload.clone()))
+            .and(handlers::upload::size_limit(sound_bytes, Msg::SoundTooLarge, default_locale))
+            .and(handlers::upload::reserve(upload_budget.clone(), sound_bytes, default_locale))
+            .and(
+                warp::multipart::form()
+                    .max_length(sound_bytes)
+                    .and(with_state(media_state_upload.clone()))
+                    .and(with_config(app_config.clone()))
+                    .and(i18n::locale(app_config.locale))
+                    .and_then(handlers::upload::upload_music),
+            )
+            .map(handlers::upload::release);
+
+        // Media routes
+        let last_media_route = warp::get()
+            .and(warp::path("last-media"))
+            .and(warp::query::<HashMap<String, String>>())
+            .and(session::client_id())
+            .and(with_state(media_state_media.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::media::last_media);
+
+        let display_state_route = warp::get()
+            .and(warp::path("display-state"))
+            .and(warp::path::end())
+            .and(with_state(media_state_media.clone()))
+            .and_then(handlers::media::display_state);
+
+        let time_route = warp::get()
+            .and(warp::path("time"))
+            .and(warp::path::end())
+            .and(with_state(media_state_media.clone()))
+            .and_then(handlers::media::server_time);
+
+        // Uploader timeline routes
+        let my_uploads_route = warp::get()
+            .and(warp::path("my-uploads"))
+            .and(warp::path::end())
+            .and(session::client_id())
+            .and(with_state(media_state_media.clone()))
+            .and_then(handlers::history::my_uploads);
+
+        let cancel_upload_route = warp::delete()
+            .and(warp::path!("my-uploads" / u64))
+            .and(session::client_id())
+            .and(with_state(media_state_media.clone()))
+            .and_then(handlers::history::cancel_upload);
+
+        let unlock_route = warp::post()
+            .and(warp::path("unlock"))
+            .and(warp::path::end())
+            .and(session::csrf_protected())
+            .and(warp::body::form())
+            .and(with_state(media_state_media.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::unlock::unlock);
+
+        // Soundboard routes
+        let soundboard_route = warp::get()
+            .and(warp::path("soundboard"))
+            .and(warp::path::end())
+            .and(session::csrf_cookie())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::soundboard::soundboard_page);
+
+        let list_sounds_route = warp::get()
+            .and(warp::path!("soundboard" / "sounds"))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::soundboard::list_sounds);
+
+        let set_hotkey_route = warp::post()
+            .and(warp::path!("soundboard" / u64 / "hotkey"))
+            .and(session::csrf_protected())
+            .and(warp::body::form())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::soundboard::set_hotkey);
+
+        let play_sound_route = warp::post()
+            .and(warp::path!("play" / u64))
+            .and(session::csrf_protected())
+            .and(session::client_id())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::soundboard::play_sound);
+
+        let play_hotkey_route = warp::post()
+            .and(warp::path!("play" / "hotkey" / u8))
+            .and(session::csrf_protected())
+            .and(session::client_id())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::soundboard::play_hotkey);
+
+        let sound_waveform_route = warp::get()
+            .and(warp::path!("sounds" / String / "waveform"))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::soundboard::sound_waveform);
+
+        // Background music routes
+        let music_status_route = warp::get()
+            .and(warp::path("music"))
+            .and(warp::path::end())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::music::music_status);
+
+        let music_control_route = warp::post()
+            .and(warp::path!("music" / String))
+            .and(session::csrf_protected())
+            .and(warp::body::form())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::music::music_control);
+
+        let now_playing_route = warp::get()
+            .and(warp::path("now-playing"))
+            .and(warp::path::end())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::now_playing::now_playing);
+
+        let set_now_playing_route = warp::post()
+            .and(warp::path("now-playing"))
+            .and(warp::path::end())
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(warp::body::content_length_limit(16 * 1024))
+            .and(warp::body::json())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::now_playing::set_now_playing);
+
+        let clear_now_playing_route = warp::delete()
+            .and(warp::path("now-playing"))
+            .and(warp::path::end())
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::now_playing::clear_now_playing);
+
+        let game_status_route = warp::get()
+            .and(warp::path("game-status"))
+            .and(warp::path::end())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::game_status::game_status);
+
+        let stats_route = warp::get()
+            .and(warp::path("stats"))
+            .and(warp::path::end())
+            .and(warp::query::<HashMap<String, String>>())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::stats::stats);
+
+        let recap_route = warp::get()
+            .and(warp::path!("stats" / "recap"))
+            .and(warp::query::<HashMap<String, String>>())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::stats::recap_page);
+
+        // Archive routes
+        let archive_route = warp::get()
+            .and(warp::path("archive"))
+            .and(warp::path::end())
+            .and(session::csrf_cookie())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::archive::archive_page);
+
+        let pin_media_route = warp::post()
+            .and(warp::path!("media" / u64 / "pin"))
+            .and(session::csrf_protected())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::archive::pin_media);
+
+        let frame_route = warp::get()
+            .and(warp::path!("media" / u64 / "frame"))
+            .and(warp::query::<HashMap<String, String>>())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::clips::frame);
+
+        let trim_route = warp::post()
+            .and(warp::path!("media" / u64 / "trim"))
+            .and(handlers::bans::not_banned(media_state.clone()))
+            .and(session::csrf_protected())
+            .and(warp::body::form())
+            .and(session::client_id())
+            .and(warp::header::optional::<String>(session::ADMIN_HEADER))
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and(with_config(app_config.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::clips::trim);
+
+        let concat_route = warp::post()
+            .and(warp::path!("media" / "concat"))
+            .and(handlers::bans::not_banned(media_state.clone()))
+            .and(session::csrf_protected())
+            .and(warp::body::content_length_limit(16 * 1024))
+            .and(warp::body::json())
+            .and(session::client_id())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and(with_config(app_config.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::clips::concat);
+
+        let show_archived_route = warp::post()
+            .and(warp::path!("archive" / u64 / "show"))
+            .and(session::csrf_protected())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::archive::show_archived);
+
+        let snapshot_route = warp::post()
+            .and(warp::path!("snapshot" / String))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and(with_config(app_config.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::camera::snapshot);
+
+        let create_share_route = warp::post()
+            .and(warp::path!("archive" / u64 / "share"))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and(with_config(app_config.clone()))
+            .and_then(handlers::share::create_share);
+
+        let share_route = warp::get()
+            .or(warp::head())
+            .unify()
+            .and(warp::path!("share" / String))
+            .and(warp::method())
+            .and(warp::header::headers_cloned())
+            .and(with_state(media_state.clone()))
+            .and(with_config(app_config.clone()))
+            .and_then(handlers::share::serve_share);
+
+        // Do not disturb routes
+        let dnd_status_route = warp::get()
+            .and(warp::path("dnd"))
+            .and(warp::path::end())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::dnd::dnd_status);
+
+        let set_dnd_route = warp::post()
+            .and(warp::path("dnd"))
+            .and(warp::path::end())
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(session::csrf_protected())
+            .and(warp::body::form())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::dnd::set_dnd);
+
+        // Theme routes
+        let theme_route = warp::get()
+            .and(warp::path("theme"))
+            .and(warp::path::end())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::theme::current_theme);
+
+        let set_theme_route = warp::post()
+            .and(warp::path("theme"))
+            .and(warp::path::end())
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(warp::body::form())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::theme::set_theme);
+
+        // Banner routes
+        let banner_route = warp::get()
+            .and(warp::path("banner"))
+            .and(warp::path::end())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::banner::banner);
+
+        let set_banner_route = warp::post()
+            .and(warp::path("banner"))
+            .and(warp::path::end())
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(warp::body::form())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::banner::set_banner);
+
+        let clear_banner_route = warp::delete()
+            .and(warp::path("banner"))
+            .and(warp::path::end())
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::banner::clear_banner);
+
+        // Bans keeping clients from uploading and chatting
+        let list_bans_route = warp::get()
+            .and(warp::path!("admin" / "bans"))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::bans::list_bans);
+
+        let ban_route = warp::post()
+            .and(warp::path!("admin" / "ban"))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(warp::body::form())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::bans::ban);
+
+        let unban_route = warp::delete()
+            .and(warp::path!("admin" / "ban"))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(warp::body::form())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::bans::unban);
+
+        let audit_route = warp::get()
+            .and(warp::path!("admin" / "audit"))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(warp::query::<HashMap<String, String>>())
+            .and(with_audit(audit.clone()))
+            .and_then(handlers::audit::audit_log);
+
+        // Word filter and the uploads it holds for review
+        let reload_word_filter_route = warp::post()
+            .and(warp::path!("word-filter" / "reload"))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and(with_config(app_config.clone()))
+            .and_then(handlers::word_filter::reload_word_filter);
+
+        let review_route = warp::get()
+            .and(warp::path("review"))
+            .and(warp::path::end())
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::word_filter::list_review);
+
+        let approve_review_route = warp::post()
+            .and(warp::path!("review" / u64))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and(i18n::locale(app_config.locale))
+            .and_then(handlers::word_filter::approve_review);
+
+        let reject_review_route = warp::delete()
+            .and(warp::path!("review" / u64))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::word_filter::reject_review);
+
+        // Displays registered on /ws
+        let list_displays_route = warp::get()
+            .and(warp::path("displays"))
+            .and(warp::path::end())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::displays::list_displays);
+
+        let rename_display_route = warp::post()
+            .and(warp::path!("displays" / String))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(warp::body::form())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::displays::rename_display);
+
+        let forget_display_route = warp::delete()
+            .and(warp::path!("displays" / String))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::displays::forget_display);
+
+        // Leaderboard routes
+        let leaderboard_route = warp::get()
+            .and(warp::path("leaderboard"))
+            .and(warp::path::end())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::leaderboard::leaderboard);
+
+        let leaderboard_page_route = warp::get()
+            .and(warp::path!("leaderboard" / "page"))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::leaderboard::leaderboard_page);
+
+        let reset_leaderboard_route = warp::post()
+            .and(warp::path!("leaderboard" / "reset"))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::leaderboard::reset_leaderboard);
+
+        let react_route = warp::post()
+            .and(warp::path("react"))
+            .and(warp::path::end())
+            .and(session::csrf_protected())
+            .and(warp::body::form())
+            .and(session::client_id())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::leaderboard::react);
+
+        // Timer routes
+        let list_timers_route = warp::get()
+            .and(warp::path("timers"))
+            .and(warp::path::end())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::timers::list_timers);
+
+        let start_timer_route = warp::post()
+            .and(warp::path("timer"))
+            .and(warp::path::end())
+            .and(session::csrf_protected())
+            .and(warp::body::form())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::timers::start_timer);
+
+        let cancel_timer_route = warp::delete()
+            .and(warp::path!("timer" / u64))
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::timers::cancel_timer);
+
+        let extend_timer_route = warp::post()
+            .and(warp::path!("timer" / u64 / "extend"))
+            .and(session::csrf_protected())
+            .and(warp::body::form())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::timers::extend_timer);
+
+        // Poll routes
+        let current_poll_route = warp::get()
+            .and(warp::path("poll"))
+            .and(warp::path::end())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::polls::current_poll);
+
+        let start_poll_route = warp::post()
+            .and(warp::path("poll"))
+            .and(warp::path::end())
+            .and(session::csrf_protected())
+            .and(warp::body::form())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and(with_config(app_config.clone()))
+            .and_then(handlers::polls::start_poll);
+
+        let vote_route = warp::post()
+            .and(warp::path!("poll" / u64 / "vote"))
+            .and(session::csrf_protected())
+            .and(warp::body::form())
+            .and(session::client_id())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::polls::vote);
+
+        // Chat ticker routes
+        let chat_route = warp::post()
+            .and(warp::path("chat"))
+            .and(warp::path::end())
+            .and(handlers::bans::not_banned(media_state.clone()))
+            .and(session::csrf_protected())
+            .and(warp::body::form())
+            .and(session::client_id())
+            .and(warp::addr::remote())
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and(with_config(app_config.clone()))
+            .and_then(handlers::chat::post_chat);
+
+        let recent_chat_route = warp::get()
+            .and(warp::path!("chat" / "recent"))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::chat::recent_chat);
+
+        // Drawing routes
+        let draw_page_route = warp::get()
+            .and(warp::path("draw"))
+            .and(warp::path::end())
+            .and(session::csrf_cookie())
+            .and_then(handlers::drawing::draw_page);
+
+        let canvas_route = warp::get()
+            .and(warp::path!("draw" / "canvas"))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::drawing::canvas);
+
+        let draw_route = warp::post()
+            .and(warp::path("draw"))
+            .and(warp::path::end())
+            .and(session::csrf_protected())
+            .and(warp::body::content_length_limit(256 * 1024))
+            .and(warp::body::json())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::drawing::draw);
+
+        let clear_canvas_route = warp::delete()
+            .and(warp::path!("draw" / "canvas"))
+            .and(with_state(media_state.clone()))
+            .and(with_ws_state(ws_clients.clone()))
+            .and_then(handlers::drawing::clear_canvas);
+
+        // Job routes
+        let list_jobs_route = warp::get()
+            .and(warp::path!("jobs"))
+            .and(with_jobs(jobs.clone()))
+            .and_then(handlers::jobs::list_jobs);
+
+        // Off unless enabled, the schema is built either way to keep the filter type the same
+        #[cfg(feature = "graphql")]
+        let graphql_route = {
+            let graphql_enabled = app_config.graphql;
+            let schema = graphql::schema(media_state.clone(), jobs.clone(), ws_clients.clone());
+            warp::path("graphql")
+                .and(warp::path::end())
+                .and_then(move || async move {
+                    if graphql_enabled {
+                        Ok(())
+                    } else {
+                        Err(warp::reject::not_found())
+                    }
+                })
+                .untuple_one()
+                .and(
+                    async_graphql_warp::graphql_subscription(schema.clone())
+                        .or(async_graphql_warp::graphql(schema)
+                            .and_then(handlers::graphql::execute))
+                        .recover(handlers::graphql::handle_rejection),
+                )
+        };
+        let cancel_job_route = warp::delete()
+            .and(warp::path!("jobs" / u64))
+            .and(with_jobs(jobs.clone()))
+            .and(with_ws_state(ws_clients_upload.clone()))
+            .and_then(handlers::jobs::cancel_job);
+
+        // Metrics routes
+        let retry_metrics_route = warp::get()
+            .and(warp::path!("metrics" / "retries"))
+            .and_then(handlers::metrics::retry_metrics);
+
+        let broadcast_metrics_route = warp::get()
+            .and(warp::path!("metrics" / "broadcast"))
+            .and(with_ws_state(ws_clients.clone()))
+            .and(with_config(app_config.clone()))
+            .and_then(handlers::metrics::broadcast_metrics);
+
+        let cleanup_metrics_route = warp::get()
+            .and(warp::path!("metrics" / "cleanup"))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::metrics::cleanup_metrics);
+
+        let upload_metrics_route = warp::get()
+            .and(warp::path!("metrics" / "uploads"))
+            .and(with_upload_budget(upload_budget.clone()))
+            .and_then(handlers::metrics::upload_metrics);
+
+        let diagnostics_route = warp::get()
+            .and(warp::path("diagnostics"))
+            .and(warp::path::end())
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_capabilities(capabilities.clone()))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::metrics::diagnostics);
+
+        // Trash routes
+        let trash_route = warp::get()
+            .and(warp::path("trash"))
+            .and(warp::path::end())
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::trash::list_trash);
+
+        let restore_route = warp::post()
+            .and(warp::path!("trash" / String / "restore"))
+            .and(session::admin_only(app_config.admin_token.clone(), audit.clone()))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::trash::restore);
+
+        let media_hits_route = warp::get()
+            .and(warp::path!("metrics" / "hits"))
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::metrics::media_hits);
+
+        // WebSocket route - THIS IS THE NEW PART
+        let ws_route = warp::path("ws")
+            .and(warp::ws())
+            .and(warp::query::<HashMap<String, String>>())
+            .and(session::client_id())
+            .and(warp::addr::remote())
+            .and(with_ws_state(ws_clients_route))
+            .and(with_state(media_state.clone()))
+            .and(with_config(app_config.clone()))
+            .and_then(
+                |ws: warp::ws::Ws,
+                 query,
+                 client,
+                 addr: Option<SocketAddr>,
+                 clients,
+                 state,
+                 config: Arc<config::AppConfig>| {
+                    let chat = config.chat.clone();
+                    let ip = addr.map(|addr| addr.ip());
+                    websocket::ws_handler(ws, query, client, ip, clients, state, chat)
+                },
+            );
+
+        // Transparent page for OBS browser sources
+        let overlay_route = warp::get()
+            .and(warp::path("overlay"))
+            .and(warp::path::end())
+            .and_then(handlers::media::overlay_page);
+
+        // Serve uploaded files
+        // Pinned media still resolves under /uploads/ so a display mid-playback keeps working
+        let dirs = &self.data_dirs;
+        let posters_dir = warp::path!("uploads" / "posters" / ..)
+            .and(serve_files(vec![dirs.posters()], media_state.clone()));
+        let previews_dir = warp::path!("uploads" / "previews" / ..)
+            .and(serve_files(vec![dirs.previews()], media_state.clone()));
+        let renditions_dir = warp::path!("uploads" / "renditions" / ..)
+            .and(serve_files(vec![dirs.renditions()], media_state.clone()));
+        let live_dir = warp::path!("uploads" / "live" / ..)
+            .and(serve_files(vec![dirs.live()], media_state.clone()));
+        let hls_dir = warp::get()
+            .or(warp::head())
+            .unify()
+            .and(warp::path!("uploads" / "hls" / String / String))
+            .and(warp::method())
+            .and(warp::header::headers_cloned())
+            .and(with_state(media_state.clone()))
+            .and_then(handlers::files::serve_hls);
+        let uploads_dir = warp::path("uploads").and(serve_files(
+            vec![dirs.uploads.clone(), dirs.archive.clone()],
+            media_state.clone(),
+        ));
+        let archive_dir =
+            warp::path("archive").and(serve_files(vec![dirs.archive.clone()], media_state.clone()));
+        let sounds_dir =
+            warp::path("sounds").and(serve_files(vec![dirs.sounds.clone()], media_state.clone()));
+        let music_dir =
+            warp::path("music").and(serve_files(vec![dirs.music.clone()], media_state.clone()));
+
+        // Combine all routes, boxed per area to keep the filter type shallow enough to compile
+        let upload_routes = upload_form_route
+            .or(capabilities_route)
+            .or(upload_video_route)
+            .or(upload_youtube_route)
+            .or(upload_url_route)
+            .or(paste_route)
+            .or(preview_caption_route)
+            .or(stage_route)
+            .or(publish_staged_route)
+            .or(imagine_route)
+            .or(upload_sound_route)
+            .or(upload_music_route)
+            .or(upload_route)
+            .boxed();
+        let media_routes = last_media_route
+            .or(display_state_route)
+            .or(time_route)
+            .or(my_uploads_route)
+            .or(cancel_upload_route)
+            .or(unlock_route)
+            .or(archive_route)
+            .or(pin_media_route)
+            .or(frame_route)
+            .or(trim_route)
+            .or(concat_route)
+            .or(show_archived_route)
+            .or(snapshot_route)
+            .or(create_share_route)
+            .or(share_route)
+            .or(dnd_status_route)
+            .or(set_dnd_route)
+            .or(theme_route)
+            .or(set_theme_route)
+            .or(banner_route)
+            .or(set_banner_route)
+            .or(clear_banner_route)
+            .boxed();
+        let admin_routes = audit_route
+            .or(list_bans_route)
+            .or(ban_route)
+            .or(unban_route)
+            .or(reload_word_filter_route)
+            .or(review_route)
+            .or(approve_review_route)
+            .or(reject_review_route)
+            .or(list_displays_route)
+            .or(rename_display_route)
+            .or(forget_display_route)
+            .boxed();
+        let game_routes = leaderboard_route
+            .or(leaderboard_page_route)
+            .or(reset_leaderboard_route)
+            .or(react_route)
+            .or(list_timers_route)
+            .or(start_timer_route)
+            .or(cancel_timer_route)
+            .or(extend_timer_route)
+            .boxed();
+        let poll_routes = current_poll_route
+            .or(start_poll_route)
+            .or(vote_route)
+            .or(chat_route)
+            .or(recent_chat_route)
+            .boxed();
+        let draw_routes = draw_page_route
+            .or(canvas_route)
+            .or(draw_route)
+            .or(clear_canvas_route)
+            .boxed();
+        let soundboard_routes = soundboard_route
+            .or(list_sounds_route)
+            .or(set_hotkey_route)
+            .or(play_sound_route)
+            .or(play_hotkey_route)
+            .or(sound_waveform_route)
+            .or(music_status_route)
+            .or(music_control_route)
+            .or(now_playing_route)
+            .or(set_now_playing_route)
+            .or(clear_now_playing_route)
+            .or(game_status_route)
+            .boxed();
+        let job_routes = list_jobs_route
+            .or(cancel_job_route)
+            .or(retry_metrics_route)
+            .or(broadcast_metrics_route)
+            .or(media_hits_route)
+            .or(cleanup_metrics_route)
+            .or(upload_metrics_route)
+            .or(diagnostics_route)
+            .or(trash_route)
+            .or(restore_route)
+            .or(stats_route)
+            .or(recap_route)
+            .boxed();
+        #[cfg(feature = "graphql")]
+        let job_routes = job_routes.or(graphql_route).boxed();
+        let file_routes = posters_dir
+            .or(previews_dir)
+            .or(renditions_dir)
+            .or(hls_dir)
+            .or(live_dir)
+            .or(uploads_dir)
+            .or(archive_dir)
+            .or(sounds_dir)
+            .or(music_dir)
+            .boxed();
+
+        index_route
+            .or(upload_routes)
+            .or(media_routes)
+            .or(admin_routes)
+            .or(game_routes)
+            .or(soundboard_routes)
+            .or(poll_routes)
+            .or(draw_routes)
+            .or(job_routes)
+            .or(ws_route) // Add WebSocket route
+            .or(overlay_route)
+            .or(file_routes)
+            .recover(move |rejection| session::handle_rejection(rejection, default_locale))
+    }
+}
+
+fn with_state(
+    state: StateHandle,
+) -> impl Filter<Extract = (StateHandle,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+// Add WebSocket state filter
+fn with_ws_state(
+    clients: websocket::WsClients,
+) -> impl Filter<Extract = (websocket::WsClients,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || clients.clone())
+}
+
+fn with_capabilities(
+    capabilities: Arc<Capabilities>,
+) -> impl Filter<Extract = (Arc<Capabilities>,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || capabilities.clone())
+}
+
+fn with_jobs(
+    jobs: jobs::SharedJobs,
+) -> impl Filter<Extract = (jobs::SharedJobs,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || jobs.clone())
+}
+
+fn with_upload_budget(
+    budget: UploadBudget,
+) -> impl Filter<Extract = (UploadBudget,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || budget.clone())
+}
+
+fn with_audit(
+    audit: AuditLog,
+) -> impl Filter<Extract = (AuditLog,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || audit.clone())
+}
+
+// Stored files, looked up in each directory in turn
+fn serve_files(
+    dirs: Vec<String>,
+    state: StateHandle,
+) -> impl Filter<Extract = (warp::http::Response<warp::hyper::Body>,), Error = Rejection> + Clone {
+    warp::get()
+        .or(warp::head())
+        .unify()
+        .and(warp::method())
+        .and(warp::path::tail())
+        .and(warp::header::headers_cloned())
+        .and(with_state(state))
+        .and_then(move |method, tail, headers, state| {
+            handlers::files::serve_file(dirs.clone(), method, tail, headers, state)
+        })
+}
+
+fn with_config(
+    config: Arc<config::AppConfig>,
+) -> impl Filter<Extract = (Arc<config::AppConfig>,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || config.clone())
+}
@@ -0,0 +1,333 @@
+use crate::errors::AppError;
+use crate::utils::{sanitize_filename, validate_file_path};
+use tokio::process::Command as AsyncCommand;
+
+/// Number of peaks in a sound's waveform preview
+pub const WAVEFORM_PEAKS: usize = 100;
+const WAVEFORM_SAMPLE_RATE: &str = "8000";
+
+/// Picture drawn for a sound turned into a video for the display
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Visualizer {
+    Waves,
+    Spectrum,
+}
+
+impl Visualizer {
+    /// A plain "on" picks the waveform, anything unknown leaves the sound without video
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "waves" | "on" | "true" | "1" => Some(Visualizer::Waves),
+            "spectrum" => Some(Visualizer::Spectrum),
+            _ => None,
+        }
+    }
+
+    fn filter(self) -> &'static str {
+        match self {
+            Visualizer::Waves => {
+                "[0:a]showwaves=s=1280x720:mode=cline:rate=30:colors=0x4fc3f7,format=yuv420p[v]"
+            }
+            Visualizer::Spectrum => {
+                "[0:a]showspectrum=s=1280x720:mode=combined:color=intensity:slide=scroll,\
+                 format=yuv420p[v]"
+            }
+        }
+    }
+}
+
+pub struct AudioProcessor;
+
+impl AudioProcessor {
+    /// Cut an audio file down to the [start, end) range (in seconds) using ffmpeg
+    /// Both paths must live in `sounds_dir`
+    pub async fn trim(
+        sounds_dir: &str,
+        input_path: &str,
+        output_path: &str,
+        start: Option<f64>,
+        end: Option<f64>,
+    ) -> Result<(), AppError> {
+        // Sanitize and validate input and output paths
+        let input_filename = sanitize_filename(input_path)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let output_filename = sanitize_filename(output_path)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output filename")))?;
+
+        // Validate that both paths are within the sounds directory
+        let validated_input_path = validate_file_path(sounds_dir, &input_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
+        let validated_output_path = validate_file_path(sounds_dir, &output_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output file path")))?;
+
+        let start_arg = start.map(|secs| format!("{:.3}", secs));
+        let end_arg = end.map(|secs| format!("{:.3}", secs));
+
+        // Seek as an output option so the cut is sample accurate
+        let mut args = vec!["-i", validated_input_path.as_str()];
+        if let Some(start_arg) = &start_arg {
+            args.extend(["-ss", start_arg.as_str()]);
+        }
+        if let Some(end_arg) = &end_arg {
+            args.extend(["-to", end_arg.as_str()]);
+        }
+        args.extend(["-vn", "-y", validated_output_path.as_str()]);
+
+        let mut cmd = AsyncCommand::new("ffmpeg");
+        cmd.args(args);
+
+        tracing::info!(
+            "Trimming audio {} to {:?}-{:?}",
+            validated_input_path,
+            start,
+            end
+        );
+        tracing::debug!("FFmpeg command: {:?}", cmd);
+
+        let output = cmd.output().await.map_err(|e| {
+            tracing::error!("Failed to execute ffmpeg: {}", e);
+            AppError::IoError(std::io::Error::other("Audio processing failed"))
+        })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::error!("FFmpeg audio trim failed: {}", stderr);
+            return Err(AppError::IoError(std::io::Error::other(format!(
+                "FFmpeg processing failed: {}",
+                stderr
+            ))));
+        }
+
+        tracing::info!("Audio trimming completed: {}", validated_output_path);
+        Ok(())
+    }
+
+    /// Decode a stored sound and compute its waveform peaks, caching them in waveforms/
+    pub async fn generate_waveform(
+        sounds_dir: &str,
+        sound_filename: &str,
+    ) -> Result<Vec<f32>, AppError> {
+        let input_filename = sanitize_filename(sound_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let validated_input_path = validate_file_path(sounds_dir, &input_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
+        let waveform_path = Self::waveform_path(&input_filename)?;
+
+        // Decode to mono 16-bit PCM at a low sample rate, plenty for a preview
+        let mut cmd = AsyncCommand::new("ffmpeg");
+        cmd.args([
+            "-v",
+            "error",
+            "-i",
+            &validated_input_path,
+            "-ac",
+            "1",
+            "-ar",
+            WAVEFORM_SAMPLE_RATE,
+            "-f",
+            "s16le",
+            "-",
+        ]);
+
+        let output = cmd.output().await.map_err(|e| {
+            tracing::error!("Failed to execute ffmpeg: {}", e);
+            AppError::IoError(std::io::Error::other("Audio processing failed"))
+        })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::error!("FFmpeg audio decode failed: {}", stderr);
+            return Err(AppError::IoError(std::io::Error::other(format!(
+                "FFmpeg processing failed: {}",
+                stderr
+            ))));
+        }
+
+        let samples: Vec<i16> = output
+            .stdout
+            .chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+        let peaks = compute_peaks(&samples, WAVEFORM_PEAKS);
+
+        tokio::fs::create_dir_all("waveforms").await?;
+        let json = serde_json::to_vec(&peaks).map_err(std::io::Error::other)?;
+        tokio::fs::write(&waveform_path, json).await?;
+
+        tracing::info!("Waveform generated: {}", waveform_path);
+        Ok(peaks)
+    }
+
+    /// Read cached waveform peaks, generating them on first request
+    pub async fn load_waveform(
+        sounds_dir: &str,
+        sound_filename: &str,
+    ) -> Result<Vec<f32>, AppError> {
+        let input_filename = sanitize_filename(sound_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let waveform_path = Self::waveform_path(&input_filename)?;
+
+        match tokio::fs::read(&waveform_path).await {
+            Ok(json) => serde_json::from_slice(&json).map_err(|e| {
+                tracing::error!("Failed to parse cached waveform {}: {}", waveform_path, e);
+                AppError::IoError(std::io::Error::other("Failed to read waveform"))
+            }),
+            Err(_) => Self::generate_waveform(sounds_dir, &input_filename).await,
+        }
+    }
+
+    /// Render a sound stored in `sounds_dir` as a video of its waveform or spectrum in the
+    /// uploads directory, returning the video's filename
+    pub async fn visualize(
+        sounds_dir: &str,
+        uploads_dir: &str,
+        sound_filename: &str,
+        visualizer: Visualizer,
+    ) -> Result<String, AppError> {
+        let input_filename = sanitize_filename(sound_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let validated_input_path = validate_file_path(sounds_dir, &input_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
+        let output_filename = Self::visualizer_filename(&input_filename);
+        let validated_output_path = validate_file_path(uploads_dir, &output_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output file path")))?;
+        tokio::fs::create_dir_all(uploads_dir).await?;
+
+        let mut cmd = AsyncCommand::new("ffmpeg");
+        cmd.args([
+            "-v",
+            "error",
+            "-i",
+            &validated_input_path,
+            "-filter_complex",
+            visualizer.filter(),
+            "-map",
+            "[v]",
+            "-map",
+            "0:a",
+            "-c:v",
+            "libx264",
+            "-preset",
+            "veryfast",
+            "-c:a",
+            "aac",
+            "-shortest",
+            "-y",
+            &validated_output_path,
+        ]);
+
+        tracing::info!("Visualizing {} as {:?}", validated_input_path, visualizer);
+        tracing::debug!("FFmpeg command: {:?}", cmd);
+
+        let output = cmd.output().await.map_err(|e| {
+            tracing::error!("Failed to execute ffmpeg: {}", e);
+            AppError::IoError(std::io::Error::other("Audio processing failed"))
+        })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::error!("FFmpeg visualizer failed: {}", stderr);
+            return Err(AppError::IoError(std::io::Error::other(format!(
+                "FFmpeg processing failed: {}",
+                stderr
+            ))));
+        }
+
+        tracing::info!("Visualizer generated: {}", validated_output_path);
+        Ok(output_filename)
+    }
+
+    /// Video made from a sound, named after it
+    pub fn visualizer_filename(sound_filename: &str) -> String {
+        let stem = std::path::Path::new(sound_filename)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(sound_filename);
+        format!("visualized_{}.mp4", stem)
+    }
+
+    fn waveform_path(sound_filename: &str) -> Result<String, AppError> {
+        validate_file_path("waveforms", &format!("{}.json", sound_filename))
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid waveform path")))
+    }
+}
+
+/// Split samples into evenly sized buckets and keep the loudest one of each, scaled to 0.0-1.0
+pub fn compute_peaks(samples: &[i16], buckets: usize) -> Vec<f32> {
+    if samples.is_empty() || buckets == 0 {
+        return Vec::new();
+    }
+
+    let bucket_size = samples.len().div_ceil(buckets);
+    samples
+        .chunks(bucket_size)
+        .map(|chunk| {
+            let peak = chunk
+                .iter()
+                .map(|sample| sample.unsigned_abs())
+                .max()
+                .unwrap_or(0);
+            // Two decimals keep the JSON small
+            ((peak as f32 / i16::MAX as f32).min(1.0) * 100.0).round() / 100.0
+        })
+        .collect()
+}
+
+/// Parse a timestamp given as seconds ("12.5"), "mm:ss" or "hh:mm:ss"
+pub fn parse_timestamp(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    let mut seconds = 0.0;
+    for part in value.split(':') {
+        let part: f64 = part.parse().ok()?;
+        if !part.is_finite() || part < 0.0 {
+            return None;
+        }
+        seconds = seconds * 60.0 + part;
+    }
+    Some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_peaks() {
+        let samples = [0, 100, -32768, 50, 16384, -10, 0, 0];
+        assert_eq!(compute_peaks(&samples, 4), vec![0.0, 1.0, 0.5, 0.0]);
+
+        // Fewer samples than buckets yields one peak per sample
+        assert_eq!(compute_peaks(&[32767, 0], 10).len(), 2);
+
+        assert!(compute_peaks(&[], 10).is_empty());
+        assert!(compute_peaks(&samples, 0).is_empty());
+    }
+
+    #[test]
+    fn test_visualizer_names() {
+        assert_eq!(Visualizer::from_name("on"), Some(Visualizer::Waves));
+        assert_eq!(Visualizer::from_name(" Spectrum "), Some(Visualizer::Spectrum));
+        assert_eq!(Visualizer::from_name("off"), None);
+        assert_eq!(
+            AudioProcessor::visualizer_filename("song.mp3"),
+            "visualized_song.mp4"
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("12.5"), Some(12.5));
+        assert_eq!(parse_timestamp("1:05"), Some(65.0));
+        assert_eq!(parse_timestamp("01:00:03"), Some(3603.0));
+        assert_eq!(parse_timestamp(" 7 "), Some(7.0));
+
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("abc"), None);
+        assert_eq!(parse_timestamp("-3"), None);
+        assert_eq!(parse_timestamp("1::2"), None);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::playback;
+use crate::session::ClientId;
+use crate::state::MediaInfo;
+use crate::stats;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, oneshot};
+
+/// One line of the audit log
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at_ms: u64,     // Unix milliseconds
+    pub actor: String,  // Session prefix or IP address, as in the stats
+    pub action: String, // "POST /banner" for admin requests, "upload" for uploads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>, // Filename of an upload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>, // Caption of an upload
+    pub allowed: bool, // False for admin requests refused for a missing or wrong token
+}
+
+impl AuditEntry {
+    pub fn admin(
+        client: Option<&ClientId>,
+        action: String,
+        allowed: bool,
+        now: SystemTime,
+    ) -> Self {
+        Self {
+            at_ms: playback::unix_ms(now),
+            actor: stats::person(client),
+            action,
+            target: None,
+            detail: None,
+            allowed,
+        }
+    }
+
+    pub fn upload(media: &MediaInfo, now: SystemTime) -> Self {
+        Self {
+            at_ms: playback::unix_ms(now),
+            actor: stats::person(media.uploader.as_ref()),
+            action: "upload".to_string(),
+            target: Some(media.filename.clone()),
+            detail: Some(media.caption.clone()).filter(|caption| !caption.is_empty()),
+            allowed: true,
+        }
+    }
+}
+
+enum Command {
+    Append(AuditEntry),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Append-only log of admin requests and uploads, one JSON entry per line
+/// Written by a background task so requests never wait on the disk
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl AuditLog {
+    pub fn open(path: PathBuf) -> Self {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(write_entries(path.clone(), receiver));
+        Self { path, commands }
+    }
+
+    pub fn record(&self, entry: AuditEntry) {
+        if self.commands.send(Command::Append(entry)).is_err() {
+            tracing::error!("Audit log task is gone, entry dropped");
+        }
+    }
+
+    /// The latest `limit` entries, newest first, including those just recorded
+    pub async fn recent(&self, limit: usize) -> std::io::Result<Vec<AuditEntry>> {
+        let (done, flushed) = oneshot::channel();
+        if self.commands.send(Command::Flush(done)).is_ok() {
+            let _ = flushed.await;
+        }
+        let log = match tokio::fs::read_to_string(&self.path).await {
+            Ok(log) => log,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(log
+            .lines()
+            .rev()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .take(limit)
+            .collect())
+    }
+}
+
+async fn write_entries(path: PathBuf, mut commands: mpsc::UnboundedReceiver<Command>) {
+    while let Some(command) = commands.recv().await {
+        match command {
+            Command::Append(entry) => {
+                if let Err(e) = append(&path, &entry).await {
+                    tracing::error!("Failed to write to {}: {}", path.display(), e);
+                }
+            }
+            Command::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+async fn append(path: &PathBuf, entry: &AuditEntry) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(&line).await?;
+    file.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[tokio::test]
+    async fn test_audit_log_appends_and_lists_newest_first() {
+        let path = std::env::temp_dir().join(format!("audit_{}.log", std::process::id()));
+        let log = AuditLog::open(path.clone());
+        assert!(log.recent(10).await.unwrap().is_empty());
+
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let admin = ClientId::Ip([192, 168, 1, 20].into());
+        log.record(AuditEntry::admin(Some(&admin), "POST /banner".to_string(), true, now));
+        log.record(AuditEntry::admin(None, "DELETE /banner".to_string(), false, now));
+        let entries = log.recent(10).await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, "DELETE /banner");
+        assert_eq!(entries[0].actor, "unknown");
+        assert!(!entries[0].allowed);
+        assert_eq!(entries[1].actor, stats::person(Some(&admin)));
+        assert_eq!(entries[1].at_ms, 1_000_000);
+    }
+}
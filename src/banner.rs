@@ -0,0 +1,89 @@
+use crate::playback;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+// One line across the top of the screen
+const MAX_TEXT_CHARS: usize = 200;
+
+/// Text pinned over the display, e.g. "rent due Friday", kept through media changes until it
+/// expires or is cleared
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Banner {
+    pub text: String,
+    pub expires_at: Option<SystemTime>, // Stays up until cleared when unset
+}
+
+/// A banner as sent to displays, which take it down themselves at `expires_at_ms`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BannerStatus {
+    pub text: String,
+    pub expires_at_ms: Option<u64>, // Unix milliseconds
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum BannerError {
+    #[error("Banner text is required")]
+    Empty,
+    #[error("Banner text can't be longer than {MAX_TEXT_CHARS} characters")]
+    TooLong,
+    #[error("Banner text has a word that isn't allowed here")]
+    Refused,
+}
+
+impl Banner {
+    pub fn new(text: &str, ttl: Option<Duration>, now: SystemTime) -> Result<Self, BannerError> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(BannerError::Empty);
+        }
+        if text.chars().count() > MAX_TEXT_CHARS {
+            return Err(BannerError::TooLong);
+        }
+        Ok(Self {
+            text: text.to_string(),
+            // Too far out to represent is as good as never
+            expires_at: ttl.and_then(|ttl| now.checked_add(ttl)),
+        })
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub fn status(&self) -> BannerStatus {
+        BannerStatus {
+            text: self.text.clone(),
+            expires_at_ms: self.expires_at.map(playback::unix_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_banner() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let banner = Banner::new("  Rent due Friday ", Some(Duration::from_secs(60)), now).unwrap();
+        assert_eq!(
+            banner.status(),
+            BannerStatus {
+                text: "Rent due Friday".to_string(),
+                expires_at_ms: Some(1_060_000),
+            }
+        );
+        assert!(!banner.is_expired(now + Duration::from_secs(59)));
+        assert!(banner.is_expired(now + Duration::from_secs(60)));
+        let pinned = Banner::new("Pinned", None, now).unwrap();
+        assert!(!pinned.is_expired(now + Duration::from_secs(365 * 24 * 3600)));
+
+        assert_eq!(Banner::new(" ", None, now), Err(BannerError::Empty));
+        assert_eq!(
+            Banner::new(&"a".repeat(201), None, now),
+            Err(BannerError::TooLong)
+        );
+    }
+}
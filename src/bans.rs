@@ -0,0 +1,211 @@
+use crate::playback;
+use crate::session::ClientId;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+const MAX_REASON_CHARS: usize = 200;
+
+/// Who a ban applies to
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BanTarget {
+    Ip(IpAddr),
+    Session(String),
+}
+
+/// A client kept from uploading and chatting
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ban {
+    #[serde(flatten)]
+    pub target: BanTarget,
+    pub reason: String,
+    pub banned_at_ms: u64,          // Unix milliseconds
+    pub expires_at_ms: Option<u64>, // Permanent when unset
+}
+
+impl Ban {
+    fn is_active(&self, now_ms: u64) -> bool {
+        self.expires_at_ms.is_none_or(|expires_at_ms| expires_at_ms > now_ms)
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum BanError {
+    #[error("Ban an ip or a session")]
+    NoTarget,
+    #[error("Not an IP address or a session id")]
+    InvalidTarget,
+    #[error("Ban reasons can't be longer than {MAX_REASON_CHARS} characters")]
+    ReasonTooLong,
+    #[error("No ban for this client")]
+    NotFound,
+}
+
+impl BanTarget {
+    /// From the `ip` or `session` field of an admin form
+    pub fn parse(ip: Option<&str>, session: Option<&str>) -> Result<Self, BanError> {
+        let ip = ip.map(str::trim).filter(|ip| !ip.is_empty());
+        let session = session.map(str::trim).filter(|session| !session.is_empty());
+        match (ip, session) {
+            (Some(ip), None) => ip.parse().map(Self::Ip).map_err(|_| BanError::InvalidTarget),
+            (None, Some(session)) => {
+                match ClientId::from_request(Some(session.to_string()), None) {
+                    Some(ClientId::Session(session)) => Ok(Self::Session(session)),
+                    _ => Err(BanError::InvalidTarget),
+                }
+            }
+            (Some(_), Some(_)) => Err(BanError::InvalidTarget),
+            (None, None) => Err(BanError::NoTarget),
+        }
+    }
+
+    fn matches(&self, session: Option<&str>, ip: Option<IpAddr>) -> bool {
+        match self {
+            Self::Ip(banned) => ip == Some(*banned),
+            Self::Session(banned) => session == Some(banned.as_str()),
+        }
+    }
+}
+
+/// Banned clients, saved to disk; temporary bans lapse on their own
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BanList {
+    bans: Vec<Ban>,
+}
+
+impl BanList {
+    /// Ban `target`, or change its ban, for `ttl` or until lifted
+    pub fn ban(
+        &mut self,
+        target: BanTarget,
+        reason: &str,
+        ttl: Option<Duration>,
+        now: SystemTime,
+    ) -> Result<Ban, BanError> {
+        let reason = reason.trim();
+        if reason.chars().count() > MAX_REASON_CHARS {
+            return Err(BanError::ReasonTooLong);
+        }
+        self.prune(now);
+        self.bans.retain(|ban| ban.target != target);
+        let ban = Ban {
+            target,
+            reason: reason.to_string(),
+            banned_at_ms: playback::unix_ms(now),
+            expires_at_ms: ttl.and_then(|ttl| now.checked_add(ttl)).map(playback::unix_ms),
+        };
+        self.bans.push(ban.clone());
+        Ok(ban)
+    }
+
+    pub fn lift(&mut self, target: &BanTarget) -> Result<Ban, BanError> {
+        let index = self
+            .bans
+            .iter()
+            .position(|ban| ban.target == *target)
+            .ok_or(BanError::NotFound)?;
+        Ok(self.bans.remove(index))
+    }
+
+    /// The active ban of a client with this session cookie or address, if any
+    pub fn find(
+        &self,
+        session: Option<&str>,
+        ip: Option<IpAddr>,
+        now: SystemTime,
+    ) -> Option<&Ban> {
+        let now_ms = playback::unix_ms(now);
+        self.bans
+            .iter()
+            .find(|ban| ban.is_active(now_ms) && ban.target.matches(session, ip))
+    }
+
+    /// Ban on the session of `client`, or on `ip`, the address it connects from
+    pub fn find_client(
+        &self,
+        client: &ClientId,
+        ip: Option<IpAddr>,
+        now: SystemTime,
+    ) -> Option<&Ban> {
+        match client {
+            ClientId::Session(session) => self.find(Some(session), ip, now),
+            ClientId::Ip(client_ip) => self.find(None, ip.or(Some(*client_ip)), now),
+        }
+    }
+
+    /// Active bans, oldest first
+    pub fn list(&self, now: SystemTime) -> Vec<Ban> {
+        let now_ms = playback::unix_ms(now);
+        self.bans
+            .iter()
+            .filter(|ban| ban.is_active(now_ms))
+            .cloned()
+            .collect()
+    }
+
+    /// Forget the bans that ran out
+    pub fn prune(&mut self, now: SystemTime) {
+        let now_ms = playback::unix_ms(now);
+        self.bans.retain(|ban| ban.is_active(now_ms));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_bans_match_and_expire() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let session = "a".repeat(32);
+        let ip: IpAddr = [192, 168, 1, 20].into();
+        let mut bans = BanList::default();
+        bans.ban(BanTarget::Ip(ip), "spam", None, now).unwrap();
+        let temp = BanTarget::parse(None, Some(&session)).unwrap();
+        let ban = bans.ban(temp.clone(), "", Some(Duration::from_secs(60)), now).unwrap();
+        assert_eq!(ban.expires_at_ms, Some(1_060_000));
+
+        assert!(bans.find(None, Some(ip), now).is_some());
+        assert!(bans.find(Some(&session), None, now).is_some());
+        assert!(bans.find_client(&ClientId::Session(session.clone()), None, now).is_some());
+        // A session of its own doesn't get a banned address through
+        let other = ClientId::Session("b".repeat(32));
+        assert!(bans.find_client(&other, Some(ip), now).is_some());
+        assert!(bans.find_client(&other, None, now).is_none());
+        assert!(bans.find(Some(&"b".repeat(32)), Some([10, 0, 0, 1].into()), now).is_none());
+
+        // The temporary ban lapses, the permanent one stays until lifted
+        let later = now + Duration::from_secs(60);
+        assert!(bans.find(Some(&session), None, later).is_none());
+        assert_eq!(bans.list(later).len(), 1);
+        let saved = serde_json::to_string(&bans).unwrap();
+        let mut bans: BanList = serde_json::from_str(&saved).unwrap();
+        assert_eq!(bans.list(now).len(), 2);
+        assert_eq!(bans.lift(&BanTarget::Ip(ip)).unwrap().reason, "spam");
+        assert_eq!(bans.lift(&BanTarget::Ip(ip)), Err(BanError::NotFound));
+    }
+
+    #[test]
+    fn test_ban_targets_are_checked() {
+        assert_eq!(
+            BanTarget::parse(Some("10.0.0.1"), None),
+            Ok(BanTarget::Ip([10, 0, 0, 1].into()))
+        );
+        assert_eq!(BanTarget::parse(Some(" "), None), Err(BanError::NoTarget));
+        assert_eq!(BanTarget::parse(Some("10.0.0"), None), Err(BanError::InvalidTarget));
+        assert_eq!(BanTarget::parse(None, Some("short")), Err(BanError::InvalidTarget));
+        assert_eq!(
+            BanTarget::parse(Some("10.0.0.1"), Some(&"a".repeat(32))),
+            Err(BanError::InvalidTarget)
+        );
+        let mut bans = BanList::default();
+        let target = BanTarget::Ip([10, 0, 0, 1].into());
+        assert_eq!(
+            bans.ban(target, &"a".repeat(201), None, UNIX_EPOCH),
+            Err(BanError::ReasonTooLong)
+        );
+    }
+}
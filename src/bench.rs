@@ -0,0 +1,294 @@
+use crate::errors::AppError;
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+// Smallest valid PNG, pasted over and over
+const PIXEL: &[u8] = include_bytes!("../tests/fixtures/pixel.png");
+// How long WebSocket clients keep listening for the last broadcasts after the final upload
+const DRAIN_TIME: Duration = Duration::from_secs(2);
+
+/// Settings for `homies_gaming_backend bench`
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchOptions {
+    /// Base URL of a running instance
+    pub url: String,
+    pub uploads: usize,
+    /// Uploads in flight at once
+    pub concurrency: usize,
+    /// WebSocket clients listening for broadcasts
+    pub clients: usize,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            url: "http://127.0.0.1:3030".to_string(),
+            uploads: 100,
+            concurrency: 10,
+            clients: 20,
+        }
+    }
+}
+
+impl BenchOptions {
+    /// `--url`, `--uploads`, `--concurrency` and `--clients`, as `--flag value` or `--flag=value`
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, AppError> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), value.to_string()),
+                None => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| AppError::ConfigError(format!("{} needs a value", arg)))?;
+                    (arg, value)
+                }
+            };
+            let count = || {
+                value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|count| *count > 0)
+                    .ok_or_else(|| {
+                        AppError::ConfigError(format!("{} needs a positive number", flag))
+                    })
+            };
+            match flag.as_str() {
+                "--url" => options.url = value.trim_end_matches('/').to_string(),
+                "--uploads" => options.uploads = count()?,
+                "--concurrency" => options.concurrency = count()?,
+                "--clients" => options.clients = count()?,
+                _ => {
+                    return Err(AppError::ConfigError(format!(
+                        "Unknown bench option {}",
+                        flag
+                    )));
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    fn ws_url(&self) -> String {
+        match self.url.split_once("://") {
+            Some(("https", rest)) => format!("wss://{}/ws", rest),
+            Some((_, rest)) => format!("ws://{}/ws", rest),
+            None => format!("ws://{}/ws", self.url),
+        }
+    }
+}
+
+/// Latency distribution of a set of samples
+#[derive(Debug, Default, PartialEq)]
+pub struct Percentiles {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Percentiles {
+    pub fn from_samples(mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        let at = |p: f64| {
+            let index = ((samples.len() as f64 * p).ceil() as usize).saturating_sub(1);
+            samples.get(index).copied().unwrap_or_default()
+        };
+        Self {
+            p50: at(0.50),
+            p90: at(0.90),
+            p99: at(0.99),
+            max: samples.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// Outcome of a bench run
+#[derive(Debug)]
+pub struct BenchReport {
+    pub uploads: usize,
+    pub failed_uploads: usize,
+    pub elapsed: Duration,
+    pub upload_latency: Percentiles,
+    /// Time between the first and the last client receiving the same broadcast
+    pub fanout_lag: Percentiles,
+    /// Broadcasts each client missed compared to the best connected one, summed
+    pub missed_broadcasts: usize,
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let succeeded = self.uploads - self.failed_uploads;
+        writeln!(
+            f,
+            "{} uploads ({} failed) in {:.2?}, {:.1} uploads/s",
+            self.uploads,
+            self.failed_uploads,
+            self.elapsed,
+            succeeded as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+        )?;
+        let row = |f: &mut std::fmt::Formatter<'_>, name: &str, p: &Percentiles| {
+            writeln!(
+                f,
+                "{:<15} p50 {:>9.2?}  p90 {:>9.2?}  p99 {:>9.2?}  max {:>9.2?}",
+                name, p.p50, p.p90, p.p99, p.max
+            )
+        };
+        row(f, "upload latency", &self.upload_latency)?;
+        row(f, "fan-out lag", &self.fanout_lag)?;
+        write!(f, "missed broadcasts: {}", self.missed_broadcasts)
+    }
+}
+
+/// Hammer a running instance with pasted uploads while WebSocket clients count the broadcasts
+pub async fn run(options: &BenchOptions) -> Result<BenchReport, AppError> {
+    let bench_error = |e: &dyn std::fmt::Display| {
+        AppError::IoError(std::io::Error::other(format!("Bench failed: {}", e)))
+    };
+
+    // Connect every listener before the first upload so they all see the same broadcasts
+    let (done_tx, done_rx) = tokio::sync::watch::channel(false);
+    let mut listeners = Vec::new();
+    for _ in 0..options.clients {
+        let (socket, _) = tokio_tungstenite::connect_async(options.ws_url())
+            .await
+            .map_err(|e| bench_error(&e))?;
+        let mut done = done_rx.clone();
+        listeners.push(tokio::spawn(async move {
+            let (_, mut read) = socket.split();
+            let mut received = Vec::new();
+            loop {
+                tokio::select! {
+                    message = read.next() => match message {
+                        Some(Ok(message)) if message.is_text() => received.push(Instant::now()),
+                        Some(Ok(_)) => {}
+                        _ => break,
+                    },
+                    _ = done.changed() => break,
+                }
+            }
+            received
+        }));
+    }
+    tracing::info!("Connected {} WebSocket clients", options.clients);
+
+    let client = reqwest::Client::new();
+    let paste_url = format!("{}/paste", options.url);
+    let permits = Arc::new(Semaphore::new(options.concurrency));
+    let started = Instant::now();
+    let mut uploads = Vec::new();
+    for index in 0..options.uploads {
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| bench_error(&e))?;
+        let request = client
+            .post(&paste_url)
+            .query(&[
+                ("caption", format!("bench {}", index)),
+                ("duration", "1".into()),
+            ])
+            .header("content-type", "image/png")
+            .body(PIXEL);
+        uploads.push(tokio::spawn(async move {
+            let sent = Instant::now();
+            let ok = matches!(request.send().await, Ok(response) if response.status().is_success());
+            drop(permit);
+            (ok, sent.elapsed())
+        }));
+    }
+
+    let mut latencies = Vec::new();
+    let mut failed_uploads = 0;
+    for upload in uploads {
+        let (ok, latency) = upload.await.map_err(|e| bench_error(&e))?;
+        if !ok {
+            failed_uploads += 1;
+        }
+        latencies.push(latency);
+    }
+    let elapsed = started.elapsed();
+
+    tokio::time::sleep(DRAIN_TIME).await;
+    let _ = done_tx.send(true);
+
+    let mut received = Vec::new();
+    for listener in listeners {
+        received.push(listener.await.map_err(|e| bench_error(&e))?);
+    }
+    let (fanout_lag, missed_broadcasts) = fanout(&received);
+
+    Ok(BenchReport {
+        uploads: options.uploads,
+        failed_uploads,
+        elapsed,
+        upload_latency: Percentiles::from_samples(latencies),
+        fanout_lag,
+        missed_broadcasts,
+    })
+}
+
+// Broadcasts arrive in the same order everywhere, so the nth message of each client is the same one
+fn fanout(received: &[Vec<Instant>]) -> (Percentiles, usize) {
+    let most = received.iter().map(Vec::len).max().unwrap_or(0);
+    let missed = received.iter().map(|times| most - times.len()).sum();
+    let lags = (0..most)
+        .filter_map(|index| {
+            let times = received.iter().filter_map(|times| times.get(index));
+            let first = times.clone().min()?;
+            let last = times.max()?;
+            Some(last.duration_since(*first))
+        })
+        .collect();
+    (Percentiles::from_samples(lags), missed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_options_from_args() {
+        let args = [
+            "--url",
+            "http://tv.lan:3030/",
+            "--uploads=500",
+            "--clients",
+            "50",
+        ];
+        let options = BenchOptions::from_args(args.map(String::from)).unwrap();
+        assert_eq!(options.url, "http://tv.lan:3030");
+        assert_eq!(options.uploads, 500);
+        assert_eq!(options.clients, 50);
+        assert_eq!(options.concurrency, BenchOptions::default().concurrency);
+        assert_eq!(options.ws_url(), "ws://tv.lan:3030/ws");
+
+        assert!(BenchOptions::from_args(["--uploads=0"].map(String::from)).is_err());
+        assert!(BenchOptions::from_args(["--clients"].map(String::from)).is_err());
+        assert!(BenchOptions::from_args(["--fast"].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn test_percentiles_and_fanout() {
+        let samples = (1..=100).map(Duration::from_millis).collect();
+        let percentiles = Percentiles::from_samples(samples);
+        assert_eq!(percentiles.p50, Duration::from_millis(50));
+        assert_eq!(percentiles.p99, Duration::from_millis(99));
+        assert_eq!(percentiles.max, Duration::from_millis(100));
+        assert_eq!(
+            Percentiles::from_samples(Vec::new()),
+            Percentiles::default()
+        );
+
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let (lag, missed) = fanout(&[vec![ms(0), ms(10)], vec![ms(4), ms(30)], vec![ms(2)]]);
+        assert_eq!(lag.max, Duration::from_millis(20));
+        assert_eq!(lag.p50, Duration::from_millis(4));
+        assert_eq!(missed, 1);
+    }
+}
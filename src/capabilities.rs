@@ -0,0 +1,114 @@
+use crate::config::AppConfig;
+use crate::state::MediaType;
+use crate::video_processing::{HwAccel, VideoPlatform, VideoProcessor};
+use serde::{Deserialize, Serialize};
+
+/// Longest video downloaded from a video page
+pub const MAX_VIDEO_SECS: u64 = 10 * 60;
+
+const MB: u64 = 1024 * 1024;
+
+/// Largest uploads of each type, in MB
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct UploadLimits {
+    pub image_mb: u64,
+    pub video_mb: u64,
+    /// Sounds and music
+    pub sound_mb: u64,
+    /// All uploads being received at once, more wait with a 503
+    pub in_flight_mb: u64,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        Self {
+            image_mb: 100,
+            video_mb: 100,
+            sound_mb: 50,
+            in_flight_mb: 300,
+        }
+    }
+}
+
+impl UploadLimits {
+    pub fn bytes(&self, media_type: MediaType) -> u64 {
+        match media_type {
+            MediaType::Image => self.image_mb * MB,
+            MediaType::Video => self.video_mb * MB,
+        }
+    }
+
+    /// Largest image or video, all a form can be held to before its file is typed
+    pub fn media_bytes(&self) -> u64 {
+        self.image_mb.max(self.video_mb) * MB
+    }
+
+    pub fn sound_bytes(&self) -> u64 {
+        self.sound_mb * MB
+    }
+
+    pub fn in_flight_bytes(&self) -> u64 {
+        self.in_flight_mb * MB
+    }
+}
+
+/// What this server can do, probed once at startup so the upload form only offers what works
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Capabilities {
+    /// Video encoding, burned-in captions and sound trimming
+    pub ffmpeg: bool,
+    /// Downloads from video pages
+    pub ytdlp: bool,
+    pub hwaccel: HwAccel,
+    /// Largest image or video
+    pub max_upload_bytes: u64,
+    pub max_image_bytes: u64,
+    pub max_video_bytes: u64,
+    pub max_sound_bytes: u64,
+    pub max_video_secs: u64,
+    pub max_video_height: u32,
+    /// Video platforms enabled in the configuration, downloadable only with yt-dlp
+    pub platforms: Vec<&'static str>,
+}
+
+impl Capabilities {
+    /// Limits of the configuration, with no external tools until [`Capabilities::probe`]
+    pub fn new(config: &AppConfig, hw_accel: HwAccel) -> Self {
+        Self {
+            ffmpeg: false,
+            ytdlp: false,
+            hwaccel: hw_accel,
+            max_upload_bytes: config.upload_limits.media_bytes(),
+            max_image_bytes: config.upload_limits.bytes(MediaType::Image),
+            max_video_bytes: config.upload_limits.bytes(MediaType::Video),
+            max_sound_bytes: config.upload_limits.sound_bytes(),
+            max_video_secs: MAX_VIDEO_SECS,
+            max_video_height: config.max_download_height(),
+            platforms: config
+                .platforms
+                .enabled()
+                .into_iter()
+                .map(VideoPlatform::name)
+                .collect(),
+        }
+    }
+
+    /// Look for ffmpeg and yt-dlp, blocking while they run
+    pub fn probe(mut self) -> Self {
+        self.ffmpeg = VideoProcessor::is_ffmpeg_available();
+        self.ytdlp = VideoProcessor::is_ytdlp_available();
+        tracing::info!(
+            "Capabilities: ffmpeg {}, yt-dlp {}, hardware acceleration {:?}",
+            self.ffmpeg,
+            self.ytdlp,
+            self.hwaccel
+        );
+        self
+    }
+
+    /// Whether the video URL tab has anything to offer, downloads are re-encoded with ffmpeg
+    pub fn video_downloads(&self) -> bool {
+        self.ytdlp && self.ffmpeg && !self.platforms.is_empty()
+    }
+}
@@ -0,0 +1,197 @@
+use crate::session::ClientId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+const MAX_NAME_CHARS: usize = 20;
+
+/// Chat ticker scrolling across the bottom of the display
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ChatConfig {
+    /// Messages kept for `GET /chat/recent`
+    pub history: usize,
+    pub max_chars: usize,
+    /// Seconds a session waits between two messages
+    pub min_interval_secs: u64,
+    /// Masked with asterisks, matched case-insensitively as whole words
+    pub banned_words: Vec<String>,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            history: 50,
+            max_chars: 140,
+            min_interval_secs: 3,
+            banned_words: Vec::new(),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ChatError {
+    #[error("Message is empty")]
+    Empty,
+    #[error("Messages are limited to {0} characters")]
+    TooLong(usize),
+    #[error("Slow down, try again in {} seconds", .0.as_millis().div_ceil(1000))]
+    RateLimited(Duration),
+    #[error("Message has a word that isn't allowed here")]
+    Refused,
+    #[error("You're banned from chatting")]
+    Banned,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ChatMessage {
+    pub id: u64,
+    pub author: String,
+    pub text: String,
+    pub sent_at_ms: u64, // Unix milliseconds
+}
+
+/// Replace banned words with asterisks, leaving the rest of the text as typed
+pub fn mask_banned_words(text: &str, banned_words: &[String]) -> String {
+    text.split_inclusive(|c: char| !c.is_alphanumeric())
+        .map(|part| {
+            let word = part.trim_end_matches(|c: char| !c.is_alphanumeric());
+            let banned = !word.is_empty()
+                && banned_words
+                    .iter()
+                    .any(|banned| banned.trim().eq_ignore_ascii_case(word));
+            match banned {
+                true => "*".repeat(word.chars().count()) + &part[word.len()..],
+                false => part.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Recent ticker messages and when each session last posted
+#[derive(Clone, Debug)]
+pub struct Chat {
+    messages: VecDeque<ChatMessage>, // Oldest first
+    last_sent: HashMap<ClientId, SystemTime>,
+    next_id: u64,
+}
+
+impl Default for Chat {
+    fn default() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            last_sent: HashMap::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl Chat {
+    pub fn post(
+        &mut self,
+        sender: &ClientId,
+        author: &str,
+        text: &str,
+        now: SystemTime,
+        config: &ChatConfig,
+    ) -> Result<ChatMessage, ChatError> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(ChatError::Empty);
+        }
+        if text.chars().count() > config.max_chars {
+            return Err(ChatError::TooLong(config.max_chars));
+        }
+        let interval = Duration::from_secs(config.min_interval_secs);
+        if let Some(wait) = self
+            .last_sent
+            .get(sender)
+            .and_then(|last| (*last + interval).duration_since(now).ok())
+            .filter(|wait| !wait.is_zero())
+        {
+            return Err(ChatError::RateLimited(wait));
+        }
+        // Nobody past their interval needs remembering
+        self.last_sent.retain(|_, last| *last + interval > now);
+        self.last_sent.insert(sender.clone(), now);
+
+        let message = ChatMessage {
+            id: self.next_id,
+            author: author.trim().chars().take(MAX_NAME_CHARS).collect(),
+            text: mask_banned_words(text, &config.banned_words),
+            sent_at_ms: now
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        };
+        self.next_id += 1;
+        self.messages.push_back(message.clone());
+        while self.messages.len() > config.history {
+            self.messages.pop_front();
+        }
+        Ok(message)
+    }
+
+    pub fn recent(&self) -> &VecDeque<ChatMessage> {
+        &self.messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_banned_words() {
+        let banned = vec!["heck".to_string()];
+        assert_eq!(
+            mask_banned_words("What the HECK, heckler!", &banned),
+            "What the ****, heckler!"
+        );
+        assert_eq!(mask_banned_words("heck", &banned), "****");
+    }
+
+    #[test]
+    fn test_post_rate_limit_and_history() {
+        let config = ChatConfig {
+            history: 2,
+            ..ChatConfig::default()
+        };
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let alice = ClientId::Session("a".repeat(32));
+        let bob = ClientId::Ip([192, 168, 1, 20].into());
+        let mut chat = Chat::default();
+
+        chat.post(&alice, "Alice", "gg", now, &config).unwrap();
+        assert_eq!(
+            chat.post(
+                &alice,
+                "Alice",
+                "gg again",
+                now + Duration::from_secs(1),
+                &config
+            ),
+            Err(ChatError::RateLimited(Duration::from_secs(2)))
+        );
+        assert_eq!(
+            chat.post(&bob, "Bob", "  ", now, &config),
+            Err(ChatError::Empty)
+        );
+        assert_eq!(
+            chat.post(&bob, "Bob", &"a".repeat(141), now, &config),
+            Err(ChatError::TooLong(140))
+        );
+        chat.post(&bob, "Bob", "nice", now, &config).unwrap();
+        chat.post(&alice, "Alice", "ty", now + Duration::from_secs(3), &config)
+            .unwrap();
+
+        let texts: Vec<_> = chat
+            .recent()
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect();
+        assert_eq!(texts, ["nice", "ty"]);
+        assert_eq!(chat.recent()[1].id, 3);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::retry::RetryPolicy;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// How often the cleanup task runs and how hard it tries to get rid of a stubborn file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CleanupConfig {
+    pub interval_ms: u64,
+    /// Random extra delay per pass, so passes don't line up with other periodic work
+    pub jitter_ms: u64,
+    /// Failed attempts on one file before giving up on it and quarantining it
+    pub max_retries: u32,
+    /// Wait before the first retry, doubling after each failure
+    pub retry_backoff_secs: u64,
+    /// Bounds of the `expires_in` an upload can set in place of the default retention
+    pub expires_in_min_secs: u64,
+    pub expires_in_max_secs: u64,
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 1000,
+            jitter_ms: 200,
+            max_retries: 5,
+            retry_backoff_secs: 2,
+            expires_in_min_secs: 5,
+            expires_in_max_secs: 600,
+        }
+    }
+}
+
+impl CleanupConfig {
+    /// Delay until the next pass, jitter included
+    pub fn next_delay(&self) -> Duration {
+        let jitter = rand::thread_rng().gen_range(0..=self.jitter_ms);
+        Duration::from_millis(self.interval_ms.max(1) + jitter)
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::default()
+            .max_attempts(self.max_retries)
+            .initial_backoff(Duration::from_secs(self.retry_backoff_secs))
+    }
+
+    /// Retention an upload asked for, kept within the configured bounds
+    pub fn expires_in(&self, secs: u64) -> u64 {
+        secs.clamp(
+            self.expires_in_min_secs,
+            self.expires_in_max_secs.max(self.expires_in_min_secs),
+        )
+    }
+}
+
+/// What happens to an expired upload
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Disposal {
+    /// Moved to the trash, restorable until purged
+    Trash,
+    /// Removed for good, for burn after viewing uploads
+    Delete,
+}
+
+/// Outcome of a failed disposal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Failure {
+    RetryAt(SystemTime),
+    /// Out of retries, the file stays on disk for someone to look at
+    Quarantined,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct CleanupStats {
+    pub deleted: u64,
+    pub trashed: u64,
+    /// Failed attempts, retries included
+    pub failures: u64,
+    pub quarantined: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Pending {
+    disposal: Disposal,
+    attempts: u32,
+    retry_at: SystemTime,
+}
+
+/// Files the cleanup task failed to dispose of, and counters for the metrics
+#[derive(Clone, Debug, Default)]
+pub struct Cleanup {
+    policy: RetryPolicy,
+    pending: HashMap<String, Pending>,
+    quarantined: Vec<String>,
+    stats: CleanupStats,
+}
+
+impl Cleanup {
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn record_success(&mut self, filename: &str, disposal: Disposal) {
+        self.pending.remove(filename);
+        match disposal {
+            Disposal::Trash => self.stats.trashed += 1,
+            Disposal::Delete => self.stats.deleted += 1,
+        }
+    }
+
+    /// Stop retrying a file that turned out to be gone already
+    pub fn forget(&mut self, filename: &str) {
+        self.pending.remove(filename);
+    }
+
+    /// Schedule another attempt with backoff, or quarantine the file once out of retries
+    pub fn record_failure(
+        &mut self,
+        filename: &str,
+        disposal: Disposal,
+        now: SystemTime,
+    ) -> Failure {
+        self.stats.failures += 1;
+        let attempts = self.pending.get(filename).map_or(0, |p| p.attempts) + 1;
+        if attempts >= self.policy.max_attempts {
+            self.pending.remove(filename);
+            self.quarantined.push(filename.to_string());
+            self.stats.quarantined += 1;
+            return Failure::Quarantined;
+        }
+        let retry_at = now + self.policy.backoff(attempts);
+        self.pending.insert(
+            filename.to_string(),
+            Pending {
+                disposal,
+                attempts,
+                retry_at,
+            },
+        );
+        Failure::RetryAt(retry_at)
+    }
+
+    /// Files whose backoff is over, to be attempted again
+    pub fn due(&self, now: SystemTime) -> Vec<(String, Disposal)> {
+        self.pending
+            .iter()
+            .filter(|(_, pending)| pending.retry_at <= now)
+            .map(|(filename, pending)| (filename.clone(), pending.disposal))
+            .collect()
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Files given up on, still on disk
+    pub fn quarantined(&self) -> &[String] {
+        &self.quarantined
+    }
+
+    pub fn stats(&self) -> CleanupStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_failures_back_off_then_quarantine() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut cleanup = Cleanup::default();
+        cleanup.set_retry_policy(
+            CleanupConfig {
+                max_retries: 3,
+                ..CleanupConfig::default()
+            }
+            .retry_policy(),
+        );
+
+        assert_eq!(
+            cleanup.record_failure("a.png", Disposal::Trash, now),
+            Failure::RetryAt(now + Duration::from_secs(2))
+        );
+        assert!(cleanup.due(now + Duration::from_secs(1)).is_empty());
+        assert_eq!(
+            cleanup.due(now + Duration::from_secs(2)),
+            [("a.png".to_string(), Disposal::Trash)]
+        );
+        assert_eq!(
+            cleanup.record_failure("a.png", Disposal::Trash, now),
+            Failure::RetryAt(now + Duration::from_secs(4))
+        );
+        assert_eq!(
+            cleanup.record_failure("a.png", Disposal::Trash, now),
+            Failure::Quarantined
+        );
+        assert!(cleanup.due(now + Duration::from_secs(60)).is_empty());
+        assert_eq!(cleanup.quarantined(), ["a.png"]);
+
+        cleanup.record_failure("b.mp4", Disposal::Delete, now);
+        cleanup.record_success("b.mp4", Disposal::Delete);
+        assert!(cleanup.due(now + Duration::from_secs(60)).is_empty());
+        assert_eq!(
+            cleanup.stats(),
+            CleanupStats {
+                deleted: 1,
+                trashed: 0,
+                failures: 4,
+                quarantined: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_expires_in_is_bounded() {
+        let config = CleanupConfig::default();
+        assert_eq!(config.expires_in(1), 5);
+        assert_eq!(config.expires_in(120), 120);
+        assert_eq!(config.expires_in(86_400), 600);
+    }
+
+    #[test]
+    fn test_next_delay_stays_within_jitter() {
+        let config = CleanupConfig::default();
+        for _ in 0..20 {
+            let delay = config.next_delay();
+            assert!((Duration::from_millis(1000)..=Duration::from_millis(1200)).contains(&delay));
+        }
+    }
+}
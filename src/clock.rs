@@ -0,0 +1,55 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Source of the current time for retention, queue and quiet hours logic
+/// Swapped for a [`ManualClock`] in tests so they can fast-forward instead of sleeping
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The real wall clock
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock that only moves when told to, clones share the same time
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl ManualClock {
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now += by;
+    }
+
+    pub fn set(&self, time: SystemTime) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = time;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(SystemTime::now())
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,245 @@
+use crate::capabilities::UploadLimits;
+use crate::chat::ChatConfig;
+use crate::cleanup::CleanupConfig;
+use crate::dnd::QuietHours;
+use crate::errors::AppError;
+use crate::game_status::GameStatusConfig;
+use crate::highlights::HighlightSchedule;
+use crate::i18n::Locale;
+use crate::camera::CameraConfig;
+use crate::imagine::Imagine;
+use crate::ingest::IngestConfig;
+use crate::listen::{self, ListenAddr};
+use crate::mqtt::MqttConfig;
+use crate::notifier::NotifierConfig;
+use crate::now_playing::NowPlayingSource;
+use crate::grpc::GrpcConfig;
+use crate::mdns::MdnsConfig;
+use crate::presets::UploadPreset;
+use crate::share::ShareConfig;
+use crate::staging::StagingConfig;
+use crate::state::DisplayPolicy;
+use crate::stats::RecapSchedule;
+use crate::theme::Theme;
+use crate::transcription::AutoCaptions;
+use crate::translation::Translation;
+use crate::trash::TrashConfig;
+use crate::twitch::TwitchConfig;
+use crate::video_processing::{
+    Chromakey, Framing, HwAccelSetting, ImageConversion, PlatformPolicy, QualityProfile,
+    Watermark,
+};
+use crate::virus_scan::VirusScan;
+use crate::webhooks::WebhookConfig;
+use crate::word_filter::WordFilterConfig;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Server configuration, read from `config.toml` (or the file named by `HOMIES_CONFIG`)
+/// Every field has a default so the file is optional
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Addresses to serve on, TCP (IPv4 or IPv6) or `unix:` sockets; `--listen` flags override them
+    pub listen: Vec<ListenAddr>,
+    /// What happens to a new upload while something is still on screen
+    pub display_policy: DisplayPolicy,
+    /// How long an uploaded video keeps the screen busy when its length is unknown
+    pub video_busy_secs: u64,
+    /// Hardware acceleration for video encoding: "auto" probes the system once at startup
+    pub hwaccel: HwAccelSetting,
+    /// Encode quality used when an upload doesn't pick one
+    pub quality: QualityProfile,
+    /// PNG overlaid on processed videos, uploads can opt out
+    pub watermark: Option<Watermark>,
+    /// Background for the green screen of uploads that ask for it, the option is hidden without
+    pub chromakey: Option<Chromakey>,
+    /// How vertical videos fill the screen when the upload doesn't pick
+    pub framing: Framing,
+    /// Fonts for captions with emoji or RTL/complex scripts, in fallback order
+    pub caption_fonts: Vec<String>,
+    /// Encode a 480p copy of tall videos for displays connecting with `?bandwidth=low`
+    pub low_renditions: bool,
+    /// Videos at least this long are also segmented for HLS streaming, 0 to never segment
+    pub hls_min_secs: u64,
+    /// Download linked videos at up to 1080p instead of 720p
+    pub allow_1080p: bool,
+    /// Uploaded videos with a larger short side are scaled down so the display can decode them,
+    /// 0 to keep them as is
+    pub max_display_height: u32,
+    /// Converting large JPEG and PNG uploads to WebP or AVIF
+    pub image_conversion: ImageConversion,
+    /// Platforms video URLs may come from
+    pub platforms: PlatformPolicy,
+    /// Index of the media pinned to the archive, outside the served archive directory
+    pub archive_index: PathBuf,
+    /// Periodic reel of the clips pinned to the archive
+    pub highlights: HighlightSchedule,
+    /// Weekly stats recap shown on screen
+    pub recap: RecapSchedule,
+    /// ClamAV scan of uploads before they are shown
+    pub virus_scan: VirusScan,
+    /// Daily window during which uploads are queued instead of shown
+    pub quiet_hours: Option<QuietHours>,
+    /// Service translating captions for uploads that ask for it
+    pub translation: Option<Translation>,
+    /// Speech to text for videos uploaded with auto captions on
+    pub auto_captions: Option<AutoCaptions>,
+    /// Stable Diffusion server generating images from prompts on POST /imagine
+    pub imagine: Option<Imagine>,
+    /// Named sets of upload options, picked with the `preset` form field
+    pub presets: Vec<UploadPreset>,
+    /// House cameras POST /snapshot/{name} puts the current frame of on screen
+    pub cameras: Vec<CameraConfig>,
+    /// RTMP screen-share relay, streams are sent to displays as HLS in `live` events
+    pub ingest: Option<IngestConfig>,
+    /// Signed links to archived media on GET /share/{token}, off without a secret
+    pub share: Option<ShareConfig>,
+    /// Endpoint polled for the track playing on Spotify or YouTube Music
+    pub now_playing: Option<NowPlayingSource>,
+    /// Game servers polled for the who's online widget
+    pub game_status: GameStatusConfig,
+    /// Twitch channels announced on screen when they go live
+    pub twitch: Option<TwitchConfig>,
+    /// Endpoints receiving a signed JSON POST on media and job events
+    pub webhooks: Vec<WebhookConfig>,
+    /// Broker the same events are published to, taking soundboard and music commands back
+    pub mqtt: Option<MqttConfig>,
+    /// ntfy or Gotify push notifications on uploads and failures
+    pub notifier: Option<NotifierConfig>,
+    /// Broadcasts buffered for slow WebSocket clients, one further behind is told to resync
+    pub ws_broadcast_capacity: usize,
+    /// Read-only GraphQL API on /graphql in builds with the `graphql` feature, subscriptions
+    /// carry the WebSocket events
+    pub graphql: bool,
+    /// gRPC control API for LAN automation in builds with the `grpc` feature, off unless
+    /// configured
+    pub grpc: Option<GrpcConfig>,
+    /// mDNS advertisement as `_homies._tcp.local`, off unless configured
+    pub mdns: Option<MdnsConfig>,
+    /// How long polls stay open when started without a duration
+    pub poll_secs: u64,
+    /// Token admin endpoints require in the `x-admin-token` header, unset leaves them open
+    pub admin_token: Option<String>,
+    /// Append-only log of admin requests and uploads, read with GET /admin/audit
+    pub audit_log: PathBuf,
+    /// Salt of the names people go by in stats, the leaderboard and the audit log, random
+    /// for each run when unset
+    pub person_salt: Option<String>,
+    /// Where leaderboard scores are kept across restarts
+    pub leaderboard_file: PathBuf,
+    /// Where registered displays are kept across restarts
+    pub displays_file: PathBuf,
+    /// Where bans are kept across restarts
+    pub bans_file: PathBuf,
+    /// Where the pinned banner is kept across restarts
+    pub banner_file: PathBuf,
+    /// Largest image, video and sound uploads
+    pub upload_limits: UploadLimits,
+    /// Ticker messages: history kept, length and rate limits, banned words
+    pub chat: ChatConfig,
+    /// Words filtered out of captions, chat messages and banners, off unless configured
+    pub word_filter: Option<WordFilterConfig>,
+    /// Display theme at startup ("dark", "light" or "party"), admins can switch it live
+    pub theme: Theme,
+    /// Language of messages for browsers asking for none of the supported ones ("en", "fr")
+    pub locale: Locale,
+    /// How long expired uploads stay restorable in the trash directory
+    pub trash: TrashConfig,
+    /// How long uploads sent to POST /stage wait for POST /publish/{id}
+    pub staging: StagingConfig,
+    /// Cleanup pass interval and how failed deletions are retried
+    pub cleanup: CleanupConfig,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            listen: listen::default_listen(),
+            display_policy: DisplayPolicy::default(),
+            video_busy_secs: 60,
+            hwaccel: HwAccelSetting::default(),
+            quality: QualityProfile::default(),
+            watermark: None,
+            chromakey: None,
+            framing: Framing::default(),
+            caption_fonts: vec!["Impact".to_string(), "Noto Color Emoji".to_string()],
+            low_renditions: true,
+            hls_min_secs: 300,
+            allow_1080p: false,
+            max_display_height: 1080,
+            image_conversion: ImageConversion::default(),
+            platforms: PlatformPolicy::default(),
+            archive_index: PathBuf::from("archive.json"),
+            highlights: HighlightSchedule::default(),
+            recap: RecapSchedule::default(),
+            virus_scan: VirusScan::default(),
+            quiet_hours: None,
+            translation: None,
+            auto_captions: None,
+            imagine: None,
+            presets: Vec::new(),
+            cameras: Vec::new(),
+            ingest: None,
+            share: None,
+            now_playing: None,
+            game_status: GameStatusConfig::default(),
+            twitch: None,
+            webhooks: Vec::new(),
+            mqtt: None,
+            notifier: None,
+            ws_broadcast_capacity: 100,
+            graphql: false,
+            grpc: None,
+            mdns: None,
+            poll_secs: 60,
+            admin_token: None,
+            audit_log: PathBuf::from("audit.log"),
+            person_salt: None,
+            leaderboard_file: PathBuf::from("leaderboard.json"),
+            displays_file: PathBuf::from("displays.json"),
+            bans_file: PathBuf::from("bans.json"),
+            banner_file: PathBuf::from("banner.json"),
+            upload_limits: UploadLimits::default(),
+            chat: ChatConfig::default(),
+            word_filter: None,
+            theme: Theme::default(),
+            locale: Locale::default(),
+            trash: TrashConfig::default(),
+            staging: StagingConfig::default(),
+            cleanup: CleanupConfig::default(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Highest resolution fetched when downloading linked videos
+    pub fn max_download_height(&self) -> u32 {
+        if self.allow_1080p { 1080 } else { 720 }
+    }
+
+    /// Display resolution cap for uploaded videos, if any
+    pub fn display_max_height(&self) -> Option<u32> {
+        Some(self.max_display_height).filter(|height| *height > 0)
+    }
+
+    pub fn load() -> Result<Self, AppError> {
+        let path =
+            std::env::var("HOMIES_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                tracing::info!("Loading configuration from {}", path);
+                toml::from_str(&contents)
+                    .map_err(|e| AppError::ConfigError(format!("{}: {}", path, e)))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::info!("No configuration file at {}, using defaults", path);
+                Ok(Self::default())
+            }
+            Err(e) => Err(AppError::IoError(e)),
+        }
+    }
+}
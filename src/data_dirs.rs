@@ -0,0 +1,114 @@
+//! Directories media is kept in, relative to the working directory unless the builder is given
+//! others
+
+use std::path::Path;
+
+/// Where uploads, the trash, the archive and the sound and music libraries live on disk
+/// Served URLs don't change with them, `/uploads/...` is always the uploads directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataDirs {
+    /// Uploaded media, with its posters, previews, renditions, HLS and live streams
+    pub uploads: String,
+    /// Media the cleanup set aside, restorable until purged
+    pub trash: String,
+    /// Media pinned to the archive and highlight reels
+    pub archive: String,
+    /// Soundboard sounds
+    pub sounds: String,
+    /// Background music tracks
+    pub music: String,
+}
+
+impl Default for DataDirs {
+    fn default() -> Self {
+        Self {
+            uploads: "uploads".to_string(),
+            trash: "trash".to_string(),
+            archive: "archive".to_string(),
+            sounds: "sounds".to_string(),
+            music: "music".to_string(),
+        }
+    }
+}
+
+impl DataDirs {
+    /// `uploads`, `trash`, `archive`, `sounds` and `music` inside `root`, so apps in the same
+    /// process don't share files
+    pub fn under(root: &Path) -> Self {
+        let dir = |name: &str| root.join(name).to_string_lossy().into_owned();
+        Self {
+            uploads: dir("uploads"),
+            trash: dir("trash"),
+            archive: dir("archive"),
+            sounds: dir("sounds"),
+            music: dir("music"),
+        }
+    }
+
+    /// Path of an uploaded file
+    pub fn upload(&self, filename: &str) -> String {
+        format!("{}/{}", self.uploads, filename)
+    }
+
+    /// Path of a file in the trash
+    pub fn trashed(&self, filename: &str) -> String {
+        format!("{}/{}", self.trash, filename)
+    }
+
+    /// Path of a file in the archive
+    pub fn archived(&self, filename: &str) -> String {
+        format!("{}/{}", self.archive, filename)
+    }
+
+    /// Path of a soundboard sound
+    pub fn sound(&self, filename: &str) -> String {
+        format!("{}/{}", self.sounds, filename)
+    }
+
+    /// Path of a music track
+    pub fn track(&self, filename: &str) -> String {
+        format!("{}/{}", self.music, filename)
+    }
+
+    /// First frames of uploaded videos, shown by clients before playback starts
+    pub fn posters(&self) -> String {
+        format!("{}/posters", self.uploads)
+    }
+
+    /// Short animated previews of videos for the history
+    pub fn previews(&self) -> String {
+        format!("{}/previews", self.uploads)
+    }
+
+    /// Smaller copies of videos for clients on slow links
+    pub fn renditions(&self) -> String {
+        format!("{}/renditions", self.uploads)
+    }
+
+    /// HLS playlists of long videos, one directory per video named after it
+    pub fn hls(&self) -> String {
+        format!("{}/hls", self.uploads)
+    }
+
+    /// Segments and playlist of the live stream relay
+    pub fn live(&self) -> String {
+        format!("{}/live", self.uploads)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirs_under_root() {
+        let dirs = DataDirs::under(Path::new("/tmp/app"));
+        assert_eq!(dirs.upload("a.png"), "/tmp/app/uploads/a.png");
+        assert_eq!(dirs.trashed("a.png"), "/tmp/app/trash/a.png");
+        assert_eq!(dirs.archived("a.png"), "/tmp/app/archive/a.png");
+        assert_eq!(dirs.sound("a.mp3"), "/tmp/app/sounds/a.mp3");
+        assert_eq!(dirs.track("a.mp3"), "/tmp/app/music/a.mp3");
+        assert_eq!(dirs.hls(), "/tmp/app/uploads/hls");
+        assert_eq!(DataDirs::default().posters(), "uploads/posters");
+    }
+}
@@ -0,0 +1,289 @@
+use crate::capabilities::Capabilities;
+use crate::data_dirs::DataDirs;
+use crate::video_processing::{CAPTION_FONT, HwAccel};
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+
+const GPU_ENCODERS: [&str; 6] = [
+    "h264_nvenc",
+    "hevc_nvenc",
+    "h264_vaapi",
+    "hevc_vaapi",
+    "h264_qsv",
+    "hevc_qsv",
+];
+// Under this a few long clips fill the disk
+const LOW_DISK_BYTES: u64 = 1024 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    /// Works, with some features off
+    Warning,
+    /// Uploads will fail until fixed
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Startup self-check of the tools, fonts, directories, disk space and encoders the server uses
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Report {
+    /// The worst status of all checks
+    pub status: Status,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn new(checks: Vec<Check>) -> Self {
+        Self {
+            status: checks
+                .iter()
+                .map(|check| check.status)
+                .max()
+                .unwrap_or(Status::Ok),
+            checks,
+        }
+    }
+
+    /// Run every check, blocking while the tools run
+    pub fn run(capabilities: &Capabilities, data_dirs: &DataDirs) -> Self {
+        let mut checks = vec![
+            tool_check(
+                "ffmpeg",
+                capabilities.ffmpeg,
+                "videos aren't re-encoded or captioned",
+            ),
+            tool_check(
+                "ffprobe",
+                ffprobe_available(),
+                "media durations and sizes are unknown",
+            ),
+            tool_check("yt-dlp", capabilities.ytdlp, "video page downloads are off"),
+            tool_check(
+                "pango-view",
+                pango_view_available(),
+                "emoji and RTL captions are drawn without shaping",
+            ),
+            font_check(Path::new(CAPTION_FONT)),
+        ];
+        let dirs = [
+            &data_dirs.uploads,
+            &data_dirs.sounds,
+            &data_dirs.music,
+            &data_dirs.archive,
+            &data_dirs.trash,
+        ];
+        checks.extend(dirs.iter().map(|dir| writable_check(Path::new(dir))));
+        checks.push(disk_space_check(Path::new("."), capabilities.max_upload_bytes));
+        if capabilities.ffmpeg {
+            checks.push(encoder_check(capabilities.hwaccel, &ffmpeg_encoders()));
+        }
+        Self::new(checks)
+    }
+
+    /// One line per check, at the level of its status
+    pub fn log(&self) {
+        let count = |status| self.checks.iter().filter(|c| c.status == status).count();
+        tracing::info!(
+            "Diagnostics: {} ok, {} warnings, {} errors",
+            count(Status::Ok),
+            count(Status::Warning),
+            count(Status::Error)
+        );
+        for check in &self.checks {
+            match check.status {
+                Status::Ok => tracing::info!("Diagnostics {}: {}", check.name, check.detail),
+                Status::Warning => tracing::warn!("Diagnostics {}: {}", check.name, check.detail),
+                Status::Error => tracing::error!("Diagnostics {}: {}", check.name, check.detail),
+            }
+        }
+    }
+}
+
+fn tool_check(tool: &str, available: bool, missing: &str) -> Check {
+    match available {
+        true => Check::new(tool, Status::Ok, "Found"),
+        false => Check::new(tool, Status::Warning, format!("Not found, {missing}")),
+    }
+}
+
+fn ffprobe_available() -> bool {
+    Command::new("ffprobe")
+        .arg("-version")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+fn pango_view_available() -> bool {
+    Command::new("pango-view")
+        .arg("--version")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+fn font_check(font: &Path) -> Check {
+    match font.is_file() {
+        true => Check::new("caption font", Status::Ok, font.display().to_string()),
+        false => Check::new(
+            "caption font",
+            Status::Warning,
+            format!(
+                "{} missing, captions use ffmpeg's default font",
+                font.display()
+            ),
+        ),
+    }
+}
+
+// Create the directory if needed and write a scratch file to it
+fn writable_check(dir: &Path) -> Check {
+    let name = format!("{} directory", dir.display());
+    let scratch = dir.join(".write-check");
+    let written = std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&scratch, b"ok"))
+        .and_then(|_| std::fs::remove_file(&scratch));
+    match written {
+        Ok(()) => Check::new(name, Status::Ok, "Writable"),
+        Err(e) => Check::new(name, Status::Error, format!("Not writable: {e}")),
+    }
+}
+
+fn disk_space_check(dir: &Path, max_upload_bytes: u64) -> Check {
+    let available = Command::new("df")
+        .args(["-Pk"])
+        .arg(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| parse_df_available(&String::from_utf8_lossy(&output.stdout)));
+    let Some(available) = available else {
+        return Check::new("disk space", Status::Warning, "Couldn't run df");
+    };
+    let detail = format!("{} MB free", available / (1024 * 1024));
+    let status = if available < max_upload_bytes {
+        Status::Error
+    } else if available < LOW_DISK_BYTES {
+        Status::Warning
+    } else {
+        Status::Ok
+    };
+    Check::new("disk space", status, detail)
+}
+
+/// Bytes available from `df -Pk` output
+pub fn parse_df_available(output: &str) -> Option<u64> {
+    let kilobytes = output
+        .lines()
+        .nth(1)?
+        .split_whitespace()
+        .nth(3)?
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+fn ffmpeg_encoders() -> String {
+    Command::new("ffmpeg")
+        .args(["-hide_banner", "-encoders"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default()
+}
+
+/// GPU encoders listed in `ffmpeg -encoders` output
+pub fn parse_gpu_encoders(output: &str) -> Vec<&'static str> {
+    let listed: Vec<_> = output
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .collect();
+    GPU_ENCODERS
+        .into_iter()
+        .filter(|encoder| listed.contains(encoder))
+        .collect()
+}
+
+fn encoder_check(hw_accel: HwAccel, encoders: &str) -> Check {
+    let found = parse_gpu_encoders(encoders);
+    let codec = hw_accel.video_codec();
+    match hw_accel {
+        HwAccel::None if found.is_empty() => {
+            Check::new("gpu encoders", Status::Ok, "None, encoding on the CPU")
+        }
+        HwAccel::None => Check::new(
+            "gpu encoders",
+            Status::Ok,
+            format!("Encoding on the CPU, ffmpeg also has {}", found.join(", ")),
+        ),
+        _ if found.contains(&codec) => {
+            Check::new("gpu encoders", Status::Ok, format!("Encoding with {codec}"))
+        }
+        _ => Check::new(
+            "gpu encoders",
+            Status::Error,
+            format!("{codec} is selected but this ffmpeg doesn't have it"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENCODERS: &str = "Encoders:
+ V..... = Video
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC (codec h264)
+ V....D h264_nvenc           NVIDIA NVENC H.264 encoder (codec h264)
+ V....D h264_vaapi           H.264/AVC (VAAPI) (codec h264)
+";
+
+    #[test]
+    fn test_parse_df_available() {
+        let output = "Filesystem     1024-blocks      Used Available Capacity Mounted on
+/dev/sda1         41152736  20000000  19039872      52% /
+";
+        assert_eq!(parse_df_available(output), Some(19039872 * 1024));
+        assert_eq!(parse_df_available(""), None);
+    }
+
+    #[test]
+    fn test_encoder_check() {
+        assert_eq!(parse_gpu_encoders(ENCODERS), ["h264_nvenc", "h264_vaapi"]);
+        assert_eq!(encoder_check(HwAccel::Cuda, ENCODERS).status, Status::Ok);
+        assert_eq!(encoder_check(HwAccel::Cuda, "").status, Status::Error);
+        assert_eq!(
+            encoder_check(HwAccel::None, ENCODERS).detail,
+            "Encoding on the CPU, ffmpeg also has h264_nvenc, h264_vaapi"
+        );
+    }
+
+    #[test]
+    fn test_report_status_is_the_worst_check() {
+        let report = Report::new(vec![
+            Check::new("a", Status::Ok, ""),
+            Check::new("b", Status::Warning, ""),
+        ]);
+        assert_eq!(report.status, Status::Warning);
+        assert_eq!(Report::new(Vec::new()).status, Status::Ok);
+    }
+}
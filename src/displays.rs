@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+const MAX_ID_CHARS: usize = 32;
+const MAX_NAME_CHARS: usize = 64;
+
+/// A display that registered on /ws, remembered across restarts so admin tooling can refer to it
+/// as "kitchen-tv" instead of a connection
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Display {
+    pub id: String,
+    pub name: String,
+    pub registered_at_ms: u64, // Unix milliseconds
+    pub last_seen_ms: u64,
+}
+
+/// A display as listed by GET /displays
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DisplayStatus {
+    #[serde(flatten)]
+    pub display: Display,
+    pub connections: u32, // Open WebSockets, 0 when offline
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DisplayError {
+    #[error("Display ids are 1 to {MAX_ID_CHARS} lowercase letters, digits and dashes")]
+    InvalidId,
+    #[error("Display names can't be longer than {MAX_NAME_CHARS} characters")]
+    NameTooLong,
+    #[error("No display with this id")]
+    NotFound,
+}
+
+/// Registered displays, saved to disk, and how many connections each has open
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DisplayRegistry {
+    displays: HashMap<String, Display>,
+    #[serde(skip)]
+    connections: HashMap<String, u32>,
+}
+
+fn valid_id(id: &str) -> bool {
+    (1..=MAX_ID_CHARS).contains(&id.len())
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+fn valid_name(name: &str) -> Result<&str, DisplayError> {
+    let name = name.trim();
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(DisplayError::NameTooLong);
+    }
+    Ok(name)
+}
+
+impl DisplayRegistry {
+    /// Register the display `id` or refresh it, renaming it when `name` is given
+    /// An empty `id` registers a new display under the next free `display-<n>`
+    pub fn connect(
+        &mut self,
+        id: &str,
+        name: Option<&str>,
+        now_ms: u64,
+    ) -> Result<Display, DisplayError> {
+        let id = match id.trim().to_ascii_lowercase() {
+            id if id.is_empty() => (1..)
+                .map(|number| format!("display-{}", number))
+                .find(|id| !self.displays.contains_key(id))
+                .unwrap_or_default(),
+            id if valid_id(&id) => id,
+            _ => return Err(DisplayError::InvalidId),
+        };
+        let name = name.map(valid_name).transpose()?.filter(|name| !name.is_empty());
+        let display = self.displays.entry(id.clone()).or_insert_with(|| Display {
+            id: id.clone(),
+            name: id.clone(),
+            registered_at_ms: now_ms,
+            last_seen_ms: now_ms,
+        });
+        if let Some(name) = name {
+            display.name = name.to_string();
+        }
+        display.last_seen_ms = now_ms;
+        *self.connections.entry(id).or_default() += 1;
+        Ok(display.clone())
+    }
+
+    pub fn disconnect(&mut self, id: &str, now_ms: u64) {
+        if let Some(connections) = self.connections.get_mut(id) {
+            *connections = connections.saturating_sub(1);
+        }
+        if let Some(display) = self.displays.get_mut(id) {
+            display.last_seen_ms = now_ms;
+        }
+    }
+
+    pub fn rename(&mut self, id: &str, name: &str) -> Result<Display, DisplayError> {
+        let name = valid_name(name)?;
+        let display = self.displays.get_mut(id).ok_or(DisplayError::NotFound)?;
+        display.name = if name.is_empty() { id.to_string() } else { name.to_string() };
+        Ok(display.clone())
+    }
+
+    /// Drop a display, it registers again the next time it connects
+    pub fn forget(&mut self, id: &str) -> Option<Display> {
+        self.displays.remove(id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Display> {
+        self.displays.get(id)
+    }
+
+    /// By id
+    pub fn list(&self) -> Vec<DisplayStatus> {
+        let mut displays: Vec<_> = self
+            .displays
+            .values()
+            .map(|display| DisplayStatus {
+                display: display.clone(),
+                connections: self.connections.get(&display.id).copied().unwrap_or(0),
+            })
+            .collect();
+        displays.sort_by(|a, b| a.display.id.cmp(&b.display.id));
+        displays
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_displays_register_and_keep_their_id() {
+        let mut registry = DisplayRegistry::default();
+        let kitchen = registry.connect("Kitchen-TV", Some(" Kitchen TV "), 1_000).unwrap();
+        assert_eq!(kitchen.id, "kitchen-tv");
+        assert_eq!(kitchen.name, "Kitchen TV");
+        assert_eq!(registry.connect("", None, 2_000).unwrap().id, "display-1");
+        assert_eq!(registry.connect("", None, 2_000).unwrap().id, "display-2");
+
+        // Reconnecting keeps the name and registration time
+        let kitchen = registry.connect("kitchen-tv", None, 3_000).unwrap();
+        assert_eq!(kitchen.name, "Kitchen TV");
+        assert_eq!(kitchen.registered_at_ms, 1_000);
+        assert_eq!(kitchen.last_seen_ms, 3_000);
+        assert_eq!(registry.list()[2].connections, 2);
+        registry.disconnect("kitchen-tv", 4_000);
+        registry.disconnect("kitchen-tv", 4_000);
+        assert_eq!(registry.list()[2].connections, 0);
+
+        // Only the registrations are saved
+        let saved = serde_json::to_string(&registry).unwrap();
+        let registry: DisplayRegistry = serde_json::from_str(&saved).unwrap();
+        assert_eq!(registry.get("kitchen-tv").unwrap().last_seen_ms, 4_000);
+        assert_eq!(registry.list().len(), 3);
+    }
+
+    #[test]
+    fn test_display_ids_and_names_are_checked() {
+        let mut registry = DisplayRegistry::default();
+        assert_eq!(registry.connect("tv/../", None, 0), Err(DisplayError::InvalidId));
+        assert_eq!(registry.connect(&"a".repeat(33), None, 0), Err(DisplayError::InvalidId));
+        assert_eq!(
+            registry.connect("tv", Some(&"a".repeat(65)), 0),
+            Err(DisplayError::NameTooLong)
+        );
+        assert_eq!(registry.rename("tv", "Den"), Err(DisplayError::NotFound));
+        registry.connect("tv", None, 0).unwrap();
+        assert_eq!(registry.rename("tv", "Den").unwrap().name, "Den");
+        assert_eq!(registry.rename("tv", " ").unwrap().name, "tv");
+        assert!(registry.forget("tv").is_some());
+        assert!(registry.list().is_empty());
+    }
+}
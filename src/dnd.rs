@@ -0,0 +1,114 @@
+use chrono::NaiveTime;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::time::SystemTime;
+
+const TIME_FORMAT: &str = "%H:%M";
+
+/// Daily window, in server local time, during which uploads are held back from the screen
+/// May span midnight, e.g. 23:00 to 08:00
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuietHours {
+    #[serde(serialize_with = "serialize_time", deserialize_with = "deserialize_time")]
+    pub start: NaiveTime,
+    #[serde(serialize_with = "serialize_time", deserialize_with = "deserialize_time")]
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+fn serialize_time<S: Serializer>(time: &NaiveTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&time.format(TIME_FORMAT).to_string())
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(value.trim(), TIME_FORMAT)
+        .map_err(|_| serde::de::Error::custom(format!("invalid time {:?}, expected HH:MM", value)))
+}
+
+/// Admin override of the quiet hours schedule
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DndMode {
+    /// Follow the configured quiet hours
+    #[default]
+    Auto,
+    /// Quiet until switched back, whatever the time
+    On,
+    /// Show uploads even during quiet hours
+    Off,
+}
+
+/// Do not disturb state: the configured schedule and the admin override
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DndState {
+    pub quiet_hours: Option<QuietHours>,
+    pub mode: DndMode,
+}
+
+impl DndState {
+    pub fn is_quiet_at(&self, time: NaiveTime) -> bool {
+        match self.mode {
+            DndMode::Auto => self.quiet_hours.is_some_and(|hours| hours.contains(time)),
+            DndMode::On => true,
+            DndMode::Off => false,
+        }
+    }
+
+    pub fn is_quiet(&self, now: SystemTime) -> bool {
+        self.is_quiet_at(chrono::DateTime::<chrono::Local>::from(now).time())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, TIME_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours_contains() {
+        let overnight: QuietHours = toml::from_str("start = \"23:00\"\nend = \"08:00\"").unwrap();
+        assert!(overnight.contains(at("23:00")));
+        assert!(overnight.contains(at("03:30")));
+        assert!(!overnight.contains(at("08:00")));
+        assert!(!overnight.contains(at("12:00")));
+
+        let afternoon = QuietHours {
+            start: at("13:00"),
+            end: at("14:00"),
+        };
+        assert!(afternoon.contains(at("13:59")));
+        assert!(!afternoon.contains(at("22:00")));
+
+        assert!(toml::from_str::<QuietHours>("start = \"11pm\"\nend = \"08:00\"").is_err());
+    }
+
+    #[test]
+    fn test_dnd_mode_overrides_schedule() {
+        let mut dnd = DndState {
+            quiet_hours: Some(QuietHours {
+                start: at("23:00"),
+                end: at("08:00"),
+            }),
+            mode: DndMode::Auto,
+        };
+        assert!(dnd.is_quiet_at(at("02:00")));
+        assert!(!dnd.is_quiet_at(at("20:00")));
+
+        dnd.mode = DndMode::Off;
+        assert!(!dnd.is_quiet_at(at("02:00")));
+        dnd.mode = DndMode::On;
+        assert!(dnd.is_quiet_at(at("20:00")));
+    }
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use thiserror::Error;
+
+// Oldest strokes are dropped past this, a doodle doesn't need more
+const MAX_STROKES: usize = 2000;
+const MAX_POINTS: usize = 1000;
+const MAX_WIDTH: f64 = 50.0;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum StrokeError {
+    #[error("Colors must be #rrggbb")]
+    Color,
+    #[error("Stroke width must be 1 to {MAX_WIDTH}")]
+    Width,
+    #[error("Strokes need 1 to {MAX_POINTS} points between 0 and 1")]
+    Points,
+}
+
+/// One pen stroke, points normalized to the canvas so every screen size draws it the same
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stroke {
+    pub color: String,
+    pub width: f64,
+    pub points: Vec<[f64; 2]>,
+}
+
+impl Stroke {
+    pub fn validate(&self) -> Result<(), StrokeError> {
+        let color = self.color.strip_prefix('#').unwrap_or_default();
+        if color.len() != 6 || !color.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(StrokeError::Color);
+        }
+        if !(1.0..=MAX_WIDTH).contains(&self.width) {
+            return Err(StrokeError::Width);
+        }
+        let in_canvas = |[x, y]: &[f64; 2]| (0.0..=1.0).contains(x) && (0.0..=1.0).contains(y);
+        if self.points.is_empty()
+            || self.points.len() > MAX_POINTS
+            || !self.points.iter().all(in_canvas)
+        {
+            return Err(StrokeError::Points);
+        }
+        Ok(())
+    }
+}
+
+/// The shared doodle on the TV: every stroke so far for late joiners, and the ones not yet
+/// relayed to displays
+#[derive(Clone, Debug, Default)]
+pub struct Canvas {
+    strokes: VecDeque<Stroke>,
+    pending: Vec<Stroke>,
+}
+
+impl Canvas {
+    pub fn draw(&mut self, strokes: Vec<Stroke>) {
+        for stroke in strokes {
+            if self.strokes.len() >= MAX_STROKES {
+                self.strokes.pop_front();
+            }
+            self.strokes.push_back(stroke.clone());
+            self.pending.push(stroke);
+        }
+    }
+
+    pub fn strokes(&self) -> &VecDeque<Stroke> {
+        &self.strokes
+    }
+
+    /// Strokes drawn since the last call, sent out as one batch
+    pub fn take_pending(&mut self) -> Vec<Stroke> {
+        std::mem::take(&mut self.pending)
+    }
+
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(x: f64) -> Stroke {
+        Stroke {
+            color: "#ff8800".to_string(),
+            width: 4.0,
+            points: vec![[x, 0.5], [0.5, 0.5]],
+        }
+    }
+
+    #[test]
+    fn test_stroke_validation() {
+        assert_eq!(stroke(0.1).validate(), Ok(()));
+        assert_eq!(stroke(1.5).validate(), Err(StrokeError::Points));
+        let mut red = stroke(0.1);
+        red.color = "red".to_string();
+        assert_eq!(red.validate(), Err(StrokeError::Color));
+        let mut thick = stroke(0.1);
+        thick.width = 80.0;
+        assert_eq!(thick.validate(), Err(StrokeError::Width));
+    }
+
+    #[test]
+    fn test_canvas_batches() {
+        let mut canvas = Canvas::default();
+        canvas.draw(vec![stroke(0.1), stroke(0.2)]);
+        assert_eq!(canvas.take_pending().len(), 2);
+        assert!(canvas.take_pending().is_empty());
+        canvas.draw(vec![stroke(0.3)]);
+        assert_eq!(canvas.strokes().len(), 3);
+
+        canvas.clear();
+        assert!(canvas.strokes().is_empty());
+        assert!(canvas.take_pending().is_empty());
+    }
+}
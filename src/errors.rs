@@ -10,6 +10,8 @@ pub enum AppError {
     IoError(#[from] std::io::Error),
     #[error("Multipart error")]
     MultipartError,
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
 }
 
 impl Reject for AppError {}
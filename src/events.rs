@@ -0,0 +1,79 @@
+use crate::state::{MediaInfo, MediaType};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+/// Media as described in outgoing events
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EventMedia {
+    pub id: u64,
+    pub filename: String,
+    pub media_type: MediaType,
+    pub caption: String,
+}
+
+impl From<&MediaInfo> for EventMedia {
+    fn from(media: &MediaInfo) -> Self {
+        Self {
+            id: media.id,
+            filename: media.filename.clone(),
+            media_type: media.media_type,
+            caption: media.caption.clone(),
+        }
+    }
+}
+
+/// Why an upload went on screen differently from what was asked, machine readable
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningReason {
+    /// ffmpeg isn't installed, the caption and watermark were left out
+    FfmpegMissing,
+    /// Re-encoding failed, the upload is shown as it came
+    ProcessingFailed,
+    /// The caption font couldn't be used, ffmpeg's default font was
+    FallbackFont,
+    /// The GPU encoder failed, the video was encoded on the CPU
+    HwaccelFallback,
+}
+
+impl WarningReason {
+    /// Short explanation for the uploader's page
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::FfmpegMissing => "caption skipped: ffmpeg missing",
+            Self::ProcessingFailed => "caption skipped: processing failed",
+            Self::FallbackFont => "caption drawn with the default font",
+            Self::HwaccelFallback => "encoded on the CPU: hardware encoding failed",
+        }
+    }
+}
+
+/// Something that happened to the media, sent to webhooks, MQTT and push notifications
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    MediaUploaded { media: EventMedia },
+    MediaShown { media: EventMedia },
+    MediaDeleted { filename: String },
+    /// Captions or a watermark couldn't be burned in, the upload went on without them
+    ProcessingFailed { filename: String, error: String },
+    /// The upload went on without something it asked for, see [`WarningReason`]
+    ProcessingWarning { filename: String, reason: WarningReason },
+    JobFailed { job_id: u64, url: String },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MediaUploaded { .. } => "media_uploaded",
+            Self::MediaShown { .. } => "media_shown",
+            Self::MediaDeleted { .. } => "media_deleted",
+            Self::ProcessingFailed { .. } => "processing_failed",
+            Self::ProcessingWarning { .. } => "processing_warning",
+            Self::JobFailed { .. } => "job_failed",
+        }
+    }
+}
+
+/// Events are queued here and delivered by a background task, so nothing waits on receivers
+pub type EventSender = mpsc::UnboundedSender<Event>;
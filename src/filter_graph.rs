@@ -0,0 +1,635 @@
+use crate::video_processing::HwAccel;
+use serde::Deserialize;
+
+// Length of a caption fade, in seconds
+const FADE_SECS: f64 = 0.5;
+
+/// How a burned-in caption appears over the video
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptionAnimation {
+    #[default]
+    Static,
+    /// Fade in when it appears and out before the video ends
+    Fade,
+    /// Slowly pulsing opacity
+    Pulse,
+}
+
+impl CaptionAnimation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "static" | "" => Some(CaptionAnimation::Static),
+            "fade" => Some(CaptionAnimation::Fade),
+            "pulse" => Some(CaptionAnimation::Pulse),
+            _ => None,
+        }
+    }
+}
+
+/// Fun effect applied to an uploaded video before its caption is drawn
+/// Reversing buffers the whole clip in memory, fine for the short clips uploads are capped to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoEffect {
+    #[default]
+    None,
+    /// Played forward then backward
+    Boomerang,
+    Reverse,
+    /// Half speed
+    Slowmo,
+    /// Double speed
+    Speedup,
+}
+
+impl VideoEffect {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "none" | "" => Some(VideoEffect::None),
+            "boomerang" => Some(VideoEffect::Boomerang),
+            "reverse" => Some(VideoEffect::Reverse),
+            "slowmo" => Some(VideoEffect::Slowmo),
+            "speedup" => Some(VideoEffect::Speedup),
+            _ => None,
+        }
+    }
+
+    /// Length of a video of `secs` seconds once the effect is applied
+    pub fn duration(self, secs: f64) -> f64 {
+        match self {
+            VideoEffect::None | VideoEffect::Reverse => secs,
+            VideoEffect::Boomerang | VideoEffect::Slowmo => secs * 2.0,
+            VideoEffect::Speedup => secs / 2.0,
+        }
+    }
+
+    /// ffmpeg audio arguments keeping the sound in step with the effect
+    /// A boomerang's sound played backward is just noise, so it's dropped
+    pub fn audio_args(self) -> &'static [&'static str] {
+        match self {
+            VideoEffect::None => &["-c:a", "copy"],
+            VideoEffect::Boomerang => &["-an"],
+            VideoEffect::Reverse => &["-af", "areverse", "-c:a", "aac"],
+            VideoEffect::Slowmo => &["-af", "atempo=0.5", "-c:a", "aac"],
+            VideoEffect::Speedup => &["-af", "atempo=2.0", "-c:a", "aac"],
+        }
+    }
+}
+
+/// Text drawn by the `drawtext` filter
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawText {
+    pub text: String,
+    pub fontfile: Option<String>,
+    pub fontsize: u32,
+    pub fontcolor: String,
+    /// Position expressions, evaluated by ffmpeg
+    pub x: String,
+    pub y: String,
+    pub shadow_offset: u32,
+    pub line_spacing: u32,
+    /// Opacity expression, evaluated per frame
+    pub alpha: Option<String>,
+    /// Timeline expression, the text is only drawn while it is non-zero
+    pub enable: Option<String>,
+}
+
+impl DrawText {
+    /// White caption centered at the bottom of the frame with a drop shadow
+    pub fn caption(text: &str, fontsize: u32) -> Self {
+        Self {
+            text: text.to_string(),
+            fontfile: None,
+            fontsize,
+            fontcolor: "white".to_string(),
+            x: "(w-text_w)/2".to_string(),
+            y: format!("h-text_h-{}", fontsize + 20), // Font size + some padding
+            shadow_offset: (fontsize as f32 * 0.04).max(1.0) as u32, // 4% of font size, minimum 1px
+            line_spacing: 5,
+            alpha: None,
+            enable: None,
+        }
+    }
+
+    /// Show the text `delay_secs` into the video, animated as asked
+    /// The fade out needs the video's length and is skipped when it is unknown
+    pub fn animate(
+        mut self,
+        animation: CaptionAnimation,
+        delay_secs: f64,
+        duration_secs: Option<f64>,
+    ) -> Self {
+        let delay = delay_secs.max(0.0);
+        match animation {
+            CaptionAnimation::Static | CaptionAnimation::Pulse if delay > 0.0 => {
+                self.enable = Some(format!("gte(t,{})", delay));
+            }
+            _ => {}
+        }
+        match animation {
+            CaptionAnimation::Static => {}
+            CaptionAnimation::Fade => {
+                let fade_out = match duration_secs {
+                    Some(end) if end > delay + 2.0 * FADE_SECS => {
+                        format!("if(gt(t,{end}-{f}),max(0,({end}-t)/{f}),1)", end = end, f = FADE_SECS)
+                    }
+                    _ => "1".to_string(),
+                };
+                self.alpha = Some(format!(
+                    "if(lt(t,{d}),0,if(lt(t,{d}+{f}),(t-{d})/{f},{out}))",
+                    d = delay,
+                    f = FADE_SECS,
+                    out = fade_out
+                ));
+            }
+            CaptionAnimation::Pulse => {
+                self.alpha = Some("0.65+0.35*sin(2*PI*t)".to_string());
+            }
+        }
+        self
+    }
+
+    pub fn fontfile(mut self, path: &str) -> Self {
+        self.fontfile = Some(path.to_string());
+        self
+    }
+
+    fn to_filter(&self) -> String {
+        let mut filter = format!("drawtext=text='{}'", escape_ffmpeg_text(&self.text));
+        if let Some(fontfile) = &self.fontfile {
+            filter.push_str(&format!(":fontfile='{}'", escape_ffmpeg_text(fontfile)));
+        }
+        filter.push_str(&format!(
+            ":fontsize={}:fontcolor={}:x={}:y={}:shadowcolor=black:shadowx={}:shadowy={}:line_spacing={}",
+            self.fontsize,
+            self.fontcolor,
+            self.x,
+            self.y,
+            self.shadow_offset,
+            self.shadow_offset,
+            self.line_spacing
+        ));
+        // Quoted so the commas in expressions don't split the filter chain
+        if let Some(alpha) = &self.alpha {
+            filter.push_str(&format!(":alpha='{}'", alpha));
+        }
+        if let Some(enable) = &self.enable {
+            filter.push_str(&format!(":enable='{}'", enable));
+        }
+        filter
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Filter(String),
+    /// Overlay another input (an image file) on top of the video so far
+    Overlay {
+        input: String,
+        x: String,
+        y: String,
+        /// Timeline expression, the image is only shown while it is non-zero
+        enable: Option<String>,
+    },
+    /// Show the video so far on top of another input (image or video), looped and stretched to
+    /// its size, which shows through the transparent parts
+    Background { input: String },
+}
+
+/// Builds the video filters for an ffmpeg invocation
+/// Filters apply in the order they are added; overlays pull in extra inputs and turn the chain into a -filter_complex graph
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FilterGraph {
+    steps: Vec<Step>,
+}
+
+impl FilterGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scale to the given size, -1/-2 keep the aspect ratio (-2 also keeps it even)
+    pub fn scale(mut self, width: i32, height: i32) -> Self {
+        self.steps
+            .push(Step::Filter(format!("scale={}:{}", width, height)));
+        self
+    }
+
+    /// Scale to fit inside the given size and pad the rest with black, for joining clips of any shape
+    pub fn fit(mut self, width: u32, height: u32) -> Self {
+        self.steps.push(Step::Filter(format!(
+            "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1",
+            w = width,
+            h = height
+        )));
+        self
+    }
+
+    /// Fill a `width`x`height` frame with the video scaled to fit, over a blurred copy of itself
+    /// stretched to cover the whole frame
+    pub fn blur_fill(mut self, width: u32, height: u32) -> Self {
+        self.steps.push(Step::Filter(format!(
+            "split[sharp][backdrop];\
+             [backdrop]scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h},\
+             boxblur=20:5[blurred];\
+             [sharp]scale=-2:{h}[fitted];[blurred][fitted]overlay=(W-w)/2:(H-h)/2",
+            w = width,
+            h = height
+        )));
+        self
+    }
+
+    /// Keep the `width`x`height` window whose top left corner is at `x`, `y`
+    pub fn crop(mut self, width: u32, height: u32, x: u32, y: u32) -> Self {
+        self.steps.push(Step::Filter(format!(
+            "crop={}:{}:{}:{}",
+            width, height, x, y
+        )));
+        self
+    }
+
+    pub fn fps(mut self, rate: u32) -> Self {
+        self.steps.push(Step::Filter(format!("fps={}", rate)));
+        self
+    }
+
+    /// Replay, reverse or retime the video so far
+    pub fn effect(mut self, effect: VideoEffect) -> Self {
+        let filter = match effect {
+            VideoEffect::None => return self,
+            // Labels stay inside this step, the chain goes on from the concat output
+            VideoEffect::Boomerang => {
+                "split[fwd][bwd];[bwd]reverse[rev];[fwd][rev]concat=n=2:v=1:a=0"
+            }
+            VideoEffect::Reverse => "reverse",
+            VideoEffect::Slowmo => "setpts=2.0*PTS",
+            VideoEffect::Speedup => "setpts=0.5*PTS",
+        };
+        self.steps.push(Step::Filter(filter.to_string()));
+        self
+    }
+
+    /// Make the pixels close to `color` transparent, `blend` softens the edges
+    pub fn chromakey(mut self, color: &str, similarity: f32, blend: f32) -> Self {
+        self.steps.push(Step::Filter(format!(
+            "chromakey=color={}:similarity={}:blend={}",
+            color, similarity, blend
+        )));
+        self
+    }
+
+    /// Put the video so far on top of an image or video file, ending with the video
+    pub fn background(mut self, input: &str) -> Self {
+        self.steps.push(Step::Background {
+            input: input.to_string(),
+        });
+        self
+    }
+
+    /// Move frames to the GPU for the hardware encoder, after every software filter
+    pub fn hwupload(mut self, hw_accel: HwAccel) -> Self {
+        match hw_accel {
+            HwAccel::None => {}
+            HwAccel::Cuda => self.steps.push(Step::Filter("hwupload_cuda".to_string())),
+            HwAccel::Vaapi => self.steps.push(Step::Filter("format=nv12,hwupload".to_string())),
+        }
+        self
+    }
+
+    /// Burn in the captions of an SRT file, must run on frames in system memory
+    pub fn subtitles(mut self, path: &str) -> Self {
+        self.steps.push(Step::Filter(format!(
+            "subtitles=filename='{}'",
+            escape_ffmpeg_text(path)
+        )));
+        self
+    }
+
+    pub fn drawtext(mut self, text: &DrawText) -> Self {
+        self.steps.push(Step::Filter(text.to_filter()));
+        self
+    }
+
+    /// Overlay an image file at the given position expressions
+    pub fn overlay(mut self, input: &str, x: &str, y: &str) -> Self {
+        self.steps.push(Step::Overlay {
+            input: input.to_string(),
+            x: x.to_string(),
+            y: y.to_string(),
+            enable: None,
+        });
+        self
+    }
+
+    /// Overlay an image file only while the timeline expression is non-zero
+    pub fn overlay_while(mut self, input: &str, x: &str, y: &str, enable: &str) -> Self {
+        self.steps.push(Step::Overlay {
+            input: input.to_string(),
+            x: x.to_string(),
+            y: y.to_string(),
+            enable: Some(enable.to_string()),
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Serialize to ffmpeg arguments, to be placed right after the main input
+    /// Overlay and background inputs are numbered from 1, so the main input must be the only one
+    /// before them
+    pub fn to_args(&self) -> Vec<String> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        let mut inputs = Vec::new();
+        let mut segments = Vec::new();
+        let mut head = "[0:v]".to_string();
+        let mut chain: Vec<String> = Vec::new();
+
+        for step in &self.steps {
+            match step {
+                Step::Filter(filter) => chain.push(filter.clone()),
+                Step::Overlay { input, x, y, enable } => {
+                    // Close the current chain so its output can feed the overlay
+                    if !chain.is_empty() {
+                        let label = format!("[v{}]", segments.len() + 1);
+                        segments.push(format!("{}{}{}", head, chain.join(","), label));
+                        head = label;
+                        chain.clear();
+                    }
+                    inputs.push(vec!["-i".to_string(), input.clone()]);
+                    head = format!("{}[{}:v]", head, inputs.len());
+                    chain.push(match enable {
+                        Some(enable) => format!("overlay=x={}:y={}:enable='{}'", x, y, enable),
+                        None => format!("overlay=x={}:y={}", x, y),
+                    });
+                }
+                Step::Background { input } => {
+                    let label = format!("[v{}]", segments.len() + 1);
+                    let filters = match chain.is_empty() {
+                        true => "null".to_string(),
+                        false => chain.join(","),
+                    };
+                    segments.push(format!("{}{}{}", head, filters, label));
+                    chain.clear();
+                    inputs.push(background_input(input));
+                    // Stretched to the video's size, which then goes on top of it
+                    let index = inputs.len();
+                    let (background, video) = (format!("[bg{}]", index), format!("[fg{}]", index));
+                    segments.push(format!(
+                        "[{}:v]{}scale2ref{}{}",
+                        index, label, background, video
+                    ));
+                    head = format!("{}{}", background, video);
+                    chain.push("overlay=shortest=1".to_string());
+                }
+            }
+        }
+
+        if inputs.is_empty() {
+            return vec!["-vf".to_string(), chain.join(",")];
+        }
+
+        segments.push(format!("{}{}", head, chain.join(",")));
+        let mut args: Vec<String> = inputs.into_iter().flatten().collect();
+        args.push("-filter_complex".to_string());
+        args.push(segments.join(";"));
+        args
+    }
+}
+
+// Input arguments looping a background forever, its sound left out of the output
+fn background_input(path: &str) -> Vec<String> {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let looped = match extension.as_str() {
+        "png" | "jpg" | "jpeg" | "webp" | "bmp" => ["-loop", "1"],
+        _ => ["-stream_loop", "-1"],
+    };
+    ["-an"]
+        .into_iter()
+        .chain(looped)
+        .chain(["-i", path])
+        .map(String::from)
+        .collect()
+}
+
+/// Escape text for use inside a quoted filter option value
+pub fn escape_ffmpeg_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace(':', "\\:")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_ffmpeg_text() {
+        assert_eq!(escape_ffmpeg_text("Hello World"), "Hello World");
+        assert_eq!(escape_ffmpeg_text("Hello: World"), "Hello\\: World");
+        assert_eq!(escape_ffmpeg_text("Hello [World]"), "Hello \\[World\\]");
+        assert_eq!(
+            escape_ffmpeg_text("Hello, World; Test"),
+            "Hello\\, World\\; Test"
+        );
+    }
+
+    #[test]
+    fn test_drawtext_escaping() {
+        let text = DrawText::caption("it's 10:30, [live]", 40).fontfile("/fonts/a:b.ttf");
+        assert_eq!(
+            FilterGraph::new().drawtext(&text).to_args(),
+            vec![
+                "-vf",
+                "drawtext=text='it\\'s 10\\:30\\, \\[live\\]':fontfile='/fonts/a\\:b.ttf':fontsize=40:fontcolor=white:x=(w-text_w)/2:y=h-text_h-60:shadowcolor=black:shadowx=1:shadowy=1:line_spacing=5",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_caption_animation() {
+        let filter = |text: DrawText| FilterGraph::new().drawtext(&text).to_args()[1].clone();
+
+        let still = filter(DrawText::caption("hi", 40).animate(CaptionAnimation::Static, 0.0, None));
+        assert!(!still.contains("alpha") && !still.contains("enable"));
+
+        let delayed = filter(DrawText::caption("hi", 40).animate(CaptionAnimation::Static, 3.0, None));
+        assert!(delayed.ends_with(":enable='gte(t,3)'"));
+
+        let fade = filter(DrawText::caption("hi", 40).animate(CaptionAnimation::Fade, 1.0, Some(10.0)));
+        assert!(fade.ends_with(
+            ":alpha='if(lt(t,1),0,if(lt(t,1+0.5),(t-1)/0.5,if(gt(t,10-0.5),max(0,(10-t)/0.5),1)))'"
+        ));
+        // No fade out when the length is unknown
+        let fade_in = filter(DrawText::caption("hi", 40).animate(CaptionAnimation::Fade, 0.0, None));
+        assert!(fade_in.ends_with(":alpha='if(lt(t,0),0,if(lt(t,0+0.5),(t-0)/0.5,1))'"));
+
+        let pulse = filter(DrawText::caption("hi", 40).animate(CaptionAnimation::Pulse, 2.0, None));
+        assert!(pulse.ends_with(":alpha='0.65+0.35*sin(2*PI*t)':enable='gte(t,2)'"));
+
+        assert_eq!(CaptionAnimation::from_name(" Fade"), Some(CaptionAnimation::Fade));
+        assert_eq!(CaptionAnimation::from_name("spin"), None);
+    }
+
+    #[test]
+    fn test_filter_order() {
+        assert!(FilterGraph::new().to_args().is_empty());
+        assert!(FilterGraph::new().hwupload(HwAccel::None).is_empty());
+
+        let graph = FilterGraph::new()
+            .scale(-2, 720)
+            .drawtext(&DrawText::caption("hi", 25))
+            .hwupload(HwAccel::Vaapi);
+        let args = graph.to_args();
+        assert_eq!(args[0], "-vf");
+        assert!(args[1].starts_with("scale=-2:720,drawtext=text='hi'"));
+        assert!(args[1].ends_with(",format=nv12,hwupload"));
+    }
+
+    #[test]
+    fn test_subtitles_graph() {
+        assert_eq!(
+            FilterGraph::new()
+                .scale(-2, 720)
+                .subtitles("uploads/clip.mp4.srt")
+                .hwupload(HwAccel::Cuda)
+                .to_args(),
+            vec!["-vf", "scale=-2:720,subtitles=filename='uploads/clip.mp4.srt',hwupload_cuda"]
+        );
+    }
+
+    #[test]
+    fn test_effect_graph() {
+        assert!(FilterGraph::new().effect(VideoEffect::None).is_empty());
+        assert_eq!(
+            FilterGraph::new()
+                .effect(VideoEffect::Boomerang)
+                .drawtext(&DrawText::caption("hi", 25))
+                .to_args()[1],
+            "split[fwd][bwd];[bwd]reverse[rev];[fwd][rev]concat=n=2:v=1:a=0,\
+             drawtext=text='hi':fontsize=25:fontcolor=white:x=(w-text_w)/2:y=h-text_h-45:\
+             shadowcolor=black:shadowx=1:shadowy=1:line_spacing=5"
+        );
+        assert_eq!(VideoEffect::from_name(" SlowMo "), Some(VideoEffect::Slowmo));
+        assert_eq!(VideoEffect::from_name("wobble"), None);
+        assert_eq!(VideoEffect::Boomerang.duration(3.0), 6.0);
+        assert_eq!(VideoEffect::Speedup.duration(3.0), 1.5);
+    }
+
+    #[test]
+    fn test_framing_graph() {
+        assert_eq!(
+            FilterGraph::new()
+                .crop(1080, 606, 0, 400)
+                .scale(1280, 720)
+                .to_args(),
+            vec!["-vf", "crop=1080:606:0:400,scale=1280:720"]
+        );
+        let args = FilterGraph::new()
+            .blur_fill(1280, 720)
+            .drawtext(&DrawText::caption("hi", 25))
+            .to_args();
+        assert!(args[1].starts_with(
+            "split[sharp][backdrop];\
+             [backdrop]scale=1280:720:force_original_aspect_ratio=increase,crop=1280:720,"
+        ));
+        assert!(args[1].contains("[blurred][fitted]overlay=(W-w)/2:(H-h)/2,drawtext="));
+    }
+
+    #[test]
+    fn test_fit_graph() {
+        assert_eq!(
+            FilterGraph::new().fit(1280, 720).fps(30).to_args(),
+            vec![
+                "-vf",
+                "scale=1280:720:force_original_aspect_ratio=decrease,pad=1280:720:(ow-iw)/2:(oh-ih)/2,setsar=1,fps=30",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlay_graph() {
+        let graph = FilterGraph::new()
+            .scale(-2, 720)
+            .overlay("watermark.png", "W-w-10", "10");
+        assert_eq!(
+            graph.to_args(),
+            vec![
+                "-i",
+                "watermark.png",
+                "-filter_complex",
+                "[0:v]scale=-2:720[v1];[v1][1:v]overlay=x=W-w-10:y=10",
+            ]
+        );
+
+        // Filters after an overlay apply to the combined picture, later overlays get the next input
+        let graph = FilterGraph::new()
+            .overlay("a.png", "0", "0")
+            .scale(640, 360)
+            .overlay("b.png", "5", "5");
+        assert_eq!(
+            graph.to_args(),
+            vec![
+                "-i",
+                "a.png",
+                "-i",
+                "b.png",
+                "-filter_complex",
+                "[0:v][1:v]overlay=x=0:y=0,scale=640:360[v1];[v1][2:v]overlay=x=5:y=5",
+            ]
+        );
+
+        let graph =
+            FilterGraph::new().overlay_while("caption.png", "(W-w)/2", "H-h-70", "gte(t,2)");
+        assert_eq!(
+            graph.to_args()[3],
+            "[0:v][1:v]overlay=x=(W-w)/2:y=H-h-70:enable='gte(t,2)'"
+        );
+    }
+
+    #[test]
+    fn test_background_graph() {
+        let graph = FilterGraph::new()
+            .chromakey("0x00FF00", 0.1, 0.2)
+            .background("beach.mp4")
+            .drawtext(&DrawText::caption("hi", 25))
+            .overlay("logo.png", "0", "0");
+        let args = graph.to_args();
+        assert_eq!(
+            args[..8],
+            ["-an", "-stream_loop", "-1", "-i", "beach.mp4", "-i", "logo.png", "-filter_complex"]
+        );
+        assert!(args[8].starts_with(
+            "[0:v]chromakey=color=0x00FF00:similarity=0.1:blend=0.2[v1];\
+             [1:v][v1]scale2ref[bg1][fg1];[bg1][fg1]overlay=shortest=1,drawtext=text='hi'"
+        ));
+        assert!(args[8].ends_with("[v3];[v3][2:v]overlay=x=0:y=0"));
+
+        // Images are looped as a single frame, a graph starting with the background keys nothing
+        assert_eq!(
+            FilterGraph::new().background("beach.jpg").to_args(),
+            vec![
+                "-an",
+                "-loop",
+                "1",
+                "-i",
+                "beach.jpg",
+                "-filter_complex",
+                "[0:v]null[v1];[1:v][v1]scale2ref[bg1][fg1];[bg1][fg1]overlay=shortest=1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,472 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+// Any status response is well under this, bigger ones are bogus
+const MAX_RESPONSE_BYTES: usize = 256 * 1024;
+// Protocol version sent in the Minecraft handshake, servers answer a status ping whatever it is
+const MINECRAFT_PROTOCOL: i32 = -1;
+const A2S_HEADER: [u8; 4] = [0xFF; 4];
+const A2S_INFO: u8 = 0x54;
+const A2S_PLAYER: u8 = 0x55;
+const A2S_CHALLENGE: u8 = 0x41;
+const A2S_INFO_REPLY: u8 = 0x49;
+const A2S_PLAYER_REPLY: u8 = 0x44;
+
+/// How a game server is asked who's online
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameQuery {
+    /// Minecraft Java server list ping, on the game port
+    Minecraft,
+    /// Steam A2S query (Valheim, Rust, CS, ...), usually on the game port + 1
+    A2s,
+    /// GET of a URL answering with a JSON status
+    Http,
+}
+
+/// A game server shown in the status widget
+#[derive(Clone, Debug, Deserialize)]
+pub struct GameServer {
+    pub name: String,
+    pub query: GameQuery,
+    /// `host:port`, or the status URL for HTTP queries
+    pub address: String,
+}
+
+/// Game servers polled for the status widget
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct GameStatusConfig {
+    pub every_secs: u64,
+    pub servers: Vec<GameServer>,
+}
+
+impl Default for GameStatusConfig {
+    fn default() -> Self {
+        Self {
+            every_secs: 30,
+            servers: Vec::new(),
+        }
+    }
+}
+
+impl GameStatusConfig {
+    pub fn every(&self) -> Duration {
+        Duration::from_secs(self.every_secs.max(5))
+    }
+}
+
+/// Last known state of a game server
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct GameStatus {
+    pub name: String,
+    pub online: bool,
+    pub players_online: u32,
+    pub players_max: u32,
+    /// Names of the players online, when the server shares them
+    pub players: Vec<String>,
+    /// Message of the day or map name
+    pub description: Option<String>,
+    /// Why the server couldn't be reached
+    pub error: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum GameQueryError {
+    #[error("Query failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Server didn't answer in time")]
+    Timeout,
+    #[error("Invalid response: {0}")]
+    InvalidResponse(&'static str),
+    #[error("Status request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Status URL returned HTTP {0}")]
+    Status(u16),
+}
+
+impl GameServer {
+    /// Query the server, an unreachable server is reported offline with the reason
+    pub async fn status(&self) -> GameStatus {
+        let result = tokio::time::timeout(QUERY_TIMEOUT, async {
+            match self.query {
+                GameQuery::Minecraft => query_minecraft(&self.address).await,
+                GameQuery::A2s => query_a2s(&self.address).await,
+                GameQuery::Http => query_http(&self.address).await,
+            }
+        })
+        .await
+        .unwrap_or(Err(GameQueryError::Timeout));
+
+        match result {
+            Ok(status) => GameStatus {
+                name: self.name.clone(),
+                online: true,
+                ..status
+            },
+            Err(e) => {
+                tracing::debug!("Game server {} is unreachable: {}", self.name, e);
+                GameStatus {
+                    name: self.name.clone(),
+                    error: Some(e.to_string()),
+                    ..GameStatus::default()
+                }
+            }
+        }
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            buf.push(value as u8);
+            return;
+        }
+        buf.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+fn read_varint(buf: &mut &[u8]) -> Result<i32, GameQueryError> {
+    let mut value = 0u32;
+    for position in 0..5 {
+        let (&byte, rest) = buf
+            .split_first()
+            .ok_or(GameQueryError::InvalidResponse("truncated varint"))?;
+        *buf = rest;
+        value |= ((byte & 0x7F) as u32) << (7 * position);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(GameQueryError::InvalidResponse("varint too long"))
+}
+
+async fn read_varint_from(stream: &mut (impl AsyncRead + Unpin)) -> Result<i32, GameQueryError> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = stream.read_u8().await?;
+        bytes.push(byte);
+        if byte & 0x80 == 0 || bytes.len() == 5 {
+            return read_varint(&mut bytes.as_slice());
+        }
+    }
+}
+
+fn minecraft_status_request(host: &str, port: u16) -> Vec<u8> {
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, 0x00);
+    write_varint(&mut handshake, MINECRAFT_PROTOCOL);
+    write_varint(&mut handshake, host.len() as i32);
+    handshake.extend_from_slice(host.as_bytes());
+    handshake.extend_from_slice(&port.to_be_bytes());
+    write_varint(&mut handshake, 1); // Next state: status
+
+    let mut request = Vec::new();
+    write_varint(&mut request, handshake.len() as i32);
+    request.extend_from_slice(&handshake);
+    // Status request: an empty packet with id 0
+    request.extend_from_slice(&[0x01, 0x00]);
+    request
+}
+
+// The description is either a plain string or a chat component with nested "extra" parts
+fn chat_text(component: &Value) -> String {
+    match component {
+        Value::String(text) => text.clone(),
+        Value::Object(object) => {
+            let mut text = object
+                .get("text")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            if let Some(Value::Array(extra)) = object.get("extra") {
+                for part in extra {
+                    text.push_str(&chat_text(part));
+                }
+            }
+            text
+        }
+        _ => String::new(),
+    }
+}
+
+fn parse_minecraft_status(json: &Value) -> GameStatus {
+    let players = &json["players"];
+    let description = chat_text(&json["description"]);
+    GameStatus {
+        players_online: players["online"].as_u64().unwrap_or_default() as u32,
+        players_max: players["max"].as_u64().unwrap_or_default() as u32,
+        players: players["sample"]
+            .as_array()
+            .map(|sample| {
+                sample
+                    .iter()
+                    .filter_map(|player| player["name"].as_str())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        description: Some(description.trim().to_string()).filter(|text| !text.is_empty()),
+        ..GameStatus::default()
+    }
+}
+
+async fn query_minecraft(address: &str) -> Result<GameStatus, GameQueryError> {
+    let (host, port) = address
+        .rsplit_once(':')
+        .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+        .unwrap_or((address, 25565));
+    let mut stream = TcpStream::connect((host, port)).await?;
+    stream
+        .write_all(&minecraft_status_request(host, port))
+        .await?;
+
+    let length = read_varint_from(&mut stream).await?;
+    let length = usize::try_from(length)
+        .ok()
+        .filter(|length| *length <= MAX_RESPONSE_BYTES)
+        .ok_or(GameQueryError::InvalidResponse("bad packet length"))?;
+    let mut packet = vec![0; length];
+    stream.read_exact(&mut packet).await?;
+
+    let mut packet = packet.as_slice();
+    if read_varint(&mut packet)? != 0x00 {
+        return Err(GameQueryError::InvalidResponse("unexpected packet"));
+    }
+    let json_length = read_varint(&mut packet)? as usize;
+    let json = packet
+        .get(..json_length)
+        .ok_or(GameQueryError::InvalidResponse("truncated status"))?;
+    let json: Value = serde_json::from_slice(json)
+        .map_err(|_| GameQueryError::InvalidResponse("status isn't JSON"))?;
+    Ok(parse_minecraft_status(&json))
+}
+
+// Reader over the little endian, NUL terminated fields of A2S replies
+struct A2sReader<'a>(&'a [u8]);
+
+impl A2sReader<'_> {
+    fn u8(&mut self) -> Result<u8, GameQueryError> {
+        let (&byte, rest) = self
+            .0
+            .split_first()
+            .ok_or(GameQueryError::InvalidResponse("truncated reply"))?;
+        self.0 = rest;
+        Ok(byte)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), GameQueryError> {
+        self.0 = self
+            .0
+            .get(count..)
+            .ok_or(GameQueryError::InvalidResponse("truncated reply"))?;
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<String, GameQueryError> {
+        let end = self
+            .0
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or(GameQueryError::InvalidResponse("unterminated string"))?;
+        let text = String::from_utf8_lossy(&self.0[..end]).to_string();
+        self.0 = &self.0[end + 1..];
+        Ok(text)
+    }
+}
+
+fn parse_a2s_info(reply: &[u8]) -> Result<GameStatus, GameQueryError> {
+    let mut reader = A2sReader(
+        reply
+            .strip_prefix(&A2S_HEADER)
+            .ok_or(GameQueryError::InvalidResponse("missing header"))?,
+    );
+    if reader.u8()? != A2S_INFO_REPLY {
+        return Err(GameQueryError::InvalidResponse("not an info reply"));
+    }
+    reader.u8()?; // Protocol
+    let name = reader.string()?;
+    let map = reader.string()?;
+    reader.string()?; // Folder
+    reader.string()?; // Game
+    reader.skip(2)?; // Steam app id
+    let players_online = reader.u8()? as u32;
+    let players_max = reader.u8()? as u32;
+    Ok(GameStatus {
+        players_online,
+        players_max,
+        description: Some(if map.is_empty() { name } else { map }),
+        ..GameStatus::default()
+    })
+}
+
+fn parse_a2s_players(reply: &[u8]) -> Result<Vec<String>, GameQueryError> {
+    let mut reader = A2sReader(
+        reply
+            .strip_prefix(&A2S_HEADER)
+            .ok_or(GameQueryError::InvalidResponse("missing header"))?,
+    );
+    if reader.u8()? != A2S_PLAYER_REPLY {
+        return Err(GameQueryError::InvalidResponse("not a player reply"));
+    }
+    let count = reader.u8()?;
+    let mut players = Vec::new();
+    for _ in 0..count {
+        reader.u8()?; // Index
+        let name = reader.string()?;
+        reader.skip(8)?; // Score and time connected
+        // Some games (Valheim) leave the names empty
+        if !name.is_empty() {
+            players.push(name);
+        }
+    }
+    Ok(players)
+}
+
+// Send an A2S request, answering the challenge newer servers reply with first
+async fn a2s_request(
+    socket: &UdpSocket,
+    request: &[u8],
+    challenge_placeholder: bool,
+) -> Result<Vec<u8>, GameQueryError> {
+    let mut reply = vec![0; 1400];
+    let mut packet = request.to_vec();
+    if challenge_placeholder {
+        packet.extend_from_slice(&[0xFF; 4]);
+    }
+    socket.send(&packet).await?;
+    let length = socket.recv(&mut reply).await?;
+    reply.truncate(length);
+
+    if reply.len() == 9 && reply.starts_with(&A2S_HEADER) && reply[4] == A2S_CHALLENGE {
+        let mut packet = request.to_vec();
+        packet.extend_from_slice(&reply[5..9]);
+        socket.send(&packet).await?;
+        let mut reply = vec![0; 1400];
+        let length = socket.recv(&mut reply).await?;
+        reply.truncate(length);
+        return Ok(reply);
+    }
+    Ok(reply)
+}
+
+async fn query_a2s(address: &str) -> Result<GameStatus, GameQueryError> {
+    let socket = UdpSocket::bind(if address.starts_with('[') {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    })
+    .await?;
+    socket.connect(address).await?;
+
+    let mut info = A2S_HEADER.to_vec();
+    info.push(A2S_INFO);
+    info.extend_from_slice(b"Source Engine Query\0");
+    let mut status = parse_a2s_info(&a2s_request(&socket, &info, false).await?)?;
+
+    let mut players = A2S_HEADER.to_vec();
+    players.push(A2S_PLAYER);
+    match a2s_request(&socket, &players, true).await {
+        Ok(reply) => status.players = parse_a2s_players(&reply).unwrap_or_default(),
+        Err(e) => tracing::debug!("No player list from {}: {}", address, e),
+    }
+    Ok(status)
+}
+
+// Same field names as GameStatus, for status pages built for this widget
+#[derive(Deserialize)]
+struct HttpStatus {
+    #[serde(default)]
+    players_online: u32,
+    #[serde(default)]
+    players_max: u32,
+    #[serde(default)]
+    players: Vec<String>,
+    description: Option<String>,
+}
+
+async fn query_http(url: &str) -> Result<GameStatus, GameQueryError> {
+    let client = reqwest::Client::builder().timeout(QUERY_TIMEOUT).build()?;
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(GameQueryError::Status(response.status().as_u16()));
+    }
+    let status: HttpStatus = serde_json::from_slice(&response.bytes().await?)
+        .map_err(|_| GameQueryError::InvalidResponse("status isn't the expected JSON"))?;
+    Ok(GameStatus {
+        players_online: status.players_online.max(status.players.len() as u32),
+        players_max: status.players_max,
+        players: status.players,
+        description: status.description,
+        ..GameStatus::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_varint_round_trip() {
+        for value in [0, 1, 127, 128, 25565, i32::MAX, -1] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value);
+            assert_eq!(read_varint(&mut buf.as_slice()).unwrap(), value);
+        }
+        let mut buf = Vec::new();
+        write_varint(&mut buf, -1);
+        assert_eq!(buf.len(), 5);
+        assert!(read_varint(&mut [0x80, 0x80].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_parse_minecraft_status() {
+        let status = parse_minecraft_status(&json!({
+            "version": { "name": "1.21", "protocol": 767 },
+            "players": {
+                "max": 20,
+                "online": 2,
+                "sample": [{ "name": "Steve", "id": "..." }, { "name": "Alex", "id": "..." }]
+            },
+            "description": { "text": "Homies ", "extra": [{ "text": "SMP" }] }
+        }));
+        assert_eq!(status.players_online, 2);
+        assert_eq!(status.players_max, 20);
+        assert_eq!(status.players, ["Steve", "Alex"]);
+        assert_eq!(status.description.as_deref(), Some("Homies SMP"));
+
+        let request = minecraft_status_request("mc.lan", 25565);
+        assert_eq!(request[0] as usize, request.len() - 3);
+        assert!(request.ends_with(&[0x63, 0xDD, 0x01, 0x01, 0x00]));
+    }
+
+    #[test]
+    fn test_parse_a2s_replies() {
+        let mut info = A2S_HEADER.to_vec();
+        info.extend_from_slice(&[A2S_INFO_REPLY, 17]);
+        info.extend_from_slice(b"Homies Valheim\0");
+        info.extend_from_slice(b"\0valheim\0Valheim\0");
+        info.extend_from_slice(&[0, 0, 3, 10, 0]);
+        let status = parse_a2s_info(&info).unwrap();
+        assert_eq!(status.players_online, 3);
+        assert_eq!(status.players_max, 10);
+        assert_eq!(status.description.as_deref(), Some("Homies Valheim"));
+        assert!(parse_a2s_info(&info[..12]).is_err());
+
+        let mut players = A2S_HEADER.to_vec();
+        players.extend_from_slice(&[A2S_PLAYER_REPLY, 2]);
+        players.extend_from_slice(b"\0viking\0");
+        players.extend_from_slice(&[0; 8]);
+        players.extend_from_slice(b"\x01\0");
+        players.extend_from_slice(&[0; 8]);
+        assert_eq!(parse_a2s_players(&players).unwrap(), ["viking"]);
+    }
+}
@@ -0,0 +1,308 @@
+use crate::jobs::{Job as JobEntry, SharedJobs};
+use crate::playback;
+use crate::state::SoundInfo;
+use crate::state_actor::StateHandle;
+use crate::stats::StatsReport;
+use crate::types::{JobStatus, MediaItem};
+use crate::websocket::{self, WsClients};
+use async_graphql::{
+    Context, EmptyMutation, Enum, Json, Object, Result, Schema, SimpleObject, Subscription,
+};
+use futures_util::{Stream, StreamExt};
+use std::time::Duration;
+
+/// Read-only GraphQL API on /graphql, with the WebSocket events as a subscription
+pub type HomiesSchema = Schema<QueryRoot, EmptyMutation, SubscriptionRoot>;
+
+pub fn schema(state: StateHandle, jobs: SharedJobs, ws_clients: WsClients) -> HomiesSchema {
+    Schema::build(QueryRoot, EmptyMutation, SubscriptionRoot)
+        .data(state)
+        .data(jobs)
+        .data(ws_clients)
+        .finish()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enum)]
+#[graphql(remote = "crate::state::MediaType")]
+pub enum MediaType {
+    Image,
+    Video,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enum)]
+#[graphql(remote = "crate::state::UploadStatus")]
+pub enum UploadStatus {
+    Queued,
+    Shown,
+    Expired,
+    Cancelled,
+    Archived,
+    Locked,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enum)]
+#[graphql(remote = "crate::jobs::JobKind")]
+pub enum JobKind {
+    Download,
+    Preview,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enum)]
+#[graphql(remote = "crate::jobs::JobStage")]
+pub enum JobStage {
+    Downloading,
+    Processing,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+/// Media on screen or waiting for it
+#[derive(SimpleObject)]
+pub struct Media {
+    id: u64,
+    filename: String,
+    media_type: MediaType,
+    caption: String,
+    play_secs: u64,
+    poster: Option<String>,
+}
+
+impl From<MediaItem> for Media {
+    fn from(item: MediaItem) -> Self {
+        Self {
+            id: item.id,
+            filename: item.filename,
+            media_type: item.media_type.into(),
+            caption: item.caption,
+            play_secs: item.play_secs,
+            poster: item.poster,
+        }
+    }
+}
+
+/// A recent upload and what became of it
+#[derive(SimpleObject)]
+pub struct Upload {
+    id: u64,
+    filename: String,
+    media_type: MediaType,
+    caption: String,
+    status: UploadStatus,
+    uploaded_at_ms: u64, // Unix milliseconds
+}
+
+#[derive(SimpleObject)]
+pub struct Sound {
+    id: u64,
+    filename: String,
+    hotkey: Option<u8>,
+}
+
+impl From<&SoundInfo> for Sound {
+    fn from(sound: &SoundInfo) -> Self {
+        Self {
+            id: sound.id,
+            filename: sound.filename.clone(),
+            hotkey: sound.hotkey,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Job {
+    id: u64,
+    kind: JobKind,
+    url: String,
+    stage: JobStage,
+    percent: f32,
+}
+
+impl From<JobStatus> for Job {
+    fn from(job: JobStatus) -> Self {
+        Self {
+            id: job.id,
+            kind: job.kind.into(),
+            url: job.url,
+            stage: job.stage.into(),
+            percent: job.percent,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct PersonUploads {
+    person: String,
+    uploads: u64,
+}
+
+#[derive(SimpleObject)]
+pub struct SoundPlays {
+    filename: String,
+    plays: u64,
+}
+
+/// Activity between two times, Unix seconds
+#[derive(SimpleObject)]
+pub struct Stats {
+    since: u64,
+    until: u64,
+    uploads: u64,
+    uploads_by_person: Vec<PersonUploads>,
+    top_sound: Option<SoundPlays>,
+    sound_plays: u64,
+    images_shown: u64,
+    videos_shown: u64,
+    video_minutes: f64,
+}
+
+impl From<StatsReport> for Stats {
+    fn from(report: StatsReport) -> Self {
+        Self {
+            since: report.since,
+            until: report.until,
+            uploads: report.uploads,
+            uploads_by_person: report
+                .uploads_by_person
+                .into_iter()
+                .map(|person| PersonUploads {
+                    person: person.person,
+                    uploads: person.uploads,
+                })
+                .collect(),
+            top_sound: report.top_sound.map(|sound| SoundPlays {
+                filename: sound.filename,
+                plays: sound.plays,
+            }),
+            sound_plays: report.sound_plays,
+            images_shown: report.images_shown,
+            videos_shown: report.videos_shown,
+            video_minutes: report.video_minutes,
+        }
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Media on screen, none when idle
+    async fn current(&self, ctx: &Context<'_>) -> Result<Option<Media>> {
+        let state = ctx.data::<StateHandle>()?;
+        let snapshot = state.call(|state| state.display_snapshot()).await;
+        Ok(snapshot.current.map(Media::from))
+    }
+
+    /// Media waiting for the screen, next first
+    async fn queue(&self, ctx: &Context<'_>) -> Result<Vec<Media>> {
+        let state = ctx.data::<StateHandle>()?;
+        let snapshot = state.call(|state| state.display_snapshot()).await;
+        Ok(snapshot.queue.into_iter().map(Media::from).collect())
+    }
+
+    /// Recent uploads of everyone, newest first, up to the last 100
+    async fn history(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 20)] limit: usize,
+    ) -> Result<Vec<Upload>> {
+        let state = ctx.data::<StateHandle>()?;
+        let history = state
+            .call(move |state| {
+                state
+                    .history()
+                    .take(limit)
+                    .map(|record| Upload {
+                        id: record.media.id,
+                        filename: record.media.filename.clone(),
+                        media_type: record.media.media_type.into(),
+                        caption: record.media.caption.clone(),
+                        status: record.status.into(),
+                        uploaded_at_ms: playback::unix_ms(record.media.upload_time),
+                    })
+                    .collect()
+            })
+            .await;
+        Ok(history)
+    }
+
+    async fn sounds(&self, ctx: &Context<'_>) -> Result<Vec<Sound>> {
+        let state = ctx.data::<StateHandle>()?;
+        let sounds = state
+            .call(|state| state.sounds().iter().map(Sound::from).collect())
+            .await;
+        Ok(sounds)
+    }
+
+    /// URL downloads and background jobs, oldest first
+    async fn jobs(&self, ctx: &Context<'_>) -> Result<Vec<Job>> {
+        let jobs = ctx.data::<SharedJobs>()?.read().await.jobs();
+        Ok(jobs.iter().map(JobEntry::status).map(Job::from).collect())
+    }
+
+    /// Activity over the last `days` (a week by default), up to the month stats are kept
+    async fn stats(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 7)] days: u64,
+    ) -> Result<Stats> {
+        let state = ctx.data::<StateHandle>()?;
+        let period = Duration::from_secs(days.clamp(1, 31) * 24 * 3600);
+        let report = state.call(move |state| state.stats_report(period)).await;
+        Ok(report.into())
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// Events as broadcast to /ws clients, only those named in `names` when given
+    async fn events(
+        &self,
+        ctx: &Context<'_>,
+        names: Option<Vec<String>>,
+    ) -> Result<impl Stream<Item = Json<serde_json::Value>> + use<>> {
+        let clients = ctx.data::<WsClients>()?;
+        let events = websocket::event_stream(clients).await.filter(move |event| {
+            let wanted = names.as_ref().is_none_or(|names| {
+                event["event"]
+                    .as_str()
+                    .is_some_and(|name| names.iter().any(|wanted| wanted == name))
+            });
+            async move { wanted }
+        });
+        Ok(events.map(Json))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::create_jobs_state;
+    use crate::state::MediaViewState;
+    use crate::websocket::create_ws_state;
+
+    #[tokio::test]
+    async fn test_subscription_filters_events_by_name() {
+        let ws_clients = create_ws_state(16);
+        let schema = schema(
+            StateHandle::spawn(MediaViewState::new()),
+            create_jobs_state(),
+            ws_clients.clone(),
+        );
+        let mut events =
+            schema.execute_stream(r#"subscription { events(names: ["caption"]) }"#);
+        // Polled once so the subscription is listening before anything is broadcast
+        let pending = futures_util::poll!(events.next());
+        assert!(pending.is_pending());
+
+        websocket::broadcast_theme(&ws_clients, crate::theme::Theme::Party).await;
+        websocket::broadcast_caption(&ws_clients, "gg", 5, None).await;
+        let response = events.next().await.unwrap();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        assert_eq!(data["events"]["event"], "caption");
+        assert_eq!(data["events"]["text"], "gg");
+    }
+}
@@ -0,0 +1,22 @@
+use serde::Deserialize;
+use std::net::SocketAddr;
+
+#[cfg(feature = "grpc")]
+mod service;
+
+#[cfg(feature = "grpc")]
+pub use service::{HomiesService, proto, serve};
+
+/// gRPC control API for LAN automation, e.g. a Raspberry Pi button box playing sounds, without
+/// the multipart plumbing of the HTTP upload
+/// Calls need the admin token in `x-admin-token` metadata when one is configured, and the
+/// server is only built in with the `grpc` feature
+#[derive(Clone, Debug, Deserialize)]
+pub struct GrpcConfig {
+    #[serde(default = "default_listen")]
+    pub listen: SocketAddr,
+}
+
+fn default_listen() -> SocketAddr {
+    ([0, 0, 0, 0], 50051).into()
+}
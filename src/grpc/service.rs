@@ -0,0 +1,261 @@
+use crate::config::AppConfig;
+use crate::handlers::upload::{self, DirectUpload};
+use crate::session::{self, ADMIN_HEADER};
+use crate::state::MediaType;
+use crate::state_actor::StateHandle;
+use crate::types::{MediaItem, UploadOutcome, UploadResponse};
+use crate::websocket::{self, WsClients};
+use futures_util::{Stream, StreamExt};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+
+/// Generated from `proto/homies.proto`
+pub mod proto {
+    tonic::include_proto!("homies");
+}
+
+use proto::homies_server::{Homies, HomiesServer};
+use proto::play_sound_request::Sound;
+
+/// Handlers of the `Homies` service, sharing the state and broadcasts of the HTTP routes
+#[derive(Clone)]
+pub struct HomiesService {
+    state: StateHandle,
+    ws_clients: WsClients,
+    config: Arc<AppConfig>,
+}
+
+impl HomiesService {
+    pub fn new(state: StateHandle, ws_clients: WsClients, config: Arc<AppConfig>) -> Self {
+        Self {
+            state,
+            ws_clients,
+            config,
+        }
+    }
+}
+
+/// Serve the service on `listen` until it fails
+pub async fn serve(
+    service: HomiesService,
+    listen: SocketAddr,
+) -> Result<(), tonic::transport::Error> {
+    let token = service.config.admin_token.clone();
+    let server = HomiesServer::with_interceptor(service, move |request: Request<()>| {
+        let given = request.metadata().get(ADMIN_HEADER).and_then(|value| value.to_str().ok());
+        if session::admin_token_matches(token.as_deref(), given) {
+            Ok(request)
+        } else {
+            tracing::warn!("Rejected gRPC call with a missing or wrong admin token");
+            Err(Status::unauthenticated("Missing or wrong admin token"))
+        }
+    });
+    tonic::transport::Server::builder()
+        .add_service(server)
+        .serve(listen)
+        .await
+}
+
+impl From<MediaItem> for proto::MediaItem {
+    fn from(item: MediaItem) -> Self {
+        let media_type = match item.media_type {
+            MediaType::Image => proto::MediaType::Image,
+            MediaType::Video => proto::MediaType::Video,
+        };
+        Self {
+            id: item.id,
+            filename: item.filename,
+            media_type: media_type.into(),
+            caption: item.caption,
+            play_secs: item.play_secs,
+            poster: item.poster,
+        }
+    }
+}
+
+impl From<UploadResponse> for proto::UploadResponse {
+    fn from(response: UploadResponse) -> Self {
+        let outcome = match response.outcome {
+            UploadOutcome::Shown => proto::UploadOutcome::Shown,
+            UploadOutcome::Queued => proto::UploadOutcome::Queued,
+            UploadOutcome::Held => proto::UploadOutcome::Held,
+            UploadOutcome::Locked => proto::UploadOutcome::Locked,
+            UploadOutcome::Staged => proto::UploadOutcome::Staged,
+            UploadOutcome::Busy => proto::UploadOutcome::Busy,
+            UploadOutcome::Refused => proto::UploadOutcome::Refused,
+            UploadOutcome::Review => proto::UploadOutcome::Review,
+        };
+        Self {
+            outcome: outcome.into(),
+            filename: response.filename,
+            queue_position: response.queue_position.map(|position| position as u64),
+            retry_after_secs: response.retry_after_secs,
+            staged_id: response.staged_id,
+            message: response.message,
+        }
+    }
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<proto::Event, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Homies for HomiesService {
+    async fn play_sound(
+        &self,
+        request: Request<proto::PlaySoundRequest>,
+    ) -> Result<Response<proto::PlaySoundReply>, Status> {
+        let sound = request.into_inner().sound;
+        tracing::info!("Received gRPC request to play {:?}", sound);
+        let sound = match sound {
+            Some(Sound::Id(id)) => Some((Some(id), None)),
+            Some(Sound::Hotkey(hotkey)) => u8::try_from(hotkey)
+                .ok()
+                .filter(|hotkey| *hotkey <= 9)
+                .map(|hotkey| (None, Some(hotkey))),
+            None => None,
+        };
+        let Some((id, hotkey)) = sound else {
+            return Err(Status::invalid_argument("A sound id or a hotkey from 0 to 9 is required"));
+        };
+        let sound = self
+            .state
+            .call(move |state| {
+                let sound = match (id, hotkey) {
+                    (Some(id), _) => state.get_sound(id).cloned(),
+                    (None, Some(hotkey)) => state.get_sound_by_hotkey(hotkey).cloned(),
+                    (None, None) => None,
+                };
+                sound.inspect(|sound| state.record_sound_play(&sound.filename, None))
+            })
+            .await;
+        let Some(sound) = sound else {
+            return Err(Status::not_found("No such sound"));
+        };
+        websocket::broadcast_new_song(&self.ws_clients, sound.filename.clone()).await;
+        Ok(Response::new(proto::PlaySoundReply {
+            filename: sound.filename,
+        }))
+    }
+
+    async fn push_media(
+        &self,
+        request: Request<proto::PushMediaRequest>,
+    ) -> Result<Response<proto::UploadResponse>, Status> {
+        let request = request.into_inner();
+        let upload = DirectUpload {
+            filename: request.filename,
+            data: request.data,
+            caption: request.caption,
+            duration_secs: request.duration_secs,
+            preset: request.preset,
+        };
+        let response = upload::publish_direct(
+            upload,
+            self.state.clone(),
+            self.ws_clients.clone(),
+            &self.config,
+            self.config.locale,
+        )
+        .await
+        .map_err(|rejection| match rejection.find::<upload::TooLarge>() {
+            Some(too_large) => Status::resource_exhausted(too_large.message.clone()),
+            None => {
+                tracing::error!("gRPC upload failed: {:?}", rejection);
+                Status::internal("Failed to publish the media")
+            }
+        })?;
+        Ok(Response::new(response.into()))
+    }
+
+    async fn get_queue(
+        &self,
+        _request: Request<proto::GetQueueRequest>,
+    ) -> Result<Response<proto::Queue>, Status> {
+        let snapshot = self.state.call(|state| state.display_snapshot()).await;
+        Ok(Response::new(proto::Queue {
+            current: snapshot.current.map(Into::into),
+            queue: snapshot.queue.into_iter().map(Into::into).collect(),
+            remaining_ms: snapshot.remaining_ms,
+        }))
+    }
+
+    type StreamEventsStream = EventStream;
+
+    async fn stream_events(
+        &self,
+        request: Request<proto::StreamEventsRequest>,
+    ) -> Result<Response<Self::StreamEventsStream>, Status> {
+        let names = request.into_inner().names;
+        let events = websocket::event_stream(&self.ws_clients).await.filter_map(move |event| {
+            let name = event["event"].as_str().unwrap_or_default().to_string();
+            let wanted = names.is_empty() || names.contains(&name);
+            async move {
+                wanted.then(|| {
+                    Ok(proto::Event {
+                        name,
+                        json: event.to_string(),
+                    })
+                })
+            }
+        });
+        Ok(Response::new(Box::pin(events)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::MediaViewState;
+    use crate::websocket::create_ws_state;
+
+    fn service() -> HomiesService {
+        HomiesService::new(
+            StateHandle::spawn(MediaViewState::new()),
+            create_ws_state(16),
+            Arc::new(AppConfig::default()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_play_sound_needs_a_known_sound() {
+        let service = service();
+        let request = |sound| Request::new(proto::PlaySoundRequest { sound });
+        let status = service.play_sound(request(None)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let status = service.play_sound(request(Some(Sound::Hotkey(10)))).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let status = service.play_sound(request(Some(Sound::Id(42)))).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_push_media_refuses_empty_uploads() {
+        let request = Request::new(proto::PushMediaRequest {
+            filename: "empty.png".to_string(),
+            ..Default::default()
+        });
+        let response = service().push_media(request).await.unwrap().into_inner();
+        assert_eq!(response.outcome(), proto::UploadOutcome::Refused);
+        assert_eq!(response.filename, None);
+    }
+
+    #[tokio::test]
+    async fn test_stream_events_filters_by_name() {
+        let service = service();
+        let queue = service.get_queue(Request::new(proto::GetQueueRequest {})).await.unwrap();
+        assert_eq!(queue.into_inner(), proto::Queue::default());
+
+        let request = Request::new(proto::StreamEventsRequest {
+            names: vec!["caption".to_string()],
+        });
+        let mut events = service.stream_events(request).await.unwrap().into_inner();
+        websocket::broadcast_theme(&service.ws_clients, crate::theme::Theme::Party).await;
+        websocket::broadcast_caption(&service.ws_clients, "gg", 5, None).await;
+        let event = events.next().await.unwrap().unwrap();
+        assert_eq!(event.name, "caption");
+        let json: serde_json::Value = serde_json::from_str(&event.json).unwrap();
+        assert_eq!(json["text"], "gg");
+    }
+}
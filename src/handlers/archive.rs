@@ -0,0 +1,242 @@
+use crate::config::AppConfig;
+use crate::errors::AppError;
+use crate::handlers::media::SharedState;
+use crate::highlights;
+use crate::i18n::{Locale, Msg};
+use crate::handlers::upload::{busy_message, create_media_info, update_state_and_broadcast};
+use crate::media_probe::MediaProbe;
+use crate::session;
+use crate::state::{Admission, ArchiveEntry, MediaSource, MediaType};
+use crate::templates::ArchiveTemplate;
+use crate::websocket;
+use askama::Template;
+use serde_json::json;
+use std::path::Path;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+// Name the index was kept under before, inside the served archive directory
+const LEGACY_ARCHIVE_INDEX: &str = "archive.json";
+
+pub async fn archive_page(
+    csrf_cookie: Option<String>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Serving archive");
+    let csrf = session::CsrfToken::from_cookie(csrf_cookie);
+    let template = ArchiveTemplate {
+        csrf_token: csrf.value.clone(),
+        entries: state
+            .call(|state| state.archive().iter().rev().cloned().collect())
+            .await,
+    };
+    match template.render() {
+        Ok(html) => Ok(csrf.attach(warp::reply::html(html))),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err(warp::reject::custom(AppError::RenderError(e)))
+        }
+    }
+}
+
+// Move shown media out of the reaper's reach and remember it
+pub async fn pin_media(id: u64, state: SharedState) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to pin media {}", id);
+    let Some(media) = state
+        .call(move |state| state.pinnable_media(id).cloned())
+        .await
+    else {
+        tracing::warn!("No shown media {} to pin", id);
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": "No media on screen with this id" })),
+            StatusCode::NOT_FOUND,
+        ));
+    };
+
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    tokio::fs::create_dir_all(&dirs.archive).await.map_err(|e| {
+        tracing::error!("Failed to create archive directory: {}", e);
+        warp::reject::custom(AppError::IoError(e))
+    })?;
+    let source = dirs.upload(&media.filename);
+    let target = dirs.archived(&media.filename);
+    tokio::fs::rename(&source, &target).await.map_err(|e| {
+        tracing::error!("Failed to move {} to the archive: {}", source, e);
+        warp::reject::custom(AppError::IoError(e))
+    })?;
+
+    let entry = state.call(move |state| state.archive_media(&media)).await;
+    save_archive(&state).await;
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&entry),
+        StatusCode::OK,
+    ))
+}
+
+// Put an archived media back on screen through the regular display flow
+pub async fn show_archived(
+    id: u64,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    locale: Locale,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to show archived media {}", id);
+    let Some(entry) = state
+        .call(move |state| state.get_archived(id).cloned())
+        .await
+    else {
+        tracing::warn!("No archived media {}", id);
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::NoArchivedMedia))));
+    };
+
+    let admission = replay(&entry, state, ws_clients).await?;
+
+    if let Some(busy_message) = busy_message(&entry.filename, &admission, locale) {
+        return Ok(warp::reply::html(busy_message));
+    }
+    Ok(warp::reply::html(format!(
+        "<p>{}</p>",
+        locale.format(Msg::ShowingAgain, &[&entry.filename])
+    )))
+}
+
+// Show a copy so the reaper can clean it up like any upload
+async fn replay(
+    entry: &ArchiveEntry,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<Admission, Rejection> {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let filename = format!("replay_{}_{}", timestamp, entry.filename);
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    let source = dirs.archived(&entry.filename);
+    let target = dirs.upload(&filename);
+    tokio::fs::create_dir_all(&dirs.uploads).await.map_err(|e| {
+        tracing::error!("Failed to create uploads directory: {}", e);
+        warp::reject::custom(AppError::IoError(e))
+    })?;
+    tokio::fs::copy(&source, &target).await.map_err(|e| {
+        tracing::error!("Failed to copy {} from the archive: {}", source, e);
+        warp::reject::custom(AppError::IoError(e))
+    })?;
+
+    let mut media_info = create_media_info(
+        filename,
+        entry.media_type,
+        entry.duration_secs,
+        entry.caption.clone(),
+        entry.play_secs,
+        None,
+    );
+    media_info.probe = MediaProbe::probe(&target).await;
+    media_info.source = MediaSource::Archive;
+    update_state_and_broadcast(state, media_info, ws_clients).await
+}
+
+/// Join the videos pinned during the last period into a reel stored in the archive
+pub async fn make_highlight_reel(
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: &AppConfig,
+) {
+    let now = state
+        .call(|state| state.now())
+        .await
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let since = now.saturating_sub(config.highlights.every().as_secs());
+    let entries = state.call(|state| state.archive().to_vec()).await;
+    let clips = highlights::select_clips(&entries, since);
+    if clips.is_empty() {
+        tracing::info!("No clips pinned since the last highlights, skipping");
+        return;
+    }
+
+    let filename = format!("{}{}.mp4", highlights::REEL_PREFIX, now);
+    tracing::info!("Building highlights {} from {} clips", filename, clips.len());
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    if let Err(e) = highlights::build_reel(&clips, &dirs.archive, &filename, config.quality).await
+    {
+        tracing::error!("Failed to build highlights: {}", e);
+        return;
+    }
+
+    let probe = MediaProbe::probe(&dirs.archived(&filename)).await;
+    let duration_secs = probe.duration_secs.map(|secs| secs.ceil() as u64).unwrap_or(0);
+    let media = create_media_info(
+        filename,
+        MediaType::Video,
+        999999, // Videos play full duration
+        "Highlights".to_string(),
+        duration_secs,
+        None,
+    );
+    let entry = state.call(move |state| state.archive_media(&media)).await;
+    save_archive(&state).await;
+
+    if config.highlights.broadcast {
+        match replay(&entry, state, ws_clients).await {
+            Ok(admission) => tracing::info!("Highlights submitted: {:?}", admission),
+            Err(e) => tracing::error!("Failed to show highlights: {:?}", e),
+        }
+    }
+}
+
+/// Restore the pinned media list saved in `path` so the archive survives restarts, and keep
+/// saving it there
+pub async fn load_archive(state: SharedState, path: &Path) {
+    let archive_index = path.to_path_buf();
+    let legacy = state
+        .call(move |state| {
+            state.set_archive_index(archive_index);
+            state.data_dirs().archived(LEGACY_ARCHIVE_INDEX)
+        })
+        .await;
+    // Moved out of the archive directory, where anyone could download it
+    if tokio::fs::metadata(path).await.is_err() && tokio::fs::metadata(&legacy).await.is_ok() {
+        match tokio::fs::rename(&legacy, path).await {
+            Ok(()) => tracing::info!("Moved {} to {}", legacy, path.display()),
+            Err(e) => tracing::error!("Failed to move {}: {}", legacy, e),
+        }
+    }
+    let entries: Vec<ArchiveEntry> = match tokio::fs::read(path).await {
+        Ok(data) => match serde_json::from_slice(&data) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::error!("Failed to parse {}: {}", path.display(), e);
+                return;
+            }
+        },
+        Err(e) => {
+            tracing::info!("No archive loaded: {}", e);
+            return;
+        }
+    };
+
+    tracing::info!("Loaded {} archived media", entries.len());
+    state.call(move |state| state.load_archive(entries)).await;
+}
+
+async fn save_archive(state: &SharedState) {
+    let (entries, path) = state
+        .call(|state| (state.archive().to_vec(), state.archive_index().cloned()))
+        .await;
+    let Some(path) = path else {
+        return;
+    };
+    let data = match serde_json::to_vec_pretty(&entries) {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::error!("Failed to serialize archive: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = tokio::fs::write(&path, data).await {
+        tracing::error!("Failed to write {}: {}", path.display(), e);
+    }
+}
@@ -0,0 +1,31 @@
+use crate::audit::AuditLog;
+use serde_json::json;
+use std::collections::HashMap;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+const DEFAULT_LIMIT: usize = 100;
+
+// Latest admin requests and uploads, newest first, `?limit=` of them
+pub async fn audit_log(
+    query: HashMap<String, String>,
+    audit: AuditLog,
+) -> Result<impl Reply, Rejection> {
+    let limit = query
+        .get("limit")
+        .and_then(|limit| limit.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_LIMIT);
+    match audit.recent(limit).await {
+        Ok(entries) => Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "entries": entries })),
+            StatusCode::OK,
+        )),
+        Err(e) => {
+            tracing::error!("Failed to read the audit log: {}", e);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "error": "Failed to read the audit log" })),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
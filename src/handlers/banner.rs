@@ -0,0 +1,112 @@
+use crate::banner::{Banner, BannerError, BannerStatus};
+use crate::handlers::media::SharedState;
+use crate::websocket;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+pub async fn banner(state: SharedState) -> Result<impl Reply, Rejection> {
+    let banner = state.call(|state| state.banner().map(Banner::status)).await;
+    Ok(warp::reply::json(&json!({ "banner": banner })))
+}
+
+// Admin pin of a text banner, kept over media changes until `expires_in_secs` pass or it's
+// cleared; 0 or no expiry keeps it up
+pub async fn set_banner(
+    form: HashMap<String, String>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    let text = form.get("text").cloned().unwrap_or_default();
+    let ttl = form
+        .get("expires_in_secs")
+        .and_then(|secs| secs.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+    let status: Result<BannerStatus, BannerError> = state
+        .call(move |state| {
+            let text = state.word_filter().check_now(&text).ok_or(BannerError::Refused)?;
+            let banner = Banner::new(&text, ttl, state.now())?;
+            let status = banner.status();
+            state.set_banner(Some(banner));
+            Ok(status)
+        })
+        .await;
+    match status {
+        Ok(status) => {
+            tracing::info!("Banner set: {}", status.text);
+            save_banner(state).await;
+            websocket::broadcast_banner(&ws_clients, Some(&status)).await;
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "banner": status })),
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => {
+            tracing::warn!("Invalid banner: {}", e);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "error": e.to_string() })),
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    }
+}
+
+pub async fn clear_banner(
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Banner cleared");
+    state.call(|state| state.set_banner(None)).await;
+    save_banner(state).await;
+    websocket::broadcast_banner(&ws_clients, None).await;
+    Ok(warp::reply::json(&json!({ "banner": null })))
+}
+
+/// Restore the banner saved in `path` unless it expired meanwhile, and keep saving it there
+pub async fn load_banner(state: SharedState, path: &Path) {
+    let banner_file = path.to_path_buf();
+    state.call(move |state| state.set_banner_file(banner_file)).await;
+    let banner: Option<Banner> = match tokio::fs::read(path).await {
+        Ok(data) => match serde_json::from_slice(&data) {
+            Ok(banner) => banner,
+            Err(e) => {
+                tracing::error!("Failed to parse {}: {}", path.display(), e);
+                return;
+            }
+        },
+        Err(e) => {
+            tracing::info!("No banner loaded: {}", e);
+            return;
+        }
+    };
+    state
+        .call(move |state| {
+            let now = state.now();
+            state.set_banner(banner.filter(|banner| !banner.is_expired(now)));
+        })
+        .await;
+}
+
+// Cleared banners are saved as null so they stay down after a restart
+async fn save_banner(state: SharedState) {
+    let (banner, path) = state
+        .call(|state| (state.banner().cloned(), state.banner_file().cloned()))
+        .await;
+    let Some(path) = path else {
+        return;
+    };
+    let data = match serde_json::to_vec_pretty(&banner) {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::error!("Failed to serialize the banner: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = tokio::fs::write(&path, data).await {
+        tracing::error!("Failed to write {}: {}", path.display(), e);
+    }
+}
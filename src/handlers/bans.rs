@@ -0,0 +1,181 @@
+use crate::bans::{BanError, BanList, BanTarget};
+use crate::handlers::media::SharedState;
+use crate::playback;
+use crate::session::SESSION_COOKIE;
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+/// A banned client tried to upload or chat
+#[derive(Debug)]
+pub struct Banned {
+    pub accept_language: Option<String>,
+    pub minutes_left: Option<u64>, // Permanent ban when unset
+}
+
+impl warp::reject::Reject for Banned {}
+
+/// Reject requests from a banned session or address, before their body is read
+pub fn not_banned(state: SharedState) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::cookie::optional::<String>(SESSION_COOKIE)
+        .and(warp::addr::remote())
+        .and(warp::header::optional::<String>("accept-language"))
+        .and_then(
+            move |session: Option<String>, addr: Option<SocketAddr>, accept_language| {
+                let state = state.clone();
+                async move {
+                    let ip = addr.map(|addr| addr.ip());
+                    let minutes_left = state
+                        .call(move |state| {
+                            let now = state.now();
+                            let ban = state.bans().find(session.as_deref(), ip, now)?;
+                            let now_ms = playback::unix_ms(now);
+                            Some(ban.expires_at_ms.map(|expires_at_ms| {
+                                expires_at_ms.saturating_sub(now_ms).div_ceil(60_000)
+                            }))
+                        })
+                        .await;
+                    match minutes_left {
+                        None => Ok(()),
+                        Some(minutes_left) => {
+                            tracing::warn!("Rejected request from a banned client");
+                            Err(warp::reject::custom(Banned {
+                                accept_language,
+                                minutes_left,
+                            }))
+                        }
+                    }
+                }
+            },
+        )
+        .untuple_one()
+}
+
+pub async fn list_bans(state: SharedState) -> Result<impl Reply, Rejection> {
+    let bans = state
+        .call(|state| {
+            let now = state.now();
+            state.bans().list(now)
+        })
+        .await;
+    Ok(warp::reply::json(&json!({ "bans": bans })))
+}
+
+// Admin ban of an `ip` or a `session`, for `expires_in_secs` or until lifted
+pub async fn ban(
+    form: HashMap<String, String>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    let target = match BanTarget::parse(
+        form.get("ip").map(String::as_str),
+        form.get("session").map(String::as_str),
+    ) {
+        Ok(target) => target,
+        Err(e) => return Ok(error_reply(e)),
+    };
+    let reason = form.get("reason").cloned().unwrap_or_default();
+    let ttl = form
+        .get("expires_in_secs")
+        .and_then(|secs| secs.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+    let banned = state
+        .call(move |state| {
+            let now = state.now();
+            state.bans_mut().ban(target, &reason, ttl, now)
+        })
+        .await;
+    match banned {
+        Ok(ban) => {
+            tracing::info!("Banned {:?} until {:?}", ban.target, ban.expires_at_ms);
+            save_bans(state).await;
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "ban": ban })),
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => Ok(error_reply(e)),
+    }
+}
+
+// Admin lifting of the ban on an `ip` or a `session`
+pub async fn unban(
+    form: HashMap<String, String>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    let target = match BanTarget::parse(
+        form.get("ip").map(String::as_str),
+        form.get("session").map(String::as_str),
+    ) {
+        Ok(target) => target,
+        Err(e) => return Ok(error_reply(e)),
+    };
+    match state.call(move |state| state.bans_mut().lift(&target)).await {
+        Ok(ban) => {
+            tracing::info!("Lifted the ban on {:?}", ban.target);
+            save_bans(state).await;
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "ban": ban })),
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => Ok(error_reply(e)),
+    }
+}
+
+fn error_reply(e: BanError) -> warp::reply::WithStatus<warp::reply::Json> {
+    let status = match e {
+        BanError::NotFound => StatusCode::NOT_FOUND,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    warp::reply::with_status(warp::reply::json(&json!({ "error": e.to_string() })), status)
+}
+
+/// Restore the bans saved in `path`, and keep saving them there
+pub async fn load_bans(state: SharedState, path: &Path) {
+    let bans_file = path.to_path_buf();
+    state.call(move |state| state.set_bans_file(bans_file)).await;
+    let bans: BanList = match tokio::fs::read(path).await {
+        Ok(data) => match serde_json::from_slice(&data) {
+            Ok(bans) => bans,
+            Err(e) => {
+                tracing::error!("Failed to parse {}: {}", path.display(), e);
+                return;
+            }
+        },
+        Err(e) => {
+            tracing::info!("No bans loaded: {}", e);
+            return;
+        }
+    };
+    state
+        .call(move |state| {
+            let now = state.now();
+            *state.bans_mut() = bans;
+            state.bans_mut().prune(now);
+        })
+        .await;
+}
+
+async fn save_bans(state: SharedState) {
+    let (bans, path) = state
+        .call(|state| (state.bans().clone(), state.bans_file().cloned()))
+        .await;
+    let Some(path) = path else {
+        return;
+    };
+    let data = match serde_json::to_vec_pretty(&bans) {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::error!("Failed to serialize bans: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = tokio::fs::write(&path, data).await {
+        tracing::error!("Failed to write {}: {}", path.display(), e);
+    }
+}
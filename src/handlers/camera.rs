@@ -0,0 +1,78 @@
+use crate::config::AppConfig;
+use crate::handlers::media::SharedState;
+use crate::handlers::upload::{busy_message, create_media_info, update_state_and_broadcast};
+use crate::i18n::{Locale, Msg};
+use crate::media_probe::MediaProbe;
+use crate::state::{MediaSource, MediaType};
+use crate::video_processing::VideoProcessor;
+use crate::websocket;
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+// Grab the current frame of a configured camera and put it on screen like an uploaded image
+pub async fn snapshot(
+    camera: String,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+    locale: Locale,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request for a snapshot of camera {}", camera);
+    let reply = |status, message: String| {
+        Ok(warp::reply::with_status(
+            warp::reply::html(format!("<p>{}</p>", message)),
+            status,
+        ))
+    };
+    let Some(camera) = config.cameras.iter().find(|known| known.name == camera) else {
+        tracing::warn!("No camera named {}", camera);
+        return reply(StatusCode::NOT_FOUND, locale.t(Msg::NoSuchCamera).to_string());
+    };
+    if !VideoProcessor::is_ffmpeg_available() {
+        return reply(
+            StatusCode::SERVICE_UNAVAILABLE,
+            locale.t(Msg::SnapshotUnavailable).to_string(),
+        );
+    }
+
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let filename = format!("snapshot_{}.jpg", stamp);
+    let uploads = state.call(|state| state.data_dirs().uploads.clone()).await;
+    let path = format!("{}/{}", uploads, filename);
+    if let Err(e) = tokio::fs::create_dir_all(&uploads).await {
+        tracing::error!("Failed to create uploads directory: {}", e);
+        return reply(StatusCode::INTERNAL_SERVER_ERROR, locale.t(Msg::SnapshotFailed).to_string());
+    }
+    if let Err(e) = camera.snapshot(&path).await {
+        tracing::error!("Snapshot of camera {} failed: {}", camera.name, e);
+        let _ = tokio::fs::remove_file(&path).await;
+        return reply(StatusCode::BAD_GATEWAY, locale.t(Msg::SnapshotFailed).to_string());
+    }
+
+    let mut media_info = create_media_info(
+        filename.clone(),
+        MediaType::Image,
+        camera.duration_secs,
+        camera.caption.clone(),
+        camera.duration_secs,
+        None,
+    );
+    media_info.probe = MediaProbe::probe(&path).await;
+    media_info.source = MediaSource::Camera;
+    let admission = update_state_and_broadcast(state, media_info, ws_clients).await?;
+    if let Some(busy_message) = busy_message(&filename, &admission, locale) {
+        return Ok(warp::reply::with_status(
+            warp::reply::html(busy_message),
+            StatusCode::OK,
+        ));
+    }
+    tracing::info!("Showing a snapshot of camera {}", camera.name);
+    reply(
+        StatusCode::OK,
+        locale.format(Msg::SnapshotShown, &[&camera.name]),
+    )
+}
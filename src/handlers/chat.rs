@@ -0,0 +1,67 @@
+use crate::chat::ChatError;
+use crate::config::AppConfig;
+use crate::handlers::media::SharedState;
+use crate::session::ClientId;
+use crate::{stats, websocket};
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+// Latest ticker messages, oldest first, for displays catching up
+pub async fn recent_chat(state: SharedState) -> Result<impl Reply, Rejection> {
+    let messages = state
+        .call(|state| state.chat().recent().iter().cloned().collect::<Vec<_>>())
+        .await;
+    Ok(warp::reply::json(&json!({ "messages": messages })))
+}
+
+// Send `text` to the ticker, signed with `name` or the session when it's left empty
+pub async fn post_chat(
+    form: HashMap<String, String>,
+    client: Option<ClientId>,
+    addr: Option<SocketAddr>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+) -> Result<impl Reply, Rejection> {
+    let Some(client) = client else {
+        tracing::warn!("Chat message without a session or client IP address");
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": "Chatting needs a session" })),
+            StatusCode::BAD_REQUEST,
+        ));
+    };
+    let author = match form.get("name").map(|name| name.trim()) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => stats::person(Some(&client)),
+    };
+    let text = form.get("text").cloned().unwrap_or_default();
+
+    let posted = websocket::post_chat(
+        &state,
+        &ws_clients,
+        client,
+        addr.map(|addr| addr.ip()),
+        author,
+        text,
+        config.chat.clone(),
+    )
+    .await;
+    let (reply, status) = match posted {
+        Ok(message) => (json!({ "message": message }), StatusCode::OK),
+        Err(e) => {
+            let status = match e {
+                ChatError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+                ChatError::Banned => StatusCode::FORBIDDEN,
+                ChatError::Empty | ChatError::TooLong(_) | ChatError::Refused => {
+                    StatusCode::BAD_REQUEST
+                }
+            };
+            (json!({ "error": e.to_string() }), status)
+        }
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&reply), status))
+}
@@ -0,0 +1,252 @@
+use crate::audio_processing::parse_timestamp;
+use crate::config::AppConfig;
+use crate::data_dirs::DataDirs;
+use crate::handlers::media::SharedState;
+use crate::handlers::upload::{admission_response, update_state_and_broadcast};
+use crate::highlights;
+use crate::i18n::{Locale, Msg};
+use crate::media_probe::MediaProbe;
+use crate::session::{self, ClientId};
+use crate::state::{Admission, MediaInfo, MediaType};
+use crate::tasks;
+use crate::types::{UploadOutcome, UploadResponse};
+use crate::video_processing::VideoProcessor;
+use crate::websocket;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+// Most clips joined in one go
+const MAX_CONCAT_CLIPS: usize = 10;
+
+#[derive(Deserialize)]
+pub struct ConcatRequest {
+    ids: Vec<u64>, // Retained videos, in play order
+    #[serde(default)]
+    caption: String,
+}
+
+fn error(status: StatusCode, message: &str) -> warp::reply::Response {
+    warp::reply::with_status(warp::reply::json(&json!({ "error": message })), status)
+        .into_response()
+}
+
+// Unique name for a video made from `original`, e.g. clip_trimmed_1700000000000.mp4
+fn clip_filename(original: &str, label: &str) -> String {
+    let stem = std::path::Path::new(original)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("clip");
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    format!("{}_{}_{}.mp4", stem, label, stamp)
+}
+
+// Media info of a video made from `original`, probed afresh with its own poster
+async fn clip_media(
+    original: &MediaInfo,
+    filename: &str,
+    dirs: &DataDirs,
+    config: &AppConfig,
+) -> MediaInfo {
+    let probe = MediaProbe::probe(&dirs.upload(filename)).await;
+    let poster = match VideoProcessor::extract_poster(dirs, filename).await {
+        Ok(poster) => Some(poster),
+        Err(e) => {
+            tracing::warn!("No poster for {}: {}", filename, e);
+            None
+        }
+    };
+    MediaInfo {
+        filename: filename.to_string(),
+        marked_for_deletion: false,
+        play_secs: probe
+            .duration_secs
+            .map(|secs| secs.ceil() as u64)
+            .unwrap_or(config.video_busy_secs),
+        probe,
+        poster,
+        preview: None,
+        low_rendition: None,
+        hls: None,
+        ..original.clone()
+    }
+}
+
+// Outcome of a clip sent to the display, as an upload's would be
+fn upload_reply(filename: &str, admission: &Admission, locale: Locale) -> warp::reply::Response {
+    let response = admission_response(filename, admission, locale).unwrap_or_else(|| {
+        UploadResponse {
+            outcome: UploadOutcome::Shown,
+            filename: Some(filename.to_string()),
+            queue_position: None,
+            retry_after_secs: None,
+            staged_id: None,
+            message: locale.format(Msg::Published, &[&filename]),
+        }
+    });
+    warp::reply::with_status(warp::reply::json(&response), StatusCode::OK).into_response()
+}
+
+// A frame of a retained video at `t` (seconds, "mm:ss" or "hh:mm:ss") as a JPEG, for picking
+// the in and out points of a trim
+pub async fn frame(
+    id: u64,
+    query: HashMap<String, String>,
+    state: SharedState,
+) -> Result<warp::reply::Response, Rejection> {
+    let Some(at_secs) = query.get("t").and_then(|t| parse_timestamp(t)) else {
+        return Ok(error(StatusCode::BAD_REQUEST, "Give the frame's time as t"));
+    };
+    let Some(media) = state.call(move |state| state.retained_media(id).cloned()).await else {
+        return Ok(error(StatusCode::NOT_FOUND, "No retained media with this id"));
+    };
+    if media.media_type != MediaType::Video {
+        return Ok(error(StatusCode::BAD_REQUEST, "Only videos have frames"));
+    }
+    if media.probe.duration_secs.is_some_and(|duration| at_secs >= duration) {
+        return Ok(error(StatusCode::BAD_REQUEST, "The video is shorter than that"));
+    }
+    if !VideoProcessor::is_ffmpeg_available() {
+        return Ok(error(StatusCode::SERVICE_UNAVAILABLE, "ffmpeg is not installed"));
+    }
+
+    let uploads = state.call(|state| state.data_dirs().uploads.clone()).await;
+    match VideoProcessor::extract_frame(&uploads, &media.filename, at_secs).await {
+        Ok(jpeg) => Ok(warp::reply::with_header(
+            warp::reply::with_header(jpeg, "content-type", "image/jpeg"),
+            "cache-control",
+            "max-age=3600",
+        )
+        .into_response()),
+        Err(e) => {
+            tracing::error!("Failed to extract a frame of {}: {}", media.filename, e);
+            Ok(error(StatusCode::INTERNAL_SERVER_ERROR, "Frame extraction failed"))
+        }
+    }
+}
+
+// Trimmed copy of a retained video from `start` to `end`, for its uploader or an admin
+// Queued or locked videos are swapped for the copy in place, others are replaced on screen
+// by it; the original goes to the trash either way
+#[allow(clippy::too_many_arguments)] // One per warp filter
+pub async fn trim(
+    id: u64,
+    form: HashMap<String, String>,
+    client: Option<ClientId>,
+    admin_token: Option<String>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+    locale: Locale,
+) -> Result<warp::reply::Response, Rejection> {
+    let start = form.get("start").and_then(|start| parse_timestamp(start));
+    let end = form.get("end").and_then(|end| parse_timestamp(end));
+    if start.is_none() && end.is_none() {
+        return Ok(error(StatusCode::BAD_REQUEST, "Give a start or an end"));
+    }
+    let start = start.unwrap_or_default();
+    if end.is_some_and(|end| end <= start) {
+        return Ok(error(StatusCode::BAD_REQUEST, "The end must come after the start"));
+    }
+    let Some(original) = state.call(move |state| state.retained_media(id).cloned()).await else {
+        return Ok(error(StatusCode::NOT_FOUND, "No retained media with this id"));
+    };
+    let admin = session::admin_token_matches(config.admin_token.as_deref(), admin_token.as_deref());
+    if !admin && (client.is_none() || original.uploader != client) {
+        return Ok(error(StatusCode::FORBIDDEN, "Only its uploader or an admin can trim this"));
+    }
+    if original.media_type != MediaType::Video {
+        return Ok(error(StatusCode::BAD_REQUEST, "Only videos can be trimmed"));
+    }
+    if original.probe.duration_secs.is_some_and(|duration| start >= duration) {
+        return Ok(error(StatusCode::BAD_REQUEST, "The video is shorter than that"));
+    }
+    if !VideoProcessor::is_ffmpeg_available() {
+        return Ok(error(StatusCode::SERVICE_UNAVAILABLE, "ffmpeg is not installed"));
+    }
+
+    let filename = clip_filename(&original.filename, "trimmed");
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    let trimmed =
+        VideoProcessor::trim_video(&dirs.uploads, &original.filename, &filename, start, end);
+    if let Err(e) = trimmed.await {
+        tracing::error!("Failed to trim {}: {}", original.filename, e);
+        return Ok(error(StatusCode::INTERNAL_SERVER_ERROR, "Trim failed"));
+    }
+    tracing::info!("Trimmed {} into {}", original.filename, filename);
+    let trimmed = clip_media(&original, &filename, &dirs, &config).await;
+
+    let replacement = trimmed.clone();
+    let admission = match state.call(move |state| state.replace_waiting(id, replacement)).await {
+        Some(admission) => {
+            tasks::trash_upload(&state, original.filename).await;
+            admission
+        }
+        None => {
+            // Off screen first, so the trimmed copy takes its place rather than waiting on it
+            tasks::trash_upload(&state, original.filename).await;
+            update_state_and_broadcast(state, trimmed, ws_clients).await?
+        }
+    };
+    Ok(upload_reply(&filename, &admission, locale))
+}
+
+// One video of retained ones played back to back, queued like an upload
+pub async fn concat(
+    request: ConcatRequest,
+    client: Option<ClientId>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+    locale: Locale,
+) -> Result<warp::reply::Response, Rejection> {
+    let ids = request.ids;
+    if !(2..=MAX_CONCAT_CLIPS).contains(&ids.len()) {
+        let message = format!("Join between 2 and {} videos", MAX_CONCAT_CLIPS);
+        return Ok(error(StatusCode::BAD_REQUEST, &message));
+    }
+    let lookup = ids.clone();
+    let clips = state
+        .call(move |state| {
+            lookup
+                .iter()
+                .map(|id| state.retained_media(*id).cloned())
+                .collect::<Option<Vec<_>>>()
+        })
+        .await;
+    let Some(clips) = clips else {
+        return Ok(error(StatusCode::NOT_FOUND, "No retained media with one of these ids"));
+    };
+    if clips.iter().any(|clip| clip.media_type != MediaType::Video) {
+        return Ok(error(StatusCode::BAD_REQUEST, "Only videos can be joined"));
+    }
+    if !VideoProcessor::is_ffmpeg_available() {
+        return Ok(error(StatusCode::SERVICE_UNAVAILABLE, "ffmpeg is not installed"));
+    }
+
+    let filename = clip_filename(&clips[0].filename, "concat");
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    let inputs: Vec<String> = clips.iter().map(|clip| dirs.upload(&clip.filename)).collect();
+    let joined = highlights::join_clips(&inputs, &dirs.uploads, &filename, config.quality);
+    if let Err(e) = joined.await {
+        tracing::error!("Failed to join {:?}: {}", ids, e);
+        return Ok(error(StatusCode::INTERNAL_SERVER_ERROR, "Joining the videos failed"));
+    }
+    tracing::info!("Joined {:?} into {}", ids, filename);
+
+    let mut joined = clip_media(&clips[0], &filename, &dirs, &config).await;
+    joined.caption = request.caption.trim().to_string();
+    joined.uploader = client;
+    joined.translation = None;
+    joined.burn_after_viewing = false;
+    joined.lock = None;
+    joined.expires_in_secs = None;
+    let admission = update_state_and_broadcast(state, joined, ws_clients).await?;
+    Ok(upload_reply(&filename, &admission, locale))
+}
@@ -0,0 +1,123 @@
+use crate::displays::{Display, DisplayError, DisplayRegistry};
+use crate::handlers::media::SharedState;
+use crate::playback;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+pub async fn list_displays(state: SharedState) -> Result<impl Reply, Rejection> {
+    let displays = state.call(|state| state.displays().list()).await;
+    Ok(warp::reply::json(&json!({ "displays": displays })))
+}
+
+// Admin renaming of a registered display, an empty name goes back to its id
+pub async fn rename_display(
+    id: String,
+    form: HashMap<String, String>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    let name = form.get("name").cloned().unwrap_or_default();
+    let renamed = state
+        .call(move |state| state.displays_mut().rename(&id, &name))
+        .await;
+    let reply = match renamed {
+        Ok(renamed) => {
+            tracing::info!("Display {} renamed to {}", renamed.id, renamed.name);
+            save_displays(state).await;
+            warp::reply::with_status(
+                warp::reply::json(&json!({ "display": renamed })),
+                StatusCode::OK,
+            )
+        }
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&json!({ "error": e.to_string() })),
+            match e {
+                DisplayError::NotFound => StatusCode::NOT_FOUND,
+                _ => StatusCode::BAD_REQUEST,
+            },
+        ),
+    };
+    Ok(reply)
+}
+
+pub async fn forget_display(id: String, state: SharedState) -> Result<impl Reply, Rejection> {
+    let Some(forgotten) = state.call(move |state| state.displays_mut().forget(&id)).await else {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": DisplayError::NotFound.to_string() })),
+            StatusCode::NOT_FOUND,
+        ));
+    };
+    tracing::info!("Forgot display {}", forgotten.id);
+    save_displays(state).await;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({ "display": forgotten })),
+        StatusCode::OK,
+    ))
+}
+
+/// Register (or recognize) a display connecting on /ws, saving the registrations
+pub(crate) async fn connect_display(
+    state: &SharedState,
+    id: String,
+    name: Option<String>,
+) -> Result<Display, DisplayError> {
+    let display = state
+        .call(move |state| {
+            let now_ms = playback::unix_ms(state.now());
+            state.displays_mut().connect(&id, name.as_deref(), now_ms)
+        })
+        .await?;
+    save_displays(state.clone()).await;
+    Ok(display)
+}
+
+pub(crate) async fn disconnect_display(state: &SharedState, id: String) {
+    state
+        .call(move |state| {
+            let now_ms = playback::unix_ms(state.now());
+            state.displays_mut().disconnect(&id, now_ms)
+        })
+        .await;
+    save_displays(state.clone()).await;
+}
+
+/// Restore the displays registered in `path`, and keep saving them there
+pub async fn load_displays(state: SharedState, path: &Path) {
+    let displays_file = path.to_path_buf();
+    state.call(move |state| state.set_displays_file(displays_file)).await;
+    let displays: DisplayRegistry = match tokio::fs::read(path).await {
+        Ok(data) => match serde_json::from_slice(&data) {
+            Ok(displays) => displays,
+            Err(e) => {
+                tracing::error!("Failed to parse {}: {}", path.display(), e);
+                return;
+            }
+        },
+        Err(e) => {
+            tracing::info!("No displays loaded: {}", e);
+            return;
+        }
+    };
+    state.call(move |state| *state.displays_mut() = displays).await;
+}
+
+async fn save_displays(state: SharedState) {
+    let (displays, path) = state
+        .call(|state| (state.displays().clone(), state.displays_file().cloned()))
+        .await;
+    let Some(path) = path else {
+        return;
+    };
+    let data = match serde_json::to_vec_pretty(&displays) {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::error!("Failed to serialize displays: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = tokio::fs::write(&path, data).await {
+        tracing::error!("Failed to write {}: {}", path.display(), e);
+    }
+}
@@ -0,0 +1,55 @@
+use crate::dnd::DndMode;
+use crate::handlers::media::SharedState;
+use serde_json::json;
+use std::collections::HashMap;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+pub async fn dnd_status(state: SharedState) -> Result<impl Reply, Rejection> {
+    let status = state
+        .call(|state| {
+            let dnd = state.dnd();
+            let quiet = state.is_quiet();
+            json!({
+                "quiet": quiet,
+                "mode": dnd.mode,
+                "quiet_hours": dnd.quiet_hours,
+                "held": if quiet { state.queue_len() } else { 0 },
+            })
+        })
+        .await;
+    Ok(warp::reply::json(&status))
+}
+
+// Admin override of the quiet hours; held media is flushed by the queue task once it lifts
+pub async fn set_dnd(
+    form: HashMap<String, String>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    let mode = match form.get("mode").map(|s| s.trim().to_lowercase()).as_deref() {
+        Some("auto") => DndMode::Auto,
+        Some("on") => DndMode::On,
+        Some("off") => DndMode::Off,
+        other => {
+            tracing::warn!("Invalid do not disturb mode: {:?}", other);
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "error": "Mode must be auto, on or off" })),
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+    };
+
+    let quiet = state
+        .call(move |state| {
+            state.set_dnd_mode(mode);
+            state.is_quiet()
+        })
+        .await;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({
+            "quiet": quiet,
+            "mode": mode,
+        })),
+        StatusCode::OK,
+    ))
+}
@@ -0,0 +1,69 @@
+use crate::drawing::Stroke;
+use crate::errors::AppError;
+use crate::handlers::media::SharedState;
+use crate::session;
+use crate::templates::DrawTemplate;
+use crate::websocket;
+use askama::Template;
+use serde::Deserialize;
+use serde_json::json;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+#[derive(Deserialize)]
+pub struct StrokeBatch {
+    strokes: Vec<Stroke>,
+}
+
+// Phone page drawing on the TV
+pub async fn draw_page(csrf_cookie: Option<String>) -> Result<impl Reply, Rejection> {
+    tracing::info!("Serving drawing page");
+    let csrf = session::CsrfToken::from_cookie(csrf_cookie);
+    let template = DrawTemplate {
+        csrf_token: csrf.value.clone(),
+    };
+    match template.render() {
+        Ok(html) => Ok(csrf.attach(warp::reply::html(html))),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err(warp::reject::custom(AppError::RenderError(e)))
+        }
+    }
+}
+
+// Every stroke so far, for displays and drawing pages joining late
+pub async fn canvas(state: SharedState) -> Result<impl Reply, Rejection> {
+    let strokes = state.call(|state| state.canvas().strokes().clone()).await;
+    Ok(warp::reply::json(&json!({ "strokes": strokes })))
+}
+
+// Strokes from a drawing page, relayed to displays with the next batch
+pub async fn draw(batch: StrokeBatch, state: SharedState) -> Result<impl Reply, Rejection> {
+    if let Some(e) = batch
+        .strokes
+        .iter()
+        .find_map(|stroke| stroke.validate().err())
+    {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": e.to_string() })),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    let count = batch.strokes.len();
+    state
+        .call(move |state| state.canvas_mut().draw(batch.strokes))
+        .await;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({ "strokes": count })),
+        StatusCode::OK,
+    ))
+}
+
+pub async fn clear_canvas(
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    state.call(|state| state.canvas_mut().clear()).await;
+    websocket::broadcast_canvas_cleared(&ws_clients).await;
+    Ok(warp::reply::json(&json!({ "strokes": [] })))
+}
@@ -0,0 +1,239 @@
+use crate::handlers::media::SharedState;
+use crate::utils::sanitize_filename;
+use percent_encoding::percent_decode_str;
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use warp::Rejection;
+use warp::http::{HeaderMap, HeaderValue, Method, Response, StatusCode, header};
+use warp::hyper::Body;
+use warp::path::Tail;
+
+/// Validator for a stored file, changes whenever its size or modification time does
+fn etag(len: u64, modified: SystemTime) -> String {
+    let modified = modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("\"{:x}-{:x}\"", len, modified)
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// If-None-Match holds a list of validators, weak ones match too for GET
+fn etag_matches(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(|candidate| candidate.trim().trim_start_matches("W/"))
+        .any(|candidate| candidate == "*" || candidate == etag)
+}
+
+/// Byte range asked for with `Range: bytes=...`, None to send the whole file
+/// Only single ranges are supported, anything else gets the whole file like a plain GET
+/// Err when the range starts past the end of the file
+fn parse_range(header: &str, len: u64) -> Result<Option<Range<u64>>, ()> {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return Ok(None);
+    };
+    let range = match (start.trim(), end.trim()) {
+        // Suffix range, the last n bytes
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return Err(()),
+            Ok(suffix) => len.saturating_sub(suffix)..len,
+            Err(_) => return Ok(None),
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => start..len,
+            Err(_) => return Ok(None),
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => start..end.saturating_add(1).min(len),
+            _ => return Ok(None),
+        },
+    };
+    if range.start >= len {
+        return Err(());
+    }
+    Ok(Some(range))
+}
+
+/// Stream a stored file from the first directory holding it, with ETag, Last-Modified and
+/// Range support so browsers can revalidate cached media and seek in videos
+pub async fn serve_file(
+    dirs: Vec<String>,
+    method: Method,
+    tail: Tail,
+    headers: HeaderMap,
+    state: SharedState,
+) -> Result<Response<Body>, Rejection> {
+    let requested = percent_decode_str(tail.as_str())
+        .decode_utf8_lossy()
+        .to_string();
+    // Only plain file names, no nested paths or traversal
+    let Some(filename) = sanitize_filename(&requested).filter(|name| *name == requested) else {
+        return Err(warp::reject::not_found());
+    };
+    serve_named(&dirs, filename, method, headers, state).await
+}
+
+/// Playlist or segment of a video's HLS stream, from its own directory of the HLS directory
+pub async fn serve_hls(
+    id: String,
+    file: String,
+    method: Method,
+    headers: HeaderMap,
+    state: SharedState,
+) -> Result<Response<Body>, Rejection> {
+    let plain = |name: &str| {
+        let name = percent_decode_str(name).decode_utf8_lossy().to_string();
+        sanitize_filename(&name).filter(|sanitized| *sanitized == name)
+    };
+    let (Some(id), Some(file)) = (plain(&id), plain(&file)) else {
+        return Err(warp::reject::not_found());
+    };
+    let hls = state.call(|state| state.data_dirs().hls()).await;
+    let dir = format!("{}/{}", hls, id);
+    serve_named(&[dir], file, method, headers, state).await
+}
+
+/// Stream a file already known to be a plain file name, as [`serve_file`] does
+pub async fn serve_named(
+    dirs: &[String],
+    filename: String,
+    method: Method,
+    headers: HeaderMap,
+    state: SharedState,
+) -> Result<Response<Body>, Rejection> {
+    let mut found = None;
+    for dir in dirs {
+        let path = format!("{}/{}", dir, filename);
+        if let Ok(file) = tokio::fs::File::open(&path).await
+            && let Ok(metadata) = file.metadata().await
+            && metadata.is_file()
+        {
+            found = Some((file, metadata, path));
+            break;
+        }
+    }
+    let Some((mut file, metadata, path)) = found else {
+        return Err(warp::reject::not_found());
+    };
+
+    let len = metadata.len();
+    let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+    let etag = etag(len, modified);
+    let last_modified = httpdate::fmt_http_date(modified);
+
+    let hit_filename = filename.clone();
+    state
+        .call(move |state| state.record_hit(&hit_filename))
+        .await;
+
+    let header_str = |name| {
+        headers
+            .get(name)
+            .and_then(|value: &HeaderValue| value.to_str().ok())
+    };
+    let not_modified = match header_str(header::IF_NONE_MATCH) {
+        Some(if_none_match) => etag_matches(if_none_match, &etag),
+        None => header_str(header::IF_MODIFIED_SINCE)
+            .and_then(|since| httpdate::parse_http_date(since).ok())
+            // HTTP dates only have whole seconds
+            .is_some_and(|since| unix_secs(modified) <= unix_secs(since)),
+    };
+
+    let response = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::LAST_MODIFIED, &last_modified)
+        .header(header::ACCEPT_RANGES, "bytes");
+    if not_modified {
+        tracing::debug!("Not modified: {}", path);
+        return Ok(response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap_or_default());
+    }
+
+    // A range is only valid against the version the client already has part of
+    let range_header = header_str(header::RANGE)
+        .filter(|_| header_str(header::IF_RANGE).is_none_or(|if_range| if_range.trim() == etag));
+    let range = match range_header
+        .map(|range| parse_range(range, len))
+        .transpose()
+    {
+        Ok(range) => range.flatten(),
+        Err(()) => {
+            return Ok(response
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Body::empty())
+                .unwrap_or_default());
+        }
+    };
+
+    let content_type = mime_guess::from_path(&path).first_or_octet_stream();
+    let response = response.header(header::CONTENT_TYPE, content_type.as_ref());
+    let (response, range) = match range {
+        Some(range) => (
+            response.status(StatusCode::PARTIAL_CONTENT).header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", range.start, range.end - 1, len),
+            ),
+            range,
+        ),
+        None => (response.status(StatusCode::OK), 0..len),
+    };
+    let response = response.header(header::CONTENT_LENGTH, range.end - range.start);
+
+    if method == Method::HEAD {
+        return Ok(response.body(Body::empty()).unwrap_or_default());
+    }
+    if range.start > 0
+        && let Err(e) = file.seek(SeekFrom::Start(range.start)).await
+    {
+        tracing::error!("Failed to seek in {}: {}", path, e);
+        return Err(warp::reject::not_found());
+    }
+    let body = Body::wrap_stream(ReaderStream::new(file.take(range.end - range.start)));
+    Ok(response.body(body).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Ok(Some(0..100)));
+        assert_eq!(parse_range("bytes=900-", 1000), Ok(Some(900..1000)));
+        assert_eq!(parse_range("bytes=-100", 1000), Ok(Some(900..1000)));
+        // Ranges running past the end are cut short
+        assert_eq!(parse_range("bytes=990-2000", 1000), Ok(Some(990..1000)));
+        assert_eq!(parse_range("bytes=1000-", 1000), Err(()));
+        // Unsupported or malformed ranges fall back to the whole file
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), Ok(None));
+        assert_eq!(parse_range("bytes=9-1", 1000), Ok(None));
+        assert_eq!(parse_range("items=0-1", 1000), Ok(None));
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        let etag = etag(1000, UNIX_EPOCH);
+        assert!(etag_matches(&etag, &etag));
+        assert!(etag_matches(&format!("\"other\", W/{}", etag), &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(!etag_matches("\"other\"", &etag));
+    }
+}
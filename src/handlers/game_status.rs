@@ -0,0 +1,8 @@
+use crate::handlers::media::SharedState;
+use serde_json::json;
+use warp::{Rejection, Reply};
+
+pub async fn game_status(state: SharedState) -> Result<impl Reply, Rejection> {
+    let servers = state.call(|state| state.game_status().to_vec()).await;
+    Ok(warp::reply::json(&json!({ "servers": servers })))
+}
@@ -0,0 +1,21 @@
+use crate::graphql::HomiesSchema;
+use async_graphql_warp::{GraphQLBadRequest, GraphQLResponse};
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+pub async fn execute(
+    (schema, request): (HomiesSchema, async_graphql::Request),
+) -> Result<GraphQLResponse, Rejection> {
+    Ok(schema.execute(request).await.into())
+}
+
+// A request that isn't GraphQL gets a 400 instead of warp's 500 for unknown rejections
+pub async fn handle_rejection(rejection: Rejection) -> Result<impl Reply, Rejection> {
+    match rejection.find::<GraphQLBadRequest>() {
+        Some(GraphQLBadRequest(e)) => Ok(warp::reply::with_status(
+            e.to_string(),
+            StatusCode::BAD_REQUEST,
+        )),
+        None => Err(rejection),
+    }
+}
@@ -0,0 +1,111 @@
+use crate::handlers::media::SharedState;
+use crate::media_probe::MediaProbe;
+use crate::session::ClientId;
+use crate::state::{MediaType, UploadRecord, UploadStatus};
+use crate::video_processing::VideoProcessor;
+use serde::Serialize;
+use serde_json::json;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+// One entry of the uploader's timeline
+#[derive(Serialize)]
+struct UploadEntry {
+    id: u64,
+    filename: String,
+    media_type: MediaType,
+    caption: String,
+    status: UploadStatus,
+    queue_position: Option<usize>,
+    uploaded_at: u64, // Unix timestamp in seconds
+    probe: MediaProbe,
+    poster: Option<String>,
+    preview: Option<String>,
+}
+
+impl UploadEntry {
+    fn new(record: &UploadRecord, queue_position: Option<usize>) -> Self {
+        Self {
+            id: record.media.id,
+            filename: record.media.filename.clone(),
+            media_type: record.media.media_type,
+            caption: record.media.caption.clone(),
+            status: record.status,
+            queue_position,
+            uploaded_at: record
+                .media
+                .upload_time
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            probe: record.media.probe.clone(),
+            poster: record.media.poster.clone(),
+            preview: record.media.preview.clone(),
+        }
+    }
+}
+
+pub async fn my_uploads(
+    client: Option<ClientId>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request for uploader timeline");
+    let uploads: Vec<UploadEntry> = match client {
+        Some(client) => {
+            state
+                .call(move |state| {
+                    state
+                        .uploads_for(&client)
+                        .into_iter()
+                        .map(|record| {
+                            UploadEntry::new(record, state.queue_position(record.media.id))
+                        })
+                        .collect()
+                })
+                .await
+        }
+        None => {
+            tracing::warn!("No session or client IP address available");
+            Vec::new()
+        }
+    };
+
+    Ok(warp::reply::json(&json!({ "uploads": uploads })))
+}
+
+pub async fn cancel_upload(
+    id: u64,
+    client: Option<ClientId>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to cancel upload {}", id);
+    let cancelled = match &client {
+        Some(client) => {
+            let client = client.clone();
+            state
+                .call(move |state| state.cancel_queued(id, &client))
+                .await
+        }
+        None => None,
+    };
+
+    let Some(media) = cancelled else {
+        tracing::warn!("No queued upload {} for this client", id);
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": "No queued upload with this id" })),
+            StatusCode::NOT_FOUND,
+        ));
+    };
+
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    let file_path = dirs.upload(&media.filename);
+    if let Err(e) = tokio::fs::remove_file(&file_path).await {
+        tracing::warn!("Failed to remove cancelled file {}: {}", file_path, e);
+    }
+    VideoProcessor::remove_thumbnails(&dirs, &media.filename).await;
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({ "cancelled": id })),
+        StatusCode::OK,
+    ))
+}
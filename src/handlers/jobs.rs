@@ -0,0 +1,34 @@
+use crate::jobs::{Job, SharedJobs};
+use crate::websocket::{self, WsClients};
+use serde_json::json;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+pub async fn list_jobs(jobs: SharedJobs) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request for running jobs");
+    let jobs: Vec<_> = jobs.read().await.jobs().iter().map(Job::status).collect();
+    Ok(warp::reply::json(&json!({ "jobs": jobs })))
+}
+
+pub async fn cancel_job(
+    id: u64,
+    jobs: SharedJobs,
+    ws_clients: WsClients,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to cancel job {}", id);
+    let Some(job) = jobs.write().await.cancel(id) else {
+        tracing::warn!("No running job {}", id);
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": "No running job with this id" })),
+            StatusCode::NOT_FOUND,
+        ));
+    };
+
+    // The worker notices the cancellation, kills its child process and removes partial files
+    websocket::broadcast_job_cancelled(&ws_clients, &job).await;
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({ "cancelled": id })),
+        StatusCode::OK,
+    ))
+}
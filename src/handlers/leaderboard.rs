@@ -0,0 +1,118 @@
+use crate::errors::AppError;
+use crate::handlers::media::SharedState;
+use crate::leaderboard::Leaderboard;
+use crate::session::ClientId;
+use crate::templates::LeaderboardTemplate;
+use crate::websocket;
+use askama::Template;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+// Reactions are shown as is on the overlay, keep them to an emoji or a short word
+const MAX_REACTION_CHARS: usize = 8;
+
+pub async fn leaderboard(state: SharedState) -> Result<impl Reply, Rejection> {
+    let rankings = state.call(|state| state.leaderboard().rankings()).await;
+    Ok(warp::reply::json(&json!({ "rankings": rankings })))
+}
+
+pub async fn leaderboard_page(state: SharedState) -> Result<impl Reply, Rejection> {
+    tracing::info!("Serving leaderboard");
+    let template = LeaderboardTemplate {
+        rankings: state.call(|state| state.leaderboard().rankings()).await,
+    };
+    match template.render() {
+        Ok(html) => Ok(warp::reply::html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err(warp::reject::custom(AppError::RenderError(e)))
+        }
+    }
+}
+
+// Start a new season
+pub async fn reset_leaderboard(state: SharedState) -> Result<impl Reply, Rejection> {
+    tracing::info!("Resetting the leaderboard");
+    state.call(|state| state.leaderboard_mut().reset()).await;
+    save_leaderboard(state).await;
+    Ok(warp::reply::json(&json!({ "rankings": [] })))
+}
+
+// React to what's on screen, scoring for its uploader and floating up on the overlay
+pub async fn react(
+    form: HashMap<String, String>,
+    client: Option<ClientId>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    let emoji = form.get("emoji").map(|emoji| emoji.trim()).unwrap_or_default();
+    if emoji.is_empty() || emoji.chars().count() > MAX_REACTION_CHARS {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": "Reaction must be an emoji" })),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let Some(media_id) = state
+        .call(move |state| state.react(client.as_ref()).map(|media| media.id))
+        .await
+    else {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": "Nothing on screen to react to" })),
+            StatusCode::NOT_FOUND,
+        ));
+    };
+    websocket::broadcast_reaction(&ws_clients, emoji, media_id).await;
+    save_leaderboard(state).await;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({ "emoji": emoji, "media_id": media_id })),
+        StatusCode::OK,
+    ))
+}
+
+/// Restore the scores saved in `path` so the leaderboard survives restarts, and keep saving
+/// them there
+pub async fn load_leaderboard(state: SharedState, path: &Path) {
+    let leaderboard_file = path.to_path_buf();
+    state
+        .call(move |state| state.set_leaderboard_file(leaderboard_file))
+        .await;
+    let leaderboard: Leaderboard = match tokio::fs::read(path).await {
+        Ok(data) => match serde_json::from_slice(&data) {
+            Ok(leaderboard) => leaderboard,
+            Err(e) => {
+                tracing::error!("Failed to parse {}: {}", path.display(), e);
+                return;
+            }
+        },
+        Err(e) => {
+            tracing::info!("No leaderboard loaded: {}", e);
+            return;
+        }
+    };
+    state
+        .call(move |state| *state.leaderboard_mut() = leaderboard)
+        .await;
+}
+
+pub(crate) async fn save_leaderboard(state: SharedState) {
+    let (leaderboard, path) = state
+        .call(|state| (state.leaderboard().clone(), state.leaderboard_file().cloned()))
+        .await;
+    let Some(path) = path else {
+        return;
+    };
+    let data = match serde_json::to_vec_pretty(&leaderboard) {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::error!("Failed to serialize leaderboard: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = tokio::fs::write(&path, data).await {
+        tracing::error!("Failed to write {}: {}", path.display(), e);
+    }
+}
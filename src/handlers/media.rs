@@ -1,57 +1,88 @@
-use crate::{errors::AppError, state::MediaViewState, templates::MediaContentTemplate};
+use crate::{
+    errors::AppError, i18n::Locale, playback, session, session::ClientId, state::MediaInfo,
+    state_actor::StateHandle, tasks, templates::MediaContentTemplate,
+};
 use askama::Template;
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::collections::HashMap;
 use std::time::Duration;
-use tokio::sync::RwLock;
-use tokio::time::sleep;
+use tokio::time::Instant;
 use warp::{Rejection, Reply};
 
-pub type SharedState = Arc<RwLock<MediaViewState>>;
+pub type SharedState = StateHandle;
 
+// Media for this client (only if not viewed yet and not deleted), marked viewed in the same
+// command so concurrent requests from one client can't both get it
+async fn take_media_for_client(state: &SharedState, client: &ClientId) -> Option<MediaInfo> {
+    let lookup_client = client.clone();
+    let (media, burning) = state
+        .call(move |state| {
+            let Some(media) = state.get_last_media_for_client(&lookup_client).cloned() else {
+                return (None, None);
+            };
+            state.mark_viewed(&media.filename, lookup_client);
+            // The first viewer is the only one, nobody else gets it from here on
+            let burning = state.burn(media.id);
+            (Some(media), burning)
+        })
+        .await;
+    if let (Some(media), Some(filename)) = (&media, burning) {
+        let delay = Duration::from_secs(media.play_secs).max(BURN_GRACE);
+        tasks::burn_after(state.clone(), filename, delay);
+    }
+    media
+}
+
+// A burned upload stays on disk long enough for the display to fetch and play it
+const BURN_GRACE: Duration = Duration::from_secs(5);
+
+// Longest a long-poll of /last-media is held open
+const MAX_WAIT_SECS: u64 = 30;
+
+// Current media for a display, long-polled with `?wait=N`: held open up to N seconds until
+// there's media it hasn't seen instead of answering with nothing right away
 pub async fn last_media(
-    addr: Option<SocketAddr>,
+    query: HashMap<String, String>,
+    client: Option<ClientId>,
     state: SharedState,
+    locale: Locale,
 ) -> Result<impl Reply, Rejection> {
     tracing::info!("Received request for last media");
-    // Get client IP
-    sleep(Duration::from_millis(100)).await;
-    let client_ip = addr.map(|socket_addr| socket_addr.ip());
-
-    let state_guard = state.read().await;
+    let wait = query
+        .get("wait")
+        .and_then(|wait| wait.trim().parse::<u64>().ok())
+        .map_or(0, |wait| wait.min(MAX_WAIT_SECS));
 
-    // Get media for this IP (only if not viewed yet and not deleted)
-    let (media_info, should_mark_viewed) = if let Some(ip) = client_ip {
-        if let Some(media) = state_guard.get_last_media_for_ip(ip) {
-            tracing::info!("Found media for IP: {:?}", ip);
-            (Some(media.clone()), true)
-        } else {
-            tracing::info!("No media found for IP: {:?}", ip);
-            (None, false)
-        }
-    } else {
-        tracing::warn!("No client IP address available");
-        (None, false)
-    };
-
-    // If we need to mark as viewed, release read lock and acquire write lock
-    if should_mark_viewed {
-        if let Some(media) = &media_info {
-            let filename = media.filename.clone();
-            drop(state_guard); // Release read lock
-            let mut state_guard = state.write().await; // Acquire write lock
-            if let Some(ip) = client_ip {
-                state_guard.mark_viewed(&filename, ip);
-                tracing::info!("Marked media as viewed: {} for IP: {:?}", filename, ip);
+    let media_info = if let Some(client) = client {
+        // Subscribed before looking so media arriving in between wakes the poll up
+        let mut changes = state.call(|state| state.subscribe_changes()).await;
+        let deadline = Instant::now() + Duration::from_secs(wait);
+        let mut media = take_media_for_client(&state, &client).await;
+        while media.is_none() && wait > 0 {
+            match tokio::time::timeout_at(deadline, changes.changed()).await {
+                Ok(Ok(())) => media = take_media_for_client(&state, &client).await,
+                Ok(Err(_)) | Err(_) => break,
             }
         }
+        match &media {
+            Some(media) => tracing::info!(
+                "Marked media as viewed: {} for client: {:?}",
+                media.filename,
+                client
+            ),
+            None => tracing::info!("No media found for client: {:?}", client),
+        }
+        media
     } else {
-        drop(state_guard); // Release read lock
-    }
+        tracing::warn!("No session or client IP address available");
+        None
+    };
 
-    // Render template
+    // Render template, with the theme for displays that missed a theme_changed event
+    let theme = state.call(|state| state.theme()).await;
     let template = MediaContentTemplate {
         media_info: media_info.as_ref(),
+        theme,
+        locale,
     };
 
     match template.render() {
@@ -66,16 +97,53 @@ pub async fn last_media(
     }
 }
 
-pub async fn index_page() -> Result<impl Reply, Rejection> {
+// Current screen state for displays that just loaded, without waiting for a WebSocket event
+pub async fn display_state(state: SharedState) -> Result<impl Reply, Rejection> {
+    let snapshot = state.call(|state| state.display_snapshot()).await;
+    Ok(warp::reply::json(&snapshot))
+}
+
+// Server clock for displays to line theirs up with, and the epoch of the video on screen
+pub async fn server_time(state: SharedState) -> Result<impl Reply, Rejection> {
+    let (now, playback) = state.call(|state| (state.now(), state.playback())).await;
+    Ok(warp::reply::json(&serde_json::json!({
+        "now_ms": playback::unix_ms(now),
+        "playback": playback,
+    })))
+}
+
+pub async fn overlay_page() -> Result<impl Reply, Rejection> {
+    tracing::info!("Serving overlay page");
+    use crate::templates::OverlayTemplate;
+    use askama::Template;
+
+    match OverlayTemplate.render() {
+        Ok(html) => Ok(warp::reply::html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err(warp::reject::custom(AppError::RenderError(e)))
+        }
+    }
+}
+
+pub async fn index_page(
+    existing_session: Option<String>,
+    state: SharedState,
+    locale: Locale,
+) -> Result<impl Reply, Rejection> {
     tracing::info!("Serving index page");
     use crate::templates::IndexTemplate;
     use askama::Template;
 
-    let template = IndexTemplate;
+    let theme = state.call(|state| state.theme()).await;
+    let template = IndexTemplate { theme, locale };
     match template.render() {
         Ok(html) => {
             tracing::info!("Successfully rendered index template");
-            Ok(warp::reply::html(html))
+            Ok(session::with_session_cookie(
+                warp::reply::html(html),
+                existing_session,
+            ))
         },
         Err(e) => {
             tracing::error!("Template render error: {}", e);
@@ -0,0 +1,80 @@
+use crate::capabilities::Capabilities;
+use crate::config::AppConfig;
+use crate::diagnostics;
+use crate::errors::AppError;
+use crate::handlers::media::SharedState;
+use crate::retry;
+use crate::upload_budget::UploadBudget;
+use crate::websocket::{self, WsClients};
+use serde_json::json;
+use std::sync::Arc;
+use warp::{Rejection, Reply};
+
+pub async fn retry_metrics() -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request for retry metrics");
+    Ok(warp::reply::json(
+        &json!({ "retries": retry::retry_stats() }),
+    ))
+}
+
+pub async fn broadcast_metrics(
+    clients: WsClients,
+    config: Arc<AppConfig>,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request for broadcast metrics");
+    let sender = clients.read().await;
+    Ok(warp::reply::json(&json!({
+        "clients": sender.receiver_count(),
+        "queued": sender.len(),
+        "capacity": config.ws_broadcast_capacity,
+        "lag": websocket::lag_stats(),
+    })))
+}
+
+pub async fn media_hits(state: SharedState) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request for media hit counts");
+    let hits: Vec<_> = state
+        .call(|state| state.hits())
+        .await
+        .into_iter()
+        .map(|(filename, hits)| json!({ "filename": filename, "hits": hits }))
+        .collect();
+    Ok(warp::reply::json(&json!({ "hits": hits })))
+}
+
+pub async fn cleanup_metrics(state: SharedState) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request for cleanup metrics");
+    let metrics = state
+        .call(|state| {
+            let cleanup = state.cleanup();
+            json!({
+                "stats": cleanup.stats(),
+                "pending_retries": cleanup.pending(),
+                "quarantined": cleanup.quarantined(),
+            })
+        })
+        .await;
+    Ok(warp::reply::json(&metrics))
+}
+
+pub async fn upload_metrics(budget: UploadBudget) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request for upload metrics");
+    Ok(warp::reply::json(&budget.stats()))
+}
+
+// Rerun the startup self-check, disk space and directories may have changed since
+pub async fn diagnostics(
+    capabilities: Arc<Capabilities>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request for diagnostics");
+    let data_dirs = state.call(|state| state.data_dirs().clone()).await;
+    let run = move || diagnostics::Report::run(&capabilities, &data_dirs);
+    let report = tokio::task::spawn_blocking(run)
+        .await
+        .map_err(|e| {
+            tracing::error!("Diagnostics failed: {}", e);
+            warp::reject::custom(AppError::IoError(std::io::Error::other(e)))
+        })?;
+    Ok(warp::reply::json(&report))
+}
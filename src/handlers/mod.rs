@@ -1,2 +1,31 @@
+pub mod archive;
+pub mod audit;
+pub mod banner;
+pub mod bans;
+pub mod camera;
+pub mod chat;
+pub mod clips;
+pub mod displays;
+pub mod dnd;
+pub mod drawing;
+pub mod files;
+pub mod game_status;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod history;
+pub mod jobs;
+pub mod leaderboard;
 pub mod media;
+pub mod metrics;
+pub mod music;
+pub mod now_playing;
+pub mod polls;
+pub mod share;
+pub mod soundboard;
+pub mod stats;
+pub mod theme;
+pub mod timers;
+pub mod trash;
+pub mod unlock;
 pub mod upload;
+pub mod word_filter;
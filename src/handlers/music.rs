@@ -0,0 +1,110 @@
+use crate::handlers::media::SharedState;
+use crate::websocket;
+use serde_json::json;
+use std::collections::HashMap;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+pub async fn music_status(state: SharedState) -> Result<impl Reply, Rejection> {
+    let (status, playlist) = state
+        .call(|state| (state.music().status(), state.music().playlist().to_vec()))
+        .await;
+    Ok(warp::reply::json(&json!({
+        "status": status,
+        "playlist": playlist,
+    })))
+}
+
+// Remote control of the music channel: play (optionally a given track), pause, next or volume
+pub async fn music_control(
+    action: String,
+    form: HashMap<String, String>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received music {} request", action);
+    let field = |name: &str| {
+        form.get(name)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    };
+
+    let status = match action.as_str() {
+        "play" => {
+            let track = match field("track").map(str::parse::<u64>) {
+                Some(Ok(track)) => Some(track),
+                Some(Err(_)) => return Ok(bad_request("Track must be a track id")),
+                None => None,
+            };
+            state.call(move |state| state.music_mut().play(track)).await
+        }
+        "pause" => Some(state.call(|state| state.music_mut().pause()).await),
+        "next" => state.call(|state| state.music_mut().skip()).await,
+        "volume" => {
+            let Some(Ok(volume)) = field("volume").map(str::parse::<u8>) else {
+                return Ok(bad_request("Volume must be a number from 0 to 100"));
+            };
+            Some(
+                state
+                    .call(move |state| state.music_mut().set_volume(volume))
+                    .await,
+            )
+        }
+        _ => {
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "error": "Unknown music action" })),
+                StatusCode::NOT_FOUND,
+            ));
+        }
+    };
+
+    let Some(status) = status else {
+        tracing::warn!("No music track to {}", action);
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": "Track not found" })),
+            StatusCode::NOT_FOUND,
+        ));
+    };
+    websocket::broadcast_music(&ws_clients, &action, &status).await;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&status),
+        StatusCode::OK,
+    ))
+}
+
+fn bad_request(error: &str) -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&json!({ "error": error })),
+        StatusCode::BAD_REQUEST,
+    )
+}
+
+/// Register the music already stored on disk so the playlist survives restarts
+pub async fn load_music_library(state: SharedState) {
+    let music = state.call(|state| state.data_dirs().music.clone()).await;
+    let mut entries = match tokio::fs::read_dir(&music).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::info!("No music library loaded: {}", e);
+            return;
+        }
+    };
+
+    let mut filenames = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Some(filename) = entry.file_name().to_str() {
+            filenames.push(filename.to_string());
+        }
+    }
+    filenames.sort();
+
+    let count = filenames.len();
+    state
+        .call(move |state| {
+            for filename in &filenames {
+                state.music_mut().add_track(filename);
+            }
+        })
+        .await;
+    tracing::info!("Loaded {} tracks into the music playlist", count);
+}
@@ -0,0 +1,59 @@
+use crate::handlers::media::SharedState;
+use crate::now_playing::NowPlaying;
+use crate::websocket;
+use serde_json::json;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+pub async fn now_playing(state: SharedState) -> Result<impl Reply, Rejection> {
+    let track = state.call(|state| state.now_playing().cloned()).await;
+    Ok(warp::reply::json(&json!({ "track": track })))
+}
+
+// Pushed by a phone shortcut, browser extension or bridge script whenever the track changes
+pub async fn set_now_playing(
+    track: NowPlaying,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    let track = match track.validate() {
+        Ok(track) => track,
+        Err(e) => {
+            tracing::warn!("Invalid now playing track: {}", e);
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "error": e.to_string() })),
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+    };
+    tracing::info!("Now playing: {}", track.title);
+    update(Some(track.clone()), state, ws_clients).await;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({ "track": track })),
+        StatusCode::OK,
+    ))
+}
+
+pub async fn clear_now_playing(
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Now playing cleared");
+    update(None, state, ws_clients).await;
+    Ok(warp::reply::json(&json!({ "track": null })))
+}
+
+/// Store the current track and tell displays, only when it changed
+pub async fn update(
+    track: Option<NowPlaying>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) {
+    let changed_to = track.clone();
+    let changed = state
+        .call(move |state| state.set_now_playing(changed_to))
+        .await;
+    if changed {
+        websocket::broadcast_now_playing(&ws_clients, track.as_ref()).await;
+    }
+}
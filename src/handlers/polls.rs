@@ -0,0 +1,125 @@
+use crate::config::AppConfig;
+use crate::handlers::media::SharedState;
+use crate::polls::{POLL_DURATION_RANGE, PollError};
+use crate::session::ClientId;
+use crate::websocket;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+fn error_reply(error: PollError) -> warp::reply::WithStatus<warp::reply::Json> {
+    let status = match error {
+        PollError::NotOpen => StatusCode::NOT_FOUND,
+        PollError::AlreadyOpen | PollError::AlreadyVoted => StatusCode::CONFLICT,
+        PollError::NoQuestion | PollError::Options | PollError::NoSuchOption => {
+            StatusCode::BAD_REQUEST
+        }
+    };
+    warp::reply::with_status(
+        warp::reply::json(&json!({ "error": error.to_string() })),
+        status,
+    )
+}
+
+// The open poll, or the results of the last one
+pub async fn current_poll(state: SharedState) -> Result<impl Reply, Rejection> {
+    let poll = state
+        .call(|state| {
+            let now = state.now();
+            state.polls().current().map(|poll| poll.status(now))
+        })
+        .await;
+    Ok(warp::reply::json(&json!({ "poll": poll })))
+}
+
+// Start a poll from a question and repeated option fields, open for `duration` seconds
+pub async fn start_poll(
+    form: Vec<(String, String)>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+) -> Result<impl Reply, Rejection> {
+    let field = |name: &str| {
+        form.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    let question = field("question").unwrap_or_default();
+    let options: Vec<String> = form
+        .iter()
+        .filter(|(key, _)| key == "option")
+        .map(|(_, value)| value.clone())
+        .collect();
+    let (min_secs, max_secs) = POLL_DURATION_RANGE;
+    let secs = field("duration")
+        .and_then(|duration| duration.trim().parse::<u64>().ok())
+        .unwrap_or(config.poll_secs)
+        .clamp(min_secs, max_secs);
+
+    let started = state
+        .call(move |state| {
+            let now = state.now();
+            state
+                .polls_mut()
+                .start(&question, &options, Duration::from_secs(secs), now)
+                .map(|poll| poll.status(now))
+        })
+        .await;
+    match started {
+        Ok(poll) => {
+            tracing::info!("Started poll {} for {}s: {}", poll.id, secs, poll.question);
+            websocket::broadcast_poll(&ws_clients, "poll", &poll).await;
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "poll": poll })),
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => Ok(error_reply(e)),
+    }
+}
+
+// One vote per session or IP address, by option index
+pub async fn vote(
+    id: u64,
+    form: HashMap<String, String>,
+    client: Option<ClientId>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    let Some(option) = form
+        .get("option")
+        .and_then(|option| option.trim().parse::<usize>().ok())
+    else {
+        return Ok(error_reply(PollError::NoSuchOption));
+    };
+    let Some(client) = client else {
+        tracing::warn!("Vote without a session or client IP address");
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": "Voting needs a session" })),
+            StatusCode::BAD_REQUEST,
+        ));
+    };
+
+    let voted = state
+        .call(move |state| {
+            let now = state.now();
+            state
+                .polls_mut()
+                .vote(id, option, client)
+                .map(|poll| poll.status(now))
+        })
+        .await;
+    match voted {
+        Ok(poll) => {
+            websocket::broadcast_poll(&ws_clients, "poll_tally", &poll).await;
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "poll": poll })),
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => Ok(error_reply(e)),
+    }
+}
@@ -0,0 +1,91 @@
+use crate::config::AppConfig;
+use crate::handlers::files;
+use crate::handlers::media::SharedState;
+use crate::share::ShareError;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use warp::http::{HeaderMap, Method, StatusCode};
+use warp::{Rejection, Reply};
+
+// Signed link to an archived media for people outside the LAN, valid for the configured time
+pub async fn create_share(
+    id: u64,
+    state: SharedState,
+    config: Arc<AppConfig>,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to share archived media {}", id);
+    let Some(share) = &config.share else {
+        return Ok(reply(
+            StatusCode::NOT_FOUND,
+            json!({ "error": "Share links are not configured" }),
+        ));
+    };
+    let (archived, now) = state
+        .call(move |state| (state.get_archived(id).is_some(), state.now()))
+        .await;
+    if !archived {
+        return Ok(reply(
+            StatusCode::NOT_FOUND,
+            json!({ "error": "No such archived media" }),
+        ));
+    }
+
+    let expires_at = unix_secs(now) + share.ttl_secs;
+    let token = share.token(id, expires_at);
+    tracing::info!("Shared archived media {} until {}", id, expires_at);
+    Ok(reply(
+        StatusCode::OK,
+        json!({ "url": share.url(&token), "expires_at": expires_at }),
+    ))
+}
+
+// Serve the archived media a share token was signed for, nothing else is reachable through it
+pub async fn serve_share(
+    token: String,
+    method: Method,
+    headers: HeaderMap,
+    state: SharedState,
+    config: Arc<AppConfig>,
+) -> Result<warp::reply::Response, Rejection> {
+    let Some(share) = &config.share else {
+        return Err(warp::reject::not_found());
+    };
+    let now = unix_secs(state.call(|state| state.now()).await);
+    let id = match share.verify(&token, now) {
+        Ok(id) => id,
+        Err(e) => {
+            tracing::warn!("Rejected share link: {}", e);
+            let status = match e {
+                ShareError::Expired => StatusCode::GONE,
+                ShareError::Malformed | ShareError::BadSignature => StatusCode::FORBIDDEN,
+            };
+            return Ok(warp::reply::with_status(e.to_string(), status).into_response());
+        }
+    };
+
+    // The entry may have been unpinned since the link was handed out
+    let Some((filename, archive)) = state
+        .call(move |state| {
+            let filename = state.get_archived(id)?.filename.clone();
+            Some((filename, state.data_dirs().archive.clone()))
+        })
+        .await
+    else {
+        return Err(warp::reject::not_found());
+    };
+    files::serve_named(&[archive], filename, method, headers, state).await
+}
+
+fn unix_secs(time: std::time::SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn reply(
+    status: StatusCode,
+    body: serde_json::Value,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(warp::reply::json(&body), status)
+}
@@ -0,0 +1,194 @@
+use crate::{
+    audio_processing::AudioProcessor,
+    errors::AppError, handlers::leaderboard, handlers::media::SharedState, session,
+    session::ClientId, state::SoundInfo, templates::SoundboardTemplate, websocket,
+};
+use askama::Template;
+use percent_encoding::percent_decode_str;
+use serde_json::json;
+use std::collections::HashMap;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+pub async fn soundboard_page(
+    csrf_cookie: Option<String>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Serving soundboard");
+    let csrf = session::CsrfToken::from_cookie(csrf_cookie);
+    let (sounds, music, playlist) = state
+        .call(|state| {
+            (
+                state.sounds().to_vec(),
+                state.music().status(),
+                state.music().playlist().to_vec(),
+            )
+        })
+        .await;
+    let template = SoundboardTemplate {
+        csrf_token: csrf.value.clone(),
+        sounds,
+        music,
+        playlist,
+    };
+    match template.render() {
+        Ok(html) => {
+            tracing::info!("Successfully rendered soundboard template");
+            Ok(csrf.attach(warp::reply::html(html)))
+        }
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err(warp::reject::custom(AppError::RenderError(e)))
+        }
+    }
+}
+
+pub async fn list_sounds(state: SharedState) -> Result<impl Reply, Rejection> {
+    let sounds = state.call(|state| state.sounds().to_vec()).await;
+    Ok(warp::reply::json(&json!({ "sounds": sounds })))
+}
+
+pub async fn play_sound(
+    sound_id: u64,
+    client: Option<ClientId>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to play sound {}", sound_id);
+    let scored = client.is_some();
+    let sound = state
+        .call(move |state| {
+            let sound = state.get_sound(sound_id).cloned();
+            sound.inspect(|sound| state.record_sound_play(&sound.filename, client.as_ref()))
+        })
+        .await;
+    Ok(play(sound, scored, state, ws_clients).await)
+}
+
+pub async fn play_hotkey(
+    hotkey: u8,
+    client: Option<ClientId>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to play hotkey {}", hotkey);
+    let scored = client.is_some();
+    let sound = state
+        .call(move |state| {
+            let sound = state.get_sound_by_hotkey(hotkey).cloned();
+            sound.inspect(|sound| state.record_sound_play(&sound.filename, client.as_ref()))
+        })
+        .await;
+    Ok(play(sound, scored, state, ws_clients).await)
+}
+
+// Bind (or clear, with an empty value) the numeric hotkey of a sound
+pub async fn set_hotkey(
+    sound_id: u64,
+    form: HashMap<String, String>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    let hotkey_str = form.get("hotkey").map(|s| s.trim()).unwrap_or_default();
+    let hotkey = if hotkey_str.is_empty() {
+        None
+    } else {
+        match hotkey_str.parse::<u8>() {
+            Ok(key) if key <= 9 => Some(key),
+            _ => {
+                tracing::warn!("Invalid hotkey: {}", hotkey_str);
+                return Ok(warp::reply::with_status(
+                    warp::reply::json(&json!({ "error": "Hotkey must be a digit from 0 to 9" })),
+                    StatusCode::BAD_REQUEST,
+                ));
+            }
+        }
+    };
+
+    match state
+        .call(move |state| state.set_sound_hotkey(sound_id, hotkey))
+        .await
+    {
+        Some(sound) => {
+            tracing::info!("Hotkey {:?} bound to sound {}", hotkey, sound.filename);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&sound),
+                StatusCode::OK,
+            ))
+        }
+        None => Ok(sound_not_found()),
+    }
+}
+
+pub async fn sound_waveform(name: String, state: SharedState) -> Result<impl Reply, Rejection> {
+    let name = percent_decode_str(&name).decode_utf8_lossy().to_string();
+    tracing::info!("Received request for waveform of {}", name);
+
+    let sounds = state.call(|state| state.data_dirs().sounds.clone()).await;
+    match AudioProcessor::load_waveform(&sounds, &name).await {
+        Ok(peaks) => Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "sound": name, "peaks": peaks })),
+            StatusCode::OK,
+        )),
+        Err(e) => {
+            tracing::warn!("No waveform for {}: {}", name, e);
+            Ok(sound_not_found())
+        }
+    }
+}
+
+/// Register the sounds already stored on disk so the soundboard survives restarts
+pub async fn load_sound_library(state: SharedState) {
+    let sounds = state.call(|state| state.data_dirs().sounds.clone()).await;
+    let mut entries = match tokio::fs::read_dir(&sounds).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::info!("No sound library loaded: {}", e);
+            return;
+        }
+    };
+
+    let mut filenames = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Some(filename) = entry.file_name().to_str() {
+            filenames.push(filename.to_string());
+        }
+    }
+    filenames.sort();
+
+    let count = filenames.len();
+    state
+        .call(move |state| {
+            for filename in &filenames {
+                state.add_sound(filename);
+            }
+        })
+        .await;
+    tracing::info!("Loaded {} sounds into the soundboard", count);
+}
+
+// Play a sound for everyone, saving the leaderboard if the play scored
+async fn play(
+    sound: Option<SoundInfo>,
+    scored: bool,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    match sound {
+        Some(sound) => {
+            websocket::broadcast_new_song(&ws_clients, sound.filename.clone()).await;
+            if scored {
+                leaderboard::save_leaderboard(state).await;
+            }
+            warp::reply::with_status(warp::reply::json(&sound), StatusCode::OK)
+        }
+        None => sound_not_found(),
+    }
+}
+
+fn sound_not_found() -> warp::reply::WithStatus<warp::reply::Json> {
+    tracing::warn!("Sound not found");
+    warp::reply::with_status(
+        warp::reply::json(&json!({ "error": "Sound not found" })),
+        StatusCode::NOT_FOUND,
+    )
+}
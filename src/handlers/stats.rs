@@ -0,0 +1,46 @@
+use crate::errors::AppError;
+use crate::handlers::media::SharedState;
+use crate::stats::StatsReport;
+use crate::templates::RecapTemplate;
+use askama::Template;
+use std::collections::HashMap;
+use std::time::Duration;
+use warp::{Rejection, Reply};
+
+const DEFAULT_DAYS: u64 = 7;
+
+// Report over the last `days` (a week unless asked otherwise), up to the month stats are kept
+async fn report(query: &HashMap<String, String>, state: SharedState) -> StatsReport {
+    let days = query
+        .get("days")
+        .and_then(|days| days.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_DAYS)
+        .clamp(1, 31);
+    let period = Duration::from_secs(days * 24 * 3600);
+    state.call(move |state| state.stats_report(period)).await
+}
+
+pub async fn stats(
+    query: HashMap<String, String>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::json(&report(&query, state).await))
+}
+
+/// The recap as a full screen page, what the display opens on schedule
+pub async fn recap_page(
+    query: HashMap<String, String>,
+    state: SharedState,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Serving stats recap");
+    let template = RecapTemplate {
+        report: report(&query, state).await,
+    };
+    match template.render() {
+        Ok(html) => Ok(warp::reply::html(html)),
+        Err(e) => {
+            tracing::error!("Template render error: {}", e);
+            Err(warp::reject::custom(AppError::RenderError(e)))
+        }
+    }
+}
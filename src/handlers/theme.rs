@@ -0,0 +1,37 @@
+use crate::handlers::media::SharedState;
+use crate::theme::Theme;
+use crate::websocket;
+use serde_json::json;
+use std::collections::HashMap;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+pub async fn current_theme(state: SharedState) -> Result<impl Reply, Rejection> {
+    let theme = state.call(|state| state.theme()).await;
+    Ok(warp::reply::json(&json!({ "theme": theme })))
+}
+
+// Admin switch of the display theme, screens restyle live on the theme_changed event
+pub async fn set_theme(
+    form: HashMap<String, String>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    let Some(theme) = form
+        .get("theme")
+        .and_then(|theme| theme.parse::<Theme>().ok())
+    else {
+        tracing::warn!("Invalid theme: {:?}", form.get("theme"));
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": "Theme must be dark, light or party" })),
+            StatusCode::BAD_REQUEST,
+        ));
+    };
+
+    state.call(move |state| state.set_theme(theme)).await;
+    websocket::broadcast_theme(&ws_clients, theme).await;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({ "theme": theme })),
+        StatusCode::OK,
+    ))
+}
@@ -0,0 +1,144 @@
+use crate::audio_processing::parse_timestamp;
+use crate::handlers::media::SharedState;
+use crate::timers::{TimerPhase, TimerStatus};
+use crate::websocket;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+fn bad_request(message: &str) -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&json!({ "error": message })),
+        StatusCode::BAD_REQUEST,
+    )
+}
+
+fn not_found() -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&json!({ "error": "No running timer with this id" })),
+        StatusCode::NOT_FOUND,
+    )
+}
+
+// Length given as seconds, "mm:ss" or "hh:mm:ss"
+fn parse_duration(form: &HashMap<String, String>, field: &str) -> Option<Duration> {
+    form.get(field)
+        .and_then(|value| parse_timestamp(value))
+        .filter(|secs| *secs >= 1.0)
+        .map(Duration::from_secs_f64)
+}
+
+pub async fn list_timers(state: SharedState) -> Result<impl Reply, Rejection> {
+    let timers: Vec<TimerStatus> = state
+        .call(|state| {
+            let now = state.now();
+            state
+                .timers()
+                .running()
+                .iter()
+                .map(|timer| timer.status(now, TimerPhase::Running))
+                .collect()
+        })
+        .await;
+    Ok(warp::reply::json(&json!({ "timers": timers })))
+}
+
+// Start a countdown on screen, e.g. label "pizza" and duration "12:00"
+pub async fn start_timer(
+    form: HashMap<String, String>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    let Some(duration) = parse_duration(&form, "duration") else {
+        return Ok(bad_request("Duration must be seconds, mm:ss or hh:mm:ss"));
+    };
+    let label = form.get("label").cloned().unwrap_or_default();
+
+    let started = state
+        .call(move |state| {
+            let now = state.now();
+            state
+                .timers_mut()
+                .start(&label, duration, now)
+                .map(|timer| timer.status(now, TimerPhase::Running))
+        })
+        .await;
+    match started {
+        Ok(status) => {
+            tracing::info!(
+                "Started timer {} ({}) for {:?}",
+                status.id,
+                status.label,
+                duration
+            );
+            websocket::broadcast_timer(&ws_clients, &status).await;
+            Ok(warp::reply::with_status(
+                warp::reply::json(&status),
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => Ok(bad_request(&e.to_string())),
+    }
+}
+
+pub async fn cancel_timer(
+    id: u64,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    let cancelled = state
+        .call(move |state| {
+            let now = state.now();
+            state
+                .timers_mut()
+                .cancel(id)
+                .map(|timer| timer.status(now, TimerPhase::Cancelled))
+        })
+        .await;
+    let Some(status) = cancelled else {
+        return Ok(not_found());
+    };
+    tracing::info!("Cancelled timer {} ({})", status.id, status.label);
+    websocket::broadcast_timer(&ws_clients, &status).await;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&status),
+        StatusCode::OK,
+    ))
+}
+
+// Add time to a running countdown, "by" takes the same formats as the duration
+pub async fn extend_timer(
+    id: u64,
+    form: HashMap<String, String>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+) -> Result<impl Reply, Rejection> {
+    let Some(by) = parse_duration(&form, "by") else {
+        return Ok(bad_request("Extension must be seconds, mm:ss or hh:mm:ss"));
+    };
+    let extended = state
+        .call(move |state| {
+            let now = state.now();
+            state
+                .timers_mut()
+                .extend(id, by, now)
+                .map(|timer| timer.status(now, TimerPhase::Running))
+        })
+        .await;
+    let Some(status) = extended else {
+        return Ok(not_found());
+    };
+    tracing::info!(
+        "Extended timer {} ({}) by {:?}",
+        status.id,
+        status.label,
+        by
+    );
+    websocket::broadcast_timer(&ws_clients, &status).await;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&status),
+        StatusCode::OK,
+    ))
+}
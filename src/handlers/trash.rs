@@ -0,0 +1,97 @@
+use crate::handlers::media::SharedState;
+use percent_encoding::percent_decode_str;
+use serde_json::json;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+pub async fn list_trash(state: SharedState) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request for the trash");
+    let entries = state.call(|state| state.trash().entries()).await;
+    Ok(warp::reply::json(&json!({ "trash": entries })))
+}
+
+// Bring an upload deleted too early back to the uploads directory
+pub async fn restore(filename: String, state: SharedState) -> Result<impl Reply, Rejection> {
+    let filename = percent_decode_str(&filename)
+        .decode_utf8_lossy()
+        .to_string();
+    tracing::info!("Received request to restore {}", filename);
+
+    // Only names the trash knows about, so the path can't point anywhere else
+    let known = filename.clone();
+    let (known, dirs) = state
+        .call(move |state| (state.trash().contains(&known), state.data_dirs().clone()))
+        .await;
+    if !known {
+        return Ok(reply(
+            StatusCode::NOT_FOUND,
+            json!({ "error": "Not in the trash" }),
+        ));
+    }
+    let target = dirs.upload(&filename);
+    if tokio::fs::try_exists(&target).await.unwrap_or(false) {
+        return Ok(reply(
+            StatusCode::CONFLICT,
+            json!({ "error": "An upload with this name already exists" }),
+        ));
+    }
+    if let Err(e) = tokio::fs::create_dir_all(&dirs.uploads).await {
+        tracing::error!("Failed to create uploads directory: {}", e);
+        return Ok(reply(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": "Restore failed" }),
+        ));
+    }
+    if let Err(e) = tokio::fs::rename(dirs.trashed(&filename), &target).await {
+        tracing::error!("Failed to restore {}: {}", filename, e);
+        return Ok(reply(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "error": "Restore failed" }),
+        ));
+    }
+
+    let restored = filename.clone();
+    state
+        .call(move |state| state.restore_from_trash(&restored))
+        .await;
+    tracing::info!("Restored {} from the trash", filename);
+    Ok(reply(
+        StatusCode::OK,
+        json!({ "filename": filename, "url": format!("/uploads/{}", filename) }),
+    ))
+}
+
+fn reply(
+    status: StatusCode,
+    body: serde_json::Value,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(warp::reply::json(&body), status)
+}
+
+/// Register the uploads already in the trash, their grace period restarts from now
+pub async fn load_trash(state: SharedState) {
+    let trash = state.call(|state| state.data_dirs().trash.clone()).await;
+    let mut entries = match tokio::fs::read_dir(&trash).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::info!("No trash loaded: {}", e);
+            return;
+        }
+    };
+
+    let mut filenames = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Some(filename) = entry.file_name().to_str() {
+            filenames.push(filename.to_string());
+        }
+    }
+    tracing::info!("Loaded {} trashed uploads", filenames.len());
+    state
+        .call(move |state| {
+            let now = state.now();
+            for filename in &filenames {
+                state.trash_mut().add(filename, now);
+            }
+        })
+        .await;
+}
@@ -0,0 +1,45 @@
+use crate::handlers::media::SharedState;
+use crate::i18n::{Locale, Msg};
+use crate::state::Admission;
+use crate::websocket;
+use std::collections::HashMap;
+use warp::{Rejection, Reply};
+
+// Release the uploads held with a passphrase, e.g. once the uploader is home to see them
+pub async fn unlock(
+    form: HashMap<String, String>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    locale: Locale,
+) -> Result<impl Reply, Rejection> {
+    let passphrase = form
+        .get("passphrase")
+        .map(|p| p.trim().to_string())
+        .unwrap_or_default();
+    if passphrase.is_empty() {
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::NoPassphrase))));
+    }
+
+    let (released, playback) = state
+        .call(move |state| (state.unlock(&passphrase), state.playback()))
+        .await;
+    if released.is_empty() {
+        tracing::warn!("Unlock attempt matched no held media");
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::NothingHeld))));
+    }
+
+    let mut queued = 0;
+    for (media, admission) in &released {
+        match admission {
+            Admission::Shown => websocket::broadcast_media(&ws_clients, media, playback).await,
+            _ => queued += 1,
+        }
+    }
+    tracing::info!("Unlocked {} media, {} queued", released.len(), queued);
+    let message = if queued > 0 {
+        locale.format(Msg::UnlockedSomeQueued, &[&released.len(), &queued])
+    } else {
+        locale.format(Msg::Unlocked, &[&released.len()])
+    };
+    Ok(warp::reply::html(format!("<p>{}</p>", message)))
+}
@@ -1,30 +1,75 @@
 use crate::{
+    audio_processing::{AudioProcessor, Visualizer, parse_timestamp},
+    capabilities::{Capabilities, MAX_VIDEO_SECS},
+    config::AppConfig,
+    data_dirs::DataDirs,
+    handlers::leaderboard,
     errors::AppError,
-    state::{MediaInfo, MediaType, MediaViewState, SoundInfo},
+    i18n::{self, Locale, Msg},
+    imagine::MAX_PROMPT_CHARS,
+    filter_graph::{CaptionAnimation, VideoEffect},
+    presets::{self, UploadPreset},
+    jobs::{self, JobKind, JobStage, SharedJobs},
+    media_probe::MediaProbe,
+    remote_media::{self, RemoteKind},
+    session::{self, ClientId},
+    state::{Admission, CaptionTranslation, MediaInfo, MediaSource, MediaType, passphrase_hash},
+    state_actor::StateHandle,
     templates::UploadTemplate,
+    types::{UploadOutcome, UploadResponse},
     utils::{sanitize_filename, validate_file_path},
-    video_processing::VideoProcessor,
+    validation,
+    video_processing::{
+        EncodeOptions, Framing, ImageFormat, LOW_RENDITION_HEIGHT, QualityProfile, VideoPlatform,
+        VideoProcessor, Watermark,
+    },
+    virus_scan::ScanVerdict,
+    staging::StagedEntry,
+    upload_budget::{UploadBudget, UploadPermit},
+    word_filter::Verdict,
+    events::{Event, WarningReason},
 };
 use askama::Template;
 use bytes::Buf;
 use futures_util::StreamExt;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 use tokio::{fs::File, io::AsyncWriteExt};
-use warp::{Rejection, Reply, multipart::FormData};
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply, multipart::FormData};
 
 use crate::websocket;
 
 // Shared state type
-pub type SharedState = Arc<RwLock<MediaViewState>>;
+pub type SharedState = StateHandle;
 
-pub async fn upload_form() -> Result<impl Reply, Rejection> {
+pub async fn upload_form(
+    existing_session: Option<String>,
+    csrf_cookie: Option<String>,
+    config: Arc<AppConfig>,
+    capabilities: Arc<Capabilities>,
+) -> Result<impl Reply, Rejection> {
     tracing::info!("Serving upload form");
-    let template = UploadTemplate;
+    let csrf = session::CsrfToken::from_cookie(csrf_cookie);
+    let template = UploadTemplate {
+        csrf_token: csrf.value.clone(),
+        watermark: config.watermark.is_some(),
+        capabilities: (*capabilities).clone(),
+        tiktok_enabled: config.platforms.is_enabled(VideoPlatform::TikTok),
+        translation_enabled: config.translation.is_some(),
+        auto_captions_enabled: config.auto_captions.is_some(),
+        chromakey: config.chromakey.clone(),
+        imagine_enabled: config.imagine.is_some(),
+        expires_in_min_secs: config.cleanup.expires_in_min_secs,
+        expires_in_max_secs: config.cleanup.expires_in_max_secs,
+        presets: config.presets.iter().map(|preset| preset.name.clone()).collect(),
+    };
     match template.render() {
         Ok(html) => {
             tracing::info!("Successfully rendered upload template");
-            Ok(warp::reply::html(html))
+            Ok(csrf.attach(session::with_session_cookie(
+                warp::reply::html(html),
+                existing_session,
+            )))
         },
         Err(e) => {
             tracing::error!("Template render error: {}", e);
@@ -35,98 +80,752 @@ pub async fn upload_form() -> Result<impl Reply, Rejection> {
 
 pub async fn upload_image(
     mut form: FormData,
-    _addr: Option<std::net::SocketAddr>,
+    client: Option<ClientId>,
     state: SharedState,
     ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+    locale: Locale,
+    format: ReplyFormat,
 ) -> Result<impl Reply, Rejection> {
     tracing::info!("Processing image upload");
     // Parse form data
-    let form_data = parse_form_data(&mut form).await?;
+    let mut form_data = parse_form_data(&mut form).await?;
+    form_data.source = format.source();
 
     // Only proceed if we have a filename
-    if !form_data.filename.is_empty() {
-        tracing::info!("Processing file: {}", form_data.filename);
-        // Validate file type
-        if !is_valid_media_type(&form_data.filename) {
-            tracing::warn!("Invalid file type uploaded: {}", form_data.filename);
-            return Ok(warp::reply::html(
-                "<p>Invalid file type! Only images and videos are allowed.</p>".to_string(),
-            ));
-        }
-
-        // Save file to disk
-        let file_size = save_uploaded_file(&form_data.filename, &form_data.file_data).await?;
-        tracing::info!("Saved file to disk, size: {} bytes", file_size);
-
-        // Check file size limit
-        if file_size > 100 * 1024 * 1024 {
-            // 100MB
-            tracing::warn!("File too large: {} bytes", file_size);
-            return Ok(warp::reply::html(
-                "<p>File too large! Maximum size is 100MB.</p>".to_string(),
-            ));
-        }
-
-        // Store values before move
-        let mut filename = form_data.filename.clone();
-        let caption = form_data.caption.clone();
-
-        // Determine media type and adjust duration
-        let media_type = detect_media_type(&form_data.filename);
-        tracing::info!("Detected media type: {:?}", media_type);
-        let final_duration = match media_type {
-            MediaType::Video => 999999, // Special value for videos (no auto-refresh)
-            MediaType::Image => form_data.duration_secs,
-        };
+    let response = if !form_data.filename.is_empty() {
+        publish_media(
+            form_data,
+            Destination::Screen,
+            client,
+            state,
+            ws_clients,
+            &config,
+            locale,
+        )
+        .await?
+    } else {
+        tracing::warn!("No media uploaded");
+        UploadResponse::refused(locale.t(Msg::NoMediaUploaded))
+    };
+    Ok(upload_reply(response, format, locale))
+}
+
+// Store and process an upload like /upload, keeping it off screen until POST /publish/{id}
+pub async fn stage_upload(
+    mut form: FormData,
+    client: Option<ClientId>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+    locale: Locale,
+    format: ReplyFormat,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Processing staged upload");
+    let mut form_data = parse_form_data(&mut form).await?;
+    form_data.source = format.source();
+    let response = if !form_data.filename.is_empty() {
+        publish_media(
+            form_data,
+            Destination::Stage,
+            client,
+            state,
+            ws_clients,
+            &config,
+            locale,
+        )
+        .await?
+    } else {
+        tracing::warn!("No media staged");
+        UploadResponse::refused(locale.t(Msg::NoMediaUploaded))
+    };
+    Ok(upload_reply(response, format, locale))
+}
+
+// Put a staged upload on screen through the regular display flow
+pub async fn publish_staged(
+    id: u64,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    locale: Locale,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Received request to publish staged upload {}", id);
+    let Some(media_info) = state
+        .call(move |state| {
+            let now = state.now();
+            state.staging_mut().take(id, now)
+        })
+        .await
+    else {
+        tracing::warn!("No staged upload {}", id);
+        return Ok(warp::reply::with_status(
+            warp::reply::html(format!("<p>{}</p>", locale.t(Msg::NoStagedUpload))),
+            StatusCode::NOT_FOUND,
+        ));
+    };
 
-        // Process video with caption overlay if it's a video and has a caption
-        if media_type == MediaType::Video && !caption.is_empty() {
-            tracing::info!("Processing video with caption overlay");
-            filename = process_video_with_caption(&filename, &caption).await?;
+    let filename = media_info.filename.clone();
+    let admission = update_state_and_broadcast(state, media_info, ws_clients).await?;
+    let message = busy_message(&filename, &admission, locale).unwrap_or_else(|| {
+        format!("<p>{}</p>", locale.format(Msg::Published, &[&filename]))
+    });
+    Ok(warp::reply::with_status(warp::reply::html(message), StatusCode::OK))
+}
+
+// First frame of the uploaded video with the caption burned in as the full encode would, as a
+// JPEG, to check its size and wrapping before uploading for real
+pub async fn preview_caption(
+    mut form: FormData,
+    state: SharedState,
+    config: Arc<AppConfig>,
+    locale: Locale,
+) -> Result<warp::reply::Response, Rejection> {
+    tracing::info!("Processing caption preview");
+    let form_data = parse_form_data(&mut form).await?;
+    let error = |status, message| {
+        let body = warp::reply::json(&serde_json::json!({ "error": message }));
+        Ok(warp::reply::with_status(body, status).into_response())
+    };
+    if form_data.filename.is_empty() {
+        return error(StatusCode::BAD_REQUEST, locale.t(Msg::NoMediaUploaded));
+    }
+    let filename = match validation::validate(
+        &form_data.filename,
+        &form_data.file_data,
+        validation::MEDIA_EXTENSIONS,
+    ) {
+        Ok(filename) if detect_media_type(&filename) == MediaType::Video => filename,
+        _ => return error(StatusCode::BAD_REQUEST, locale.t(Msg::PreviewNeedsVideo)),
+    };
+    if !VideoProcessor::is_ffmpeg_available() {
+        return error(StatusCode::SERVICE_UNAVAILABLE, locale.t(Msg::PreviewUnavailable));
+    }
+
+    // Saved under a name of its own, the upload for real may come while this one renders
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let extension = filename.rsplit('.').next().unwrap_or("mp4");
+    let input = format!("caption_preview_{}.{}", stamp, extension);
+    let output = format!("caption_preview_{}.jpg", stamp);
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    save_uploaded_file(&dirs, &input, &form_data.file_data).await?;
+
+    let options = EncodeOptions {
+        quality: form_data.quality.unwrap_or(config.quality),
+        watermark: config
+            .watermark
+            .clone()
+            .filter(|_| form_data.watermark.unwrap_or(true)),
+        display_max_height: config.display_max_height(),
+        framing: form_data.framing.unwrap_or(config.framing),
+        caption_fonts: config.caption_fonts.clone(),
+        ..EncodeOptions::default()
+    };
+    let (input_path, output_path) = (dirs.upload(&input), dirs.upload(&output));
+    let caption = &form_data.caption;
+    let rendered =
+        VideoProcessor::preview_caption(&dirs.uploads, &input_path, &output_path, caption, &options)
+            .await;
+    let jpeg = match rendered {
+        Ok(()) => tokio::fs::read(&output_path).await.map_err(AppError::IoError),
+        Err(e) => Err(e),
+    };
+    for path in [&input_path, &output_path] {
+        if let Err(e) = tokio::fs::remove_file(path).await
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::warn!("Failed to remove caption preview file {}: {}", path, e);
         }
+    }
 
-        // Create media info (use processed filename and empty caption for videos since it's now embedded)
-        let final_caption = if media_type == MediaType::Video && !caption.is_empty() {
-            String::new() // Caption is now embedded in video, don't show separately
-        } else {
-            caption.clone()
-        };
+    match jpeg {
+        Ok(jpeg) => Ok(warp::reply::with_header(
+            warp::reply::with_header(jpeg, "content-type", "image/jpeg"),
+            "cache-control",
+            "no-store",
+        )
+        .into_response()),
+        Err(e) => {
+            tracing::error!("Failed to preview caption: {}", e);
+            error(StatusCode::INTERNAL_SERVER_ERROR, locale.t(Msg::PreviewFailed))
+        }
+    }
+}
 
-        let media_info =
-            create_media_info(filename.clone(), media_type, final_duration, final_caption);
+// Direct link upload handler (image hosts, links straight to an mp4 or mp3)
+pub async fn upload_url(
+    form: std::collections::HashMap<String, String>,
+    client: Option<ClientId>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+    locale: Locale,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Processing direct URL upload");
+    let url = form.get("url").map(|url| url.trim()).unwrap_or_default();
+    if url.is_empty() {
+        tracing::warn!("No URL provided");
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::NoUrl))));
+    }
+
+    // Video pages need yt-dlp, not a plain download
+    if VideoPlatform::from_url(url).is_some() {
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::VideoPageUrl))));
+    }
 
-        // Update shared state and broadcast appropriate events
-        update_state_and_broadcast(state, media_info.clone(), ws_clients.clone()).await?;
+    // Direct links get the same limit as the upload form
+    let file = match remote_media::fetch(url, config.upload_limits.media_bytes()).await {
+        Ok(file) => file,
+        Err(e) => {
+            tracing::warn!("Failed to fetch {}: {}", url, e);
+            return Ok(warp::reply::html(format!("<p>{}</p>", e)));
+        }
+    };
 
-        // If it's a video, also broadcast the video event
-        if media_type == MediaType::Video {
-            websocket::broadcast_video_event(&ws_clients, filename.clone()).await;
+    match file.kind {
+        RemoteKind::Media => {
+            let form_data = FormDataParsed {
+                filename: file.filename,
+                file_data: file.data,
+                duration_secs: parse_duration(form.get("duration")),
+                caption: form
+                    .get("caption")
+                    .map(|caption| caption.trim().to_string())
+                    .unwrap_or_default(),
+                quality: form.get("quality").and_then(|name| QualityProfile::from_name(name)),
+                watermark: form.get("watermark").and_then(|value| parse_toggle(value)),
+                caption_animation: form
+                    .get("caption_animation")
+                    .and_then(|name| CaptionAnimation::from_name(name))
+                    .unwrap_or_default(),
+                caption_delay_secs: form
+                    .get("caption_delay")
+                    .map(|delay| parse_caption_delay(delay))
+                    .unwrap_or(0.0),
+                effect: form
+                    .get("effect")
+                    .and_then(|name| VideoEffect::from_name(name))
+                    .unwrap_or_default(),
+                chromakey: form
+                    .get("chromakey")
+                    .and_then(|value| parse_toggle(value))
+                    .unwrap_or(false),
+                chromakey_similarity: form
+                    .get("chromakey_similarity")
+                    .and_then(|value| value.trim().parse().ok()),
+                chromakey_blend: form
+                    .get("chromakey_blend")
+                    .and_then(|value| value.trim().parse().ok()),
+                framing: form.get("framing").and_then(|name| Framing::from_name(name)),
+                translate_to: form.get("translate_to").cloned(),
+                auto_captions: form
+                    .get("auto_captions")
+                    .and_then(|value| parse_toggle(value))
+                    .unwrap_or(false),
+                image_format: form
+                    .get("image_format")
+                    .and_then(|name| ImageFormat::from_name(name)),
+                burn_after_viewing: form
+                    .get("burn_after_viewing")
+                    .and_then(|value| parse_toggle(value))
+                    .unwrap_or(false),
+                passphrase: form.get("passphrase").cloned(),
+                expires_in_secs: form
+                    .get("expires_in")
+                    .and_then(|secs| secs.trim().parse().ok()),
+                preset: form.get("preset").cloned(),
+                source: MediaSource::UrlDownload,
+            };
+            let response = publish_media(
+                form_data,
+                Destination::Screen,
+                client,
+                state,
+                ws_clients,
+                &config,
+                locale,
+            )
+            .await?;
+            Ok(upload_html(&response, locale))
         }
+        RemoteKind::Sound => {
+            let options = SoundOptions::default();
+            let (filename, data) = (&file.filename, &file.data);
+            store_sound(filename, data, options, state, ws_clients, &config, locale).await
+        }
+    }
+}
 
-        // Return success response
-        let caption_message = if media_type == MediaType::Video && !caption.is_empty() {
-            "<br/>Caption embedded in video"
-        } else if !caption.is_empty() {
-            &format!("<br/>Caption: {}", caption)
-        } else {
-            ""
+// Raw image body upload, for scripts and extensions posting a clipboard screenshot
+// Caption, duration and quality come from the query string
+#[allow(clippy::too_many_arguments)] // One per warp filter
+pub async fn paste_image(
+    content_type: Option<String>,
+    query: std::collections::HashMap<String, String>,
+    body: bytes::Bytes,
+    client: Option<ClientId>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+    locale: Locale,
+    format: ReplyFormat,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Processing pasted image ({} bytes)", body.len());
+    let extension = content_type
+        .as_deref()
+        .filter(|content_type| content_type.trim().starts_with("image/"))
+        .and_then(remote_media::extension_for)
+        .map(|(extension, _)| extension);
+    let Some(extension) = extension else {
+        tracing::warn!("Refused pasted content type: {:?}", content_type);
+        let response = UploadResponse::refused(locale.t(Msg::PasteNeedsImage));
+        return Ok(warp::reply::with_status(
+            upload_reply(response, format, locale),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ));
+    };
+    if body.is_empty() {
+        let response = UploadResponse::refused(locale.t(Msg::NoImagePasted));
+        return Ok(warp::reply::with_status(
+            upload_reply(response, format, locale),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let form_data = FormDataParsed {
+        filename: format!("paste_{}.{}", timestamp, extension),
+        file_data: body.to_vec(),
+        duration_secs: parse_duration(query.get("duration")),
+        caption: query
+            .get("caption")
+            .map(|caption| caption.trim().to_string())
+            .unwrap_or_default(),
+        watermark: query.get("watermark").and_then(|value| parse_toggle(value)),
+        translate_to: query.get("translate_to").cloned(),
+        image_format: query.get("image_format").and_then(|name| ImageFormat::from_name(name)),
+        burn_after_viewing: query
+            .get("burn_after_viewing")
+            .and_then(|value| parse_toggle(value))
+            .unwrap_or(false),
+        passphrase: query.get("passphrase").cloned(),
+        expires_in_secs: query
+            .get("expires_in")
+            .and_then(|secs| secs.trim().parse().ok()),
+        preset: query.get("preset").cloned(),
+        source: MediaSource::Screenshot,
+        ..Default::default()
+    };
+    let response = publish_media(
+        form_data,
+        Destination::Screen,
+        client,
+        state,
+        ws_clients,
+        &config,
+        locale,
+    )
+    .await?;
+    Ok(warp::reply::with_status(
+        upload_reply(response, format, locale),
+        StatusCode::OK,
+    ))
+}
+
+/// Media sent without a form, e.g. over gRPC, the other options are the upload defaults
+pub struct DirectUpload {
+    pub filename: String,
+    pub data: Vec<u8>,
+    pub caption: String,
+    pub duration_secs: Option<u64>,
+    pub preset: Option<String>,
+}
+
+/// Publish media like an upload from the form, for callers other than the HTTP routes
+pub async fn publish_direct(
+    upload: DirectUpload,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: &AppConfig,
+    locale: Locale,
+) -> Result<UploadResponse, Rejection> {
+    tracing::info!("Processing direct upload of {} ({} bytes)", upload.filename, upload.data.len());
+    if upload.data.is_empty() {
+        return Ok(UploadResponse::refused(locale.t(Msg::NoMediaUploaded)));
+    }
+    let form_data = FormDataParsed {
+        filename: upload.filename,
+        file_data: upload.data,
+        duration_secs: upload.duration_secs.map_or(DEFAULT_DURATION_SECS, clamp_duration),
+        caption: upload.caption.trim().to_string(),
+        preset: upload.preset,
+        source: MediaSource::Api,
+        ..Default::default()
+    };
+    publish_media(form_data, Destination::Screen, None, state, ws_clients, config, locale).await
+}
+
+// Image generated from a prompt by the configured Stable Diffusion backend, then published like
+// an upload
+pub async fn imagine(
+    form: std::collections::HashMap<String, String>,
+    client: Option<ClientId>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+    locale: Locale,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Processing image generation request");
+    let Some(imagine) = &config.imagine else {
+        tracing::warn!("Image generation asked but no backend is configured");
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::ImagineUnavailable))));
+    };
+    let prompt = form.get("prompt").map(|prompt| prompt.trim()).unwrap_or_default();
+    if prompt.is_empty() {
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::NoPrompt))));
+    }
+    if prompt.chars().count() > MAX_PROMPT_CHARS {
+        let message = locale.format(Msg::PromptTooLong, &[&MAX_PROMPT_CHARS]);
+        return Ok(warp::reply::html(format!("<p>{}</p>", message)));
+    }
+
+    let image = match imagine.generate(prompt).await {
+        Ok(image) => image,
+        Err(e) => {
+            tracing::warn!("Failed to generate an image for {:?}: {}", prompt, e);
+            let message = locale.format(Msg::ImagineFailed, &[&e]);
+            return Ok(warp::reply::html(format!("<p>{}</p>", message)));
+        }
+    };
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let form_data = FormDataParsed {
+        filename: format!("imagine_{}.png", timestamp),
+        file_data: image,
+        duration_secs: parse_duration(form.get("duration")),
+        caption: form
+            .get("caption")
+            .map(|caption| caption.trim().to_string())
+            .unwrap_or_default(),
+        watermark: form.get("watermark").and_then(|value| parse_toggle(value)),
+        translate_to: form.get("translate_to").cloned(),
+        source: MediaSource::Imagine,
+        ..Default::default()
+    };
+    let response = publish_media(
+        form_data,
+        Destination::Screen,
+        client,
+        state,
+        ws_clients,
+        &config,
+        locale,
+    )
+    .await?;
+    Ok(upload_html(&response, locale))
+}
+
+// Where a processed upload goes
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Destination {
+    Screen,
+    // Held in the staging collection until published
+    Stage,
+    // Held in the review collection until an admin approves it
+    Review,
+}
+
+// Validate, store and process an uploaded image or video, then put it on screen or stage it
+async fn publish_media(
+    mut form_data: FormDataParsed,
+    mut destination: Destination,
+    client: Option<ClientId>,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: &AppConfig,
+    locale: Locale,
+) -> Result<UploadResponse, Rejection> {
+    tracing::info!("Processing file: {}", form_data.filename);
+    if let Some(name) = form_data.preset.take().filter(|name| !name.trim().is_empty()) {
+        let Some(preset) = presets::find(&config.presets, &name) else {
+            tracing::warn!("Unknown preset: {}", name);
+            return Ok(UploadResponse::refused(locale.t(Msg::UnknownPreset)));
         };
+        apply_preset(&mut form_data, preset);
+    }
+    match filter_caption(&state, &form_data.caption).await {
+        Some((caption, review)) => {
+            form_data.caption = caption;
+            if review {
+                tracing::info!("Holding {} for review of its caption", form_data.filename);
+                destination = Destination::Review;
+            }
+        }
+        None => return Ok(UploadResponse::refused(locale.t(Msg::CaptionRefused))),
+    }
+    // Validate file type from its content, naming it after its actual type
+    form_data.filename = match validation::validate(
+        &form_data.filename,
+        &form_data.file_data,
+        validation::MEDIA_EXTENSIONS,
+    ) {
+        Ok(filename) => filename,
+        Err(e) => {
+            tracing::warn!("Invalid file uploaded: {}: {}", form_data.filename, e);
+            return Ok(UploadResponse::refused(locale.t(Msg::InvalidMediaType)));
+        }
+    };
 
-        tracing::info!("Upload completed successfully: {}", filename);
-        return Ok(warp::reply::html(format!(
-            r#"<p>Uploaded {} successfully! Display duration: {} seconds{}</p>"#,
-            filename,
-            if final_duration == 999999 {
-                "Full video".to_string()
-            } else {
-                final_duration.to_string()
-            },
-            caption_message
-        )));
+    // Check the size limit of its type before anything is written
+    let media_type = detect_media_type(&form_data.filename);
+    let limit_bytes = config.upload_limits.bytes(media_type);
+    if form_data.file_data.len() as u64 > limit_bytes {
+        tracing::warn!("File too large: {} bytes", form_data.file_data.len());
+        let message = match media_type {
+            MediaType::Image => Msg::ImageTooLarge,
+            MediaType::Video => Msg::VideoTooLarge,
+        };
+        return Err(warp::reject::custom(TooLarge::new(message, limit_bytes, locale)));
+    }
+
+    // Save file to disk
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    let file_size = save_uploaded_file(&dirs, &form_data.filename, &form_data.file_data).await?;
+    tracing::info!("Saved file to disk, size: {} bytes", file_size);
+
+    let upload_path = dirs.upload(&form_data.filename);
+    if let Some(rejection) = scan_upload(&upload_path, config, locale).await {
+        return Ok(UploadResponse::refused(rejection));
+    }
+
+    // Store values before move
+    let mut filename = form_data.filename.clone();
+    let translation =
+        translate_caption(&form_data.caption, form_data.translate_to.as_deref(), config).await;
+    // The translated caption is the one burned in or shown
+    let caption = translation
+        .as_ref()
+        .map(|translation| translation.translated.clone())
+        .unwrap_or_else(|| form_data.caption.clone());
+
+    // Adjust duration to the media type
+    tracing::info!("Detected media type: {:?}", media_type);
+    let final_duration = match media_type {
+        MediaType::Video => 999999, // Special value for videos (no auto-refresh)
+        MediaType::Image => form_data.duration_secs,
+    };
+
+    // The configured watermark applies unless the upload opted out
+    let watermark = config
+        .watermark
+        .clone()
+        .filter(|_| form_data.watermark.unwrap_or(true));
+
+    // Speech captions are burned in along with everything else
+    let subtitles = if media_type == MediaType::Video && form_data.auto_captions {
+        auto_caption(&dirs.upload(&filename), config).await
+    } else {
+        None
+    };
+
+    let options = EncodeOptions {
+        hw_accel: state.call(|state| state.hw_accel()).await,
+        quality: form_data.quality.unwrap_or(config.quality),
+        watermark: watermark.clone(),
+        display_max_height: config.display_max_height(),
+        caption_animation: form_data.caption_animation,
+        caption_delay_secs: form_data.caption_delay_secs,
+        subtitles: subtitles.clone(),
+        effect: form_data.effect,
+        chromakey: config
+            .chromakey
+            .clone()
+            .filter(|_| form_data.chromakey)
+            .map(|key| key.tuned(form_data.chromakey_similarity, form_data.chromakey_blend)),
+        framing: form_data.framing.unwrap_or(config.framing),
+        caption_fonts: config.caption_fonts.clone(),
+    };
+    let probe = match media_type {
+        MediaType::Video => MediaProbe::probe(&dirs.upload(&filename)).await,
+        _ => MediaProbe::default(),
+    };
+    // Videos over the profile's or the display's cap are scaled down even without an overlay
+    let too_tall = probe
+        .width
+        .zip(probe.height)
+        .is_some_and(|(width, height)| options.needs_downscale(width, height));
+    // Vertical videos are reframed for the horizontal display when asked to
+    let reframe = probe
+        .width
+        .zip(probe.height)
+        .is_some_and(|(width, height)| options.needs_framing(width, height));
+
+    // Re-encode videos that need captions, a watermark or an effect, or a smaller size
+    let mut warnings = Vec::new();
+    if media_type == MediaType::Video
+        && (!caption.is_empty()
+            || watermark.is_some()
+            || subtitles.is_some()
+            || options.effect != VideoEffect::None
+            || options.chromakey.is_some()
+            || too_tall
+            || reframe)
+    {
+        tracing::info!("Processing video with caption/watermark overlay, effect or downscale");
+        (filename, warnings) = process_video(&dirs, &filename, &caption, &options, &state).await?;
+    } else if media_type == MediaType::Image
+        && let Some(watermark) = watermark.filter(|watermark| watermark.images)
+    {
+        filename = watermark_image(&dirs, &filename, &watermark).await;
+    }
+    // Large stills are sent to the display in a lighter format
+    if media_type == MediaType::Image && config.image_conversion.applies_to(&filename, file_size) {
+        let format = form_data.image_format.unwrap_or(config.image_conversion.format);
+        filename = convert_image(&dirs, &filename, format).await;
+    }
+    if let Some(subtitles) = &subtitles
+        && let Err(e) = tokio::fs::remove_file(subtitles).await
+    {
+        tracing::warn!("Failed to remove subtitles {}: {}", subtitles, e);
+    }
+
+    report_warnings(&state, &ws_clients, &filename, &warnings).await;
+    // Captions that couldn't be burned in are shown by the display instead
+    let embedded = media_type == MediaType::Video && !caption.is_empty() && !skipped(&warnings);
+
+    // Create media info (use processed filename and empty caption for videos since it's now embedded)
+    let final_caption = if embedded {
+        String::new() // Caption is now embedded in video, don't show separately
+    } else {
+        caption.clone()
+    };
+
+    // Probe the final file so displays get layout hints
+    let probe = MediaProbe::probe(&dirs.upload(&filename)).await;
+    let play_secs = match media_type {
+        MediaType::Video => probe
+            .duration_secs
+            .map(|secs| secs.ceil() as u64)
+            .unwrap_or(config.video_busy_secs),
+        MediaType::Image => form_data.duration_secs,
+    };
+
+    let mut media_info = create_media_info(
+        filename.clone(),
+        media_type,
+        final_duration,
+        final_caption,
+        play_secs,
+        client,
+    );
+    media_info.probe = probe;
+    media_info.translation = translation;
+    media_info.source = form_data.source;
+    media_info.burn_after_viewing = form_data.burn_after_viewing;
+    media_info.lock = form_data
+        .passphrase
+        .as_deref()
+        .map(str::trim)
+        .filter(|passphrase| !passphrase.is_empty())
+        .map(passphrase_hash);
+    media_info.expires_in_secs = form_data
+        .expires_in_secs
+        .map(|secs| config.cleanup.expires_in(secs));
+    // Clients registered as being on a slow link get a smaller copy
+    if media_type == MediaType::Video
+        && config.low_renditions
+        && media_info
+            .probe
+            .height
+            .is_some_and(|height| height > LOW_RENDITION_HEIGHT)
+    {
+        match VideoProcessor::encode_low_rendition(&dirs, &filename).await {
+            Ok(url) => media_info.low_rendition = Some(url),
+            Err(e) => tracing::warn!("No low rendition for {}: {}", filename, e),
+        }
+    }
+    // Long videos are also streamed, displays can start before the whole file is in
+    if media_type == MediaType::Video
+        && config.hls_min_secs > 0
+        && media_info
+            .probe
+            .duration_secs
+            .is_some_and(|secs| secs >= config.hls_min_secs as f64)
+    {
+        match VideoProcessor::segment_hls(&dirs, &filename).await {
+            Ok(url) => media_info.hls = Some(url),
+            Err(e) => tracing::warn!("No HLS playlist for {}: {}", filename, e),
+        }
+    }
+
+    if destination == Destination::Stage {
+        let expiry = config.staging.expiry();
+        let staged = state
+            .call(move |state| {
+                let expires_at = state.now() + expiry;
+                state.staging_mut().add(media_info, expires_at)
+            })
+            .await;
+        tracing::info!("Staged {} as {}", staged.filename, staged.id);
+        let minutes = expiry.as_secs().div_ceil(60);
+        return Ok(UploadResponse {
+            outcome: UploadOutcome::Staged,
+            filename: Some(staged.filename.clone()),
+            queue_position: None,
+            retry_after_secs: None,
+            staged_id: Some(staged.id),
+            message: locale.format(Msg::Staged, &[&staged.filename, &minutes]),
+        });
+    }
+    if destination == Destination::Review {
+        let held = hold_for_review(&state, media_info, config).await;
+        return Ok(UploadResponse {
+            outcome: UploadOutcome::Review,
+            filename: Some(held.filename.clone()),
+            queue_position: None,
+            retry_after_secs: None,
+            staged_id: None,
+            message: locale.format(Msg::InReview, &[&held.filename]),
+        });
     }
 
-    tracing::warn!("No media uploaded");
-    Ok(warp::reply::html("<p>No media uploaded!</p>".to_string()))
+    // Update shared state and broadcast appropriate events
+    let admission = update_state_and_broadcast(state, media_info, ws_clients).await?;
+    if let Some(response) = admission_response(&filename, &admission, locale) {
+        return Ok(response);
+    }
+
+    // Return success response
+    let caption_message = if embedded {
+        locale.t(Msg::CaptionEmbedded)
+    } else if !caption.is_empty() {
+        &locale.format(Msg::CaptionShown, &[&caption])
+    } else {
+        ""
+    };
+
+    tracing::info!("Upload completed successfully: {}", filename);
+    let duration = if final_duration == 999999 {
+        locale.t(Msg::FullVideo).to_string()
+    } else {
+        final_duration.to_string()
+    };
+    Ok(UploadResponse {
+        outcome: UploadOutcome::Shown,
+        filename: Some(filename.clone()),
+        queue_position: None,
+        retry_after_secs: None,
+        staged_id: None,
+        message: locale.format(Msg::Uploaded, &[&filename, &duration, &caption_message]),
+    })
 }
 
 // Struct to hold parsed form data
@@ -135,6 +834,117 @@ struct FormDataParsed {
     file_data: Vec<u8>,
     duration_secs: u64,
     caption: String,
+    quality: Option<QualityProfile>,
+    watermark: Option<bool>,
+    caption_animation: CaptionAnimation,
+    caption_delay_secs: f64,
+    effect: VideoEffect,
+    chromakey: bool,
+    // Uploader's tuning of the green screen key, the configured values otherwise
+    chromakey_similarity: Option<f32>,
+    chromakey_blend: Option<f32>,
+    // Server default when the upload doesn't pick
+    framing: Option<Framing>,
+    translate_to: Option<String>,
+    auto_captions: bool,
+    image_format: Option<ImageFormat>,
+    burn_after_viewing: bool,
+    passphrase: Option<String>, // Held until unlocked with it
+    expires_in_secs: Option<u64>, // Unbounded until checked against the config
+    preset: Option<String>,
+    source: MediaSource,
+}
+
+// Seconds an image stays on screen when the upload doesn't say
+const DEFAULT_DURATION_SECS: u64 = 5;
+
+impl Default for FormDataParsed {
+    fn default() -> Self {
+        Self {
+            filename: String::new(),
+            file_data: Vec::new(),
+            duration_secs: DEFAULT_DURATION_SECS,
+            caption: String::new(),
+            quality: None,
+            watermark: None,
+            caption_animation: CaptionAnimation::default(),
+            caption_delay_secs: 0.0,
+            effect: VideoEffect::default(),
+            chromakey: false,
+            chromakey_similarity: None,
+            chromakey_blend: None,
+            framing: None,
+            translate_to: None,
+            auto_captions: false,
+            image_format: None,
+            burn_after_viewing: false,
+            passphrase: None,
+            expires_in_secs: None,
+            preset: None,
+            source: MediaSource::WebForm,
+        }
+    }
+}
+
+// Between 1 and 60 seconds, so a typo can't hold the screen
+fn clamp_duration(secs: u64) -> u64 {
+    secs.clamp(1, 60)
+}
+
+// Uploaded duration field, the default when missing or not a number
+fn parse_duration(value: Option<&String>) -> u64 {
+    value
+        .and_then(|duration| duration.trim().parse::<u64>().ok())
+        .map_or(DEFAULT_DURATION_SECS, clamp_duration)
+}
+
+// Replace the options the preset sets, the rest stay as uploaded
+fn apply_preset(form_data: &mut FormDataParsed, preset: &UploadPreset) {
+    tracing::info!("Applying preset {}", preset.name);
+    if let Some(quality) = preset.quality {
+        form_data.quality = Some(quality);
+    }
+    if let Some(watermark) = preset.watermark {
+        form_data.watermark = Some(watermark);
+    }
+    if let Some(animation) = preset.caption_animation {
+        form_data.caption_animation = animation;
+    }
+    if let Some(delay) = preset.caption_delay_secs.filter(|delay| delay.is_finite()) {
+        form_data.caption_delay_secs = delay.clamp(0.0, 60.0);
+    }
+    if let Some(effect) = preset.effect {
+        form_data.effect = effect;
+    }
+    if let Some(framing) = preset.framing {
+        form_data.framing = Some(framing);
+    }
+    if let Some(auto_captions) = preset.auto_captions {
+        form_data.auto_captions = auto_captions;
+    }
+    if let Some(image_format) = preset.image_format {
+        form_data.image_format = Some(image_format);
+    }
+    if let Some(duration_secs) = preset.duration_secs {
+        form_data.duration_secs = clamp_duration(duration_secs);
+    }
+    if let Some(expires_in_secs) = preset.expires_in_secs {
+        form_data.expires_in_secs = Some(expires_in_secs);
+    }
+    if let Some(burn_after_viewing) = preset.burn_after_viewing {
+        form_data.burn_after_viewing = burn_after_viewing;
+    }
+}
+
+// Seconds before a caption appears, capped so a typo can't hide it for the whole video
+fn parse_caption_delay(value: &str) -> f64 {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|delay| delay.is_finite())
+        .unwrap_or(0.0)
+        .clamp(0.0, 60.0)
 }
 
 // Parse form data from multipart
@@ -142,8 +952,24 @@ async fn parse_form_data(form: &mut FormData) -> Result<FormDataParsed, Rejectio
     tracing::info!("Parsing form data");
     let mut filename = String::new();
     let mut file_data = Vec::new();
-    let mut duration_secs = 5u64; // Default duration
+    let mut duration_secs = DEFAULT_DURATION_SECS;
     let mut caption = String::new(); // Default caption
+    let mut quality = None; // Falls back to the configured profile
+    let mut watermark = None; // Watermark by default when one is configured
+    let mut caption_animation = CaptionAnimation::default();
+    let mut caption_delay_secs = 0.0;
+    let mut effect = VideoEffect::default();
+    let mut chromakey = false;
+    let mut chromakey_similarity = None;
+    let mut chromakey_blend = None;
+    let mut framing = None;
+    let mut translate_to = None;
+    let mut auto_captions = false;
+    let mut image_format = None; // Falls back to the configured format
+    let mut burn_after_viewing = false;
+    let mut passphrase = None;
+    let mut expires_in_secs = None; // Default retention
+    let mut preset = None;
 
     // Process the stream directly without collecting
     while let Some(result) = form.next().await {
@@ -174,8 +1000,7 @@ async fn parse_form_data(form: &mut FormData) -> Result<FormDataParsed, Rejectio
                         tracing::info!("Processing duration field");
                         let duration_str = read_field_as_string(field).await?;
                         if let Ok(parsed_duration) = duration_str.trim().parse::<u64>() {
-                            // Clamp duration between 1 and 60 seconds
-                            duration_secs = parsed_duration.clamp(1, 60);
+                            duration_secs = clamp_duration(parsed_duration);
                             tracing::info!("Parsed duration: {} seconds", duration_secs);
                         } else {
                             tracing::warn!("Failed to parse duration, using default: {} seconds", duration_secs);
@@ -187,6 +1012,81 @@ async fn parse_form_data(form: &mut FormData) -> Result<FormDataParsed, Rejectio
                         caption = caption.trim().to_string();
                         tracing::info!("Parsed caption: {}", caption);
                     }
+                    "quality" => {
+                        let quality_str = read_field_as_string(field).await?;
+                        quality = QualityProfile::from_name(&quality_str);
+                        tracing::info!("Parsed quality: {:?}", quality);
+                    }
+                    "watermark" => {
+                        watermark = parse_toggle(&read_field_as_string(field).await?);
+                        tracing::info!("Parsed watermark: {:?}", watermark);
+                    }
+                    "caption_animation" => {
+                        let animation_str = read_field_as_string(field).await?;
+                        caption_animation =
+                            CaptionAnimation::from_name(&animation_str).unwrap_or_default();
+                        tracing::info!("Parsed caption animation: {:?}", caption_animation);
+                    }
+                    "caption_delay" => {
+                        caption_delay_secs =
+                            parse_caption_delay(&read_field_as_string(field).await?);
+                        tracing::info!("Parsed caption delay: {} seconds", caption_delay_secs);
+                    }
+                    "effect" => {
+                        effect = VideoEffect::from_name(&read_field_as_string(field).await?)
+                            .unwrap_or_default();
+                        tracing::info!("Parsed effect: {:?}", effect);
+                    }
+                    "chromakey" => {
+                        chromakey =
+                            parse_toggle(&read_field_as_string(field).await?).unwrap_or(false);
+                        tracing::info!("Parsed chromakey: {}", chromakey);
+                    }
+                    "chromakey_similarity" => {
+                        chromakey_similarity =
+                            read_field_as_string(field).await?.trim().parse().ok();
+                        tracing::info!("Parsed chromakey similarity: {:?}", chromakey_similarity);
+                    }
+                    "chromakey_blend" => {
+                        chromakey_blend =
+                            read_field_as_string(field).await?.trim().parse().ok();
+                        tracing::info!("Parsed chromakey blend: {:?}", chromakey_blend);
+                    }
+                    "framing" => {
+                        framing = Framing::from_name(&read_field_as_string(field).await?);
+                        tracing::info!("Parsed framing: {:?}", framing);
+                    }
+                    "translate_to" => {
+                        translate_to = Some(read_field_as_string(field).await?);
+                        tracing::info!("Parsed translation target: {:?}", translate_to);
+                    }
+                    "auto_captions" => {
+                        auto_captions =
+                            parse_toggle(&read_field_as_string(field).await?).unwrap_or(false);
+                        tracing::info!("Parsed auto captions: {}", auto_captions);
+                    }
+                    "image_format" => {
+                        image_format = ImageFormat::from_name(&read_field_as_string(field).await?);
+                        tracing::info!("Parsed image format: {:?}", image_format);
+                    }
+                    "burn_after_viewing" => {
+                        burn_after_viewing =
+                            parse_toggle(&read_field_as_string(field).await?).unwrap_or(false);
+                        tracing::info!("Parsed burn after viewing: {}", burn_after_viewing);
+                    }
+                    "passphrase" => {
+                        passphrase = Some(read_field_as_string(field).await?);
+                        tracing::info!("Parsed hold passphrase");
+                    }
+                    "expires_in" => {
+                        expires_in_secs = read_field_as_string(field).await?.trim().parse().ok();
+                        tracing::info!("Parsed expires in: {:?}", expires_in_secs);
+                    }
+                    "preset" => {
+                        preset = Some(read_field_as_string(field).await?)
+                            .filter(|name| !name.trim().is_empty());
+                        tracing::info!("Parsed preset: {:?}", preset);
+                    }
                     _ => {
                         tracing::debug!("Unknown field: {}", field.name());
                     }
@@ -205,9 +1105,35 @@ async fn parse_form_data(form: &mut FormData) -> Result<FormDataParsed, Rejectio
         file_data,
         duration_secs,
         caption,
+        quality,
+        watermark,
+        caption_animation,
+        caption_delay_secs,
+        effect,
+        chromakey,
+        chromakey_similarity,
+        chromakey_blend,
+        framing,
+        translate_to,
+        auto_captions,
+        image_format,
+        burn_after_viewing,
+        passphrase,
+        expires_in_secs,
+        preset,
+        source: MediaSource::WebForm,
     })
 }
 
+// Parse a checkbox-style form value
+fn parse_toggle(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "on" | "true" | "1" | "yes" => Some(true),
+        "off" | "false" | "0" | "no" => Some(false),
+        _ => None,
+    }
+}
+
 // Read a form field as a string
 async fn read_field_as_string(mut field: warp::multipart::Part) -> Result<String, Rejection> {
     let mut field_data = Vec::new();
@@ -231,7 +1157,11 @@ async fn read_field_as_string(mut field: warp::multipart::Part) -> Result<String
 }
 
 // Save uploaded file to disk
-async fn save_uploaded_file(filename: &str, file_data: &[u8]) -> Result<u64, Rejection> {
+async fn save_uploaded_file(
+    dirs: &DataDirs,
+    filename: &str,
+    file_data: &[u8],
+) -> Result<u64, Rejection> {
     // Sanitize the filename to prevent path traversal
     let sanitized_filename = sanitize_filename(filename)
         .ok_or_else(|| {
@@ -240,24 +1170,16 @@ async fn save_uploaded_file(filename: &str, file_data: &[u8]) -> Result<u64, Rej
         })?;
     
     // Validate the file path to ensure it's within the uploads directory
-    let file_path = validate_file_path("uploads", &sanitized_filename)
+    let file_path = validate_file_path(&dirs.uploads, &sanitized_filename)
         .ok_or_else(|| {
             tracing::error!("Invalid file path: {}", filename);
             warp::reject::custom(AppError::IoError(std::io::Error::other("Invalid file path")))
         })?;
     
-    // Validate file content matches extension
-    if !is_valid_file_content(&sanitized_filename, file_data) {
-        tracing::error!("File content does not match extension for: {}", sanitized_filename);
-        return Err(warp::reject::custom(AppError::IoError(std::io::Error::other(
-            "File content does not match file extension"
-        ))));
-    }
-    
     tracing::info!("Saving uploaded file: {} ({} bytes)", sanitized_filename, file_data.len());
 
     // Create directory
-    tokio::fs::create_dir_all("uploads").await.map_err(|e| {
+    tokio::fs::create_dir_all(&dirs.uploads).await.map_err(|e| {
         tracing::error!("Failed to create uploads directory: {}", e);
         warp::reject::custom(AppError::IoError(e))
     })?;
@@ -279,64 +1201,387 @@ async fn save_uploaded_file(filename: &str, file_data: &[u8]) -> Result<u64, Rej
 }
 
 // Create MediaInfo struct
-fn create_media_info(
+pub(crate) fn create_media_info(
     filename: String,
     media_type: MediaType,
     duration_secs: u64,
     caption: String,
+    play_secs: u64,
+    uploader: Option<ClientId>,
 ) -> MediaInfo {
     MediaInfo {
+        id: 0, // Assigned by the state on submission
         filename,
         media_type,
-        upload_time: std::time::SystemTime::now(),
+        upload_time: std::time::UNIX_EPOCH, // Stamped by the state on submission
         marked_for_deletion: false,
         duration_secs,
         caption,
+        play_secs,
+        uploader,
+        probe: MediaProbe::default(),
+        translation: None,
+        burn_after_viewing: false,
+        lock: None,
+        poster: None,
+        preview: None,
+        low_rendition: None,
+        hls: None,
+        expires_in_secs: None,
+        source: MediaSource::default(),
+        platform: None,
+    }
+}
+
+// The caption as the word filter lets it through and whether it waits for an admin's approval,
+// none when it's refused
+async fn filter_caption(state: &SharedState, caption: &str) -> Option<(String, bool)> {
+    let checked = caption.to_string();
+    match state.call(move |state| state.word_filter().check(&checked)).await {
+        Verdict::Allowed(caption) => Some((caption, false)),
+        Verdict::NeedsApproval => Some((caption.to_string(), true)),
+        Verdict::Rejected => {
+            tracing::warn!("Caption refused by the word filter");
+            None
+        }
+    }
+}
+
+// Keep an upload off screen until an admin approves it, for as long as staged uploads wait
+async fn hold_for_review(
+    state: &SharedState,
+    media_info: MediaInfo,
+    config: &AppConfig,
+) -> StagedEntry {
+    let expiry = config.staging.expiry();
+    let held = state
+        .call(move |state| {
+            let expires_at = state.now() + expiry;
+            state.review_mut().add(media_info, expires_at)
+        })
+        .await;
+    tracing::info!("Holding {} for review as {}", held.filename, held.id);
+    held
+}
+
+// Transcribe a video's speech to an SRT file, the upload goes on without captions if that fails
+async fn auto_caption(video_path: &str, config: &AppConfig) -> Option<String> {
+    let Some(auto_captions) = &config.auto_captions else {
+        tracing::warn!("Auto captions asked but no speech to text backend is configured");
+        return None;
+    };
+    match auto_captions.subtitle_file(video_path).await {
+        Ok(subtitles) => subtitles,
+        Err(e) => {
+            tracing::error!("Failed to auto caption {}: {}", video_path, e);
+            None
+        }
+    }
+}
+
+// Translate a caption when the upload asked for a language, keeping the original if that fails
+async fn translate_caption(
+    caption: &str,
+    translate_to: Option<&str>,
+    config: &AppConfig,
+) -> Option<CaptionTranslation> {
+    let language = translate_to.map(str::trim).filter(|language| !language.is_empty())?;
+    if caption.is_empty() {
+        return None;
+    }
+    let Some(translation) = &config.translation else {
+        tracing::warn!("Caption translation to {} asked but no backend is configured", language);
+        return None;
+    };
+    match translation.translate(caption, language).await {
+        Ok(translated) => Some(CaptionTranslation {
+            original: caption.to_string(),
+            translated,
+            language: language.to_string(),
+        }),
+        Err(e) => {
+            tracing::error!("Failed to translate caption: {}", e);
+            None
+        }
     }
 }
 
-// Update state and broadcast new media
-async fn update_state_and_broadcast(
+// Update state and broadcast new media if it went straight on screen
+pub(crate) async fn update_state_and_broadcast(
     state: SharedState,
     media_info: MediaInfo,
     ws_clients: websocket::WsClients,
-) -> Result<(), Rejection> {
+) -> Result<Admission, Rejection> {
     let filename = media_info.filename.clone();
     let media_type = media_info.media_type; // MediaType implements Copy, no need to clone
 
     tracing::info!("Updating state with new media: {} ({:?})", filename, media_type);
 
+    // Clients show the first frame while the video loads
+    let mut media_info = media_info;
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    if media_type == MediaType::Video && media_info.poster.is_none() {
+        match VideoProcessor::extract_poster(&dirs, &filename).await {
+            Ok(poster) => media_info.poster = Some(poster),
+            Err(e) => tracing::warn!("No poster for {}: {}", filename, e),
+        }
+    }
+
     // Update shared state
-    let mut state = state.write().await;
-    state.set_last_media(media_info);
+    let submitted = media_info.clone();
+    let (admission, playback) = state
+        .call(move |state| (state.submit_media(submitted), state.playback()))
+        .await;
+    // Known uploaders score a point on the leaderboard
+    if media_info.uploader.is_some() && !matches!(admission, Admission::Rejected(_)) {
+        leaderboard::save_leaderboard(state.clone()).await;
+    }
 
-    tracing::info!("New media uploaded: {}", filename);
+    match &admission {
+        Admission::Shown => {
+            tracing::info!("New media uploaded: {}", filename);
+            websocket::broadcast_media(&ws_clients, &media_info, playback).await;
+        }
+        Admission::Queued(position) => {
+            tracing::info!("New media queued at position {}: {}", position, filename);
+        }
+        Admission::Held(position) => {
+            tracing::info!("New media held for quiet hours at position {}: {}", position, filename);
+        }
+        Admission::Locked(position) => {
+            tracing::info!("New media locked at position {}: {}", position, filename);
+        }
+        Admission::Rejected(_) => {
+            tracing::info!("New media rejected, screen busy: {}", filename);
+            let file_path = dirs.upload(&filename);
+            if let Err(e) = tokio::fs::remove_file(&file_path).await {
+                tracing::warn!("Failed to remove rejected file {}: {}", file_path, e);
+            }
+            VideoProcessor::remove_thumbnails(&dirs, &filename).await;
+        }
+    }
+
+    Ok(admission)
+}
+
+// Response for uploads that did not go straight on screen
+pub(crate) fn busy_message(
+    filename: &str,
+    admission: &Admission,
+    locale: Locale,
+) -> Option<String> {
+    admission_response(filename, admission, locale)
+        .map(|response| format!("<p>{}</p>", response.message))
+}
+
+// Reply to an upload that didn't go on screen right away
+pub(crate) fn admission_response(
+    filename: &str,
+    admission: &Admission,
+    locale: Locale,
+) -> Option<UploadResponse> {
+    let (outcome, position, message) = match admission {
+        Admission::Shown => return None,
+        Admission::Queued(position) => (
+            UploadOutcome::Queued,
+            Some(*position),
+            locale.format(Msg::Queued, &[&filename, position]),
+        ),
+        Admission::Held(position) => (
+            UploadOutcome::Held,
+            Some(*position),
+            locale.format(Msg::HeldForQuietHours, &[&filename, position]),
+        ),
+        Admission::Locked(position) => (
+            UploadOutcome::Locked,
+            Some(*position),
+            locale.format(Msg::HeldForPassphrase, &[&filename]),
+        ),
+        Admission::Rejected(remaining) => (
+            UploadOutcome::Busy,
+            None,
+            locale.format(Msg::ScreenBusy, &[&remaining.as_secs().max(1)]),
+        ),
+    };
+    Some(UploadResponse {
+        outcome,
+        // A refused upload is deleted, there's nothing left under its name
+        filename: (outcome != UploadOutcome::Busy).then(|| filename.to_string()),
+        queue_position: position,
+        retry_after_secs: match admission {
+            Admission::Rejected(remaining) => Some(remaining.as_secs().max(1)),
+            _ => None,
+        },
+        staged_id: None,
+        message,
+    })
+}
 
-    // Broadcast to websocket clients
-    if media_type != MediaType::Video {
-        tracing::info!("Broadcasting new media event");
-        websocket::broadcast_new_media(&ws_clients).await;
+/// Format of upload replies: JSON [`UploadResponse`]s for clients sending
+/// `Accept: application/json`, HTML for the upload page otherwise
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplyFormat {
+    Html,
+    Json,
+}
+
+impl ReplyFormat {
+    // Clients asking for JSON are bots and scripts rather than the upload page
+    fn source(self) -> MediaSource {
+        match self {
+            ReplyFormat::Html => MediaSource::WebForm,
+            ReplyFormat::Json => MediaSource::Api,
+        }
     }
+}
 
-    Ok(())
+pub fn reply_format() -> impl Filter<Extract = (ReplyFormat,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("accept").map(|accept: Option<String>| {
+        match accept.is_some_and(|accept| accept.contains("application/json")) {
+            true => ReplyFormat::Json,
+            false => ReplyFormat::Html,
+        }
+    })
 }
 
-fn detect_media_type(filename: &str) -> MediaType {
-    let ext = filename.split('.').next_back().unwrap_or("").to_lowercase();
-    match ext.as_str() {
-        "mp4" | "mov" | "avi" | "webm" | "ogg" | "mkv" | "wmv" | "flv" | "m4v" => MediaType::Video,
-        _ => MediaType::Image, // Default to image for jpg, png, gif, etc.
+/// An upload over the size limit of its type, answered with a 413
+#[derive(Debug)]
+pub struct TooLarge {
+    pub message: String,
+    pub limit_bytes: u64,
+}
+
+impl warp::reject::Reject for TooLarge {}
+
+impl TooLarge {
+    fn new(message: Msg, limit_bytes: u64, locale: Locale) -> Self {
+        Self {
+            message: locale.format(message, &[&(limit_bytes / (1024 * 1024))]),
+            limit_bytes,
+        }
+    }
+}
+
+/// Reject requests declaring a body over `limit_bytes`, before it is read
+pub fn size_limit(
+    limit_bytes: u64,
+    message: Msg,
+    default_locale: Locale,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<u64>("content-length")
+        .and(i18n::locale(default_locale))
+        .and_then(move |length: Option<u64>, locale: Locale| async move {
+            match length {
+                Some(length) if length > limit_bytes => {
+                    tracing::warn!("Upload too large: {} bytes", length);
+                    Err(warp::reject::custom(TooLarge::new(message, limit_bytes, locale)))
+                }
+                _ => Ok(()),
+            }
+        })
+        .untuple_one()
+}
+
+// Suggested wait for uploads turned away while the upload budget is spent
+const BUSY_RETRY_SECS: u64 = 5;
+
+/// An upload turned away while others hold the whole upload budget, answered with a 503
+#[derive(Debug)]
+pub struct Busy {
+    pub message: String,
+    pub retry_after_secs: u64,
+}
+
+impl warp::reject::Reject for Busy {}
+
+/// Hold the declared length of the request, or `max_bytes` when it declares none, out of the
+/// upload budget until the reply is out, see [`release`]
+pub fn reserve(
+    budget: UploadBudget,
+    max_bytes: u64,
+    default_locale: Locale,
+) -> impl Filter<Extract = (UploadPermit,), Error = Rejection> + Clone {
+    warp::header::optional::<u64>("content-length")
+        .and(i18n::locale(default_locale))
+        .and_then(move |length: Option<u64>, locale: Locale| {
+            let budget = budget.clone();
+            async move {
+                match budget.try_reserve(length.unwrap_or(max_bytes)) {
+                    Some(permit) => Ok(permit),
+                    None => {
+                        tracing::warn!("Upload budget spent, turning an upload away");
+                        Err(warp::reject::custom(Busy {
+                            message: locale.format(Msg::UploadsBusy, &[&BUSY_RETRY_SECS]),
+                            retry_after_secs: BUSY_RETRY_SECS,
+                        }))
+                    }
+                }
+            }
+        })
+}
+
+/// Give back the upload budget [`reserve`]d for a request along with its reply
+pub fn release<R: Reply>(permit: UploadPermit, reply: R) -> R {
+    drop(permit);
+    reply
+}
+
+/// A 413 with the limit for uploads turned away by [`size_limit`], a 503 saying when to retry
+/// for those turned away by [`reserve`], none for other rejections
+pub fn rejection_reply(rejection: &Rejection) -> Option<warp::reply::Response> {
+    if let Some(too_large) = rejection.find::<TooLarge>() {
+        let reply = warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({
+                "error": too_large.message,
+                "limit_bytes": too_large.limit_bytes,
+            })),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        );
+        return Some(reply.into_response());
     }
+    let busy = rejection.find::<Busy>()?;
+    let reply = warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({
+            "error": busy.message,
+            "retry_after_secs": busy.retry_after_secs,
+        })),
+        StatusCode::SERVICE_UNAVAILABLE,
+    );
+    let reply = warp::reply::with_header(reply, "retry-after", busy.retry_after_secs);
+    Some(reply.into_response())
 }
 
-fn is_valid_media_type(filename: &str) -> bool {
+// Outcome of an upload as shown on the upload page, staged uploads get a button publishing them
+fn upload_html(response: &UploadResponse, locale: Locale) -> warp::reply::Html<String> {
+    let publish = response
+        .staged_id
+        .map(|id| {
+            format!(
+                "<button hx-post=\"/publish/{}\" hx-target=\"#media-result\">{}</button>",
+                id,
+                locale.t(Msg::PublishNow)
+            )
+        })
+        .unwrap_or_default();
+    warp::reply::html(format!("<p>{}</p>{}", response.message, publish))
+}
+
+fn upload_reply(
+    response: UploadResponse,
+    format: ReplyFormat,
+    locale: Locale,
+) -> warp::reply::Response {
+    match format {
+        ReplyFormat::Html => upload_html(&response, locale).into_response(),
+        ReplyFormat::Json => warp::reply::json(&response).into_response(),
+    }
+}
+
+fn detect_media_type(filename: &str) -> MediaType {
     let ext = filename.split('.').next_back().unwrap_or("").to_lowercase();
     match ext.as_str() {
-        // Images
-        "jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp" | "tiff" | "svg" => true,
-        // Videos
-        "mp4" | "mov" | "avi" | "webm" | "ogg" | "mkv" | "wmv" | "flv" | "m4v" => true,
-        _ => false,
+        "mp4" | "mov" | "avi" | "webm" | "ogg" | "mkv" | "wmv" | "flv" | "m4v" => MediaType::Video,
+        _ => MediaType::Image, // Default to image for jpg, png, gif, etc.
     }
 }
 
@@ -345,10 +1590,13 @@ pub async fn upload_sound(
     _addr: Option<std::net::SocketAddr>,
     state: SharedState,
     ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+    locale: Locale,
 ) -> Result<impl Reply, Rejection> {
     tracing::info!("Processing sound upload");
     let mut original_filename = String::new();
     let mut file_data = Vec::new();
+    let mut options = SoundOptions::default();
 
     // Process the stream directly without collecting
     while let Some(result) = form.next().await {
@@ -364,9 +1612,10 @@ pub async fn upload_sound(
                         // Validate sound file type
                         if !is_valid_sound_type(&original_filename) {
                             tracing::warn!("Invalid sound file type: {}", original_filename);
-                            return Ok(warp::reply::html(
-                                "<p>Invalid sound file type! Only MP3, WAV, and OGG files are allowed.</p>".to_string(),
-                            ));
+                            return Ok(warp::reply::html(format!(
+                                "<p>{}</p>",
+                                locale.t(Msg::InvalidSoundType)
+                            )));
                         }
 
                         // Collect file data
@@ -383,6 +1632,24 @@ pub async fn upload_sound(
                             }
                         }
                     }
+                    "hotkey" => {
+                        let hotkey_str = read_field_as_string(field).await?;
+                        options.hotkey = hotkey_str.trim().parse::<u8>().ok().filter(|key| *key <= 9);
+                        tracing::info!("Parsed hotkey: {:?}", options.hotkey);
+                    }
+                    "start" => {
+                        options.trim_start = parse_timestamp(&read_field_as_string(field).await?);
+                        tracing::info!("Parsed trim start: {:?}", options.trim_start);
+                    }
+                    "end" => {
+                        options.trim_end = parse_timestamp(&read_field_as_string(field).await?);
+                        tracing::info!("Parsed trim end: {:?}", options.trim_end);
+                    }
+                    "visualize" => {
+                        options.visualize =
+                            Visualizer::from_name(&read_field_as_string(field).await?);
+                        tracing::info!("Parsed visualizer: {:?}", options.visualize);
+                    }
                     _ => {
                         tracing::debug!("Unknown field in sound upload: {}", field.name());
                     }
@@ -397,76 +1664,320 @@ pub async fn upload_sound(
 
     // Only proceed if we have a filename
     if !original_filename.is_empty() {
-        tracing::info!("Processing sound file: {} ({} bytes)", original_filename, file_data.len());
-        // Check file size limit (50MB for sounds)
-        if file_data.len() > 50 * 1024 * 1024 {
-            tracing::warn!("Sound file too large: {} bytes", file_data.len());
-            return Ok(warp::reply::html(
-                "<p>Sound file too large! Maximum size is 50MB.</p>".to_string(),
-            ));
-        }
-
-        // Sanitize the filename to prevent path traversal
-        let sanitized_filename = sanitize_filename(&original_filename)
-            .ok_or_else(|| {
-                tracing::error!("Invalid sound filename provided: {}", original_filename);
-                warp::reject::custom(AppError::IoError(std::io::Error::other("Invalid filename")))
-            })?;
-        
-        // Validate the file path to ensure it's within the sounds directory
-        let file_path = validate_file_path("sounds", &sanitized_filename)
-            .ok_or_else(|| {
-                tracing::error!("Invalid sound file path: {}", original_filename);
-                warp::reject::custom(AppError::IoError(std::io::Error::other("Invalid file path")))
-            })?;
-            
-        // Validate file content matches extension
-        if !is_valid_sound_content(&sanitized_filename, &file_data) {
-            tracing::error!("Sound file content does not match extension for: {}", sanitized_filename);
-            return Err(warp::reject::custom(AppError::IoError(std::io::Error::other(
-                "Sound file content does not match file extension"
-            ))));
-        }
-
-        // Create directory
-        tokio::fs::create_dir_all("sounds").await.map_err(|e| {
-            tracing::error!("Failed to create sounds directory: {}", e);
-            warp::reject::custom(AppError::IoError(e))
-        })?;
+        let (filename, data) = (&original_filename, &file_data);
+        return store_sound(filename, data, options, state, ws_clients, &config, locale).await;
+    }
 
-        // Create file
-        let mut file = File::create(&file_path).await.map_err(|e| {
-            tracing::error!("Failed to create sound file: {}", e);
-            warp::reject::custom(AppError::IoError(e))
-        })?;
+    tracing::warn!("No sound file uploaded");
+    Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::NoSoundUploaded))))
+}
 
-        // Write file data
-        file.write_all(&file_data).await.map_err(|e| {
-            tracing::error!("Failed to write sound file: {}", e);
-            warp::reject::custom(AppError::IoError(e))
-        })?;
+// Background music upload, added to the music playlist instead of the soundboard
+pub async fn upload_music(
+    mut form: FormData,
+    state: SharedState,
+    config: Arc<AppConfig>,
+    locale: Locale,
+) -> Result<impl Reply, Rejection> {
+    tracing::info!("Processing music upload");
+    let mut original_filename = String::new();
+    let mut file_data = Vec::new();
 
-        // Update shared state with new sound
-        let sound_info = SoundInfo {
-            filename: sanitized_filename.clone(),
-            upload_time: std::time::SystemTime::now(),
-            marked_for_deletion: false,
-        };
+    while let Some(result) = form.next().await {
+        match result {
+            Ok(mut field) => {
+                if field.name() != "music" {
+                    tracing::debug!("Unknown field in music upload: {}", field.name());
+                    continue;
+                }
+                original_filename = field.filename().unwrap_or("unnamed").to_string();
+                tracing::info!("Music filename: {}", original_filename);
+                while let Some(chunk_result) = field.data().await {
+                    match chunk_result {
+                        Ok(mut chunk) => {
+                            let bytes = chunk.copy_to_bytes(chunk.remaining());
+                            file_data.extend_from_slice(&bytes);
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed to read music file data: {}", e);
+                            return Err(warp::reject::custom(AppError::MultipartError));
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to read field: {}", e);
+                return Err(warp::reject::custom(AppError::MultipartError));
+            }
+        }
+    }
 
-        let mut state = state.write().await;
-        state.set_last_sound(sound_info);
-        tracing::info!("New sound uploaded: {}", sanitized_filename);
-        websocket::broadcast_new_song(&ws_clients, sanitized_filename.clone()).await;
+    if original_filename.is_empty() {
+        tracing::warn!("No music file uploaded");
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::NoMusicUploaded))));
+    }
 
+    // Sanitize the filename and check the content is really audio
+    let Some(sanitized_filename) = sanitize_filename(&original_filename) else {
+        tracing::warn!("Invalid music filename provided: {}", original_filename);
         return Ok(warp::reply::html(format!(
-            r#"<p>Sound {} uploaded successfully!</p>"#,
-            sanitized_filename
+            "<p>{}</p>",
+            locale.t(Msg::InvalidMusicFilename)
         )));
+    };
+    let filename =
+        match validation::validate(&sanitized_filename, &file_data, validation::SOUND_EXTENSIONS) {
+            Ok(filename) => filename,
+            Err(e) => {
+                tracing::warn!("Rejected music {}: {}", sanitized_filename, e);
+                let message = locale.format(Msg::InvalidMusicFile, &[&e]);
+                return Ok(warp::reply::html(format!("<p>{}</p>", message)));
+            }
+        };
+    let music = state.call(|state| state.data_dirs().music.clone()).await;
+    let file_path = validate_file_path(&music, &filename).ok_or_else(|| {
+        tracing::error!("Invalid music file path: {}", original_filename);
+        warp::reject::custom(AppError::IoError(std::io::Error::other("Invalid file path")))
+    })?;
+
+    tokio::fs::create_dir_all(&music).await.map_err(|e| {
+        tracing::error!("Failed to create music directory: {}", e);
+        warp::reject::custom(AppError::IoError(e))
+    })?;
+    tokio::fs::write(&file_path, &file_data).await.map_err(|e| {
+        tracing::error!("Failed to write music file: {}", e);
+        warp::reject::custom(AppError::IoError(e))
+    })?;
+
+    if let Some(rejection) = scan_upload(&file_path, &config, locale).await {
+        return Ok(warp::reply::html(format!("<p>{}</p>", rejection)));
     }
 
-    tracing::warn!("No sound file uploaded");
-    Ok(warp::reply::html(
-        "<p>No sound file uploaded!</p>".to_string(),
+    let track_filename = filename.clone();
+    let track = state
+        .call(move |state| state.music_mut().add_track(&track_filename))
+        .await;
+    tracing::info!("New music track uploaded: {} (track {})", filename, track.id);
+
+    Ok(warp::reply::html(format!(
+        "<p>{}</p>",
+        locale.format(Msg::TrackAdded, &[&filename])
+    )))
+}
+
+// Run the configured virus scan on a stored upload, deleting it and explaining why when it fails
+async fn scan_upload(path: &str, config: &AppConfig, locale: Locale) -> Option<String> {
+    let message = match config.virus_scan.scan_file(path).await {
+        Ok(ScanVerdict::Clean) => return None,
+        Ok(ScanVerdict::Infected(signature)) => locale.format(Msg::VirusDetected, &[&signature]),
+        Err(e) => {
+            tracing::error!("Virus scan failed for {}: {}", path, e);
+            locale.t(Msg::VirusScanFailed).to_string()
+        }
+    };
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        tracing::warn!("Failed to remove rejected file {}: {}", path, e);
+    }
+    Some(message)
+}
+
+// Optional settings of a sound upload
+#[derive(Default)]
+struct SoundOptions {
+    hotkey: Option<u8>,
+    trim_start: Option<f64>,
+    trim_end: Option<f64>,
+    visualize: Option<Visualizer>, // Also shown on screen as a waveform or spectrum video
+}
+
+// Validate, trim and store a sound, then register it in the soundboard
+async fn store_sound(
+    original_filename: &str,
+    file_data: &[u8],
+    options: SoundOptions,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: &AppConfig,
+    locale: Locale,
+) -> Result<warp::reply::Html<String>, Rejection> {
+    let SoundOptions {
+        hotkey,
+        trim_start,
+        trim_end,
+        visualize,
+    } = options;
+    tracing::info!("Processing sound file: {} ({} bytes)", original_filename, file_data.len());
+    // Check file size limit
+    let limit_bytes = config.upload_limits.sound_bytes();
+    if file_data.len() as u64 > limit_bytes {
+        tracing::warn!("Sound file too large: {} bytes", file_data.len());
+        return Err(warp::reject::custom(TooLarge::new(Msg::SoundTooLarge, limit_bytes, locale)));
+    }
+
+    // Sanitize the filename to prevent path traversal
+    let sanitized_filename = sanitize_filename(original_filename)
+        .ok_or_else(|| {
+            tracing::error!("Invalid sound filename provided: {}", original_filename);
+            warp::reject::custom(AppError::IoError(std::io::Error::other("Invalid filename")))
+        })?;
+
+    // Check the content is really a sound, naming it after its actual type
+    let sanitized_filename =
+        match validation::validate(&sanitized_filename, file_data, validation::SOUND_EXTENSIONS) {
+            Ok(filename) => filename,
+            Err(e) => {
+                tracing::warn!("Rejected sound {}: {}", sanitized_filename, e);
+                let message = locale.format(Msg::InvalidSoundFile, &[&e]);
+                return Ok(warp::reply::html(format!("<p>{}</p>", message)));
+            }
+        };
+
+    // Validate the file path to ensure it's within the sounds directory
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    let file_path = validate_file_path(&dirs.sounds, &sanitized_filename)
+        .ok_or_else(|| {
+            tracing::error!("Invalid sound file path: {}", original_filename);
+            warp::reject::custom(AppError::IoError(std::io::Error::other("Invalid file path")))
+        })?;
+
+    // Validate the trim range before doing any work
+    let trim_requested = trim_start.is_some() || trim_end.is_some();
+    if let (Some(start), Some(end)) = (trim_start, trim_end)
+        && end <= start
+    {
+        tracing::warn!("Invalid trim range: {} - {}", start, end);
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::InvalidTrimRange))));
+    }
+    if trim_requested && !VideoProcessor::is_ffmpeg_available() {
+        tracing::error!("Audio trimming not available. ffmpeg is not installed.");
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::TrimUnavailable))));
+    }
+
+    // Untrimmed uploads are written next to the final file and cut down into it
+    let write_path = if trim_requested {
+        dirs.sound(&format!("untrimmed_{}", sanitized_filename))
+    } else {
+        file_path.clone()
+    };
+
+    // Create directory
+    tokio::fs::create_dir_all(&dirs.sounds).await.map_err(|e| {
+        tracing::error!("Failed to create sounds directory: {}", e);
+        warp::reject::custom(AppError::IoError(e))
+    })?;
+
+    // Create file
+    let mut file = File::create(&write_path).await.map_err(|e| {
+        tracing::error!("Failed to create sound file: {}", e);
+        warp::reject::custom(AppError::IoError(e))
+    })?;
+
+    // Write file data
+    file.write_all(file_data).await.map_err(|e| {
+        tracing::error!("Failed to write sound file: {}", e);
+        warp::reject::custom(AppError::IoError(e))
+    })?;
+
+    if let Some(rejection) = scan_upload(&write_path, config, locale).await {
+        return Ok(warp::reply::html(format!("<p>{}</p>", rejection)));
+    }
+
+    if trim_requested {
+        let trim_result =
+            AudioProcessor::trim(&dirs.sounds, &write_path, &file_path, trim_start, trim_end)
+                .await;
+        if let Err(e) = tokio::fs::remove_file(&write_path).await {
+            tracing::warn!("Failed to remove untrimmed sound {}: {}", write_path, e);
+        }
+        if let Err(e) = trim_result {
+            tracing::error!("Failed to trim sound: {}", e);
+            return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::TrimFailed))));
+        }
+    }
+
+    // Register the sound in the soundboard library
+    let sound_filename = sanitized_filename.clone();
+    state
+        .call(move |state| {
+            let sound_info = state.add_sound(&sound_filename);
+            if hotkey.is_some() {
+                state.set_sound_hotkey(sound_info.id, hotkey);
+            }
+        })
+        .await;
+    tracing::info!("New sound uploaded: {}", sanitized_filename);
+
+    // Precompute the soundboard waveform preview in the background
+    let waveform_filename = sanitized_filename.clone();
+    let sounds = dirs.sounds.clone();
+    tokio::spawn(async move {
+        if let Err(e) = AudioProcessor::generate_waveform(&sounds, &waveform_filename).await {
+            tracing::warn!("Failed to generate waveform for {}: {}", waveform_filename, e);
+        }
+    });
+
+    websocket::broadcast_new_song(&ws_clients, sanitized_filename.clone()).await;
+
+    if let Some(visualizer) = visualize {
+        let filename = &sanitized_filename;
+        let reply = publish_visualizer(filename, visualizer, state, ws_clients, config, locale);
+        return Ok(warp::reply::html(reply.await?));
+    }
+
+    Ok(warp::reply::html(format!(
+        "<p>{}</p>",
+        locale.format(Msg::SoundUploaded, &[&sanitized_filename])
+    )))
+}
+
+// Turn a stored sound into a video of its waveform or spectrum and put it on screen
+async fn publish_visualizer(
+    sound_filename: &str,
+    visualizer: Visualizer,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    config: &AppConfig,
+    locale: Locale,
+) -> Result<String, Rejection> {
+    let uploaded = locale.format(Msg::SoundUploaded, &[&sound_filename]);
+    if !VideoProcessor::is_ffmpeg_available() {
+        tracing::warn!("FFmpeg not available, no visualizer for {}", sound_filename);
+        return Ok(format!("<p>{} {}</p>", uploaded, locale.t(Msg::VisualizerFailed)));
+    }
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    let visualized =
+        AudioProcessor::visualize(&dirs.sounds, &dirs.uploads, sound_filename, visualizer);
+    let filename = match visualized.await {
+        Ok(filename) => filename,
+        Err(e) => {
+            tracing::warn!("Failed to visualize {}: {}", sound_filename, e);
+            return Ok(format!("<p>{} {}</p>", uploaded, locale.t(Msg::VisualizerFailed)));
+        }
+    };
+
+    let probe = MediaProbe::probe(&dirs.upload(&filename)).await;
+    let play_secs = probe
+        .duration_secs
+        .map(|secs| secs.ceil() as u64)
+        .unwrap_or(config.video_busy_secs);
+    let mut media_info = create_media_info(
+        filename.clone(),
+        MediaType::Video,
+        999999, // Videos play full duration
+        String::new(),
+        play_secs,
+        None, // Sound uploads aren't attributed
+    );
+    media_info.probe = probe;
+    media_info.source = MediaSource::Soundboard;
+
+    let admission = update_state_and_broadcast(state, media_info, ws_clients).await?;
+    if let Some(busy_message) = busy_message(&filename, &admission, locale) {
+        return Ok(format!("<p>{}</p>{}", uploaded, busy_message));
+    }
+    Ok(format!(
+        "<p>{}</p>",
+        locale.format(Msg::SoundVisualized, &[&sound_filename])
     ))
 }
 
@@ -476,27 +1987,38 @@ fn is_valid_sound_type(filename: &str) -> bool {
     matches!(ext.as_str(), "mp3" | "wav" | "ogg" | "flac" | "m4a")
 }
 
-// Process video with caption overlay using ffmpeg
-async fn process_video_with_caption(
+// Re-encode a video with its caption and watermark using ffmpeg
+async fn process_video(
+    dirs: &DataDirs,
     original_filename: &str,
     caption: &str,
-) -> Result<String, Rejection> {
-    tracing::info!("Processing video with caption overlay: {}", original_filename);
+    options: &EncodeOptions,
+    state: &SharedState,
+) -> Result<(String, Vec<WarningReason>), Rejection> {
+    tracing::info!("Processing video with caption/watermark overlay: {}", original_filename);
     // Check if ffmpeg is available
     if !VideoProcessor::is_ffmpeg_available() {
-        tracing::warn!("FFmpeg not available, skipping caption overlay");
-        return Ok(original_filename.to_string());
+        tracing::warn!("FFmpeg not available, skipping caption/watermark overlay");
+        return Ok((original_filename.to_string(), vec![WarningReason::FfmpegMissing]));
     }
 
     // Generate output filename
     let output_filename = VideoProcessor::generate_output_filename(original_filename);
 
-    let input_path = format!("uploads/{}", original_filename);
-    let output_path = format!("uploads/{}", output_filename);
+    let input_path = dirs.upload(original_filename);
+    let output_path = dirs.upload(&output_filename);
 
-    // Process video with caption overlay
-    match VideoProcessor::add_caption_overlay(&input_path, &output_path, caption).await {
-        Ok(_) => {
+    // Process video with caption overlay, keeping the original until the output is known to play
+    let encoded = VideoProcessor::encode_verified(
+        &dirs.uploads,
+        &input_path,
+        &output_path,
+        caption,
+        options,
+        None,
+    );
+    match encoded.await {
+        Ok(warnings) => {
             tracing::info!(
                 "Successfully processed video with caption: {}",
                 output_filename
@@ -507,12 +2029,125 @@ async fn process_video_with_caption(
                 tracing::warn!("Failed to remove original video file {}: {}", input_path, e);
             }
 
-            Ok(output_filename)
+            Ok((output_filename, warnings))
         }
         Err(e) => {
             tracing::error!("Failed to process video with caption: {}", e);
-            // Return original filename if processing fails
-            Ok(original_filename.to_string())
+            let event = Event::ProcessingFailed {
+                filename: original_filename.to_string(),
+                error: e.to_string(),
+            };
+            state.call(move |state| state.notify(event)).await;
+            // The original is still there, shown as uploaded
+            Ok((original_filename.to_string(), vec![WarningReason::ProcessingFailed]))
+        }
+    }
+}
+
+// Whether the caption and watermark were left out of a video
+fn skipped(warnings: &[WarningReason]) -> bool {
+    warnings.iter().any(|reason| {
+        matches!(reason, WarningReason::FfmpegMissing | WarningReason::ProcessingFailed)
+    })
+}
+
+// Record what an upload went without as events, and tell the upload pages
+async fn report_warnings(
+    state: &SharedState,
+    ws_clients: &websocket::WsClients,
+    filename: &str,
+    warnings: &[WarningReason],
+) {
+    for &reason in warnings {
+        tracing::warn!("{}: {}", filename, reason.message());
+        let event = Event::ProcessingWarning {
+            filename: filename.to_string(),
+            reason,
+        };
+        state.call(move |state| state.notify(event)).await;
+        websocket::broadcast_processing_warning(ws_clients, filename, reason).await;
+    }
+}
+
+// Overlay the watermark on an uploaded image, keeping the original if that fails
+async fn watermark_image(
+    dirs: &DataDirs,
+    original_filename: &str,
+    watermark: &Watermark,
+) -> String {
+    // Animated GIFs would lose all but their first frame
+    if original_filename.to_lowercase().ends_with(".gif") || !VideoProcessor::is_ffmpeg_available() {
+        return original_filename.to_string();
+    }
+
+    let output_filename = VideoProcessor::generate_output_filename(original_filename);
+    let input_path = dirs.upload(original_filename);
+    let output_path = dirs.upload(&output_filename);
+
+    match VideoProcessor::watermark_image(&dirs.uploads, &input_path, &output_path, watermark).await
+    {
+        Ok(_) => {
+            if let Err(e) = tokio::fs::remove_file(&input_path).await {
+                tracing::warn!("Failed to remove original image file {}: {}", input_path, e);
+            }
+            output_filename
+        }
+        Err(e) => {
+            tracing::error!("Failed to watermark image: {}", e);
+            let _ = tokio::fs::remove_file(&output_path).await;
+            original_filename.to_string()
+        }
+    }
+}
+
+// Convert an uploaded image, keeping the original if that fails or doesn't make it smaller
+async fn convert_image(dirs: &DataDirs, original_filename: &str, format: ImageFormat) -> String {
+    let Some(extension) = format.extension() else {
+        return original_filename.to_string();
+    };
+    if !VideoProcessor::is_ffmpeg_available() {
+        return original_filename.to_string();
+    }
+
+    let output_filename = VideoProcessor::generate_output_filename(original_filename);
+    let output_filename = match output_filename.rsplit_once('.') {
+        Some((name, _)) => format!("{}.{}", name, extension),
+        None => format!("{}.{}", output_filename, extension),
+    };
+    let input_path = dirs.upload(original_filename);
+    let output_path = dirs.upload(&output_filename);
+
+    let converted = VideoProcessor::convert_image(&dirs.uploads, &input_path, &output_path, format);
+    if let Err(e) = converted.await {
+        tracing::error!("Failed to convert image to {}: {}", extension, e);
+        let _ = tokio::fs::remove_file(&output_path).await;
+        return original_filename.to_string();
+    }
+
+    let size = |path: String| async move {
+        tokio::fs::metadata(path)
+            .await
+            .map(|metadata| metadata.len())
+            .ok()
+    };
+    match (size(input_path.clone()).await, size(output_path.clone()).await) {
+        (Some(original), Some(converted)) if converted < original => {
+            tracing::info!(
+                "Converted {} to {} ({} -> {} bytes)",
+                original_filename,
+                extension,
+                original,
+                converted
+            );
+            if let Err(e) = tokio::fs::remove_file(&input_path).await {
+                tracing::warn!("Failed to remove original image file {}: {}", input_path, e);
+            }
+            output_filename
+        }
+        _ => {
+            tracing::info!("Keeping {}, {} wasn't any smaller", original_filename, extension);
+            let _ = tokio::fs::remove_file(&output_path).await;
+            original_filename.to_string()
         }
     }
 }
@@ -520,22 +2155,49 @@ async fn process_video_with_caption(
 // Video upload handler (YouTube, TikTok)
 pub async fn upload_video_url(
     form: std::collections::HashMap<String, String>,
+    client: Option<ClientId>,
     state: SharedState,
     ws_clients: websocket::WsClients,
+    config: Arc<AppConfig>,
+    jobs: SharedJobs,
+    locale: Locale,
 ) -> Result<impl Reply, Rejection> {
     tracing::info!("Processing video URL upload");
+    let caption = form.get("caption").map(|caption| caption.trim()).unwrap_or_default();
+    let Some((caption, review)) = filter_caption(&state, caption).await else {
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::CaptionRefused))));
+    };
     let video_url = form
         .get("video_url")
         .cloned()
         .or_else(|| form.get("youtube_url").cloned()) // Backward compatibility
         .unwrap_or_default();
-    let caption = form.get("caption").cloned().unwrap_or_default();
+    let translation =
+        translate_caption(&caption, form.get("translate_to").map(String::as_str), &config).await;
+    let caption = translation
+        .as_ref()
+        .map(|translation| translation.translated.clone())
+        .unwrap_or(caption);
+    let quality = form
+        .get("quality")
+        .and_then(|name| QualityProfile::from_name(name))
+        .unwrap_or(config.quality);
+    let watermark = config
+        .watermark
+        .clone()
+        .filter(|_| form.get("watermark").and_then(|value| parse_toggle(value)).unwrap_or(true));
 
     if video_url.is_empty() {
         tracing::warn!("No video URL provided");
-        return Ok(warp::reply::html(
-            "<p>No video URL provided!</p>".to_string(),
-        ));
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::NoVideoUrl))));
+    }
+
+    if !config.platforms.is_supported_video_url(&video_url) {
+        tracing::warn!("Refused video URL: {}", video_url);
+        return Ok(warp::reply::html(format!(
+            "<p>{}</p>",
+            config.platforms.rejection_message(&video_url)
+        )));
     }
 
     tracing::info!("Downloading video from URL: {}", video_url);
@@ -543,123 +2205,130 @@ pub async fn upload_video_url(
     // Check if yt-dlp is available
     if !VideoProcessor::is_ytdlp_available() {
         tracing::error!("Video download not available. yt-dlp is not installed.");
-        return Ok(warp::reply::html(
-            "<p>Video download not available. yt-dlp is not installed.</p>".to_string(),
-        ));
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::YtDlpMissing))));
     }
 
     // Get video info first
-    let video_info = match VideoProcessor::get_video_metadata(&video_url).await {
+    let video_info = match VideoProcessor::get_video_metadata(&video_url, &config.platforms).await {
         Ok(info) => info,
         Err(e) => {
             tracing::error!("Failed to get video info: {}", e);
-            let user_error = VideoProcessor::get_user_friendly_error(&e.to_string(), &video_url);
+            let user_error =
+                VideoProcessor::get_user_friendly_error(&e.to_string(), &video_url, locale);
             return Ok(warp::reply::html(format!("<p>{}</p>", user_error)));
         }
     };
 
-    tracing::info!("Video info - Title: {}, Duration: {}s, Uploader: {}", 
-                   video_info.title, video_info.duration, video_info.uploader);
+    tracing::info!("Video info - Title: {}, Duration: {}s, Uploader: {}, Platform: {:?}", 
+                   video_info.title, video_info.duration, video_info.uploader, video_info.platform);
 
     // Check video duration (limit to reasonable length)
-    if video_info.duration > 600 {
-        // 10 minutes
+    if video_info.duration > MAX_VIDEO_SECS {
         tracing::warn!("Video too long: {} seconds", video_info.duration);
-        return Ok(warp::reply::html(
-            "<p>Video too long! Maximum duration is 10 minutes.</p>".to_string(),
-        ));
+        return Ok(warp::reply::html(format!("<p>{}</p>", locale.t(Msg::VideoTooLong))));
     }
 
     // Use streaming download and processing for better performance
-    let filename = match VideoProcessor::stream_process_video(&video_url, "uploads", 
-        if !caption.is_empty() { Some(&caption) } else { None }).await {
-        Ok(filename) => {
-            tracing::info!("Successfully downloaded and processed video: {}", filename);
-            filename
+    let options = EncodeOptions {
+        hw_accel: state.call(|state| state.hw_accel()).await,
+        quality,
+        watermark,
+        display_max_height: config.display_max_height(),
+        framing: form
+            .get("framing")
+            .and_then(|name| Framing::from_name(name))
+            .unwrap_or(config.framing),
+        caption_fonts: config.caption_fonts.clone(),
+        ..EncodeOptions::default()
+    };
+    // Progress is reported to the jobs list and WebSocket clients while downloading
+    let progress =
+        jobs::start_job(jobs, ws_clients.clone(), JobKind::Download, &video_url).await;
+    tracing::info!("Downloading as job {}", progress.id());
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    let processed = match VideoProcessor::stream_process_video(&video_url, &dirs.uploads, 
+        if !caption.is_empty() { Some(&caption) } else { None }, &options, config.max_download_height(), &progress).await {
+        Ok(processed) => {
+            tracing::info!("Successfully downloaded and processed video: {}", processed.filename);
+            progress.stage(JobStage::Done);
+            processed
         },
         Err(e) => {
             tracing::error!("Failed to download/process video: {}", e);
-            let user_error = VideoProcessor::get_user_friendly_error(&e.to_string(), &video_url);
+            if progress.is_cancelled() {
+                let message = locale.t(Msg::DownloadCancelled);
+                return Ok(warp::reply::html(format!("<p>{}</p>", message)));
+            }
+            progress.stage(JobStage::Failed);
+            let (job_id, url) = (progress.id(), video_url.clone());
+            state
+                .call(move |state| state.notify(Event::JobFailed { job_id, url }))
+                .await;
+            let user_error =
+                VideoProcessor::get_user_friendly_error(&e.to_string(), &video_url, locale);
             return Ok(warp::reply::html(format!("<p>{}</p>", user_error)));
         }
     };
+    let filename = processed.filename;
+    // Degraded to the plain download, the caption and watermark are missing
+    let embedded = processed.error.is_none();
+    if let Some(error) = processed.error {
+        let event = Event::ProcessingFailed {
+            filename: filename.clone(),
+            error,
+        };
+        state.call(move |state| state.notify(event)).await;
+    }
+    report_warnings(&state, &ws_clients, &filename, &processed.warnings).await;
+
+    if let Some(rejection) = scan_upload(&dirs.upload(&filename), &config, locale).await {
+        return Ok(warp::reply::html(format!("<p>{}</p>", rejection)));
+    }
+
+    // Keep the screen busy for the length of the file, the site's figure is only a fallback
+    let probe = MediaProbe::probe(&dirs.upload(&filename)).await;
+    let play_secs = probe
+        .duration_secs
+        .map(|secs| secs.ceil() as u64)
+        .unwrap_or(video_info.duration);
 
     // Create media info
-    let media_info = create_media_info(
+    let mut media_info = create_media_info(
         filename.clone(),
         MediaType::Video,
-        999999,        // Videos play full duration
-        String::new(), // Caption is embedded if provided
+        999999, // Videos play full duration
+        // Caption is embedded if provided, shown by the display when that failed
+        if embedded { String::new() } else { caption.clone() },
+        play_secs,
+        client,
     );
+    media_info.probe = probe;
+    media_info.translation = translation;
+    media_info.source = MediaSource::UrlDownload;
+    media_info.platform = Some(video_info.platform);
+    if review {
+        let held = hold_for_review(&state, media_info, &config).await;
+        let message = locale.format(Msg::InReview, &[&held.filename]);
+        return Ok(warp::reply::html(format!("<p>{}</p>", message)));
+    }
 
     // Update shared state and broadcast video event
-    update_state_and_broadcast(state, media_info, ws_clients.clone()).await?;
-
-    // Broadcast the video event for video downloads
-    websocket::broadcast_video_event(&ws_clients, filename.clone()).await;
+    let admission = update_state_and_broadcast(state, media_info, ws_clients).await?;
+    if let Some(busy_message) = busy_message(&video_info.title, &admission, locale) {
+        return Ok(warp::reply::html(busy_message));
+    }
 
     // Return success response
-    let caption_message = if !caption.is_empty() {
-        "<br/>Caption embedded in video"
+    let caption_message = if !caption.is_empty() && embedded {
+        locale.t(Msg::CaptionEmbedded)
     } else {
         ""
     };
 
     tracing::info!("Video URL upload completed successfully");
+    let (title, duration) = (&video_info.title, &video_info.duration);
     Ok(warp::reply::html(format!(
-        r#"<p>Downloaded "{}" successfully!<br/>Duration: {} seconds{}</p>"#,
-        video_info.title, video_info.duration, caption_message
+        "<p>{}</p>",
+        locale.format(Msg::Downloaded, &[title, duration, &caption_message])
     )))
 }
-
-/// Validate file content matches the file extension
-fn is_valid_file_content(filename: &str, data: &[u8]) -> bool {
-    let ext = filename.split('.').next_back().unwrap_or("").to_lowercase();
-    
-    match ext.as_str() {
-        // Image formats
-        "jpg" | "jpeg" => data.starts_with(&[0xFF, 0xD8, 0xFF]),
-        "png" => data.starts_with(&[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A]),
-        "gif" => data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a"),
-        "webp" => data.starts_with(b"RIFF") && data.len() > 12 && data[8..12] == *b"WEBP",
-        "bmp" => data.starts_with(b"BM"),
-        "svg" => data.starts_with(b"<?xml") || data.starts_with(b"<svg"),
-        
-        // Video formats
-        "mp4" => data.starts_with(b"\x00\x00\x00\x18ftypmp42") || 
-                 data.starts_with(b"\x00\x00\x00\x20ftypmp42") ||
-                 data.starts_with(b"\x00\x00\x00\x18ftypmp41") ||
-                 data.starts_with(b"\x00\x00\x00\x18ftypiso5"),
-        "mov" | "m4v" => data.starts_with(b"\x00\x00\x00\x14ftypqt") || 
-                         data.starts_with(b"\x00\x00\x00\x20ftypM4V"),
-        "avi" => data.starts_with(b"RIFF") && data.len() > 8 && data[8..12] == *b"AVI ",
-        "webm" => data.starts_with(b"\x1A\x45\xDF\xA3"),
-        "mkv" => data.starts_with(b"\x1A\x45\xDF\xA3"),
-        "ogg" => data.starts_with(b"OggS"),
-        "wmv" => data.starts_with(b"\x30\x26\xB2\x75\x8E\x66\xCF\x11"),
-        "flv" => data.starts_with(b"FLV\x01"),
-        
-        // If we don't recognize the extension, we'll allow it (better to be permissive than restrictive)
-        _ => true,
-    }
-}
-
-/// Validate sound file content matches the file extension
-fn is_valid_sound_content(filename: &str, data: &[u8]) -> bool {
-    let ext = filename.split('.').next_back().unwrap_or("").to_lowercase();
-    
-    match ext.as_str() {
-        "mp3" => data.starts_with(&[0xFF, 0xFB]) || // MP3 with ID3v2
-                 data.starts_with(&[0x49, 0x44, 0x33]) || // ID3v2 header
-                 data.starts_with(&[0xFF, 0xF3]) || // MP3 without ID3
-                 data.starts_with(&[0xFF, 0xF2]),
-        "wav" => data.starts_with(b"RIFF") && data.len() > 8 && data[8..12] == *b"WAVE",
-        "ogg" => data.starts_with(b"OggS"),
-        "flac" => data.starts_with(b"fLaC"),
-        "m4a" => data.starts_with(b"\x00\x00\x00\x20ftypM4A") ||
-                 data.starts_with(b"\x00\x00\x00\x18ftypmp42") ||
-                 data.starts_with(b"\x00\x00\x00\x18ftypM4A "),
-        // If we don't recognize the extension, we'll allow it
-        _ => true,
-    }
-}
@@ -0,0 +1,125 @@
+use crate::config::AppConfig;
+use crate::handlers::media::SharedState;
+use crate::handlers::upload::{admission_response, update_state_and_broadcast};
+use crate::i18n::{Locale, Msg};
+use crate::types::{UploadOutcome, UploadResponse};
+use crate::video_processing::VideoProcessor;
+use crate::websocket;
+use crate::word_filter::WordFilter;
+use serde_json::json;
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+pub async fn load_word_filter(state: SharedState, config: &AppConfig) {
+    let Some(word_filter) = &config.word_filter else {
+        return;
+    };
+    let word_filter = match WordFilter::load(word_filter) {
+        Ok(word_filter) => word_filter,
+        Err(e) => {
+            tracing::error!("Word filter not loaded: {}", e);
+            return;
+        }
+    };
+    tracing::info!("Word filter loaded with {} words", word_filter.len());
+    state.call(move |state| state.set_word_filter(word_filter)).await;
+}
+
+// Admin reload of the word list file after editing it, the words in use stay when that fails
+pub async fn reload_word_filter(
+    state: SharedState,
+    config: Arc<AppConfig>,
+) -> Result<impl Reply, Rejection> {
+    let Some(word_filter) = &config.word_filter else {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": "No word filter is configured" })),
+            StatusCode::NOT_FOUND,
+        ));
+    };
+    let word_filter = match WordFilter::load(word_filter) {
+        Ok(word_filter) => word_filter,
+        Err(e) => {
+            tracing::error!("Word filter not reloaded: {}", e);
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "error": e.to_string() })),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+    let words = word_filter.len();
+    tracing::info!("Word filter reloaded with {} words", words);
+    state.call(move |state| state.set_word_filter(word_filter)).await;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({ "words": words })),
+        StatusCode::OK,
+    ))
+}
+
+pub async fn list_review(state: SharedState) -> Result<impl Reply, Rejection> {
+    let entries = state.call(|state| state.review().entries()).await;
+    Ok(warp::reply::json(&json!({ "review": entries })))
+}
+
+// Admin approval of an upload the word filter held, it goes on screen like any other
+pub async fn approve_review(
+    id: u64,
+    state: SharedState,
+    ws_clients: websocket::WsClients,
+    locale: Locale,
+) -> Result<impl Reply, Rejection> {
+    let Some(media_info) = state
+        .call(move |state| {
+            let now = state.now();
+            state.review_mut().take(id, now)
+        })
+        .await
+    else {
+        return Ok(not_in_review());
+    };
+    let filename = media_info.filename.clone();
+    tracing::info!("Approved {} from review", filename);
+    let admission = update_state_and_broadcast(state, media_info, ws_clients).await?;
+    let response = admission_response(&filename, &admission, locale).unwrap_or_else(|| {
+        UploadResponse {
+            outcome: UploadOutcome::Shown,
+            filename: Some(filename.clone()),
+            queue_position: None,
+            retry_after_secs: None,
+            staged_id: None,
+            message: locale.format(Msg::Published, &[&filename]),
+        }
+    });
+    Ok(warp::reply::with_status(warp::reply::json(&response), StatusCode::OK))
+}
+
+// Admin refusal of an upload the word filter held, deleting it
+pub async fn reject_review(id: u64, state: SharedState) -> Result<impl Reply, Rejection> {
+    let Some(media_info) = state
+        .call(move |state| {
+            let now = state.now();
+            state.review_mut().take(id, now)
+        })
+        .await
+    else {
+        return Ok(not_in_review());
+    };
+    let filename = media_info.filename;
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    if let Err(e) = tokio::fs::remove_file(dirs.upload(&filename)).await {
+        tracing::error!("Failed to delete rejected upload {}: {}", filename, e);
+    }
+    VideoProcessor::remove_thumbnails(&dirs, &filename).await;
+    tracing::info!("Rejected {} from review", filename);
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({ "filename": filename })),
+        StatusCode::OK,
+    ))
+}
+
+fn not_in_review() -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&json!({ "error": "No such upload in review, it may have expired" })),
+        StatusCode::NOT_FOUND,
+    )
+}
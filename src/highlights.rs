@@ -0,0 +1,385 @@
+use crate::errors::AppError;
+use crate::filter_graph::{DrawText, FilterGraph};
+use crate::media_probe::MediaProbe;
+use crate::retry::{self, RetryPolicy};
+use crate::state::{ArchiveEntry, MediaType};
+use crate::video_processing::{HwAccel, QualityProfile};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Archive filename prefix of generated reels, so they are not folded into the next one
+pub const REEL_PREFIX: &str = "highlights_";
+
+// Every segment is encoded alike so the concat demuxer can join them without re-encoding
+const REEL_WIDTH: u32 = 1280;
+const REEL_HEIGHT: u32 = 720;
+const REEL_FPS: u32 = 30;
+const TITLE_CARD_SECS: u32 = 2;
+const SILENCE: &str = "anullsrc=r=44100:cl=stereo";
+
+/// Periodic highlight reel of the clips pinned to the archive
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct HighlightSchedule {
+    pub enabled: bool,
+    /// Hours between reels, each covering the clips pinned during that time
+    pub every_hours: u64,
+    /// Put the reel on screen once it is ready
+    pub broadcast: bool,
+}
+
+impl Default for HighlightSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            every_hours: 7 * 24,
+            broadcast: false,
+        }
+    }
+}
+
+impl HighlightSchedule {
+    pub fn every(&self) -> Duration {
+        Duration::from_secs(self.every_hours.max(1) * 3600)
+    }
+}
+
+pub fn is_reel(entry: &ArchiveEntry) -> bool {
+    entry.filename.starts_with(REEL_PREFIX)
+}
+
+/// Archived videos pinned at or after `since` (Unix seconds), oldest first
+pub fn select_clips(entries: &[ArchiveEntry], since: u64) -> Vec<&ArchiveEntry> {
+    let mut clips: Vec<&ArchiveEntry> = entries
+        .iter()
+        .filter(|entry| {
+            entry.media_type == MediaType::Video && entry.pinned_at >= since && !is_reel(entry)
+        })
+        .collect();
+    clips.sort_by_key(|entry| entry.pinned_at);
+    clips
+}
+
+/// Time until the next reel is due, counting from the last one in the archive
+pub fn next_run_delay(entries: &[ArchiveEntry], every: Duration, now: u64) -> Duration {
+    let Some(last) = entries
+        .iter()
+        .filter(|entry| is_reel(entry))
+        .map(|entry| entry.pinned_at)
+        .max()
+    else {
+        return Duration::ZERO;
+    };
+    let elapsed = Duration::from_secs(now.saturating_sub(last));
+    every.saturating_sub(elapsed)
+}
+
+/// Concat demuxer script joining the given files, relative to the script's directory
+fn concat_list(files: &[String]) -> String {
+    files
+        .iter()
+        .map(|file| format!("file '{}'\n", file.replace('\'', "'\\''")))
+        .collect()
+}
+
+fn title(index: usize, entry: &ArchiveEntry) -> String {
+    if entry.caption.is_empty() {
+        format!("#{}", index + 1)
+    } else {
+        format!("#{} - {}", index + 1, entry.caption)
+    }
+}
+
+fn encoding_args(quality: QualityProfile) -> Vec<String> {
+    let mut args = vec!["-c:v".to_string(), "libx264".to_string()];
+    args.extend(quality.encoder_args(HwAccel::None).into_iter().map(String::from));
+    args.extend(
+        ["-pix_fmt", "yuv420p", "-c:a", "aac", "-ar", "44100", "-ac", "2"].map(String::from),
+    );
+    args
+}
+
+/// Black card with the clip's title, and silence so every segment has an audio track
+fn title_card_args(text: &str, output: &str, quality: QualityProfile) -> Vec<String> {
+    let mut text = DrawText::caption(text, 64);
+    text.y = "(h-text_h)/2".to_string();
+
+    let mut args: Vec<String> = [
+        "-f",
+        "lavfi",
+        "-i",
+        &format!(
+            "color=c=black:s={}x{}:r={}:d={}",
+            REEL_WIDTH, REEL_HEIGHT, REEL_FPS, TITLE_CARD_SECS
+        ),
+        "-f",
+        "lavfi",
+        "-i",
+        SILENCE,
+    ]
+    .map(String::from)
+    .to_vec();
+    args.extend(FilterGraph::new().drawtext(&text).to_args());
+    args.push("-shortest".to_string());
+    args.extend(encoding_args(quality));
+    args.extend(["-y".to_string(), output.to_string()]);
+    args
+}
+
+/// Re-encode a clip to the reel's size, frame rate and audio layout
+fn clip_args(input: &str, has_audio: bool, output: &str, quality: QualityProfile) -> Vec<String> {
+    let mut args = vec!["-i".to_string(), input.to_string()];
+    if !has_audio {
+        args.extend(["-f", "lavfi", "-i", SILENCE].map(String::from));
+    }
+    args.extend(
+        FilterGraph::new()
+            .fit(REEL_WIDTH, REEL_HEIGHT)
+            .fps(REEL_FPS)
+            .to_args(),
+    );
+    if has_audio {
+        args.extend(["-map", "0:v:0", "-map", "0:a:0"].map(String::from));
+    } else {
+        args.extend(["-map", "0:v:0", "-map", "1:a:0", "-shortest"].map(String::from));
+    }
+    args.extend(encoding_args(quality));
+    args.extend(["-y".to_string(), output.to_string()]);
+    args
+}
+
+async fn run_ffmpeg(label: &'static str, args: &[String]) -> Result<(), AppError> {
+    tracing::debug!("FFmpeg command: ffmpeg {:?}", args);
+    let output = RetryPolicy::default()
+        .output(label, retry::command("ffmpeg", args))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute ffmpeg: {}", e);
+            AppError::IoError(std::io::Error::other("Highlight reel failed"))
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        tracing::error!("FFmpeg {} failed: {}", label, stderr);
+        return Err(AppError::IoError(std::io::Error::other(format!(
+            "FFmpeg processing failed: {}",
+            stderr
+        ))));
+    }
+    Ok(())
+}
+
+/// Join the clips, stored in `dir`, into `dir/output` with a title card before each one
+/// Clips that can't be read are left out; fails if none could be used
+pub async fn build_reel(
+    clips: &[&ArchiveEntry],
+    dir: &str,
+    output: &str,
+    quality: QualityProfile,
+) -> Result<(), AppError> {
+    let work_dir = format!("{}/.{}", dir, output);
+    tokio::fs::create_dir_all(&work_dir)
+        .await
+        .map_err(AppError::IoError)?;
+
+    let result = assemble(clips, dir, &work_dir, output, quality).await;
+
+    if let Err(e) = tokio::fs::remove_dir_all(&work_dir).await {
+        tracing::warn!("Failed to remove {}: {}", work_dir, e);
+    }
+    result
+}
+
+async fn assemble(
+    clips: &[&ArchiveEntry],
+    dir: &str,
+    work_dir: &str,
+    output: &str,
+    quality: QualityProfile,
+) -> Result<(), AppError> {
+    let segments = build_segments(clips, dir, work_dir, quality).await?;
+    concat_segments(&segments, work_dir, &format!("{}/{}", dir, output)).await
+}
+
+/// Join videos, given by path, one after the other into `dir/output`, each fit to the reel's
+/// size, frame rate and audio layout first
+/// Unlike the reel's, a clip that can't be read fails the whole join
+pub async fn join_clips(
+    inputs: &[String],
+    dir: &str,
+    output: &str,
+    quality: QualityProfile,
+) -> Result<(), AppError> {
+    let work_dir = format!("{}/.{}", dir, output);
+    tokio::fs::create_dir_all(&work_dir)
+        .await
+        .map_err(AppError::IoError)?;
+
+    let mut result = Ok(());
+    let mut segments = Vec::new();
+    for (index, input) in inputs.iter().enumerate() {
+        let probe = MediaProbe::probe(input).await;
+        let clip_name = format!("clip_{}.mp4", index);
+        let clip_path = format!("{}/{}", work_dir, clip_name);
+        result = run_ffmpeg(
+            "ffmpeg join clip",
+            &clip_args(input, probe.has_audio, &clip_path, quality),
+        )
+        .await;
+        if result.is_err() {
+            break;
+        }
+        segments.push(clip_name);
+    }
+    if result.is_ok() {
+        result = concat_segments(&segments, &work_dir, &format!("{}/{}", dir, output)).await;
+    }
+
+    if let Err(e) = tokio::fs::remove_dir_all(&work_dir).await {
+        tracing::warn!("Failed to remove {}: {}", work_dir, e);
+    }
+    result
+}
+
+// Join segments encoded alike, listed in `work_dir`, without re-encoding them
+async fn concat_segments(
+    segments: &[String],
+    work_dir: &str,
+    output: &str,
+) -> Result<(), AppError> {
+    let list_path = format!("{}/list.txt", work_dir);
+    tokio::fs::write(&list_path, concat_list(segments))
+        .await
+        .map_err(AppError::IoError)?;
+
+    let args: Vec<String> = [
+        "-f",
+        "concat",
+        "-safe",
+        "0",
+        "-i",
+        &list_path,
+        "-c",
+        "copy",
+        "-movflags",
+        "+faststart",
+        "-y",
+        output,
+    ]
+    .map(String::from)
+    .to_vec();
+    run_ffmpeg("ffmpeg highlights concat", &args).await
+}
+
+// Encode the title cards and clips, returning the segment filenames in play order
+async fn build_segments(
+    clips: &[&ArchiveEntry],
+    dir: &str,
+    work_dir: &str,
+    quality: QualityProfile,
+) -> Result<Vec<String>, AppError> {
+    let mut segments = Vec::new();
+    for (index, clip) in clips.iter().enumerate() {
+        let input = format!("{}/{}", dir, clip.filename);
+        let probe = MediaProbe::probe(&input).await;
+        let clip_name = format!("clip_{}.mp4", index);
+        if let Err(e) = run_ffmpeg(
+            "ffmpeg highlights clip",
+            &clip_args(
+                &input,
+                probe.has_audio,
+                &format!("{}/{}", work_dir, clip_name),
+                quality,
+            ),
+        )
+        .await
+        {
+            tracing::warn!("Leaving {} out of the highlights: {}", clip.filename, e);
+            continue;
+        }
+
+        let card_name = format!("card_{}.mp4", index);
+        run_ffmpeg(
+            "ffmpeg highlights title",
+            &title_card_args(
+                &title(segments.len() / 2, clip),
+                &format!("{}/{}", work_dir, card_name),
+                quality,
+            ),
+        )
+        .await?;
+        segments.push(card_name);
+        segments.push(clip_name);
+    }
+
+    if segments.is_empty() {
+        return Err(AppError::IoError(std::io::Error::other(
+            "No clip could be added to the highlights",
+        )));
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(filename: &str, media_type: MediaType, pinned_at: u64) -> ArchiveEntry {
+        ArchiveEntry {
+            id: 0,
+            filename: filename.to_string(),
+            media_type,
+            caption: String::new(),
+            duration_secs: 10,
+            play_secs: 10,
+            pinned_at,
+        }
+    }
+
+    #[test]
+    fn test_select_clips() {
+        let entries = vec![
+            entry("b.mp4", MediaType::Video, 300),
+            entry("old.mp4", MediaType::Video, 50),
+            entry("a.mp4", MediaType::Video, 200),
+            entry("meme.png", MediaType::Image, 250),
+            entry("highlights_1.mp4", MediaType::Video, 400),
+        ];
+        let clips: Vec<&str> = select_clips(&entries, 100)
+            .into_iter()
+            .map(|clip| clip.filename.as_str())
+            .collect();
+        assert_eq!(clips, vec!["a.mp4", "b.mp4"]);
+    }
+
+    #[test]
+    fn test_next_run_delay() {
+        let every = Duration::from_secs(1000);
+        let clips = vec![entry("a.mp4", MediaType::Video, 900)];
+        assert_eq!(next_run_delay(&clips, every, 1000), Duration::ZERO);
+
+        let mut entries = clips.clone();
+        entries.push(entry("highlights_1.mp4", MediaType::Video, 600));
+        assert_eq!(next_run_delay(&entries, every, 1000), Duration::from_secs(600));
+        assert_eq!(next_run_delay(&entries, every, 5000), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_concat_list() {
+        assert_eq!(
+            concat_list(&["card_0.mp4".to_string(), "it's.mp4".to_string()]),
+            "file 'card_0.mp4'\nfile 'it'\\''s.mp4'\n"
+        );
+    }
+
+    #[test]
+    fn test_clip_args_add_silence() {
+        let args = clip_args("a.mp4", false, "out.mp4", QualityProfile::Fast);
+        assert_eq!(&args[..6], ["-i", "a.mp4", "-f", "lavfi", "-i", SILENCE]);
+        assert!(args.windows(2).any(|pair| pair == ["-map", "1:a:0"]));
+
+        let args = clip_args("a.mp4", true, "out.mp4", QualityProfile::Fast);
+        assert!(!args.contains(&SILENCE.to_string()));
+        assert!(args.windows(2).any(|pair| pair == ["-map", "0:a:0"]));
+    }
+}
@@ -0,0 +1,458 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use warp::{Filter, Rejection};
+
+/// Language of the messages shown to people, from `Accept-Language` or the configured default
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+}
+
+impl Locale {
+    const ALL: [Locale; 2] = [Locale::En, Locale::Fr];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Fr => "fr",
+        }
+    }
+
+    /// Best supported language of an `Accept-Language` header, by quality then order
+    pub fn negotiate(accept_language: Option<&str>, default: Locale) -> Locale {
+        let mut ranges: Vec<(&str, f32)> = accept_language
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        // Stable, equal qualities keep the client's order
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranges
+            .iter()
+            .find_map(|(tag, _)| {
+                if *tag == "*" {
+                    return Some(default);
+                }
+                let primary = tag.split('-').next().unwrap_or_default();
+                Self::ALL
+                    .into_iter()
+                    .find(|locale| locale.code().eq_ignore_ascii_case(primary))
+            })
+            .unwrap_or(default)
+    }
+
+    /// Catalog text of `message`
+    pub fn t(self, message: Msg) -> &'static str {
+        message.texts()[self as usize]
+    }
+
+    /// Catalog text of `message` with each `{}` replaced by the next argument
+    pub fn format(self, message: Msg, args: &[&dyn Display]) -> String {
+        let mut args = args.iter();
+        let mut parts = self.t(message).split("{}");
+        let mut text = parts.next().unwrap_or_default().to_string();
+        for part in parts {
+            if let Some(arg) = args.next() {
+                text.push_str(&arg.to_string());
+            }
+            text.push_str(part);
+        }
+        text
+    }
+}
+
+/// Locale of the request, negotiated from its `Accept-Language` header
+pub fn locale(default: Locale) -> impl Filter<Extract = (Locale,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("accept-language").map(
+        move |accept_language: Option<String>| {
+            Locale::negotiate(accept_language.as_deref(), default)
+        },
+    )
+}
+
+/// Messages of the catalog, `{}` marks where arguments go
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Msg {
+    // Display pages
+    Upload,
+    Loading,
+    NoNewMedia,
+    NoVideoSupport,
+    // Media uploads
+    NoMediaUploaded,
+    NoUrl,
+    VideoPageUrl,
+    PasteNeedsImage,
+    NoImagePasted,
+    InvalidMediaType,
+    UnknownPreset,
+    FileTooLarge,
+    ImageTooLarge,
+    VideoTooLarge,
+    VirusDetected,
+    VirusScanFailed,
+    Uploaded,
+    FullVideo,
+    CaptionEmbedded,
+    CaptionShown,
+    Queued,
+    HeldForQuietHours,
+    HeldForPassphrase,
+    ScreenBusy,
+    PreviewNeedsVideo,
+    PreviewUnavailable,
+    PreviewFailed,
+    Staged,
+    PublishNow,
+    Published,
+    NoStagedUpload,
+    CaptionRefused,
+    InReview,
+    NoSuchCamera,
+    SnapshotUnavailable,
+    SnapshotFailed,
+    SnapshotShown,
+    // Video links
+    NoVideoUrl,
+    YtDlpMissing,
+    VideoTooLong,
+    DownloadCancelled,
+    Downloaded,
+    TikTokLoginRequired,
+    YouTubeLoginRequired,
+    AgeRestricted,
+    PrivateVideo,
+    VideoTooLongToDownload,
+    TikTokDownloadFailed,
+    YouTubeDownloadFailed,
+    // Image generation
+    ImagineUnavailable,
+    NoPrompt,
+    PromptTooLong,
+    ImagineFailed,
+    // Sounds and music
+    InvalidSoundType,
+    NoSoundUploaded,
+    SoundTooLarge,
+    InvalidSoundFile,
+    InvalidTrimRange,
+    TrimUnavailable,
+    TrimFailed,
+    SoundUploaded,
+    SoundVisualized,
+    VisualizerFailed,
+    NoMusicUploaded,
+    InvalidMusicFilename,
+    InvalidMusicFile,
+    TrackAdded,
+    // Archive
+    NoArchivedMedia,
+    ShowingAgain,
+    // Passphrase holds
+    NoPassphrase,
+    NothingHeld,
+    Unlocked,
+    UnlockedSomeQueued,
+    // Rejections
+    FormExpired,
+    AdminsOnly,
+    Banned,
+    BannedFor,
+    UploadsBusy,
+}
+
+impl Msg {
+    // One text per locale, in `Locale` order
+    fn texts(self) -> [&'static str; 2] {
+        match self {
+            Msg::Upload => ["Upload", "Envoyer"],
+            Msg::Loading => ["Loading...", "Chargement..."],
+            Msg::NoNewMedia => ["No new media", "Rien de nouveau"],
+            Msg::NoVideoSupport => [
+                "Your browser does not support the video tag.",
+                "Votre navigateur ne peut pas lire cette vidéo.",
+            ],
+            Msg::NoMediaUploaded => ["No media uploaded!", "Aucun média envoyé !"],
+            Msg::NoUrl => ["No URL provided!", "Aucune URL donnée !"],
+            Msg::VideoPageUrl => [
+                "This is a video page, use the Video URL tab instead.",
+                "C'est une page vidéo, utilisez plutôt l'onglet URL vidéo.",
+            ],
+            Msg::PasteNeedsImage => [
+                "Paste needs an image Content-Type (png, jpeg, gif or webp).",
+                "Le collage demande un Content-Type image (png, jpeg, gif ou webp).",
+            ],
+            Msg::NoImagePasted => ["No image pasted!", "Aucune image collée !"],
+            Msg::UnknownPreset => ["No preset with this name!", "Aucun préréglage de ce nom !"],
+            Msg::InvalidMediaType => [
+                "Invalid file type! Only images and videos are allowed.",
+                "Type de fichier invalide ! Seules les images et vidéos sont acceptées.",
+            ],
+            Msg::FileTooLarge => [
+                "File too large! Maximum size is {}MB.",
+                "Fichier trop gros ! La taille maximale est de {} Mo.",
+            ],
+            Msg::ImageTooLarge => [
+                "Image too large! Maximum size is {}MB.",
+                "Image trop grosse ! La taille maximale est de {} Mo.",
+            ],
+            Msg::VideoTooLarge => [
+                "Video too large! Maximum size is {}MB.",
+                "Vidéo trop grosse ! La taille maximale est de {} Mo.",
+            ],
+            Msg::VirusDetected => [
+                "Upload rejected: virus detected ({}).",
+                "Envoi refusé : virus détecté ({}).",
+            ],
+            Msg::VirusScanFailed => [
+                "Upload rejected: the virus scan failed, try again later.",
+                "Envoi refusé : l'analyse antivirus a échoué, réessayez plus tard.",
+            ],
+            Msg::Uploaded => [
+                "Uploaded {} successfully! Display duration: {} seconds{}",
+                "{} envoyé ! Durée d'affichage : {} secondes{}",
+            ],
+            Msg::FullVideo => ["Full video", "Vidéo entière"],
+            Msg::PreviewNeedsVideo => [
+                "Caption previews need a video file!",
+                "L'aperçu de la légende demande une vidéo !",
+            ],
+            Msg::PreviewUnavailable => [
+                "Caption previews not available. ffmpeg is not installed.",
+                "Aperçu de la légende indisponible. ffmpeg n'est pas installé.",
+            ],
+            Msg::PreviewFailed => [
+                "Failed to render the caption preview.",
+                "Échec du rendu de l'aperçu de la légende.",
+            ],
+            Msg::Staged => [
+                "Staged {}, publish it within {} minutes.",
+                "{} en attente, publiez-le dans les {} minutes.",
+            ],
+            Msg::PublishNow => ["Publish now", "Publier maintenant"],
+            Msg::Published => ["Published {}!", "{} publié !"],
+            Msg::NoStagedUpload => [
+                "No such staged upload, it may have expired.",
+                "Envoi en attente introuvable, il a peut-être expiré.",
+            ],
+            Msg::CaptionRefused => [
+                "The caption has a word that isn't allowed here.",
+                "La légende contient un mot qui n'est pas autorisé ici.",
+            ],
+            Msg::InReview => [
+                "{} will be shown once an admin approves its caption.",
+                "{} sera affiché une fois sa légende approuvée par un admin.",
+            ],
+            Msg::NoSuchCamera => ["No camera with this name!", "Aucune caméra de ce nom !"],
+            Msg::SnapshotUnavailable => [
+                "Camera snapshots not available. ffmpeg is not installed.",
+                "Captures de caméra indisponibles. ffmpeg n'est pas installé.",
+            ],
+            Msg::SnapshotFailed => [
+                "Couldn't get a picture from the camera.",
+                "Impossible d'obtenir une image de la caméra.",
+            ],
+            Msg::SnapshotShown => [
+                "Showing camera {} on screen!",
+                "Caméra {} affichée à l'écran !",
+            ],
+            Msg::CaptionEmbedded => [
+                "<br/>Caption embedded in video",
+                "<br/>Légende incrustée dans la vidéo",
+            ],
+            Msg::CaptionShown => ["<br/>Caption: {}", "<br/>Légende : {}"],
+            Msg::Queued => [
+                "Uploaded {} - screen busy, queued at position {}.",
+                "{} envoyé - écran occupé, en attente en position {}.",
+            ],
+            Msg::HeldForQuietHours => [
+                "Uploaded {} - quiet hours, it will be shown when they end (position {}).",
+                "{} envoyé - heures calmes, il sera affiché à leur fin (position {}).",
+            ],
+            Msg::HeldForPassphrase => [
+                "Uploaded {} - held until it's unlocked with its passphrase.",
+                "{} envoyé - retenu jusqu'à son déverrouillage avec sa phrase secrète.",
+            ],
+            Msg::ScreenBusy => [
+                "Screen busy! Try again in {} seconds.",
+                "Écran occupé ! Réessayez dans {} secondes.",
+            ],
+            Msg::NoVideoUrl => ["No video URL provided!", "Aucune URL de vidéo donnée !"],
+            Msg::YtDlpMissing => [
+                "Video download not available. yt-dlp is not installed.",
+                "Téléchargement vidéo indisponible. yt-dlp n'est pas installé.",
+            ],
+            Msg::VideoTooLong => [
+                "Video too long! Maximum duration is 10 minutes.",
+                "Vidéo trop longue ! La durée maximale est de 10 minutes.",
+            ],
+            Msg::DownloadCancelled => ["Download cancelled.", "Téléchargement annulé."],
+            Msg::Downloaded => [
+                r#"Downloaded "{}" successfully!<br/>Duration: {} seconds{}"#,
+                r#""{}" téléchargé !<br/>Durée : {} secondes{}"#,
+            ],
+            Msg::TikTokLoginRequired => [
+                "This TikTok video requires login to view (age-restricted or sensitive content). Please try a different public TikTok video.",
+                "Cette vidéo TikTok demande une connexion (contenu limité par l'âge ou sensible). Essayez une autre vidéo TikTok publique.",
+            ],
+            Msg::YouTubeLoginRequired => [
+                "This YouTube video requires authentication. Please try a different public video.",
+                "Cette vidéo YouTube demande une connexion. Essayez une autre vidéo publique.",
+            ],
+            Msg::AgeRestricted => [
+                "This video is age-restricted and cannot be downloaded. Please try a different video.",
+                "Cette vidéo est limitée par l'âge et ne peut pas être téléchargée. Essayez une autre vidéo.",
+            ],
+            Msg::PrivateVideo => [
+                "This video is private or unavailable. Please check the URL and try again.",
+                "Cette vidéo est privée ou indisponible. Vérifiez l'URL et réessayez.",
+            ],
+            Msg::VideoTooLongToDownload => [
+                "Video is too long (maximum 10 minutes allowed).",
+                "La vidéo est trop longue (10 minutes maximum).",
+            ],
+            Msg::TikTokDownloadFailed => [
+                "Failed to download TikTok video. Make sure it's a public, non-restricted video and try again.",
+                "Échec du téléchargement de la vidéo TikTok. Vérifiez qu'elle est publique et sans restriction, puis réessayez.",
+            ],
+            Msg::YouTubeDownloadFailed => [
+                "Failed to download YouTube video. Please check the URL and try again.",
+                "Échec du téléchargement de la vidéo YouTube. Vérifiez l'URL et réessayez.",
+            ],
+            Msg::ImagineUnavailable => [
+                "Image generation is not set up on this server.",
+                "La génération d'images n'est pas configurée sur ce serveur.",
+            ],
+            Msg::NoPrompt => ["No prompt given!", "Aucune description donnée !"],
+            Msg::PromptTooLong => [
+                "Prompt is too long (maximum {} characters).",
+                "Description trop longue ({} caractères maximum).",
+            ],
+            Msg::ImagineFailed => [
+                "Image generation failed: {}",
+                "Échec de la génération d'image : {}",
+            ],
+            Msg::InvalidSoundType => [
+                "Invalid sound file type! Only MP3, WAV, and OGG files are allowed.",
+                "Type de son invalide ! Seuls les fichiers MP3, WAV et OGG sont acceptés.",
+            ],
+            Msg::NoSoundUploaded => ["No sound file uploaded!", "Aucun son envoyé !"],
+            Msg::SoundTooLarge => [
+                "Sound file too large! Maximum size is {}MB.",
+                "Son trop gros ! La taille maximale est de {} Mo.",
+            ],
+            Msg::InvalidSoundFile => ["Invalid sound file! {}.", "Son invalide ! {}."],
+            Msg::InvalidTrimRange => [
+                "Invalid trim range! End must be after start.",
+                "Découpe invalide ! La fin doit être après le début.",
+            ],
+            Msg::TrimUnavailable => [
+                "Audio trimming not available. ffmpeg is not installed.",
+                "Découpe audio indisponible. ffmpeg n'est pas installé.",
+            ],
+            Msg::TrimFailed => [
+                "Failed to trim sound. Check the start and end times.",
+                "Échec de la découpe du son. Vérifiez le début et la fin.",
+            ],
+            Msg::SoundUploaded => ["Sound {} uploaded successfully!", "Son {} envoyé !"],
+            Msg::SoundVisualized => [
+                "Sound {} uploaded, its visualizer is on screen!",
+                "Son {} envoyé, sa visualisation est à l'écran !",
+            ],
+            Msg::VisualizerFailed => [
+                "Its visualizer video couldn't be made.",
+                "Sa vidéo de visualisation n'a pas pu être créée.",
+            ],
+            Msg::NoMusicUploaded => ["No music file uploaded!", "Aucune musique envoyée !"],
+            Msg::InvalidMusicFilename => [
+                "Invalid music filename!",
+                "Nom de fichier musical invalide !",
+            ],
+            Msg::InvalidMusicFile => ["Invalid music file! {}.", "Musique invalide ! {}."],
+            Msg::TrackAdded => [
+                "Track {} added to the music playlist!",
+                "Morceau {} ajouté à la playlist !",
+            ],
+            Msg::NoArchivedMedia => [
+                "No archived media with this id.",
+                "Aucun média archivé avec cet id.",
+            ],
+            Msg::ShowingAgain => ["Showing {} again.", "{} est réaffiché."],
+            Msg::NoPassphrase => ["No passphrase given!", "Aucune phrase secrète donnée !"],
+            Msg::NothingHeld => [
+                "Nothing is held with this passphrase.",
+                "Rien n'est retenu avec cette phrase secrète.",
+            ],
+            Msg::Unlocked => ["Unlocked {} uploads.", "{} envois déverrouillés."],
+            Msg::UnlockedSomeQueued => [
+                "Unlocked {} uploads, {} waiting in the queue.",
+                "{} envois déverrouillés, {} en file d'attente.",
+            ],
+            Msg::FormExpired => [
+                "This form has expired, reload the page and try again.",
+                "Ce formulaire a expiré, rechargez la page et réessayez.",
+            ],
+            Msg::AdminsOnly => ["Admins only.", "Réservé aux admins."],
+            Msg::Banned => [
+                "You're banned from uploading and chatting.",
+                "Vous êtes banni des envois et du chat.",
+            ],
+            Msg::BannedFor => [
+                "You're banned from uploading and chatting for {} more minutes.",
+                "Vous êtes banni des envois et du chat pendant encore {} minutes.",
+            ],
+            Msg::UploadsBusy => [
+                "Too many uploads in progress, try again in {} seconds.",
+                "Trop d'envois en cours, réessayez dans {} secondes.",
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let fr = Some("fr-FR,fr;q=0.9,en;q=0.8");
+        assert_eq!(Locale::negotiate(fr, Locale::En), Locale::Fr);
+        assert_eq!(
+            Locale::negotiate(Some("de, en;q=0.5"), Locale::Fr),
+            Locale::En
+        );
+        assert_eq!(
+            Locale::negotiate(Some("en;q=0.2, FR"), Locale::En),
+            Locale::Fr
+        );
+        assert_eq!(
+            Locale::negotiate(Some("de, *;q=0.1"), Locale::Fr),
+            Locale::Fr
+        );
+        assert_eq!(Locale::negotiate(Some("fr;q=0"), Locale::En), Locale::En);
+        assert_eq!(Locale::negotiate(None, Locale::Fr), Locale::Fr);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            Locale::Fr.format(Msg::Queued, &[&"cat.png", &2]),
+            "cat.png envoyé - écran occupé, en attente en position 2."
+        );
+        assert_eq!(
+            Locale::En.format(Msg::ScreenBusy, &[]),
+            "Screen busy! Try again in  seconds."
+        );
+        assert_eq!(Locale::En.t(Msg::AdminsOnly), "Admins only.");
+    }
+}
@@ -0,0 +1,278 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+// Generating takes a while on a small GPU, and longer when the queue isn't empty
+const IMAGINE_TIMEOUT: Duration = Duration::from_secs(120);
+const COMFYUI_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Longest prompt accepted, the backends truncate anyway
+pub const MAX_PROMPT_CHARS: usize = 500;
+// Stands for the prompt in a ComfyUI workflow
+const PROMPT_PLACEHOLDER: &str = "%prompt%";
+
+/// Stable Diffusion server prompts are sent to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImagineBackend {
+    /// AUTOMATIC1111 web UI started with `--api`
+    #[default]
+    A1111,
+    ComfyUi,
+}
+
+/// Optional text-to-image generation behind POST /imagine
+#[derive(Clone, Debug, Deserialize)]
+pub struct Imagine {
+    #[serde(default)]
+    pub backend: ImagineBackend,
+    /// Base URL, e.g. `http://localhost:7860` for A1111 or `http://localhost:8188` for ComfyUI
+    pub url: String,
+    /// ComfyUI workflow saved in API format, its "%prompt%" strings are replaced by the prompt
+    pub workflow: Option<String>,
+    #[serde(default)]
+    pub negative_prompt: String,
+    #[serde(default = "default_size")]
+    pub width: u32,
+    #[serde(default = "default_size")]
+    pub height: u32,
+    #[serde(default = "default_steps")]
+    pub steps: u32,
+}
+
+fn default_size() -> u32 {
+    512
+}
+
+fn default_steps() -> u32 {
+    20
+}
+
+#[derive(Error, Debug)]
+pub enum ImagineError {
+    #[error("Image generation request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Image generation backend returned HTTP {0}")]
+    Status(u16),
+    #[error("Unexpected image generation response")]
+    InvalidResponse,
+    #[error("Invalid ComfyUI workflow: {0}")]
+    Workflow(String),
+    #[error("Image generation timed out")]
+    Timeout,
+}
+
+// Image saved by a ComfyUI workflow, fetched back with GET /view
+#[derive(Debug, PartialEq)]
+struct ComfyImage {
+    filename: String,
+    subfolder: String,
+    kind: String,
+}
+
+impl Imagine {
+    /// Generate an image for the prompt, returning the encoded file (PNG for both backends)
+    pub async fn generate(&self, prompt: &str) -> Result<Vec<u8>, ImagineError> {
+        let client = reqwest::Client::builder()
+            .timeout(IMAGINE_TIMEOUT)
+            .build()?;
+        let image = match self.backend {
+            ImagineBackend::A1111 => self.generate_a1111(&client, prompt).await?,
+            ImagineBackend::ComfyUi => self.generate_comfyui(&client, prompt).await?,
+        };
+        tracing::info!("Generated a {} byte image for: {}", image.len(), prompt);
+        Ok(image)
+    }
+
+    fn base(&self) -> &str {
+        self.url.trim_end_matches('/')
+    }
+
+    async fn generate_a1111(
+        &self,
+        client: &reqwest::Client,
+        prompt: &str,
+    ) -> Result<Vec<u8>, ImagineError> {
+        let response = client
+            .post(format!("{}/sdapi/v1/txt2img", self.base()))
+            .header("content-type", "application/json")
+            .body(self.a1111_request(prompt).to_string())
+            .send()
+            .await?;
+        let body = json_body(response).await?;
+        parse_a1111_response(&body)
+    }
+
+    fn a1111_request(&self, prompt: &str) -> Value {
+        json!({
+            "prompt": prompt,
+            "negative_prompt": self.negative_prompt,
+            "width": self.width,
+            "height": self.height,
+            "steps": self.steps,
+        })
+    }
+
+    // Queue the workflow, wait for it to show up in the history, then download its image
+    async fn generate_comfyui(
+        &self,
+        client: &reqwest::Client,
+        prompt: &str,
+    ) -> Result<Vec<u8>, ImagineError> {
+        let Some(path) = &self.workflow else {
+            return Err(ImagineError::Workflow("no workflow configured".to_string()));
+        };
+        let workflow = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| ImagineError::Workflow(format!("{}: {}", path, e)))?;
+        let workflow: Value = serde_json::from_str(&workflow)
+            .map_err(|e| ImagineError::Workflow(format!("{}: {}", path, e)))?;
+
+        let response = client
+            .post(format!("{}/prompt", self.base()))
+            .header("content-type", "application/json")
+            .body(json!({ "prompt": fill_workflow(workflow, prompt) }).to_string())
+            .send()
+            .await?;
+        let queued = json_body(response).await?;
+        let prompt_id = queued["prompt_id"]
+            .as_str()
+            .ok_or(ImagineError::InvalidResponse)?;
+
+        let started = Instant::now();
+        let image = loop {
+            if started.elapsed() > IMAGINE_TIMEOUT {
+                return Err(ImagineError::Timeout);
+            }
+            tokio::time::sleep(COMFYUI_POLL_INTERVAL).await;
+            let response = client
+                .get(format!("{}/history/{}", self.base(), prompt_id))
+                .send()
+                .await?;
+            if let Some(image) = parse_comfyui_history(&json_body(response).await?, prompt_id) {
+                break image;
+            }
+        };
+
+        let response = client
+            .get(format!("{}/view", self.base()))
+            .query(&[
+                ("filename", &image.filename),
+                ("subfolder", &image.subfolder),
+                ("type", &image.kind),
+            ])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(ImagineError::Status(response.status().as_u16()));
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+async fn json_body(response: reqwest::Response) -> Result<Value, ImagineError> {
+    if !response.status().is_success() {
+        return Err(ImagineError::Status(response.status().as_u16()));
+    }
+    serde_json::from_slice(&response.bytes().await?).map_err(|_| ImagineError::InvalidResponse)
+}
+
+// A1111 answers with the images base64 encoded
+fn parse_a1111_response(body: &Value) -> Result<Vec<u8>, ImagineError> {
+    let image = body["images"][0]
+        .as_str()
+        .ok_or(ImagineError::InvalidResponse)?;
+    STANDARD
+        .decode(image)
+        .map_err(|_| ImagineError::InvalidResponse)
+}
+
+// Replace the placeholder strings anywhere in the workflow with the prompt
+fn fill_workflow(workflow: Value, prompt: &str) -> Value {
+    match workflow {
+        Value::String(text) if text.contains(PROMPT_PLACEHOLDER) => {
+            Value::String(text.replace(PROMPT_PLACEHOLDER, prompt))
+        }
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|value| fill_workflow(value, prompt))
+                .collect(),
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key, fill_workflow(value, prompt)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+// First image of a finished prompt, none while it is still queued or running
+fn parse_comfyui_history(body: &Value, prompt_id: &str) -> Option<ComfyImage> {
+    let outputs = body[prompt_id]["outputs"].as_object()?;
+    let image = outputs
+        .values()
+        .find_map(|output| output["images"].as_array()?.first())?;
+    Some(ComfyImage {
+        filename: image["filename"].as_str()?.to_string(),
+        subfolder: image["subfolder"].as_str().unwrap_or_default().to_string(),
+        kind: image["type"].as_str().unwrap_or("output").to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_a1111_round_trip() {
+        let imagine = Imagine {
+            backend: ImagineBackend::A1111,
+            url: "http://localhost:7860/".to_string(),
+            workflow: None,
+            negative_prompt: "blurry".to_string(),
+            width: default_size(),
+            height: default_size(),
+            steps: default_steps(),
+        };
+        assert_eq!(imagine.base(), "http://localhost:7860");
+        let body = imagine.a1111_request("a cat dabbing");
+        assert_eq!(body["prompt"], "a cat dabbing");
+        assert_eq!(body["negative_prompt"], "blurry");
+        assert_eq!(body["width"], 512);
+
+        let image = parse_a1111_response(&json!({ "images": ["iVBORw=="] })).unwrap();
+        assert_eq!(image, [0x89, b'P', b'N', b'G']);
+        assert!(parse_a1111_response(&json!({ "error": "OOM" })).is_err());
+    }
+
+    #[test]
+    fn test_comfyui_workflow() {
+        let workflow = json!({
+            "6": { "class_type": "CLIPTextEncode", "inputs": { "text": "%prompt%, meme" } },
+            "3": { "inputs": { "seed": 42 } },
+        });
+        let filled = fill_workflow(workflow, "a cat dabbing");
+        assert_eq!(filled["6"]["inputs"]["text"], "a cat dabbing, meme");
+        assert_eq!(filled["3"]["inputs"]["seed"], 42);
+
+        assert_eq!(parse_comfyui_history(&json!({}), "abc"), None);
+        let history = json!({
+            "abc": { "outputs": { "9": { "images": [
+                { "filename": "ComfyUI_00001_.png", "subfolder": "", "type": "output" }
+            ] } } }
+        });
+        assert_eq!(
+            parse_comfyui_history(&history, "abc"),
+            Some(ComfyImage {
+                filename: "ComfyUI_00001_.png".to_string(),
+                subfolder: String::new(),
+                kind: "output".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use crate::data_dirs::DataDirs;
+use serde::Deserialize;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::{Child, Command};
+
+/// URL of the live playlist and its segments, in [`DataDirs::live`]
+pub const LIVE_URL: &str = "/uploads/live";
+/// Playlist displays are sent in `live` events
+pub const LIVE_PLAYLIST: &str = "index.m3u8";
+// How often the relay is checked for its first segment and for exiting
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Screen-share ingest: an ffmpeg relay listening for an RTMP stream (OBS, a phone app) and
+/// republishing it as HLS for the displays
+/// WHIP senders go through a WHIP to RTMP gateway, e.g. MediaMTX, pushing to `listen`
+#[derive(Clone, Debug, Deserialize)]
+pub struct IngestConfig {
+    /// RTMP address the relay listens on, senders push to it followed by the stream key
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// Secret last path segment of the ingest URL, only that stream is accepted
+    pub stream_key: String,
+    /// One second segments in fMP4 instead of four second MPEG-TS ones, closer to live at
+    /// the cost of more requests
+    #[serde(default)]
+    pub low_latency: bool,
+}
+
+fn default_listen() -> String {
+    "rtmp://0.0.0.0:1935/live".to_string()
+}
+
+impl IngestConfig {
+    /// URL senders push to
+    pub fn ingest_url(&self) -> String {
+        format!("{}/{}", self.listen.trim_end_matches('/'), self.stream_key)
+    }
+
+    /// Arguments of the ffmpeg relay, copying the stream into a rolling HLS playlist in `dir`
+    pub fn relay_args(&self, dir: &str) -> Vec<String> {
+        let (segment_secs, segment_type, extension) = match self.low_latency {
+            true => ("1", "fmp4", "m4s"),
+            false => ("4", "mpegts", "ts"),
+        };
+        let args = [
+            "-hide_banner",
+            "-listen",
+            "1",
+            "-i",
+            &self.ingest_url(),
+            "-c",
+            "copy",
+            "-f",
+            "hls",
+            "-hls_time",
+            segment_secs,
+            "-hls_list_size",
+            "6",
+            "-hls_segment_type",
+            segment_type,
+            "-hls_flags",
+            "delete_segments+independent_segments",
+            "-hls_segment_filename",
+            &format!("{}/live_%05d.{}", dir, extension),
+            "-y",
+            &format!("{}/{}", dir, LIVE_PLAYLIST),
+        ];
+        args.into_iter().map(str::to_string).collect()
+    }
+}
+
+/// URL of the live playlist, as served under /uploads
+pub fn playlist_url() -> String {
+    format!("{}/{}", LIVE_URL, LIVE_PLAYLIST)
+}
+
+/// Start the relay in an empty [`DataDirs::live`], so a stale playlist isn't mistaken for a new
+/// stream
+pub async fn spawn_relay(config: &IngestConfig, dirs: &DataDirs) -> std::io::Result<Child> {
+    let live = dirs.live();
+    if let Err(e) = tokio::fs::remove_dir_all(&live).await
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!("Failed to clear {}: {}", live, e);
+    }
+    tokio::fs::create_dir_all(&live).await?;
+    Command::new("ffmpeg")
+        .args(config.relay_args(&live))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+}
+
+/// Wait until a sender connected and the first segment is out, false if the relay exited first
+pub async fn wait_for_stream(relay: &mut Child, dirs: &DataDirs) -> bool {
+    let playlist = Path::new(&dirs.live()).join(LIVE_PLAYLIST);
+    loop {
+        if tokio::fs::try_exists(&playlist).await.unwrap_or(false) {
+            return true;
+        }
+        match relay.try_wait() {
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Ok(Some(status)) => {
+                tracing::warn!("Ingest relay exited before a stream started: {}", status);
+                return false;
+            }
+            Err(e) => {
+                tracing::error!("Failed to check the ingest relay: {}", e);
+                return false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_args() {
+        let config = IngestConfig {
+            listen: "rtmp://0.0.0.0:1935/live/".to_string(),
+            stream_key: "k3y".to_string(),
+            low_latency: false,
+        };
+        assert_eq!(config.ingest_url(), "rtmp://0.0.0.0:1935/live/k3y");
+        let args = config.relay_args("uploads/live").join(" ");
+        assert!(args.starts_with("-hide_banner -listen 1 -i rtmp://0.0.0.0:1935/live/k3y -c copy"));
+        assert!(args.contains("-hls_time 4"));
+        assert!(args.ends_with("uploads/live/live_%05d.ts -y uploads/live/index.m3u8"));
+
+        let low_latency = IngestConfig {
+            low_latency: true,
+            ..config
+        };
+        let args = low_latency.relay_args("uploads/live").join(" ");
+        assert!(args.contains("-hls_time 1 -hls_list_size 6 -hls_segment_type fmp4"));
+        assert_eq!(playlist_url(), "/uploads/live/index.m3u8");
+    }
+}
@@ -0,0 +1,181 @@
+use crate::websocket::{self, WsClients};
+use crate::types::JobStatus;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{RwLock, mpsc};
+use tokio_util::sync::CancellationToken;
+
+pub type SharedJobs = Arc<RwLock<JobRegistry>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStage {
+    Downloading,
+    Processing,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    /// A video page fetched with yt-dlp
+    Download,
+    /// An animated preview made in the background, `url` is the video's
+    Preview,
+}
+
+/// A long-running URL download or background job and its progress
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: u64,
+    pub kind: JobKind,
+    pub url: String,
+    pub stage: JobStage,
+    pub percent: f32,
+    /// Kills the job's running yt-dlp/ffmpeg child when cancelled
+    cancel: CancellationToken,
+}
+
+impl Job {
+    pub fn status(&self) -> JobStatus {
+        JobStatus {
+            id: self.id,
+            kind: self.kind,
+            url: self.url.clone(),
+            stage: self.stage,
+            percent: self.percent,
+        }
+    }
+}
+
+enum JobUpdate {
+    Percent(f32),
+    Stage(JobStage),
+}
+
+/// Running jobs, each removed once its reporter is dropped
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: HashMap<u64, Job>,
+    next_id: u64,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn jobs(&self) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self.jobs.values().cloned().collect();
+        jobs.sort_by_key(|job| job.id);
+        jobs
+    }
+
+    /// Cancel a running job, returning it if it existed and wasn't cancelled already
+    pub fn cancel(&mut self, id: u64) -> Option<Job> {
+        let job = self.jobs.get_mut(&id)?;
+        if job.cancel.is_cancelled() {
+            return None;
+        }
+        job.cancel.cancel();
+        job.stage = JobStage::Cancelled;
+        Some(job.clone())
+    }
+
+    fn insert(&mut self, kind: JobKind, url: &str) -> Job {
+        self.next_id += 1;
+        let job = Job {
+            id: self.next_id,
+            kind,
+            url: url.to_string(),
+            stage: match kind {
+                JobKind::Download => JobStage::Downloading,
+                JobKind::Preview => JobStage::Processing,
+            },
+            percent: 0.0,
+            cancel: CancellationToken::new(),
+        };
+        self.jobs.insert(job.id, job.clone());
+        job
+    }
+}
+
+pub fn create_jobs_state() -> SharedJobs {
+    Arc::new(RwLock::new(JobRegistry::new()))
+}
+
+/// Handle the worker uses to report progress without waiting on locks
+/// Updates are applied and broadcast in order by a forwarding task
+#[derive(Clone)]
+pub struct ProgressReporter {
+    id: u64,
+    cancel: CancellationToken,
+    tx: mpsc::UnboundedSender<JobUpdate>,
+}
+
+impl ProgressReporter {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn cancel_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    pub fn percent(&self, percent: f32) {
+        let _ = self.tx.send(JobUpdate::Percent(percent));
+    }
+
+    pub fn stage(&self, stage: JobStage) {
+        let _ = self.tx.send(JobUpdate::Stage(stage));
+    }
+}
+
+/// Register a job and start forwarding its progress to the registry and WebSocket clients
+pub async fn start_job(
+    jobs: SharedJobs,
+    ws_clients: WsClients,
+    kind: JobKind,
+    url: &str,
+) -> ProgressReporter {
+    let job = jobs.write().await.insert(kind, url);
+    tracing::info!("Started job {} for {}", job.id, url);
+    websocket::broadcast_job_progress(&ws_clients, &job).await;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let id = job.id;
+    let cancel = job.cancel.clone();
+    tokio::spawn(async move {
+        while let Some(update) = rx.recv().await {
+            let job = {
+                let mut jobs_guard = jobs.write().await;
+                let Some(job) = jobs_guard.jobs.get_mut(&id) else {
+                    break;
+                };
+                // Late updates from the dying worker must not hide the cancellation
+                if job.stage == JobStage::Cancelled {
+                    continue;
+                }
+                match update {
+                    JobUpdate::Percent(percent) => job.percent = percent,
+                    JobUpdate::Stage(stage) => job.stage = stage,
+                }
+                job.clone()
+            };
+            websocket::broadcast_job_progress(&ws_clients, &job).await;
+        }
+
+        // Every reporter is gone, the job is over
+        jobs.write().await.jobs.remove(&id);
+        tracing::info!("Job {} finished", id);
+    });
+
+    ProgressReporter { id, cancel, tx }
+}
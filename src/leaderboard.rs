@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Points per action, posting counts the most, reactions say it was a good post
+const UPLOAD_POINTS: u64 = 10;
+const REACTION_POINTS: u64 = 3;
+const SOUND_POINTS: u64 = 1;
+
+/// What one person did since the last reset
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    pub uploads: u64,
+    pub reactions_received: u64,
+    pub sounds_played: u64,
+}
+
+impl Score {
+    pub fn points(&self) -> u64 {
+        self.uploads * UPLOAD_POINTS
+            + self.reactions_received * REACTION_POINTS
+            + self.sounds_played * SOUND_POINTS
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Ranking {
+    pub rank: usize,
+    pub person: String,
+    pub points: u64,
+    #[serde(flatten)]
+    pub score: Score,
+}
+
+/// Points per uploader, saved to disk so the competition survives restarts
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Leaderboard {
+    scores: HashMap<String, Score>,
+}
+
+impl Leaderboard {
+    fn score(&mut self, person: &str) -> &mut Score {
+        self.scores.entry(person.to_string()).or_default()
+    }
+
+    pub fn record_upload(&mut self, person: &str) {
+        self.score(person).uploads += 1;
+    }
+
+    pub fn record_reaction(&mut self, person: &str) {
+        self.score(person).reactions_received += 1;
+    }
+
+    pub fn record_sound_play(&mut self, person: &str) {
+        self.score(person).sounds_played += 1;
+    }
+
+    /// Highest points first, people with the same points share a rank
+    pub fn rankings(&self) -> Vec<Ranking> {
+        let mut scores: Vec<_> = self.scores.iter().collect();
+        scores.sort_by(|a, b| b.1.points().cmp(&a.1.points()).then(a.0.cmp(b.0)));
+        let mut rankings: Vec<Ranking> = Vec::with_capacity(scores.len());
+        for (index, (person, score)) in scores.into_iter().enumerate() {
+            let points = score.points();
+            let rank = match rankings.last() {
+                Some(previous) if previous.points == points => previous.rank,
+                _ => index + 1,
+            };
+            rankings.push(Ranking {
+                rank,
+                person: person.clone(),
+                points,
+                score: score.clone(),
+            });
+        }
+        rankings
+    }
+
+    pub fn reset(&mut self) {
+        self.scores.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rankings() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.record_upload("alice");
+        leaderboard.record_reaction("alice");
+        leaderboard.record_upload("bob");
+        leaderboard.record_sound_play("bob");
+        leaderboard.record_sound_play("bob");
+        leaderboard.record_sound_play("bob");
+        leaderboard.record_sound_play("carol");
+
+        let rankings = leaderboard.rankings();
+        let ranks: Vec<_> = rankings
+            .iter()
+            .map(|ranking| (ranking.rank, ranking.person.as_str(), ranking.points))
+            .collect();
+        assert_eq!(ranks, [(1, "alice", 13), (1, "bob", 13), (3, "carol", 1)]);
+        assert_eq!(rankings[1].score.sounds_played, 3);
+
+        leaderboard.reset();
+        assert!(leaderboard.rankings().is_empty());
+    }
+}
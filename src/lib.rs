@@ -0,0 +1,73 @@
+//! Homies gaming backend: uploads media from the group and puts it on a shared screen
+//!
+//! Embed it with [`App::builder`], serve [`App::routes`] with warp, or test against the filter
+//! with `warp::test` without binding a port
+
+pub mod app;
+pub mod audio_processing;
+pub mod audit;
+pub mod banner;
+pub mod bans;
+pub mod bench;
+pub mod camera;
+pub mod capabilities;
+pub mod chat;
+pub mod cleanup;
+pub mod clock;
+pub mod config;
+pub mod data_dirs;
+pub mod diagnostics;
+pub mod displays;
+pub mod dnd;
+pub mod drawing;
+pub mod errors;
+pub mod events;
+pub mod filter_graph;
+pub mod game_status;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod grpc;
+pub mod handlers;
+pub mod highlights;
+pub mod i18n;
+pub mod imagine;
+pub mod ingest;
+pub mod jobs;
+pub mod leaderboard;
+pub mod listen;
+pub mod mdns;
+pub mod media_probe;
+pub mod mqtt;
+pub mod music;
+pub mod notifier;
+pub mod now_playing;
+pub mod polls;
+pub mod presets;
+pub mod remote_media;
+pub mod retry;
+pub mod playback;
+pub mod session;
+pub mod share;
+pub mod staging;
+pub mod state;
+pub mod stats;
+pub mod state_actor;
+pub mod tasks;
+pub mod templates;
+pub mod theme;
+pub mod timers;
+pub mod transcription;
+pub mod types;
+pub mod trash;
+pub mod translation;
+pub mod twitch;
+pub mod upload_budget;
+pub mod utils;
+pub mod validation;
+pub mod video_processing;
+pub mod virus_scan;
+pub mod webhooks;
+pub mod websocket;
+pub mod word_filter;
+
+pub use app::{App, AppBuilder};
@@ -0,0 +1,213 @@
+use crate::errors::AppError;
+use futures_util::Stream;
+use listenfd::ListenFd;
+use serde::{Deserialize, Deserializer};
+use socket2::{Domain, Socket, Type};
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tokio::net::{TcpListener, UnixListener};
+
+const UNIX_PREFIX: &str = "unix:";
+const LISTEN_BACKLOG: i32 = 1024;
+
+/// Address the server accepts connections on
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    /// Unix domain socket, for running behind a reverse proxy
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddr {
+    type Err = AppError;
+
+    /// `host:port`, `[v6 host]:port` or `unix:/path/to.sock`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if let Some(path) = value.strip_prefix(UNIX_PREFIX) {
+            if path.is_empty() {
+                return Err(AppError::ConfigError("Empty Unix socket path".to_string()));
+            }
+            return Ok(ListenAddr::Unix(PathBuf::from(path)));
+        }
+        value.parse().map(ListenAddr::Tcp).map_err(|_| {
+            AppError::ConfigError(format!(
+                "Invalid listen address {:?}, expected host:port, [::]:port or unix:/path",
+                value
+            ))
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ListenAddr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "http://{}", addr),
+            ListenAddr::Unix(path) => write!(f, "{}{}", UNIX_PREFIX, path.display()),
+        }
+    }
+}
+
+pub fn default_listen() -> Vec<ListenAddr> {
+    vec![ListenAddr::Tcp(SocketAddr::from(([0, 0, 0, 0], 3030)))]
+}
+
+/// Listen addresses given with `--listen` (repeatable), overriding the configuration
+pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Vec<ListenAddr>, AppError> {
+    let mut args = args.into_iter();
+    let mut addrs = Vec::new();
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--listen=") {
+            Some(value) => value.to_string(),
+            None if arg == "--listen" => args.next().ok_or_else(|| {
+                AppError::ConfigError("--listen needs an address".to_string())
+            })?,
+            None => continue,
+        };
+        addrs.push(value.parse()?);
+    }
+    Ok(addrs)
+}
+
+/// Bound listener for one listen address
+pub enum Incoming {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl Incoming {
+    pub fn bind(addr: &ListenAddr) -> Result<Self, AppError> {
+        match addr {
+            ListenAddr::Tcp(addr) => bind_tcp(*addr).map(Incoming::Tcp),
+            ListenAddr::Unix(path) => {
+                // A socket file left by a previous run would make the bind fail
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                Ok(Incoming::Unix(UnixListener::bind(path)?))
+            }
+        }
+    }
+}
+
+/// Listeners handed over by systemd socket activation (`LISTEN_FDS`), empty when started normally
+/// systemd keeps the sockets open across restarts, so clients queue up instead of being refused
+pub fn inherited() -> Result<Vec<(ListenAddr, Incoming)>, AppError> {
+    let mut fds = ListenFd::from_env();
+    let mut listeners = Vec::new();
+    for index in 0..fds.len() {
+        // A socket of the wrong kind is left in place, so the Unix attempt still sees it
+        if let Ok(Some(listener)) = fds.take_tcp_listener(index) {
+            listener.set_nonblocking(true)?;
+            let addr = ListenAddr::Tcp(listener.local_addr()?);
+            listeners.push((addr, Incoming::Tcp(TcpListener::from_std(listener)?)));
+            continue;
+        }
+        match fds.take_unix_listener(index)? {
+            Some(listener) => {
+                listener.set_nonblocking(true)?;
+                let path = listener
+                    .local_addr()?
+                    .as_pathname()
+                    .map(PathBuf::from)
+                    .unwrap_or_default();
+                let incoming = Incoming::Unix(UnixListener::from_std(listener)?);
+                listeners.push((ListenAddr::Unix(path), incoming));
+            }
+            None => tracing::warn!("Ignoring inherited file descriptor {}", index),
+        }
+    }
+    Ok(listeners)
+}
+
+/// Accepted TCP connections, for `warp::serve(..).run_incoming`
+pub fn tcp_connections(
+    listener: TcpListener,
+) -> impl Stream<Item = std::io::Result<tokio::net::TcpStream>> {
+    futures_util::stream::unfold(listener, |listener| async move {
+        let conn = listener.accept().await.map(|(stream, _)| stream);
+        Some((conn, listener))
+    })
+}
+
+/// Accepted Unix socket connections, for `warp::serve(..).run_incoming`
+pub fn unix_connections(
+    listener: UnixListener,
+) -> impl Stream<Item = std::io::Result<tokio::net::UnixStream>> {
+    futures_util::stream::unfold(listener, |listener| async move {
+        let conn = listener.accept().await.map(|(stream, _)| stream);
+        Some((conn, listener))
+    })
+}
+
+// IPv6 sockets are made v6 only, so `0.0.0.0:port` and `[::]:port` can both be bound
+fn bind_tcp(addr: SocketAddr) -> Result<TcpListener, AppError> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    Ok(TcpListener::from_std(socket.into())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listen_addr() {
+        assert_eq!(
+            "0.0.0.0:3030".parse::<ListenAddr>().unwrap(),
+            ListenAddr::Tcp(SocketAddr::from(([0, 0, 0, 0], 3030)))
+        );
+        assert!(matches!(
+            "[::]:3030".parse::<ListenAddr>().unwrap(),
+            ListenAddr::Tcp(addr) if addr.is_ipv6() && addr.port() == 3030
+        ));
+        assert_eq!(
+            "unix:/run/homies.sock".parse::<ListenAddr>().unwrap(),
+            ListenAddr::Unix(PathBuf::from("/run/homies.sock"))
+        );
+        assert!("unix:".parse::<ListenAddr>().is_err());
+        assert!("localhost".parse::<ListenAddr>().is_err());
+    }
+
+    #[test]
+    fn test_listen_from_args() {
+        let args = ["homies", "--listen", "127.0.0.1:8080", "--listen=unix:/tmp/h.sock", "-v"]
+            .map(String::from);
+        assert_eq!(
+            from_args(args).unwrap(),
+            vec![
+                ListenAddr::Tcp(SocketAddr::from(([127, 0, 0, 1], 8080))),
+                ListenAddr::Unix(PathBuf::from("/tmp/h.sock")),
+            ]
+        );
+        assert!(from_args(["homies", "--listen"].map(String::from)).is_err());
+        assert!(from_args(["homies"].map(String::from)).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bind_v4_and_v6_on_one_port() {
+        let Ok(Incoming::Tcp(v4)) = Incoming::bind(&"127.0.0.1:0".parse().unwrap()) else {
+            panic!("Failed to bind IPv4");
+        };
+        let port = v4.local_addr().unwrap().port();
+        // Skipped where the sandbox has no IPv6 loopback
+        if let Ok(v6) = Incoming::bind(&format!("[::1]:{}", port).parse().unwrap()) {
+            assert!(matches!(v6, Incoming::Tcp(_)));
+        }
+    }
+}
@@ -1,158 +1,127 @@
-mod errors;
-mod handlers;
-mod state;
-mod templates;
-mod utils;
-mod video_processing;
-mod websocket; // Add this
-
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::RwLock;
-use warp::Filter;
+#[cfg(feature = "grpc")]
+use homies_gaming_backend::grpc;
+use homies_gaming_backend::{App, bench, config, listen, mdns};
 
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
-    tracing::info!("Starting Homies Gaming Backend server");
-
-    // Create shared state
-    let media_state = Arc::new(RwLock::new(state::MediaViewState::new()));
-    tracing::info!("Media state initialized");
-
-    // Create WebSocket state
-    let ws_clients = websocket::create_ws_state();
-    tracing::info!("WebSocket state initialized");
-
-    // Start background cleanup task
-    start_cleanup_task(media_state.clone());
-    tracing::info!("Background cleanup task started");
-
-    // Clone for different routes
-    let media_state_upload = media_state.clone();
-    let media_state_media = media_state.clone();
-    let ws_clients_upload = ws_clients.clone();
-    let ws_clients_route = ws_clients.clone();
-
-    // Index route
-    let index_route = warp::get()
-        .and(warp::path::end())
-        .and_then(handlers::media::index_page);
-
-    // Upload routes
-    let upload_form_route = warp::get()
-        .and(warp::path("upload"))
-        .and_then(handlers::upload::upload_form);
-
-    let upload_route = warp::post()
-        .and(warp::path("upload"))
-        .and(warp::multipart::form().max_length(100 * 1024 * 1024)) // 100MB limit
-        .and(warp::addr::remote())
-        .and(with_state(media_state_upload.clone()))
-        .and(with_ws_state(ws_clients_upload.clone())) // Add WebSocket state
-        .and_then(handlers::upload::upload_image);
-
-    let upload_video_route = warp::post()
-        .and(warp::path("upload-video"))
-        .and(warp::body::form())
-        .and(with_state(media_state_upload.clone()))
-        .and(with_ws_state(ws_clients_upload.clone()))
-        .and_then(handlers::upload::upload_video_url);
 
-    // Backward compatibility for YouTube uploads
-    let upload_youtube_route = warp::post()
-        .and(warp::path("upload-youtube"))
-        .and(warp::body::form())
-        .and(with_state(media_state_upload.clone()))
-        .and(with_ws_state(ws_clients_upload.clone()))
-        .and_then(handlers::upload::upload_video_url);
-
-    let upload_sound_route = warp::post()
-        .and(warp::path("upload-sound"))
-        .and(warp::multipart::form().max_length(50 * 1024 * 1024)) // 50MB limit for sounds
-        .and(warp::addr::remote())
-        .and(with_state(media_state_upload.clone()))
-        .and(with_ws_state(ws_clients_upload.clone())) // Add WebSocket state
-        .and_then(handlers::upload::upload_sound);
-
-    // Media routes
-    let last_media_route = warp::get()
-        .and(warp::path("last-media"))
-        .and(warp::addr::remote())
-        .and(with_state(media_state_media))
-        .and_then(handlers::media::last_media);
-
-    // WebSocket route - THIS IS THE NEW PART
-    let ws_route = warp::path("ws")
-        .and(warp::ws())
-        .and(with_ws_state(ws_clients_route))
-        .and_then(
-            |ws: warp::ws::Ws, clients| async move { websocket::ws_handler(ws, clients).await },
-        );
-
-    // Serve uploaded files
-    let uploads_dir = warp::path("uploads").and(warp::fs::dir("uploads/"));
-    let sounds_dir = warp::path("sounds").and(warp::fs::dir("sounds/"));
-
-    // Combine all routes
-    let routes = index_route
-        .or(upload_form_route)
-        .or(upload_video_route)
-        .or(upload_youtube_route)
-        .or(upload_sound_route)
-        .or(upload_route)
-        .or(last_media_route)
-        .or(ws_route) // Add WebSocket route
-        .or(uploads_dir)
-        .or(sounds_dir);
-
-    tracing::info!("Server running on http://0.0.0.0:3030");
-    println!("Server running on http://0.0.0.0:3030");
-    warp::serve(routes).run(([0, 0, 0, 0], 3030)).await;
-}
-
-fn with_state(
-    state: Arc<RwLock<state::MediaViewState>>,
-) -> impl Filter<Extract = (Arc<RwLock<state::MediaViewState>>,), Error = std::convert::Infallible> + Clone
-{
-    warp::any().map(move || state.clone())
-}
-
-// Add WebSocket state filter
-fn with_ws_state(
-    clients: websocket::WsClients,
-) -> impl Filter<Extract = (websocket::WsClients,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || clients.clone())
-}
-
-// Background cleanup task
-fn start_cleanup_task(state: Arc<RwLock<state::MediaViewState>>) {
-    tokio::spawn(async move {
-        let deletion_threshold = Duration::from_secs(10);
+    // `bench` load tests a running instance instead of starting one
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if(|arg| arg == "bench").is_some() {
+        let options = match bench::BenchOptions::from_args(args) {
+            Ok(options) => options,
+            Err(e) => {
+                tracing::error!("Invalid bench options: {}", e);
+                std::process::exit(1);
+            }
+        };
+        match bench::run(&options).await {
+            Ok(report) => println!("{}", report),
+            Err(e) => {
+                tracing::error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-        loop {
-            tokio::time::sleep(Duration::from_secs(1)).await;
+    tracing::info!("Starting Homies Gaming Backend server");
 
-            let files_to_delete = {
-                let state_guard = state.read().await;
-                state_guard.get_files_to_delete(deletion_threshold)
-            };
+    // Load configuration
+    let app_config = match config::AppConfig::load() {
+        Ok(app_config) => app_config,
+        Err(e) => {
+            tracing::error!("Failed to load configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let app = App::builder().config(app_config).build().await;
+    let routes = app.routes();
+
+    // Command line addresses replace the configured ones
+    let listen_addrs = match listen::from_args(args) {
+        Ok(addrs) if !addrs.is_empty() => addrs,
+        Ok(_) => app.config().listen.clone(),
+        Err(e) => {
+            tracing::error!("Invalid command line: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-            for filename in files_to_delete {
-                let file_path = format!("uploads/{}", filename);
-                match tokio::fs::remove_file(&file_path).await {
-                    Ok(_) => {
-                        tracing::info!("Deleted file: {}", filename);
-                        let mut state_guard = state.write().await;
-                        state_guard.remove_file_from_state(&filename);
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to delete file {}: {}", file_path, e);
-                        let mut state_guard = state.write().await;
-                        state_guard.mark_for_deletion(&filename);
-                    }
+    // Sockets passed in by systemd take the place of the configured addresses
+    let listeners = match listen::inherited() {
+        Ok(inherited) if !inherited.is_empty() => {
+            tracing::info!("Socket activated with {} listeners", inherited.len());
+            inherited
+        }
+        Ok(_) => listen_addrs
+            .into_iter()
+            .map(|addr| match listen::Incoming::bind(&addr) {
+                Ok(incoming) => (addr, incoming),
+                Err(e) => {
+                    tracing::error!("Failed to listen on {}: {}", addr, e);
+                    std::process::exit(1);
                 }
-            }
+            })
+            .collect(),
+        Err(e) => {
+            tracing::error!("Failed to use the sockets passed by systemd: {}", e);
+            std::process::exit(1);
         }
+    };
+
+    // Advertised with the first TCP port, kept alive until the servers stop
+    let tcp_port = listeners.iter().find_map(|(addr, _)| match addr {
+        listen::ListenAddr::Tcp(addr) => Some(addr.port()),
+        listen::ListenAddr::Unix(_) => None,
     });
+    let _mdns = match (&app.config().mdns, tcp_port) {
+        (Some(mdns_config), Some(port)) => match mdns::advertise(mdns_config, port) {
+            Ok(daemon) => {
+                tracing::info!("Advertising {} over mDNS", mdns_config.base_url(port));
+                Some(daemon)
+            }
+            Err(e) => {
+                tracing::error!("Failed to advertise over mDNS: {}", e);
+                None
+            }
+        },
+        (Some(_), None) => {
+            tracing::warn!("mDNS advertisement needs a TCP address to advertise");
+            None
+        }
+        (None, _) => None,
+    };
+
+    let mut servers = Vec::new();
+    for (addr, incoming) in listeners {
+        tracing::info!("Server running on {}", addr);
+        println!("Server running on {}", addr);
+        let server = warp::serve(routes.clone());
+        servers.push(match incoming {
+            listen::Incoming::Tcp(listener) => {
+                tokio::spawn(server.run_incoming(listen::tcp_connections(listener)))
+            }
+            listen::Incoming::Unix(listener) => {
+                tokio::spawn(server.run_incoming(listen::unix_connections(listener)))
+            }
+        });
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(grpc) = app.config().grpc.clone() {
+        let service = app.grpc_service();
+        tracing::info!("gRPC server running on {}", grpc.listen);
+        servers.push(tokio::spawn(async move {
+            if let Err(e) = grpc::serve(service, grpc.listen).await {
+                tracing::error!("gRPC server on {} failed: {}", grpc.listen, e);
+            }
+        }));
+    }
+    #[cfg(not(feature = "grpc"))]
+    if app.config().grpc.is_some() {
+        tracing::warn!("[grpc] is configured, but this build lacks the grpc feature");
+    }
+    futures_util::future::join_all(servers).await;
 }
@@ -0,0 +1,92 @@
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Service type displays and phone apps browse for
+pub const SERVICE_TYPE: &str = "_homies._tcp.local.";
+
+/// mDNS/Bonjour advertisement of the server, so clients on the LAN find it without a
+/// hardcoded IP
+#[derive(Clone, Debug, Deserialize)]
+pub struct MdnsConfig {
+    /// Instance name shown to browsing clients, also the `<name>.local` host name
+    #[serde(default = "default_name")]
+    pub name: String,
+    /// URL clients should open, e.g. a reverse proxy's; `http://<name>.local:<port>/` otherwise
+    pub base_url: Option<String>,
+}
+
+fn default_name() -> String {
+    "Homies".to_string()
+}
+
+impl MdnsConfig {
+    /// `<name>.local.`, lowercase with anything but letters and digits as dashes
+    pub fn host_name(&self) -> String {
+        let host: String = self
+            .name
+            .trim()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+            .collect();
+        let host = host.trim_matches('-');
+        format!("{}.local.", if host.is_empty() { "homies" } else { host })
+    }
+
+    pub fn base_url(&self, port: u16) -> String {
+        match &self.base_url {
+            Some(url) => url.clone(),
+            None => format!("http://{}:{}/", self.host_name().trim_end_matches('.'), port),
+        }
+    }
+
+    /// TXT record of the service
+    pub fn properties(&self, port: u16) -> HashMap<String, String> {
+        HashMap::from([
+            ("url".to_string(), self.base_url(port)),
+            ("path".to_string(), "/".to_string()),
+            ("version".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+        ])
+    }
+}
+
+/// Announce the server listening on `port` on every interface, for as long as the returned
+/// daemon lives
+pub fn advertise(config: &MdnsConfig, port: u16) -> Result<ServiceDaemon, mdns_sd::Error> {
+    let daemon = ServiceDaemon::new()?;
+    let service = ServiceInfo::new(
+        SERVICE_TYPE,
+        &config.name,
+        &config.host_name(),
+        "",
+        port,
+        config.properties(port),
+    )?
+    .enable_addr_auto();
+    daemon.register(service)?;
+    Ok(daemon)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advertised_names_and_url() {
+        let config = MdnsConfig {
+            name: " Living Room TV ".to_string(),
+            base_url: None,
+        };
+        assert_eq!(config.host_name(), "living-room-tv.local.");
+        let properties = config.properties(3030);
+        assert_eq!(properties["url"], "http://living-room-tv.local:3030/");
+        assert_eq!(properties["path"], "/");
+
+        let config = MdnsConfig {
+            name: "🎮".to_string(),
+            base_url: Some("https://homies.example.com/".to_string()),
+        };
+        assert_eq!(config.host_name(), "homies.local.");
+        assert_eq!(config.base_url(3030), "https://homies.example.com/");
+    }
+}
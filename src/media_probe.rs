@@ -0,0 +1,216 @@
+use crate::types::Layout;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::process::Command as AsyncCommand;
+
+/// Aspect ratio of the display the media is shown on
+const SCREEN_ASPECT: f64 = 16.0 / 9.0;
+const ASPECT_TOLERANCE: f64 = 0.05;
+
+/// What we know about a stored media file
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct MediaProbe {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub duration_secs: Option<f64>,
+    pub codec: Option<String>,
+    pub has_audio: bool,
+    pub size_bytes: u64,
+}
+
+impl MediaProbe {
+    /// Probe a file with ffprobe, falling back to reading image headers
+    /// Never fails: whatever could not be determined is left empty
+    pub async fn probe(path: &str) -> Self {
+        let size_bytes = tokio::fs::metadata(path)
+            .await
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+
+        let mut probe = match Self::run_ffprobe(path).await {
+            Some(json) => Self::from_ffprobe_json(&json),
+            None => Self::default(),
+        };
+        probe.size_bytes = size_bytes;
+
+        if probe.width.is_none()
+            && let Ok(size) = imagesize::size(path)
+        {
+            probe.width = Some(size.width as u32);
+            probe.height = Some(size.height as u32);
+        }
+
+        tracing::info!("Probed {}: {:?}", path, probe);
+        probe
+    }
+
+    async fn run_ffprobe(path: &str) -> Option<Value> {
+        let output = AsyncCommand::new("ffprobe")
+            .args([
+                "-v",
+                "quiet",
+                "-print_format",
+                "json",
+                "-show_format",
+                "-show_streams",
+                path,
+            ])
+            .output()
+            .await
+            .map_err(|e| tracing::debug!("Failed to execute ffprobe: {}", e))
+            .ok()?;
+
+        if !output.status.success() {
+            tracing::debug!("ffprobe could not read {}", path);
+            return None;
+        }
+        serde_json::from_slice(&output.stdout).ok()
+    }
+
+    /// Extract dimensions, duration and codec of the first video stream from ffprobe output
+    pub fn from_ffprobe_json(json: &Value) -> Self {
+        let streams = json["streams"].as_array().cloned().unwrap_or_default();
+        let video_stream = streams
+            .iter()
+            .find(|stream| stream["codec_type"].as_str() == Some("video"));
+        let main_stream = video_stream.or_else(|| streams.first());
+
+        // ffprobe reports durations as strings
+        let parse_duration = |value: &Value| {
+            value
+                .as_str()
+                .and_then(|duration| duration.parse::<f64>().ok())
+                .filter(|duration| duration.is_finite() && *duration > 0.0)
+        };
+
+        Self {
+            width: video_stream
+                .and_then(|stream| stream["width"].as_u64())
+                .map(|width| width as u32),
+            height: video_stream
+                .and_then(|stream| stream["height"].as_u64())
+                .map(|height| height as u32),
+            duration_secs: parse_duration(&json["format"]["duration"])
+                .or_else(|| main_stream.and_then(|stream| parse_duration(&stream["duration"]))),
+            codec: main_stream
+                .and_then(|stream| stream["codec_name"].as_str())
+                .map(|codec| codec.to_string()),
+            has_audio: streams
+                .iter()
+                .any(|stream| stream["codec_type"].as_str() == Some("audio")),
+            size_bytes: 0,
+        }
+    }
+
+    /// Whether the file has a video stream with a length, i.e. a re-encode produced something
+    pub fn is_playable_video(&self) -> bool {
+        self.size_bytes > 0
+            && self.width.is_some_and(|width| width > 0)
+            && self.height.is_some_and(|height| height > 0)
+            && self.duration_secs.is_some()
+    }
+
+    pub fn orientation(&self) -> Option<&'static str> {
+        let (width, height) = (self.width?, self.height?);
+        Some(match width.cmp(&height) {
+            std::cmp::Ordering::Greater => "landscape",
+            std::cmp::Ordering::Less => "portrait",
+            std::cmp::Ordering::Equal => "square",
+        })
+    }
+
+    /// How the media fits a 16:9 screen: "pillarbox" (bars on the sides),
+    /// "letterbox" (bars top and bottom) or "none"
+    pub fn letterbox(&self) -> Option<&'static str> {
+        let (width, height) = (self.width?, self.height?);
+        if height == 0 {
+            return None;
+        }
+        let aspect = width as f64 / height as f64;
+        Some(if aspect < SCREEN_ASPECT - ASPECT_TOLERANCE {
+            "pillarbox"
+        } else if aspect > SCREEN_ASPECT + ASPECT_TOLERANCE {
+            "letterbox"
+        } else {
+            "none"
+        })
+    }
+
+    /// Display hints sent along with WebSocket media events
+    pub fn layout_hint(&self) -> Layout {
+        Layout {
+            width: self.width,
+            height: self.height,
+            orientation: self.orientation().map(str::to_string),
+            letterbox: self.letterbox().map(str::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_ffprobe_json() {
+        let json = json!({
+            "streams": [
+                { "codec_type": "audio", "codec_name": "aac", "duration": "12.0" },
+                { "codec_type": "video", "codec_name": "h264", "width": 1080, "height": 1920 }
+            ],
+            "format": { "duration": "12.345000" }
+        });
+        let probe = MediaProbe::from_ffprobe_json(&json);
+        assert_eq!(probe.width, Some(1080));
+        assert_eq!(probe.height, Some(1920));
+        assert_eq!(probe.duration_secs, Some(12.345));
+        assert_eq!(probe.codec.as_deref(), Some("h264"));
+        assert!(probe.has_audio);
+
+        // Images have no duration
+        let json = json!({
+            "streams": [{ "codec_type": "video", "codec_name": "png", "width": 640, "height": 480 }],
+            "format": { "duration": "N/A" }
+        });
+        let probe = MediaProbe::from_ffprobe_json(&json);
+        assert_eq!(probe.duration_secs, None);
+        assert_eq!(probe.codec.as_deref(), Some("png"));
+        assert!(!probe.has_audio);
+
+        assert_eq!(MediaProbe::from_ffprobe_json(&json!({})), MediaProbe::default());
+    }
+
+    #[test]
+    fn test_is_playable_video() {
+        let json = json!({
+            "streams": [
+                { "codec_type": "video", "codec_name": "h264", "width": 1280, "height": 720 }
+            ],
+            "format": { "duration": "3.0" }
+        });
+        let mut probe = MediaProbe::from_ffprobe_json(&json);
+        // Nothing on disk
+        assert!(!probe.is_playable_video());
+        probe.size_bytes = 4096;
+        assert!(probe.is_playable_video());
+        probe.duration_secs = None;
+        assert!(!probe.is_playable_video());
+        assert!(!MediaProbe::default().is_playable_video());
+    }
+
+    #[test]
+    fn test_layout_hints() {
+        let probe = |width, height| MediaProbe {
+            width: Some(width),
+            height: Some(height),
+            ..MediaProbe::default()
+        };
+        assert_eq!(probe(1080, 1920).orientation(), Some("portrait"));
+        assert_eq!(probe(1080, 1920).letterbox(), Some("pillarbox"));
+        assert_eq!(probe(1920, 1080).letterbox(), Some("none"));
+        assert_eq!(probe(2560, 1080).letterbox(), Some("letterbox"));
+        assert_eq!(probe(500, 500).orientation(), Some("square"));
+        assert_eq!(MediaProbe::default().letterbox(), None);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::events::Event;
+use rumqttc::{LastWill, MqttOptions, QoS};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// MQTT broker the backend publishes its events to and takes commands from, so smart home
+/// automations (Home Assistant, Node-RED) can react to new media
+#[derive(Clone, Debug, Deserialize)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Every topic starts with it, e.g. `homies/media/new`
+    #[serde(default = "default_topic_prefix")]
+    pub topic_prefix: String,
+}
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_client_id() -> String {
+    "homies".to_string()
+}
+
+fn default_topic_prefix() -> String {
+    "homies".to_string()
+}
+
+impl MqttConfig {
+    pub fn options(&self) -> MqttOptions {
+        let mut options = MqttOptions::new(&self.client_id, &self.host, self.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &self.username {
+            options.set_credentials(username, self.password.clone().unwrap_or_default());
+        }
+        // The broker flags the backend offline when the connection drops
+        options.set_last_will(LastWill::new(
+            self.status_topic(),
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        options
+    }
+
+    /// Retained `online`/`offline` availability of the backend
+    pub fn status_topic(&self) -> String {
+        format!("{}/status", self.topic_prefix)
+    }
+
+    /// Where commands are read from, see [`MqttCommand`]
+    pub fn command_topic(&self) -> String {
+        format!("{}/command", self.topic_prefix)
+    }
+
+    pub fn event_topic(&self, event: &Event) -> String {
+        let suffix = match event {
+            Event::MediaUploaded { .. } => "media/new",
+            Event::MediaShown { .. } => "media/shown",
+            Event::MediaDeleted { .. } => "media/deleted",
+            Event::ProcessingFailed { .. } => "media/failed",
+            Event::ProcessingWarning { .. } => "media/warning",
+            Event::JobFailed { .. } => "jobs/failed",
+        };
+        format!("{}/{}", self.topic_prefix, suffix)
+    }
+}
+
+/// JSON published to the command topic, e.g. `{"action": "play_sound", "hotkey": 1}`
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum MqttCommand {
+    /// Play a soundboard sound, by id or by hotkey
+    PlaySound { id: Option<u64>, hotkey: Option<u8> },
+    /// Pause the background music
+    Stop,
+}
+
+impl MqttCommand {
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        serde_json::from_slice(payload).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topics_and_commands() {
+        let config = MqttConfig {
+            host: "broker.lan".to_string(),
+            port: default_port(),
+            client_id: default_client_id(),
+            username: None,
+            password: None,
+            topic_prefix: "den".to_string(),
+        };
+        let event = Event::MediaDeleted {
+            filename: "a.png".to_string(),
+        };
+        assert_eq!(config.event_topic(&event), "den/media/deleted");
+        assert_eq!(config.command_topic(), "den/command");
+
+        assert_eq!(
+            MqttCommand::parse(br#"{"action": "play_sound", "hotkey": 1}"#),
+            Some(MqttCommand::PlaySound {
+                id: None,
+                hotkey: Some(1)
+            })
+        );
+        assert_eq!(
+            MqttCommand::parse(br#"{"action": "stop"}"#),
+            Some(MqttCommand::Stop)
+        );
+        assert_eq!(MqttCommand::parse(b"flash"), None);
+    }
+}
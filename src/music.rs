@@ -0,0 +1,163 @@
+use serde::Serialize;
+
+// Volume of the music channel until someone changes it, in percent
+const DEFAULT_VOLUME: u8 = 40;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MusicTrack {
+    pub id: u64,
+    pub filename: String,
+}
+
+/// What the music channel is doing, sent along with every `music` event
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MusicStatus {
+    pub playing: bool,
+    /// Percent, 0 to 100
+    pub volume: u8,
+    pub track: Option<MusicTrack>,
+}
+
+/// Background music playlist, looping under whatever is on screen
+/// Separate from the soundboard, whose sounds are one-shot effects played over it
+#[derive(Clone, Debug)]
+pub struct MusicState {
+    playlist: Vec<MusicTrack>, // In upload order
+    next_track_id: u64,
+    current: Option<u64>, // Track playing or paused
+    playing: bool,
+    volume: u8,
+}
+
+impl Default for MusicState {
+    fn default() -> Self {
+        Self {
+            playlist: Vec::new(),
+            next_track_id: 1,
+            current: None,
+            playing: false,
+            volume: DEFAULT_VOLUME,
+        }
+    }
+}
+
+impl MusicState {
+    /// Add a stored track to the playlist, reusing the entry on re-upload
+    pub fn add_track(&mut self, filename: &str) -> MusicTrack {
+        if let Some(track) = self
+            .playlist
+            .iter()
+            .find(|track| track.filename == filename)
+        {
+            return track.clone();
+        }
+        let track = MusicTrack {
+            id: self.next_track_id,
+            filename: filename.to_string(),
+        };
+        self.next_track_id += 1;
+        self.playlist.push(track.clone());
+        track
+    }
+
+    pub fn playlist(&self) -> &[MusicTrack] {
+        &self.playlist
+    }
+
+    pub fn status(&self) -> MusicStatus {
+        MusicStatus {
+            playing: self.playing,
+            volume: self.volume,
+            track: self.current_track().cloned(),
+        }
+    }
+
+    fn current_track(&self) -> Option<&MusicTrack> {
+        let id = self.current?;
+        self.playlist.iter().find(|track| track.id == id)
+    }
+
+    /// Play the given track, or resume the current one (the first track when none was picked)
+    /// None when there is no such track
+    pub fn play(&mut self, id: Option<u64>) -> Option<MusicStatus> {
+        let id = match id {
+            Some(id) => id,
+            None => self.current_track().or(self.playlist.first())?.id,
+        };
+        self.playlist.iter().find(|track| track.id == id)?;
+        self.current = Some(id);
+        self.playing = true;
+        Some(self.status())
+    }
+
+    pub fn pause(&mut self) -> MusicStatus {
+        self.playing = false;
+        self.status()
+    }
+
+    /// Move on to the track after the current one, wrapping around the playlist
+    pub fn skip(&mut self) -> Option<MusicStatus> {
+        let position = self
+            .current
+            .and_then(|id| self.playlist.iter().position(|track| track.id == id));
+        let next = match position {
+            Some(position) => self.playlist.get((position + 1) % self.playlist.len()),
+            None => self.playlist.first(),
+        }?;
+        self.current = Some(next.id);
+        self.playing = true;
+        Some(self.status())
+    }
+
+    pub fn set_volume(&mut self, volume: u8) -> MusicStatus {
+        self.volume = volume.min(100);
+        self.status()
+    }
+
+    /// Drop a track whose file is gone, stopping the music if it was the current one
+    pub fn remove_track(&mut self, filename: &str) {
+        self.playlist.retain(|track| track.filename != filename);
+        if self.current_track().is_none() {
+            self.current = None;
+            self.playing = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playlist_controls() {
+        let mut music = MusicState::default();
+        assert!(music.play(None).is_none());
+        assert!(music.skip().is_none());
+
+        let lofi = music.add_track("lofi.mp3");
+        let chill = music.add_track("chill.mp3");
+        assert_eq!(music.add_track("lofi.mp3"), lofi);
+
+        // Resuming with nothing picked starts at the top of the playlist
+        let status = music.play(None).unwrap();
+        assert!(status.playing);
+        assert_eq!(status.track, Some(lofi.clone()));
+
+        assert_eq!(music.skip().unwrap().track, Some(chill.clone()));
+        assert_eq!(music.skip().unwrap().track, Some(lofi.clone()));
+
+        let status = music.pause();
+        assert!(!status.playing);
+        assert_eq!(status.track, Some(lofi.clone()));
+        assert_eq!(music.play(None).unwrap().track, Some(lofi.clone()));
+        assert!(music.play(Some(42)).is_none());
+
+        assert_eq!(music.set_volume(250).volume, 100);
+
+        music.remove_track("lofi.mp3");
+        let status = music.status();
+        assert!(!status.playing);
+        assert_eq!(status.track, None);
+        assert_eq!(music.playlist(), [chill]);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::events::Event;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyService {
+    Ntfy,
+    Gotify,
+}
+
+/// Push notifications to the house admin's phone, through ntfy or Gotify
+#[derive(Clone, Debug, Deserialize)]
+pub struct NotifierConfig {
+    pub service: NotifyService,
+    /// Server root, e.g. `https://ntfy.sh` or `https://gotify.example.com`
+    pub url: String,
+    /// ntfy topic, unused by Gotify
+    #[serde(default)]
+    pub topic: String,
+    /// ntfy access token or Gotify application token
+    pub token: Option<String>,
+    /// Event names notified about
+    #[serde(default = "default_events")]
+    pub events: Vec<String>,
+}
+
+fn default_events() -> Vec<String> {
+    vec![
+        "media_uploaded".to_string(),
+        "processing_failed".to_string(),
+        "job_failed".to_string(),
+    ]
+}
+
+#[derive(Error, Debug)]
+pub enum NotifyError {
+    #[error("Notification request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Notification service returned HTTP {0}")]
+    Status(u16),
+}
+
+/// Title, body and priority (1 to 5) of the notification for an event
+pub fn message(event: &Event) -> (String, String, u8) {
+    match event {
+        Event::MediaUploaded { media } if media.caption.is_empty() => {
+            ("New media".to_string(), media.filename.clone(), 3)
+        }
+        Event::MediaUploaded { media } => (
+            "New media".to_string(),
+            format!("{}: {}", media.filename, media.caption),
+            3,
+        ),
+        Event::MediaShown { media } => ("On screen".to_string(), media.filename.clone(), 2),
+        Event::MediaDeleted { filename } => ("Media deleted".to_string(), filename.clone(), 1),
+        Event::ProcessingFailed { filename, error } => (
+            "Processing failed".to_string(),
+            format!("{}: {}", filename, error),
+            4,
+        ),
+        Event::ProcessingWarning { filename, reason } => (
+            "Processing warning".to_string(),
+            format!("{}: {}", filename, reason.message()),
+            2,
+        ),
+        Event::JobFailed { job_id, url } => (
+            "Download failed".to_string(),
+            format!("Job {}: {}", job_id, url),
+            4,
+        ),
+    }
+}
+
+impl NotifierConfig {
+    fn wants(&self, event: &Event) -> bool {
+        self.events.iter().any(|name| name == event.name())
+    }
+
+    pub async fn send(&self, client: &reqwest::Client, event: &Event) -> Result<(), NotifyError> {
+        let (title, body, priority) = message(event);
+        let root = self.url.trim_end_matches('/');
+        let request = match self.service {
+            NotifyService::Ntfy => {
+                let request = client
+                    .post(format!("{}/{}", root, self.topic))
+                    .header("title", title)
+                    .header("priority", priority.to_string())
+                    .header("tags", event.name())
+                    .body(body);
+                match &self.token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            }
+            NotifyService::Gotify => client
+                .post(format!("{}/message", root))
+                .query(&[("token", self.token.as_deref().unwrap_or_default())])
+                .header("content-type", "application/json")
+                .body(
+                    json!({
+                        "title": title,
+                        "message": body,
+                        // Gotify priorities go up to 10
+                        "priority": priority * 2,
+                    })
+                    .to_string(),
+                ),
+        };
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(NotifyError::Status(response.status().as_u16()));
+        }
+        Ok(())
+    }
+}
+
+/// Notify about every queued event the config asks for
+pub async fn run(mut events: mpsc::UnboundedReceiver<Event>, config: NotifierConfig) {
+    let client = match reqwest::Client::builder().timeout(NOTIFY_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!("Failed to set up the notification client: {}", e);
+            return;
+        }
+    };
+    while let Some(event) = events.recv().await {
+        if !config.wants(&event) {
+            continue;
+        }
+        match config.send(&client, &event).await {
+            Ok(()) => tracing::info!("Sent {} notification", event.name()),
+            Err(e) => tracing::warn!("Failed to send {} notification: {}", event.name(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventMedia;
+    use crate::state::MediaType;
+
+    #[test]
+    fn test_messages_and_default_events() {
+        let config: NotifierConfig =
+            toml::from_str("service = \"ntfy\"\nurl = \"https://ntfy.sh\"\ntopic = \"homies\"")
+                .unwrap();
+        let uploaded = Event::MediaUploaded {
+            media: EventMedia {
+                id: 1,
+                filename: "clip.mp4".to_string(),
+                media_type: MediaType::Video,
+                caption: "gg".to_string(),
+            },
+        };
+        assert!(config.wants(&uploaded));
+        assert_eq!(
+            message(&uploaded),
+            ("New media".to_string(), "clip.mp4: gg".to_string(), 3)
+        );
+
+        let deleted = Event::MediaDeleted {
+            filename: "clip.mp4".to_string(),
+        };
+        assert!(!config.wants(&deleted));
+        let failed = Event::ProcessingFailed {
+            filename: "clip.mp4".to_string(),
+            error: "ffmpeg exited with 1".to_string(),
+        };
+        assert!(config.wants(&failed));
+        assert_eq!(message(&failed).2, 4);
+    }
+}
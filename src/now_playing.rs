@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use thiserror::Error;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+// Longer titles are cut, the overlay only has room for a line or two
+const MAX_FIELD_CHARS: usize = 200;
+
+/// Track playing on someone's Spotify or YouTube Music, shown on the TV between media
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NowPlaying {
+    pub title: String,
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub album: Option<String>,
+    #[serde(default)]
+    pub artwork_url: Option<String>,
+    /// Where it plays, e.g. "spotify" or "youtube-music"
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum NowPlayingError {
+    #[error("A title is required")]
+    MissingTitle,
+    #[error("Artwork must be an http(s) URL")]
+    InvalidArtwork,
+    #[error("Now playing request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Now playing source returned HTTP {0}")]
+    Status(u16),
+    #[error("Unexpected now playing response")]
+    InvalidResponse,
+}
+
+fn clean(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| {
+            value
+                .trim()
+                .chars()
+                .take(MAX_FIELD_CHARS)
+                .collect::<String>()
+        })
+        .filter(|value| !value.is_empty())
+}
+
+impl NowPlaying {
+    /// Trim and cap every field, refusing tracks without a title or with artwork the
+    /// display couldn't load
+    pub fn validate(self) -> Result<Self, NowPlayingError> {
+        let title = clean(Some(self.title)).ok_or(NowPlayingError::MissingTitle)?;
+        let artwork_url = clean(self.artwork_url);
+        if let Some(url) = &artwork_url
+            && !(url.starts_with("https://") || url.starts_with("http://"))
+        {
+            return Err(NowPlayingError::InvalidArtwork);
+        }
+        Ok(Self {
+            title,
+            artist: clean(self.artist),
+            album: clean(self.album),
+            artwork_url,
+            source: clean(self.source),
+        })
+    }
+
+    /// Read either this struct's own JSON or the response of Spotify's currently playing
+    /// endpoint, None when nothing is playing
+    pub fn from_json(body: &Value) -> Result<Option<Self>, NowPlayingError> {
+        if body.is_null() {
+            return Ok(None);
+        }
+        if let Some(item) = body.get("item") {
+            if item.is_null() || body["is_playing"] == Value::Bool(false) {
+                return Ok(None);
+            }
+            let artists: Vec<_> = item["artists"]
+                .as_array()
+                .map(|artists| {
+                    artists
+                        .iter()
+                        .filter_map(|artist| artist["name"].as_str())
+                        .collect()
+                })
+                .unwrap_or_default();
+            let track = Self {
+                title: item["name"].as_str().unwrap_or_default().to_string(),
+                artist: Some(artists.join(", ")),
+                album: item["album"]["name"].as_str().map(str::to_string),
+                artwork_url: item["album"]["images"][0]["url"]
+                    .as_str()
+                    .map(str::to_string),
+                source: Some("spotify".to_string()),
+            };
+            return track.validate().map(Some);
+        }
+        let track: Self =
+            serde_json::from_value(body.clone()).map_err(|_| NowPlayingError::InvalidResponse)?;
+        track.validate().map(Some)
+    }
+}
+
+/// Endpoint polled for the current track: Spotify's currently playing API or any service
+/// answering with a [`NowPlaying`] JSON object
+#[derive(Clone, Debug, Deserialize)]
+pub struct NowPlayingSource {
+    /// e.g. `https://api.spotify.com/v1/me/player/currently-playing`
+    pub url: String,
+    /// Sent as a bearer token
+    pub token: Option<String>,
+    #[serde(default = "default_poll_secs")]
+    pub every_secs: u64,
+}
+
+fn default_poll_secs() -> u64 {
+    15
+}
+
+impl NowPlayingSource {
+    pub fn every(&self) -> Duration {
+        Duration::from_secs(self.every_secs.max(1))
+    }
+
+    pub async fn fetch(&self) -> Result<Option<NowPlaying>, NowPlayingError> {
+        let client = reqwest::Client::builder().timeout(FETCH_TIMEOUT).build()?;
+        let mut request = client.get(&self.url);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        // Spotify answers 204 when nothing is playing
+        if response.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(NowPlayingError::Status(response.status().as_u16()));
+        }
+        let body: Value = serde_json::from_slice(&response.bytes().await?)
+            .map_err(|_| NowPlayingError::InvalidResponse)?;
+        NowPlaying::from_json(&body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate() {
+        let track = NowPlaying {
+            title: "  Snowman ".to_string(),
+            artist: Some(" ".to_string()),
+            album: None,
+            artwork_url: Some("https://i.scdn.co/image/abc".to_string()),
+            source: Some("youtube-music".to_string()),
+        }
+        .validate()
+        .unwrap();
+        assert_eq!(track.title, "Snowman");
+        assert_eq!(track.artist, None);
+
+        let untitled = NowPlaying {
+            title: " ".to_string(),
+            ..track.clone()
+        };
+        assert!(matches!(
+            untitled.validate(),
+            Err(NowPlayingError::MissingTitle)
+        ));
+        let scripted = NowPlaying {
+            artwork_url: Some("javascript:alert(1)".to_string()),
+            ..track
+        };
+        assert!(matches!(
+            scripted.validate(),
+            Err(NowPlayingError::InvalidArtwork)
+        ));
+    }
+
+    #[test]
+    fn test_from_json() {
+        let spotify = json!({
+            "is_playing": true,
+            "item": {
+                "name": "Redbone",
+                "artists": [{ "name": "Childish Gambino" }],
+                "album": {
+                    "name": "Awaken, My Love!",
+                    "images": [{ "url": "https://i.scdn.co/image/redbone" }]
+                }
+            }
+        });
+        let track = NowPlaying::from_json(&spotify).unwrap().unwrap();
+        assert_eq!(track.title, "Redbone");
+        assert_eq!(track.artist.as_deref(), Some("Childish Gambino"));
+        assert_eq!(
+            track.artwork_url.as_deref(),
+            Some("https://i.scdn.co/image/redbone")
+        );
+        assert_eq!(track.source.as_deref(), Some("spotify"));
+
+        let paused = json!({ "is_playing": false, "item": spotify["item"] });
+        assert!(NowPlaying::from_json(&paused).unwrap().is_none());
+        assert!(NowPlaying::from_json(&Value::Null).unwrap().is_none());
+
+        let own = json!({ "title": "Lo-fi beats", "source": "youtube-music" });
+        assert_eq!(
+            NowPlaying::from_json(&own).unwrap().unwrap().title,
+            "Lo-fi beats"
+        );
+        assert!(NowPlaying::from_json(&json!({ "song": "?" })).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Time between announcing a video and playing it, for every display to load it first
+pub const START_LEAD: Duration = Duration::from_millis(1500);
+
+/// When the video on screen started playing for the display group, in server time
+/// Displays line their clock up with GET /time and seek to `now - start_at_ms`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct PlaybackEpoch {
+    pub media_id: u64,
+    pub start_at_ms: u64, // Unix milliseconds
+}
+
+impl PlaybackEpoch {
+    /// Epoch of a video announced now, starting after the lead time
+    pub fn starting(media_id: u64, now: SystemTime) -> Self {
+        Self {
+            media_id,
+            start_at_ms: unix_ms(now + START_LEAD),
+        }
+    }
+
+    /// Where playback is at `now_ms`, none before it started
+    pub fn position_ms(&self, now_ms: u64) -> Option<u64> {
+        now_ms.checked_sub(self.start_at_ms)
+    }
+}
+
+pub fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playback_epoch() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let epoch = PlaybackEpoch::starting(3, now);
+        assert_eq!(epoch.start_at_ms, 1_001_500);
+        assert_eq!(epoch.position_ms(1_001_499), None);
+        assert_eq!(epoch.position_ms(1_001_500), Some(0));
+        assert_eq!(epoch.position_ms(1_004_000), Some(2_500));
+    }
+}
@@ -0,0 +1,242 @@
+use crate::session::ClientId;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+const MAX_OPTIONS: usize = 6;
+// Everything is drawn on screen, keep it readable from the couch
+const MAX_QUESTION_CHARS: usize = 120;
+const MAX_OPTION_CHARS: usize = 60;
+/// Shortest and longest a poll can stay open
+pub const POLL_DURATION_RANGE: (u64, u64) = (10, 3600);
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PollError {
+    #[error("A poll needs a question")]
+    NoQuestion,
+    #[error("A poll needs 2 to {MAX_OPTIONS} options")]
+    Options,
+    #[error("A poll is already open")]
+    AlreadyOpen,
+    #[error("No open poll with this id")]
+    NotOpen,
+    #[error("No such option")]
+    NoSuchOption,
+    #[error("Already voted")]
+    AlreadyVoted,
+}
+
+#[derive(Clone, Debug)]
+pub struct Poll {
+    pub id: u64,
+    pub question: String,
+    pub options: Vec<String>,
+    pub closes_at: SystemTime,
+    pub closed: bool,
+    votes: HashMap<ClientId, usize>, // Voter -> option index
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct OptionTally {
+    pub text: String,
+    pub votes: u64,
+}
+
+/// A poll as sent to displays and voters
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PollStatus {
+    pub id: u64,
+    pub question: String,
+    pub options: Vec<OptionTally>,
+    pub total_votes: u64,
+    pub remaining_secs: u64,
+    pub closed: bool,
+}
+
+impl Poll {
+    pub fn status(&self, now: SystemTime) -> PollStatus {
+        let mut options: Vec<_> = self
+            .options
+            .iter()
+            .map(|text| OptionTally {
+                text: text.clone(),
+                votes: 0,
+            })
+            .collect();
+        for option in self.votes.values() {
+            options[*option].votes += 1;
+        }
+        let remaining = match self.closed {
+            true => Duration::ZERO,
+            false => self.closes_at.duration_since(now).unwrap_or_default(),
+        };
+        PollStatus {
+            id: self.id,
+            question: self.question.clone(),
+            options,
+            total_votes: self.votes.len() as u64,
+            remaining_secs: remaining.as_secs(),
+            closed: self.closed,
+        }
+    }
+}
+
+/// The poll on screen, one at a time; the last one stays around with its results once closed
+#[derive(Clone, Debug)]
+pub struct Polls {
+    current: Option<Poll>,
+    next_id: u64,
+}
+
+impl Default for Polls {
+    fn default() -> Self {
+        Self {
+            current: None,
+            next_id: 1,
+        }
+    }
+}
+
+impl Polls {
+    pub fn start(
+        &mut self,
+        question: &str,
+        options: &[String],
+        duration: Duration,
+        now: SystemTime,
+    ) -> Result<&Poll, PollError> {
+        if self.current.as_ref().is_some_and(|poll| !poll.closed) {
+            return Err(PollError::AlreadyOpen);
+        }
+        let question: String = question.trim().chars().take(MAX_QUESTION_CHARS).collect();
+        if question.is_empty() {
+            return Err(PollError::NoQuestion);
+        }
+        let options: Vec<String> = options
+            .iter()
+            .map(|option| {
+                option
+                    .trim()
+                    .chars()
+                    .take(MAX_OPTION_CHARS)
+                    .collect::<String>()
+            })
+            .filter(|option| !option.is_empty())
+            .collect();
+        if !(2..=MAX_OPTIONS).contains(&options.len()) {
+            return Err(PollError::Options);
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        Ok(self.current.insert(Poll {
+            id,
+            question,
+            options,
+            closes_at: now + duration,
+            closed: false,
+            votes: HashMap::new(),
+        }))
+    }
+
+    pub fn current(&self) -> Option<&Poll> {
+        self.current.as_ref()
+    }
+
+    /// Count one vote per session or IP address
+    pub fn vote(&mut self, id: u64, option: usize, voter: ClientId) -> Result<&Poll, PollError> {
+        let poll = self
+            .current
+            .as_mut()
+            .filter(|poll| poll.id == id && !poll.closed)
+            .ok_or(PollError::NotOpen)?;
+        if option >= poll.options.len() {
+            return Err(PollError::NoSuchOption);
+        }
+        if poll.votes.contains_key(&voter) {
+            return Err(PollError::AlreadyVoted);
+        }
+        poll.votes.insert(voter, option);
+        Ok(poll)
+    }
+
+    /// Close the open poll once its time is up, returning it for the results
+    pub fn close_expired(&mut self, now: SystemTime) -> Option<&Poll> {
+        let poll = self
+            .current
+            .as_mut()
+            .filter(|poll| !poll.closed && poll.closes_at <= now)?;
+        poll.closed = true;
+        Some(poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_poll_votes_and_results() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let options = ["Pizza".to_string(), " Tacos ".to_string(), String::new()];
+        let alice = ClientId::Session("a".repeat(32));
+        let bob = ClientId::Ip([192, 168, 1, 20].into());
+        let mut polls = Polls::default();
+
+        assert_eq!(
+            polls
+                .start("Dinner?", &options[..1], Duration::from_secs(60), now)
+                .err(),
+            Some(PollError::Options)
+        );
+        let id = polls
+            .start("Dinner?", &options, Duration::from_secs(60), now)
+            .unwrap()
+            .id;
+        assert_eq!(
+            polls
+                .start("Again?", &options, Duration::from_secs(60), now)
+                .err(),
+            Some(PollError::AlreadyOpen)
+        );
+
+        polls.vote(id, 1, alice.clone()).unwrap();
+        polls.vote(id, 1, bob).unwrap();
+        assert_eq!(
+            polls.vote(id, 0, alice.clone()).err(),
+            Some(PollError::AlreadyVoted)
+        );
+        assert_eq!(
+            polls.vote(id, 2, alice).err(),
+            Some(PollError::NoSuchOption)
+        );
+
+        assert!(polls.close_expired(now + Duration::from_secs(59)).is_none());
+        let status = polls
+            .close_expired(now + Duration::from_secs(60))
+            .unwrap()
+            .status(now);
+        assert!(status.closed);
+        assert_eq!(status.total_votes, 2);
+        assert_eq!(
+            status.options,
+            [
+                OptionTally {
+                    text: "Pizza".to_string(),
+                    votes: 0
+                },
+                OptionTally {
+                    text: "Tacos".to_string(),
+                    votes: 2
+                },
+            ]
+        );
+        assert!(polls.close_expired(now + Duration::from_secs(61)).is_none());
+        assert_eq!(
+            polls.vote(id, 0, ClientId::Ip([10, 0, 0, 1].into())).err(),
+            Some(PollError::NotOpen)
+        );
+    }
+}
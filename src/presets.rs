@@ -0,0 +1,69 @@
+use crate::filter_graph::{CaptionAnimation, VideoEffect};
+use crate::video_processing::{Framing, ImageFormat, QualityProfile};
+use serde::Deserialize;
+
+/// Named set of upload options, picked with the `preset` form field instead of setting each
+/// one, e.g. "meme" for a fast, watermarked encode with a fading caption
+/// Options the preset sets replace the upload's own, the rest are left as sent
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct UploadPreset {
+    pub name: String,
+    pub quality: Option<QualityProfile>,
+    pub watermark: Option<bool>,
+    pub caption_animation: Option<CaptionAnimation>,
+    pub caption_delay_secs: Option<f64>,
+    pub effect: Option<VideoEffect>,
+    pub framing: Option<Framing>,
+    pub auto_captions: Option<bool>,
+    pub image_format: Option<ImageFormat>,
+    /// How long images stay on screen
+    pub duration_secs: Option<u64>,
+    pub expires_in_secs: Option<u64>,
+    pub burn_after_viewing: Option<bool>,
+}
+
+/// The preset called `name`, ignoring case
+pub fn find<'a>(presets: &'a [UploadPreset], name: &str) -> Option<&'a UploadPreset> {
+    let name = name.trim();
+    presets
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Presets {
+        presets: Vec<UploadPreset>,
+    }
+
+    #[test]
+    fn test_presets_from_config() {
+        let Presets { presets } = toml::from_str(
+            r#"
+            [[presets]]
+            name = "meme"
+            quality = "fast"
+            watermark = true
+            caption_animation = "fade"
+
+            [[presets]]
+            name = "clip"
+            effect = "boomerang"
+            framing = "blur-fill"
+            "#,
+        )
+        .unwrap();
+        let meme = find(&presets, " Meme").unwrap();
+        assert_eq!(meme.quality, Some(QualityProfile::Fast));
+        assert_eq!(meme.watermark, Some(true));
+        assert_eq!(meme.caption_animation, Some(CaptionAnimation::Fade));
+        assert_eq!(meme.effect, None);
+        let clip = find(&presets, "clip").unwrap();
+        assert_eq!(clip.effect, Some(VideoEffect::Boomerang));
+        assert_eq!(clip.framing, Some(Framing::BlurFill));
+        assert!(find(&presets, "unknown").is_none());
+    }
+}
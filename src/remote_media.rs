@@ -0,0 +1,152 @@
+use crate::utils::sanitize_filename;
+use futures_util::StreamExt;
+use percent_encoding::percent_decode_str;
+use std::time::Duration;
+use thiserror::Error;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_REDIRECTS: usize = 5;
+
+/// What a fetched file is, decided by its Content-Type
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RemoteKind {
+    Media,
+    Sound,
+}
+
+/// A file downloaded from a direct link
+#[derive(Debug)]
+pub struct RemoteFile {
+    pub filename: String,
+    pub kind: RemoteKind,
+    pub data: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum FetchError {
+    #[error("Only http and https links are supported")]
+    InvalidUrl,
+    #[error("Could not download the link: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("The link returned HTTP {0}")]
+    Status(u16),
+    #[error("The link is not an image, video or sound (got {0})")]
+    UnsupportedType(String),
+    #[error("File too large! Maximum size is {}MB", .0 / (1024 * 1024))]
+    TooLarge(u64),
+}
+
+/// Content types accepted from direct links, with the extension the file is stored under
+pub fn extension_for(content_type: &str) -> Option<(&'static str, RemoteKind)> {
+    let mime = content_type.split(';').next()?.trim().to_lowercase();
+    Some(match mime.as_str() {
+        "image/jpeg" => ("jpg", RemoteKind::Media),
+        "image/png" => ("png", RemoteKind::Media),
+        "image/gif" => ("gif", RemoteKind::Media),
+        "image/webp" => ("webp", RemoteKind::Media),
+        "video/mp4" => ("mp4", RemoteKind::Media),
+        "video/webm" => ("webm", RemoteKind::Media),
+        "audio/mpeg" | "audio/mp3" => ("mp3", RemoteKind::Sound),
+        "audio/ogg" => ("ogg", RemoteKind::Sound),
+        "audio/wav" | "audio/x-wav" => ("wav", RemoteKind::Sound),
+        _ => return None,
+    })
+}
+
+/// Name to store a fetched file under: the last path segment of the URL with the given extension
+fn filename_for(url: &reqwest::Url, extension: &str) -> String {
+    let segment = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(|segment| percent_decode_str(segment).decode_utf8_lossy().to_string())
+        .unwrap_or_default();
+    let stem = segment
+        .rsplit_once('.')
+        .map(|(stem, _)| stem.to_string())
+        .unwrap_or(segment);
+    sanitize_filename(&format!("{}.{}", stem, extension))
+        .filter(|name| !name.starts_with('.'))
+        .unwrap_or_else(|| format!("download.{}", extension))
+}
+
+/// Download a direct image, video or sound link, refusing anything over `max_bytes`
+pub async fn fetch(url: &str, max_bytes: u64) -> Result<RemoteFile, FetchError> {
+    let url = reqwest::Url::parse(url.trim()).map_err(|_| FetchError::InvalidUrl)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(FetchError::InvalidUrl);
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+        .build()?;
+    let response = client.get(url.clone()).send().await?;
+    if !response.status().is_success() {
+        return Err(FetchError::Status(response.status().as_u16()));
+    }
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string();
+    let (extension, kind) =
+        extension_for(&content_type).ok_or(FetchError::UnsupportedType(content_type))?;
+
+    // Trust but verify: the declared length is checked up front, the body while streaming
+    if response.content_length().is_some_and(|length| length > max_bytes) {
+        return Err(FetchError::TooLarge(max_bytes));
+    }
+    let mut data = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        if (data.len() + chunk.len()) as u64 > max_bytes {
+            return Err(FetchError::TooLarge(max_bytes));
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let filename = filename_for(&url, extension);
+    tracing::info!("Fetched {} as {} ({} bytes)", url, filename, data.len());
+    Ok(RemoteFile {
+        filename,
+        kind,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_for() {
+        assert_eq!(extension_for("image/png"), Some(("png", RemoteKind::Media)));
+        assert_eq!(
+            extension_for("video/MP4; codecs=avc1"),
+            Some(("mp4", RemoteKind::Media))
+        );
+        assert_eq!(extension_for("audio/mpeg"), Some(("mp3", RemoteKind::Sound)));
+        assert_eq!(extension_for("text/html; charset=utf-8"), None);
+    }
+
+    #[test]
+    fn test_filename_for() {
+        let url = |url: &str| reqwest::Url::parse(url).unwrap();
+        assert_eq!(
+            filename_for(&url("https://i.imgur.com/AbC123.jpeg?x=1"), "jpg"),
+            "AbC123.jpg"
+        );
+        assert_eq!(
+            filename_for(&url("https://example.com/memes/so%20true.gif"), "gif"),
+            "so true.gif"
+        );
+        assert_eq!(filename_for(&url("https://example.com/"), "png"), "download.png");
+        assert_eq!(
+            filename_for(&url("https://example.com/..%2F..%2Fetc"), "mp4"),
+            "download.mp4"
+        );
+    }
+}
@@ -0,0 +1,350 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::process::{Output, Stdio};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::{Child, ChildStdout, Command as AsyncCommand};
+use tokio_util::sync::CancellationToken;
+
+/// stderr fragments of failures worth another attempt: network hiccups, rate limits, busy GPUs
+const TRANSIENT_ERRORS: &[&str] = &[
+    "HTTP Error 429",
+    "Too Many Requests",
+    "HTTP Error 500",
+    "HTTP Error 502",
+    "HTTP Error 503",
+    "HTTP Error 504",
+    "timed out",
+    "Connection reset",
+    "Connection refused",
+    "Network is unreachable",
+    "Temporary failure in name resolution",
+    "Resource temporarily unavailable",
+    "out of memory",
+];
+
+/// Per-command retry counters
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct RetryStats {
+    /// Invocations, counting all attempts of one call as one
+    pub calls: u64,
+    /// Extra attempts made after a transient failure
+    pub retries: u64,
+    /// Calls that still failed transiently after the last attempt
+    pub exhausted: u64,
+}
+
+static RETRY_STATS: LazyLock<Mutex<HashMap<&'static str, RetryStats>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Snapshot of the retry counters, keyed by command label
+pub fn retry_stats() -> HashMap<&'static str, RetryStats> {
+    RETRY_STATS
+        .lock()
+        .map(|stats| stats.clone())
+        .unwrap_or_default()
+}
+
+fn record(label: &'static str, update: impl FnOnce(&mut RetryStats)) {
+    if let Ok(mut stats) = RETRY_STATS.lock() {
+        update(stats.entry(label).or_default());
+    }
+}
+
+/// Whether a failed command's stderr looks like a transient failure
+pub fn is_transient_error(stderr: &str) -> bool {
+    TRANSIENT_ERRORS
+        .iter()
+        .any(|pattern| stderr.contains(pattern))
+}
+
+/// Command builder for `RetryPolicy::output`, running `program` with fixed arguments
+pub fn command<'a, S: AsRef<std::ffi::OsStr>>(
+    program: &'a str,
+    args: &'a [S],
+) -> impl FnMut() -> AsyncCommand + 'a {
+    move || {
+        let mut cmd = AsyncCommand::new(program);
+        cmd.args(args);
+        cmd
+    }
+}
+
+/// How often and how patiently to re-run a failing subprocess
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Classifies a failed attempt's stderr as retryable
+    pub retry_on: fn(&str) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            retry_on: is_transient_error,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Delay before the attempt following `attempt` (1-based), doubling each time
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Run the command built by `build` until it succeeds, fails for good or runs out of attempts
+    /// Returns the last attempt's output; spawn errors are returned right away
+    pub async fn output(
+        &self,
+        label: &'static str,
+        build: impl FnMut() -> AsyncCommand,
+    ) -> std::io::Result<Output> {
+        self.output_with_lines(label, build, |_| {}, None).await
+    }
+
+    /// Like `output`, killing the child and failing with an `Interrupted` error once `cancel` fires
+    pub async fn output_cancellable(
+        &self,
+        label: &'static str,
+        build: impl FnMut() -> AsyncCommand,
+        cancel: Option<&CancellationToken>,
+    ) -> std::io::Result<Output> {
+        self.output_with_lines(label, build, |_| {}, cancel).await
+    }
+
+    /// Like `output_cancellable`, also handing each stdout line to `on_line` as soon as it is printed
+    pub async fn output_with_lines(
+        &self,
+        label: &'static str,
+        mut build: impl FnMut() -> AsyncCommand,
+        mut on_line: impl FnMut(&str),
+        cancel: Option<&CancellationToken>,
+    ) -> std::io::Result<Output> {
+        record(label, |stats| stats.calls += 1);
+
+        let mut attempt = 1;
+        loop {
+            let output = run_streaming(build(), &mut on_line, cancel).await?;
+            if output.status.success() {
+                return Ok(output);
+            }
+
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if !(self.retry_on)(&stderr) {
+                return Ok(output);
+            }
+            if attempt >= self.max_attempts {
+                tracing::error!("{} still failing after {} attempts", label, attempt);
+                record(label, |stats| stats.exhausted += 1);
+                return Ok(output);
+            }
+
+            let delay = self.backoff(attempt);
+            tracing::warn!(
+                "{} failed transiently (attempt {}/{}), retrying in {:?}",
+                label,
+                attempt,
+                self.max_attempts,
+                delay
+            );
+            record(label, |stats| stats.retries += 1);
+            match cancel {
+                Some(cancel) => tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = cancel.cancelled() => return Err(cancelled_error()),
+                },
+                None => tokio::time::sleep(delay).await,
+            }
+            attempt += 1;
+        }
+    }
+}
+
+/// Error returned when a command is killed through its cancellation token
+pub fn cancelled_error() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Interrupted, "cancelled")
+}
+
+pub fn is_cancelled(error: &std::io::Error) -> bool {
+    error.kind() == std::io::ErrorKind::Interrupted
+}
+
+/// Run a command with piped output, streaming stdout line by line
+async fn run_streaming(
+    mut cmd: AsyncCommand,
+    on_line: &mut impl FnMut(&str),
+    cancel: Option<&CancellationToken>,
+) -> std::io::Result<Output> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    // Drain stderr concurrently so a chatty child can't block on a full pipe
+    let mut stderr_pipe = child.stderr.take();
+    let stderr_task = tokio::spawn(async move {
+        let mut stderr = Vec::new();
+        if let Some(pipe) = stderr_pipe.as_mut() {
+            let _ = pipe.read_to_end(&mut stderr).await;
+        }
+        stderr
+    });
+
+    let mut stdout = Vec::new();
+    let stdout_pipe = child.stdout.take();
+    let run = read_and_wait(&mut child, stdout_pipe, &mut stdout, on_line);
+    let status = match cancel {
+        Some(cancel) => tokio::select! {
+            status = run => Some(status),
+            _ = cancel.cancelled() => None,
+        },
+        None => Some(run.await),
+    };
+    let Some(status) = status else {
+        tracing::info!("Killing cancelled command");
+        let _ = child.kill().await;
+        stderr_task.abort();
+        return Err(cancelled_error());
+    };
+    let status = status?;
+
+    let stderr = stderr_task.await.unwrap_or_default();
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+async fn read_and_wait(
+    child: &mut Child,
+    stdout_pipe: Option<ChildStdout>,
+    stdout: &mut Vec<u8>,
+    on_line: &mut impl FnMut(&str),
+) -> std::io::Result<std::process::ExitStatus> {
+    if let Some(pipe) = stdout_pipe {
+        let mut lines = BufReader::new(pipe).lines();
+        while let Some(line) = lines.next_line().await? {
+            on_line(&line);
+            stdout.extend_from_slice(line.as_bytes());
+            stdout.push(b'\n');
+        }
+    }
+    child.wait().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(10), Duration::from_secs(30));
+        assert_eq!(policy.backoff(100), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_is_transient_error() {
+        assert!(is_transient_error(
+            "ERROR: unable to download video data: HTTP Error 429: Too Many Requests"
+        ));
+        assert!(is_transient_error("Read timed out."));
+        assert!(!is_transient_error(
+            "ERROR: Private video. Sign in if you've been granted access"
+        ));
+        assert!(!is_transient_error(""));
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let policy = RetryPolicy::default()
+            .max_attempts(3)
+            .initial_backoff(Duration::ZERO);
+        let command = |stderr: &'static str| {
+            move || {
+                let mut cmd = AsyncCommand::new("sh");
+                cmd.args(["-c", &format!("echo '{}' >&2; exit 1", stderr)]);
+                cmd
+            }
+        };
+
+        let output = policy
+            .output("test transient", command("HTTP Error 503"))
+            .await
+            .unwrap();
+        assert!(!output.status.success());
+        let stats = retry_stats()["test transient"];
+        assert_eq!((stats.calls, stats.retries, stats.exhausted), (1, 2, 1));
+
+        policy
+            .output("test permanent", command("Video unavailable"))
+            .await
+            .unwrap();
+        let stats = retry_stats()["test permanent"];
+        assert_eq!((stats.calls, stats.retries, stats.exhausted), (1, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_output_with_lines() {
+        let mut lines = Vec::new();
+        let output = RetryPolicy::default()
+            .output_with_lines(
+                "test lines",
+                || {
+                    let mut cmd = AsyncCommand::new("sh");
+                    cmd.args(["-c", "echo one; echo two; echo oops >&2"]);
+                    cmd
+                },
+                |line| lines.push(line.to_string()),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(lines, vec!["one", "two"]);
+        assert_eq!(output.stdout, b"one\ntwo\n");
+        assert_eq!(output.stderr, b"oops\n");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_kills_command() {
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            canceller.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        let result = RetryPolicy::default()
+            .output_cancellable("test cancel", command("sleep", &["10"]), Some(&cancel))
+            .await;
+        assert!(result.is_err_and(|e| is_cancelled(&e)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
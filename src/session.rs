@@ -0,0 +1,290 @@
+use crate::audit::{AuditEntry, AuditLog};
+use crate::handlers::bans::Banned;
+use crate::handlers::upload;
+use crate::i18n::{Locale, Msg};
+use rand::Rng;
+use std::net::{IpAddr, SocketAddr};
+use std::time::SystemTime;
+use warp::http::{HeaderValue, StatusCode, header::SET_COOKIE};
+use warp::{Filter, Rejection, Reply};
+
+pub const SESSION_COOKIE: &str = "homies_session";
+pub const CSRF_COOKIE: &str = "homies_csrf";
+/// Header htmx sends the page's CSRF token in, set with `hx-headers` in the templates
+pub const CSRF_HEADER: &str = "x-csrf-token";
+pub const ADMIN_HEADER: &str = "x-admin-token";
+const SESSION_ID_LEN: usize = 32;
+const SESSION_MAX_AGE_SECS: u64 = 365 * 24 * 60 * 60;
+
+/// Identifies a client for view tracking and upload ownership
+/// Prefers the session cookie and falls back to the IP address for cookieless clients
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ClientId {
+    Session(String),
+    Ip(IpAddr),
+}
+
+impl ClientId {
+    pub fn from_request(session: Option<String>, addr: Option<SocketAddr>) -> Option<Self> {
+        match session.filter(|id| is_valid_session_id(id)) {
+            Some(id) => Some(ClientId::Session(id)),
+            None => addr.map(|socket_addr| ClientId::Ip(socket_addr.ip())),
+        }
+    }
+}
+
+/// Extract the client id from the session cookie or remote address
+pub fn client_id()
+-> impl Filter<Extract = (Option<ClientId>,), Error = std::convert::Infallible> + Clone {
+    warp::cookie::optional::<String>(SESSION_COOKIE)
+        .and(warp::addr::remote())
+        .map(ClientId::from_request)
+}
+
+/// Extract the raw session cookie, if the client already has a valid one
+pub fn existing_session()
+-> impl Filter<Extract = (Option<String>,), Error = std::convert::Infallible> + Clone {
+    warp::cookie::optional::<String>(SESSION_COOKIE)
+        .map(|session: Option<String>| session.filter(|id| is_valid_session_id(id)))
+}
+
+pub fn generate_session_id() -> String {
+    format!("{:032x}", rand::thread_rng().r#gen::<u128>())
+}
+
+/// Attach a fresh session cookie to the reply unless the client already has one
+pub fn with_session_cookie(reply: impl Reply, existing: Option<String>) -> warp::reply::Response {
+    match existing {
+        Some(_) => reply.into_response(),
+        None => {
+            let session_id = generate_session_id();
+            tracing::info!("Issuing new session id");
+            warp::reply::with_header(
+                reply,
+                "set-cookie",
+                format!(
+                    "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+                    SESSION_COOKIE, session_id, SESSION_MAX_AGE_SECS
+                ),
+            )
+            .into_response()
+        }
+    }
+}
+
+/// CSRF token rendered into form pages, submitted back in a header and matched against its cookie
+/// Other sites can neither read the cookie nor set the header, so they can't forge form posts
+pub struct CsrfToken {
+    pub value: String,
+    issued: bool,
+}
+
+impl CsrfToken {
+    /// Reuse the client's token so several open tabs stay valid, or issue a new one
+    pub fn from_cookie(existing: Option<String>) -> Self {
+        match existing.filter(|token| is_valid_session_id(token)) {
+            Some(value) => Self {
+                value,
+                issued: false,
+            },
+            None => Self {
+                value: generate_session_id(),
+                issued: true,
+            },
+        }
+    }
+
+    /// Set the cookie on the reply if the token is new
+    pub fn attach(&self, reply: impl Reply) -> warp::reply::Response {
+        let mut response = reply.into_response();
+        if self.issued {
+            let cookie = format!(
+                "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Strict",
+                CSRF_COOKIE, self.value, SESSION_MAX_AGE_SECS
+            );
+            if let Ok(value) = HeaderValue::from_str(&cookie) {
+                // Appended, the session cookie may be set on the same reply
+                response.headers_mut().append(SET_COOKIE, value);
+            }
+        }
+        response
+    }
+}
+
+/// Extract the raw CSRF cookie for pages rendering a form
+pub fn csrf_cookie()
+-> impl Filter<Extract = (Option<String>,), Error = std::convert::Infallible> + Clone {
+    warp::cookie::optional::<String>(CSRF_COOKIE)
+}
+
+#[derive(Debug)]
+pub struct CsrfRejected {
+    accept_language: Option<String>, // For the message shown instead
+}
+
+impl warp::reject::Reject for CsrfRejected {}
+
+/// Reject form submissions whose CSRF header doesn't match the cookie
+/// Goes before body extraction so forged uploads are never read
+pub fn csrf_protected() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::cookie::optional::<String>(CSRF_COOKIE)
+        .and(warp::header::optional::<String>(CSRF_HEADER))
+        .and(warp::header::optional::<String>("accept-language"))
+        .and_then(
+            |cookie: Option<String>, header: Option<String>, accept_language| async move {
+                if csrf_tokens_match(cookie.as_deref(), header.as_deref()) {
+                    Ok(())
+                } else {
+                    tracing::warn!("Rejected form submission with a missing or wrong CSRF token");
+                    Err(warp::reject::custom(CsrfRejected { accept_language }))
+                }
+            },
+        )
+        .untuple_one()
+}
+
+#[derive(Debug)]
+pub struct AdminRejected {
+    accept_language: Option<String>,
+}
+
+impl warp::reject::Reject for AdminRejected {}
+
+/// Reject requests without the admin token in the `x-admin-token` header, recording every
+/// attempt in the audit log
+/// Without a configured token admin routes stay open, as on a trusted LAN
+pub fn admin_only(
+    token: Option<String>,
+    audit: AuditLog,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    // Who asked for what, e.g. "POST /banner"
+    let request = client_id().and(warp::method()).and(warp::path::full()).map(
+        |client: Option<ClientId>, method: warp::http::Method, path: warp::path::FullPath| {
+            (client, format!("{} {}", method, path.as_str()))
+        },
+    );
+    warp::header::optional::<String>(ADMIN_HEADER)
+        .and(warp::header::optional::<String>("accept-language"))
+        .and(request)
+        .and_then(move |header: Option<String>, accept_language, request| {
+            let (client, action): (Option<ClientId>, String) = request;
+            let (token, audit) = (token.clone(), audit.clone());
+            async move {
+                let allowed = admin_token_matches(token.as_deref(), header.as_deref());
+                let entry = AuditEntry::admin(client.as_ref(), action, allowed, SystemTime::now());
+                audit.record(entry);
+                if allowed {
+                    Ok(())
+                } else {
+                    tracing::warn!("Rejected admin request with a missing or wrong token");
+                    Err(warp::reject::custom(AdminRejected { accept_language }))
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Whether `given` is the admin token, anything goes when none is configured
+pub fn admin_token_matches(token: Option<&str>, given: Option<&str>) -> bool {
+    token.is_none_or(|token| given.is_some_and(|given| constant_time_eq(token, given)))
+}
+
+/// Turn CSRF, admin and ban rejections into a 403 the page can show and upload rejections into
+/// their reply, see [`upload::rejection_reply`], leaving other rejections alone
+/// Shown in the request's language, `default_locale` when it asked for none we have
+pub async fn handle_rejection(
+    rejection: Rejection,
+    default_locale: Locale,
+) -> Result<warp::reply::Response, Rejection> {
+    let forbidden = |accept_language: &Option<String>, message: Msg| {
+        let locale = Locale::negotiate(accept_language.as_deref(), default_locale);
+        warp::reply::with_status(
+            warp::reply::html(format!("<p>{}</p>", locale.t(message))),
+            StatusCode::FORBIDDEN,
+        )
+        .into_response()
+    };
+    if let Some(rejected) = rejection.find::<CsrfRejected>() {
+        return Ok(forbidden(&rejected.accept_language, Msg::FormExpired));
+    }
+    if let Some(rejected) = rejection.find::<AdminRejected>() {
+        return Ok(forbidden(&rejected.accept_language, Msg::AdminsOnly));
+    }
+    if let Some(banned) = rejection.find::<Banned>() {
+        let locale = Locale::negotiate(banned.accept_language.as_deref(), default_locale);
+        let message = match banned.minutes_left {
+            Some(minutes) => locale.format(Msg::BannedFor, &[&minutes]),
+            None => locale.t(Msg::Banned).to_string(),
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::html(format!("<p>{}</p>", message)),
+            StatusCode::FORBIDDEN,
+        )
+        .into_response());
+    }
+    upload::rejection_reply(&rejection).ok_or(rejection)
+}
+
+fn csrf_tokens_match(cookie: Option<&str>, header: Option<&str>) -> bool {
+    match (cookie, header) {
+        (Some(cookie), Some(header)) if is_valid_session_id(cookie) => {
+            constant_time_eq(cookie, header)
+        }
+        _ => false,
+    }
+}
+
+// Compare every byte so the time taken doesn't leak how much matched
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn is_valid_session_id(id: &str) -> bool {
+    id.len() == SESSION_ID_LEN && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_id_from_request() {
+        let addr: SocketAddr = "192.168.1.10:50000".parse().unwrap();
+        let session = generate_session_id();
+
+        assert_eq!(
+            ClientId::from_request(Some(session.clone()), Some(addr)),
+            Some(ClientId::Session(session))
+        );
+        // Malformed cookies fall back to the IP address
+        assert_eq!(
+            ClientId::from_request(Some("not-a-session".to_string()), Some(addr)),
+            Some(ClientId::Ip(addr.ip()))
+        );
+        assert_eq!(ClientId::from_request(None, None), None);
+    }
+
+    #[test]
+    fn test_csrf_tokens_match() {
+        let token = generate_session_id();
+        assert!(csrf_tokens_match(Some(&token), Some(&token)));
+        assert!(!csrf_tokens_match(Some(&token), Some(&generate_session_id())));
+        assert!(!csrf_tokens_match(Some(&token), None));
+        assert!(!csrf_tokens_match(None, Some(&token)));
+        // A forged cookie must still look like one we issued
+        assert!(!csrf_tokens_match(Some("x"), Some("x")));
+    }
+
+    #[test]
+    fn test_csrf_token_reused_from_cookie() {
+        let existing = generate_session_id();
+        let token = CsrfToken::from_cookie(Some(existing.clone()));
+        assert_eq!(token.value, existing);
+        assert!(!token.issued);
+        assert!(CsrfToken::from_cookie(Some("bogus".to_string())).issued);
+    }
+}
@@ -0,0 +1,109 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use thiserror::Error;
+
+/// Signed, time-limited links to archived media for people outside the LAN
+#[derive(Clone, Debug, Deserialize)]
+pub struct ShareConfig {
+    /// Key the tokens are signed with, changing it revokes every link handed out
+    pub secret: String,
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Public address the server is reachable at, e.g. `https://homies.example.com`, links
+    /// are relative when unset
+    pub base_url: Option<String>,
+}
+
+fn default_ttl_secs() -> u64 {
+    7 * 24 * 3600
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ShareError {
+    #[error("Malformed share token")]
+    Malformed,
+    #[error("Share token signature doesn't match")]
+    BadSignature,
+    #[error("Share link expired")]
+    Expired,
+}
+
+impl ShareConfig {
+    /// Token for `/share/{token}`: the archive id and expiry in Unix seconds, and their HMAC
+    pub fn token(&self, id: u64, expires_at: u64) -> String {
+        let payload = format!("{}.{}", id, expires_at);
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Link to hand out for a token
+    pub fn url(&self, token: &str) -> String {
+        let base = self.base_url.as_deref().unwrap_or("").trim_end_matches('/');
+        format!("{}/share/{}", base, token)
+    }
+
+    /// Archive id a token was issued for, checked against its signature and expiry
+    pub fn verify(&self, token: &str, now: u64) -> Result<u64, ShareError> {
+        let (payload, signature) = token.rsplit_once('.').ok_or(ShareError::Malformed)?;
+        let (id, expires_at) = payload.split_once('.').ok_or(ShareError::Malformed)?;
+        let id = id.parse::<u64>().map_err(|_| ShareError::Malformed)?;
+        let expires_at = expires_at.parse::<u64>().map_err(|_| ShareError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| ShareError::Malformed)?;
+        // Constant time, so the signature can't be guessed byte by byte
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| ShareError::BadSignature)?;
+        if expires_at <= now {
+            return Err(ShareError::Expired);
+        }
+        Ok(id)
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC takes keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn share(secret: &str) -> ShareConfig {
+        ShareConfig {
+            secret: secret.to_string(),
+            ttl_secs: default_ttl_secs(),
+            base_url: Some("https://homies.example.com/".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_share_tokens() {
+        let config = share("s3cret");
+        let token = config.token(7, 1_000);
+        assert!(token.starts_with("7.1000."));
+        assert_eq!(config.verify(&token, 999), Ok(7));
+        assert_eq!(config.verify(&token, 1_000), Err(ShareError::Expired));
+        assert_eq!(
+            share("other").verify(&token, 999),
+            Err(ShareError::BadSignature)
+        );
+        // A token can't be moved to another media or given a longer life
+        let forged = token.replacen("7.1000", "8.1000", 1);
+        assert_eq!(config.verify(&forged, 999), Err(ShareError::BadSignature));
+        let forged = token.replacen("7.1000", "7.9000", 1);
+        assert_eq!(config.verify(&forged, 999), Err(ShareError::BadSignature));
+        assert_eq!(config.verify("garbage", 999), Err(ShareError::Malformed));
+        assert_eq!(
+            config.url(&token),
+            format!("https://homies.example.com/share/{}", token)
+        );
+    }
+}
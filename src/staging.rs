@@ -0,0 +1,139 @@
+use crate::state::MediaInfo;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long staged uploads wait to be published before they are deleted
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct StagingConfig {
+    pub expiry_secs: u64,
+    /// Seconds between sweeps of the staged uploads past their expiry
+    pub sweep_interval_secs: u64,
+}
+
+impl Default for StagingConfig {
+    fn default() -> Self {
+        Self {
+            expiry_secs: 3600,
+            sweep_interval_secs: 60,
+        }
+    }
+}
+
+impl StagingConfig {
+    pub fn expiry(&self) -> Duration {
+        Duration::from_secs(self.expiry_secs)
+    }
+
+    pub fn sweep_interval(&self) -> Duration {
+        Duration::from_secs(self.sweep_interval_secs.max(1))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StagedEntry {
+    pub id: u64,
+    pub filename: String,
+    pub expires_at_ms: u64, // Unix milliseconds
+}
+
+/// Uploads stored and processed but not on screen yet, waiting for POST /publish/{id}
+#[derive(Clone, Debug)]
+pub struct Staging {
+    entries: Vec<(u64, MediaInfo, SystemTime)>,
+    next_id: u64,
+}
+
+impl Default for Staging {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl Staging {
+    /// Hold an upload until `expires_at`, returning its staging id
+    pub fn add(&mut self, media: MediaInfo, expires_at: SystemTime) -> StagedEntry {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push((id, media, expires_at));
+        self.entry(id).expect("just staged")
+    }
+
+    /// Remove a staged upload to publish it, none if unknown or expired
+    pub fn take(&mut self, id: u64, now: SystemTime) -> Option<MediaInfo> {
+        let index = self
+            .entries
+            .iter()
+            .position(|(staged, _, expires_at)| *staged == id && *expires_at > now)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    pub fn entry(&self, id: u64) -> Option<StagedEntry> {
+        self.entries
+            .iter()
+            .find(|(staged, _, _)| *staged == id)
+            .map(|(id, media, expires_at)| StagedEntry {
+                id: *id,
+                filename: media.filename.clone(),
+                expires_at_ms: expires_at
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64,
+            })
+    }
+
+    pub fn entries(&self) -> Vec<StagedEntry> {
+        self.entries
+            .iter()
+            .filter_map(|(id, _, _)| self.entry(*id))
+            .collect()
+    }
+
+    /// Remove the uploads past their expiry, returning their files to be deleted
+    pub fn take_expired(&mut self, now: SystemTime) -> Vec<String> {
+        let (expired, kept) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|(_, _, expires_at)| *expires_at <= now);
+        self.entries = kept;
+        expired
+            .into_iter()
+            .map(|(_, media, _)| media.filename)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::upload::create_media_info;
+    use crate::state::MediaType;
+
+    fn media(filename: &str) -> MediaInfo {
+        create_media_info(filename.to_string(), MediaType::Image, 0, String::new(), 5, None)
+    }
+
+    #[test]
+    fn test_staging_expiry() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut staging = Staging::default();
+        let first = staging.add(media("a.png"), now + Duration::from_secs(60));
+        let second = staging.add(media("b.mp4"), now + Duration::from_secs(120));
+        assert_eq!((first.id, second.id), (1, 2));
+        assert_eq!(first.expires_at_ms, 1_000_060_000);
+        assert_eq!(staging.entries().len(), 2);
+
+        assert!(staging.take(3, now).is_none());
+        assert!(staging.take(1, now + Duration::from_secs(60)).is_none());
+        assert_eq!(
+            staging.take_expired(now + Duration::from_secs(60)),
+            ["a.png"]
+        );
+        let published = staging.take(2, now).unwrap();
+        assert_eq!(published.filename, "b.mp4");
+        assert!(staging.take(2, now).is_none());
+        assert!(staging.entries().is_empty());
+    }
+}
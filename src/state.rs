@@ -1,74 +1,705 @@
-use std::collections::{HashMap, HashSet};
-use std::net::IpAddr;
+use serde::{Deserialize, Serialize};
+use crate::audit::{AuditEntry, AuditLog};
+use crate::banner::{Banner, BannerStatus};
+use crate::bans::BanList;
+use crate::chat::Chat;
+use crate::data_dirs::DataDirs;
+use crate::cleanup::Cleanup;
+use crate::clock::{SharedClock, SystemClock};
+use crate::displays::DisplayRegistry;
+use crate::dnd::{DndMode, DndState, QuietHours};
+use crate::drawing::Canvas;
+use crate::events::{Event, EventSender};
+use crate::game_status::GameStatus;
+use crate::leaderboard::Leaderboard;
+use crate::media_probe::MediaProbe;
+use crate::music::MusicState;
+use crate::now_playing::NowPlaying;
+use crate::playback::PlaybackEpoch;
+use crate::polls::Polls;
+use crate::session::ClientId;
+use crate::staging::Staging;
+use crate::stats::{self, StatsLog, StatsReport};
+use crate::theme::Theme;
+use crate::types::MediaItem;
+use crate::timers::Timers;
+use crate::trash::Trash;
+use crate::video_processing::{HwAccel, VideoPlatform};
+use crate::word_filter::WordFilter;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
 
 #[derive(Clone, Debug)]
 pub struct MediaInfo {
+    pub id: u64,
     pub filename: String,
     pub media_type: MediaType,
     pub upload_time: SystemTime,
     pub marked_for_deletion: bool,
     pub duration_secs: u64,
     pub caption: String,
+    pub play_secs: u64, // How long the media keeps the screen busy
+    pub uploader: Option<ClientId>,
+    pub probe: MediaProbe,
+    pub translation: Option<CaptionTranslation>,
+    pub burn_after_viewing: bool, // Deleted once the first display has shown it
+    pub lock: Option<String>,     // Passphrase hash, held until unlocked with it
+    pub poster: Option<String>,   // URL of a video's first frame
+    pub preview: Option<String>,  // URL of a video's animated preview, made in the background
+    pub low_rendition: Option<String>, // URL of a smaller copy of a video, for slow links
+    pub hls: Option<String>,           // URL of a long video's HLS playlist
+    pub expires_in_secs: Option<u64>,  // Retention after being shown, the default otherwise
+    pub source: MediaSource,
+    pub platform: Option<VideoPlatform>, // Site a downloaded video came from
 }
 
-#[derive(Clone, Debug)]
+/// Caption as typed by the uploader and as shown, when it was translated
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CaptionTranslation {
+    pub original: String,
+    pub translated: String,
+    pub language: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct SoundInfo {
+    pub id: u64,
     pub filename: String,
+    pub hotkey: Option<u8>, // Numeric key (0-9) that plays the sound from the soundboard
+    #[serde(skip)]
     pub upload_time: SystemTime,
+    #[serde(skip)]
     pub marked_for_deletion: bool,
 }
 
-#[derive(Clone, Debug, PartialEq, Copy)]
+#[derive(Clone, Debug, PartialEq, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum MediaType {
     Image,
     Video,
 }
 
+/// What to do with a new upload while the screen is still showing something
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisplayPolicy {
+    /// Replace whatever is playing right away
+    #[default]
+    Interrupt,
+    /// Wait in line until the current media is done
+    Queue,
+    /// Refuse the upload until the screen is free
+    RejectWhileBusy,
+}
+
+/// Where media came from, displays can take only some sources with `/ws?sources=`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MediaSource {
+    /// The upload form
+    #[default]
+    WebForm,
+    /// JSON replies and the gRPC PushMedia call, i.e. bots and scripts
+    Api,
+    /// Linked media, including YouTube and TikTok videos
+    UrlDownload,
+    /// Images pasted from the clipboard
+    Screenshot,
+    /// Visualizers of sounds uploaded to the soundboard
+    Soundboard,
+    Camera,
+    Imagine,
+    /// Media replayed from the archive, highlights included
+    Archive,
+}
+
+/// Outcome of submitting media for display
+#[derive(Clone, Debug, PartialEq)]
+pub enum Admission {
+    Shown,
+    Queued(usize),      // 1-based position in the queue
+    Held(usize),        // Queued until quiet hours end, 1-based position
+    Locked(usize),      // Held until unlocked with its passphrase, 1-based position
+    Rejected(Duration), // Time left on the current media
+}
+
+/// Where an upload currently stands, as reported to its uploader
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadStatus {
+    Queued,
+    Shown,
+    Expired,
+    Cancelled,
+    Archived,
+    Locked,
+}
+
+/// Media pinned to the archive, kept out of the cleanup and persisted across restarts
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub id: u64,
+    pub filename: String,
+    pub media_type: MediaType,
+    pub caption: String,
+    pub duration_secs: u64,
+    pub play_secs: u64,
+    pub pinned_at: u64, // Unix timestamp in seconds
+}
+
+impl From<&MediaInfo> for MediaItem {
+    fn from(media: &MediaInfo) -> Self {
+        Self {
+            id: media.id,
+            filename: media.filename.clone(),
+            media_type: media.media_type,
+            caption: media.caption.clone(),
+            play_secs: media.play_secs,
+            poster: media.poster.clone(),
+        }
+    }
+}
+
+/// What the screen should be showing right now, so a display joining mid-playback can catch up
+#[derive(Clone, Debug, Serialize)]
+pub struct DisplaySnapshot {
+    pub current: Option<MediaItem>,
+    pub elapsed_ms: u64,   // Since the current media went on screen
+    pub remaining_ms: u64, // Until the screen is free, 0 when idle
+    pub queue: Vec<MediaItem>,
+    pub quiet: bool,
+    pub banner: Option<BannerStatus>,
+}
+
+#[derive(Clone, Debug)]
+pub struct UploadRecord {
+    pub media: MediaInfo,
+    pub status: UploadStatus,
+}
+
+const HISTORY_LIMIT: usize = 100;
+
+/// An upload that went on screen and is still on disk
+#[derive(Clone, Debug)]
+struct StoredFile {
+    shown_at: SystemTime,
+    marked_for_deletion: bool, // Being burned, or the cleanup is retrying it
+    retention: Option<Duration>, // Set by the uploader, overrides the cleanup threshold
+}
+
+/// Hash a hold passphrase, so the passphrase itself is never kept
+pub fn passphrase_hash(passphrase: &str) -> String {
+    Sha256::digest(passphrase.trim().as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
 pub struct MediaViewState {
     last_media: Option<MediaInfo>,
-    last_sound: Option<SoundInfo>,               // Add this line
-    viewed_by: HashMap<String, HashSet<IpAddr>>, // filename -> set of IPs that viewed it
+    sounds: Vec<SoundInfo>,                        // Soundboard library, in upload order
+    next_sound_id: u64,
+    viewed_by: HashMap<String, HashSet<ClientId>>, // filename -> set of clients that viewed it
+    display_policy: DisplayPolicy,
+    shown_at: Option<SystemTime>,   // When last_media went on screen
+    busy_until: Option<SystemTime>, // When last_media is done playing
+    playback: Option<PlaybackEpoch>, // When last_media started playing, for videos
+    stored: HashMap<String, StoredFile>, // Shown uploads still on disk, replaced ones included
+    queue: VecDeque<MediaInfo>,
+    locked: Vec<MediaInfo>, // Held with a passphrase, in upload order
+    next_media_id: u64,
+    history: VecDeque<UploadRecord>, // Most recent uploads, oldest first
+    hw_accel: HwAccel,
+    data_dirs: DataDirs, // Where uploads, the trash and the archive are kept
+    archive: Vec<ArchiveEntry>, // Pinned media, in pin order
+    archive_index: Option<PathBuf>, // Where the archive is saved, unsaved when unset
+    dnd: DndState,
+    clock: SharedClock,
+    hits: HashMap<String, u64>, // filename -> times it was served
+    music: MusicState,
+    now_playing: Option<NowPlaying>,
+    game_status: Vec<GameStatus>, // Last poll of the configured game servers
+    event_sinks: Vec<EventSender>, // Webhook, MQTT and notifier tasks
+    audit: Option<AuditLog>,
+    stats: StatsLog,
+    leaderboard: Leaderboard,
+    leaderboard_file: Option<PathBuf>, // Where scores are saved, unsaved when unset
+    displays: DisplayRegistry,
+    displays_file: Option<PathBuf>, // Where registrations are saved, unsaved when unset
+    bans: BanList,
+    bans_file: Option<PathBuf>, // Where bans are saved, unsaved when unset
+    timers: Timers,
+    polls: Polls,
+    canvas: Canvas,
+    chat: Chat,
+    word_filter: WordFilter,
+    theme: Theme,
+    banner: Option<Banner>, // Pinned over whatever is on screen
+    banner_file: Option<PathBuf>, // Where the banner is saved, unsaved when unset
+    trash: Trash,
+    staging: Staging,
+    review: Staging, // Uploads the word filter holds for an admin's approval
+    cleanup: Cleanup,
+    changes: watch::Sender<u64>, // Version of the screen and queue, see subscribe_changes
+}
+
+impl Default for MediaViewState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MediaViewState {
     pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: SharedClock) -> Self {
         Self {
             last_media: None,
-            last_sound: None, // Initialize sound field
+            sounds: Vec::new(),
+            next_sound_id: 1,
             viewed_by: HashMap::new(),
+            display_policy: DisplayPolicy::default(),
+            shown_at: None,
+            busy_until: None,
+            playback: None,
+            stored: HashMap::new(),
+            queue: VecDeque::new(),
+            locked: Vec::new(),
+            next_media_id: 1,
+            history: VecDeque::new(),
+            hw_accel: HwAccel::default(),
+            data_dirs: DataDirs::default(),
+            archive: Vec::new(),
+            archive_index: None,
+            dnd: DndState::default(),
+            clock,
+            hits: HashMap::new(),
+            music: MusicState::default(),
+            now_playing: None,
+            game_status: Vec::new(),
+            event_sinks: Vec::new(),
+            audit: None,
+            stats: StatsLog::default(),
+            leaderboard: Leaderboard::default(),
+            leaderboard_file: None,
+            displays: DisplayRegistry::default(),
+            displays_file: None,
+            bans: BanList::default(),
+            bans_file: None,
+            timers: Timers::default(),
+            polls: Polls::default(),
+            canvas: Canvas::default(),
+            chat: Chat::default(),
+            word_filter: WordFilter::default(),
+            theme: Theme::default(),
+            banner: None,
+            banner_file: None,
+            trash: Trash::default(),
+            staging: Staging::default(),
+            review: Staging::default(),
+            cleanup: Cleanup::default(),
+            changes: watch::Sender::new(0),
         }
     }
 
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    /// Start of the video on screen, shared by every display so they play in lockstep
+    pub fn playback(&self) -> Option<PlaybackEpoch> {
+        self.playback
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        self.dnd.is_quiet(self.now())
+    }
+
+    /// Record uploads in the audit log from now on
+    pub fn set_audit_log(&mut self, audit: AuditLog) {
+        self.audit = Some(audit);
+    }
+
+    /// Queue events for a delivery task from now on
+    pub fn add_event_sink(&mut self, sink: EventSender) {
+        self.event_sinks.push(sink);
+    }
+
+    /// Hand an event to the webhook, MQTT and notifier tasks, if any
+    pub fn notify(&self, event: Event) {
+        for sink in &self.event_sinks {
+            if sink.send(event.clone()).is_err() {
+                tracing::warn!("Event task is gone, {} dropped", event.name());
+            }
+        }
+    }
+
+    pub fn set_display_policy(&mut self, policy: DisplayPolicy) {
+        tracing::info!("Display policy set to {:?}", policy);
+        self.display_policy = policy;
+    }
+
+    pub fn set_quiet_hours(&mut self, quiet_hours: Option<QuietHours>) {
+        tracing::info!("Quiet hours set to {:?}", quiet_hours);
+        self.dnd.quiet_hours = quiet_hours;
+    }
+
+    pub fn set_dnd_mode(&mut self, mode: DndMode) {
+        tracing::info!("Do not disturb set to {:?}", mode);
+        self.dnd.mode = mode;
+    }
+
+    pub fn dnd(&self) -> &DndState {
+        &self.dnd
+    }
+
+    pub fn music(&self) -> &MusicState {
+        &self.music
+    }
+
+    pub fn music_mut(&mut self) -> &mut MusicState {
+        &mut self.music
+    }
+
+    pub fn now_playing(&self) -> Option<&NowPlaying> {
+        self.now_playing.as_ref()
+    }
+
+    /// Returns whether the track changed
+    pub fn set_now_playing(&mut self, track: Option<NowPlaying>) -> bool {
+        if self.now_playing == track {
+            return false;
+        }
+        self.now_playing = track;
+        true
+    }
+
+    /// The pinned banner, none once it expired
+    pub fn banner(&self) -> Option<&Banner> {
+        self.banner
+            .as_ref()
+            .filter(|banner| !banner.is_expired(self.now()))
+    }
+
+    pub fn set_banner(&mut self, banner: Option<Banner>) {
+        self.banner = banner;
+    }
+
+    /// Save the banner to this file from now on
+    pub fn set_banner_file(&mut self, path: PathBuf) {
+        self.banner_file = Some(path);
+    }
+
+    pub fn banner_file(&self) -> Option<&PathBuf> {
+        self.banner_file.as_ref()
+    }
+
+    pub fn game_status(&self) -> &[GameStatus] {
+        &self.game_status
+    }
+
+    pub fn set_game_status(&mut self, status: Vec<GameStatus>) {
+        self.game_status = status;
+    }
+
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn set_hw_accel(&mut self, hw_accel: HwAccel) {
+        self.hw_accel = hw_accel;
+    }
+
+    pub fn hw_accel(&self) -> HwAccel {
+        self.hw_accel
+    }
+
+    pub fn set_data_dirs(&mut self, data_dirs: DataDirs) {
+        self.data_dirs = data_dirs;
+    }
+
+    pub fn data_dirs(&self) -> &DataDirs {
+        &self.data_dirs
+    }
+
     pub fn set_last_media(&mut self, media: MediaInfo) {
         tracing::info!("Setting last media: {} ({:?})", media.filename, media.media_type);
+        let now = self.now();
+        self.shown_at = Some(now);
+        self.busy_until = Some(now + Duration::from_secs(media.play_secs));
+        self.playback = (media.media_type == MediaType::Video)
+            .then(|| PlaybackEpoch::starting(media.id, now));
+        self.stored.insert(
+            media.filename.clone(),
+            StoredFile {
+                shown_at: now,
+                marked_for_deletion: false,
+                retention: media.expires_in_secs.map(Duration::from_secs),
+            },
+        );
         self.last_media = Some(media);
+        self.changed();
+    }
+
+    /// Watch the screen and the queue instead of polling them, the version goes up with every
+    /// change of the media on screen or in the queue
+    pub fn subscribe_changes(&self) -> watch::Receiver<u64> {
+        self.changes.subscribe()
+    }
+
+    fn changed(&self) {
+        self.changes.send_modify(|version| *version += 1);
+    }
+
+    /// Submit new media for display, honouring the display policy when the screen is busy
+    pub fn submit_media(&mut self, mut media: MediaInfo) -> Admission {
+        media.id = self.next_media_id;
+        media.upload_time = self.now();
+        self.next_media_id += 1;
+
+        // Locked media waits for its passphrase, whatever is on screen
+        if media.lock.is_some() {
+            tracing::info!("Holding media until unlocked: {}", media.filename);
+            self.record_upload(&media, UploadStatus::Locked);
+            self.locked.push(media);
+            return Admission::Locked(self.locked.len());
+        }
+
+        // Quiet hours accept everything, whatever the policy, and show it once they end
+        if self.is_quiet() {
+            tracing::info!("Quiet hours, holding media: {}", media.filename);
+            self.record_upload(&media, UploadStatus::Queued);
+            self.queue.push_back(media);
+            self.changed();
+            return Admission::Held(self.queue.len());
+        }
+
+        let remaining = match self.busy_remaining() {
+            Some(remaining) => remaining,
+            None => {
+                self.record_upload(&media, UploadStatus::Shown);
+                self.show(media);
+                return Admission::Shown;
+            }
+        };
+
+        match self.display_policy {
+            DisplayPolicy::Interrupt => {
+                self.record_upload(&media, UploadStatus::Shown);
+                self.show(media);
+                Admission::Shown
+            }
+            DisplayPolicy::Queue => {
+                tracing::info!("Screen busy, queueing media: {}", media.filename);
+                self.record_upload(&media, UploadStatus::Queued);
+                self.queue.push_back(media);
+                self.changed();
+                Admission::Queued(self.queue.len())
+            }
+            DisplayPolicy::RejectWhileBusy => {
+                tracing::info!("Screen busy, rejecting media: {}", media.filename);
+                Admission::Rejected(remaining)
+            }
+        }
+    }
+
+    /// Release the media held with a passphrase, showing the first if the screen is free and
+    /// queueing the rest, whatever the display policy
+    pub fn unlock(&mut self, passphrase: &str) -> Vec<(MediaInfo, Admission)> {
+        let hash = passphrase_hash(passphrase);
+        let (unlocked, locked) = std::mem::take(&mut self.locked)
+            .into_iter()
+            .partition(|media| media.lock.as_ref() == Some(&hash));
+        self.locked = locked;
+
+        let mut released = Vec::new();
+        for mut media in unlocked {
+            tracing::info!("Unlocked media: {}", media.filename);
+            media.lock = None;
+            let admission = if self.is_quiet() || self.busy_remaining().is_some() {
+                self.set_upload_status(media.id, UploadStatus::Queued);
+                self.queue.push_back(media.clone());
+                self.changed();
+                if self.is_quiet() {
+                    Admission::Held(self.queue.len())
+                } else {
+                    Admission::Queued(self.queue.len())
+                }
+            } else {
+                self.set_upload_status(media.id, UploadStatus::Shown);
+                self.show(media.clone());
+                Admission::Shown
+            };
+            released.push((media, admission));
+        }
+        released
+    }
+
+    /// Time left before the current media has finished playing, None if the screen is free
+    pub fn busy_remaining(&self) -> Option<Duration> {
+        self.busy_until?
+            .duration_since(self.now())
+            .ok()
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    pub fn display_snapshot(&self) -> DisplaySnapshot {
+        let current = self
+            .last_media
+            .as_ref()
+            .filter(|media| !media.marked_for_deletion);
+        let elapsed = current
+            .and(self.shown_at)
+            .and_then(|shown_at| self.now().duration_since(shown_at).ok())
+            .unwrap_or_default();
+        DisplaySnapshot {
+            current: current.map(MediaItem::from),
+            elapsed_ms: elapsed.as_millis() as u64,
+            remaining_ms: self.busy_remaining().unwrap_or_default().as_millis() as u64,
+            queue: self.queue.iter().map(MediaItem::from).collect(),
+            quiet: self.is_quiet(),
+            banner: self.banner().map(Banner::status),
+        }
+    }
+
+    /// Put the next queued media on screen once the current one is done and quiet hours are over
+    pub fn advance_queue(&mut self) -> Option<MediaInfo> {
+        if self.queue.is_empty() || self.busy_remaining().is_some() || self.is_quiet() {
+            return None;
+        }
+        let next = self.queue.pop_front()?;
+        tracing::info!(
+            "Advancing queue to {} after waiting {:?}",
+            next.filename,
+            self.now()
+                .duration_since(next.upload_time)
+                .unwrap_or_default()
+        );
+        self.set_upload_status(next.id, UploadStatus::Shown);
+        self.show(next.clone());
+        Some(next)
+    }
+
+    // Put media on screen, letting webhooks know
+    fn show(&mut self, media: MediaInfo) {
+        self.stats
+            .record_shown(self.now(), media.media_type, media.play_secs);
+        self.notify(Event::MediaShown {
+            media: (&media).into(),
+        });
+        self.set_last_media(media);
+    }
+
+    /// 1-based position of a media in the display queue
+    pub fn queue_position(&self, id: u64) -> Option<usize> {
+        self.queue
+            .iter()
+            .position(|media| media.id == id)
+            .map(|index| index + 1)
+    }
+
+    /// Remove a queued or locked media on behalf of its uploader, returning it so its file can
+    /// be deleted
+    pub fn cancel_queued(&mut self, id: u64, uploader: &ClientId) -> Option<MediaInfo> {
+        let owned = |media: &MediaInfo| media.id == id && media.uploader.as_ref() == Some(uploader);
+        let media = match self.queue.iter().position(owned) {
+            Some(index) => self.queue.remove(index)?,
+            None => {
+                let index = self.locked.iter().position(owned)?;
+                self.locked.remove(index)
+            }
+        };
+        tracing::info!("Cancelled queued media: {}", media.filename);
+        self.changed();
+        self.set_upload_status(id, UploadStatus::Cancelled);
+        Some(media)
     }
 
-    pub fn mark_viewed(&mut self, filename: &str, ip: IpAddr) -> bool {
+    /// Recent uploads of everyone, newest first
+    pub fn history(&self) -> impl Iterator<Item = &UploadRecord> {
+        self.history.iter().rev()
+    }
+
+    /// Recent uploads from the given uploader, newest first
+    pub fn uploads_for(&self, uploader: &ClientId) -> Vec<&UploadRecord> {
+        self.history
+            .iter()
+            .rev()
+            .filter(|record| record.media.uploader.as_ref() == Some(uploader))
+            .collect()
+    }
+
+    fn record_upload(&mut self, media: &MediaInfo, status: UploadStatus) {
+        self.stats.record_upload(self.now(), media.uploader.as_ref());
+        if let Some(uploader) = &media.uploader {
+            self.leaderboard
+                .record_upload(&stats::person(Some(uploader)));
+        }
+        self.notify(Event::MediaUploaded {
+            media: media.into(),
+        });
+        if let Some(audit) = &self.audit {
+            audit.record(AuditEntry::upload(media, self.now()));
+        }
+        if self.history.len() >= HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(UploadRecord {
+            media: media.clone(),
+            status,
+        });
+    }
+
+    /// Attach the animated preview of a video to every copy of it still around
+    pub fn set_preview(&mut self, id: u64, url: &str) {
+        let copies = self
+            .history
+            .iter_mut()
+            .map(|record| &mut record.media)
+            .chain(self.queue.iter_mut())
+            .chain(self.locked.iter_mut())
+            .chain(self.last_media.iter_mut());
+        for media in copies.filter(|media| media.id == id) {
+            media.preview = Some(url.to_string());
+        }
+    }
+
+    fn set_upload_status(&mut self, id: u64, status: UploadStatus) {
+        if let Some(record) = self.history.iter_mut().find(|record| record.media.id == id) {
+            record.status = status;
+        }
+    }
+
+    pub fn mark_viewed(&mut self, filename: &str, client: ClientId) -> bool {
         let viewed_set = self
             .viewed_by
             .entry(filename.to_string())
             .or_default(); // Use or_default() instead of or_insert_with(HashSet::new)
-        viewed_set.insert(ip)
-        // Returns true if IP was newly inserted (first view), false if already existed
+        viewed_set.insert(client)
+        // Returns true if client was newly inserted (first view), false if already existed
     }
 
     pub fn get_last_media(&self) -> Option<&MediaInfo> {
         self.last_media.as_ref()
     }
 
-    pub fn has_been_viewed(&self, filename: &str, ip: IpAddr) -> bool {
+    pub fn has_been_viewed(&self, filename: &str, client: &ClientId) -> bool {
         self.viewed_by
             .get(filename)
-            .map(|viewed_set| viewed_set.contains(&ip))
+            .map(|viewed_set| viewed_set.contains(client))
             .unwrap_or(false)
     }
 
-    pub fn get_last_media_for_ip(&self, ip: IpAddr) -> Option<&MediaInfo> {
+    pub fn get_last_media_for_client(&self, client: &ClientId) -> Option<&MediaInfo> {
         if let Some(media) = &self.last_media {
-            // If IP hasn't viewed this media yet and it's not marked for deletion, return it
-            if !self.has_been_viewed(&media.filename, ip) && !media.marked_for_deletion {
+            // If client hasn't viewed this media yet and it's not marked for deletion, return it
+            if !self.has_been_viewed(&media.filename, client) && !media.marked_for_deletion {
                 return Some(media);
             }
         }
@@ -77,26 +708,54 @@ impl MediaViewState {
 
     // Mark file for deletion
     pub fn mark_for_deletion(&mut self, filename: &str) {
-        if let Some(media) = &mut self.last_media {
-            if media.filename == filename {
-                media.marked_for_deletion = true;
-            }
+        if let Some(media) = &mut self.last_media
+            && media.filename == filename
+        {
+            media.marked_for_deletion = true;
+        }
+        if let Some(stored) = self.stored.get_mut(filename) {
+            stored.marked_for_deletion = true;
         }
     }
 
-    pub fn get_files_to_delete(&self, threshold: Duration) -> Vec<String> {
-        let now = SystemTime::now();
-        let mut files = Vec::new();
-
-        if let Some(media) = &self.last_media {
-            if let Ok(elapsed) = now.duration_since(media.upload_time) {
-                if elapsed > threshold && !media.marked_for_deletion {
-                    files.push(media.filename.clone());
-                }
-            }
+    /// Take a burn after viewing upload away from every other client, returning its filename
+    /// once so the caller deletes it; None for other media or once it's already burning
+    pub fn burn(&mut self, id: u64) -> Option<String> {
+        let archived = self
+            .last_media
+            .as_ref()
+            .is_some_and(|media| self.is_archived(&media.filename));
+        let media = self.last_media.as_mut().filter(|media| {
+            media.id == id && media.burn_after_viewing && !media.marked_for_deletion
+        })?;
+        if archived {
+            return None;
         }
+        let filename = media.filename.clone();
+        self.mark_for_deletion(&filename);
+        Some(filename)
+    }
 
-        files
+    /// Shown uploads whose time on disk is up, oldest first, whether still on screen or replaced
+    pub fn get_files_to_delete(&self, threshold: Duration) -> Vec<String> {
+        let now = self.now();
+        // Count from when the media went on screen so queued items get their full time
+        let mut expired: Vec<_> = self
+            .stored
+            .iter()
+            .filter(|(filename, stored)| {
+                !stored.marked_for_deletion
+                    && now
+                        .duration_since(stored.shown_at)
+                        .is_ok_and(|elapsed| elapsed > stored.retention.unwrap_or(threshold))
+                    && !self.is_archived(filename)
+            })
+            .collect();
+        expired.sort_by_key(|(_, stored)| stored.shown_at);
+        expired
+            .into_iter()
+            .map(|(filename, _)| filename.clone())
+            .collect()
     }
 
     // Check if a file exists in our state
@@ -108,29 +767,766 @@ impl MediaViewState {
         }
     }
 
-    pub fn set_last_sound(&mut self, sound: SoundInfo) {
-        self.last_sound = Some(sound);
+    /// Register a stored sound in the soundboard library, reusing the entry on re-upload
+    pub fn add_sound(&mut self, filename: &str) -> SoundInfo {
+        if let Some(sound) = self.sounds.iter().find(|sound| sound.filename == filename) {
+            return sound.clone();
+        }
+        let sound = SoundInfo {
+            id: self.next_sound_id,
+            filename: filename.to_string(),
+            hotkey: None,
+            upload_time: self.now(),
+            marked_for_deletion: false,
+        };
+        self.next_sound_id += 1;
+        self.sounds.push(sound.clone());
+        sound
+    }
+
+    pub fn sounds(&self) -> &[SoundInfo] {
+        &self.sounds
+    }
+
+    pub fn get_sound(&self, id: u64) -> Option<&SoundInfo> {
+        self.sounds.iter().find(|sound| sound.id == id)
+    }
+
+    pub fn get_sound_by_hotkey(&self, hotkey: u8) -> Option<&SoundInfo> {
+        self.sounds.iter().find(|sound| sound.hotkey == Some(hotkey))
+    }
+
+    /// Count a soundboard play for the stats, and for the leaderboard when the player is known
+    pub fn record_sound_play(&mut self, filename: &str, player: Option<&ClientId>) {
+        self.stats.record_sound_play(self.now(), filename);
+        if let Some(player) = player {
+            self.leaderboard
+                .record_sound_play(&stats::person(Some(player)));
+        }
+    }
+
+    /// Credit the uploader of what's on screen with a reaction, returning the media
+    /// Reacting to your own upload shows the reaction without scoring
+    pub fn react(&mut self, reactor: Option<&ClientId>) -> Option<&MediaInfo> {
+        let media = self.last_media.as_ref()?;
+        if let Some(uploader) = &media.uploader
+            && Some(uploader) != reactor
+        {
+            self.leaderboard
+                .record_reaction(&stats::person(Some(uploader)));
+        }
+        Some(media)
+    }
+
+    pub fn leaderboard(&self) -> &Leaderboard {
+        &self.leaderboard
+    }
+
+    pub fn leaderboard_mut(&mut self) -> &mut Leaderboard {
+        &mut self.leaderboard
+    }
+
+    /// Save scores to this file from now on
+    pub fn set_leaderboard_file(&mut self, path: PathBuf) {
+        self.leaderboard_file = Some(path);
+    }
+
+    pub fn leaderboard_file(&self) -> Option<&PathBuf> {
+        self.leaderboard_file.as_ref()
+    }
+
+    pub fn displays(&self) -> &DisplayRegistry {
+        &self.displays
+    }
+
+    pub fn displays_mut(&mut self) -> &mut DisplayRegistry {
+        &mut self.displays
+    }
+
+    /// Save display registrations to this file from now on
+    pub fn set_displays_file(&mut self, path: PathBuf) {
+        self.displays_file = Some(path);
+    }
+
+    pub fn displays_file(&self) -> Option<&PathBuf> {
+        self.displays_file.as_ref()
+    }
+
+    /// Clients kept from uploading and chatting
+    pub fn bans(&self) -> &BanList {
+        &self.bans
+    }
+
+    pub fn bans_mut(&mut self) -> &mut BanList {
+        &mut self.bans
+    }
+
+    /// Save bans to this file from now on
+    pub fn set_bans_file(&mut self, path: PathBuf) {
+        self.bans_file = Some(path);
+    }
+
+    pub fn bans_file(&self) -> Option<&PathBuf> {
+        self.bans_file.as_ref()
+    }
+
+    pub fn timers(&self) -> &Timers {
+        &self.timers
+    }
+
+    pub fn timers_mut(&mut self) -> &mut Timers {
+        &mut self.timers
+    }
+
+    pub fn polls(&self) -> &Polls {
+        &self.polls
+    }
+
+    pub fn polls_mut(&mut self) -> &mut Polls {
+        &mut self.polls
+    }
+
+    pub fn canvas(&self) -> &Canvas {
+        &self.canvas
+    }
+
+    pub fn canvas_mut(&mut self) -> &mut Canvas {
+        &mut self.canvas
+    }
+
+    pub fn chat(&self) -> &Chat {
+        &self.chat
+    }
+
+    pub fn chat_mut(&mut self) -> &mut Chat {
+        &mut self.chat
+    }
+
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        tracing::info!("Display theme set to {}", theme);
+        self.theme = theme;
+    }
+
+    /// Expired uploads that can still be restored
+    pub fn trash(&self) -> &Trash {
+        &self.trash
+    }
+
+    pub fn trash_mut(&mut self) -> &mut Trash {
+        &mut self.trash
+    }
+
+    /// Take an upload moved back out of the trash, its time on disk starts over from now
+    pub fn restore_from_trash(&mut self, filename: &str) {
+        self.trash.remove(filename);
+        self.stored.insert(
+            filename.to_string(),
+            StoredFile {
+                shown_at: self.now(),
+                marked_for_deletion: false,
+                retention: None,
+            },
+        );
+    }
+
+    /// Uploads stored and processed, waiting to be published
+    pub fn staging(&self) -> &Staging {
+        &self.staging
+    }
+
+    pub fn staging_mut(&mut self) -> &mut Staging {
+        &mut self.staging
+    }
+
+    /// Uploads waiting for an admin to approve their caption
+    pub fn review(&self) -> &Staging {
+        &self.review
+    }
+
+    pub fn review_mut(&mut self) -> &mut Staging {
+        &mut self.review
+    }
+
+    pub fn word_filter(&self) -> &WordFilter {
+        &self.word_filter
+    }
+
+    pub fn set_word_filter(&mut self, word_filter: WordFilter) {
+        self.word_filter = word_filter;
+    }
+
+    /// Files the cleanup failed on and its counters
+    pub fn cleanup(&self) -> &Cleanup {
+        &self.cleanup
+    }
+
+    pub fn cleanup_mut(&mut self) -> &mut Cleanup {
+        &mut self.cleanup
+    }
+
+    /// Uploads, sound plays and media shown during the `period` up to now
+    pub fn stats_report(&self, period: Duration) -> StatsReport {
+        self.stats.report(self.now(), period)
+    }
+
+    /// Bind a hotkey to a sound, taking it away from whichever sound had it before
+    pub fn set_sound_hotkey(&mut self, id: u64, hotkey: Option<u8>) -> Option<SoundInfo> {
+        self.get_sound(id)?;
+        for sound in self.sounds.iter_mut() {
+            if sound.id == id {
+                sound.hotkey = hotkey;
+            } else if hotkey.is_some() && sound.hotkey == hotkey {
+                sound.hotkey = None;
+            }
+        }
+        self.get_sound(id).cloned()
     }
 
     pub fn get_last_sound(&self) -> Option<&SoundInfo> {
-        self.last_sound.as_ref()
+        self.sounds.last()
+    }
+
+    /// Shown media that is still on disk and can be pinned
+    pub fn pinnable_media(&self, id: u64) -> Option<&MediaInfo> {
+        self.history
+            .iter()
+            .find(|record| record.media.id == id && record.status == UploadStatus::Shown)
+            .map(|record| &record.media)
+    }
+
+    /// Media still in uploads/: on screen, shown and not cleaned up yet, queued or locked
+    pub fn retained_media(&self, id: u64) -> Option<&MediaInfo> {
+        self.history
+            .iter()
+            .find(|record| {
+                record.media.id == id
+                    && matches!(
+                        record.status,
+                        UploadStatus::Shown | UploadStatus::Queued | UploadStatus::Locked
+                    )
+            })
+            .map(|record| &record.media)
+    }
+
+    /// Swap queued or locked media for a new version of it, keeping its id and place in line
+    pub fn replace_waiting(&mut self, id: u64, mut media: MediaInfo) -> Option<Admission> {
+        let quiet = self.is_quiet();
+        let (waiting, admission) = match self.queue.iter().position(|queued| queued.id == id) {
+            Some(index) if quiet => (&mut self.queue[index], Admission::Held(index + 1)),
+            Some(index) => (&mut self.queue[index], Admission::Queued(index + 1)),
+            None => {
+                let index = self.locked.iter().position(|locked| locked.id == id)?;
+                (&mut self.locked[index], Admission::Locked(index + 1))
+            }
+        };
+        media.id = id;
+        media.upload_time = waiting.upload_time;
+        *waiting = media.clone();
+        if let Some(record) = self.history.iter_mut().find(|record| record.media.id == id) {
+            record.media = media;
+        }
+        self.changed();
+        Some(admission)
+    }
+
+    /// Record media whose file was moved to the archive, reusing the entry if it was pinned already
+    pub fn archive_media(&mut self, media: &MediaInfo) -> ArchiveEntry {
+        self.set_upload_status(media.id, UploadStatus::Archived);
+        if let Some(entry) = self.archive.iter().find(|entry| entry.filename == media.filename) {
+            return entry.clone();
+        }
+        let entry = ArchiveEntry {
+            id: self.archive.iter().map(|entry| entry.id).max().unwrap_or(0) + 1,
+            filename: media.filename.clone(),
+            media_type: media.media_type,
+            caption: media.caption.clone(),
+            duration_secs: media.duration_secs,
+            play_secs: media.play_secs,
+            pinned_at: self
+                .now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        };
+        tracing::info!("Archived media: {}", entry.filename);
+        self.archive.push(entry.clone());
+        entry
+    }
+
+    /// Replace the archive with the one persisted on disk
+    pub fn load_archive(&mut self, entries: Vec<ArchiveEntry>) {
+        self.archive = entries;
+    }
+
+    pub fn archive(&self) -> &[ArchiveEntry] {
+        &self.archive
+    }
+
+    /// Save the archive to this file from now on
+    pub fn set_archive_index(&mut self, path: PathBuf) {
+        self.archive_index = Some(path);
+    }
+
+    pub fn archive_index(&self) -> Option<&PathBuf> {
+        self.archive_index.as_ref()
+    }
+
+    pub fn get_archived(&self, id: u64) -> Option<&ArchiveEntry> {
+        self.archive.iter().find(|entry| entry.id == id)
+    }
+
+    pub fn record_hit(&mut self, filename: &str) {
+        *self.hits.entry(filename.to_string()).or_default() += 1;
+    }
+
+    /// Times each stored file was served, most requested first
+    pub fn hits(&self) -> Vec<(String, u64)> {
+        let mut hits: Vec<_> = self
+            .hits
+            .iter()
+            .map(|(filename, count)| (filename.clone(), *count))
+            .collect();
+        hits.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hits
+    }
+
+    pub fn is_archived(&self, filename: &str) -> bool {
+        self.archive.iter().any(|entry| entry.filename == filename)
     }
 
     // Update remove_file_from_state to handle sounds:
     pub fn remove_file_from_state(&mut self, filename: &str) {
         // Remove from last_media if it matches
-        if let Some(media) = &self.last_media {
-            if media.filename == filename {
-                self.last_media = None;
-            }
+        if let Some(media) = &self.last_media
+            && media.filename == filename
+        {
+            self.last_media = None;
+            self.playback = None;
+            self.changed();
         }
-        // Remove from last_sound if it matches
-        if let Some(sound) = &self.last_sound {
-            if sound.filename == filename {
-                self.last_sound = None;
+        // Remove from the sound library if it matches
+        self.sounds.retain(|sound| sound.filename != filename);
+        self.music.remove_track(filename);
+        // Shown uploads whose file is gone are now expired
+        for record in self.history.iter_mut() {
+            if record.media.filename == filename && record.status == UploadStatus::Shown {
+                record.status = UploadStatus::Expired;
             }
         }
+        self.stored.remove(filename);
         // Remove from viewed_by tracking
         self.viewed_by.remove(filename);
+        self.hits.remove(filename);
+        self.notify(Event::MediaDeleted {
+            filename: filename.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn media(filename: &str, play_secs: u64) -> MediaInfo {
+        MediaInfo {
+            id: 0,
+            filename: filename.to_string(),
+            media_type: MediaType::Image,
+            upload_time: SystemTime::now(),
+            marked_for_deletion: false,
+            duration_secs: play_secs,
+            caption: String::new(),
+            play_secs,
+            uploader: Some(ClientId::Session("a".repeat(32))),
+            probe: MediaProbe::default(),
+            translation: None,
+            burn_after_viewing: false,
+            lock: None,
+            poster: None,
+            preview: None,
+            low_rendition: None,
+            hls: None,
+            expires_in_secs: None,
+            source: MediaSource::WebForm,
+            platform: None,
+        }
+    }
+
+    #[test]
+    fn test_submit_media_when_idle() {
+        let mut state = MediaViewState::new();
+        state.set_display_policy(DisplayPolicy::RejectWhileBusy);
+        assert_eq!(state.submit_media(media("a.png", 5)), Admission::Shown);
+        assert!(state.busy_remaining().is_some());
+    }
+
+    #[test]
+    fn test_quiet_hours_hold_media() {
+        let mut state = MediaViewState::new();
+        state.set_display_policy(DisplayPolicy::RejectWhileBusy);
+        state.set_dnd_mode(DndMode::On);
+        assert_eq!(state.submit_media(media("a.png", 30)), Admission::Held(1));
+        assert_eq!(state.submit_media(media("b.png", 30)), Admission::Held(2));
+        assert!(state.busy_remaining().is_none());
+        assert!(state.advance_queue().is_none());
+
+        // Held media goes on screen in order once quiet hours end
+        state.set_dnd_mode(DndMode::Off);
+        assert_eq!(state.advance_queue().unwrap().filename, "a.png");
+        assert_eq!(state.queue_len(), 1);
+    }
+
+    #[test]
+    fn test_display_snapshot() {
+        let mut state = MediaViewState::new();
+        let idle = state.display_snapshot();
+        assert!(idle.current.is_none());
+        assert_eq!(idle.remaining_ms, 0);
+
+        state.set_display_policy(DisplayPolicy::Queue);
+        state.submit_media(media("a.png", 30));
+        state.submit_media(media("b.png", 30));
+        let snapshot = state.display_snapshot();
+        assert_eq!(snapshot.current.unwrap().filename, "a.png");
+        assert!(snapshot.remaining_ms > 29_000 && snapshot.remaining_ms <= 30_000);
+        assert!(snapshot.elapsed_ms < 1_000);
+        assert_eq!(snapshot.queue.len(), 1);
+        assert_eq!(snapshot.queue[0].filename, "b.png");
+
+        // Cleaned up media is no longer on screen
+        state.mark_for_deletion("a.png");
+        assert!(state.display_snapshot().current.is_none());
+    }
+
+    #[test]
+    fn test_submit_media_interrupt() {
+        let mut state = MediaViewState::new();
+        state.submit_media(media("a.png", 30));
+        assert_eq!(state.submit_media(media("b.png", 30)), Admission::Shown);
+        assert_eq!(state.last_media.as_ref().unwrap().filename, "b.png");
+    }
+
+    #[test]
+    fn test_submit_media_queue() {
+        let mut state = MediaViewState::new();
+        state.set_display_policy(DisplayPolicy::Queue);
+        state.submit_media(media("a.png", 30));
+        assert_eq!(state.submit_media(media("b.png", 30)), Admission::Queued(1));
+        assert_eq!(state.submit_media(media("c.png", 30)), Admission::Queued(2));
+
+        // Still busy, nothing advances even once the file itself is cleaned up
+        state.remove_file_from_state("a.png");
+        assert!(state.advance_queue().is_none());
+
+        // Once the current media is done the next one takes over
+        state.busy_until = Some(SystemTime::now());
+        assert_eq!(state.advance_queue().unwrap().filename, "b.png");
+        assert_eq!(state.last_media.as_ref().unwrap().filename, "b.png");
+    }
+
+    #[test]
+    fn test_changes_are_watched() {
+        let mut state = MediaViewState::new();
+        state.set_display_policy(DisplayPolicy::Queue);
+        let changes = state.subscribe_changes();
+        state.submit_media(media("a.png", 30));
+        assert!(changes.has_changed().unwrap());
+        assert_eq!(*changes.borrow(), 1);
+
+        // Queued, then cancelled by its uploader
+        let mut queued = media("b.png", 30);
+        queued.uploader = Some(ClientId::Ip([10, 0, 0, 1].into()));
+        state.submit_media(queued.clone());
+        let id = state.queue[0].id;
+        state.cancel_queued(id, queued.uploader.as_ref().unwrap());
+        assert_eq!(*changes.borrow(), 3);
+
+        // Views don't change the screen
+        state.mark_viewed("a.png", ClientId::Ip([10, 0, 0, 2].into()));
+        assert_eq!(*changes.borrow(), 3);
+        state.remove_file_from_state("a.png");
+        assert_eq!(*changes.borrow(), 4);
+    }
+
+    #[test]
+    fn test_replace_waiting_keeps_the_place_in_line() {
+        let mut state = MediaViewState::new();
+        state.set_display_policy(DisplayPolicy::Queue);
+        state.submit_media(media("a.mp4", 30));
+        state.submit_media(media("b.mp4", 30));
+        state.submit_media(media("c.mp4", 30));
+        let id = state.queue[0].id;
+
+        let admission = state.replace_waiting(id, media("b_trimmed.mp4", 10));
+        assert_eq!(admission, Some(Admission::Queued(1)));
+        assert_eq!(state.queue[0].filename, "b_trimmed.mp4");
+        assert_eq!(state.queue[0].id, id);
+        assert_eq!(state.retained_media(id).unwrap().filename, "b_trimmed.mp4");
+        // What's on screen isn't waiting
+        let shown = state.last_media.as_ref().unwrap().id;
+        assert_eq!(state.replace_waiting(shown, media("a_trimmed.mp4", 10)), None);
+    }
+
+    #[test]
+    fn test_clock_drives_retention_and_queue() {
+        let clock = ManualClock::default();
+        let mut state = MediaViewState::with_clock(Arc::new(clock.clone()));
+        state.set_display_policy(DisplayPolicy::Queue);
+        state.submit_media(media("a.png", 30));
+        state.submit_media(media("b.png", 30));
+        assert!(state.get_files_to_delete(Duration::from_secs(10)).is_empty());
+
+        clock.advance(Duration::from_secs(11));
+        assert_eq!(state.get_files_to_delete(Duration::from_secs(10)), vec!["a.png"]);
+        assert_eq!(state.display_snapshot().elapsed_ms, 11_000);
+        assert!(state.advance_queue().is_none());
+
+        clock.advance(Duration::from_secs(20));
+        let next = state.advance_queue().unwrap();
+        assert_eq!(next.filename, "b.png");
+        assert_eq!(state.display_snapshot().remaining_ms, 30_000);
+    }
+
+    #[test]
+    fn test_videos_get_a_playback_epoch() {
+        let clock = ManualClock::default();
+        let mut state = MediaViewState::with_clock(Arc::new(clock.clone()));
+        state.set_display_policy(DisplayPolicy::Queue);
+        let video = MediaInfo {
+            media_type: MediaType::Video,
+            ..media("a.mp4", 30)
+        };
+        state.submit_media(video);
+        state.submit_media(media("b.png", 5));
+        let epoch = state.playback().unwrap();
+        assert_eq!(epoch.media_id, 1);
+        assert_eq!(
+            epoch.start_at_ms,
+            crate::playback::unix_ms(state.now() + crate::playback::START_LEAD)
+        );
+
+        // Stills have nothing to line up
+        clock.advance(Duration::from_secs(30));
+        state.advance_queue().unwrap();
+        assert_eq!(state.playback(), None);
+    }
+
+    #[test]
+    fn test_replaced_media_expires_too() {
+        let clock = ManualClock::default();
+        let mut state = MediaViewState::with_clock(Arc::new(clock.clone()));
+        state.submit_media(media("a.png", 30));
+        clock.advance(Duration::from_secs(5));
+        state.submit_media(media("b.png", 30));
+
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(state.get_files_to_delete(Duration::from_secs(10)), ["a.png"]);
+        state.mark_for_deletion("a.png");
+        assert!(state.get_files_to_delete(Duration::from_secs(10)).is_empty());
+
+        clock.advance(Duration::from_secs(5));
+        state.remove_file_from_state("a.png");
+        assert_eq!(state.get_files_to_delete(Duration::from_secs(10)), ["b.png"]);
+    }
+
+    #[test]
+    fn test_expires_in_overrides_the_threshold() {
+        let clock = ManualClock::default();
+        let mut state = MediaViewState::with_clock(Arc::new(clock.clone()));
+        let mut announcement = media("a.png", 5);
+        announcement.expires_in_secs = Some(600);
+        state.set_last_media(announcement);
+        clock.advance(Duration::from_secs(1));
+        let mut meme = media("b.png", 5);
+        meme.expires_in_secs = Some(5);
+        state.set_last_media(meme);
+
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(state.get_files_to_delete(Duration::from_secs(10)), ["b.png"]);
+        clock.advance(Duration::from_secs(594));
+        assert_eq!(
+            state.get_files_to_delete(Duration::from_secs(10)),
+            ["a.png", "b.png"]
+        );
+    }
+
+    #[test]
+    fn test_submit_media_reject_while_busy() {
+        let mut state = MediaViewState::new();
+        state.set_display_policy(DisplayPolicy::RejectWhileBusy);
+        state.submit_media(media("a.png", 30));
+        match state.submit_media(media("b.png", 30)) {
+            Admission::Rejected(remaining) => assert!(remaining <= Duration::from_secs(30)),
+            other => panic!("Expected rejection, got {:?}", other),
+        }
+        assert_eq!(state.last_media.as_ref().unwrap().filename, "a.png");
+    }
+
+    #[test]
+    fn test_events() {
+        let mut state = MediaViewState::new();
+        let (sender, mut events) = tokio::sync::mpsc::unbounded_channel();
+        state.add_event_sink(sender);
+        state.set_display_policy(DisplayPolicy::Queue);
+        state.submit_media(media("a.png", 30));
+        state.submit_media(media("b.png", 30));
+        state.remove_file_from_state("a.png");
+        state.busy_until = Some(SystemTime::now());
+        state.advance_queue();
+
+        let mut names = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            names.push(event.name());
+        }
+        assert_eq!(
+            names,
+            [
+                "media_uploaded",
+                "media_shown",
+                "media_uploaded",
+                "media_deleted",
+                "media_shown"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_burn_after_viewing() {
+        let viewer = ClientId::Ip([192, 168, 1, 10].into());
+        let other = ClientId::Ip([192, 168, 1, 11].into());
+        let mut state = MediaViewState::new();
+        let mut secret = media("secret.png", 5);
+        secret.burn_after_viewing = true;
+        state.submit_media(secret);
+        let id = state.last_media.as_ref().unwrap().id;
+
+        assert!(state.get_last_media_for_client(&viewer).is_some());
+        assert_eq!(state.burn(id).as_deref(), Some("secret.png"));
+        // Burned once, and gone for everyone else
+        assert!(state.burn(id).is_none());
+        assert!(state.get_last_media_for_client(&other).is_none());
+        assert!(state.get_files_to_delete(Duration::ZERO).is_empty());
+
+        state.submit_media(media("plain.png", 5));
+        let id = state.last_media.as_ref().unwrap().id;
+        assert!(state.burn(id).is_none());
+    }
+
+    #[test]
+    fn test_unlock() {
+        let uploader = ClientId::Session("a".repeat(32));
+        let mut state = MediaViewState::new();
+        state.set_display_policy(DisplayPolicy::RejectWhileBusy);
+        let mut held = media("home.png", 30);
+        held.lock = Some(passphrase_hash("home sweet home"));
+        assert_eq!(state.submit_media(held.clone()), Admission::Locked(1));
+        assert_eq!(state.submit_media(held.clone()), Admission::Locked(2));
+        held.lock = Some(passphrase_hash("other"));
+        assert_eq!(state.submit_media(held), Admission::Locked(3));
+        assert!(state.last_media.is_none());
+        assert_eq!(state.uploads_for(&uploader)[0].status, UploadStatus::Locked);
+
+        assert!(state.unlock("wrong").is_empty());
+        let admissions: Vec<_> = state
+            .unlock(" home sweet home ")
+            .into_iter()
+            .map(|(media, admission)| (media.id, admission))
+            .collect();
+        // Unlocked media queues even when the policy would reject it
+        assert_eq!(admissions, [(1, Admission::Shown), (2, Admission::Queued(1))]);
+        assert!(state.last_media.as_ref().unwrap().lock.is_none());
+        assert!(state.unlock("home sweet home").is_empty());
+
+        // The uploader can still drop the one left locked
+        assert!(state.cancel_queued(3, &uploader).is_some());
+        assert!(state.unlock("other").is_empty());
+    }
+
+    #[test]
+    fn test_upload_history() {
+        let uploader = ClientId::Session("a".repeat(32));
+        let other = ClientId::Ip([192, 168, 1, 10].into());
+        let mut state = MediaViewState::new();
+        state.set_display_policy(DisplayPolicy::Queue);
+        state.submit_media(media("a.png", 30));
+        state.submit_media(media("b.png", 30));
+
+        let uploads = state.uploads_for(&uploader);
+        assert_eq!(uploads.len(), 2);
+        assert_eq!(uploads[0].media.filename, "b.png");
+        assert_eq!(uploads[0].status, UploadStatus::Queued);
+        assert_eq!(uploads[1].status, UploadStatus::Shown);
+        assert!(state.uploads_for(&other).is_empty());
+
+        // Only the uploader can cancel, and only while queued
+        let queued_id = uploads[0].media.id;
+        assert!(state.cancel_queued(queued_id, &other).is_none());
+        assert!(state.cancel_queued(queued_id, &uploader).is_some());
+        assert_eq!(state.uploads_for(&uploader)[0].status, UploadStatus::Cancelled);
+        assert!(state.queue_position(queued_id).is_none());
+
+        state.remove_file_from_state("a.png");
+        assert_eq!(state.uploads_for(&uploader)[1].status, UploadStatus::Expired);
+    }
+
+    #[test]
+    fn test_preview_reaches_every_copy() {
+        let uploader = ClientId::Session("a".repeat(32));
+        let mut state = MediaViewState::new();
+        state.set_display_policy(DisplayPolicy::Queue);
+        state.submit_media(media("a.mp4", 30));
+        state.submit_media(media("b.mp4", 30));
+        let queued_id = state.uploads_for(&uploader)[0].media.id;
+
+        state.set_preview(queued_id, "/uploads/previews/b.webp");
+        let preview = Some("/uploads/previews/b.webp".to_string());
+        assert_eq!(state.uploads_for(&uploader)[0].media.preview, preview);
+        assert_eq!(state.queue[0].preview, preview);
+        assert_eq!(state.last_media.as_ref().unwrap().preview, None);
+    }
+
+    #[test]
+    fn test_sound_hotkeys() {
+        let mut state = MediaViewState::new();
+        let airhorn = state.add_sound("airhorn.mp3");
+        let bruh = state.add_sound("bruh.mp3");
+        assert_eq!(state.add_sound("airhorn.mp3").id, airhorn.id);
+
+        state.set_sound_hotkey(airhorn.id, Some(1));
+        assert_eq!(state.get_sound_by_hotkey(1).unwrap().filename, "airhorn.mp3");
+
+        // Rebinding a hotkey moves it to the new sound
+        state.set_sound_hotkey(bruh.id, Some(1));
+        assert_eq!(state.get_sound_by_hotkey(1).unwrap().filename, "bruh.mp3");
+        assert_eq!(state.get_sound(airhorn.id).unwrap().hotkey, None);
+
+        assert!(state.set_sound_hotkey(42, Some(2)).is_none());
+    }
+
+    #[test]
+    fn test_archive_media() {
+        let mut state = MediaViewState::new();
+        state.set_display_policy(DisplayPolicy::Queue);
+        state.submit_media(media("a.png", 30));
+        state.submit_media(media("b.png", 30));
+
+        // Only media that went on screen can be pinned
+        assert!(state.pinnable_media(2).is_none());
+        let shown = state.pinnable_media(1).unwrap().clone();
+        let entry = state.archive_media(&shown);
+        assert_eq!(entry.id, 1);
+        assert_eq!(state.archive_media(&shown).id, entry.id);
+        assert!(state.pinnable_media(1).is_none());
+
+        // The reaper leaves archived files alone
+        state.stored.get_mut("a.png").unwrap().shown_at =
+            SystemTime::now() - Duration::from_secs(60);
+        assert!(state.get_files_to_delete(Duration::from_secs(10)).is_empty());
+        assert_eq!(state.get_archived(1).unwrap().filename, "a.png");
     }
 }
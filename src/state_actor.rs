@@ -0,0 +1,80 @@
+use crate::state::MediaViewState;
+use std::panic::AssertUnwindSafe;
+use tokio::sync::{mpsc, oneshot};
+
+// Commands waiting for the actor, callers wait for room beyond this
+const COMMAND_BUFFER: usize = 256;
+
+type Command = Box<dyn FnOnce(&mut MediaViewState) + Send>;
+
+/// Handle to the task owning the media state
+/// Every access is a command run to completion on that task, in arrival order, so no request
+/// can hold the state across an await and stall the others the way a lock guard could
+#[derive(Clone)]
+pub struct StateHandle {
+    commands: mpsc::Sender<Command>,
+}
+
+impl StateHandle {
+    /// Move the state into its own task
+    pub fn spawn(mut state: MediaViewState) -> Self {
+        let (commands, mut receiver) = mpsc::channel::<Command>(COMMAND_BUFFER);
+        tokio::spawn(async move {
+            while let Some(command) = receiver.recv().await {
+                // A panicking command fails its own caller, the state stays up for the rest
+                if std::panic::catch_unwind(AssertUnwindSafe(|| command(&mut state))).is_err() {
+                    tracing::error!("State command panicked");
+                }
+            }
+            tracing::info!("State actor stopped");
+        });
+        Self { commands }
+    }
+
+    /// Run `f` against the state on the actor task and wait for its result
+    pub async fn call<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut MediaViewState) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        let command: Command = Box::new(move |state| {
+            let _ = reply.send(f(state));
+        });
+        if self.commands.send(command).await.is_err() {
+            panic!("State actor stopped");
+        }
+        result.await.expect("State command failed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_commands_run_in_order_and_survive_panics() {
+        let state = StateHandle::spawn(MediaViewState::new());
+        let adds: Vec<_> = (0..10)
+            .map(|index| {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    state
+                        .call(move |state| state.add_sound(&format!("{}.mp3", index)).id)
+                        .await
+                })
+            })
+            .collect();
+        for add in adds {
+            add.await.unwrap();
+        }
+        assert_eq!(state.call(|state| state.sounds().len()).await, 10);
+
+        let failed = tokio::spawn({
+            let state = state.clone();
+            async move { state.call(|_| panic!("boom")).await }
+        });
+        assert!(failed.await.is_err());
+        assert_eq!(state.call(|state| state.sounds().len()).await, 10);
+    }
+}
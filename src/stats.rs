@@ -0,0 +1,302 @@
+use crate::session::ClientId;
+use crate::state::MediaType;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Local, TimeZone, Timelike, Weekday};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Activity older than this is dropped, no report looks further back
+const RETENTION: Duration = Duration::from_secs(31 * 24 * 3600);
+
+// Salt of the names people go by, set once from the config
+static PERSON_SALT: OnceLock<String> = OnceLock::new();
+
+/// Weekly recap of the stats, put on screen on schedule
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RecapSchedule {
+    pub enabled: bool,
+    /// Day of the week, e.g. "sunday" or "sun"
+    pub day: String,
+    /// Local hour, 0 to 23
+    pub hour: u32,
+    /// Days covered by the recap
+    pub days: u64,
+}
+
+impl Default for RecapSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            day: "sunday".to_string(),
+            hour: 19,
+            days: 7,
+        }
+    }
+}
+
+impl RecapSchedule {
+    pub fn weekday(&self) -> Weekday {
+        self.day.parse().unwrap_or(Weekday::Sun)
+    }
+
+    /// Time from `now` until the next recap is due
+    pub fn next_delay<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Duration {
+        let days_ahead =
+            (7 + self.weekday().num_days_from_monday() - now.weekday().num_days_from_monday()) % 7;
+        let day = now.clone() + ChronoDuration::days(days_ahead as i64);
+        let mut due = day
+            .with_hour(self.hour.min(23))
+            .and_then(|due| due.with_minute(0))
+            .and_then(|due| due.with_second(0))
+            .and_then(|due| due.with_nanosecond(0))
+            .unwrap_or(day);
+        if due <= *now {
+            due += ChronoDuration::days(7);
+        }
+        (due - now.clone()).to_std().unwrap_or_default()
+    }
+
+    pub fn next_delay_local(&self) -> Duration {
+        self.next_delay(&Local::now())
+    }
+}
+
+fn random_salt() -> String {
+    format!("{:032x}", rand::thread_rng().r#gen::<u128>())
+}
+
+/// Salt the names of `person`, a random salt is used when unset; the first call wins
+pub fn init_person_salt(salt: Option<&str>) {
+    let _ = PERSON_SALT.set(salt.map(str::to_string).unwrap_or_else(random_salt));
+}
+
+/// Who uploaded, as shown in reports: a salted hash of the session or IP address, so neither
+/// can be read back from it
+pub fn person(uploader: Option<&ClientId>) -> String {
+    let key = match uploader {
+        Some(ClientId::Session(id)) => format!("session {}", id),
+        Some(ClientId::Ip(ip)) => format!("ip {}", ip),
+        None => return "unknown".to_string(),
+    };
+    let salt = PERSON_SALT.get_or_init(random_salt);
+    let digest = Sha256::digest(format!("{}:{}", salt, key).as_bytes());
+    let hash: String = digest[..4].iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("homie {}", hash)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PersonUploads {
+    pub person: String,
+    pub uploads: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SoundPlays {
+    pub filename: String,
+    pub plays: u64,
+}
+
+/// Activity between two times, Unix seconds
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StatsReport {
+    pub since: u64,
+    pub until: u64,
+    pub uploads: u64,
+    /// Most uploads first
+    pub uploads_by_person: Vec<PersonUploads>,
+    pub top_sound: Option<SoundPlays>,
+    pub sound_plays: u64,
+    pub images_shown: u64,
+    pub videos_shown: u64,
+    pub video_minutes: f64,
+}
+
+impl StatsReport {
+    /// Video time displayed, e.g. "1h 05m"
+    pub fn video_time(&self) -> String {
+        let minutes = self.video_minutes.round() as u64;
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// Timestamped uploads, sound plays and displayed media, kept for a month
+#[derive(Clone, Debug, Default)]
+pub struct StatsLog {
+    uploads: Vec<(SystemTime, String)>,       // Uploader
+    sound_plays: Vec<(SystemTime, String)>,   // Sound filename
+    shown: Vec<(SystemTime, MediaType, u64)>, // Seconds on screen
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl StatsLog {
+    pub fn record_upload(&mut self, at: SystemTime, uploader: Option<&ClientId>) {
+        self.uploads.push((at, person(uploader)));
+        self.prune(at);
+    }
+
+    pub fn record_sound_play(&mut self, at: SystemTime, filename: &str) {
+        self.sound_plays.push((at, filename.to_string()));
+        self.prune(at);
+    }
+
+    pub fn record_shown(&mut self, at: SystemTime, media_type: MediaType, play_secs: u64) {
+        self.shown.push((at, media_type, play_secs));
+        self.prune(at);
+    }
+
+    fn prune(&mut self, now: SystemTime) {
+        let Some(cutoff) = now.checked_sub(RETENTION) else {
+            return;
+        };
+        self.uploads.retain(|(at, _)| *at >= cutoff);
+        self.sound_plays.retain(|(at, _)| *at >= cutoff);
+        self.shown.retain(|(at, _, _)| *at >= cutoff);
+    }
+
+    /// Activity during the `period` before `now`
+    pub fn report(&self, now: SystemTime, period: Duration) -> StatsReport {
+        let since = now.checked_sub(period).unwrap_or(UNIX_EPOCH);
+        let in_period = |at: &SystemTime| *at >= since && *at <= now;
+
+        let mut by_person: HashMap<&str, u64> = HashMap::new();
+        for (_, person) in self.uploads.iter().filter(|(at, _)| in_period(at)) {
+            *by_person.entry(person).or_default() += 1;
+        }
+        let mut uploads_by_person: Vec<_> = by_person
+            .into_iter()
+            .map(|(person, uploads)| PersonUploads {
+                person: person.to_string(),
+                uploads,
+            })
+            .collect();
+        uploads_by_person.sort_by(|a, b| b.uploads.cmp(&a.uploads).then(a.person.cmp(&b.person)));
+
+        let mut by_sound: HashMap<&str, u64> = HashMap::new();
+        for (_, filename) in self.sound_plays.iter().filter(|(at, _)| in_period(at)) {
+            *by_sound.entry(filename).or_default() += 1;
+        }
+        let sound_plays = by_sound.values().sum();
+        let top_sound = by_sound
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0)))
+            .map(|(filename, plays)| SoundPlays {
+                filename: filename.to_string(),
+                plays,
+            });
+
+        let shown: Vec<_> = self
+            .shown
+            .iter()
+            .filter(|(at, _, _)| in_period(at))
+            .collect();
+        let videos: Vec<_> = shown
+            .iter()
+            .filter(|(_, media_type, _)| *media_type == MediaType::Video)
+            .collect();
+
+        StatsReport {
+            since: unix_secs(since),
+            until: unix_secs(now),
+            uploads: uploads_by_person.iter().map(|entry| entry.uploads).sum(),
+            uploads_by_person,
+            top_sound,
+            sound_plays,
+            images_shown: (shown.len() - videos.len()) as u64,
+            videos_shown: videos.len() as u64,
+            video_minutes: videos.iter().map(|(_, _, secs)| *secs).sum::<u64>() as f64 / 60.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut log = StatsLog::default();
+        let start = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let days = |days: u64| start + Duration::from_secs(days * 24 * 3600);
+        let alice = ClientId::Session("a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6".to_string());
+        let bob = ClientId::Ip("192.168.1.20".parse().unwrap());
+
+        // Too old for a weekly report
+        log.record_upload(start, Some(&bob));
+        log.record_upload(days(5), Some(&alice));
+        log.record_upload(days(6), Some(&alice));
+        log.record_upload(days(6), Some(&bob));
+        log.record_sound_play(days(6), "airhorn.mp3");
+        log.record_sound_play(days(6), "bruh.mp3");
+        log.record_sound_play(days(7), "airhorn.mp3");
+        log.record_shown(days(6), MediaType::Video, 90);
+        log.record_shown(days(6), MediaType::Video, 30);
+        log.record_shown(days(6), MediaType::Image, 5);
+
+        let report = log.report(days(8), Duration::from_secs(7 * 24 * 3600));
+        assert_eq!(report.uploads, 3);
+        assert_eq!(
+            report.uploads_by_person,
+            vec![
+                PersonUploads {
+                    person: person(Some(&alice)),
+                    uploads: 2
+                },
+                PersonUploads {
+                    person: person(Some(&bob)),
+                    uploads: 1
+                },
+            ]
+        );
+        assert_eq!(
+            report.top_sound,
+            Some(SoundPlays {
+                filename: "airhorn.mp3".to_string(),
+                plays: 2
+            })
+        );
+        assert_eq!(report.sound_plays, 3);
+        assert_eq!((report.images_shown, report.videos_shown), (1, 2));
+        assert_eq!(report.video_minutes, 2.0);
+        assert_eq!(report.video_time(), "0h 02m");
+    }
+
+    #[test]
+    fn test_person_hides_the_session_and_ip() {
+        let session = ClientId::Session("a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6".to_string());
+        let ip = ClientId::Ip("192.168.1.20".parse().unwrap());
+
+        assert!(person(Some(&session)).starts_with("homie "));
+        assert!(!person(Some(&session)).contains("a1b2c3d4"));
+        assert!(!person(Some(&ip)).contains("192.168"));
+        // The same person keeps their name, different people get different ones
+        assert_eq!(person(Some(&ip)), person(Some(&ip)));
+        assert_ne!(person(Some(&session)), person(Some(&ip)));
+        assert_eq!(person(None), "unknown");
+    }
+
+    #[test]
+    fn test_next_recap_delay() {
+        let schedule = RecapSchedule::default();
+        // Wednesday 2024-01-03 12:00 UTC, the recap is due Sunday at 19:00
+        let wednesday = chrono::Utc.with_ymd_and_hms(2024, 1, 3, 12, 0, 0).unwrap();
+        assert_eq!(
+            schedule.next_delay(&wednesday),
+            Duration::from_secs((4 * 24 + 7) * 3600)
+        );
+        // Sunday right after the recap, the next one is a week later
+        let sunday = chrono::Utc.with_ymd_and_hms(2024, 1, 7, 19, 0, 0).unwrap();
+        assert_eq!(
+            schedule.next_delay(&sunday),
+            Duration::from_secs(7 * 24 * 3600)
+        );
+    }
+}
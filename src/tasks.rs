@@ -0,0 +1,599 @@
+use crate::state_actor::StateHandle;
+use crate::cleanup::{CleanupConfig, Disposal, Failure};
+use crate::data_dirs::DataDirs;
+use crate::game_status::GameStatusConfig;
+use crate::ingest::{self, IngestConfig};
+use crate::jobs::{self, JobKind, JobStage, SharedJobs};
+use crate::mqtt::{MqttCommand, MqttConfig};
+use crate::notifier::{self, NotifierConfig};
+use crate::now_playing::NowPlayingSource;
+use crate::staging::StagingConfig;
+use crate::state::MediaType;
+use crate::stats::RecapSchedule;
+use crate::timers::TimerPhase;
+use crate::video_processing::VideoProcessor;
+use crate::trash::TrashConfig;
+use crate::twitch::{LiveTracker, TwitchClient, TwitchConfig};
+use crate::events::Event;
+use crate::webhooks::{self, WebhookConfig};
+use crate::{config, handlers, highlights, websocket};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// How long an upload stays on disk after it went on screen
+pub const DELETION_THRESHOLD: Duration = Duration::from_secs(10);
+// Seconds between re-syncs of the running timers
+const TIMER_SYNC_SECS: u64 = 5;
+// Strokes drawn within this are relayed together
+const DRAW_BATCH: Duration = Duration::from_millis(100);
+
+// Background task putting queued media on screen once the current one is done
+// Also flushes what was held during quiet hours once they end
+pub fn start_queue_task(state: StateHandle, ws_clients: websocket::WsClients) {
+    tokio::spawn(async move {
+        let mut was_quiet = false;
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+
+            let (next_media, quiet, queued, playback) = state
+                .call(|state| {
+                    let quiet = state.is_quiet();
+                    let next_media = state.advance_queue();
+                    (next_media, quiet, state.queue_len(), state.playback())
+                })
+                .await;
+            if was_quiet && !quiet {
+                tracing::info!(
+                    "Quiet hours over, flushing {} held media",
+                    queued + usize::from(next_media.is_some())
+                );
+            }
+            was_quiet = quiet;
+            if let Some(media) = next_media {
+                tracing::info!("Showing queued media: {}", media.filename);
+                websocket::broadcast_media(&ws_clients, &media, playback).await;
+            }
+        }
+    });
+}
+
+// Background task putting together the highlight reel on schedule
+pub fn start_highlights_task(
+    state: StateHandle,
+    ws_clients: websocket::WsClients,
+    config: Arc<config::AppConfig>,
+) {
+    tokio::spawn(async move {
+        let every = config.highlights.every();
+        // Pick up the schedule where the last reel in the archive left it
+        let mut delay = state
+            .call(move |state| {
+                let now = state
+                    .now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                highlights::next_run_delay(state.archive(), every, now)
+            })
+            .await;
+
+        loop {
+            tracing::info!("Next highlights in {:?}", delay);
+            tokio::time::sleep(delay).await;
+            handlers::archive::make_highlight_reel(state.clone(), ws_clients.clone(), &config)
+                .await;
+            delay = every;
+        }
+    });
+}
+
+// Background task putting the stats recap on screen every week
+pub fn start_recap_task(ws_clients: websocket::WsClients, schedule: RecapSchedule) {
+    tokio::spawn(async move {
+        loop {
+            let delay = schedule.next_delay_local();
+            tracing::info!("Next stats recap in {:?}", delay);
+            tokio::time::sleep(delay).await;
+            websocket::broadcast_recap(&ws_clients, schedule.days).await;
+            // Don't fire twice within the same second
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+}
+
+// Background task following the track playing on the configured music service
+pub fn start_now_playing_task(
+    state: StateHandle,
+    ws_clients: websocket::WsClients,
+    source: NowPlayingSource,
+) {
+    tokio::spawn(async move {
+        loop {
+            match source.fetch().await {
+                Ok(track) => {
+                    handlers::now_playing::update(track, state.clone(), ws_clients.clone()).await
+                }
+                Err(e) => tracing::warn!("Failed to fetch now playing: {}", e),
+            }
+            tokio::time::sleep(source.every()).await;
+        }
+    });
+}
+
+// Background task polling the game servers for the who's online widget
+pub fn start_game_status_task(
+    state: StateHandle,
+    ws_clients: websocket::WsClients,
+    config: GameStatusConfig,
+) {
+    tokio::spawn(async move {
+        loop {
+            let status =
+                futures_util::future::join_all(config.servers.iter().map(|server| server.status()))
+                    .await;
+            let online = status.iter().filter(|server| server.online).count();
+            tracing::debug!("{} of {} game servers online", online, status.len());
+            websocket::broadcast_game_status(&ws_clients, &status).await;
+            state.call(move |state| state.set_game_status(status)).await;
+            tokio::time::sleep(config.every()).await;
+        }
+    });
+}
+
+// Background task announcing Twitch channels going live
+pub fn start_twitch_task(
+    state: StateHandle,
+    ws_clients: websocket::WsClients,
+    config: TwitchConfig,
+) {
+    tokio::spawn(async move {
+        let mut client = match TwitchClient::new(config) {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::error!("Twitch notifications disabled: {}", e);
+                return;
+            }
+        };
+        let mut tracker = LiveTracker::default();
+        loop {
+            match client.live_streams().await {
+                Ok(streams) => {
+                    let now = state.call(|state| state.now()).await;
+                    for stream in tracker.update(streams, client.config(), now) {
+                        let embed_url = client.config().embed_url(&stream.user_login);
+                        websocket::broadcast_twitch_live(&ws_clients, &stream, &embed_url).await;
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to check Twitch streams: {}", e),
+            }
+            tokio::time::sleep(client.config().every()).await;
+        }
+    });
+}
+
+// Background task running the screen-share relay, telling displays when a stream starts and ends
+// The relay takes one sender at a time and is restarted once it's done
+pub fn start_ingest_task(ws_clients: websocket::WsClients, config: IngestConfig, dirs: DataDirs) {
+    tokio::spawn(async move {
+        tracing::info!("Screen-share ingest listening on {}", config.listen);
+        loop {
+            let mut relay = match ingest::spawn_relay(&config, &dirs).await {
+                Ok(relay) => relay,
+                Err(e) => {
+                    tracing::error!("Screen-share ingest disabled: {}", e);
+                    return;
+                }
+            };
+            if ingest::wait_for_stream(&mut relay, &dirs).await {
+                tracing::info!("Screen share started");
+                websocket::broadcast_live(&ws_clients, Some(&ingest::playlist_url())).await;
+                match relay.wait().await {
+                    Ok(status) => tracing::info!("Screen share ended: {}", status),
+                    Err(e) => tracing::warn!("Lost the ingest relay: {}", e),
+                }
+                websocket::broadcast_live(&ws_clients, None).await;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+}
+
+// Background task delivering the events queued by the state to webhooks
+pub fn start_webhook_task(
+    events: tokio::sync::mpsc::UnboundedReceiver<Event>,
+    config: Vec<WebhookConfig>,
+) {
+    tokio::spawn(webhooks::run(events, config));
+}
+
+// Background task sending push notifications for the queued events
+pub fn start_notifier_task(
+    events: tokio::sync::mpsc::UnboundedReceiver<Event>,
+    config: NotifierConfig,
+) {
+    tokio::spawn(notifier::run(events, config));
+}
+
+// Low priority background task making the animated previews of uploaded videos, one at a time
+// so it never competes much with the uploads being processed
+pub fn start_preview_task(
+    state: StateHandle,
+    ws_clients: websocket::WsClients,
+    jobs: SharedJobs,
+    mut events: tokio::sync::mpsc::UnboundedReceiver<Event>,
+) {
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let Event::MediaUploaded { media } = event else {
+                continue;
+            };
+            if media.media_type != MediaType::Video {
+                continue;
+            }
+
+            let url = format!("/uploads/{}", media.filename);
+            let progress =
+                jobs::start_job(jobs.clone(), ws_clients.clone(), JobKind::Preview, &url).await;
+            let dirs = state.call(|state| state.data_dirs().clone()).await;
+            let cancel = progress.cancel_token();
+            match VideoProcessor::extract_preview(&dirs, &media.filename, Some(cancel)).await {
+                Ok(preview) => {
+                    progress.stage(JobStage::Done);
+                    state
+                        .call(move |state| state.set_preview(media.id, &preview))
+                        .await;
+                }
+                Err(_) if progress.is_cancelled() => {
+                    tracing::info!("Preview of {} cancelled", media.filename);
+                }
+                Err(e) => {
+                    progress.stage(JobStage::Failed);
+                    tracing::warn!("Failed to make a preview of {}: {}", media.filename, e);
+                }
+            }
+        }
+    });
+}
+
+// Background task publishing the queued events to the MQTT broker and running the commands
+// published to the command topic
+pub fn start_mqtt_task(
+    state: StateHandle,
+    ws_clients: websocket::WsClients,
+    mut events: tokio::sync::mpsc::UnboundedReceiver<Event>,
+    config: MqttConfig,
+) {
+    use rumqttc::{AsyncClient, Incoming, QoS};
+
+    tokio::spawn(async move {
+        let (client, mut eventloop) = AsyncClient::new(config.options(), 64);
+        let command_topic = config.command_topic();
+        loop {
+            tokio::select! {
+                Some(event) = events.recv() => {
+                    let payload = match serde_json::to_vec(&event) {
+                        Ok(payload) => payload,
+                        Err(e) => {
+                            tracing::error!("Failed to serialize {}: {}", event.name(), e);
+                            continue;
+                        }
+                    };
+                    let topic = config.event_topic(&event);
+                    if let Err(e) = client.try_publish(&topic, QoS::AtLeastOnce, false, payload) {
+                        tracing::warn!("Failed to publish {} to MQTT: {}", topic, e);
+                    }
+                }
+                notification = eventloop.poll() => match notification {
+                    Ok(rumqttc::Event::Incoming(Incoming::ConnAck(_))) => {
+                        tracing::info!("Connected to MQTT broker {}", config.host);
+                        // Subscriptions don't survive a reconnect
+                        let _ = client.try_subscribe(&command_topic, QoS::AtLeastOnce);
+                        let _ = client.try_publish(
+                            config.status_topic(),
+                            QoS::AtLeastOnce,
+                            true,
+                            "online",
+                        );
+                    }
+                    Ok(rumqttc::Event::Incoming(Incoming::Publish(publish)))
+                        if publish.topic == command_topic =>
+                    {
+                        match MqttCommand::parse(&publish.payload) {
+                            Some(command) => {
+                                run_mqtt_command(command, &state, &ws_clients).await
+                            }
+                            None => tracing::warn!("Ignoring unknown MQTT command"),
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        // The event loop reconnects on the next poll
+                        tracing::warn!("MQTT connection error: {}", e);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                },
+            }
+        }
+    });
+}
+
+async fn run_mqtt_command(
+    command: MqttCommand,
+    state: &StateHandle,
+    ws_clients: &websocket::WsClients,
+) {
+    tracing::info!("Running MQTT command {:?}", command);
+    match command {
+        MqttCommand::PlaySound { id, hotkey } => {
+            let sound = state
+                .call(move |state| {
+                    let sound = match (id, hotkey) {
+                        (Some(id), _) => state.get_sound(id).cloned(),
+                        (None, Some(hotkey)) => state.get_sound_by_hotkey(hotkey).cloned(),
+                        (None, None) => None,
+                    };
+                    sound.inspect(|sound| state.record_sound_play(&sound.filename, None))
+                })
+                .await;
+            match sound {
+                Some(sound) => websocket::broadcast_new_song(ws_clients, sound.filename).await,
+                None => tracing::warn!("MQTT asked for a sound that doesn't exist"),
+            }
+        }
+        MqttCommand::Stop => {
+            let status = state.call(|state| state.music_mut().pause()).await;
+            websocket::broadcast_music(ws_clients, "pause", &status).await;
+        }
+    }
+}
+
+/// Announce timers that ran out and re-sync the running ones every few seconds
+pub fn start_timer_task(state: StateHandle, ws_clients: websocket::WsClients) {
+    tokio::spawn(async move {
+        let mut ticks = 0u64;
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            ticks += 1;
+            let sync = ticks.is_multiple_of(TIMER_SYNC_SECS);
+
+            let statuses = state
+                .call(move |state| {
+                    let now = state.now();
+                    let mut statuses: Vec<_> = state
+                        .timers_mut()
+                        .take_finished(now)
+                        .iter()
+                        .map(|timer| timer.status(now, TimerPhase::Done))
+                        .collect();
+                    if sync {
+                        statuses.extend(
+                            state
+                                .timers()
+                                .running()
+                                .iter()
+                                .map(|timer| timer.status(now, TimerPhase::Running)),
+                        );
+                    }
+                    statuses
+                })
+                .await;
+            for status in &statuses {
+                if status.phase == TimerPhase::Done {
+                    tracing::info!("Timer {} ({}) is done", status.id, status.label);
+                }
+                websocket::broadcast_timer(&ws_clients, status).await;
+            }
+        }
+    });
+}
+
+/// Relay strokes to displays in batches, so a fast drawer doesn't flood them
+pub fn start_draw_task(state: StateHandle, ws_clients: websocket::WsClients) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(DRAW_BATCH).await;
+            let strokes = state.call(|state| state.canvas_mut().take_pending()).await;
+            if !strokes.is_empty() {
+                websocket::broadcast_strokes(&ws_clients, &strokes).await;
+            }
+        }
+    });
+}
+
+/// Close polls whose time is up and announce their results
+pub fn start_poll_task(state: StateHandle, ws_clients: websocket::WsClients) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            let results = state
+                .call(|state| {
+                    let now = state.now();
+                    state.polls_mut().close_expired(now).map(|poll| poll.status(now))
+                })
+                .await;
+            if let Some(results) = results {
+                tracing::info!("Poll {} closed with {} votes", results.id, results.total_votes);
+                websocket::broadcast_poll(&ws_clients, "poll_results", &results).await;
+            }
+        }
+    });
+}
+
+// Background cleanup task, restarted if a pass panics so expired uploads keep going away
+pub fn start_cleanup_task(state: StateHandle, config: CleanupConfig) {
+    tokio::spawn(async move {
+        loop {
+            let worker = tokio::spawn({
+                let state = state.clone();
+                let config = config.clone();
+                async move {
+                    loop {
+                        tokio::time::sleep(config.next_delay()).await;
+                        delete_expired_files(&state).await;
+                    }
+                }
+            });
+            // The worker never returns, it only ends by panicking
+            let Err(e) = worker.await;
+            tracing::error!("Cleanup task stopped, restarting: {}", e);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+}
+
+/// One cleanup pass: move the on-screen upload to the trash once it has been shown long enough,
+/// and retry the files earlier passes failed on
+pub async fn delete_expired_files(state: &StateHandle) {
+    let files_to_delete = state
+        .call(|state| {
+            let now = state.now();
+            let mut files: Vec<_> = state
+                .get_files_to_delete(DELETION_THRESHOLD)
+                .into_iter()
+                .map(|filename| (filename, Disposal::Trash))
+                .collect();
+            files.extend(state.cleanup().due(now));
+            files
+        })
+        .await;
+
+    for (filename, disposal) in files_to_delete {
+        dispose(state, filename, disposal).await;
+    }
+}
+
+/// Move an upload to the trash and forget it, it can be restored until purged
+pub async fn trash_upload(state: &StateHandle, filename: String) {
+    dispose(state, filename, Disposal::Trash).await;
+}
+
+/// Delete an upload for good and forget it
+pub async fn delete_upload(state: &StateHandle, filename: String) {
+    dispose(state, filename, Disposal::Delete).await;
+}
+
+// Trash or delete an upload; a failure hides it from displays and is retried with backoff
+// until the file is quarantined
+async fn dispose(state: &StateHandle, filename: String, disposal: Disposal) {
+    let dirs = state.call(|state| state.data_dirs().clone()).await;
+    let file_path = dirs.upload(&filename);
+    let result = match disposal {
+        Disposal::Trash => match tokio::fs::create_dir_all(&dirs.trash).await {
+            Ok(_) => tokio::fs::rename(&file_path, dirs.trashed(&filename)).await,
+            Err(e) => Err(e),
+        },
+        Disposal::Delete => tokio::fs::remove_file(&file_path).await,
+    };
+    match result {
+        Ok(_) => {
+            tracing::info!("Disposed of file ({:?}): {}", disposal, filename);
+            VideoProcessor::remove_thumbnails(&dirs, &filename).await;
+            state
+                .call(move |state| {
+                    state.cleanup_mut().record_success(&filename, disposal);
+                    if disposal == Disposal::Trash {
+                        let now = state.now();
+                        state.trash_mut().add(&filename, now);
+                    }
+                    state.remove_file_from_state(&filename);
+                })
+                .await;
+        }
+        // Already gone, e.g. burned while the cleanup had it lined up
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            tracing::info!("File already gone: {}", filename);
+            state
+                .call(move |state| {
+                    state.cleanup_mut().forget(&filename);
+                    state.remove_file_from_state(&filename);
+                })
+                .await;
+        }
+        Err(e) => {
+            tracing::error!("Failed to dispose of file {}: {}", file_path, e);
+            state
+                .call(move |state| {
+                    let now = state.now();
+                    state.mark_for_deletion(&filename);
+                    match state.cleanup_mut().record_failure(&filename, disposal, now) {
+                        Failure::RetryAt(at) => {
+                            let wait = at.duration_since(now).unwrap_or_default();
+                            tracing::warn!("Retrying {} in {:?}", filename, wait);
+                        }
+                        Failure::Quarantined => {
+                            tracing::error!("Giving up on {}, left in the uploads", filename);
+                            state.remove_file_from_state(&filename);
+                        }
+                    }
+                })
+                .await;
+        }
+    }
+}
+
+// Second cleanup stage: delete trashed uploads for good once their grace period is over
+pub fn start_trash_purge_task(state: StateHandle, config: TrashConfig) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(config.purge_interval()).await;
+            purge_trash(&state, config.retention()).await;
+        }
+    });
+}
+
+/// One purge pass over the trash
+pub async fn purge_trash(state: &StateHandle, retention: Duration) {
+    let (expired, dirs) = state
+        .call(move |state| {
+            let now = state.now();
+            (state.trash_mut().take_expired(now, retention), state.data_dirs().clone())
+        })
+        .await;
+
+    for filename in expired {
+        match tokio::fs::remove_file(dirs.trashed(&filename)).await {
+            Ok(_) => tracing::info!("Purged file from the trash: {}", filename),
+            Err(e) => tracing::error!("Failed to purge {} from the trash: {}", filename, e),
+        }
+    }
+}
+
+// Delete the staged uploads nobody published, and the held ones nobody reviewed, before their
+// expiry
+pub fn start_staging_sweep_task(state: StateHandle, config: StagingConfig) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(config.sweep_interval()).await;
+            sweep_staging(&state).await;
+        }
+    });
+}
+
+/// One sweep over the staged uploads and those held for review
+pub async fn sweep_staging(state: &StateHandle) {
+    let (expired, dirs) = state
+        .call(|state| {
+            let now = state.now();
+            let mut expired = state.staging_mut().take_expired(now);
+            expired.extend(state.review_mut().take_expired(now));
+            (expired, state.data_dirs().clone())
+        })
+        .await;
+
+    for filename in expired {
+        match tokio::fs::remove_file(dirs.upload(&filename)).await {
+            Ok(_) => tracing::info!("Deleted unpublished staged upload: {}", filename),
+            Err(e) => tracing::error!("Failed to delete staged upload {}: {}", filename, e),
+        }
+        VideoProcessor::remove_thumbnails(&dirs, &filename).await;
+    }
+}
+
+/// Burn a burn after viewing upload once the display that got it had time to play it
+pub fn burn_after(state: StateHandle, filename: String, delay: Duration) {
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        tracing::info!("Burning {} after viewing", filename);
+        delete_upload(&state, filename).await;
+    });
+}
@@ -1,9 +1,41 @@
-use crate::state::{MediaInfo, MediaType};
+use crate::capabilities::Capabilities;
+use crate::i18n::{Locale, Msg};
+use crate::music::{MusicStatus, MusicTrack};
+use crate::state::{ArchiveEntry, MediaInfo, MediaType, SoundInfo};
+use crate::leaderboard::Ranking;
+use crate::stats::StatsReport;
+use crate::theme::Theme;
+use crate::video_processing::Chromakey;
 use askama::Template;
 
 #[derive(Template)]
 #[template(path = "index.html")]
-pub struct IndexTemplate;
+pub struct IndexTemplate {
+    pub theme: Theme,
+    pub locale: Locale,
+}
+
+#[derive(Template)]
+#[template(path = "overlay.html")]
+pub struct OverlayTemplate;
+
+#[derive(Template)]
+#[template(path = "draw.html")]
+pub struct DrawTemplate {
+    pub csrf_token: String,
+}
+
+#[derive(Template)]
+#[template(path = "leaderboard.html")]
+pub struct LeaderboardTemplate {
+    pub rankings: Vec<Ranking>,
+}
+
+#[derive(Template)]
+#[template(path = "recap.html")]
+pub struct RecapTemplate {
+    pub report: StatsReport,
+}
 
 #[derive(Template)]
 #[template(path = "media_container.html")]
@@ -13,14 +45,48 @@ pub struct MediaContainerTemplate;
 #[template(path = "media_content.html")]
 pub struct MediaContentTemplate<'a> {
     pub media_info: Option<&'a MediaInfo>,
+    pub theme: Theme,
+    pub locale: Locale,
 }
 
 #[derive(Template)]
 #[template(path = "upload.html")]
-pub struct UploadTemplate;
+pub struct UploadTemplate {
+    pub csrf_token: String,
+    pub watermark: bool,
+    /// Tools and limits, options needing a missing tool are left out
+    pub capabilities: Capabilities,
+    pub tiktok_enabled: bool,
+    pub translation_enabled: bool,
+    pub auto_captions_enabled: bool,
+    /// Configured green screen background, its key values prefill the form
+    pub chromakey: Option<Chromakey>,
+    pub imagine_enabled: bool,
+    /// Bounds of the retention an upload can ask for
+    pub expires_in_min_secs: u64,
+    pub expires_in_max_secs: u64,
+    /// Names of the configured presets
+    pub presets: Vec<String>,
+}
 
 #[derive(Template)]
 #[template(path = "greet.html")]
 pub struct GreetTemplate {
     pub name: String,
 }
+
+#[derive(Template)]
+#[template(path = "soundboard.html")]
+pub struct SoundboardTemplate {
+    pub csrf_token: String,
+    pub sounds: Vec<SoundInfo>,
+    pub music: MusicStatus,
+    pub playlist: Vec<MusicTrack>,
+}
+
+#[derive(Template)]
+#[template(path = "archive.html")]
+pub struct ArchiveTemplate {
+    pub csrf_token: String,
+    pub entries: Vec<ArchiveEntry>,
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Look of the display pages, switched live with a `theme_changed` event
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+    /// Animated colors for game nights
+    Party,
+}
+
+impl Theme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+            Theme::Party => "party",
+        }
+    }
+}
+
+// Rendered into the templates as the body's `theme-*` class
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Theme {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "dark" => Ok(Theme::Dark),
+            "light" => Ok(Theme::Light),
+            "party" => Ok(Theme::Party),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_theme() {
+        assert_eq!(" Party ".parse(), Ok(Theme::Party));
+        assert_eq!(
+            "light".parse::<Theme>().map(|theme| theme.to_string()),
+            Ok("light".to_string())
+        );
+        assert_eq!("neon".parse::<Theme>(), Err(()));
+    }
+}
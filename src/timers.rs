@@ -0,0 +1,163 @@
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Longest countdown accepted, extensions included
+pub const MAX_TIMER: Duration = Duration::from_secs(24 * 3600);
+// More than this and the screen is all timers
+const MAX_TIMERS: usize = 8;
+// Labels are drawn on screen, keep them short
+const MAX_LABEL_CHARS: usize = 40;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timer {
+    pub id: u64,
+    pub label: String,
+    pub ends_at: SystemTime,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimerPhase {
+    Running,
+    Done,
+    Cancelled,
+}
+
+/// A timer as sent to displays, which count down locally between syncs
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TimerStatus {
+    pub id: u64,
+    pub label: String,
+    pub phase: TimerPhase,
+    pub remaining_secs: u64,
+    pub ends_at_ms: u64, // Unix milliseconds
+}
+
+impl Timer {
+    pub fn status(&self, now: SystemTime, phase: TimerPhase) -> TimerStatus {
+        let remaining = match phase {
+            TimerPhase::Running => self.ends_at.duration_since(now).unwrap_or_default(),
+            TimerPhase::Done | TimerPhase::Cancelled => Duration::ZERO,
+        };
+        TimerStatus {
+            id: self.id,
+            label: self.label.clone(),
+            phase,
+            // Round up so a timer shows 0 only once it's done
+            remaining_secs: remaining.as_millis().div_ceil(1000) as u64,
+            ends_at_ms: self
+                .ends_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TimerError {
+    #[error("Timers can't run longer than 24 hours")]
+    TooLong,
+    #[error("At most {MAX_TIMERS} timers can run at once")]
+    TooMany,
+}
+
+/// Countdowns running on the screen, e.g. "pizza in 12:00"
+#[derive(Clone, Debug)]
+pub struct Timers {
+    timers: Vec<Timer>, // In start order
+    next_id: u64,
+}
+
+impl Default for Timers {
+    fn default() -> Self {
+        Self {
+            timers: Vec::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl Timers {
+    pub fn start(
+        &mut self,
+        label: &str,
+        duration: Duration,
+        now: SystemTime,
+    ) -> Result<Timer, TimerError> {
+        if duration > MAX_TIMER {
+            return Err(TimerError::TooLong);
+        }
+        if self.timers.len() >= MAX_TIMERS {
+            return Err(TimerError::TooMany);
+        }
+        let timer = Timer {
+            id: self.next_id,
+            label: label.trim().chars().take(MAX_LABEL_CHARS).collect(),
+            ends_at: now + duration,
+        };
+        self.next_id += 1;
+        self.timers.push(timer.clone());
+        Ok(timer)
+    }
+
+    pub fn cancel(&mut self, id: u64) -> Option<Timer> {
+        let index = self.timers.iter().position(|timer| timer.id == id)?;
+        Some(self.timers.remove(index))
+    }
+
+    /// Push a running timer's end back, capped at [`MAX_TIMER`] from now
+    pub fn extend(&mut self, id: u64, by: Duration, now: SystemTime) -> Option<Timer> {
+        let timer = self.timers.iter_mut().find(|timer| timer.id == id)?;
+        timer.ends_at = (timer.ends_at + by).min(now + MAX_TIMER);
+        Some(timer.clone())
+    }
+
+    pub fn running(&self) -> &[Timer] {
+        &self.timers
+    }
+
+    /// Remove the timers that ran out, returning them so they can be announced
+    pub fn take_finished(&mut self, now: SystemTime) -> Vec<Timer> {
+        let (finished, running) = std::mem::take(&mut self.timers)
+            .into_iter()
+            .partition(|timer| timer.ends_at <= now);
+        self.timers = running;
+        finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timers() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut timers = Timers::default();
+        let pizza = timers
+            .start(" pizza ", Duration::from_secs(12 * 60), now)
+            .unwrap();
+        let tea = timers.start("tea", Duration::from_secs(180), now).unwrap();
+        assert_eq!(pizza.label, "pizza");
+        assert_eq!(
+            timers.start("nap", Duration::from_secs(25 * 3600), now),
+            Err(TimerError::TooLong)
+        );
+
+        let status = pizza.status(now + Duration::from_millis(500), TimerPhase::Running);
+        assert_eq!(status.remaining_secs, 12 * 60);
+        assert_eq!(status.ends_at_ms, (1_000_000 + 12 * 60) * 1000);
+
+        let tea = timers.extend(tea.id, Duration::from_secs(60), now).unwrap();
+        assert_eq!(tea.ends_at, now + Duration::from_secs(240));
+        let finished = timers.take_finished(now + Duration::from_secs(240));
+        assert_eq!(finished, [tea]);
+        assert_eq!(timers.running().len(), 1);
+
+        assert_eq!(timers.cancel(pizza.id), Some(pizza));
+        assert!(timers.running().is_empty());
+        assert!(timers.extend(1, Duration::from_secs(60), now).is_none());
+    }
+}
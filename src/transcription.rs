@@ -0,0 +1,313 @@
+use crate::errors::AppError;
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::process::Command as AsyncCommand;
+
+const TRANSCRIBE_TIMEOUT: Duration = Duration::from_secs(600);
+// whisper.cpp only reads 16 kHz mono WAV
+const WHISPER_SAMPLE_RATE: &str = "16000";
+
+/// Where the speech in uploaded videos is turned into text
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "backend", rename_all = "kebab-case")]
+pub enum AutoCaptions {
+    /// Local whisper.cpp build, run as a subprocess
+    WhisperCpp {
+        #[serde(default = "default_whisper_binary")]
+        binary: String,
+        model: String,
+    },
+    /// OpenAI compatible `/audio/transcriptions` endpoint
+    Api {
+        url: String,
+        api_key: Option<String>,
+        #[serde(default = "default_api_model")]
+        model: String,
+    },
+}
+
+fn default_whisper_binary() -> String {
+    "whisper-cli".to_string()
+}
+
+fn default_api_model() -> String {
+    "whisper-1".to_string()
+}
+
+/// A line of transcript, with its time range in seconds
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+impl AutoCaptions {
+    /// Transcribe a video's audio into an SRT file next to it, returning the file's path
+    /// None when the video has no speech to caption
+    pub async fn subtitle_file(&self, video_path: &str) -> Result<Option<String>, AppError> {
+        let audio_path = format!("{}.whisper.wav", video_path);
+        let result = tokio::time::timeout(TRANSCRIBE_TIMEOUT, self.transcribe(video_path, &audio_path))
+            .await
+            .unwrap_or_else(|_| {
+                Err(AppError::IoError(std::io::Error::other("Transcription timed out")))
+            });
+        if let Err(e) = tokio::fs::remove_file(&audio_path).await {
+            tracing::debug!("Failed to remove {}: {}", audio_path, e);
+        }
+
+        let segments = result?;
+        if segments.is_empty() {
+            tracing::info!("No speech found in {}", video_path);
+            return Ok(None);
+        }
+        let srt_path = format!("{}.srt", video_path);
+        tokio::fs::write(&srt_path, to_srt(&segments))
+            .await
+            .map_err(AppError::IoError)?;
+        tracing::info!("Wrote {} caption lines to {}", segments.len(), srt_path);
+        Ok(Some(srt_path))
+    }
+
+    async fn transcribe(&self, video_path: &str, audio_path: &str) -> Result<Vec<Segment>, AppError> {
+        extract_audio(video_path, audio_path).await?;
+        match self {
+            AutoCaptions::WhisperCpp { binary, model } => {
+                run_whisper_cpp(binary, model, audio_path).await
+            }
+            AutoCaptions::Api {
+                url,
+                api_key,
+                model,
+            } => transcribe_with_api(url, api_key.as_deref(), model, audio_path).await,
+        }
+    }
+}
+
+async fn extract_audio(video_path: &str, audio_path: &str) -> Result<(), AppError> {
+    let output = AsyncCommand::new("ffmpeg")
+        .args([
+            "-i",
+            video_path,
+            "-vn",
+            "-ac",
+            "1",
+            "-ar",
+            WHISPER_SAMPLE_RATE,
+            "-c:a",
+            "pcm_s16le",
+            "-y",
+            audio_path,
+        ])
+        .output()
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute ffmpeg: {}", e);
+            AppError::IoError(std::io::Error::other("Audio extraction failed"))
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        tracing::error!("FFmpeg audio extraction failed: {}", stderr);
+        return Err(AppError::IoError(std::io::Error::other(
+            "Audio extraction failed, the video may have no sound",
+        )));
+    }
+    Ok(())
+}
+
+async fn run_whisper_cpp(binary: &str, model: &str, audio_path: &str) -> Result<Vec<Segment>, AppError> {
+    // -oj writes <output base>.json next to the audio
+    let output = AsyncCommand::new(binary)
+        .args(["-m", model, "-f", audio_path, "-oj", "-of", audio_path, "-np"])
+        .output()
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to execute {}: {}", binary, e);
+            AppError::IoError(std::io::Error::other("whisper.cpp is not available"))
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        tracing::error!("whisper.cpp failed: {}", stderr);
+        return Err(AppError::IoError(std::io::Error::other("Transcription failed")));
+    }
+
+    let json_path = format!("{}.json", audio_path);
+    let json = tokio::fs::read(&json_path).await.map_err(AppError::IoError)?;
+    let _ = tokio::fs::remove_file(&json_path).await;
+    let json: Value = serde_json::from_slice(&json).map_err(|e| {
+        tracing::error!("Failed to parse whisper.cpp output: {}", e);
+        AppError::IoError(std::io::Error::other("Transcription failed"))
+    })?;
+    Ok(parse_whisper_cpp(&json))
+}
+
+async fn transcribe_with_api(
+    url: &str,
+    api_key: Option<&str>,
+    model: &str,
+    audio_path: &str,
+) -> Result<Vec<Segment>, AppError> {
+    let request_error = |e: reqwest::Error| {
+        tracing::error!("Transcription request failed: {}", e);
+        AppError::IoError(std::io::Error::other("Transcription failed"))
+    };
+
+    let audio = tokio::fs::read(audio_path).await.map_err(AppError::IoError)?;
+    let form = reqwest::multipart::Form::new()
+        .text("model", model.to_string())
+        .text("response_format", "verbose_json")
+        .part(
+            "file",
+            reqwest::multipart::Part::bytes(audio)
+                .file_name("audio.wav")
+                .mime_str("audio/wav")
+                .map_err(request_error)?,
+        );
+
+    let mut request = reqwest::Client::new().post(url).multipart(form);
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    let response = request.send().await.map_err(request_error)?;
+    if !response.status().is_success() {
+        tracing::error!("Transcription API returned HTTP {}", response.status());
+        return Err(AppError::IoError(std::io::Error::other("Transcription failed")));
+    }
+    let body = response.bytes().await.map_err(request_error)?;
+    let json: Value = serde_json::from_slice(&body).map_err(|e| {
+        tracing::error!("Failed to parse transcription response: {}", e);
+        AppError::IoError(std::io::Error::other("Transcription failed"))
+    })?;
+    Ok(parse_api(&json))
+}
+
+/// Segments from whisper.cpp's `-oj` output, whose offsets are in milliseconds
+fn parse_whisper_cpp(json: &Value) -> Vec<Segment> {
+    json["transcription"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|line| {
+            Some(Segment {
+                start: line["offsets"]["from"].as_f64()? / 1000.0,
+                end: line["offsets"]["to"].as_f64()? / 1000.0,
+                text: line["text"].as_str()?.trim().to_string(),
+            })
+        })
+        .filter(|segment| !segment.text.is_empty())
+        .collect()
+}
+
+/// Segments from an OpenAI style `verbose_json` response, in seconds
+fn parse_api(json: &Value) -> Vec<Segment> {
+    json["segments"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|line| {
+            Some(Segment {
+                start: line["start"].as_f64()?,
+                end: line["end"].as_f64()?,
+                text: line["text"].as_str()?.trim().to_string(),
+            })
+        })
+        .filter(|segment| !segment.text.is_empty())
+        .collect()
+}
+
+fn srt_timestamp(secs: f64) -> String {
+    let millis = (secs.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+pub fn to_srt(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .enumerate()
+        .map(|(index, segment)| {
+            format!(
+                "{}\n{} --> {}\n{}\n\n",
+                index + 1,
+                srt_timestamp(segment.start),
+                srt_timestamp(segment.end),
+                segment.text
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_to_srt() {
+        let segments = vec![
+            Segment {
+                start: 0.0,
+                end: 2.5,
+                text: "no way".to_string(),
+            },
+            Segment {
+                start: 3661.042,
+                end: 3662.0,
+                text: "clutch".to_string(),
+            },
+        ];
+        assert_eq!(
+            to_srt(&segments),
+            "1\n00:00:00,000 --> 00:00:02,500\nno way\n\n2\n01:01:01,042 --> 01:01:02,000\nclutch\n\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_transcripts() {
+        let whisper_cpp = json!({
+            "transcription": [
+                { "offsets": { "from": 0, "to": 1500 }, "text": " Let's go" },
+                { "offsets": { "from": 1500, "to": 2000 }, "text": " " }
+            ]
+        });
+        assert_eq!(
+            parse_whisper_cpp(&whisper_cpp),
+            vec![Segment {
+                start: 0.0,
+                end: 1.5,
+                text: "Let's go".to_string()
+            }]
+        );
+
+        let api = json!({ "segments": [{ "start": 1.0, "end": 2.0, "text": " gg" }] });
+        assert_eq!(
+            parse_api(&api),
+            vec![Segment {
+                start: 1.0,
+                end: 2.0,
+                text: "gg".to_string()
+            }]
+        );
+        assert!(parse_api(&json!({ "text": "" })).is_empty());
+    }
+
+    #[test]
+    fn test_backend_config() {
+        let whisper: AutoCaptions =
+            toml::from_str("backend = \"whisper-cpp\"\nmodel = \"ggml-base.bin\"").unwrap();
+        assert!(matches!(
+            whisper,
+            AutoCaptions::WhisperCpp { ref binary, .. } if binary == "whisper-cli"
+        ));
+        let api: AutoCaptions =
+            toml::from_str("backend = \"api\"\nurl = \"https://example.com/v1/audio/transcriptions\"")
+                .unwrap();
+        assert!(matches!(api, AutoCaptions::Api { ref model, .. } if model == "whisper-1"));
+    }
+}
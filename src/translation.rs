@@ -0,0 +1,164 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::time::Duration;
+use thiserror::Error;
+
+const TRANSLATE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Translation service captions are sent to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranslationBackend {
+    #[default]
+    LibreTranslate,
+    DeepL,
+}
+
+/// Optional translation of captions before they are shown or burned in
+#[derive(Clone, Debug, Deserialize)]
+pub struct Translation {
+    #[serde(default)]
+    pub backend: TranslationBackend,
+    /// Base URL, e.g. `https://libretranslate.com` or `https://api-free.deepl.com`
+    pub url: String,
+    pub api_key: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum TranslateError {
+    #[error("Invalid target language: {0}")]
+    InvalidLanguage(String),
+    #[error("Translation request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Translation service returned HTTP {0}")]
+    Status(u16),
+    #[error("Unexpected translation response")]
+    InvalidResponse,
+}
+
+/// Language codes look like `de`, `pt-BR` or `zh-Hans`
+pub fn is_valid_language(code: &str) -> bool {
+    (2..=10).contains(&code.len())
+        && code.chars().all(|c| c.is_ascii_alphabetic() || c == '-')
+        && code.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+impl Translation {
+    pub async fn translate(&self, text: &str, target: &str) -> Result<String, TranslateError> {
+        let target = target.trim();
+        if !is_valid_language(target) {
+            return Err(TranslateError::InvalidLanguage(target.to_string()));
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(TRANSLATE_TIMEOUT)
+            .build()?;
+        let base = self.url.trim_end_matches('/');
+        let mut request = match self.backend {
+            TranslationBackend::LibreTranslate => client.post(format!("{}/translate", base)),
+            TranslationBackend::DeepL => client.post(format!("{}/v2/translate", base)),
+        };
+        if self.backend == TranslationBackend::DeepL
+            && let Some(api_key) = &self.api_key
+        {
+            request = request.header("authorization", format!("DeepL-Auth-Key {}", api_key));
+        }
+
+        let response = request
+            .header("content-type", "application/json")
+            .body(self.request_body(text, target).to_string())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(TranslateError::Status(response.status().as_u16()));
+        }
+        let body: Value = serde_json::from_slice(&response.bytes().await?)
+            .map_err(|_| TranslateError::InvalidResponse)?;
+
+        let translated = self.parse_response(&body)?;
+        tracing::info!("Translated caption to {}: {}", target, translated);
+        Ok(translated)
+    }
+
+    fn request_body(&self, text: &str, target: &str) -> Value {
+        match self.backend {
+            TranslationBackend::LibreTranslate => json!({
+                "q": text,
+                "source": "auto",
+                "target": target,
+                "format": "text",
+                "api_key": self.api_key.as_deref().unwrap_or_default(),
+            }),
+            // DeepL wants upper case codes and sends the key in a header
+            TranslationBackend::DeepL => json!({
+                "text": [text],
+                "target_lang": target.to_uppercase(),
+            }),
+        }
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<String, TranslateError> {
+        let translated = match self.backend {
+            TranslationBackend::LibreTranslate => body["translatedText"].as_str(),
+            TranslationBackend::DeepL => body["translations"][0]["text"].as_str(),
+        };
+        translated
+            .map(str::to_string)
+            .ok_or(TranslateError::InvalidResponse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(backend: TranslationBackend) -> Translation {
+        Translation {
+            backend,
+            url: "http://localhost:5000".to_string(),
+            api_key: Some("secret".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_is_valid_language() {
+        assert!(is_valid_language("de"));
+        assert!(is_valid_language("pt-BR"));
+        assert!(!is_valid_language("d"));
+        assert!(!is_valid_language("-de"));
+        assert!(!is_valid_language("de&q=x"));
+    }
+
+    #[test]
+    fn test_libretranslate_round_trip() {
+        let libre = translation(TranslationBackend::LibreTranslate);
+        let body = libre.request_body("gg", "fr");
+        assert_eq!(body["q"], "gg");
+        assert_eq!(body["target"], "fr");
+        assert_eq!(body["api_key"], "secret");
+        assert_eq!(
+            libre
+                .parse_response(&json!({ "translatedText": "bien joué" }))
+                .unwrap(),
+            "bien joué"
+        );
+        assert!(libre.parse_response(&json!({ "error": "nope" })).is_err());
+    }
+
+    #[test]
+    fn test_deepl_round_trip() {
+        let deepl = translation(TranslationBackend::DeepL);
+        let body = deepl.request_body("gg", "pt-br");
+        assert_eq!(body["text"], json!(["gg"]));
+        assert_eq!(body["target_lang"], "PT-BR");
+        assert!(body.get("api_key").is_none());
+        assert_eq!(
+            deepl
+                .parse_response(&json!({
+                    "translations": [{ "detected_source_language": "EN", "text": "bom jogo" }]
+                }))
+                .unwrap(),
+            "bom jogo"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Grace period before trashed uploads are deleted
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct TrashConfig {
+    pub retention_secs: u64,
+    /// Seconds between purges of the uploads past their retention
+    pub purge_interval_secs: u64,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            retention_secs: 24 * 3600,
+            purge_interval_secs: 60,
+        }
+    }
+}
+
+impl TrashConfig {
+    pub fn retention(&self) -> Duration {
+        Duration::from_secs(self.retention_secs)
+    }
+
+    pub fn purge_interval(&self) -> Duration {
+        Duration::from_secs(self.purge_interval_secs.max(1))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TrashEntry {
+    pub filename: String,
+    pub trashed_at_ms: u64, // Unix milliseconds
+}
+
+/// Uploads moved to the trash directory, oldest first
+#[derive(Clone, Debug, Default)]
+pub struct Trash {
+    entries: Vec<(String, SystemTime)>,
+}
+
+impl Trash {
+    pub fn add(&mut self, filename: &str, now: SystemTime) {
+        self.remove(filename);
+        self.entries.push((filename.to_string(), now));
+    }
+
+    /// Forget a trashed upload, false if it wasn't in the trash
+    pub fn remove(&mut self, filename: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|(trashed, _)| trashed != filename);
+        self.entries.len() != before
+    }
+
+    pub fn contains(&self, filename: &str) -> bool {
+        self.entries.iter().any(|(trashed, _)| trashed == filename)
+    }
+
+    pub fn entries(&self) -> Vec<TrashEntry> {
+        self.entries
+            .iter()
+            .map(|(filename, trashed_at)| TrashEntry {
+                filename: filename.clone(),
+                trashed_at_ms: trashed_at
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64,
+            })
+            .collect()
+    }
+
+    /// Remove the uploads trashed longer than `retention` ago, returning them to be purged
+    pub fn take_expired(&mut self, now: SystemTime, retention: Duration) -> Vec<String> {
+        let (expired, kept) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|(_, trashed_at)| *trashed_at + retention <= now);
+        self.entries = kept;
+        expired.into_iter().map(|(filename, _)| filename).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trash_expiry() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let retention = Duration::from_secs(60);
+        let mut trash = Trash::default();
+        trash.add("a.png", now);
+        trash.add("b.mp4", now + Duration::from_secs(30));
+        assert!(trash.contains("a.png"));
+        assert_eq!(trash.entries()[0].trashed_at_ms, 1_000_000_000);
+
+        assert!(
+            trash
+                .take_expired(now + Duration::from_secs(59), retention)
+                .is_empty()
+        );
+        assert_eq!(
+            trash.take_expired(now + Duration::from_secs(60), retention),
+            ["a.png"]
+        );
+        assert!(trash.remove("b.mp4"));
+        assert!(!trash.remove("b.mp4"));
+        assert!(trash.entries().is_empty());
+    }
+}
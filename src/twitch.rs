@@ -0,0 +1,292 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant, SystemTime};
+use thiserror::Error;
+
+const TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
+const STREAMS_URL: &str = "https://api.twitch.tv/helix/streams";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Helix takes at most this many user_login parameters per request
+const MAX_LOGINS_PER_REQUEST: usize = 100;
+
+/// Twitch channels announced on screen when they go live
+#[derive(Clone, Debug, Deserialize)]
+pub struct TwitchConfig {
+    /// App credentials from dev.twitch.tv, used for an app access token
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(default = "default_poll_secs")]
+    pub every_secs: u64,
+    /// Minutes before a channel going live again is announced again, so a stream
+    /// restarting after a crash doesn't take over the screen twice
+    #[serde(default = "default_cooldown_mins")]
+    pub cooldown_mins: u64,
+    /// Domain the display loads the embed from, Twitch refuses embeds without it
+    #[serde(default = "default_embed_parent")]
+    pub embed_parent: String,
+    pub channels: Vec<TwitchChannel>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TwitchChannel {
+    pub login: String,
+    /// Overrides the shared cooldown for this channel
+    pub cooldown_mins: Option<u64>,
+}
+
+fn default_poll_secs() -> u64 {
+    60
+}
+
+fn default_cooldown_mins() -> u64 {
+    60
+}
+
+fn default_embed_parent() -> String {
+    "localhost".to_string()
+}
+
+impl TwitchConfig {
+    pub fn every(&self) -> Duration {
+        Duration::from_secs(self.every_secs.max(10))
+    }
+
+    fn cooldown(&self, login: &str) -> Duration {
+        let minutes = self
+            .channels
+            .iter()
+            .find(|channel| channel.login.eq_ignore_ascii_case(login))
+            .and_then(|channel| channel.cooldown_mins)
+            .unwrap_or(self.cooldown_mins);
+        Duration::from_secs(minutes * 60)
+    }
+
+    /// Player embed for a channel, muted so it can autoplay
+    pub fn embed_url(&self, login: &str) -> String {
+        format!(
+            "https://player.twitch.tv/?channel={}&parent={}&muted=true",
+            login, self.embed_parent
+        )
+    }
+}
+
+/// A live stream as listed by Helix
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LiveStream {
+    pub user_login: String,
+    pub user_name: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub game_name: String,
+}
+
+#[derive(Error, Debug)]
+pub enum TwitchError {
+    #[error("Twitch request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Twitch returned HTTP {0}")]
+    Status(u16),
+    #[error("Unexpected Twitch response")]
+    InvalidResponse,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct StreamsResponse {
+    data: Vec<LiveStream>,
+}
+
+/// Helix client holding the app access token between polls
+pub struct TwitchClient {
+    config: TwitchConfig,
+    http: reqwest::Client,
+    token: Option<(String, Instant)>, // Token and when it expires
+}
+
+impl TwitchClient {
+    pub fn new(config: TwitchConfig) -> Result<Self, TwitchError> {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        Ok(Self {
+            config,
+            http,
+            token: None,
+        })
+    }
+
+    pub fn config(&self) -> &TwitchConfig {
+        &self.config
+    }
+
+    async fn token(&mut self) -> Result<String, TwitchError> {
+        if let Some((token, expires)) = &self.token
+            && Instant::now() < *expires
+        {
+            return Ok(token.clone());
+        }
+        let response = self
+            .http
+            .post(TOKEN_URL)
+            .form(&[
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+                ("grant_type", "client_credentials"),
+            ])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(TwitchError::Status(response.status().as_u16()));
+        }
+        let token: TokenResponse = serde_json::from_slice(&response.bytes().await?)
+            .map_err(|_| TwitchError::InvalidResponse)?;
+        // Renew a minute early rather than fail a poll
+        let expires = Instant::now() + Duration::from_secs(token.expires_in.saturating_sub(60));
+        self.token = Some((token.access_token.clone(), expires));
+        Ok(token.access_token)
+    }
+
+    /// Channels from the config that are live right now
+    pub async fn live_streams(&mut self) -> Result<Vec<LiveStream>, TwitchError> {
+        let token = self.token().await?;
+        let mut streams = Vec::new();
+        for channels in self.config.channels.chunks(MAX_LOGINS_PER_REQUEST) {
+            let logins: Vec<_> = channels
+                .iter()
+                .map(|channel| ("user_login", channel.login.as_str()))
+                .collect();
+            let response = self
+                .http
+                .get(STREAMS_URL)
+                .query(&logins)
+                .header("client-id", &self.config.client_id)
+                .bearer_auth(&token)
+                .send()
+                .await?;
+            if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+                // Revoked or expired early, fetch a new one next poll
+                self.token = None;
+            }
+            if !response.status().is_success() {
+                return Err(TwitchError::Status(response.status().as_u16()));
+            }
+            let page: StreamsResponse = serde_json::from_slice(&response.bytes().await?)
+                .map_err(|_| TwitchError::InvalidResponse)?;
+            streams.extend(page.data);
+        }
+        Ok(streams)
+    }
+}
+
+/// Which channels are live and when each was last announced
+#[derive(Debug, Default)]
+pub struct LiveTracker {
+    live: HashSet<String>,
+    announced_at: HashMap<String, SystemTime>,
+}
+
+impl LiveTracker {
+    /// Record the streams live now, returning those that just went live and are out of
+    /// their cooldown
+    pub fn update(
+        &mut self,
+        streams: Vec<LiveStream>,
+        config: &TwitchConfig,
+        now: SystemTime,
+    ) -> Vec<LiveStream> {
+        let mut went_live = Vec::new();
+        let mut live = HashSet::new();
+        for stream in streams {
+            let login = stream.user_login.to_lowercase();
+            live.insert(login.clone());
+            if self.live.contains(&login) {
+                continue;
+            }
+            let cooled_down = self.announced_at.get(&login).is_none_or(|announced| {
+                now.duration_since(*announced).unwrap_or_default() >= config.cooldown(&login)
+            });
+            if cooled_down {
+                self.announced_at.insert(login, now);
+                went_live.push(stream);
+            } else {
+                tracing::info!("{} is live again, still in cooldown", stream.user_name);
+            }
+        }
+        self.live = live;
+        went_live
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> TwitchConfig {
+        TwitchConfig {
+            client_id: "id".to_string(),
+            client_secret: "secret".to_string(),
+            every_secs: 60,
+            cooldown_mins: 60,
+            embed_parent: "tv.lan".to_string(),
+            channels: vec![
+                TwitchChannel {
+                    login: "alice".to_string(),
+                    cooldown_mins: None,
+                },
+                TwitchChannel {
+                    login: "Bob".to_string(),
+                    cooldown_mins: Some(5),
+                },
+            ],
+        }
+    }
+
+    fn stream(login: &str) -> LiveStream {
+        LiveStream {
+            user_login: login.to_string(),
+            user_name: login.to_string(),
+            title: String::new(),
+            game_name: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_live_tracker_cooldowns() {
+        let config = config();
+        let mut tracker = LiveTracker::default();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let minutes = |minutes: u64| start + Duration::from_secs(minutes * 60);
+
+        let live = tracker.update(vec![stream("alice"), stream("bob")], &config, start);
+        assert_eq!(live.len(), 2);
+        // Still live, nothing new
+        assert!(
+            tracker
+                .update(vec![stream("alice"), stream("bob")], &config, minutes(1))
+                .is_empty()
+        );
+
+        // Both drop and come back ten minutes later, only bob's cooldown is over
+        tracker.update(Vec::new(), &config, minutes(9));
+        let live = tracker.update(vec![stream("alice"), stream("bob")], &config, minutes(10));
+        assert_eq!(live, vec![stream("bob")]);
+
+        tracker.update(Vec::new(), &config, minutes(30));
+        let live = tracker.update(vec![stream("alice")], &config, minutes(61));
+        assert_eq!(live, vec![stream("alice")]);
+    }
+
+    #[test]
+    fn test_embed_url() {
+        assert_eq!(
+            config().embed_url("alice"),
+            "https://player.twitch.tv/?channel=alice&parent=tv.lan&muted=true"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Request and response types of the JSON API and the WebSocket events, for bots and scripts
+//! talking to the server instead of scraping its pages
+//!
+//! The server builds its replies from these types, so they stay in step with what is sent.
+//! Events this version doesn't describe come out as [`WsEvent::Other`]:
+//!
+//! ```
+//! use homies_gaming_backend::types::WsEvent;
+//!
+//! let event: WsEvent =
+//!     serde_json::from_str(r#"{"event": "caption", "text": "gg", "duration_secs": 5}"#).unwrap();
+//! assert_eq!(
+//!     event,
+//!     WsEvent::Caption { text: "gg".to_string(), duration_secs: 5, source: None }
+//! );
+//!
+//! let event: WsEvent = serde_json::from_str(r#"{"event": "reaction", "emoji": "🔥"}"#).unwrap();
+//! assert_eq!(event, WsEvent::Other);
+//! ```
+
+use crate::banner::BannerStatus;
+use crate::events::WarningReason;
+use crate::jobs::{JobKind, JobStage};
+use crate::state::{MediaSource, MediaType};
+use crate::theme::Theme;
+use crate::video_processing::VideoPlatform;
+use serde::{Deserialize, Serialize};
+
+/// Media on screen or waiting for it, as listed by GET /display-state
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MediaItem {
+    pub id: u64,
+    pub filename: String,
+    pub media_type: MediaType,
+    pub caption: String,
+    pub play_secs: u64,
+    pub poster: Option<String>, // URL of a video's first frame
+}
+
+/// A URL download or background job, as listed by GET /jobs and sent in job events
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub id: u64,
+    pub kind: JobKind,
+    pub url: String,
+    pub stage: JobStage,
+    pub percent: f32,
+}
+
+/// What became of an upload
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadOutcome {
+    Shown,
+    Queued,
+    /// Queued until quiet hours end
+    Held,
+    /// Held until unlocked with its passphrase
+    Locked,
+    /// Kept off screen until POST /publish/{staged_id}
+    Staged,
+    /// Its caption tripped the word filter, kept off screen until an admin approves it
+    Review,
+    /// The screen is busy and the display policy refuses new media, retry later
+    Busy,
+    /// Invalid, infected or otherwise not accepted, `message` says why
+    Refused,
+}
+
+/// Reply to an upload sent with `Accept: application/json`, the HTML reply otherwise
+///
+/// ```
+/// use homies_gaming_backend::types::{UploadOutcome, UploadResponse};
+///
+/// let reply = r#"{"outcome": "queued", "filename": "gg.png", "queue_position": 2,
+///     "message": "gg.png queued at position 2"}"#;
+/// let response: UploadResponse = serde_json::from_str(reply).unwrap();
+/// assert_eq!(response.outcome, UploadOutcome::Queued);
+/// assert_eq!(response.queue_position, Some(2));
+/// assert_eq!(response.staged_id, None);
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UploadResponse {
+    pub outcome: UploadOutcome,
+    /// Name the upload was stored under, none when it was refused before being stored
+    #[serde(default)]
+    pub filename: Option<String>,
+    /// 1-based, when queued, held or locked
+    #[serde(default)]
+    pub queue_position: Option<usize>,
+    /// When busy
+    #[serde(default)]
+    pub retry_after_secs: Option<u64>,
+    /// When staged
+    #[serde(default)]
+    pub staged_id: Option<u64>,
+    /// Human readable, in the request's language
+    pub message: String,
+}
+
+impl UploadResponse {
+    pub fn refused(message: impl Into<String>) -> Self {
+        Self {
+            outcome: UploadOutcome::Refused,
+            filename: None,
+            queue_position: None,
+            retry_after_secs: None,
+            staged_id: None,
+            message: message.into(),
+        }
+    }
+}
+
+/// Size and fit on a 16:9 screen of the media in an event
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Layout {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// "landscape", "portrait" or "square"
+    pub orientation: Option<String>,
+    /// "pillarbox" (bars on the sides), "letterbox" (bars top and bottom) or "none"
+    pub letterbox: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LiveStatus {
+    Started,
+    Ended,
+}
+
+/// Event broadcast to `/ws` clients, tagged by its `event` field
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WsEvent {
+    /// An image went on screen, displays reload `url`
+    BrowserBackend {
+        url: String,
+        media_id: u64,
+        layout: Layout,
+        #[serde(default)]
+        source: MediaSource,
+    },
+    /// A video went on screen
+    Video {
+        url: String,
+        media_id: u64,
+        layout: Layout,
+        poster: Option<String>,
+        /// Smaller copy, taken out before the event reaches clients, which get it as `url` on
+        /// a slow link
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        low_url: Option<String>,
+        /// HLS playlist of a long video
+        hls: Option<String>,
+        /// Server time in Unix milliseconds the displays start playing at
+        start_at: Option<u64>,
+        #[serde(default)]
+        source: MediaSource,
+        /// Site a downloaded video came from
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        platform: Option<VideoPlatform>,
+    },
+    /// Caption of the media on screen
+    Caption {
+        text: String,
+        duration_secs: u64,
+        /// Source of the media it goes with
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        source: Option<MediaSource>,
+    },
+    JobProgress { job: JobStatus },
+    JobCancelled { job: JobStatus },
+    /// What an upload went without, e.g. its caption when ffmpeg is missing
+    ProcessingWarning {
+        filename: String,
+        reason: WarningReason,
+        message: String,
+    },
+    ThemeChanged { theme: Theme },
+    /// Banner pinned over the displays, none when it was taken down
+    Banner { banner: Option<BannerStatus> },
+    /// Screen share starting with its playlist, or ending
+    Live { status: LiveStatus, url: Option<String> },
+    /// The client fell behind and missed events, it should reload the display state
+    Resync { missed: u64 },
+    /// Sent only to a display connecting with `?display=`, with the id to reconnect with
+    Registered { id: String, name: String },
+    #[serde(other)]
+    Other,
+}
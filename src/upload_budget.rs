@@ -0,0 +1,97 @@
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// Permits are kilobytes, a semaphore can't hand out more than u32::MAX at once
+const PERMIT_BYTES: u64 = 1024;
+
+/// Bytes of uploads being received at once, shared by every upload route so a burst of big
+/// uploads can't buffer more than the box has memory for
+#[derive(Clone, Debug)]
+pub struct UploadBudget {
+    permits: u32,
+    semaphore: Arc<Semaphore>,
+    rejected: Arc<AtomicU64>,
+}
+
+/// Part of the budget held by an upload until its reply is sent
+#[derive(Debug)]
+pub struct UploadPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BudgetStats {
+    pub budget_bytes: u64,
+    pub in_flight_bytes: u64,
+    /// Uploads turned away since startup because the budget was spent
+    pub rejected: u64,
+}
+
+impl UploadBudget {
+    pub fn new(budget_bytes: u64) -> Self {
+        let permits = u32::try_from(budget_bytes.div_ceil(PERMIT_BYTES))
+            .unwrap_or(u32::MAX)
+            .max(1);
+        Self {
+            permits,
+            semaphore: Arc::new(Semaphore::new(permits as usize)),
+            rejected: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Reserve `bytes` of the budget, or `None` when the uploads in flight leave too little
+    /// An upload bigger than the whole budget takes all of it, so it's only let through while
+    /// nothing else is in flight
+    pub fn try_reserve(&self, bytes: u64) -> Option<UploadPermit> {
+        let permits = u32::try_from(bytes.div_ceil(PERMIT_BYTES))
+            .unwrap_or(u32::MAX)
+            .clamp(1, self.permits);
+        match self.semaphore.clone().try_acquire_many_owned(permits) {
+            Ok(permit) => Some(UploadPermit { _permit: permit }),
+            Err(_) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn stats(&self) -> BudgetStats {
+        let available = self.semaphore.available_permits() as u64;
+        BudgetStats {
+            budget_bytes: u64::from(self.permits) * PERMIT_BYTES,
+            in_flight_bytes: (u64::from(self.permits) - available) * PERMIT_BYTES,
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_budget_reserves_and_releases() {
+        let budget = UploadBudget::new(10 * 1024);
+        let first = budget.try_reserve(6 * 1024).unwrap();
+        assert_eq!(budget.stats().in_flight_bytes, 6 * 1024);
+        assert!(budget.try_reserve(5 * 1024).is_none());
+        let second = budget.try_reserve(4 * 1024).unwrap();
+        drop(first);
+        drop(second);
+
+        // Bigger than the whole budget, allowed alone
+        let whole = budget.try_reserve(100 * 1024).unwrap();
+        assert!(budget.try_reserve(1).is_none());
+        drop(whole);
+        assert_eq!(
+            budget.stats(),
+            BudgetStats {
+                budget_bytes: 10 * 1024,
+                in_flight_bytes: 0,
+                rejected: 2,
+            }
+        );
+    }
+}
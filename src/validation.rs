@@ -0,0 +1,111 @@
+use thiserror::Error;
+
+/// Detected types accepted as display media
+pub const MEDIA_EXTENSIONS: &[&str] = &[
+    // Images
+    "jpg", "png", "gif", "webp", "bmp", "tif", "avif", "svg",
+    // Videos (Ogg video is detected as Ogg audio)
+    "mp4", "m4v", "mov", "avi", "webm", "mkv", "wmv", "flv", "ogg",
+];
+
+/// Detected types accepted into the soundboard
+pub const SOUND_EXTENSIONS: &[&str] = &["mp3", "wav", "ogg", "opus", "flac", "m4a"];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ValidationError {
+    #[error("File content is not a recognized image, video or sound")]
+    Unrecognized,
+    #[error("{0} files are not allowed here")]
+    NotAllowed(&'static str),
+}
+
+/// Extension of the file type the content actually is
+pub fn detect_extension(data: &[u8]) -> Option<&'static str> {
+    // SVG is text with no magic bytes of its own, infer only sees XML
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+    let head = head.trim_start();
+    if (head.starts_with("<?xml") || head.starts_with("<svg")) && head.contains("<svg") {
+        return Some("svg");
+    }
+    infer::get(data).map(|kind| kind.extension())
+}
+
+// Spellings of the same type that shouldn't trigger a rename
+fn canonical_extension(extension: &str) -> String {
+    match extension.to_lowercase().as_str() {
+        "jpeg" | "jpe" => "jpg".to_string(),
+        "tiff" => "tif".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Check an upload's content against the types allowed here
+/// Returns the filename to store it under, with the extension rewritten if it lied about the type
+pub fn validate(filename: &str, data: &[u8], allowed: &[&str]) -> Result<String, ValidationError> {
+    let detected = detect_extension(data).ok_or(ValidationError::Unrecognized)?;
+    if !allowed.contains(&detected) {
+        return Err(ValidationError::NotAllowed(detected));
+    }
+
+    let (stem, claimed) = match filename.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (filename, None),
+    };
+    if claimed.is_some_and(|claimed| canonical_extension(claimed) == detected) {
+        return Ok(filename.to_string());
+    }
+
+    tracing::warn!("{} is actually {}, renaming", filename, detected);
+    Ok(format!("{}.{}", stem, detected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    const MP4_ISOM: &[u8] = b"\0\0\0\x20ftypisom\0\0\x02\0isomiso2avc1mp41";
+    const MP3: &[u8] = b"ID3\x04\0\0\0\0\0\0";
+
+    #[test]
+    fn test_validate_accepts_matching_types() {
+        assert_eq!(validate("cat.png", PNG, MEDIA_EXTENSIONS), Ok("cat.png".to_string()));
+        // isom is the most common mp4 brand
+        assert_eq!(validate("clip.mp4", MP4_ISOM, MEDIA_EXTENSIONS), Ok("clip.mp4".to_string()));
+        assert_eq!(
+            validate("photo.JPEG", b"\xFF\xD8\xFF\xE0\0\x10JFIF", MEDIA_EXTENSIONS),
+            Ok("photo.JPEG".to_string())
+        );
+        assert_eq!(validate("airhorn.mp3", MP3, SOUND_EXTENSIONS), Ok("airhorn.mp3".to_string()));
+        assert_eq!(
+            validate("logo.svg", b"<?xml version=\"1.0\"?>\n<svg></svg>", MEDIA_EXTENSIONS),
+            Ok("logo.svg".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_renames_mismatched_extension() {
+        assert_eq!(validate("cat.jpg", PNG, MEDIA_EXTENSIONS), Ok("cat.png".to_string()));
+        assert_eq!(validate("clip", MP4_ISOM, MEDIA_EXTENSIONS), Ok("clip.mp4".to_string()));
+    }
+
+    #[test]
+    fn test_validate_rejects() {
+        assert_eq!(
+            validate("evil.png", b"hello world", MEDIA_EXTENSIONS),
+            Err(ValidationError::Unrecognized)
+        );
+        assert_eq!(
+            validate("evil.png", b"<html><script>alert(1)</script>", MEDIA_EXTENSIONS),
+            Err(ValidationError::NotAllowed("html"))
+        );
+        assert_eq!(
+            validate("song.png", MP3, MEDIA_EXTENSIONS),
+            Err(ValidationError::NotAllowed("mp3"))
+        );
+        assert_eq!(
+            validate("cat.mp3", PNG, SOUND_EXTENSIONS),
+            Err(ValidationError::NotAllowed("png"))
+        );
+    }
+}
@@ -1,19 +1,464 @@
+use crate::data_dirs::DataDirs;
 use crate::errors::AppError;
+use crate::events::WarningReason;
+use crate::filter_graph::{CaptionAnimation, DrawText, FilterGraph, VideoEffect};
+use crate::i18n::{Locale, Msg};
+use crate::jobs::{JobStage, ProgressReporter};
+use crate::media_probe::MediaProbe;
+use crate::retry::{self, RetryPolicy};
 use crate::utils::{sanitize_filename, validate_file_path};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::process::Command;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 use tokio::process::Command as AsyncCommand;
 
+/// Font burned-in captions are drawn with, ffmpeg's default font is used without it
+pub const CAPTION_FONT: &str = "/usr/share/fonts/truetype/wintc/impact.ttf";
+/// URL of the posters, in [`DataDirs::posters`]
+pub const POSTER_URL: &str = "/uploads/posters";
+/// URL of the animated previews, in [`DataDirs::previews`]
+pub const PREVIEW_URL: &str = "/uploads/previews";
+/// URL of the low renditions, in [`DataDirs::renditions`]
+pub const RENDITION_URL: &str = "/uploads/renditions";
+/// URL of the HLS playlists, in [`DataDirs::hls`]
+pub const HLS_URL: &str = "/uploads/hls";
+/// Playlist in each video's directory of [`DataDirs::hls`]
+pub const HLS_PLAYLIST: &str = "index.m3u8";
+// Length of the HLS segments, in seconds
+const HLS_SEGMENT_SECS: &str = "4";
+/// Height of the copies in [`DataDirs::renditions`], videos no taller than this don't get one
+pub const LOW_RENDITION_HEIGHT: u32 = 480;
+// Length of the animated previews, in seconds
+const PREVIEW_SECS: &str = "2";
+// Frames compared to find where the action is in a vertical video, tiny is plenty
+const MOTION_WIDTH: u32 = 32;
+const MOTION_HEIGHT: u32 = 64;
+
+/// Hardware acceleration requested in the configuration
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HwAccelSetting {
+    #[default]
+    Auto,
+    None,
+    Cuda,
+    Vaapi,
+}
+
+/// Hardware acceleration actually used for encoding, resolved once at startup
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HwAccel {
+    #[default]
+    None,
+    Cuda,
+    Vaapi,
+}
+
+impl HwAccel {
+    /// Resolve the configured setting, probing the system only for "auto"
+    pub fn resolve(setting: HwAccelSetting) -> Self {
+        let hw_accel = match setting {
+            HwAccelSetting::None => HwAccel::None,
+            HwAccelSetting::Cuda => HwAccel::Cuda,
+            HwAccelSetting::Vaapi => HwAccel::Vaapi,
+            HwAccelSetting::Auto => {
+                if VideoProcessor::is_cuda_available() {
+                    HwAccel::Cuda
+                } else if VideoProcessor::is_vaapi_available() {
+                    HwAccel::Vaapi
+                } else {
+                    HwAccel::None
+                }
+            }
+        };
+        tracing::info!("Hardware acceleration: {:?} (configured: {:?})", hw_accel, setting);
+        hw_accel
+    }
+
+    /// Decoder options, placed before the input
+    /// Decoded frames come back to system memory, where the caption and watermark filters run
+    fn input_args(self) -> &'static [&'static str] {
+        match self {
+            HwAccel::None => &[],
+            HwAccel::Cuda => &["-hwaccel", "cuda"],
+            HwAccel::Vaapi => &["-vaapi_device", "/dev/dri/renderD128", "-hwaccel", "vaapi"],
+        }
+    }
+
+    /// Encoder passed to ffmpeg as `-c:v`
+    pub fn video_codec(self) -> &'static str {
+        match self {
+            HwAccel::None => "libx264",
+            HwAccel::Cuda => "h264_nvenc",
+            HwAccel::Vaapi => "h264_vaapi",
+        }
+    }
+}
+
+/// Speed/size trade-off for re-encoded videos
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityProfile {
+    /// Quick encodes capped at 720p, for getting clips on the TV fast
+    Fast,
+    /// Capped at 1080p
+    #[default]
+    Balanced,
+    /// Slow encodes at source resolution, for archiving
+    Quality,
+}
+
+impl QualityProfile {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "fast" => Some(QualityProfile::Fast),
+            "balanced" => Some(QualityProfile::Balanced),
+            "quality" => Some(QualityProfile::Quality),
+            _ => None,
+        }
+    }
+
+    fn max_height(self) -> Option<u32> {
+        match self {
+            QualityProfile::Fast => Some(720),
+            QualityProfile::Balanced => Some(1080),
+            QualityProfile::Quality => None,
+        }
+    }
+
+    /// Encoder preset and rate control arguments for the given encoder
+    pub(crate) fn encoder_args(self, hw_accel: HwAccel) -> Vec<&'static str> {
+        match hw_accel {
+            HwAccel::None => match self {
+                QualityProfile::Fast => vec!["-preset", "veryfast", "-crf", "28"],
+                QualityProfile::Balanced => vec!["-preset", "fast", "-crf", "23"],
+                QualityProfile::Quality => vec!["-preset", "slow", "-crf", "18"],
+            },
+            HwAccel::Cuda => match self {
+                QualityProfile::Fast => vec!["-preset", "p1", "-cq", "28"],
+                QualityProfile::Balanced => vec!["-preset", "p4", "-cq", "23"],
+                QualityProfile::Quality => vec!["-preset", "p7", "-cq", "19"],
+            },
+            HwAccel::Vaapi => match self {
+                QualityProfile::Fast => vec!["-qp", "28"],
+                QualityProfile::Balanced => vec!["-qp", "23"],
+                QualityProfile::Quality => vec!["-qp", "19"],
+            },
+        }
+    }
+}
+
+/// Corner of the frame the watermark is placed in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// PNG overlaid on processed media
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Watermark {
+    pub path: String,
+    #[serde(default)]
+    pub corner: Corner,
+    /// Distance from the frame edges in pixels
+    #[serde(default = "default_watermark_margin")]
+    pub margin: u32,
+    /// Also watermark uploaded images, not just processed videos
+    #[serde(default)]
+    pub images: bool,
+}
+
+fn default_watermark_margin() -> u32 {
+    20
+}
+
+impl Watermark {
+    /// Add the watermark overlay on top of everything already in the graph
+    pub fn overlay(&self, graph: FilterGraph) -> FilterGraph {
+        let margin = self.margin;
+        let (x, y) = match self.corner {
+            Corner::TopLeft => (margin.to_string(), margin.to_string()),
+            Corner::TopRight => (format!("W-w-{}", margin), margin.to_string()),
+            Corner::BottomLeft => (margin.to_string(), format!("H-h-{}", margin)),
+            Corner::BottomRight => (format!("W-w-{}", margin), format!("H-h-{}", margin)),
+        };
+        graph.overlay(&self.path, &x, &y)
+    }
+}
+
+/// How vertical videos fill a horizontal display
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Framing {
+    /// Played as they are, with black bars on the sides
+    #[default]
+    Original,
+    /// The sides filled with a blurred, zoomed in copy of the video
+    BlurFill,
+    /// Cropped to 16:9 around where things move the most
+    Crop,
+}
+
+impl Framing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "original" => Some(Framing::Original),
+            "blur-fill" | "blur" => Some(Framing::BlurFill),
+            "crop" => Some(Framing::Crop),
+            _ => None,
+        }
+    }
+}
+
+/// Background shown through the green screen of videos uploaded with it on
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Chromakey {
+    /// Image or video, looped for as long as the upload plays
+    pub background: String,
+    /// Color keyed out, in any form ffmpeg reads
+    #[serde(default = "default_key_color")]
+    pub color: String,
+    /// How close to the color a pixel must be to be keyed out, from 0.01 to 1
+    #[serde(default = "default_key_similarity")]
+    pub similarity: f32,
+    /// Softness of the edges, 0 keys out the matching pixels fully
+    #[serde(default = "default_key_blend")]
+    pub blend: f32,
+}
+
+fn default_key_color() -> String {
+    "0x00FF00".to_string()
+}
+
+fn default_key_similarity() -> f32 {
+    0.1
+}
+
+fn default_key_blend() -> f32 {
+    0.1
+}
+
+impl Chromakey {
+    /// The same key with the similarity and blend the uploader picked, kept in range
+    pub fn tuned(mut self, similarity: Option<f32>, blend: Option<f32>) -> Self {
+        if let Some(similarity) = similarity.filter(|value| value.is_finite()) {
+            self.similarity = similarity.clamp(0.01, 1.0);
+        }
+        if let Some(blend) = blend.filter(|value| value.is_finite()) {
+            self.blend = blend.clamp(0.0, 1.0);
+        }
+        self
+    }
+
+    /// Key out the color and put what's left on top of the background
+    pub fn apply(&self, graph: FilterGraph) -> FilterGraph {
+        graph
+            .chromakey(&self.color, self.similarity, self.blend)
+            .background(&self.background)
+    }
+}
+
+/// Format still uploads are converted to, smaller files load faster on a display over WiFi
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    /// Keep uploads as they are
+    #[default]
+    Original,
+    Webp,
+    Avif,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "original" => Some(ImageFormat::Original),
+            "webp" => Some(ImageFormat::Webp),
+            "avif" => Some(ImageFormat::Avif),
+            _ => None,
+        }
+    }
+
+    /// Extension of converted files, None when nothing gets converted
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            ImageFormat::Original => None,
+            ImageFormat::Webp => Some("webp"),
+            ImageFormat::Avif => Some("avif"),
+        }
+    }
+
+    fn encoder_args(self) -> &'static [&'static str] {
+        match self {
+            ImageFormat::Original => &[],
+            ImageFormat::Webp => &["-c:v", "libwebp", "-quality", "80"],
+            ImageFormat::Avif => &["-c:v", "libaom-av1", "-still-picture", "1", "-crf", "30"],
+        }
+    }
+}
+
+/// Conversion of large JPEG and PNG uploads
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ImageConversion {
+    /// Format used when the upload doesn't pick one
+    pub format: ImageFormat,
+    /// Smaller uploads are kept as they are
+    pub min_bytes: u64,
+}
+
+impl Default for ImageConversion {
+    fn default() -> Self {
+        Self {
+            format: ImageFormat::Original,
+            min_bytes: 512 * 1024,
+        }
+    }
+}
+
+impl ImageConversion {
+    /// Whether an upload is worth converting, GIFs would lose their animation
+    pub fn applies_to(&self, filename: &str, size: u64) -> bool {
+        let ext = filename.split('.').next_back().unwrap_or("").to_lowercase();
+        matches!(ext.as_str(), "jpg" | "jpeg" | "png") && size >= self.min_bytes
+    }
+}
+
+/// How re-encoded videos are produced
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EncodeOptions {
+    pub hw_accel: HwAccel,
+    pub quality: QualityProfile,
+    /// Watermark to overlay, if enabled for this upload
+    pub watermark: Option<Watermark>,
+    /// Largest video the display can decode, by its short side, whatever the quality profile
+    /// allows
+    pub display_max_height: Option<u32>,
+    pub caption_animation: CaptionAnimation,
+    /// Seconds into the video before the caption appears
+    pub caption_delay_secs: f64,
+    /// SRT file of speech captions to burn in
+    pub subtitles: Option<String>,
+    pub effect: VideoEffect,
+    /// Green screen replacement, if the upload asked for it
+    pub chromakey: Option<Chromakey>,
+    pub framing: Framing,
+    /// Fonts for captions with emoji or RTL/complex scripts, in fallback order
+    pub caption_fonts: Vec<String>,
+}
+
+impl EncodeOptions {
+    /// The lower of the quality profile's and the display's resolution caps, for the short side
+    /// so portrait videos get as many lines as landscape ones
+    fn max_height(&self) -> Option<u32> {
+        match (self.quality.max_height(), self.display_max_height) {
+            (Some(quality), Some(display)) => Some(quality.min(display)),
+            (quality, display) => quality.or(display),
+        }
+    }
+
+    /// Output dimensions after applying the resolution cap, keeping the aspect ratio and even sizes
+    fn capped_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let short_side = width.min(height);
+        match self.max_height() {
+            Some(max_height) if short_side > max_height => {
+                let scale = |side: u32| {
+                    (side as u64 * max_height as u64 / short_side as u64) as u32 & !1
+                };
+                (scale(width), scale(height))
+            }
+            _ => (width, height),
+        }
+    }
+
+    /// Size to scale down to, none when the source already fits
+    fn scaled_size(&self, width: u32, height: u32) -> Option<(u32, u32)> {
+        Some(self.capped_size(width, height)).filter(|size| *size != (width, height))
+    }
+
+    /// Whether a video of this size gets reframed to 16:9
+    pub fn needs_framing(&self, width: u32, height: u32) -> bool {
+        self.framing != Framing::Original && height > width
+    }
+
+    /// 16:9 output dimensions of a reframed vertical video, at most as tall as the part kept
+    fn framed_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let source_height = match self.framing {
+            Framing::Crop => crop_height(width),
+            _ => height,
+        };
+        let height = self
+            .max_height()
+            .map_or(source_height, |max_height| max_height.min(source_height))
+            & !1;
+        ((height as u64 * 16 / 9) as u32 & !1, height)
+    }
+
+    /// Output dimensions, reframed or capped
+    fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.needs_framing(width, height) {
+            true => self.framed_size(width, height),
+            false => self.capped_size(width, height),
+        }
+    }
+
+    /// Whether a video of this size is over the profile's or the display's cap, so it's
+    /// re-encoded even without anything to burn in
+    pub fn needs_downscale(&self, width: u32, height: u32) -> bool {
+        self.scaled_size(width, height).is_some()
+    }
+}
+
+/// A downloaded video, shown without its caption or watermark if re-encoding it failed
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcessedVideo {
+    pub filename: String,
+    /// Why the video wasn't re-encoded, when it was asked to be
+    pub error: Option<String>,
+    /// What the video went without, for the uploader
+    pub warnings: Vec<WarningReason>,
+}
+
+// Caption rendered to a PNG, overlaid where drawtext would have drawn it
+// Only the delay applies, fade and pulse animations are drawtext's
+struct CaptionImage {
+    path: String,
+    font_size: u32,
+    enable: Option<String>,
+}
+
+impl CaptionImage {
+    fn overlay(&self, graph: FilterGraph) -> FilterGraph {
+        let y = format!("H-h-{}", self.font_size + 20);
+        match &self.enable {
+            Some(enable) => graph.overlay_while(&self.path, "(W-w)/2", &y, enable),
+            None => graph.overlay(&self.path, "(W-w)/2", &y),
+        }
+    }
+}
+
 pub struct VideoProcessor;
 
 impl VideoProcessor {
-    /// Process a video file to add caption overlay using ffmpeg
-    /// Returns the path to the processed video file
-    pub async fn add_caption_overlay(
+    /// Re-encode a video with the configured quality, adding the caption (if any) and watermark
+    /// Returns what the encode had to give up on, when it only worked with the fallback
+    pub async fn encode_video(
+        uploads_dir: &str,
         input_path: &str,
         output_path: &str,
         caption: &str,
-    ) -> Result<(), AppError> {
+        options: &EncodeOptions,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Vec<WarningReason>, AppError> {
+        let EncodeOptions { hw_accel, quality, .. } = *options;
         // Sanitize and validate input and output paths
         let input_filename = sanitize_filename(input_path)
             .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
@@ -21,175 +466,854 @@ impl VideoProcessor {
             .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output filename")))?;
             
         // Validate that both paths are within the uploads directory
-        let validated_input_path = validate_file_path("uploads", &input_filename)
+        let validated_input_path = validate_file_path(uploads_dir, &input_filename)
             .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
-        let validated_output_path = validate_file_path("uploads", &output_filename)
+        let validated_output_path = validate_file_path(uploads_dir, &output_filename)
             .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output file path")))?;
 
         // Get video dimensions first
         let video_info = Self::get_video_info(&validated_input_path).await?;
 
-        // Escape caption text for ffmpeg
-        let escaped_caption = escape_ffmpeg_text(caption);
+        // The caption is drawn after scaling, so size it for the output resolution
+        let (width, height) = options.output_size(video_info.width, video_info.height);
+        let resize = Self::resize_graph(&validated_input_path, &video_info, options).await;
 
         // Calculate font size based on video resolution
-        let font_size = Self::calculate_font_size(video_info.width, video_info.height);
-        let shadow_offset = (font_size as f32 * 0.04).max(1.0) as u32; // 4% of font size, minimum 1px
-        let bottom_margin = font_size + 20; // Font size + some padding
+        let font_size = Self::calculate_font_size(width, height);
 
         tracing::info!(
-            "Video resolution: {}x{}, calculated font size: {}",
+            "Video resolution: {}x{} (output {}x{}), calculated font size: {}",
             video_info.width,
             video_info.height,
+            width,
+            height,
             font_size
         );
 
+        tracing::info!("Using hardware acceleration: {:?}, quality: {:?}", hw_accel, quality);
+
+        let (caption_text, caption_image) = Self::caption_layer(
+            caption,
+            options,
+            (width, font_size),
+            video_info
+                .duration_secs
+                .map(|secs| options.effect.duration(secs)),
+            &validated_output_path,
+        )
+        .await;
+
+        // Build the filter graph with dynamic font sizing and wrapped text
+        let mut graph = resize.clone();
+        if let Some(subtitles) = &options.subtitles {
+            graph = graph.subtitles(subtitles);
+        }
+        // Before the caption, so it reads normally whatever the effect does to the video
+        graph = graph.effect(options.effect);
+        if let Some(chromakey) = &options.chromakey {
+            graph = chromakey.apply(graph);
+        }
+        if let Some(text) = &caption_text {
+            graph = graph.drawtext(
+                &text
+                    .clone()
+                    .fontfile(CAPTION_FONT),
+            );
+        }
+        if let Some(image) = &caption_image {
+            graph = image.overlay(graph);
+        }
+        if let Some(watermark) = &options.watermark {
+            graph = watermark.overlay(graph);
+        }
+
+        // Try with Impact font first, fallback to Liberation Sans Bold
+        let args = Self::encode_args(&validated_input_path, &validated_output_path, graph, options);
+
+        tracing::info!("Processing video with caption: {}", caption);
+        tracing::debug!("FFmpeg command: ffmpeg {:?}", args);
+
+        // A busy GPU (encoder session limit) clears up quickly, retry once
+        let output = RetryPolicy::default()
+            .max_attempts(2)
+            .output_cancellable("ffmpeg encode", retry::command("ffmpeg", &args), cancel)
+            .await;
+
+        let encoded = match output {
+            Err(e) => {
+                tracing::error!("Failed to execute ffmpeg: {}", e);
+                Err(AppError::IoError(std::io::Error::other("Video processing failed")))
+            }
+            Ok(output) if !output.status.success() => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                tracing::error!("FFmpeg failed: {}", stderr);
+
+                // Try fallback with system default font
+                Self::encode_video_fallback(
+                    &validated_input_path,
+                    &validated_output_path,
+                    caption_text.as_ref(),
+                    caption_image.as_ref(),
+                    options,
+                    resize,
+                    cancel,
+                )
+                .await
+            }
+            Ok(_) => {
+                tracing::info!("Video processing completed successfully");
+                Ok(Vec::new())
+            }
+        };
+        if let Some(image) = &caption_image
+            && let Err(e) = tokio::fs::remove_file(&image.path).await
+        {
+            tracing::warn!("Failed to remove caption image {}: {}", image.path, e);
+        }
+        encoded
+    }
+
+    // The caption as burned into a video `width` wide: drawn by drawtext, or rendered to a PNG
+    // next to `output_path` for the text drawtext can't do
+    async fn caption_layer(
+        caption: &str,
+        options: &EncodeOptions,
+        (width, font_size): (u32, u32),
+        duration_secs: Option<f64>,
+        output_path: &str,
+    ) -> (Option<DrawText>, Option<CaptionImage>) {
+        // drawtext has no glyphs for emoji and does no shaping, those captions are rendered
+        // with the font chain by pango, which shapes with HarfBuzz
+        let rendered = has_emoji(caption) || needs_shaping(caption);
+        let caption_image = match rendered && !options.caption_fonts.is_empty() {
+            true => Self::render_caption(caption, options, font_size, width, output_path).await,
+            false => None,
+        };
         // Wrap text to fit within video width
-        let wrapped_caption = Self::wrap_text(&escaped_caption, video_info.width, font_size);
-
-        // Check for hardware acceleration
-        let (use_hw_accel, hw_accel_args, filter_prefix, filter_suffix, video_codec) = if Self::is_cuda_available() {
-            tracing::info!("CUDA detected, using GPU acceleration");
-            (
-                true,
-                vec!["-hwaccel", "cuda", "-hwaccel_output_format", "cuda"],
-                "hwupload_cuda,",
-                ",hwdownload",
-                "h264_nvenc"
-            )
-        } else if Self::is_vaapi_available() {
-            tracing::info!("VAAPI detected, using GPU acceleration");
-            (
-                true,
-                vec!["-vaapi_device", "/dev/dri/renderD128", "-hwaccel", "vaapi", "-hwaccel_output_format", "vaapi"],
-                "hwupload,",
-                "",
-                "h264_vaapi"
+        let caption_text = (!caption.is_empty() && caption_image.is_none()).then(|| {
+            DrawText::caption(&Self::wrap_text(caption, width, font_size), font_size).animate(
+                options.caption_animation,
+                options.caption_delay_secs,
+                duration_secs,
             )
-        } else {
-            tracing::info!("No hardware acceleration available, using CPU processing");
-            (false, vec![], "", "", "libx264")
+        });
+        (caption_text, caption_image)
+    }
+
+    /// Draw the caption on the first frame of a video as the encode would, saved as a JPEG
+    /// Without animation or delay, so it shows whatever the upload picked
+    pub async fn preview_caption(
+        uploads_dir: &str,
+        input_path: &str,
+        output_path: &str,
+        caption: &str,
+        options: &EncodeOptions,
+    ) -> Result<(), AppError> {
+        let input_filename = sanitize_filename(input_path)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let output_filename = sanitize_filename(output_path)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output filename")))?;
+        let validated_input_path = validate_file_path(uploads_dir, &input_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
+        let validated_output_path = validate_file_path(uploads_dir, &output_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output file path")))?;
+
+        let video_info = Self::get_video_info(&validated_input_path).await?;
+        let options = EncodeOptions {
+            caption_animation: CaptionAnimation::Static,
+            caption_delay_secs: 0.0,
+            ..options.clone()
         };
+        let (width, height) = options.output_size(video_info.width, video_info.height);
+        let font_size = Self::calculate_font_size(width, height);
+        let (caption_text, caption_image) = Self::caption_layer(
+            caption,
+            &options,
+            (width, font_size),
+            None,
+            &validated_output_path,
+        )
+        .await;
+
+        let mut graph = Self::resize_graph(&validated_input_path, &video_info, &options).await;
+        if let Some(text) = caption_text {
+            // Like the fallback encode, ffmpeg's default font when Impact isn't installed
+            graph = match std::path::Path::new(CAPTION_FONT).is_file() {
+                true => graph.drawtext(&text.fontfile(CAPTION_FONT)),
+                false => graph.drawtext(&text),
+            };
+        }
+        if let Some(image) = &caption_image {
+            graph = image.overlay(graph);
+        }
+        if let Some(watermark) = &options.watermark {
+            graph = watermark.overlay(graph);
+        }
+        let filter_args = graph.to_args();
+
+        let mut args = vec!["-i", validated_input_path.as_str()];
+        args.extend(filter_args.iter().map(String::as_str));
+        args.extend(["-frames:v", "1", "-q:v", "3", "-y", &validated_output_path]);
+        let output = AsyncCommand::new("ffmpeg").args(&args).output().await;
 
-        // Build ffmpeg command with dynamic font sizing and wrapped text
-        let filter_complex = format!(
-            "{}drawtext=text='{}':fontfile=/usr/share/fonts/truetype/wintc/impact.ttf:fontsize={}:fontcolor=white:x=(w-text_w)/2:y=h-text_h-{}:shadowcolor=black:shadowx={}:shadowy={}:line_spacing=5{}",
-            filter_prefix, wrapped_caption, font_size, bottom_margin, shadow_offset, shadow_offset, filter_suffix
+        if let Some(image) = &caption_image
+            && let Err(e) = tokio::fs::remove_file(&image.path).await
+        {
+            tracing::warn!("Failed to remove caption image {}: {}", image.path, e);
+        }
+        let output = output?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::error!("FFmpeg caption preview failed: {}", stderr);
+            return Err(AppError::IoError(std::io::Error::other(
+                "Caption preview failed",
+            )));
+        }
+        Ok(())
+    }
+
+    // Render a caption to a transparent PNG next to the output with pango-view, whose font
+    // fallback finds the emoji and scripts Impact lacks; None (drawtext) when that fails
+    async fn render_caption(
+        caption: &str,
+        options: &EncodeOptions,
+        font_size: u32,
+        width: u32,
+        output_path: &str,
+    ) -> Option<CaptionImage> {
+        let path = format!("{}.caption.png", output_path);
+        let font = format!("{} {}", options.caption_fonts.join(", "), font_size);
+        let output = AsyncCommand::new("pango-view")
+            .args(["--no-display", "--pixels", "--align=center", "--wrap=word-char"])
+            .args(["--background=transparent", "--foreground=white", "--margin=0"])
+            .arg(format!("--font={}", font))
+            // Same margins as the wrapped drawtext captions
+            .arg(format!("--width={}", width * 9 / 10))
+            .arg(format!("--output={}", path))
+            .arg(format!("--text={}", caption))
+            .output()
+            .await;
+        match output {
+            Ok(output) if output.status.success() => {
+                tracing::info!("Rendered caption to {}", path);
+                let delay = options.caption_delay_secs;
+                Some(CaptionImage {
+                    path,
+                    font_size,
+                    enable: (delay > 0.0).then(|| format!("gte(t,{})", delay)),
+                })
+            }
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                tracing::warn!("pango-view failed, caption drawn without shaping: {}", stderr);
+                None
+            }
+            Err(e) => {
+                tracing::warn!("pango-view not available, caption drawn without shaping: {}", e);
+                None
+            }
+        }
+    }
+
+    // Arguments of an encode through `graph`, which only holds software filters: the frames
+    // are uploaded to the GPU after the last of them when the encoder needs it
+    fn encode_args(
+        input_path: &str,
+        output_path: &str,
+        graph: FilterGraph,
+        options: &EncodeOptions,
+    ) -> Vec<String> {
+        let hw_accel = options.hw_accel;
+        // Hardware acceleration args are input options, so they go before the input
+        let mut args: Vec<String> =
+            hw_accel.input_args().iter().map(|arg| arg.to_string()).collect();
+        args.extend(["-i".to_string(), input_path.to_string()]);
+        args.extend(graph.hwupload(hw_accel).to_args());
+        // Audio is copied without re-encoding unless an effect retimes it
+        args.extend(
+            options
+                .effect
+                .audio_args()
+                .iter()
+                .chain(&["-c:v", hw_accel.video_codec()])
+                .chain(&options.quality.encoder_args(hw_accel))
+                .chain(&["-y", output_path]) // Overwrite output file
+                .map(|arg| arg.to_string()),
         );
+        args
+    }
 
-        // Try with Impact font first, fallback to Liberation Sans Bold
-        let mut cmd = AsyncCommand::new("ffmpeg");
-        
-        // Build arguments correctly
-        let mut args = vec!["-i", &validated_input_path];
-        
-        // Add hardware acceleration args if available (as input options)
-        if use_hw_accel {
-            args.extend_from_slice(&hw_accel_args);
+    // Scaling down to the resolution cap, or reframing a vertical video
+    async fn resize_graph(
+        input_path: &str,
+        info: &VideoInfo,
+        options: &EncodeOptions,
+    ) -> FilterGraph {
+        let graph = FilterGraph::new();
+        if !options.needs_framing(info.width, info.height) {
+            return match options.scaled_size(info.width, info.height) {
+                Some((width, height)) => graph.scale(width as i32, height as i32),
+                None => graph,
+            };
         }
-        
-        // Add processing args
-        args.extend(&[
-            "-vf",
-            &filter_complex,
-            "-c:a",
-            "copy", // Copy audio without re-encoding
+        let (width, height) = options.framed_size(info.width, info.height);
+        match options.framing {
+            Framing::Crop => {
+                let crop = crop_height(info.width);
+                let center = Self::action_center(input_path).await.unwrap_or(0.5);
+                let y = crop_offset(info.height, crop, center);
+                tracing::info!("Cropping {}x{} at y={} around the action", info.width, crop, y);
+                graph
+                    .crop(info.width, crop, 0, y)
+                    .scale(width as i32, height as i32)
+            }
+            _ => graph.blur_fill(width, height),
+        }
+    }
+
+    /// Vertical position of the motion in the first minute of a video, from 0 (top) to 1
+    async fn action_center(input_path: &str) -> Option<f64> {
+        let filter = format!(
+            "fps=2,scale={}:{},format=gray,tblend=all_mode=difference",
+            MOTION_WIDTH, MOTION_HEIGHT
+        );
+        let output = AsyncCommand::new("ffmpeg")
+            .args(["-v", "error", "-t", "60", "-i", input_path, "-vf", &filter])
+            .args(["-f", "rawvideo", "-"])
+            .output()
+            .await
+            .ok()
+            .filter(|output| output.status.success())?;
+        motion_center(&output.stdout, MOTION_WIDTH, MOTION_HEIGHT)
+    }
+
+    /// Re-encode like [`Self::encode_video`] and check the output plays, so the caller only
+    /// deletes the original once there is something to show instead. Broken output is removed
+    pub async fn encode_verified(
+        uploads_dir: &str,
+        input_path: &str,
+        output_path: &str,
+        caption: &str,
+        options: &EncodeOptions,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Vec<WarningReason>, AppError> {
+        let encoded =
+            Self::encode_video(uploads_dir, input_path, output_path, caption, options, cancel);
+        let verified = match encoded.await {
+            Ok(warnings) if MediaProbe::probe(output_path).await.is_playable_video() => {
+                Ok(warnings)
+            }
+            Ok(_) => Err(AppError::IoError(std::io::Error::other(
+                "Processed video is not playable",
+            ))),
+            Err(e) => Err(e),
+        };
+        if verified.is_err()
+            && let Err(e) = tokio::fs::remove_file(output_path).await
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::warn!("Failed to remove broken output {}: {}", output_path, e);
+        }
+        verified
+    }
+
+    /// Fallback method using system default font, on the paths encode_video validated
+    async fn encode_video_fallback(
+        validated_input_path: &str,
+        validated_output_path: &str,
+        caption_text: Option<&DrawText>,
+        caption_image: Option<&CaptionImage>,
+        options: &EncodeOptions,
+        resize: FilterGraph,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Vec<WarningReason>, AppError> {
+        // Simpler filter without specific font file but with dynamic sizing
+        let mut graph = resize;
+        if let Some(subtitles) = &options.subtitles {
+            graph = graph.subtitles(subtitles);
+        }
+        graph = graph.effect(options.effect);
+        if let Some(chromakey) = &options.chromakey {
+            graph = chromakey.apply(graph);
+        }
+        if let Some(text) = caption_text {
+            graph = graph.drawtext(text);
+        }
+        if let Some(image) = caption_image {
+            graph = image.overlay(graph);
+        }
+        if let Some(watermark) = &options.watermark {
+            graph = watermark.overlay(graph);
+        }
+        let filter_args = graph.to_args();
+
+        // Base arguments - just input file (no hardware acceleration in fallback)
+        let mut args = vec!["-i", validated_input_path];
+        args.extend(filter_args.iter().map(String::as_str));
+        args.extend(options.effect.audio_args());
+        args.extend(["-c:v", "libx264"]); // Always use software encoder in fallback
+        args.extend(options.quality.encoder_args(HwAccel::None));
+        args.extend(["-y", validated_output_path]);
+
+        let output = RetryPolicy::default()
+            .max_attempts(2)
+            .output_cancellable("ffmpeg encode fallback", retry::command("ffmpeg", &args), cancel)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute ffmpeg fallback: {}", e);
+                AppError::IoError(std::io::Error::other("Video processing failed"))
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::error!("FFmpeg fallback failed: {}", stderr);
+            return Err(AppError::IoError(std::io::Error::other(format!(
+                "FFmpeg processing failed: {}",
+                stderr
+            ))));
+        }
+
+        tracing::info!("Video processing completed with fallback font");
+        Ok(fallback_warnings(caption_text.is_some(), options.hw_accel))
+    }
+
+    /// Save the first frame of an uploaded video as a JPEG in [`DataDirs::posters`], returning its
+    /// URL
+    pub async fn extract_poster(dirs: &DataDirs, filename: &str) -> Result<String, AppError> {
+        let filename = sanitize_filename(filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let validated_input_path = validate_file_path(&dirs.uploads, &filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
+        let posters = dirs.posters();
+        tokio::fs::create_dir_all(&posters).await?;
+        let poster = Self::poster_filename(&filename);
+        let output_path = format!("{}/{}", posters, poster);
+
+        let args = [
+            "-ss",
+            "0",
+            "-i",
+            validated_input_path.as_str(),
+            "-vframes",
+            "1",
+            "-q:v",
+            "3",
+            "-y",
+            &output_path,
+        ];
+        tracing::debug!("FFmpeg command: ffmpeg {:?}", args);
+
+        let output = RetryPolicy::default()
+            .max_attempts(2)
+            .output("ffmpeg poster", retry::command("ffmpeg", &args))
+            .await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::error!("FFmpeg poster extraction failed: {}", stderr);
+            return Err(AppError::IoError(std::io::Error::other(format!(
+                "FFmpeg processing failed: {}",
+                stderr
+            ))));
+        }
+
+        tracing::info!("Poster extracted: {}", output_path);
+        Ok(format!("{}/{}", POSTER_URL, poster))
+    }
+
+    /// One frame of an uploaded video at `at_secs`, as a JPEG
+    pub async fn extract_frame(
+        uploads_dir: &str,
+        filename: &str,
+        at_secs: f64,
+    ) -> Result<Vec<u8>, AppError> {
+        let filename = sanitize_filename(filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let validated_input_path = validate_file_path(uploads_dir, &filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
+        let at = format!("{:.3}", at_secs);
+
+        // Seeking before the input is fast, and exact since ffmpeg decodes up to the timestamp
+        let args = [
+            "-ss",
+            at.as_str(),
+            "-i",
+            validated_input_path.as_str(),
+            "-frames:v",
+            "1",
+            "-q:v",
+            "3",
+            "-f",
+            "image2pipe",
             "-c:v",
-            video_codec,
-            "-preset", 
-            "fast", // Faster encoding
-            "-y",   // Overwrite output file
-            &validated_output_path,
+            "mjpeg",
+            "pipe:1",
+        ];
+        tracing::debug!("FFmpeg command: ffmpeg {:?}", args);
+
+        let output = AsyncCommand::new("ffmpeg").args(args).output().await?;
+        if !output.status.success() || output.stdout.is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::error!("FFmpeg frame extraction failed: {}", stderr);
+            return Err(AppError::IoError(std::io::Error::other("Frame extraction failed")));
+        }
+        Ok(output.stdout)
+    }
+
+    /// Cut an uploaded video down to [start, end) seconds, re-encoded so the cut lands on the
+    /// exact frames picked rather than the nearest keyframes
+    pub async fn trim_video(
+        uploads_dir: &str,
+        input_filename: &str,
+        output_filename: &str,
+        start: f64,
+        end: Option<f64>,
+    ) -> Result<(), AppError> {
+        let input_filename = sanitize_filename(input_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let output_filename = sanitize_filename(output_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output filename")))?;
+        let validated_input_path = validate_file_path(uploads_dir, &input_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
+        let validated_output_path = validate_file_path(uploads_dir, &output_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output file path")))?;
+        let start_arg = format!("{:.3}", start);
+        let end_arg = end.map(|secs| format!("{:.3}", secs));
+
+        let mut args = vec!["-i", validated_input_path.as_str(), "-ss", start_arg.as_str()];
+        if let Some(end_arg) = &end_arg {
+            args.extend(["-to", end_arg.as_str()]);
+        }
+        args.extend(["-c:v", "libx264", "-preset", "veryfast", "-crf", "23"]);
+        args.extend(["-c:a", "aac", "-movflags", "+faststart", "-y"]);
+        args.push(validated_output_path.as_str());
+        tracing::info!("Trimming video {} to {}-{:?}", validated_input_path, start, end);
+        tracing::debug!("FFmpeg command: ffmpeg {:?}", args);
+
+        let output = AsyncCommand::new("ffmpeg").args(&args).output().await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::error!("FFmpeg video trim failed: {}", stderr);
+            if let Err(e) = tokio::fs::remove_file(&validated_output_path).await
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                tracing::warn!("Failed to remove {}: {}", validated_output_path, e);
+            }
+            return Err(AppError::IoError(std::io::Error::other("Video trim failed")));
+        }
+        Ok(())
+    }
+
+    /// Poster of a video, named after it
+    pub fn poster_filename(filename: &str) -> String {
+        let stem = std::path::Path::new(filename)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(filename);
+        format!("{}.jpg", stem)
+    }
+
+    /// Save the first seconds of an uploaded video as a small looping WebP in
+    /// [`DataDirs::previews`], returning its URL
+    /// Runs on a single thread, previews shouldn't slow down the uploads being processed
+    pub async fn extract_preview(
+        dirs: &DataDirs,
+        filename: &str,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<String, AppError> {
+        let filename = sanitize_filename(filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let validated_input_path = validate_file_path(&dirs.uploads, &filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
+        let previews = dirs.previews();
+        tokio::fs::create_dir_all(&previews).await?;
+        let preview = Self::preview_filename(&filename);
+        let output_path = format!("{}/{}", previews, preview);
+
+        let filter_args = FilterGraph::new().fps(10).scale(320, -2).to_args();
+        let mut args = vec![
+            "-ss",
+            "0",
+            "-t",
+            PREVIEW_SECS,
+            "-i",
+            validated_input_path.as_str(),
+        ];
+        args.extend(filter_args.iter().map(String::as_str));
+        args.extend([
+            "-c:v",
+            "libwebp",
+            "-loop",
+            "0",
+            "-q:v",
+            "50",
+            "-an",
+            "-threads",
+            "1",
+            "-y",
+            &output_path,
         ]);
-        
-        cmd.args(args);
+        tracing::debug!("FFmpeg command: ffmpeg {:?}", args);
+
+        let output = RetryPolicy::default()
+            .max_attempts(2)
+            .output_cancellable("ffmpeg preview", retry::command("ffmpeg", &args), cancel)
+            .await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::error!("FFmpeg preview failed: {}", stderr);
+            return Err(AppError::IoError(std::io::Error::other(format!(
+                "FFmpeg processing failed: {}",
+                stderr
+            ))));
+        }
+
+        tracing::info!("Preview extracted: {}", output_path);
+        Ok(format!("{}/{}", PREVIEW_URL, preview))
+    }
+
+    /// Animated preview of a video, named after it
+    pub fn preview_filename(filename: &str) -> String {
+        let stem = std::path::Path::new(filename)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(filename);
+        format!("{}.webp", stem)
+    }
+
+    /// Encode a [`LOW_RENDITION_HEIGHT`] copy of an uploaded video in [`DataDirs::renditions`],
+    /// returning its URL
+    pub async fn encode_low_rendition(dirs: &DataDirs, filename: &str) -> Result<String, AppError> {
+        let filename = sanitize_filename(filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let validated_input_path = validate_file_path(&dirs.uploads, &filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
+        let renditions = dirs.renditions();
+        tokio::fs::create_dir_all(&renditions).await?;
+        let rendition = Self::rendition_filename(&filename);
+        let output_path = format!("{}/{}", renditions, rendition);
+
+        let filter_args = FilterGraph::new()
+            .scale(-2, LOW_RENDITION_HEIGHT as i32)
+            .to_args();
+        let mut args = vec!["-i", validated_input_path.as_str()];
+        args.extend(filter_args.iter().map(String::as_str));
+        args.extend([
+            "-c:v",
+            "libx264",
+            "-preset",
+            "veryfast",
+            "-crf",
+            "28",
+            "-c:a",
+            "aac",
+            "-b:a",
+            "96k",
+            "-movflags",
+            "+faststart",
+            "-y",
+            &output_path,
+        ]);
+        tracing::debug!("FFmpeg command: ffmpeg {:?}", args);
+
+        let output = RetryPolicy::default()
+            .max_attempts(2)
+            .output("ffmpeg rendition", retry::command("ffmpeg", &args))
+            .await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::error!("FFmpeg rendition failed: {}", stderr);
+            return Err(AppError::IoError(std::io::Error::other(format!(
+                "FFmpeg processing failed: {}",
+                stderr
+            ))));
+        }
+
+        tracing::info!("Low rendition encoded: {}", output_path);
+        Ok(format!("{}/{}", RENDITION_URL, rendition))
+    }
+
+    /// Split an uploaded video into HLS segments under [`DataDirs::hls`], returning the playlist
+    /// URL
+    /// The streams are copied when the segments can hold them, re-encoded otherwise
+    pub async fn segment_hls(dirs: &DataDirs, filename: &str) -> Result<String, AppError> {
+        let filename = sanitize_filename(filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let validated_input_path = validate_file_path(&dirs.uploads, &filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
+        let id = Self::hls_id(&filename);
+        let dir = format!("{}/{}", dirs.hls(), id);
+        tokio::fs::create_dir_all(&dir).await?;
+        let segments = format!("{}/segment_%03d.ts", dir);
+        let playlist = format!("{}/{}", dir, HLS_PLAYLIST);
+
+        let mut stderr = String::new();
+        for codecs in [
+            &["-c", "copy"][..],
+            &["-c:v", "libx264", "-preset", "veryfast", "-c:a", "aac"][..],
+        ] {
+            let mut args = vec!["-i", validated_input_path.as_str()];
+            args.extend(codecs);
+            args.extend([
+                "-f",
+                "hls",
+                "-hls_time",
+                HLS_SEGMENT_SECS,
+                "-hls_playlist_type",
+                "vod",
+                "-hls_segment_filename",
+                &segments,
+                "-y",
+                &playlist,
+            ]);
+            tracing::debug!("FFmpeg command: ffmpeg {:?}", args);
+            let output = AsyncCommand::new("ffmpeg").args(&args).output().await?;
+            if output.status.success() {
+                tracing::info!("HLS playlist written: {}", playlist);
+                return Ok(format!("{}/{}/{}", HLS_URL, id, HLS_PLAYLIST));
+            }
+            stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            tracing::warn!("FFmpeg HLS segmenting with {:?} failed: {}", codecs, stderr);
+        }
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        Err(AppError::IoError(std::io::Error::other(format!(
+            "FFmpeg processing failed: {}",
+            stderr
+        ))))
+    }
+
+    /// Directory of a video's HLS playlist in [`DataDirs::hls`], named after it
+    pub fn hls_id(filename: &str) -> String {
+        std::path::Path::new(filename)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(filename)
+            .to_string()
+    }
+
+    /// Low rendition of a video, named after it
+    pub fn rendition_filename(filename: &str) -> String {
+        let stem = std::path::Path::new(filename)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(filename);
+        format!("{}_{}p.mp4", stem, LOW_RENDITION_HEIGHT)
+    }
+
+    /// Delete the poster, preview, low rendition and HLS playlist of a video going away, if it
+    /// had them
+    pub async fn remove_thumbnails(dirs: &DataDirs, filename: &str) {
+        let hls = format!("{}/{}", dirs.hls(), Self::hls_id(filename));
+        match tokio::fs::remove_dir_all(&hls).await {
+            Ok(_) => tracing::info!("Removed {}", hls),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("Failed to remove {}: {}", hls, e),
+        }
+        let paths = [
+            format!("{}/{}", dirs.posters(), Self::poster_filename(filename)),
+            format!("{}/{}", dirs.previews(), Self::preview_filename(filename)),
+            format!("{}/{}", dirs.renditions(), Self::rendition_filename(filename)),
+        ];
+        for path in paths {
+            match tokio::fs::remove_file(&path).await {
+                Ok(_) => tracing::info!("Removed {}", path),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Failed to remove {}: {}", path, e),
+            }
+        }
+    }
+
+    /// Overlay the watermark on a still image
+    pub async fn watermark_image(
+        uploads_dir: &str,
+        input_path: &str,
+        output_path: &str,
+        watermark: &Watermark,
+    ) -> Result<(), AppError> {
+        // Sanitize and validate input and output paths
+        let input_filename = sanitize_filename(input_path)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
+        let output_filename = sanitize_filename(output_path)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output filename")))?;
+
+        // Validate that both paths are within the uploads directory
+        let validated_input_path = validate_file_path(uploads_dir, &input_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
+        let validated_output_path = validate_file_path(uploads_dir, &output_filename)
+            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output file path")))?;
 
-        tracing::info!("Processing video with caption: {}", caption);
-        tracing::debug!("FFmpeg command: {:?}", cmd);
+        let filter_args = watermark.overlay(FilterGraph::new()).to_args();
+        let mut args = vec!["-i", validated_input_path.as_str()];
+        args.extend(filter_args.iter().map(String::as_str));
+        args.extend(["-frames:v", "1", "-y", &validated_output_path]);
 
-        let output = cmd.output().await.map_err(|e| {
-            tracing::error!("Failed to execute ffmpeg: {}", e);
-            AppError::IoError(std::io::Error::other("Video processing failed"))
-        })?;
+        tracing::debug!("FFmpeg command: ffmpeg {:?}", args);
+
+        let output = RetryPolicy::default()
+            .max_attempts(2)
+            .output("ffmpeg watermark", retry::command("ffmpeg", &args))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute ffmpeg: {}", e);
+                AppError::IoError(std::io::Error::other("Image processing failed"))
+            })?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            tracing::error!("FFmpeg failed: {}", stderr);
-
-            // Try fallback with system default font
-            return Self::add_caption_overlay_fallback(
-                &validated_input_path,
-                &validated_output_path,
-                caption,
-                font_size,
-                shadow_offset,
-                bottom_margin,
-            )
-            .await;
+            tracing::error!("FFmpeg watermark failed: {}", stderr);
+            return Err(AppError::IoError(std::io::Error::other(format!(
+                "FFmpeg processing failed: {}",
+                stderr
+            ))));
         }
 
-        tracing::info!("Video processing completed successfully");
+        tracing::info!("Image watermarked: {}", validated_output_path);
         Ok(())
     }
 
-    /// Fallback method using system default font
-    async fn add_caption_overlay_fallback(
+    /// Re-encode a still image in another format
+    pub async fn convert_image(
+        uploads_dir: &str,
         input_path: &str,
         output_path: &str,
-        caption: &str,
-        font_size: u32,
-        shadow_offset: u32,
-        bottom_margin: u32,
+        format: ImageFormat,
     ) -> Result<(), AppError> {
         // Sanitize and validate input and output paths
         let input_filename = sanitize_filename(input_path)
             .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
         let output_filename = sanitize_filename(output_path)
             .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output filename")))?;
-            
+
         // Validate that both paths are within the uploads directory
-        let validated_input_path = validate_file_path("uploads", &input_filename)
+        let validated_input_path = validate_file_path(uploads_dir, &input_filename)
             .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
-        let validated_output_path = validate_file_path("uploads", &output_filename)
+        let validated_output_path = validate_file_path(uploads_dir, &output_filename)
             .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid output file path")))?;
 
-        let escaped_caption = escape_ffmpeg_text(caption);
-
-        // Simpler filter without specific font file but with dynamic sizing and text wrapping
-        let filter_complex = format!(
-            "drawtext=text='{}':fontsize={}:fontcolor=white:x=(w-text_w)/2:y=h-text_h-{}:shadowcolor=black:shadowx={}:shadowy={}:line_spacing=5",
-            escaped_caption, font_size, bottom_margin, shadow_offset, shadow_offset
-        );
+        let mut args = vec!["-i", validated_input_path.as_str()];
+        args.extend(format.encoder_args());
+        args.extend(["-frames:v", "1", "-y", &validated_output_path]);
 
-        let mut cmd = AsyncCommand::new("ffmpeg");
-        
-        // Base arguments - just input file (no hardware acceleration in fallback)
-        let args = vec![
-            "-i", &validated_input_path,
-            "-vf", &filter_complex,
-            "-c:a", "copy",
-            "-c:v", "libx264", // Always use software encoder in fallback
-            "-preset", "fast",
-            "-y", &validated_output_path,
-        ];
-        
-        cmd.args(args);
+        tracing::debug!("FFmpeg command: ffmpeg {:?}", args);
 
-        let output = cmd.output().await.map_err(|e| {
-            tracing::error!("Failed to execute ffmpeg fallback: {}", e);
-            AppError::IoError(std::io::Error::other("Video processing failed"))
-        })?;
+        let output = RetryPolicy::default()
+            .max_attempts(2)
+            .output("ffmpeg convert image", retry::command("ffmpeg", &args))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute ffmpeg: {}", e);
+                AppError::IoError(std::io::Error::other("Image conversion failed"))
+            })?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            tracing::error!("FFmpeg fallback failed: {}", stderr);
+            tracing::error!("FFmpeg image conversion failed: {}", stderr);
             return Err(AppError::IoError(std::io::Error::other(format!(
                 "FFmpeg processing failed: {}",
                 stderr
             ))));
         }
 
-        tracing::info!("Video processing completed with fallback font");
+        tracing::info!("Image converted: {}", validated_output_path);
         Ok(())
     }
 
@@ -272,7 +1396,7 @@ impl VideoProcessor {
     /// Download video from supported platforms (YouTube, TikTok) and process it with caption if provided
     pub async fn download_and_process_video(url: &str, output_dir: &str, caption: Option<&str>) -> Result<String, AppError> {
         // Validate video URL
-        if !Self::is_supported_video_url(url) {
+        if VideoPlatform::from_url(url).is_none() {
             return Err(AppError::IoError(std::io::Error::other(
                 "Invalid video URL. Supported platforms: YouTube, TikTok",
             )));
@@ -285,13 +1409,6 @@ impl VideoProcessor {
             )));
         }
 
-        // Validate output directory
-        if output_dir != "uploads" {
-            return Err(AppError::IoError(std::io::Error::other(
-                "Invalid output directory",
-            )));
-        }
-
         // Create output directory
         tokio::fs::create_dir_all(output_dir).await.map_err(|e| {
             tracing::error!("Failed to create output directory: {}", e);
@@ -375,35 +1492,44 @@ impl VideoProcessor {
         tracing::info!("Successfully downloaded video to: {}", temp_path);
 
         // If caption is provided, process the video with caption overlay
-        if let Some(caption_text) = caption {
-            if !caption_text.trim().is_empty() {
-                tracing::info!("Processing video with caption overlay");
-                
-                // Generate output filename
-                let output_filename = format!("video_{}_captioned.mp4", timestamp);
-                // Sanitize the filename
-                let sanitized_output_filename = sanitize_filename(&output_filename)
-                    .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid filename")))?;
-                let output_path = format!("{}/{}", output_dir, sanitized_output_filename);
-                
-                // Process video with caption
-                match Self::add_caption_overlay(&temp_path, &output_path, caption_text).await {
-                    Ok(_) => {
-                        // Remove temporary file
-                        tokio::task::spawn_blocking(move || {
-                            let _ = std::fs::remove_file(&temp_path);
-                        });
-                        tracing::info!("Video processing completed: {}", output_path);
-                        return Ok(sanitized_output_filename);
-                    }
-                    Err(e) => {
-                        // Clean up files on error
-                        tokio::task::spawn_blocking(move || {
-                            let _ = std::fs::remove_file(&temp_path);
-                            let _ = std::fs::remove_file(&output_path);
-                        });
-                        return Err(e);
-                    }
+        if let Some(caption_text) = caption
+            && !caption_text.trim().is_empty()
+        {
+            tracing::info!("Processing video with caption overlay");
+            
+            // Generate output filename
+            let output_filename = format!("video_{}_captioned.mp4", timestamp);
+            // Sanitize the filename
+            let sanitized_output_filename = sanitize_filename(&output_filename)
+                .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid filename")))?;
+            let output_path = format!("{}/{}", output_dir, sanitized_output_filename);
+            
+            // Process video with caption, a broken output is removed before returning
+            let options = EncodeOptions::default();
+            let encoded = Self::encode_verified(
+                output_dir,
+                &temp_path,
+                &output_path,
+                caption_text,
+                &options,
+                None,
+            )
+            .await;
+            match encoded {
+                Ok(_) => {
+                    // Remove temporary file
+                    tokio::task::spawn_blocking(move || {
+                        let _ = std::fs::remove_file(&temp_path);
+                    });
+                    tracing::info!("Video processing completed: {}", output_path);
+                    return Ok(sanitized_output_filename);
+                }
+                Err(e) => {
+                    // Clean up files on error
+                    tokio::task::spawn_blocking(move || {
+                        let _ = std::fs::remove_file(&temp_path);
+                    });
+                    return Err(e);
                 }
             }
         }
@@ -429,9 +1555,16 @@ impl VideoProcessor {
     }
 
     /// Stream video download directly to processing (most efficient approach)
-    pub async fn stream_process_video(url: &str, output_dir: &str, caption: Option<&str>) -> Result<String, AppError> {
-        // Validate video URL
-        if !Self::is_supported_video_url(url) {
+    pub async fn stream_process_video(
+        url: &str,
+        output_dir: &str,
+        caption: Option<&str>,
+        options: &EncodeOptions,
+        max_height: u32,
+        progress: &ProgressReporter,
+    ) -> Result<ProcessedVideo, AppError> {
+        // Validate video URL, the platform policy was checked when fetching metadata
+        if VideoPlatform::from_url(url).is_none() {
             return Err(AppError::IoError(std::io::Error::other(
                 "Invalid video URL. Supported platforms: YouTube, TikTok",
             )));
@@ -450,13 +1583,6 @@ impl VideoProcessor {
             )));
         }
 
-        // Validate output directory
-        if output_dir != "uploads" {
-            return Err(AppError::IoError(std::io::Error::other(
-                "Invalid output directory",
-            )));
-        }
-
         // Create output directory
         tokio::fs::create_dir_all(output_dir).await.map_err(|e| {
             tracing::error!("Failed to create output directory: {}", e);
@@ -478,105 +1604,307 @@ impl VideoProcessor {
             .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid filename")))?;
         let output_path = format!("{}/{}", output_dir, sanitized_output_filename);
 
-        // First, download the video using yt-dlp
+        // First, download the video using yt-dlp, then make sure it ends up as mp4
         tracing::info!("Downloading video: {}", url);
-        
-        let mut download_cmd = AsyncCommand::new("yt-dlp");
-        download_cmd.args([
-            "--cookies-from-browser",
-            "firefox",
-            "--format",
-            "mp4[height<=720]/mp4/best[height<=720]/best",
-            "--output",
-            &output_path,
-            "--no-playlist",
-            url,
-        ]);
-
-        let download_output = download_cmd.output().await.map_err(|e| {
-            tracing::error!("Failed to execute yt-dlp: {}", e);
-            AppError::IoError(std::io::Error::other("Video download failed"))
-        })?;
+        let download_stem = format!("video_{}_download", timestamp);
+        let downloaded_path =
+            Self::download_video(url, output_dir, &download_stem, max_height, progress).await?;
+
+        if downloaded_path.ends_with(".mp4") {
+            tokio::fs::rename(&downloaded_path, &output_path).await.map_err(|e| {
+                tracing::error!("Failed to move downloaded video: {}", e);
+                AppError::IoError(std::io::Error::other("Video download failed"))
+            })?;
+        } else {
+            let remuxed =
+                Self::remux_to_mp4(&downloaded_path, &output_path, Some(progress.cancel_token())).await;
+            let _ = tokio::fs::remove_file(&downloaded_path).await;
+            remuxed?;
+        }
 
-        if !download_output.status.success() {
-            let stderr = String::from_utf8_lossy(&download_output.stderr);
-            tracing::error!("yt-dlp download failed: {}", stderr);
+        // Check if the file was created
+        if tokio::fs::metadata(&output_path).await.is_err() {
+            return Err(AppError::IoError(std::io::Error::other(
+                "Downloaded video file not found",
+            )));
+        }
 
-            // Clean up any partial file
-            let _ = tokio::fs::remove_file(&output_path).await;
+        tracing::info!("Video downloaded successfully: {}", output_path);
 
-            // Check for specific TikTok authentication issues
-            if stderr.contains("Log in for access") || stderr.contains("cookies") {
-                return Err(AppError::IoError(std::io::Error::other(
-                    "TikTok video requires authentication. This video may be age-restricted or private. Try a different public TikTok video.",
-                )));
+        // If a caption or watermark is requested, re-encode the video with them
+        let caption_text = caption.map(str::trim).unwrap_or_default();
+        // Also when it has to be reframed or is over the quality profile's resolution cap
+        let resize = Self::get_video_info(&output_path).await.is_ok_and(|info| {
+            options.needs_framing(info.width, info.height)
+                || options.needs_downscale(info.width, info.height)
+        });
+        if !caption_text.is_empty() || options.watermark.is_some() || resize {
+            tracing::info!("Processing video with caption/watermark overlay");
+            progress.stage(JobStage::Processing);
+            
+            // Generate processed filename
+            let processed_filename = format!("video_{}_captioned_final.mp4", timestamp);
+            // Sanitize the filename
+            let sanitized_processed_filename = sanitize_filename(&processed_filename)
+                .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid filename")))?;
+            let processed_path = format!("{}/{}", output_dir, sanitized_processed_filename);
+            
+            // Process video with caption, the download is only removed once the result plays
+            match Self::encode_verified(
+                output_dir,
+                &output_path,
+                &processed_path,
+                caption_text,
+                options,
+                Some(progress.cancel_token()),
+            )
+            .await
+            {
+                Ok(warnings) => {
+                    // Remove original file to save space
+                    let _ = tokio::fs::remove_file(&output_path).await;
+                    tracing::info!("Video processing completed: {}", processed_path);
+                    return Ok(ProcessedVideo {
+                        filename: sanitized_processed_filename,
+                        error: None,
+                        warnings,
+                    });
+                }
+                Err(_) if progress.is_cancelled() => {
+                    let _ = tokio::fs::remove_file(&output_path).await;
+                    return Err(AppError::IoError(std::io::Error::other("Download cancelled")));
+                }
+                Err(e) => {
+                    tracing::warn!("Showing {} without its caption/watermark: {}", output_path, e);
+                    return Ok(ProcessedVideo {
+                        filename: sanitized_output_filename,
+                        error: Some(e.to_string()),
+                        warnings: vec![WarningReason::ProcessingFailed],
+                    });
+                }
             }
+        }
 
-            // Check for other TikTok-specific issues
-            if stderr.contains("not comfortable for some audiences") {
-                return Err(AppError::IoError(std::io::Error::other(
-                    "TikTok video is age-restricted and cannot be downloaded without authentication. Please try a different video.",
-                )));
+        tracing::info!("Video processing completed successfully: {}", output_path);
+        Ok(ProcessedVideo {
+            filename: sanitized_output_filename,
+            error: None,
+            warnings: Vec::new(),
+        })
+    }
+
+    /// Download with yt-dlp, walking down the format ladder until a selector is available
+    /// Returns the path of the downloaded file, whose extension depends on the format picked
+    async fn download_video(
+        url: &str,
+        output_dir: &str,
+        stem: &str,
+        max_height: u32,
+        progress: &ProgressReporter,
+    ) -> Result<String, AppError> {
+        let output_template = format!("{}/{}.%(ext)s", output_dir, stem);
+        let mut last_stderr = String::new();
+
+        for format in format_ladder(max_height) {
+            let download_args = [
+                "--cookies-from-browser",
+                "firefox",
+                "--format",
+                &format,
+                "--output",
+                &output_template,
+                "--print",
+                "after_move:filepath",
+                // --print implies --quiet, ask for progress lines anyway
+                "--newline",
+                "--progress",
+                "--no-playlist",
+                url,
+            ];
+
+            // Only forward whole-percent changes, yt-dlp prints progress many times a second
+            let mut last_percent = None;
+            let on_line = |line: &str| {
+                if let Some(percent) = parse_progress(line)
+                    && last_percent != Some(percent as u32)
+                {
+                    last_percent = Some(percent as u32);
+                    progress.percent(percent);
+                }
+            };
+
+            // Downloads hit rate limits the most, so give them extra and slower attempts
+            let download_output = RetryPolicy::default()
+                .max_attempts(4)
+                .initial_backoff(Duration::from_secs(2))
+                .output_with_lines(
+                    "yt-dlp download",
+                    retry::command("yt-dlp", &download_args),
+                    on_line,
+                    Some(progress.cancel_token()),
+                )
+                .await;
+            let download_output = match download_output {
+                Ok(download_output) => download_output,
+                Err(e) if retry::is_cancelled(&e) => {
+                    tracing::info!("Download of {} cancelled", url);
+                    Self::remove_partial_downloads(output_dir, stem).await;
+                    return Err(AppError::IoError(e));
+                }
+                Err(e) => {
+                    tracing::error!("Failed to execute yt-dlp: {}", e);
+                    return Err(AppError::IoError(std::io::Error::other("Video download failed")));
+                }
+            };
+
+            if download_output.status.success() {
+                let stdout = String::from_utf8_lossy(&download_output.stdout);
+                // The file path is printed after the last progress line
+                let downloaded_path = stdout
+                    .lines()
+                    .rfind(|line| !line.trim().is_empty() && parse_progress(line).is_none())
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                // yt-dlp reports the path it wrote, make sure it is the one we asked for
+                let downloaded_filename = downloaded_path.rsplit('/').next().unwrap_or_default();
+                if !downloaded_filename.starts_with(stem)
+                    || validate_file_path(output_dir, downloaded_filename).is_none()
+                {
+                    tracing::error!("Unexpected yt-dlp output path: {}", downloaded_path);
+                    return Err(AppError::IoError(std::io::Error::other(
+                        "Downloaded video file not found",
+                    )));
+                }
+                tracing::info!("Downloaded {} with format {}", downloaded_path, format);
+                return Ok(format!("{}/{}", output_dir, downloaded_filename));
             }
 
-            // Check for private/unavailable content
-            if stderr.contains("Private video") || stderr.contains("Video unavailable") {
-                return Err(AppError::IoError(std::io::Error::other(
-                    "Video is private or unavailable. Please check the URL and try again.",
-                )));
+            last_stderr = String::from_utf8_lossy(&download_output.stderr).to_string();
+            if !is_format_unavailable(&last_stderr) {
+                break;
             }
+            tracing::warn!("Format {} not available, trying the next one", format);
+        }
 
-            return Err(AppError::IoError(std::io::Error::other("Video download failed")));
+        let stderr = last_stderr;
+        tracing::error!("yt-dlp download failed: {}", stderr);
+
+        // Clean up any partial file
+        Self::remove_partial_downloads(output_dir, stem).await;
+
+        // Check for specific TikTok authentication issues
+        if stderr.contains("Log in for access") || stderr.contains("cookies") {
+            return Err(AppError::IoError(std::io::Error::other(
+                "TikTok video requires authentication. This video may be age-restricted or private. Try a different public TikTok video.",
+            )));
         }
 
-        // Check if the file was created
-        if tokio::fs::metadata(&output_path).await.is_err() {
+        // Check for other TikTok-specific issues
+        if stderr.contains("not comfortable for some audiences") {
             return Err(AppError::IoError(std::io::Error::other(
-                "Downloaded video file not found",
+                "TikTok video is age-restricted and cannot be downloaded without authentication. Please try a different video.",
             )));
         }
 
-        tracing::info!("Video downloaded successfully: {}", output_path);
+        // Check for private/unavailable content
+        if stderr.contains("Private video") || stderr.contains("Video unavailable") {
+            return Err(AppError::IoError(std::io::Error::other(
+                "Video is private or unavailable. Please check the URL and try again.",
+            )));
+        }
 
-        // If caption is provided, process the video with caption overlay
-        if let Some(caption_text) = caption {
-            if !caption_text.trim().is_empty() {
-                tracing::info!("Processing video with caption overlay");
-                
-                // Generate processed filename
-                let processed_filename = format!("video_{}_captioned_final.mp4", timestamp);
-                // Sanitize the filename
-                let sanitized_processed_filename = sanitize_filename(&processed_filename)
-                    .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid filename")))?;
-                let processed_path = format!("{}/{}", output_dir, sanitized_processed_filename);
-                
-                // Process video with caption
-                match Self::add_caption_overlay(&output_path, &processed_path, caption_text).await {
-                    Ok(_) => {
-                        // Remove original file to save space
-                        let _ = tokio::fs::remove_file(&output_path).await;
-                        tracing::info!("Video processing completed: {}", processed_path);
-                        return Ok(sanitized_processed_filename);
-                    }
-                    Err(e) => {
-                        // Clean up files on error
-                        let _ = tokio::fs::remove_file(&output_path).await;
-                        let _ = tokio::fs::remove_file(&processed_path).await;
-                        return Err(e);
-                    }
-                }
+        Err(AppError::IoError(std::io::Error::other("Video download failed")))
+    }
+
+    /// Remove leftovers (.part, .ytdl, unmerged streams) of a failed download
+    async fn remove_partial_downloads(output_dir: &str, stem: &str) {
+        let Ok(mut entries) = tokio::fs::read_dir(output_dir).await else {
+            return;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if entry.file_name().to_string_lossy().starts_with(stem) {
+                let _ = tokio::fs::remove_file(entry.path()).await;
             }
         }
+    }
 
-        tracing::info!("Video processing completed successfully: {}", output_path);
-        Ok(sanitized_output_filename)
+    /// Put a downloaded video into an mp4 container without re-encoding
+    /// Falls back to a full transcode when the streams can't go into mp4 as they are
+    async fn remux_to_mp4(
+        input_path: &str,
+        output_path: &str,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<(), AppError> {
+        tracing::info!("Remuxing {} to mp4", input_path);
+        let remux_args = [
+            "-i",
+            input_path,
+            "-c",
+            "copy",
+            "-movflags",
+            "+faststart",
+            "-y",
+            output_path,
+        ];
+        let output = RetryPolicy::default()
+            .max_attempts(2)
+            .output_cancellable("ffmpeg remux", retry::command("ffmpeg", &remux_args), cancel)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute ffmpeg: {}", e);
+                AppError::IoError(std::io::Error::other("Video processing failed"))
+            })?;
+        if output.status.success() {
+            return Ok(());
+        }
+        tracing::warn!(
+            "Remux failed, transcoding instead: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let transcode_args = [
+            "-i",
+            input_path,
+            "-c:v",
+            "libx264",
+            "-preset",
+            "fast",
+            "-c:a",
+            "aac",
+            "-movflags",
+            "+faststart",
+            "-y",
+            output_path,
+        ];
+        let output = RetryPolicy::default()
+            .max_attempts(2)
+            .output_cancellable("ffmpeg transcode", retry::command("ffmpeg", &transcode_args), cancel)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute ffmpeg: {}", e);
+                AppError::IoError(std::io::Error::other("Video processing failed"))
+            })?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::error!("FFmpeg transcode failed: {}", stderr);
+            let _ = tokio::fs::remove_file(output_path).await;
+            return Err(AppError::IoError(std::io::Error::other(format!(
+                "FFmpeg processing failed: {}",
+                stderr
+            ))));
+        }
+        Ok(())
     }
 
     /// Get video metadata from supported platforms (YouTube, TikTok)
-    pub async fn get_video_metadata(url: &str) -> Result<VideoMetadata, AppError> {
-        if !Self::is_supported_video_url(url) {
+    pub async fn get_video_metadata(
+        url: &str,
+        platforms: &PlatformPolicy,
+    ) -> Result<VideoMetadata, AppError> {
+        if !platforms.is_supported_video_url(url) {
             return Err(AppError::IoError(std::io::Error::other(
-                "Invalid video URL. Supported platforms: YouTube, TikTok",
+                platforms.rejection_message(url),
             )));
         }
 
@@ -586,19 +1914,21 @@ impl VideoProcessor {
             )));
         }
 
-        let mut cmd = AsyncCommand::new("yt-dlp");
-        cmd.args([
+        let args = [
             "--cookies-from-browser",
             "firefox", // Use Firefox cookies for authentication
             "--dump-json",
             "--no-playlist",
             url,
-        ]);
+        ];
 
-        let output = cmd.output().await.map_err(|e| {
-            tracing::error!("Failed to execute yt-dlp for info: {}", e);
-            AppError::IoError(std::io::Error::other("Failed to get video information"))
-        })?;
+        let output = RetryPolicy::default()
+            .output("yt-dlp metadata", retry::command("yt-dlp", &args))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to execute yt-dlp for info: {}", e);
+                AppError::IoError(std::io::Error::other("Failed to get video information"))
+            })?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -642,31 +1972,9 @@ impl VideoProcessor {
         })
     }
 
-    /// Check if URL is a valid video platform URL (YouTube or TikTok)
-    fn is_supported_video_url(url: &str) -> bool {
-        // YouTube URLs
-        url.contains("youtube.com/watch") || 
-        url.contains("youtu.be/") || 
-        url.contains("youtube.com/shorts/") ||
-        url.contains("m.youtube.com/watch") ||
-        // TikTok URLs
-        url.contains("tiktok.com/@") ||
-        url.contains("vm.tiktok.com/") ||
-        url.contains("vt.tiktok.com/") ||
-        url.contains("tiktok.com/t/") ||
-        url.contains("m.tiktok.com/")
-    }
-
     /// Get video information (width, height, duration)
-    async fn get_video_info(input_path: &str) -> Result<VideoInfo, AppError> {
-        // Sanitize and validate input path
-        let input_filename = sanitize_filename(input_path)
-            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input filename")))?;
-            
-        // Validate that the path is within the uploads directory
-        let validated_input_path = validate_file_path("uploads", &input_filename)
-            .ok_or_else(|| AppError::IoError(std::io::Error::other("Invalid input file path")))?;
-
+    /// Callers pass a path they already validated
+    async fn get_video_info(validated_input_path: &str) -> Result<VideoInfo, AppError> {
         let mut cmd = AsyncCommand::new("ffprobe");
         cmd.args([
             "-v",
@@ -675,7 +1983,7 @@ impl VideoProcessor {
             "json",
             "-show_format",
             "-show_streams",
-            &validated_input_path,
+            validated_input_path,
         ]);
 
         let output = cmd.output().await.map_err(|e| {
@@ -703,12 +2011,20 @@ impl VideoProcessor {
             .as_array()
             .ok_or_else(|| AppError::IoError(std::io::Error::other("No streams found in video")))?;
 
+        let duration_secs = json["format"]["duration"]
+            .as_str()
+            .and_then(|duration| duration.parse::<f64>().ok());
+
         for stream in streams {
             if stream["codec_type"].as_str() == Some("video") {
                 let width = stream["width"].as_u64().unwrap_or(1920) as u32;
                 let height = stream["height"].as_u64().unwrap_or(1080) as u32;
 
-                return Ok(VideoInfo { width, height });
+                return Ok(VideoInfo {
+                    width,
+                    height,
+                    duration_secs,
+                });
             }
         }
 
@@ -716,6 +2032,7 @@ impl VideoProcessor {
         Ok(VideoInfo {
             width: 1920,
             height: 1080,
+            duration_secs,
         })
     }
 
@@ -783,7 +2100,9 @@ impl VideoProcessor {
 
     /// Detect the platform from URL
     fn detect_platform(url: &str) -> VideoPlatform {
-        if url.contains("youtube.com") || url.contains("youtu.be") {
+        if let Some(platform) = VideoPlatform::from_url(url) {
+            platform
+        } else if url.contains("youtube.com") || url.contains("youtu.be") {
             VideoPlatform::YouTube
         } else if url.contains("tiktok.com") {
             VideoPlatform::TikTok
@@ -793,35 +2112,27 @@ impl VideoProcessor {
     }
 
     /// Get user-friendly error message for common video download issues
-    pub fn get_user_friendly_error(error_msg: &str, url: &str) -> String {
+    pub fn get_user_friendly_error(error_msg: &str, url: &str, locale: Locale) -> String {
         let platform = Self::detect_platform(url);
 
-        if error_msg.contains("Log in for access") || error_msg.contains("cookies") {
+        let message = if error_msg.contains("Log in for access") || error_msg.contains("cookies") {
             match platform {
-                VideoPlatform::TikTok => {
-                    "This TikTok video requires login to view (age-restricted or sensitive content). Please try a different public TikTok video.".to_string()
-                }
-                VideoPlatform::YouTube => {
-                    "This YouTube video requires authentication. Please try a different public video.".to_string()
-                }
+                VideoPlatform::TikTok => Msg::TikTokLoginRequired,
+                VideoPlatform::YouTube => Msg::YouTubeLoginRequired,
             }
         } else if error_msg.contains("not comfortable for some audiences") {
-            "This video is age-restricted and cannot be downloaded. Please try a different video."
-                .to_string()
+            Msg::AgeRestricted
         } else if error_msg.contains("Private video") || error_msg.contains("Video unavailable") {
-            "This video is private or unavailable. Please check the URL and try again.".to_string()
+            Msg::PrivateVideo
         } else if error_msg.contains("Video too long") {
-            "Video is too long (maximum 10 minutes allowed).".to_string()
+            Msg::VideoTooLongToDownload
         } else {
             match platform {
-                VideoPlatform::TikTok => {
-                    "Failed to download TikTok video. Make sure it's a public, non-restricted video and try again.".to_string()
-                }
-                VideoPlatform::YouTube => {
-                    "Failed to download YouTube video. Please check the URL and try again.".to_string()
-                }
+                VideoPlatform::TikTok => Msg::TikTokDownloadFailed,
+                VideoPlatform::YouTube => Msg::YouTubeDownloadFailed,
             }
-        }
+        };
+        locale.t(message).to_string()
     }
 
     /// Generate a unique output filename for processed video
@@ -841,12 +2152,86 @@ impl VideoProcessor {
 }
 
 /// Video platform types
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum VideoPlatform {
     YouTube,
     TikTok,
 }
 
+impl VideoPlatform {
+    pub const ALL: [VideoPlatform; 2] = [VideoPlatform::YouTube, VideoPlatform::TikTok];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VideoPlatform::YouTube => "YouTube",
+            VideoPlatform::TikTok => "TikTok",
+        }
+    }
+
+    /// Platform a video URL belongs to, if it is a video link we know how to download
+    pub fn from_url(url: &str) -> Option<Self> {
+        // YouTube URLs
+        if url.contains("youtube.com/watch")
+            || url.contains("youtu.be/")
+            || url.contains("youtube.com/shorts/")
+            || url.contains("m.youtube.com/watch")
+        {
+            return Some(VideoPlatform::YouTube);
+        }
+
+        // TikTok URLs
+        if url.contains("tiktok.com/@")
+            || url.contains("vm.tiktok.com/")
+            || url.contains("vt.tiktok.com/")
+            || url.contains("tiktok.com/t/")
+            || url.contains("m.tiktok.com/")
+        {
+            return Some(VideoPlatform::TikTok);
+        }
+
+        None
+    }
+}
+
+/// Which platforms URL uploads are accepted from, configured by the admin
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PlatformPolicy {
+    /// Platforms turned off, e.g. ["tiktok"] on the work screen
+    pub disabled: Vec<VideoPlatform>,
+}
+
+impl PlatformPolicy {
+    pub fn is_enabled(&self, platform: VideoPlatform) -> bool {
+        !self.disabled.contains(&platform)
+    }
+
+    /// Enabled platforms, in display order
+    pub fn enabled(&self) -> Vec<VideoPlatform> {
+        VideoPlatform::ALL
+            .into_iter()
+            .filter(|platform| self.is_enabled(*platform))
+            .collect()
+    }
+
+    /// Check if URL is a video URL from an enabled platform
+    pub fn is_supported_video_url(&self, url: &str) -> bool {
+        VideoPlatform::from_url(url).is_some_and(|platform| self.is_enabled(platform))
+    }
+
+    /// Why a URL is refused, for the uploader
+    pub fn rejection_message(&self, url: &str) -> String {
+        match VideoPlatform::from_url(url) {
+            Some(platform) => format!("{} videos are disabled on this server.", platform.name()),
+            None => {
+                let names: Vec<&str> = self.enabled().into_iter().map(VideoPlatform::name).collect();
+                format!("Invalid video URL. Supported platforms: {}", names.join(", "))
+            }
+        }
+    }
+}
+
 /// Video metadata from supported platforms
 #[derive(Debug, Clone)]
 pub struct VideoMetadata {
@@ -861,32 +2246,280 @@ pub struct VideoMetadata {
 struct VideoInfo {
     pub width: u32,
     pub height: u32,
+    pub duration_secs: Option<f64>,
+}
+
+/// Whether text has emoji, which drawtext's fonts have no glyphs for
+pub fn has_emoji(text: &str) -> bool {
+    text.chars().any(|c| {
+        matches!(c as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE0F)
+    })
+}
+
+/// Whether text has right-to-left or complex scripts, whose letters drawtext can't join or
+/// reorder: Hebrew, Arabic, Indic, Thai, Tibetan, Myanmar, Khmer
+pub fn needs_shaping(text: &str) -> bool {
+    text.chars().any(|c| {
+        matches!(
+            c as u32,
+            0x0590..=0x08FF | 0x0900..=0x0DFF | 0x0E00..=0x0FFF | 0x1000..=0x109F
+                | 0x1780..=0x17FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF
+        )
+    })
+}
+
+// What the fallback encode gives up: the caption font, and the GPU when there was one
+fn fallback_warnings(captioned: bool, hw_accel: HwAccel) -> Vec<WarningReason> {
+    let mut warnings = Vec::new();
+    if captioned {
+        warnings.push(WarningReason::FallbackFont);
+    }
+    if hw_accel != HwAccel::None {
+        warnings.push(WarningReason::HwaccelFallback);
+    }
+    warnings
+}
+
+/// Height of the 16:9 window cropped out of a vertical video
+fn crop_height(width: u32) -> u32 {
+    (width as u64 * 9 / 16) as u32 & !1
 }
 
-/// Escape special characters in text for ffmpeg drawtext filter
-fn escape_ffmpeg_text(text: &str) -> String {
-    text.replace('\\', "\\\\")
-        .replace('\'', "\\'")
-        .replace(':', "\\:")
-        .replace('[', "\\[")
-        .replace(']', "\\]")
-        .replace(',', "\\,")
-        .replace(';', "\\;")
+/// Top of a `crop`-tall window centred on `center` (0 to 1) and kept inside the video
+fn crop_offset(height: u32, crop: u32, center: f64) -> u32 {
+    let top = (height as f64 * center.clamp(0.0, 1.0) - crop as f64 / 2.0).max(0.0) as u32;
+    top.min(height.saturating_sub(crop)) & !1
+}
+
+/// Vertical centre of the differences between consecutive grayscale frames, from 0 (top) to 1
+/// None when nothing moves
+pub fn motion_center(frames: &[u8], width: u32, height: u32) -> Option<f64> {
+    let (width, height) = (width as usize, height as usize);
+    let mut weighted = 0.0;
+    let mut total = 0.0;
+    for frame in frames.chunks_exact(width * height) {
+        for (row, pixels) in frame.chunks_exact(width).enumerate() {
+            let motion: f64 = pixels.iter().map(|&pixel| pixel as f64).sum();
+            weighted += motion * (row as f64 + 0.5);
+            total += motion;
+        }
+    }
+    (total > 0.0).then(|| weighted / total / height as f64)
+}
+
+/// yt-dlp format selectors tried in order, from ready-to-play mp4 down to whatever exists
+fn format_ladder(max_height: u32) -> Vec<String> {
+    vec![
+        // Single-file mp4, no merging needed
+        format!("mp4[height<={}]", max_height),
+        // Separate H.264 and AAC streams, merged into mp4
+        format!("bestvideo[height<={}][ext=mp4]+bestaudio[ext=m4a]", max_height),
+        // Any codec (VP9/AV1 + Opus), remuxed afterwards
+        format!("bestvideo[height<={}]+bestaudio", max_height),
+        format!("best[height<={}]", max_height),
+        "best".to_string(),
+    ]
+}
+
+fn is_format_unavailable(stderr: &str) -> bool {
+    stderr.contains("Requested format is not available")
+}
+
+/// Percentage from a yt-dlp `--newline` progress line like "[download]  42.3% of 10.00MiB at ..."
+fn parse_progress(line: &str) -> Option<f32> {
+    let rest = line.trim().strip_prefix("[download]")?;
+    let percent = rest.split_whitespace().next()?.strip_suffix('%')?;
+    percent.parse().ok().filter(|percent: &f32| (0.0..=100.0).contains(percent))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn options(quality: QualityProfile, display_max_height: Option<u32>) -> EncodeOptions {
+        EncodeOptions {
+            quality,
+            display_max_height,
+            ..EncodeOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_hardware_encodes_filter_in_system_memory() {
+        let graph = FilterGraph::new()
+            .drawtext(&DrawText::caption("hi", 25))
+            .overlay("watermark.png", "10", "10");
+        let vaapi = EncodeOptions {
+            hw_accel: HwAccel::Vaapi,
+            ..EncodeOptions::default()
+        };
+        let args = VideoProcessor::encode_args("uploads/in.mp4", "uploads/out.mp4", graph, &vaapi);
+        assert!(!args.iter().any(|arg| arg == "-hwaccel_output_format"));
+        let complex = args.iter().position(|arg| arg == "-filter_complex").unwrap();
+        assert!(args[complex + 1].starts_with("[0:v]drawtext=text='hi'"));
+        assert!(args[complex + 1].ends_with("overlay=x=10:y=10,format=nv12,hwupload"));
+        assert_eq!(args[..4], ["-vaapi_device", "/dev/dri/renderD128", "-hwaccel", "vaapi"]);
+
+        let cuda = EncodeOptions {
+            hw_accel: HwAccel::Cuda,
+            ..EncodeOptions::default()
+        };
+        let graph = FilterGraph::new();
+        let args = VideoProcessor::encode_args("uploads/in.mp4", "uploads/out.mp4", graph, &cuda);
+        assert_eq!(args[..4], ["-hwaccel", "cuda", "-i", "uploads/in.mp4"]);
+        assert_eq!(args[4..6], ["-vf", "hwupload_cuda"]);
+        assert_eq!(args.last().unwrap(), "uploads/out.mp4");
+    }
+
+    #[test]
+    fn test_quality_profile_caps_resolution() {
+        let fast = options(QualityProfile::Fast, None);
+        let balanced = options(QualityProfile::Balanced, None);
+        assert_eq!(fast.capped_size(1920, 1080), (1280, 720));
+        assert_eq!(balanced.capped_size(1920, 1080), (1920, 1080));
+        assert_eq!(options(QualityProfile::Quality, None).capped_size(3840, 2160), (3840, 2160));
+        // Portrait videos are capped by their width, keeping even sizes
+        assert_eq!(balanced.capped_size(1080, 1920), (1080, 1920));
+        assert_eq!(fast.capped_size(1080, 1920), (720, 1280));
+        assert_eq!(fast.capped_size(1081, 1921), (720, 1278));
+
+        assert_eq!(fast.scaled_size(1920, 1080), Some((1280, 720)));
+        assert_eq!(balanced.scaled_size(1920, 1080), None);
+        assert!(fast.needs_downscale(1920, 1080));
+        assert!(!balanced.needs_downscale(1080, 1920));
+
+        assert_eq!(QualityProfile::from_name(" Fast "), Some(QualityProfile::Fast));
+        assert_eq!(QualityProfile::from_name("ultra"), None);
+    }
+
+    #[test]
+    fn test_chromakey_tuning() {
+        let key = Chromakey {
+            background: "beach.jpg".to_string(),
+            color: default_key_color(),
+            similarity: default_key_similarity(),
+            blend: default_key_blend(),
+        };
+        let tuned = key.clone().tuned(Some(0.3), None);
+        assert_eq!((tuned.similarity, tuned.blend), (0.3, 0.1));
+        let tuned = key.tuned(Some(0.0), Some(f32::NAN));
+        assert_eq!((tuned.similarity, tuned.blend), (0.01, 0.1));
+    }
+
+    #[test]
+    fn test_framing_vertical_videos() {
+        let blur = EncodeOptions {
+            framing: Framing::BlurFill,
+            ..options(QualityProfile::Balanced, None)
+        };
+        assert!(!blur.needs_framing(1920, 1080));
+        assert!(!options(QualityProfile::Balanced, None).needs_framing(1080, 1920));
+        assert_eq!(blur.output_size(1080, 1920), (1920, 1080));
+        assert_eq!(blur.output_size(720, 1280), (1920, 1080));
+        assert_eq!(blur.output_size(360, 640), (1136, 640));
+
+        let crop = EncodeOptions {
+            framing: Framing::Crop,
+            ..options(QualityProfile::Fast, None)
+        };
+        assert_eq!(crop.output_size(1080, 1920), (1076, 606));
+        assert_eq!(crop.output_size(2160, 3840), (1280, 720));
+        assert_eq!(crop_height(1080), 606);
+        assert_eq!(crop_offset(1920, 606, 0.5), 656);
+        assert_eq!(crop_offset(1920, 606, 0.0), 0);
+        assert_eq!(crop_offset(1920, 606, 1.0), 1314);
+        assert_eq!(Framing::from_name("Blur"), Some(Framing::BlurFill));
+    }
+
+    #[test]
+    fn test_has_emoji() {
+        assert!(has_emoji("gg 😂"));
+        assert!(has_emoji("❤️"));
+        assert!(has_emoji("🇫🇷"));
+        assert!(!has_emoji("GG EZ, à bientôt!"));
+        assert!(!has_emoji(""));
+    }
+
+    #[test]
+    fn test_needs_shaping() {
+        assert!(needs_shaping("مرحبا"));
+        assert!(needs_shaping("gg שלום"));
+        assert!(needs_shaping("नमस्ते"));
+        assert!(needs_shaping("สวัสดี"));
+        assert!(!needs_shaping("Привет, γεια, こんにちは"));
+        assert!(!needs_shaping("gg 😂"));
+    }
+
+    #[test]
+    fn test_fallback_warnings() {
+        assert!(fallback_warnings(false, HwAccel::None).is_empty());
+        assert_eq!(
+            fallback_warnings(true, HwAccel::Vaapi),
+            [WarningReason::FallbackFont, WarningReason::HwaccelFallback]
+        );
+    }
+
+    #[test]
+    fn test_motion_center() {
+        // Two 2x4 frames, moving in the bottom row then in the top one
+        let frames = [0, 0, 0, 0, 0, 0, 10, 10, 10, 10, 0, 0, 0, 0, 0, 0];
+        assert_eq!(motion_center(&frames[..8], 2, 4), Some(0.875));
+        assert_eq!(motion_center(&frames, 2, 4), Some(0.5));
+        assert_eq!(motion_center(&[0; 8], 2, 4), None);
+    }
+
+    #[test]
+    fn test_display_caps_resolution() {
+        // The display cap wins over a quality profile that keeps the source resolution
+        let quality = options(QualityProfile::Quality, Some(1080));
+        assert_eq!(quality.capped_size(3840, 2160), (1920, 1080));
+        assert_eq!(quality.scaled_size(3840, 2160), Some((1920, 1080)));
+        assert!(quality.needs_downscale(3840, 2160));
+        assert!(!quality.needs_downscale(1920, 1080));
+        assert!(!options(QualityProfile::Quality, None).needs_downscale(3840, 2160));
+
+        // and the quality cap wins when it is lower
+        let fast = options(QualityProfile::Fast, Some(1080));
+        assert_eq!(fast.scaled_size(3840, 2160), Some((1280, 720)));
+    }
+
+    #[test]
+    fn test_format_ladder() {
+        let ladder = format_ladder(1080);
+        assert_eq!(ladder.first().unwrap(), "mp4[height<=1080]");
+        assert_eq!(ladder.last().unwrap(), "best");
+        assert!(ladder.iter().any(|format| format == "bestvideo[height<=1080]+bestaudio"));
+
+        assert!(is_format_unavailable(
+            "ERROR: [youtube] abc: Requested format is not available. Use --list-formats"
+        ));
+        assert!(!is_format_unavailable("ERROR: Private video"));
+    }
+
     #[test]
-    fn test_escape_ffmpeg_text() {
-        assert_eq!(escape_ffmpeg_text("Hello World"), "Hello World");
-        assert_eq!(escape_ffmpeg_text("Hello: World"), "Hello\\: World");
-        assert_eq!(escape_ffmpeg_text("Hello [World]"), "Hello \\[World\\]");
+    fn test_parse_progress() {
         assert_eq!(
-            escape_ffmpeg_text("Hello, World; Test"),
-            "Hello\\, World\\; Test"
+            parse_progress("[download]  42.3% of   10.00MiB at    1.00MiB/s ETA 00:06"),
+            Some(42.3)
         );
+        assert_eq!(parse_progress("[download] 100% of 10.00MiB in 00:00:05"), Some(100.0));
+        assert_eq!(parse_progress("[download] Destination: uploads/video.mp4"), None);
+        assert_eq!(parse_progress("uploads/video_1_download.mp4"), None);
+    }
+
+    #[test]
+    fn test_image_conversion_applies_to_large_stills() {
+        let conversion = ImageConversion {
+            format: ImageFormat::Webp,
+            min_bytes: 1000,
+        };
+        assert!(conversion.applies_to("photo.JPG", 1000));
+        assert!(conversion.applies_to("screenshot.png", 5000));
+        assert!(!conversion.applies_to("screenshot.png", 999));
+        assert!(!conversion.applies_to("party.gif", 5000));
+        assert!(!conversion.applies_to("already.webp", 5000));
+        assert_eq!(ImageFormat::from_name(" AVIF "), Some(ImageFormat::Avif));
+        assert_eq!(ImageFormat::Original.extension(), None);
     }
 
     #[test]
@@ -899,6 +2532,13 @@ mod tests {
         assert!(result.starts_with("video_captioned_"));
     }
 
+    #[test]
+    fn test_thumbnail_filenames() {
+        assert_eq!(VideoProcessor::poster_filename("clip_01.mp4"), "clip_01.jpg");
+        assert_eq!(VideoProcessor::poster_filename("no_extension"), "no_extension.jpg");
+        assert_eq!(VideoProcessor::preview_filename("clip_01.mp4"), "clip_01.webp");
+    }
+
     #[test]
     fn test_wrap_text() {
         // Short text should not be wrapped
@@ -917,45 +2557,59 @@ mod tests {
 
     #[test]
     fn test_is_supported_video_url() {
+        let policy = PlatformPolicy::default();
+
         // YouTube URLs
-        assert!(VideoProcessor::is_supported_video_url(
+        assert!(policy.is_supported_video_url(
             "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
         ));
-        assert!(VideoProcessor::is_supported_video_url(
+        assert!(policy.is_supported_video_url(
             "https://youtu.be/dQw4w9WgXcQ"
         ));
-        assert!(VideoProcessor::is_supported_video_url(
+        assert!(policy.is_supported_video_url(
             "https://www.youtube.com/shorts/abc123"
         ));
-        assert!(VideoProcessor::is_supported_video_url(
+        assert!(policy.is_supported_video_url(
             "https://m.youtube.com/watch?v=dQw4w9WgXcQ"
         ));
 
         // TikTok URLs
-        assert!(VideoProcessor::is_supported_video_url(
+        assert!(policy.is_supported_video_url(
             "https://www.tiktok.com/@user/video/1234567890"
         ));
-        assert!(VideoProcessor::is_supported_video_url(
+        assert!(policy.is_supported_video_url(
             "https://vm.tiktok.com/abc123"
         ));
-        assert!(VideoProcessor::is_supported_video_url(
+        assert!(policy.is_supported_video_url(
             "https://vt.tiktok.com/abc123"
         ));
-        assert!(VideoProcessor::is_supported_video_url(
+        assert!(policy.is_supported_video_url(
             "https://tiktok.com/t/abc123"
         ));
-        assert!(VideoProcessor::is_supported_video_url(
+        assert!(policy.is_supported_video_url(
             "https://m.tiktok.com/@user/video/1234567890"
         ));
 
         // Invalid URLs
-        assert!(!VideoProcessor::is_supported_video_url(
+        assert!(!policy.is_supported_video_url(
             "https://www.example.com"
         ));
-        assert!(!VideoProcessor::is_supported_video_url(
+        assert!(!policy.is_supported_video_url(
             "https://www.instagram.com/p/abc123"
         ));
-        assert!(!VideoProcessor::is_supported_video_url(""));
+        assert!(!policy.is_supported_video_url(""));
+
+        // Disabled platforms are refused
+        let policy = PlatformPolicy {
+            disabled: vec![VideoPlatform::TikTok],
+        };
+        assert!(policy.is_supported_video_url("https://youtu.be/dQw4w9WgXcQ"));
+        assert!(!policy.is_supported_video_url("https://vm.tiktok.com/abc123"));
+        assert_eq!(policy.enabled(), vec![VideoPlatform::YouTube]);
+        assert_eq!(
+            policy.rejection_message("https://vm.tiktok.com/abc123"),
+            "TikTok videos are disabled on this server."
+        );
     }
 
     #[test]
@@ -994,24 +2648,31 @@ mod tests {
 
         // Test TikTok authentication error
         let auth_error = "Log in for access. Use --cookies-from-browser";
-        let result = VideoProcessor::get_user_friendly_error(auth_error, tiktok_url);
+        let result = VideoProcessor::get_user_friendly_error(auth_error, tiktok_url, Locale::En);
         assert!(result.contains("TikTok video requires login"));
         assert!(result.contains("age-restricted"));
 
         // Test age-restricted content
         let age_error = "not comfortable for some audiences";
-        let result = VideoProcessor::get_user_friendly_error(age_error, tiktok_url);
+        let result = VideoProcessor::get_user_friendly_error(age_error, tiktok_url, Locale::En);
         assert!(result.contains("age-restricted"));
 
         // Test private video
         let private_error = "Private video";
-        let result = VideoProcessor::get_user_friendly_error(private_error, youtube_url);
+        let result =
+            VideoProcessor::get_user_friendly_error(private_error, youtube_url, Locale::En);
         assert!(result.contains("private or unavailable"));
 
         // Test generic TikTok error
         let generic_error = "Some other error";
-        let result = VideoProcessor::get_user_friendly_error(generic_error, tiktok_url);
+        let result =
+            VideoProcessor::get_user_friendly_error(generic_error, tiktok_url, Locale::En);
         assert!(result.contains("TikTok video"));
         assert!(result.contains("public, non-restricted"));
+
+        // Test the same error in French
+        let result =
+            VideoProcessor::get_user_friendly_error(private_error, youtube_url, Locale::Fr);
+        assert!(result.contains("privée ou indisponible"));
     }
 }
@@ -0,0 +1,167 @@
+use crate::errors::AppError;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::process::Command as AsyncCommand;
+
+const SCAN_TIMEOUT: Duration = Duration::from_secs(120);
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Optional ClamAV scan of uploads before they are shown
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct VirusScan {
+    pub enabled: bool,
+    /// clamd socket, a Unix socket path or host:port; `clamscan` is run when unset
+    pub clamd: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScanVerdict {
+    Clean,
+    /// Name of the detected signature
+    Infected(String),
+}
+
+impl VirusScan {
+    /// Scan a stored file, always clean when scanning is turned off
+    pub async fn scan_file(&self, path: &str) -> Result<ScanVerdict, AppError> {
+        if !self.enabled {
+            return Ok(ScanVerdict::Clean);
+        }
+
+        let scan = async {
+            match &self.clamd {
+                Some(address) => scan_with_clamd(address, path).await,
+                None => scan_with_clamscan(path).await,
+            }
+        };
+        let verdict = tokio::time::timeout(SCAN_TIMEOUT, scan)
+            .await
+            .map_err(|_| AppError::IoError(std::io::Error::other("Virus scan timed out")))??;
+
+        match &verdict {
+            ScanVerdict::Clean => tracing::info!("Virus scan clean: {}", path),
+            ScanVerdict::Infected(signature) => {
+                tracing::warn!("Virus scan detected {} in {}", signature, path)
+            }
+        }
+        Ok(verdict)
+    }
+}
+
+async fn scan_with_clamd(address: &str, path: &str) -> Result<ScanVerdict, AppError> {
+    let file = tokio::fs::File::open(path).await?;
+    // Paths are Unix sockets, anything else is host:port
+    let reply = if address.contains('/') {
+        instream(UnixStream::connect(address).await?, file).await?
+    } else {
+        instream(TcpStream::connect(address).await?, file).await?
+    };
+    parse_clamd_reply(&reply)
+}
+
+/// Stream the file to clamd with the INSTREAM command and return its reply
+async fn instream(
+    mut socket: impl AsyncRead + AsyncWrite + Unpin,
+    mut file: impl AsyncRead + Unpin,
+) -> Result<String, AppError> {
+    socket.write_all(b"zINSTREAM\0").await?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        // Each chunk is prefixed with its length, a zero length ends the stream
+        socket.write_all(&(read as u32).to_be_bytes()).await?;
+        if read == 0 {
+            break;
+        }
+        socket.write_all(&buffer[..read]).await?;
+    }
+    socket.flush().await?;
+
+    let mut reply = Vec::new();
+    socket.read_to_end(&mut reply).await?;
+    Ok(String::from_utf8_lossy(&reply).to_string())
+}
+
+/// Parse a clamd reply such as `stream: OK` or `stream: Eicar-Signature FOUND`
+fn parse_clamd_reply(reply: &str) -> Result<ScanVerdict, AppError> {
+    let reply = reply.trim_end_matches(['\0', '\n']).trim();
+    let result = reply.split_once(": ").map_or(reply, |(_, result)| result);
+    if result == "OK" {
+        Ok(ScanVerdict::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(ScanVerdict::Infected(signature.to_string()))
+    } else {
+        Err(AppError::IoError(std::io::Error::other(format!(
+            "clamd error: {}",
+            reply
+        ))))
+    }
+}
+
+async fn scan_with_clamscan(path: &str) -> Result<ScanVerdict, AppError> {
+    let output = AsyncCommand::new("clamscan")
+        .args(["--no-summary", "--stdout", path])
+        .output()
+        .await?;
+    // Exit code 1 means a virus was found, anything else but 0 is an error
+    match output.status.code() {
+        Some(0) => Ok(ScanVerdict::Clean),
+        Some(1) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            parse_clamd_reply(stdout.lines().next().unwrap_or_default())
+        }
+        _ => Err(AppError::IoError(std::io::Error::other(format!(
+            "clamscan failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clamd_reply() {
+        assert_eq!(parse_clamd_reply("stream: OK\0").unwrap(), ScanVerdict::Clean);
+        assert_eq!(
+            parse_clamd_reply("stream: Eicar-Signature FOUND\0").unwrap(),
+            ScanVerdict::Infected("Eicar-Signature".to_string())
+        );
+        assert_eq!(
+            parse_clamd_reply("uploads/a.png: Win.Test.EICAR_HDB-1 FOUND\n").unwrap(),
+            ScanVerdict::Infected("Win.Test.EICAR_HDB-1".to_string())
+        );
+        assert!(parse_clamd_reply("INSTREAM size limit exceeded. ERROR\0").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_instream_framing() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let clamd = tokio::spawn(async move {
+            let mut command = [0u8; 10];
+            server.read_exact(&mut command).await.unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+
+            let mut data = Vec::new();
+            loop {
+                let length = server.read_u32().await.unwrap() as usize;
+                if length == 0 {
+                    break;
+                }
+                let mut chunk = vec![0u8; length];
+                server.read_exact(&mut chunk).await.unwrap();
+                data.extend(chunk);
+            }
+            server.write_all(b"stream: OK\0").await.unwrap();
+            data
+        });
+
+        let reply = instream(client, &b"hello clamd"[..]).await.unwrap();
+        assert_eq!(parse_clamd_reply(&reply).unwrap(), ScanVerdict::Clean);
+        assert_eq!(clamd.await.unwrap(), b"hello clamd");
+    }
+}
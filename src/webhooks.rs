@@ -0,0 +1,210 @@
+use crate::events::Event;
+use crate::retry::RetryPolicy;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Header carrying `sha256=<hex HMAC of the body>` when the webhook has a secret
+pub const SIGNATURE_HEADER: &str = "x-homies-signature";
+
+/// Endpoint receiving a JSON POST for backend events, e.g. a Home Assistant or n8n webhook
+#[derive(Clone, Debug, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Key the body is signed with, receivers should check the signature header with it
+    pub secret: Option<String>,
+    /// Event names to send, all of them when empty
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Deliveries tried before giving up on an event
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+}
+
+fn default_max_attempts() -> u32 {
+    5
+}
+
+impl WebhookConfig {
+    fn wants(&self, event: &Event) -> bool {
+        self.events.is_empty() || self.events.iter().any(|name| name == event.name())
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    #[serde(flatten)]
+    event: &'a Event,
+    /// Unix seconds, lets receivers drop replayed deliveries
+    timestamp: u64,
+}
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Webhook request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Webhook returned HTTP {0}")]
+    Status(u16),
+}
+
+/// Hex HMAC-SHA256 of a payload, as sent in the signature header
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+async fn post(
+    client: &reqwest::Client,
+    webhook: &WebhookConfig,
+    event: &Event,
+    body: &[u8],
+) -> Result<(), WebhookError> {
+    let mut request = client
+        .post(&webhook.url)
+        .header("content-type", "application/json")
+        .header("x-homies-event", event.name())
+        .body(body.to_vec());
+    if let Some(secret) = &webhook.secret {
+        request = request.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, body)));
+    }
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(WebhookError::Status(response.status().as_u16()));
+    }
+    Ok(())
+}
+
+/// Deliver an event to one webhook, backing off between failed attempts
+pub async fn deliver(client: &reqwest::Client, webhook: &WebhookConfig, event: &Event) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let body = match serde_json::to_vec(&Payload { event, timestamp }) {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Failed to serialize {} webhook: {}", event.name(), e);
+            return;
+        }
+    };
+
+    let policy = RetryPolicy::default().max_attempts(webhook.max_attempts);
+    let mut attempt = 1;
+    loop {
+        match post(client, webhook, event, &body).await {
+            Ok(()) => {
+                tracing::info!("Delivered {} webhook to {}", event.name(), webhook.url);
+                return;
+            }
+            Err(e) if attempt >= policy.max_attempts => {
+                tracing::error!(
+                    "Giving up on {} webhook to {} after {} attempts: {}",
+                    event.name(),
+                    webhook.url,
+                    attempt,
+                    e
+                );
+                return;
+            }
+            Err(e) => {
+                let delay = policy.backoff(attempt);
+                tracing::warn!(
+                    "{} webhook to {} failed ({}), retrying in {:?}",
+                    event.name(),
+                    webhook.url,
+                    e,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Send every queued event to the webhooks that want it, each delivery on its own task so a
+/// receiver that is down doesn't hold up the others
+pub async fn run(mut events: mpsc::UnboundedReceiver<Event>, webhooks: Vec<WebhookConfig>) {
+    let client = match reqwest::Client::builder().timeout(DELIVERY_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!("Failed to set up the webhook client: {}", e);
+            return;
+        }
+    };
+    let webhooks: Vec<_> = webhooks.into_iter().map(Arc::new).collect();
+    while let Some(event) = events.recv().await {
+        let event = Arc::new(event);
+        for webhook in webhooks.iter().filter(|webhook| webhook.wants(&event)) {
+            let client = client.clone();
+            let webhook = webhook.clone();
+            let event = event.clone();
+            tokio::spawn(async move { deliver(&client, &webhook, &event).await });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventMedia;
+    use crate::state::MediaType;
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_payload_and_event_filter() {
+        let event = Event::JobFailed {
+            job_id: 3,
+            url: "https://youtu.be/x".to_string(),
+        };
+        let payload = serde_json::to_value(Payload {
+            event: &event,
+            timestamp: 42,
+        })
+        .unwrap();
+        assert_eq!(
+            payload,
+            serde_json::json!({
+                "event": "job_failed",
+                "job_id": 3,
+                "url": "https://youtu.be/x",
+                "timestamp": 42
+            })
+        );
+
+        let webhook = WebhookConfig {
+            url: "http://ha.lan/api/webhook/homies".to_string(),
+            secret: None,
+            events: vec!["media_shown".to_string()],
+            max_attempts: 1,
+        };
+        assert!(!webhook.wants(&event));
+        assert!(webhook.wants(&Event::MediaShown {
+            media: EventMedia {
+                id: 1,
+                filename: "a.png".to_string(),
+                media_type: MediaType::Image,
+                caption: String::new(),
+            }
+        }));
+    }
+}
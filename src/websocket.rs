@@ -1,25 +1,131 @@
 // use percent_encoding::percent_encode;
+use crate::banner::BannerStatus;
+use crate::chat::{ChatConfig, ChatError, ChatMessage};
+use crate::drawing::Stroke;
+use crate::events::WarningReason;
+use crate::game_status::GameStatus;
+use crate::handlers::displays;
+use crate::jobs::Job;
+use crate::media_probe::MediaProbe;
+use crate::music::MusicStatus;
+use crate::now_playing::NowPlaying;
+use crate::playback::PlaybackEpoch;
+use crate::polls::PollStatus;
+use crate::session::ClientId;
+use crate::state::{MediaInfo, MediaSource, MediaType};
+use crate::state_actor::StateHandle;
+use crate::tasks;
+use crate::theme::Theme;
+use crate::timers::TimerStatus;
+use crate::twitch::LiveStream;
+use crate::types::{LiveStatus, WsEvent};
 use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use serde::Serialize;
 use serde_json::json;
+use std::net::IpAddr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::{RwLock, broadcast};
 
 const FRAGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
 
+/// Events delivered to `/ws?mode=overlay` clients, the streaming overlay has no use for the rest
+pub const OVERLAY_EVENTS: &[&str] = &[
+    "reaction",
+    "caption",
+    "now_playing_music",
+    "timer",
+    "poll",
+    "poll_tally",
+    "poll_results",
+    "draw",
+    "draw_clear",
+    "chat",
+    "resync",
+];
+
 // Use warp's Message type consistently
 pub type WsClients = Arc<RwLock<broadcast::Sender<warp::ws::Message>>>;
 
-pub fn create_ws_state() -> WsClients {
-    let (tx, _rx) = broadcast::channel(100);
-    tracing::info!("Created WebSocket broadcast channel with capacity 100");
+// Times a client fell behind and the broadcasts it missed, since startup
+static LAGGED: AtomicU64 = AtomicU64::new(0);
+static MISSED_MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+/// Clients falling behind the broadcast channel, counted since startup
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct LagStats {
+    /// Resync events sent to clients that fell behind
+    pub lagged: u64,
+    /// Broadcasts dropped before a lagging client could read them
+    pub missed_messages: u64,
+}
+
+pub fn lag_stats() -> LagStats {
+    LagStats {
+        lagged: LAGGED.load(Ordering::Relaxed),
+        missed_messages: MISSED_MESSAGES.load(Ordering::Relaxed),
+    }
+}
+
+/// `capacity` broadcasts are kept for slow clients, the oldest are dropped past that
+pub fn create_ws_state(capacity: usize) -> WsClients {
+    let capacity = capacity.max(1);
+    let (tx, _rx) = broadcast::channel(capacity);
+    tracing::info!("Created WebSocket broadcast channel with capacity {}", capacity);
     Arc::new(RwLock::new(tx))
 }
 
-pub async fn broadcast_new_media(clients: &WsClients) {
+// Next message for a client, a client that fell behind gets a resync event in place of
+// what it missed so it can refetch the current state instead of silently going stale
+async fn next_message(
+    rx: &mut broadcast::Receiver<warp::ws::Message>,
+) -> Option<warp::ws::Message> {
+    match rx.recv().await {
+        Ok(message) => Some(message),
+        Err(RecvError::Lagged(missed)) => {
+            tracing::warn!("WebSocket client lagged behind, {} messages missed", missed);
+            LAGGED.fetch_add(1, Ordering::Relaxed);
+            MISSED_MESSAGES.fetch_add(missed, Ordering::Relaxed);
+            let message_json = json!(WsEvent::Resync { missed });
+            Some(warp::ws::Message::text(message_json.to_string()))
+        }
+        Err(RecvError::Closed) => None,
+    }
+}
+
+/// Broadcasts as JSON for consumers other than WebSocket clients, e.g. GraphQL subscriptions
+/// Falling behind gives a resync event, like on /ws
+pub async fn event_stream(
+    clients: &WsClients,
+) -> impl futures_util::Stream<Item = serde_json::Value> + use<> {
+    let rx = clients.read().await.subscribe();
+    futures_util::stream::unfold(rx, |mut rx| async move {
+        loop {
+            let message = next_message(&mut rx).await?;
+            if let Some(json) = message
+                .to_str()
+                .ok()
+                .and_then(|text| serde_json::from_str(text).ok())
+            {
+                return Some((json, rx));
+            }
+        }
+    })
+}
+
+pub async fn broadcast_new_media(
+    clients: &WsClients,
+    media_id: u64,
+    probe: &MediaProbe,
+    source: MediaSource,
+) {
     tracing::info!("Broadcasting new media event");
-    let message_json = json!({
-        "event": "browser_backend",
-        "url": "/?ws=true"
+    let message_json = json!(WsEvent::BrowserBackend {
+        url: "/?ws=true".to_string(),
+        media_id,
+        layout: probe.layout_hint(),
+        source,
     });
 
     let message_string = message_json.to_string();
@@ -36,7 +142,8 @@ pub async fn broadcast_new_song(clients: &WsClients, uri: String) {
     let encoded_uri = utf8_percent_encode(&uri, FRAGMENT).to_string();
     let message_json = json!({
         "event": "song",
-        "url": format!("/sounds/{}?ws=true", encoded_uri)
+        "url": format!("/sounds/{}?ws=true", encoded_uri),
+        "source": MediaSource::Soundboard,
     });
 
     let message_string = message_json.to_string();
@@ -64,12 +171,277 @@ pub async fn broadcast_new_browser_raw(clients: &WsClients, url: String) {
     tracing::info!("Broadcast new browser raw result: {:?}", result);
 }
 
-pub async fn broadcast_video_event(clients: &WsClients, filename: String) {
-    let video_url = format!("/uploads/{}", filename);
-    tracing::info!("Broadcasting video event for: {}", video_url);
+/// Tell displays what the background music channel should be doing
+pub async fn broadcast_music(clients: &WsClients, action: &str, status: &MusicStatus) {
+    tracing::info!("Broadcasting music event: {}", action);
+    let url = status
+        .track
+        .as_ref()
+        .map(|track| format!("/music/{}", utf8_percent_encode(&track.filename, FRAGMENT)));
+    let message_json = json!({
+        "event": "music",
+        "action": action,
+        "url": url,
+        "playing": status.playing,
+        "volume": status.volume as f64 / 100.0,
+        "track": status.track
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::info!("Broadcast music result: {:?}", result);
+}
+
+/// Track to show as an ambient overlay between media, None to hide it
+pub async fn broadcast_now_playing(clients: &WsClients, track: Option<&NowPlaying>) {
+    tracing::info!(
+        "Broadcasting now playing event: {:?}",
+        track.map(|track| &track.title)
+    );
+    let message_json = json!({
+        "event": "now_playing_music",
+        "title": track.map(|track| &track.title),
+        "artist": track.and_then(|track| track.artist.as_ref()),
+        "album": track.and_then(|track| track.album.as_ref()),
+        "artwork_url": track.and_then(|track| track.artwork_url.as_ref()),
+        "source": track.and_then(|track| track.source.as_ref())
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::info!("Broadcast now playing result: {:?}", result);
+}
+
+pub async fn broadcast_game_status(clients: &WsClients, servers: &[GameStatus]) {
+    let message_json = json!({
+        "event": "game_status",
+        "servers": servers
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::debug!("Broadcast game status result: {:?}", result);
+}
+
+/// Open a Twitch stream that just went live on the display
+pub async fn broadcast_twitch_live(clients: &WsClients, stream: &LiveStream, embed_url: &str) {
+    tracing::info!("Broadcasting Twitch live event: {}", stream.user_login);
+    let message_json = json!({
+        "event": "browser_raw",
+        "url": embed_url,
+        "source": "twitch",
+        "channel": stream.user_name,
+        "title": stream.title,
+        "game": stream.game_name
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::info!("Broadcast Twitch live result: {:?}", result);
+}
+
+/// Screen share starting with the playlist to play, or ending when there's none
+pub async fn broadcast_live(clients: &WsClients, playlist: Option<&str>) {
+    tracing::info!("Broadcasting live event: {:?}", playlist);
+    let message_json = json!(WsEvent::Live {
+        status: match playlist {
+            Some(_) => LiveStatus::Started,
+            None => LiveStatus::Ended,
+        },
+        url: playlist.map(str::to_string),
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::info!("Broadcast live result: {:?}", result);
+}
+
+/// Reaction to what's on screen, floated up by overlays
+pub async fn broadcast_reaction(clients: &WsClients, emoji: &str, media_id: u64) {
+    tracing::info!("Broadcasting reaction {} to media {}", emoji, media_id);
+    let message_json = json!({
+        "event": "reaction",
+        "emoji": emoji,
+        "media_id": media_id
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::debug!("Broadcast reaction result: {:?}", result);
+}
+
+/// Countdown on screen, sent on changes and re-sent every few seconds so displays stay in sync
+pub async fn broadcast_timer(clients: &WsClients, timer: &TimerStatus) {
+    let message_json = json!({
+        "event": "timer",
+        "id": timer.id,
+        "label": timer.label,
+        "phase": timer.phase,
+        "remaining_secs": timer.remaining_secs,
+        "ends_at_ms": timer.ends_at_ms
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::debug!("Broadcast timer result: {:?}", result);
+}
+
+/// Poll news for the screen: `poll` when it opens, `poll_tally` on each vote and
+/// `poll_results` once it closes
+pub async fn broadcast_poll(clients: &WsClients, event: &str, poll: &PollStatus) {
+    tracing::info!("Broadcasting {} for poll {}", event, poll.id);
+    let message_json = json!({
+        "event": event,
+        "poll": poll
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::debug!("Broadcast poll result: {:?}", result);
+}
+
+/// Batch of strokes for displays to add to the shared doodle
+pub async fn broadcast_strokes(clients: &WsClients, strokes: &[Stroke]) {
+    let message_json = json!({
+        "event": "draw",
+        "strokes": strokes
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::debug!("Broadcast strokes result: {:?}", result);
+}
+
+pub async fn broadcast_canvas_cleared(clients: &WsClients) {
+    tracing::info!("Broadcasting canvas clear");
+    let message_json = json!({ "event": "draw_clear" });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::info!("Broadcast canvas clear result: {:?}", result);
+}
+
+/// Ticker message for displays to scroll across the bottom of the screen
+pub async fn broadcast_chat(clients: &WsClients, message: &ChatMessage) {
+    let message_json = json!({
+        "event": "chat",
+        "message": message
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::debug!("Broadcast chat result: {:?}", result);
+}
+
+/// Add a ticker message from `sender`, connected from `ip`, and put it on screen, shared by
+/// `POST /chat` and the WebSocket `chat` message
+pub async fn post_chat(
+    state: &StateHandle,
+    clients: &WsClients,
+    sender: ClientId,
+    ip: Option<IpAddr>,
+    author: String,
+    text: String,
+    config: ChatConfig,
+) -> Result<ChatMessage, ChatError> {
+    let message = state
+        .call(move |state| {
+            // A banned address stays banned whatever session it chats with
+            if state.bans().find_client(&sender, ip, state.now()).is_some() {
+                return Err(ChatError::Banned);
+            }
+            let text = state.word_filter().check_now(&text).ok_or(ChatError::Refused)?;
+            let now = state.now();
+            state.chat_mut().post(&sender, &author, &text, now, &config)
+        })
+        .await?;
+    broadcast_chat(clients, &message).await;
+    Ok(message)
+}
+
+/// Restyle the display pages without a reload
+pub async fn broadcast_theme(clients: &WsClients, theme: Theme) {
+    tracing::info!("Broadcasting theme change to {}", theme);
+    let message_json = json!(WsEvent::ThemeChanged { theme });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::info!("Broadcast theme change result: {:?}", result);
+}
+
+/// Pin a banner over the displays, or take it down when `banner` is none
+pub async fn broadcast_banner(clients: &WsClients, banner: Option<&BannerStatus>) {
+    tracing::info!("Broadcasting banner: {:?}", banner.map(|banner| &banner.text));
+    let message_json = json!(WsEvent::Banner {
+        banner: banner.cloned(),
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::info!("Broadcast banner result: {:?}", result);
+}
+
+/// Open the stats recap on the display
+pub async fn broadcast_recap(clients: &WsClients, days: u64) {
+    tracing::info!("Broadcasting stats recap");
     let message_json = json!({
-        "event": "video",
-        "url": video_url
+        "event": "browser_raw",
+        "url": format!("/stats/recap?days={}", days),
+        "source": "recap"
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::info!("Broadcast stats recap result: {:?}", result);
+}
+
+pub async fn broadcast_video_event(
+    clients: &WsClients,
+    media: &MediaInfo,
+    playback: Option<PlaybackEpoch>,
+) {
+    let video_url = format!("/uploads/{}", media.filename);
+    tracing::info!("Broadcasting video event for: {}", video_url);
+    let message_json = json!(WsEvent::Video {
+        url: video_url.clone(),
+        media_id: media.id,
+        layout: media.probe.layout_hint(),
+        poster: media.poster.clone(),
+        // Swapped in for `url` for clients on a slow link, then dropped
+        low_url: media.low_rendition.clone(),
+        hls: media.hls.clone(),
+        // Lined up with GET /time
+        start_at: playback.map(|playback| playback.start_at_ms),
+        source: media.source,
+        platform: media.platform,
     });
 
     let message_string = message_json.to_string();
@@ -83,19 +455,318 @@ pub async fn broadcast_video_event(clients: &WsClients, filename: String) {
     tracing::info!("Broadcasted video event for: {}", video_url);
 }
 
+pub async fn broadcast_job_progress(clients: &WsClients, job: &Job) {
+    let message_json = json!(WsEvent::JobProgress { job: job.status() });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::debug!("Broadcast job progress result: {:?}", result);
+}
+
+pub async fn broadcast_job_cancelled(clients: &WsClients, job: &Job) {
+    tracing::info!("Broadcasting job cancelled event: {}", job.id);
+    let message_json = json!(WsEvent::JobCancelled { job: job.status() });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::info!("Broadcast job cancelled result: {:?}", result);
+}
+
+/// Tell upload pages what an upload went without, e.g. "caption skipped: ffmpeg missing"
+pub async fn broadcast_processing_warning(
+    clients: &WsClients,
+    filename: &str,
+    reason: WarningReason,
+) {
+    tracing::info!("Broadcasting processing warning for {}: {:?}", filename, reason);
+    let message_json = json!(WsEvent::ProcessingWarning {
+        filename: filename.to_string(),
+        reason,
+        message: reason.message().to_string(),
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::debug!("Broadcast processing warning result: {:?}", result);
+}
+
+/// Announce media that just went on screen with the event matching its type
+/// Videos carry the playback epoch the state started for them
+pub async fn broadcast_media(
+    clients: &WsClients,
+    media: &MediaInfo,
+    playback: Option<PlaybackEpoch>,
+) {
+    match media.media_type {
+        MediaType::Image => {
+            broadcast_new_media(clients, media.id, &media.probe, media.source).await
+        }
+        MediaType::Video => broadcast_video_event(clients, media, playback).await,
+    }
+    // Burned in captions are cleared from the media info, only shown ones go out
+    if !media.caption.is_empty() {
+        broadcast_caption(clients, &media.caption, media.play_secs, Some(media.source)).await;
+    }
+}
+
+/// Caption of the media on screen, for overlays showing it on their own
+pub async fn broadcast_caption(
+    clients: &WsClients,
+    caption: &str,
+    duration_secs: u64,
+    source: Option<MediaSource>,
+) {
+    let message_json = json!(WsEvent::Caption {
+        text: caption.to_string(),
+        duration_secs,
+        source,
+    });
+
+    let ws_message = warp::ws::Message::text(message_json.to_string());
+
+    let sender = clients.read().await;
+    let result = sender.send(ws_message);
+    tracing::debug!("Broadcast caption result: {:?}", result);
+}
+
+/// Sources a client takes media from, `?sources=web-form,url-download` for only those or
+/// `?ignore_sources=soundboard,tiktok` to skip some
+/// Matched against the `source` and `platform` of media, caption and sound events, events
+/// without either always go through
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceFilter {
+    only: Vec<String>,
+    ignore: Vec<String>,
+}
+
+impl SourceFilter {
+    pub fn from_query(query: &std::collections::HashMap<String, String>) -> Self {
+        let list = |name: &str| -> Vec<String> {
+            query
+                .get(name)
+                .map(|names| {
+                    names
+                        .split(',')
+                        .map(|name| name.trim().to_lowercase())
+                        .filter(|name| !name.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Self {
+            only: list("sources"),
+            ignore: list("ignore_sources"),
+        }
+    }
+
+    pub fn allows(&self, message: &warp::ws::Message) -> bool {
+        if self.only.is_empty() && self.ignore.is_empty() {
+            return true;
+        }
+        let Some(json) = message
+            .to_str()
+            .ok()
+            .and_then(|text| serde_json::from_str::<serde_json::Value>(text).ok())
+        else {
+            return true;
+        };
+        let tags: Vec<&str> = ["source", "platform"]
+            .iter()
+            .filter_map(|key| json[key].as_str())
+            .collect();
+        if tags.is_empty() {
+            return true;
+        }
+        let listed = |names: &[String]| tags.iter().any(|tag| names.iter().any(|name| name == tag));
+        !listed(&self.ignore) && (self.only.is_empty() || listed(&self.only))
+    }
+}
+
+// What a client is sent
+#[derive(Clone, Debug, Default)]
+struct EventFilter {
+    events: Option<&'static [&'static str]>, // Only these when set
+    sources: SourceFilter,
+}
+
+impl EventFilter {
+    fn allows(&self, message: &warp::ws::Message) -> bool {
+        self.events.is_none_or(|events| is_event(message, events)) && self.sources.allows(message)
+    }
+}
+
+// Whether a message is one of the given events, anything that isn't a JSON event is dropped
+fn is_event(message: &warp::ws::Message, events: &[&str]) -> bool {
+    message
+        .to_str()
+        .ok()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(text).ok())
+        .and_then(|json| json["event"].as_str().map(|event| events.contains(&event)))
+        .unwrap_or(false)
+}
+
+// Strokes of a `{"event": "draw", "strokes": [...]}` message, sent by drawing pages
+fn draw_strokes(message: &warp::ws::Message) -> Option<Vec<Stroke>> {
+    #[derive(serde::Deserialize)]
+    struct Draw {
+        event: String,
+        strokes: Vec<Stroke>,
+    }
+    let draw: Draw = serde_json::from_str(message.to_str().ok()?).ok()?;
+    (draw.event == "draw").then_some(draw.strokes)
+}
+
+// Name and text of a `{"event": "chat", "text": "...", "name": "..."}` message
+fn chat_text(message: &warp::ws::Message) -> Option<(String, String)> {
+    let json: serde_json::Value = serde_json::from_str(message.to_str().ok()?).ok()?;
+    if json["event"] != "chat" {
+        return None;
+    }
+    let name = json["name"].as_str().unwrap_or_default().to_string();
+    Some((name, json["text"].as_str()?.to_string()))
+}
+
+// Media id of a `{"event": "ack", "media_id": N}` message, sent by displays done showing it
+fn ack_media_id(message: &warp::ws::Message) -> Option<u64> {
+    let json: serde_json::Value = serde_json::from_str(message.to_str().ok()?).ok()?;
+    if json["event"] != "ack" {
+        return None;
+    }
+    json["media_id"].as_u64()
+}
+
+/// Link quality a client registers on connect with `?bandwidth=low`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Bandwidth {
+    #[default]
+    High,
+    /// Sent the low rendition of videos when there is one
+    Low,
+}
+
+impl Bandwidth {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "high" => Some(Bandwidth::High),
+            "low" => Some(Bandwidth::Low),
+            _ => None,
+        }
+    }
+}
+
+// Give a video event the URL for the client's bandwidth, other messages go out as they are
+fn for_bandwidth(message: warp::ws::Message, bandwidth: Bandwidth) -> warp::ws::Message {
+    if !is_event(&message, &["video"]) {
+        return message;
+    }
+    let Ok(mut json) = serde_json::from_str::<serde_json::Value>(message.to_str().unwrap_or(""))
+    else {
+        return message;
+    };
+    let low_url = json.as_object_mut().and_then(|event| event.remove("low_url"));
+    if bandwidth == Bandwidth::Low
+        && let Some(low_url) = low_url.filter(|url| url.is_string())
+    {
+        json["url"] = low_url;
+    }
+    warp::ws::Message::text(json.to_string())
+}
+
 // WebSocket connection handler
 use futures_util::{SinkExt, StreamExt};
 
 pub async fn ws_handler(
     ws: warp::ws::Ws,
+    query: std::collections::HashMap<String, String>,
+    client: Option<ClientId>,
+    ip: Option<IpAddr>,
     clients: WsClients,
+    state: StateHandle,
+    chat: ChatConfig,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     tracing::info!("WebSocket connection request received");
-    Ok(ws.on_upgrade(move |websocket| handle_websocket(websocket, clients)))
+    // Overlays only get the events they draw, displays get everything from the sources they
+    // take
+    let filter = EventFilter {
+        events: match query.get("mode").map(String::as_str) {
+            Some("overlay") => Some(OVERLAY_EVENTS),
+            _ => None,
+        },
+        sources: SourceFilter::from_query(&query),
+    };
+    let bandwidth = query
+        .get("bandwidth")
+        .and_then(|name| Bandwidth::from_name(name))
+        .unwrap_or_default();
+    // Displays register with `?display=<id>`, or an empty id to be given one
+    let display = query
+        .get("display")
+        .map(|id| (id.clone(), query.get("name").cloned()));
+    Ok(ws.on_upgrade(move |mut websocket| async move {
+        let display = match display {
+            Some((id, name)) => register_display(&mut websocket, &state, id, name).await,
+            None => None,
+        };
+        let peer = Peer { client, ip };
+        handle_websocket(websocket, peer, clients, state.clone(), chat, filter, bandwidth).await;
+        if let Some(id) = display {
+            displays::disconnect_display(&state, id).await;
+        }
+    }))
+}
+
+// Register the display and tell it the id it got, the connection goes on unregistered when
+// the id is invalid
+async fn register_display(
+    websocket: &mut warp::ws::WebSocket,
+    state: &StateHandle,
+    id: String,
+    name: Option<String>,
+) -> Option<String> {
+    let registered = match displays::connect_display(state, id, name).await {
+        Ok(registered) => registered,
+        Err(e) => {
+            tracing::warn!("Display registration refused: {}", e);
+            return None;
+        }
+    };
+    tracing::info!("Display {} ({}) connected", registered.id, registered.name);
+    let message_json = json!(WsEvent::Registered {
+        id: registered.id.clone(),
+        name: registered.name,
+    });
+    if let Err(e) = websocket.send(warp::ws::Message::text(message_json.to_string())).await {
+        tracing::warn!("Failed to send display registration: {:?}", e);
+    }
+    Some(registered.id)
+}
+
+// Who is on the other end of a connection, checked against bans when it chats
+struct Peer {
+    client: Option<ClientId>,
+    ip: Option<IpAddr>,
 }
 
-async fn handle_websocket(websocket: warp::ws::WebSocket, clients: WsClients) {
-    tracing::info!("Handling new WebSocket connection");
+async fn handle_websocket(
+    websocket: warp::ws::WebSocket,
+    peer: Peer,
+    clients: WsClients,
+    state: StateHandle,
+    chat: ChatConfig,
+    filter: EventFilter,
+    bandwidth: Bandwidth,
+) {
+    tracing::info!(
+        "Handling new WebSocket connection ({:?}, bandwidth: {:?})",
+        filter,
+        bandwidth
+    );
     let (mut ws_sender, mut ws_receiver) = websocket.split();
 
     // Subscribe to broadcast channel
@@ -104,10 +775,49 @@ async fn handle_websocket(websocket: warp::ws::WebSocket, clients: WsClients) {
         sender.subscribe()
     };
 
-    // Handle incoming messages (keepalive/pong)
+    // Handle incoming messages (keepalive/pong, acks, drawing, chat)
+    let chat_clients = clients.clone();
     let incoming_task = tokio::spawn(async move {
         while let Some(result) = ws_receiver.next().await {
             match result {
+                Ok(msg) if msg.is_text() => {
+                    // A burn after viewing upload goes as soon as a display is done with it
+                    if let Some(media_id) = ack_media_id(&msg)
+                        && let Some(filename) = state.call(move |state| state.burn(media_id)).await
+                    {
+                        tracing::info!("Burning {} after viewing", filename);
+                        tasks::delete_upload(&state, filename).await;
+                    } else if let Some(strokes) = draw_strokes(&msg) {
+                        // Relayed with the next batch, bad strokes are dropped
+                        let strokes: Vec<_> = strokes
+                            .into_iter()
+                            .filter(|stroke| stroke.validate().is_ok())
+                            .collect();
+                        state.call(move |state| state.canvas_mut().draw(strokes)).await;
+                    } else if let Some((name, text)) = chat_text(&msg) {
+                        let Some(sender) = peer.client.clone() else {
+                            tracing::warn!("Chat message without a session or client IP address");
+                            continue;
+                        };
+                        let author = match name.trim() {
+                            "" => crate::stats::person(Some(&sender)),
+                            _ => name,
+                        };
+                        let posted = post_chat(
+                            &state,
+                            &chat_clients,
+                            sender,
+                            peer.ip,
+                            author,
+                            text,
+                            chat.clone(),
+                        )
+                        .await;
+                        if let Err(e) = posted {
+                            tracing::info!("Chat message dropped: {}", e);
+                        }
+                    }
+                }
                 Ok(msg) if msg.is_pong() => {
                     tracing::debug!("Received pong message");
                 }      // Handle pong messages
@@ -126,8 +836,11 @@ async fn handle_websocket(websocket: warp::ws::WebSocket, clients: WsClients) {
 
     // Handle outgoing messages (broadcast)
     let outgoing_task = tokio::spawn(async move {
-        while let Ok(message) = rx.recv().await {
-            if let Err(e) = ws_sender.send(message).await {
+        while let Some(message) = next_message(&mut rx).await {
+            if !filter.allows(&message) {
+                continue;
+            }
+            if let Err(e) = ws_sender.send(for_bandwidth(message, bandwidth)).await {
                 tracing::warn!("Failed to send WebSocket message: {:?}", e);
                 break;
             }
@@ -146,3 +859,134 @@ async fn handle_websocket(websocket: warp::ws::WebSocket, clients: WsClients) {
     
     tracing::info!("WebSocket connection handler finished");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lagging_client_gets_resync_event() {
+        let clients = create_ws_state(2);
+        let mut rx = clients.read().await.subscribe();
+        let before = lag_stats();
+
+        for index in 0..5 {
+            let _ = clients
+                .read()
+                .await
+                .send(warp::ws::Message::text(index.to_string()));
+        }
+
+        let resync = next_message(&mut rx).await.unwrap();
+        let resync: serde_json::Value = serde_json::from_str(resync.to_str().unwrap()).unwrap();
+        assert_eq!(resync["event"], "resync");
+        assert_eq!(resync["missed"], 3);
+        // Then picks up with the oldest broadcast still buffered
+        assert_eq!(next_message(&mut rx).await.unwrap().to_str(), Ok("3"));
+        assert_eq!(next_message(&mut rx).await.unwrap().to_str(), Ok("4"));
+        let after = lag_stats();
+        assert!(after.lagged > before.lagged);
+        assert!(after.missed_messages >= before.missed_messages + 3);
+
+        drop(clients);
+        assert!(next_message(&mut rx).await.is_none());
+    }
+
+    #[test]
+    fn test_overlay_event_filter() {
+        let message = |json: serde_json::Value| warp::ws::Message::text(json.to_string());
+        assert!(is_event(
+            &message(json!({ "event": "caption", "text": "gg" })),
+            OVERLAY_EVENTS
+        ));
+        assert!(!is_event(
+            &message(json!({ "event": "browser_backend", "url": "/?ws=true" })),
+            OVERLAY_EVENTS
+        ));
+        assert!(!is_event(&warp::ws::Message::text("not json"), OVERLAY_EVENTS));
+    }
+
+    #[test]
+    fn test_source_filter() {
+        let message = |json: serde_json::Value| warp::ws::Message::text(json.to_string());
+        let query = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+        let tiktok = message(json!({
+            "event": "video", "source": "url-download", "platform": "tiktok"
+        }));
+        let song = message(json!({ "event": "song", "source": "soundboard" }));
+        let upload = message(json!({ "event": "browser_backend", "source": "web-form" }));
+        let theme = message(json!({ "event": "theme_changed", "theme": "party" }));
+
+        let office = SourceFilter::from_query(&query(&[("ignore_sources", "Soundboard, tiktok")]));
+        assert!(!office.allows(&tiktok));
+        assert!(!office.allows(&song));
+        assert!(office.allows(&upload));
+        assert!(office.allows(&theme));
+
+        let downloads = SourceFilter::from_query(&query(&[("sources", "url-download")]));
+        assert!(downloads.allows(&tiktok));
+        assert!(!downloads.allows(&upload));
+        assert!(downloads.allows(&theme));
+        assert!(SourceFilter::default().allows(&song));
+    }
+
+    #[test]
+    fn test_draw_strokes() {
+        let message = |json: serde_json::Value| warp::ws::Message::text(json.to_string());
+        let strokes = draw_strokes(&message(json!({
+            "event": "draw",
+            "strokes": [{ "color": "#ffffff", "width": 3.0, "points": [[0.1, 0.2]] }]
+        })));
+        assert_eq!(strokes.map(|strokes| strokes.len()), Some(1));
+        assert!(draw_strokes(&message(json!({ "event": "ack", "strokes": [] }))).is_none());
+    }
+
+    #[test]
+    fn test_chat_text() {
+        let message = |json: serde_json::Value| warp::ws::Message::text(json.to_string());
+        assert_eq!(
+            chat_text(&message(json!({ "event": "chat", "text": "gg", "name": "Sam" }))),
+            Some(("Sam".to_string(), "gg".to_string()))
+        );
+        assert_eq!(
+            chat_text(&message(json!({ "event": "chat", "text": "gg" }))),
+            Some((String::new(), "gg".to_string()))
+        );
+        assert_eq!(chat_text(&message(json!({ "event": "draw", "text": "gg" }))), None);
+    }
+
+    #[test]
+    fn test_ack_media_id() {
+        let message = |json: serde_json::Value| warp::ws::Message::text(json.to_string());
+        assert_eq!(ack_media_id(&message(json!({ "event": "ack", "media_id": 7 }))), Some(7));
+        assert_eq!(ack_media_id(&message(json!({ "event": "ack" }))), None);
+        assert_eq!(ack_media_id(&message(json!({ "event": "reaction", "media_id": 7 }))), None);
+    }
+
+    #[test]
+    fn test_video_url_follows_bandwidth() {
+        let video = |low_url: Option<&str>| {
+            let json = json!({ "event": "video", "url": "/uploads/a.mp4", "low_url": low_url });
+            warp::ws::Message::text(json.to_string())
+        };
+        let url = |message: warp::ws::Message| {
+            let json: serde_json::Value = serde_json::from_str(message.to_str().unwrap()).unwrap();
+            assert!(json.get("low_url").is_none());
+            json["url"].clone()
+        };
+        let low = Some("/uploads/renditions/a_480p.mp4");
+        assert_eq!(url(for_bandwidth(video(low), Bandwidth::High)), "/uploads/a.mp4");
+        assert_eq!(
+            url(for_bandwidth(video(low), Bandwidth::Low)),
+            "/uploads/renditions/a_480p.mp4"
+        );
+        assert_eq!(url(for_bandwidth(video(None), Bandwidth::Low)), "/uploads/a.mp4");
+        let other = warp::ws::Message::text("3");
+        assert_eq!(for_bandwidth(other.clone(), Bandwidth::Low), other);
+    }
+}
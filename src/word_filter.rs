@@ -0,0 +1,148 @@
+use crate::chat::mask_banned_words;
+use serde::Deserialize;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Words kept off the screen in captions, the chat ticker and banners
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WordFilterConfig {
+    pub action: FilterAction,
+    /// One word per line, `#` starts a comment; read again on POST /word-filter/reload
+    pub words_file: Option<PathBuf>,
+    /// Filtered along with the file's
+    pub words: Vec<String>,
+}
+
+/// What happens to text with a filtered word in it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    /// Refused with an error
+    Reject,
+    /// Sent with the words masked with asterisks
+    #[default]
+    Censor,
+    /// Uploads wait for an admin in the review list, chat messages and banners, which have no
+    /// review, are refused
+    Approve,
+}
+
+/// What the filter makes of a text
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+    /// Good to go as this text, censored or as typed
+    Allowed(String),
+    Rejected,
+    NeedsApproval,
+}
+
+#[derive(Error, Debug)]
+pub enum WordFilterError {
+    #[error("Failed to read the word list {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// Filtered words, matched case-insensitively as whole words
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WordFilter {
+    action: FilterAction,
+    words: Vec<String>,
+}
+
+// Words of a list file, without blank lines and comments
+fn parse_words(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+}
+
+impl WordFilter {
+    pub fn new(action: FilterAction, words: Vec<String>) -> Self {
+        Self { action, words }
+    }
+
+    /// The configured words and those of the word list file
+    pub fn load(config: &WordFilterConfig) -> Result<Self, WordFilterError> {
+        let mut words = config.words.clone();
+        if let Some(path) = &config.words_file {
+            let list = std::fs::read_to_string(path).map_err(|source| WordFilterError::Read {
+                path: path.clone(),
+                source,
+            })?;
+            words.extend(parse_words(&list));
+        }
+        Ok(Self::new(config.action, words))
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn check(&self, text: &str) -> Verdict {
+        let masked = mask_banned_words(text, &self.words);
+        if masked == text {
+            return Verdict::Allowed(masked);
+        }
+        match self.action {
+            FilterAction::Reject => Verdict::Rejected,
+            FilterAction::Censor => Verdict::Allowed(masked),
+            FilterAction::Approve => Verdict::NeedsApproval,
+        }
+    }
+
+    /// Text that can't wait for an approval, refused when it would need one
+    pub fn check_now(&self, text: &str) -> Option<String> {
+        match self.check(text) {
+            Verdict::Allowed(text) => Some(text),
+            Verdict::Rejected | Verdict::NeedsApproval => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_filter_actions() {
+        let words = parse_words("# Kept off the TV\nheck\n\n  darn # mild\n").collect::<Vec<_>>();
+        assert_eq!(words, ["heck", "darn"]);
+
+        let filter = WordFilter::new(FilterAction::Censor, words.clone());
+        assert_eq!(filter.check("gg wp"), Verdict::Allowed("gg wp".to_string()));
+        assert_eq!(filter.check("Darn, heckler"), Verdict::Allowed("****, heckler".to_string()));
+
+        let filter = WordFilter::new(FilterAction::Reject, words.clone());
+        assert_eq!(filter.check("what the HECK"), Verdict::Rejected);
+        assert_eq!(filter.check_now("gg"), Some("gg".to_string()));
+
+        let filter = WordFilter::new(FilterAction::Approve, words);
+        assert_eq!(filter.check("heck"), Verdict::NeedsApproval);
+        assert_eq!(filter.check_now("heck"), None);
+    }
+
+    #[test]
+    fn test_word_filter_load() {
+        let path = std::env::temp_dir().join(format!("words_{}.txt", std::process::id()));
+        std::fs::write(&path, "heck\n").unwrap();
+        let config = WordFilterConfig {
+            action: FilterAction::Reject,
+            words_file: Some(path.clone()),
+            words: vec!["darn".to_string()],
+        };
+        let filter = WordFilter::load(&config).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(filter.len(), 2);
+        assert_eq!(filter.check("darn"), Verdict::Rejected);
+        assert!(WordFilter::load(&config).is_err());
+    }
+}
@@ -0,0 +1,1569 @@
+use futures_util::SinkExt;
+use homies_gaming_backend::capabilities::UploadLimits;
+use homies_gaming_backend::clock::ManualClock;
+use homies_gaming_backend::config::AppConfig;
+use homies_gaming_backend::data_dirs::DataDirs;
+use homies_gaming_backend::session::ClientId;
+use homies_gaming_backend::state::MediaSource;
+use homies_gaming_backend::types::{UploadOutcome, UploadResponse, WsEvent};
+use homies_gaming_backend::video_processing::HwAccelSetting;
+use homies_gaming_backend::word_filter::{FilterAction, WordFilterConfig};
+use homies_gaming_backend::{App, stats, tasks};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use warp::http::StatusCode;
+use warp::Filter;
+
+const BOUNDARY: &str = "homies-test-boundary";
+const CSRF: &str = "0123456789abcdef0123456789abcdef";
+const SESSION_A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+const SESSION_B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+const PIXEL: &[u8] = include_bytes!("fixtures/pixel.png");
+
+async fn test_app(data: &Path) -> App {
+    test_app_with_clock(data, ManualClock::default()).await
+}
+
+async fn test_app_with_clock(data: &Path, clock: ManualClock) -> App {
+    let config = AppConfig {
+        hwaccel: HwAccelSetting::None,
+        banner_file: data.join("banner.json"),
+        ..AppConfig::default()
+    };
+    App::builder()
+        .config(config)
+        .background_tasks(false)
+        .clock(Arc::new(clock))
+        .data_dirs(DataDirs::under(data))
+        .build()
+        .await
+}
+
+fn multipart_body(filename: &str, data: &[u8], caption: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend(format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"{filename}\"\r\nContent-Type: image/png\r\n\r\n"
+    ).as_bytes());
+    body.extend(data);
+    body.extend(
+        format!(
+            "\r\n--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"duration\"\r\n\r\n5\r\n\
+             --{BOUNDARY}\r\nContent-Disposition: form-data; name=\"caption\"\r\n\r\n{caption}\r\n\
+             --{BOUNDARY}--\r\n"
+        )
+        .as_bytes(),
+    );
+    body
+}
+
+async fn upload(app: &App, filename: &str, caption: &str) -> warp::http::Response<bytes::Bytes> {
+    warp::test::request()
+        .method("POST")
+        .path("/upload")
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .header(
+            "cookie",
+            format!("homies_session={SESSION_A}; homies_csrf={CSRF}"),
+        )
+        .header("x-csrf-token", CSRF)
+        .body(multipart_body(filename, PIXEL, caption))
+        .reply(&app.routes())
+        .await
+}
+
+async fn stored(app: &App, filename: &str) -> bool {
+    tokio::fs::try_exists(app.data_dirs().upload(filename))
+        .await
+        .unwrap()
+}
+
+async fn trashed(app: &App, filename: &str) -> bool {
+    tokio::fs::try_exists(app.data_dirs().trashed(filename))
+        .await
+        .unwrap()
+}
+
+async fn last_media(app: &App, session: &str) -> String {
+    let response = warp::test::request()
+        .path("/last-media")
+        .header("cookie", format!("homies_session={session}"))
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    String::from_utf8_lossy(response.body()).to_string()
+}
+
+#[tokio::test]
+async fn test_upload_is_stored_and_broadcast() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let mut events = app.ws_clients().read().await.subscribe();
+
+    let response = upload(&app, "it-broadcast.png", "gg").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(stored(&app, "it-broadcast.png").await);
+
+    let event = events.try_recv().expect("no WebSocket event sent");
+    let event: serde_json::Value = serde_json::from_str(event.to_str().unwrap()).unwrap();
+    assert_eq!(event["event"], "browser_backend");
+}
+
+#[tokio::test]
+async fn test_json_upload_replies_and_events_match_the_api_types() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let mut events = app.ws_clients().read().await.subscribe();
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/upload")
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .header(
+            "cookie",
+            format!("homies_session={SESSION_A}; homies_csrf={CSRF}"),
+        )
+        .header("x-csrf-token", CSRF)
+        .header("accept", "application/json")
+        .body(multipart_body("it-typed.png", PIXEL, "gg"))
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let reply: UploadResponse = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(reply.outcome, UploadOutcome::Shown);
+    assert_eq!(reply.filename.as_deref(), Some("it-typed.png"));
+
+    let event = events.try_recv().expect("no WebSocket event sent");
+    let event: WsEvent = serde_json::from_str(event.to_str().unwrap()).unwrap();
+    // Asking for JSON tags it as coming from the API rather than the form
+    assert!(matches!(
+        event,
+        WsEvent::BrowserBackend { source: MediaSource::Api, .. }
+    ));
+    let event = events.try_recv().expect("no caption event sent");
+    let event: WsEvent = serde_json::from_str(event.to_str().unwrap()).unwrap();
+    assert_eq!(
+        event,
+        WsEvent::Caption {
+            text: "gg".to_string(),
+            duration_secs: 5,
+            source: Some(MediaSource::Api),
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_upload_without_csrf_token_is_rejected() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let response = warp::test::request()
+        .method("POST")
+        .path("/upload")
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .header("cookie", format!("homies_csrf={CSRF}"))
+        .body(multipart_body("it-forged.png", PIXEL, ""))
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(!stored(&app, "it-forged.png").await);
+    let snapshot = app.state().call(|state| state.display_snapshot()).await;
+    assert!(snapshot.current.is_none());
+}
+
+#[tokio::test]
+async fn test_buttons_without_csrf_token_are_rejected() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let routes = app.routes();
+    let paths = [
+        "/play/1",
+        "/play/hotkey/1",
+        "/archive/1/show",
+        "/music/pause",
+        "/dnd",
+        "/media/1/pin",
+        "/react",
+        "/timer",
+        "/timer/1/extend",
+        "/poll",
+        "/poll/1/vote",
+        "/chat",
+        "/draw",
+    ];
+    for path in paths {
+        let forged = warp::test::request()
+            .method("POST")
+            .path(path)
+            .header("cookie", format!("homies_csrf={CSRF}"))
+            .reply(&routes)
+            .await;
+        assert_eq!(forged.status(), StatusCode::FORBIDDEN, "{path}");
+        let sent = warp::test::request()
+            .method("POST")
+            .path(path)
+            .header("cookie", format!("homies_csrf={CSRF}"))
+            .header("x-csrf-token", CSRF)
+            .reply(&routes)
+            .await;
+        assert_ne!(sent.status(), StatusCode::FORBIDDEN, "{path}");
+    }
+}
+
+#[tokio::test]
+async fn test_concat_without_csrf_token_is_rejected() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let routes = app.routes();
+    upload(&app, "it-concat-a.png", "").await;
+    upload(&app, "it-concat-b.png", "").await;
+    let before = app.state().call(|state| state.queue_len()).await;
+    // No content-type: a cross-site no-cors fetch can send this body as-is.
+    let forged = warp::test::request()
+        .method("POST")
+        .path("/media/concat")
+        .header("cookie", format!("homies_csrf={CSRF}"))
+        .body(r#"{"ids":[1,2]}"#)
+        .reply(&routes)
+        .await;
+    assert_eq!(forged.status(), StatusCode::FORBIDDEN);
+    let after = app.state().call(|state| state.queue_len()).await;
+    assert_eq!(after, before);
+}
+
+#[tokio::test]
+async fn test_last_media_is_shown_once_per_client() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    upload(&app, "it-views.png", "once").await;
+
+    let first = last_media(&app, SESSION_A).await;
+    assert!(first.contains("/uploads/it-views.png"));
+    assert!(first.contains("once"));
+    assert!(!last_media(&app, SESSION_A).await.contains("it-views.png"));
+    // Every client gets to see it once
+    assert!(last_media(&app, SESSION_B).await.contains("it-views.png"));
+}
+
+#[tokio::test]
+async fn test_cleanup_deletes_media_once_shown_long_enough() {
+    let data = tempfile::tempdir().unwrap();
+    let clock = ManualClock::default();
+    let app = test_app_with_clock(data.path(), clock.clone()).await;
+    upload(&app, "it-cleanup.png", "").await;
+
+    clock.advance(Duration::from_secs(5));
+    tasks::delete_expired_files(app.state()).await;
+    assert!(stored(&app, "it-cleanup.png").await);
+
+    clock.advance(tasks::DELETION_THRESHOLD);
+    tasks::delete_expired_files(app.state()).await;
+    assert!(!stored(&app, "it-cleanup.png").await);
+    assert!(!last_media(&app, SESSION_B).await.contains("it-cleanup.png"));
+    assert!(trashed(&app, "it-cleanup.png").await);
+    let metrics = warp::test::request()
+        .path("/metrics/cleanup")
+        .reply(&app.routes())
+        .await;
+    let metrics: serde_json::Value = serde_json::from_slice(metrics.body()).unwrap();
+    assert_eq!(metrics["stats"]["trashed"], 1);
+    assert_eq!(metrics["stats"]["failures"], 0);
+
+    let retention = app.config().trash.retention();
+    clock.advance(retention);
+    tasks::purge_trash(app.state(), retention).await;
+    assert!(!trashed(&app, "it-cleanup.png").await);
+}
+
+#[tokio::test]
+async fn test_trashed_upload_can_be_restored() {
+    let data = tempfile::tempdir().unwrap();
+    let clock = ManualClock::default();
+    let app = test_app_with_clock(data.path(), clock.clone()).await;
+    upload(&app, "it-restore.png", "").await;
+    clock.advance(tasks::DELETION_THRESHOLD + Duration::from_secs(1));
+    tasks::delete_expired_files(app.state()).await;
+    assert!(trashed(&app, "it-restore.png").await);
+
+    let restore = || {
+        warp::test::request()
+            .method("POST")
+            .path("/trash/it-restore.png/restore")
+    };
+    let response = restore().reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(stored(&app, "it-restore.png").await);
+    assert!(!trashed(&app, "it-restore.png").await);
+    let response = restore().reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_restored_upload_is_cleaned_up_again() {
+    let data = tempfile::tempdir().unwrap();
+    let clock = ManualClock::default();
+    let app = test_app_with_clock(data.path(), clock.clone()).await;
+    upload(&app, "it-restore-again.png", "").await;
+    clock.advance(tasks::DELETION_THRESHOLD + Duration::from_secs(1));
+    tasks::delete_expired_files(app.state()).await;
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/trash/it-restore-again.png/restore")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    // The restored upload gets its full time on disk again before going back to the trash
+    tasks::delete_expired_files(app.state()).await;
+    assert!(stored(&app, "it-restore-again.png").await);
+    clock.advance(tasks::DELETION_THRESHOLD + Duration::from_secs(1));
+    tasks::delete_expired_files(app.state()).await;
+    assert!(!stored(&app, "it-restore-again.png").await);
+    assert!(trashed(&app, "it-restore-again.png").await);
+}
+
+#[tokio::test]
+async fn test_media_is_served_with_etags_and_ranges() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    upload(&app, "it-served.png", "").await;
+
+    let response = warp::test::request()
+        .path("/uploads/it-served.png")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "image/png");
+    assert_eq!(response.body().as_ref(), PIXEL);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+    let response = warp::test::request()
+        .path("/uploads/it-served.png")
+        .header("if-none-match", &etag)
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert!(response.body().is_empty());
+
+    let response = warp::test::request()
+        .path("/uploads/it-served.png")
+        .header("range", "bytes=1-3")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.body().as_ref(), &PIXEL[1..4]);
+
+    let hits = app.state().call(|state| state.hits()).await;
+    assert_eq!(hits, vec![("it-served.png".to_string(), 3)]);
+
+    let response = warp::test::request()
+        .path("/uploads/..%2Fsrc%2Fmain.rs")
+        .reply(&app.routes())
+        .await;
+    assert!(response.status().is_client_error());
+}
+
+#[tokio::test]
+async fn test_posters_and_previews_are_served() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let posters = app.data_dirs().posters();
+    tokio::fs::create_dir_all(&posters).await.unwrap();
+    tokio::fs::write(format!("{posters}/it-poster.jpg"), PIXEL).await.unwrap();
+
+    let response = warp::test::request()
+        .path("/uploads/posters/it-poster.jpg")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body().as_ref(), PIXEL);
+    let response = warp::test::request()
+        .path("/uploads/posters/../it-poster.jpg")
+        .reply(&app.routes())
+        .await;
+    assert!(response.status().is_client_error());
+
+    let previews = app.data_dirs().previews();
+    tokio::fs::create_dir_all(&previews).await.unwrap();
+    tokio::fs::write(format!("{previews}/it-preview.webp"), PIXEL).await.unwrap();
+    let response = warp::test::request()
+        .path("/uploads/previews/it-preview.webp")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_leaderboard_reset_requires_admin_token() {
+    let data = tempfile::tempdir().unwrap();
+    let leaderboard_file = data.path().join("leaderboard.json");
+    let config = AppConfig {
+        hwaccel: HwAccelSetting::None,
+        admin_token: Some("letmein".to_string()),
+        leaderboard_file: leaderboard_file.clone(),
+        ..AppConfig::default()
+    };
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    let reset = |token: &str| {
+        warp::test::request()
+            .method("POST")
+            .path("/leaderboard/reset")
+            .header("x-admin-token", token)
+    };
+    let response = reset("guess").reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = reset("letmein").reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body().as_ref(), br#"{"rankings":[]}"#);
+    let saved = tokio::fs::read_to_string(&leaderboard_file).await.unwrap();
+    assert!(saved.contains("scores"), "{saved}");
+}
+
+#[tokio::test]
+async fn test_dnd_override_requires_admin_token() {
+    let data = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        hwaccel: HwAccelSetting::None,
+        admin_token: Some("letmein".to_string()),
+        audit_log: data.path().join("audit.log"),
+        ..AppConfig::default()
+    };
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    let set_dnd = || {
+        warp::test::request()
+            .method("POST")
+            .path("/dnd")
+            .header("cookie", format!("homies_csrf={CSRF}"))
+            .header("x-csrf-token", CSRF)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("mode=on")
+    };
+    let response = set_dnd().reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(!app.state().call(|state| state.is_quiet()).await);
+    let response = set_dnd().header("x-admin-token", "letmein").reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(app.state().call(|state| state.is_quiet()).await);
+}
+
+#[tokio::test]
+async fn test_now_playing_push_requires_admin_token() {
+    let data = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        hwaccel: HwAccelSetting::None,
+        admin_token: Some("letmein".to_string()),
+        audit_log: data.path().join("audit.log"),
+        ..AppConfig::default()
+    };
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    let set = || {
+        warp::test::request()
+            .method("POST")
+            .path("/now-playing")
+            .json(&serde_json::json!({ "title": "Rickroll", "artist": "Rick Astley" }))
+    };
+    let clear = || warp::test::request().method("DELETE").path("/now-playing");
+    let playing = || app.state().call(|state| state.now_playing().is_some());
+
+    let response = set().reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(!playing().await);
+    let response = set().header("x-admin-token", "letmein").reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(playing().await);
+
+    let response = clear().reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(playing().await);
+    let response = clear().header("x-admin-token", "letmein").reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!playing().await);
+}
+
+#[tokio::test]
+async fn test_theme_change_restyles_displays() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let mut events = app.ws_clients().read().await.subscribe();
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/theme")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("theme=party")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let event = events.try_recv().expect("no WebSocket event sent");
+    let event: serde_json::Value = serde_json::from_str(event.to_str().unwrap()).unwrap();
+    assert_eq!(event["event"], "theme_changed");
+    assert_eq!(event["theme"], "party");
+
+    let index = warp::test::request().path("/").reply(&app.routes()).await;
+    assert!(String::from_utf8_lossy(index.body()).contains(r#"<body class="theme-party">"#));
+    assert!(last_media(&app, SESSION_A).await.contains("applyTheme('party')"));
+}
+
+#[tokio::test]
+async fn test_banner_stays_up_until_it_expires_or_is_cleared() {
+    let data = tempfile::tempdir().unwrap();
+    let clock = ManualClock::default();
+    let app = test_app_with_clock(data.path(), clock.clone()).await;
+    let mut events = app.ws_clients().read().await.subscribe();
+    let banner = || async {
+        let response = warp::test::request()
+            .path("/display-state")
+            .reply(&app.routes())
+            .await;
+        serde_json::from_slice::<serde_json::Value>(response.body()).unwrap()["banner"].clone()
+    };
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/banner")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("text=Rent+due+Friday&expires_in_secs=60")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let event = events.try_recv().expect("no WebSocket event sent");
+    let event: serde_json::Value = serde_json::from_str(event.to_str().unwrap()).unwrap();
+    assert_eq!(event["event"], "banner");
+    assert_eq!(event["banner"]["text"], "Rent due Friday");
+
+    upload(&app, "it-banner.png", "").await;
+    assert_eq!(banner().await["text"], "Rent due Friday");
+    clock.advance(Duration::from_secs(60));
+    assert!(banner().await.is_null());
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/banner")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("text=Pinned")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(banner().await["expires_at_ms"].is_null());
+    let response = warp::test::request()
+        .method("DELETE")
+        .path("/banner")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(banner().await.is_null());
+}
+
+#[tokio::test]
+async fn test_banner_is_kept_across_restarts_until_it_expires() {
+    let data = tempfile::tempdir().unwrap();
+    let clock = ManualClock::default();
+    let set_banner = |body: &'static str| {
+        warp::test::request()
+            .method("POST")
+            .path("/banner")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(body)
+    };
+    async fn banner(app: &App) -> Option<String> {
+        app.state().call(|state| state.banner().map(|banner| banner.text.clone())).await
+    }
+
+    let app = test_app_with_clock(data.path(), clock.clone()).await;
+    let response = set_banner("text=Rent+due+Friday&expires_in_secs=60")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let app = test_app_with_clock(data.path(), clock.clone()).await;
+    assert_eq!(banner(&app).await.as_deref(), Some("Rent due Friday"));
+    clock.advance(Duration::from_secs(60));
+    let app = test_app_with_clock(data.path(), clock.clone()).await;
+    assert_eq!(banner(&app).await, None);
+
+    let app = test_app_with_clock(data.path(), clock.clone()).await;
+    set_banner("text=Pinned").reply(&app.routes()).await;
+    let response = warp::test::request()
+        .method("DELETE")
+        .path("/banner")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let app = test_app_with_clock(data.path(), clock.clone()).await;
+    assert_eq!(banner(&app).await, None);
+}
+
+#[tokio::test]
+async fn test_messages_follow_accept_language() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let unlock = |language: &str| {
+        warp::test::request()
+            .method("POST")
+            .path("/unlock")
+            .header("cookie", format!("homies_csrf={CSRF}"))
+            .header("x-csrf-token", CSRF)
+            .header("accept-language", language)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("passphrase=")
+    };
+    let response = unlock("fr-CA,fr;q=0.9,en;q=0.8").reply(&app.routes()).await;
+    assert_eq!(response.body().as_ref(), "<p>Aucune phrase secrète donnée !</p>".as_bytes());
+    let response = unlock("de").reply(&app.routes()).await;
+    assert_eq!(response.body().as_ref(), b"<p>No passphrase given!</p>");
+}
+
+#[tokio::test]
+async fn test_upload_form_follows_capabilities() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let response = warp::test::request().path("/capabilities").reply(&app.routes()).await;
+    let capabilities: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(capabilities, serde_json::to_value(&**app.capabilities()).unwrap());
+    assert_eq!(capabilities["max_upload_bytes"], 100 * 1024 * 1024);
+
+    let form = warp::test::request().path("/upload").reply(&app.routes()).await;
+    let form = String::from_utf8_lossy(form.body());
+    assert!(form.contains("Maximum file size: 100MB"));
+    assert_eq!(form.contains(r#"id="caption-animation""#), app.capabilities().ffmpeg);
+    assert_eq!(form.contains(r#"name="effect""#), app.capabilities().ffmpeg);
+    assert_eq!(
+        form.contains("showTab('video-tab')"),
+        app.capabilities().video_downloads()
+    );
+}
+
+#[tokio::test]
+async fn test_chromakey_option_needs_a_background() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let form = warp::test::request().path("/upload").reply(&app.routes()).await;
+    assert!(!String::from_utf8_lossy(form.body()).contains(r#"name="chromakey""#));
+
+    let config: AppConfig = toml::from_str(
+        r#"
+        hwaccel = "none"
+        [chromakey]
+        background = "backgrounds/beach.jpg"
+        similarity = 0.25
+        "#,
+    )
+    .unwrap();
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    let form = warp::test::request().path("/upload").reply(&app.routes()).await;
+    let form = String::from_utf8_lossy(form.body());
+    // Like the other video options, only offered when ffmpeg is there to apply it
+    assert_eq!(form.contains(r#"name="chromakey""#), app.capabilities().ffmpeg);
+    assert_eq!(form.contains(r#"value="0.25""#), app.capabilities().ffmpeg);
+}
+
+#[tokio::test]
+async fn test_imagined_image_goes_on_screen() {
+    use base64::Engine;
+
+    // Stands in for AUTOMATIC1111's txt2img API
+    let txt2img = warp::post()
+        .and(warp::path!("sdapi" / "v1" / "txt2img"))
+        .and(warp::body::json())
+        .map(|body: serde_json::Value| {
+            assert_eq!(body["prompt"], "a cat dabbing");
+            let image = base64::engine::general_purpose::STANDARD.encode(PIXEL);
+            warp::reply::json(&serde_json::json!({ "images": [image] }))
+        });
+    let (backend, server) = warp::serve(txt2img).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let imagine = |prompt: &str| {
+        warp::test::request()
+            .method("POST")
+            .path("/imagine")
+            .header("cookie", format!("homies_csrf={CSRF}"))
+            .header("x-csrf-token", CSRF)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(format!("prompt={prompt}"))
+    };
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let response = imagine("a+cat+dabbing").reply(&app.routes()).await;
+    assert!(String::from_utf8_lossy(response.body()).contains("not set up"));
+
+    let config: AppConfig = toml::from_str(&format!(
+        r#"
+        hwaccel = "none"
+        [imagine]
+        url = "http://{backend}"
+        "#
+    ))
+    .unwrap();
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    let response = imagine("+").reply(&app.routes()).await;
+    assert_eq!(response.body().as_ref(), b"<p>No prompt given!</p>");
+
+    let response = imagine("a+cat+dabbing").reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let snapshot = app.state().call(|state| state.display_snapshot()).await;
+    let filename = snapshot.current.expect("nothing on screen").filename;
+    assert!(filename.starts_with("imagine_"));
+    assert!(stored(&app, &filename).await);
+}
+
+#[tokio::test]
+async fn test_skipped_caption_is_reported_and_shown() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let mut events = app.ws_clients().read().await.subscribe();
+    // Passes the type check but can't be re-encoded, whether ffmpeg is installed or not
+    let clip = b"\0\0\0\x20ftypisom\0\0\x02\0isomiso2avc1mp41";
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/upload")
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .header(
+            "cookie",
+            format!("homies_session={SESSION_A}; homies_csrf={CSRF}"),
+        )
+        .header("x-csrf-token", CSRF)
+        .body(multipart_body("it-skipped.mp4", clip, "no ffmpeg no caption"))
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(stored(&app, "it-skipped.mp4").await);
+
+    let warning = std::iter::from_fn(|| events.try_recv().ok())
+        .map(|event| serde_json::from_str::<serde_json::Value>(event.to_str().unwrap()).unwrap())
+        .find(|event| event["event"] == "processing_warning")
+        .expect("no processing warning sent");
+    assert_eq!(warning["filename"], "it-skipped.mp4");
+    let reason = warning["reason"].as_str().unwrap();
+    assert!(["ffmpeg_missing", "processing_failed"].contains(&reason));
+    assert!(warning["message"].as_str().unwrap().starts_with("caption skipped"));
+    // Not burned in, so the display shows it
+    assert!(last_media(&app, SESSION_A).await.contains("no ffmpeg no caption"));
+}
+
+#[tokio::test]
+async fn test_caption_preview_needs_a_video() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let response = warp::test::request()
+        .method("POST")
+        .path("/preview-caption")
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .header("cookie", format!("homies_csrf={CSRF}"))
+        .header("x-csrf-token", CSRF)
+        .body(multipart_body("it-preview.png", PIXEL, "gg"))
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["error"], "Caption previews need a video file!");
+    // Nothing is published or left behind
+    assert!(!stored(&app, "it-preview.png").await);
+}
+
+#[tokio::test]
+async fn test_staged_upload_is_broadcast_when_published() {
+    let data = tempfile::tempdir().unwrap();
+    let clock = ManualClock::default();
+    let app = test_app_with_clock(data.path(), clock.clone()).await;
+    let mut events = app.ws_clients().read().await.subscribe();
+    let post = |path: &str, body: Vec<u8>| {
+        warp::test::request()
+            .method("POST")
+            .path(path)
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .header("cookie", format!("homies_csrf={CSRF}"))
+            .header("x-csrf-token", CSRF)
+            .body(body)
+    };
+
+    let response = post("/stage", multipart_body("it-staged.png", PIXEL, "gg"))
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(String::from_utf8_lossy(response.body()).contains("hx-post=\"/publish/1\""));
+    assert!(stored(&app, "it-staged.png").await);
+    assert!(events.try_recv().is_err());
+
+    let response = post("/publish/1", Vec::new()).reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let event = events.try_recv().expect("no WebSocket event sent");
+    let event: serde_json::Value = serde_json::from_str(event.to_str().unwrap()).unwrap();
+    assert_eq!(event["event"], "browser_backend");
+    let response = post("/publish/1", Vec::new()).reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Left unpublished past its expiry, it is deleted
+    post("/stage", multipart_body("it-unpublished.png", PIXEL, ""))
+        .reply(&app.routes())
+        .await;
+    clock.advance(AppConfig::default().staging.expiry());
+    tasks::sweep_staging(app.state()).await;
+    assert!(!stored(&app, "it-unpublished.png").await);
+    let response = post("/publish/2", Vec::new()).reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_share_links_serve_only_their_archived_media() {
+    let data = tempfile::tempdir().unwrap();
+    let config: AppConfig = toml::from_str(
+        r#"
+        hwaccel = "none"
+        [share]
+        secret = "s3cret"
+        ttl_secs = 60
+        base_url = "https://homies.example.com"
+        "#,
+    )
+    .unwrap();
+    let clock = ManualClock::default();
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .clock(Arc::new(clock.clone()))
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    upload(&app, "it-share.png", "").await;
+    // Pinned without POST /media/{id}/pin, which would rewrite the archive index on disk
+    let entry = app
+        .state()
+        .call(|state| {
+            let id = state.display_snapshot().current.expect("nothing on screen").id;
+            let media = state.pinnable_media(id).cloned().unwrap();
+            state.archive_media(&media)
+        })
+        .await;
+    let dirs = app.data_dirs();
+    tokio::fs::create_dir_all(&dirs.archive).await.unwrap();
+    tokio::fs::rename(dirs.upload("it-share.png"), dirs.archived("it-share.png"))
+        .await
+        .unwrap();
+
+    let response = warp::test::request()
+        .method("POST")
+        .path(&format!("/archive/{}/share", entry.id))
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    let url = body["url"].as_str().unwrap();
+    let path = url.strip_prefix("https://homies.example.com").unwrap();
+
+    let response = warp::test::request().path(path).reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body().as_ref(), PIXEL);
+    let tampered = format!("{}x", path);
+    let response = warp::test::request().path(&tampered).reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    clock.advance(Duration::from_secs(60));
+    let response = warp::test::request().path(path).reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::GONE);
+}
+
+#[tokio::test]
+async fn test_archive_index_is_not_served() {
+    let data = tempfile::tempdir().unwrap();
+    let archive_index = data.path().join("archive.json");
+    let config = AppConfig {
+        hwaccel: HwAccelSetting::None,
+        archive_index: archive_index.clone(),
+        ..AppConfig::default()
+    };
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    upload(&app, "it-pin.png", "").await;
+    let id = app
+        .state()
+        .call(|state| state.display_snapshot().current.expect("nothing on screen").id)
+        .await;
+
+    let response = warp::test::request()
+        .method("POST")
+        .path(&format!("/media/{}/pin", id))
+        .header("cookie", format!("homies_csrf={CSRF}"))
+        .header("x-csrf-token", CSRF)
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let saved = tokio::fs::read_to_string(&archive_index).await.unwrap();
+    assert!(saved.contains("it-pin.png"), "{saved}");
+    for path in ["/archive/archive.json", "/uploads/archive.json"] {
+        let response = warp::test::request().path(path).reply(&app.routes()).await;
+        assert!(response.status().is_client_error(), "{path}");
+    }
+}
+
+#[tokio::test]
+async fn test_hls_playlists_are_served_from_their_directory() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let hls = format!("{}/it-hls", app.data_dirs().hls());
+    tokio::fs::create_dir_all(&hls).await.unwrap();
+    let playlist = "#EXTM3U\n#EXT-X-ENDLIST\n";
+    tokio::fs::write(format!("{hls}/index.m3u8"), playlist)
+        .await
+        .unwrap();
+
+    let response = warp::test::request()
+        .path("/uploads/hls/it-hls/index.m3u8")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body().as_ref(), playlist.as_bytes());
+    for path in ["/uploads/hls/it-hls/segment_000.ts", "/uploads/hls/..%2Fhls/it-hls"] {
+        let response = warp::test::request().path(path).reply(&app.routes()).await;
+        assert!(response.status().is_client_error());
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_of_unknown_camera() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let response = warp::test::request()
+        .method("POST")
+        .path("/snapshot/garage")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.body().as_ref(), b"<p>No camera with this name!</p>");
+}
+
+#[cfg(feature = "graphql")]
+#[tokio::test]
+async fn test_graphql_lists_what_is_on_screen_when_enabled() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let query = r#"{"query": "{ current { filename } }"}"#;
+    let response = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .header("content-type", "application/json")
+        .body(query)
+        .reply(&app.routes())
+        .await;
+    assert!(response.status().is_client_error());
+
+    let config = AppConfig {
+        hwaccel: HwAccelSetting::None,
+        graphql: true,
+        ..AppConfig::default()
+    };
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .clock(Arc::new(ManualClock::default()))
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    upload(&app, "it-graphql.png", "gg").await;
+    let query = concat!(
+        r#"{"query": "{ current { filename caption mediaType } "#,
+        r#"history(limit: 1) { filename status } queue { id } sounds { id } jobs { id } }"}"#
+    );
+    let response = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .header("content-type", "application/json")
+        .body(query)
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["errors"], serde_json::Value::Null, "{body}");
+    let data = &body["data"];
+    assert_eq!(data["current"]["filename"], "it-graphql.png");
+    assert_eq!(data["current"]["caption"], "gg");
+    assert_eq!(data["current"]["mediaType"], "IMAGE");
+    assert_eq!(data["history"][0]["filename"], "it-graphql.png");
+    assert_eq!(data["history"][0]["status"], "SHOWN");
+    assert_eq!(data["queue"], serde_json::json!([]));
+    assert_eq!(data["jobs"], serde_json::json!([]));
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .header("content-type", "application/json")
+        .body("{")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_displays_register_over_the_websocket() {
+    let data = tempfile::tempdir().unwrap();
+    let displays_file = data.path().join("displays.json");
+    let app = App::builder()
+        .config(AppConfig {
+            hwaccel: HwAccelSetting::None,
+            displays_file: displays_file.clone(),
+            ..AppConfig::default()
+        })
+        .background_tasks(false)
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    let mut display = warp::test::ws()
+        .path("/ws?display=it-den&name=Den%20TV")
+        .handshake(app.routes())
+        .await
+        .unwrap();
+    let registered = display.recv().await.unwrap();
+    let registered: WsEvent = serde_json::from_str(registered.to_str().unwrap()).unwrap();
+    assert_eq!(
+        registered,
+        WsEvent::Registered {
+            id: "it-den".to_string(),
+            name: "Den TV".to_string(),
+        }
+    );
+
+    let response = warp::test::request()
+        .path("/displays")
+        .reply(&app.routes())
+        .await;
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    let den = &body["displays"][0];
+    assert_eq!(den["id"], "it-den");
+    assert_eq!(den["name"], "Den TV");
+    assert_eq!(den["connections"], 1);
+    let saved = tokio::fs::read_to_string(&displays_file).await.unwrap();
+    assert!(saved.contains("Den TV"));
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/displays/it-den")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("name=Den")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = warp::test::request()
+        .method("DELETE")
+        .path("/displays/it-den")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["display"]["name"], "Den");
+    let response = warp::test::request()
+        .method("DELETE")
+        .path("/displays/it-den")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_word_filter_holds_captions_for_review() {
+    let data = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        hwaccel: HwAccelSetting::None,
+        word_filter: Some(WordFilterConfig {
+            action: FilterAction::Approve,
+            words: vec!["heck".to_string()],
+            ..WordFilterConfig::default()
+        }),
+        ..AppConfig::default()
+    };
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .clock(Arc::new(ManualClock::default()))
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+
+    let response = upload(&app, "it-review.png", "heck yes").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = warp::test::request()
+        .path("/review")
+        .reply(&app.routes())
+        .await;
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["review"][0]["filename"], "it-review.png");
+    let response = warp::test::request()
+        .path("/last-media")
+        .reply(&app.routes())
+        .await;
+    assert!(!String::from_utf8_lossy(response.body()).contains("it-review.png"));
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/review/1")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let reply: UploadResponse = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(reply.outcome, UploadOutcome::Shown);
+    let response = warp::test::request()
+        .method("POST")
+        .path("/review/1")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The ticker has no review, the message is refused
+    let response = warp::test::request()
+        .method("POST")
+        .path("/chat")
+        .header("cookie", format!("homies_session={SESSION_A}; homies_csrf={CSRF}"))
+        .header("x-csrf-token", CSRF)
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("text=what+the+heck")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_admin_requests_and_uploads_are_audited() {
+    let data = tempfile::tempdir().unwrap();
+    let audit_log = data.path().join("audit.log");
+    let config = AppConfig {
+        hwaccel: HwAccelSetting::None,
+        admin_token: Some("letmein".to_string()),
+        audit_log: audit_log.clone(),
+        banner_file: data.path().join("banner.json"),
+        ..AppConfig::default()
+    };
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .clock(Arc::new(ManualClock::default()))
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    upload(&app, "it-audit.png", "gg").await;
+    let set_banner = |token: &str| {
+        warp::test::request()
+            .method("POST")
+            .path("/banner")
+            .header("x-admin-token", token)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body("text=rent+due")
+    };
+    let response = set_banner("guess").reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = set_banner("letmein").reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = warp::test::request()
+        .path("/admin/audit?limit=4")
+        .header("x-admin-token", "letmein")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    let entries = body["entries"].as_array().unwrap();
+    let actions: Vec<_> = entries
+        .iter()
+        .map(|entry| (entry["action"].as_str().unwrap(), entry["allowed"].as_bool().unwrap()))
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            ("GET /admin/audit", true),
+            ("POST /banner", true),
+            ("POST /banner", false),
+            ("upload", true),
+        ]
+    );
+    assert_eq!(entries[3]["target"], "it-audit.png");
+    assert_eq!(entries[3]["detail"], "gg");
+    let uploader = ClientId::Session(SESSION_A.to_string());
+    assert_eq!(entries[3]["actor"], stats::person(Some(&uploader)).as_str());
+}
+
+#[tokio::test]
+async fn test_banned_sessions_cant_upload_or_chat() {
+    const BANNED: &str = "cccccccccccccccccccccccccccccccc";
+    let data = tempfile::tempdir().unwrap();
+    let bans_file = data.path().join("bans.json");
+    let config = AppConfig {
+        hwaccel: HwAccelSetting::None,
+        bans_file: bans_file.clone(),
+        ..AppConfig::default()
+    };
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .clock(Arc::new(ManualClock::default()))
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    let admin = |method: &str, form: String| {
+        warp::test::request()
+            .method(method)
+            .path("/admin/ban")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(form)
+    };
+    let upload = || {
+        warp::test::request()
+            .method("POST")
+            .path("/upload")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .header("cookie", format!("homies_session={BANNED}; homies_csrf={CSRF}"))
+            .header("x-csrf-token", CSRF)
+            .body(multipart_body("it-banned.png", PIXEL, ""))
+    };
+
+    let form = format!("session={BANNED}&reason=spam&expires_in_secs=600");
+    let response = admin("POST", form).reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["ban"]["session"], BANNED);
+    let saved = tokio::fs::read_to_string(&bans_file).await.unwrap();
+    assert!(saved.contains(BANNED));
+
+    let response = upload().reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(String::from_utf8_lossy(response.body()).contains("for 10 more minutes"));
+    assert!(!stored(&app, "it-banned.png").await);
+    let response = warp::test::request()
+        .method("POST")
+        .path("/chat")
+        .header("cookie", format!("homies_session={BANNED}; homies_csrf={CSRF}"))
+        .header("x-csrf-token", CSRF)
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("text=gg")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = admin("DELETE", format!("session={BANNED}")).reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = upload().reply(&app.routes()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+// Chat over a served WebSocket, which has the remote address warp::test::ws() lacks, then
+// wait for a stroke sent after it: the server handles a connection's messages in order
+async fn chat_over_websocket(app: &App, addr: SocketAddr, session: &str, text: &str) {
+    let mut request = format!("ws://{addr}/ws").into_client_request().unwrap();
+    let cookie = format!("homies_session={session}").parse().unwrap();
+    request.headers_mut().insert("cookie", cookie);
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    let strokes = app.state().call(|state| state.canvas().strokes().len()).await;
+    let chat = serde_json::json!({ "event": "chat", "text": text });
+    socket.send(Message::Text(chat.to_string())).await.unwrap();
+    let stroke = serde_json::json!({
+        "event": "draw",
+        "strokes": [{ "color": "#ff0000", "width": 2.0, "points": [[0.5, 0.5]] }],
+    });
+    socket.send(Message::Text(stroke.to_string())).await.unwrap();
+    while app.state().call(|state| state.canvas().strokes().len()).await == strokes {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_banned_address_cant_chat_over_the_websocket_with_a_new_session() {
+    let data = tempfile::tempdir().unwrap();
+    let app = App::builder()
+        .config(AppConfig {
+            hwaccel: HwAccelSetting::None,
+            bans_file: data.path().join("bans.json"),
+            ..AppConfig::default()
+        })
+        .background_tasks(false)
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    let (addr, server) = warp::serve(app.routes()).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    let recent = || async {
+        let response = warp::test::request()
+            .path("/chat/recent")
+            .reply(&app.routes())
+            .await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        body["messages"].as_array().unwrap().len()
+    };
+
+    chat_over_websocket(&app, addr, SESSION_A, "gg").await;
+    assert_eq!(recent().await, 1);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/admin/ban")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("ip=127.0.0.1&reason=spam")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    chat_over_websocket(&app, addr, SESSION_B, "ban evasion").await;
+    assert_eq!(recent().await, 1);
+}
+
+#[tokio::test]
+async fn test_uploads_over_their_type_limit_are_refused() {
+    let data = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        hwaccel: HwAccelSetting::None,
+        upload_limits: UploadLimits {
+            image_mb: 0,
+            sound_mb: 0,
+            ..UploadLimits::default()
+        },
+        ..AppConfig::default()
+    };
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .clock(Arc::new(ManualClock::default()))
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+
+    // Images are held to their own limit once the file is typed, nothing is written
+    let response = upload(&app, "it-too-large.png", "").await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["limit_bytes"], 0);
+    assert_eq!(body["error"], "Image too large! Maximum size is 0MB.");
+    assert!(!stored(&app, "it-too-large.png").await);
+
+    // Sounds are refused from their declared length, before the form is read
+    let response = warp::test::request()
+        .method("POST")
+        .path("/upload-sound")
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .header("cookie", format!("homies_session={SESSION_A}; homies_csrf={CSRF}"))
+        .header("x-csrf-token", CSRF)
+        .body(multipart_body("it-too-large.mp3", PIXEL, ""))
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["error"], "Sound file too large! Maximum size is 0MB.");
+}
+
+#[tokio::test]
+async fn test_sound_and_music_libraries_come_from_the_data_dirs() {
+    let data = tempfile::tempdir().unwrap();
+    for (dir, file) in [("sounds", "it-horn.mp3"), ("music", "it-lofi.mp3")] {
+        tokio::fs::create_dir_all(data.path().join(dir)).await.unwrap();
+        tokio::fs::write(data.path().join(dir).join(file), b"ID3").await.unwrap();
+    }
+    let app = test_app(data.path()).await;
+
+    let response = warp::test::request()
+        .path("/soundboard/sounds")
+        .reply(&app.routes())
+        .await;
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["sounds"][0]["filename"], "it-horn.mp3");
+    let response = warp::test::request().path("/music").reply(&app.routes()).await;
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["playlist"][0]["filename"], "it-lofi.mp3");
+
+    for path in ["/sounds/it-horn.mp3", "/music/it-lofi.mp3"] {
+        let response = warp::test::request().path(path).reply(&app.routes()).await;
+        assert_eq!(response.status(), StatusCode::OK, "{path}");
+        assert_eq!(response.body().as_ref(), b"ID3");
+    }
+}
+
+#[tokio::test]
+async fn test_upload_budget_is_given_back_after_uploads() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let response = upload(&app, "it-budget.png", "").await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = warp::test::request()
+        .path("/metrics/uploads")
+        .reply(&app.routes())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["budget_bytes"], 300 * 1024 * 1024);
+    assert_eq!(body["in_flight_bytes"], 0);
+    assert_eq!(body["rejected"], 0);
+}
+
+#[tokio::test]
+async fn test_last_media_long_poll_waits_for_an_upload() {
+    // A session of its own, so media from other tests doesn't answer the poll
+    const DISPLAY: &str = "dddddddddddddddddddddddddddddddd";
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let routes = app.routes();
+    let poll = warp::test::request()
+        .path("/last-media?wait=10")
+        .header("cookie", format!("homies_session={DISPLAY}"))
+        .reply(&routes);
+    let uploading = async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        upload(&app, "it-long-poll.png", "").await
+    };
+    let (response, uploaded) = tokio::join!(poll, uploading);
+    assert_eq!(uploaded.status(), StatusCode::OK);
+    assert!(String::from_utf8_lossy(response.body()).contains("it-long-poll.png"));
+
+    // Already seen, and without a wait the answer comes right away
+    let started = std::time::Instant::now();
+    let body = last_media(&app, DISPLAY).await;
+    assert!(!body.contains("it-long-poll.png"));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_frames_are_only_taken_from_retained_videos() {
+    let data = tempfile::tempdir().unwrap();
+    let app = test_app(data.path()).await;
+    let routes = app.routes();
+    let frame = |path: &str| warp::test::request().path(path).reply(&routes);
+    assert_eq!(frame("/media/1/frame?t=1").await.status(), StatusCode::NOT_FOUND);
+
+    upload(&app, "it-frame.png", "").await;
+    assert_eq!(frame("/media/1/frame").await.status(), StatusCode::BAD_REQUEST);
+    let response = frame("/media/1/frame?t=0:01.5").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["error"], "Only videos have frames");
+}
+
+#[tokio::test]
+async fn test_only_uploaders_trim_their_videos() {
+    let data = tempfile::tempdir().unwrap();
+    let config = AppConfig {
+        hwaccel: HwAccelSetting::None,
+        admin_token: Some("letmein".to_string()),
+        ..AppConfig::default()
+    };
+    let app = App::builder()
+        .config(config)
+        .background_tasks(false)
+        .clock(Arc::new(ManualClock::default()))
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    let routes = app.routes();
+    let trim = |session: &str, form: &str| {
+        warp::test::request()
+            .method("POST")
+            .path("/media/1/trim")
+            .header("cookie", format!("homies_session={session}; homies_csrf={CSRF}"))
+            .header("x-csrf-token", CSRF)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(form)
+            .reply(&routes)
+    };
+    assert_eq!(trim(SESSION_A, "start=1").await.status(), StatusCode::NOT_FOUND);
+
+    upload(&app, "it-trim.png", "").await;
+    // Forged from another site, without the token
+    let forged = warp::test::request()
+        .method("POST")
+        .path("/media/1/trim")
+        .header("cookie", format!("homies_session={SESSION_A}; homies_csrf={CSRF}"))
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("start=1")
+        .reply(&routes)
+        .await;
+    assert_eq!(forged.status(), StatusCode::FORBIDDEN);
+    assert_eq!(trim(SESSION_A, "").await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(trim(SESSION_A, "start=5&end=0:02").await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(trim(SESSION_B, "start=1").await.status(), StatusCode::FORBIDDEN);
+    let response = trim(SESSION_A, "start=1").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["error"], "Only videos can be trimmed");
+}
+
+#[tokio::test]
+async fn test_only_retained_videos_are_joined() {
+    let data = tempfile::tempdir().unwrap();
+    let app = App::builder()
+        .config(AppConfig {
+            hwaccel: HwAccelSetting::None,
+            ..AppConfig::default()
+        })
+        .background_tasks(false)
+        .clock(Arc::new(ManualClock::default()))
+        .data_dirs(DataDirs::under(data.path()))
+        .build()
+        .await;
+    let routes = app.routes();
+    let concat = |ids: serde_json::Value| {
+        warp::test::request()
+            .method("POST")
+            .path("/media/concat")
+            .header("cookie", format!("homies_session={SESSION_A}; homies_csrf={CSRF}"))
+            .header("x-csrf-token", CSRF)
+            .json(&serde_json::json!({ "ids": ids }))
+            .reply(&routes)
+    };
+    let response = concat(serde_json::json!([1])).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = concat(serde_json::json!([1, 2])).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    upload(&app, "it-concat.png", "").await;
+    let response = concat(serde_json::json!([1, 1])).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["error"], "Only videos can be joined");
+}